@@ -0,0 +1,183 @@
+; Checks the CHIP-8 arithmetic, skip and memory instructions, drawing each test's number in a
+; 4x4 grid followed by a tick when it passed or a cross when it failed:
+;
+;   0 3XKK    1 4XKK    2 5XY0    3 9XY0
+;   4 7XKK    5 8XY0    6 8XY1    7 8XY2
+;   8 8XY3    9 8XY4    A 8XY5    B 8XY7
+;   C 8XY6    D 8XYE    E FX33    F FX55, FX65 and FX1E
+;
+; 2NNN and 00EE are covered by the calls to report. Each test counts its failed checks in V0;
+; VA and VB are where the next result goes and VC is the test number.
+
+        CLS
+        LD VA, 0
+        LD VB, 1
+        LD VC, 0
+
+        LD V0, 0                ; 0: 3XKK skips when equal
+        LD V1, 0x2A
+        SE V1, 0x2A
+        ADD V0, 1
+        CALL report
+
+        LD V0, 0                ; 1: 4XKK skips when not equal
+        SNE V1, 0x2B
+        ADD V0, 1
+        CALL report
+
+        LD V0, 0                ; 2: 5XY0
+        LD V2, 0x2A
+        SE V1, V2
+        ADD V0, 1
+        CALL report
+
+        LD V0, 0                ; 3: 9XY0
+        LD V2, 0x2B
+        SNE V1, V2
+        ADD V0, 1
+        CALL report
+
+        LD V0, 0                ; 4: 7XKK wraps around
+        LD V1, 0xF0
+        ADD V1, 0x20
+        SE V1, 0x10
+        ADD V0, 1
+        CALL report
+
+        LD V0, 0                ; 5: 8XY0
+        LD V2, 0x55
+        LD V1, V2
+        SE V1, 0x55
+        ADD V0, 1
+        CALL report
+
+        LD V0, 0                ; 6: 8XY1
+        LD V1, 0x0F
+        LD V2, 0xF0
+        OR V1, V2
+        SE V1, 0xFF
+        ADD V0, 1
+        CALL report
+
+        LD V0, 0                ; 7: 8XY2
+        LD V1, 0x3C
+        LD V2, 0x0F
+        AND V1, V2
+        SE V1, 0x0C
+        ADD V0, 1
+        CALL report
+
+        LD V0, 0                ; 8: 8XY3
+        LD V1, 0x3C
+        XOR V1, V2
+        SE V1, 0x33
+        ADD V0, 1
+        CALL report
+
+        LD V0, 0                ; 9: 8XY4 carries into VF
+        LD V1, 0xFF
+        LD V2, 0x02
+        ADD V1, V2
+        LD V3, VF
+        SE V1, 0x01
+        ADD V0, 1
+        SE V3, 1
+        ADD V0, 1
+        CALL report
+
+        LD V0, 0                ; A: 8XY5 borrows, clearing VF
+        LD V1, 0x10
+        LD V2, 0x20
+        SUB V1, V2
+        LD V3, VF
+        SE V1, 0xF0
+        ADD V0, 1
+        SE V3, 0
+        ADD V0, 1
+        CALL report
+
+        LD V0, 0                ; B: 8XY7 doesn't borrow, setting VF
+        LD V1, 0x10
+        LD V2, 0x20
+        SUBN V1, V2
+        LD V3, VF
+        SE V1, 0x10
+        ADD V0, 1
+        SE V3, 1
+        ADD V0, 1
+        CALL report
+
+        LD V0, 0                ; C: 8XY6 shifts the low bit into VF
+        LD V1, 0x81
+        SHR V1
+        LD V3, VF
+        SE V1, 0x40
+        ADD V0, 1
+        SE V3, 1
+        ADD V0, 1
+        CALL report
+
+        LD V0, 0                ; D: 8XYE shifts the high bit into VF
+        LD V1, 0x81
+        SHL V1
+        LD V3, VF
+        SE V1, 0x02
+        ADD V0, 1
+        SE V3, 1
+        ADD V0, 1
+        CALL report
+
+        LD I, scratch           ; E: FX33, read back with FX65
+        LD V1, 234
+        LD B, V1
+        LD V2, [I]
+        LD V5, 0
+        SE V0, 2
+        ADD V5, 1
+        SE V1, 3
+        ADD V5, 1
+        SE V2, 4
+        ADD V5, 1
+        LD V0, V5
+        CALL report
+
+        LD I, scratch           ; F: FX55, then FX65 from one byte further on with FX1E
+        LD V0, 7
+        LD V1, 8
+        LD V2, 9
+        LD [I], V2
+        LD I, scratch
+        LD V1, 1
+        ADD I, V1
+        LD V1, [I]
+        LD V5, 0
+        SE V0, 8
+        ADD V5, 1
+        SE V1, 9
+        ADD V5, 1
+        LD V0, V5
+        CALL report
+
+done:   JP done
+
+; draws test VC's number and its mark at VA, VB, then moves on to the next place in the grid
+report:
+        LD F, VC
+        DRW VA, VB, 5
+        ADD VA, 5
+        LD I, fail
+        SNE V0, 0
+        LD I, pass
+        DRW VA, VB, 5
+        ADD VA, 11
+        ADD VC, 1
+        SE VA, 64
+        RET
+        LD VA, 0
+        ADD VB, 7
+        RET
+
+pass:   .byte 0x00, 0x08, 0x10, 0xA0, 0x40
+fail:   .byte 0x88, 0x50, 0x20, 0x50, 0x88
+scratch:
+        .byte 0, 0, 0
@@ -0,0 +1,17 @@
+; A checkerboard over the whole lores screen, for checking scaling, colours and the edges.
+
+        CLS
+        LD I, checker
+        LD V1, 0                ; y
+row:    LD V0, 0                ; x
+column: DRW V0, V1, 8
+        ADD V0, 8
+        SE V0, 64
+        JP column
+        ADD V1, 8
+        SE V1, 32
+        JP row
+done:   JP done
+
+checker:
+        .byte 0xAA, 0x55, 0xAA, 0x55, 0xAA, 0x55, 0xAA, 0x55
@@ -0,0 +1,124 @@
+//! Interpreter benchmarks, run against the library core without ggez.
+//!
+//! Record a baseline before a change and compare against it afterwards; criterion
+//! then prints the change relative to the baseline next to each result:
+//!
+//!     cargo bench -- --save-baseline before
+//!     cargo bench -- --baseline before
+//!
+//! Every instruction is logged at trace level. `cycle` benchmarks with a logger installed
+//! at the binary's default level, which checks that the interpreter never formats, let alone
+//! prints, a record it's going to throw away.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use log::{Level, LevelFilter, Log, Metadata, Record};
+
+use chip_8_emulator::instruction::Instruction;
+use chip_8_emulator::{Cpu, Display, Memory};
+
+const CYCLES: u64 = 1000;
+
+/// Counts the records it's handed instead of printing them.
+struct CountingLogger(AtomicUsize);
+
+impl Log for CountingLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= Level::Info
+    }
+
+    fn log(&self, _record: &Record) {
+        self.0.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn flush(&self) {}
+}
+
+static LOGGER: CountingLogger = CountingLogger(AtomicUsize::new(0));
+
+fn cpu(rom: Vec<u8>) -> Cpu {
+    let mut cpu = Cpu::new(Memory::new(), Display::new());
+    cpu.init(rom);
+    cpu.seed(0);
+    cpu
+}
+
+fn decode(c: &mut Criterion) {
+    // one of each family, weighted roughly like a typical game loop
+    let opcodes: [u16; 16] = [
+        0x6A02, 0x7A01, 0x8124, 0x8AB5, 0x3A10, 0x4B00, 0xA2F0, 0xD125, 0x2300, 0x00EE, 0x1204, 0xF01E, 0xE19E,
+        0xC0FF, 0xF233, 0xF065,
+    ];
+
+    let mut group = c.benchmark_group("decode");
+    group.throughput(Throughput::Elements(opcodes.len() as u64));
+    group.bench_function("opcode mix", |b| {
+        b.iter(|| {
+            for &opcode in opcodes.iter() {
+                black_box(Instruction::decode(black_box(opcode)));
+            }
+        })
+    });
+    group.finish();
+}
+
+fn cycle(c: &mut Criterion) {
+    // V0 = 1, then loop over ADD/ADD/SUB/SHR/SE forever; both sides of the skip jump back
+    let rom = vec![
+        0x60, 0x01, 0x71, 0x01, 0x82, 0x14, 0x83, 0x25, 0x84, 0x36, 0x31, 0x00, 0x12, 0x02, 0x12, 0x02,
+    ];
+    let mut cpu = cpu(rom);
+    // as the binary runs by default: informative, but not tracing
+    log::set_logger(&LOGGER).expect("nothing else sets a logger");
+    log::set_max_level(LevelFilter::Info);
+
+    let mut group = c.benchmark_group("cycle");
+    group.throughput(Throughput::Elements(CYCLES));
+    group.bench_function("arithmetic loop", |b| {
+        b.iter(|| {
+            for _ in 0..CYCLES {
+                cpu.cycle().unwrap();
+            }
+        })
+    });
+    group.finish();
+    assert_eq!(LOGGER.0.load(Ordering::Relaxed), 0, "the hot loop logged below the enabled level");
+}
+
+fn draw(c: &mut Criterion) {
+    // I = font "0", then draw it and jump back forever; half the cycles are DXYN
+    let rom = vec![0xA0, 0x00, 0xD0, 0x15, 0x12, 0x02];
+    let mut cpu = cpu(rom);
+
+    let mut group = c.benchmark_group("draw");
+    group.throughput(Throughput::Elements(CYCLES / 2));
+    group.bench_function("DXYN 5-row sprite", |b| {
+        b.iter(|| {
+            for _ in 0..CYCLES {
+                cpu.cycle().unwrap();
+            }
+        })
+    });
+    group.finish();
+}
+
+fn framebuffer(c: &mut Criterion) {
+    // the IBM logo leaves a realistic mix of lit and unlit pixels
+    let rom = std::fs::read(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/roms/ibm.ch8")).unwrap();
+    let mut cpu = cpu(rom);
+    for _ in 0..100 {
+        cpu.cycle().unwrap();
+    }
+
+    let mut rgba = Vec::new();
+    c.bench_function("display to rgba", |b| {
+        b.iter(|| {
+            cpu.display().to_rgba([0xFF; 4], [0, 0, 0, 0xFF], &mut rgba);
+            black_box(&rgba);
+        })
+    });
+}
+
+criterion_group!(benches, decode, cycle, draw, framebuffer);
+criterion_main!(benches);
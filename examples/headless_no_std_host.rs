@@ -0,0 +1,74 @@
+//! Runs a ROM the way firmware driving a 128x64 SSD1306-style OLED would, using only what the
+//! crate offers without its `std` feature, and prints the panel's framebuffer as text.
+//!
+//! ```sh
+//! cargo run --example headless_no_std_host --no-default-features
+//! ```
+//!
+//! Everything but `main` would carry over to a `#![no_std]` binary as it is: the firmware reads
+//! the keypad, calls `run_frame` on each 60 Hz tick and sends `oled_frame`'s pages to the panel.
+
+use chip_8_emulator::{Chip8Error, Cpu, Display, Flow, Memory, RomError, CYCLES_PER_FRAME, DEFAULT_LOAD_ADDRESS};
+
+const ROM: &[u8] = include_bytes!("../tests/roms/ibm.ch8");
+
+const OLED_WIDTH: usize = 128;
+/// The panel's rows come in pages of 8, a byte per column per page with the top row in bit 0.
+const OLED_PAGES: usize = 8;
+
+/// Starts `rom` on a fresh machine. There's no OS to seed CXKK from, so the firmware brings its
+/// own seed, e.g. from an ADC's noise.
+fn boot(rom: &[u8], seed: u64) -> Result<Cpu, RomError> {
+    let mut cpu = Cpu::new(Memory::new(), Display::new());
+    cpu.seed(seed);
+    cpu.load_rom(DEFAULT_LOAD_ADDRESS, rom)?;
+    Ok(cpu)
+}
+
+/// One 60 Hz tick: sets the keypad from `keys`, a bit per key, runs a frame's instructions and
+/// ticks the timers. Returns whether the buzzer should sound.
+fn run_frame(cpu: &mut Cpu, keys: u16) -> Result<bool, Chip8Error> {
+    for key in 0..16 {
+        if keys & (1 << key) != 0 {
+            cpu.keys_mut().press(key);
+        } else {
+            cpu.keys_mut().release(key);
+        }
+    }
+    for _ in 0..CYCLES_PER_FRAME {
+        if cpu.cycle()? == Flow::FrameBoundary || cpu.halted().is_some() {
+            break;
+        }
+    }
+    cpu.tick_timers();
+    Ok(cpu.is_buzzing())
+}
+
+/// Lays the screen out as the panel takes it, each lores pixel as a 2x2 block; a hires screen
+/// fits as it is.
+fn oled_frame(display: &Display, out: &mut [u8; OLED_WIDTH * OLED_PAGES]) {
+    let scale = OLED_WIDTH / display.width();
+    out.iter_mut().for_each(|byte| *byte = 0);
+    for y in 0..display.height() * scale {
+        for x in 0..OLED_WIDTH {
+            if display.pixel(x / scale, y / scale) != 0 {
+                out[y / 8 * OLED_WIDTH + x] |= 1 << (y % 8);
+            }
+        }
+    }
+}
+
+fn main() -> Result<(), Chip8Error> {
+    let mut cpu = boot(ROM, 0x5EED).expect("the IBM logo fits in memory");
+    for _ in 0..60 {
+        run_frame(&mut cpu, 0)?;
+    }
+    let mut oled = [0; OLED_WIDTH * OLED_PAGES];
+    oled_frame(cpu.display(), &mut oled);
+
+    for y in 0..OLED_PAGES * 8 {
+        let row: String = (0..OLED_WIDTH).map(|x| if oled[y / 8 * OLED_WIDTH + x] & (1 << (y % 8)) != 0 { '#' } else { '.' }).collect();
+        println!("{}", row);
+    }
+    Ok(())
+}
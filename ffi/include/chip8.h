@@ -0,0 +1,137 @@
+#ifndef CHIP8_H
+#define CHIP8_H
+
+/* Generated by cbindgen from src/lib.rs; don't edit by hand. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * Returned by the functions that can fail when they succeed.
+ */
+#define CHIP8_OK 0
+
+/**
+ * Returned by the functions that can fail when they don't; `chip8_last_error` says why.
+ */
+#define CHIP8_ERROR -1
+
+/**
+ * An interpreter, made by `chip8_new` and released with `chip8_free`.
+ */
+typedef struct Chip8 Chip8;
+
+/**
+ * Makes an interpreter with nothing loaded; free it with `chip8_free`. Returns NULL if that
+ * failed.
+ */
+Chip8 *chip8_new(void);
+
+/**
+ * Frees an interpreter made by `chip8_new`; NULL is ignored.
+ *
+ * # Safety
+ *
+ * `chip8` must be NULL or come from `chip8_new`, and not be used again.
+ */
+void chip8_free(Chip8 *chip8);
+
+/**
+ * Resets the machine and loads the `len` bytes at `rom` at 0x200. A ROM that's turned away
+ * leaves the machine as it was.
+ *
+ * # Safety
+ *
+ * `chip8` must be NULL or come from `chip8_new`, and `rom` must be NULL or point to `len`
+ * readable bytes.
+ */
+int32_t chip8_load_rom(Chip8 *chip8, const uint8_t *rom, uintptr_t len);
+
+/**
+ * Executes up to `cycles` instructions, stopping early if the program halts. Fails, then and
+ * on every later call, if it halted on an error.
+ *
+ * # Safety
+ *
+ * `chip8` must be NULL or come from `chip8_new`.
+ */
+int32_t chip8_step(Chip8 *chip8, uint32_t cycles);
+
+/**
+ * Counts the delay and sound timers down by one; call it 60 times a second. They stay as they
+ * are once the program has halted.
+ *
+ * # Safety
+ *
+ * `chip8` must be NULL or come from `chip8_new`.
+ */
+int32_t chip8_tick_timers(Chip8 *chip8);
+
+/**
+ * Presses or releases keypad key `key`, 0 to 0xF.
+ *
+ * # Safety
+ *
+ * `chip8` must be NULL or come from `chip8_new`.
+ */
+int32_t chip8_set_key(Chip8 *chip8, uint8_t key, bool pressed);
+
+/**
+ * The display's width in pixels: 64, or 128 once a SUPER-CHIP program switches to hires. 0 if
+ * `chip8` is NULL.
+ *
+ * # Safety
+ *
+ * `chip8` must be NULL or come from `chip8_new`.
+ */
+uintptr_t chip8_width(Chip8 *chip8);
+
+/**
+ * The display's height in pixels: 32, or 64 in hires. 0 if `chip8` is NULL.
+ *
+ * # Safety
+ *
+ * `chip8` must be NULL or come from `chip8_new`.
+ */
+uintptr_t chip8_height(Chip8 *chip8);
+
+/**
+ * Copies the display into `out`, row-major, one byte per pixel holding its lit bitplanes: 0
+ * is off, 1 is on for classic ROMs, and XO-CHIP adds 2 and 3. Returns the number of bytes
+ * written, `chip8_width` times `chip8_height`, or `CHIP8_ERROR` if `out_len` is too small.
+ *
+ * # Safety
+ *
+ * `chip8` must be NULL or come from `chip8_new`, and `out` must be NULL or point to `out_len`
+ * writable bytes.
+ */
+intptr_t chip8_framebuffer(Chip8 *chip8, uint8_t *out, uintptr_t out_len);
+
+/**
+ * Whether the buzzer should be sounding. False if `chip8` is NULL.
+ *
+ * # Safety
+ *
+ * `chip8` must be NULL or come from `chip8_new`.
+ */
+bool chip8_sound_on(Chip8 *chip8);
+
+/**
+ * Whether the program has stopped, by 00FD, a jump to itself or an error. False if `chip8` is
+ * NULL.
+ *
+ * # Safety
+ *
+ * `chip8` must be NULL or come from `chip8_new`.
+ */
+bool chip8_is_halted(Chip8 *chip8);
+
+/**
+ * Why the last call that failed on this thread did, or NULL if none has. The string is owned
+ * by the library and stays valid until the next failure on the same thread.
+ */
+const char *chip8_last_error(void);
+
+#endif /* CHIP8_H */
@@ -0,0 +1,271 @@
+use std::any::Any;
+use std::cell::RefCell;
+use std::ffi::CString;
+use std::os::raw::c_char;
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use std::slice;
+
+use chip_8_emulator::{Cpu, Display, Memory, DEFAULT_LOAD_ADDRESS};
+
+#[cfg(feature = "libretro")]
+pub mod libretro;
+
+/// Returned by the functions that can fail when they succeed.
+pub const CHIP8_OK: i32 = 0;
+/// Returned by the functions that can fail when they don't; `chip8_last_error` says why.
+pub const CHIP8_ERROR: i32 = -1;
+
+/// An interpreter, made by `chip8_new` and released with `chip8_free`.
+pub struct Chip8 {
+    cpu: Cpu,
+}
+
+thread_local! {
+    // what `chip8_last_error` hands out, kept alive until the next failure on this thread
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_last_error(message: String) {
+    let message = CString::new(message.replace('\0', " ")).expect("NULs were replaced");
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(message));
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    match payload.downcast_ref::<&str>() {
+        Some(message) => message,
+        None => payload.downcast_ref::<String>().map_or("unknown panic", String::as_str),
+    }
+}
+
+/// Runs `body`, turning its error, or a panic, into `failed` and a message for
+/// `chip8_last_error`: unwinding into C is undefined behaviour.
+fn guard<T>(failed: T, body: impl FnOnce() -> Result<T, String>) -> T {
+    match panic::catch_unwind(AssertUnwindSafe(body)) {
+        Ok(Ok(value)) => value,
+        Ok(Err(message)) => {
+            set_last_error(message);
+            failed
+        }
+        Err(payload) => {
+            set_last_error(format!("panicked: {}", panic_message(&*payload)));
+            failed
+        }
+    }
+}
+
+unsafe fn handle<'a>(chip8: *mut Chip8) -> Result<&'a mut Chip8, String> {
+    chip8.as_mut().ok_or_else(|| String::from("chip8 is NULL"))
+}
+
+/// Makes an interpreter with nothing loaded; free it with `chip8_free`. Returns NULL if that
+/// failed.
+#[no_mangle]
+pub extern "C" fn chip8_new() -> *mut Chip8 {
+    guard(ptr::null_mut(), || Ok(Box::into_raw(Box::new(Chip8 { cpu: Cpu::new(Memory::new(), Display::new()) }))))
+}
+
+/// Frees an interpreter made by `chip8_new`; NULL is ignored.
+///
+/// # Safety
+///
+/// `chip8` must be NULL or come from `chip8_new`, and not be used again.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_free(chip8: *mut Chip8) {
+    guard((), || {
+        if !chip8.is_null() {
+            drop(Box::from_raw(chip8));
+        }
+        Ok(())
+    })
+}
+
+/// Resets the machine and loads the `len` bytes at `rom` at 0x200. A ROM that's turned away
+/// leaves the machine as it was.
+///
+/// # Safety
+///
+/// `chip8` must be NULL or come from `chip8_new`, and `rom` must be NULL or point to `len`
+/// readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_load_rom(chip8: *mut Chip8, rom: *const u8, len: usize) -> i32 {
+    guard(CHIP8_ERROR, || {
+        let chip8 = handle(chip8)?;
+        if rom.is_null() {
+            return Err(String::from("rom is NULL"));
+        }
+        chip8.cpu.load_rom(DEFAULT_LOAD_ADDRESS, slice::from_raw_parts(rom, len)).map_err(|error| error.to_string())?;
+        Ok(CHIP8_OK)
+    })
+}
+
+/// Executes up to `cycles` instructions, stopping early if the program halts. Fails, then and
+/// on every later call, if it halted on an error.
+///
+/// # Safety
+///
+/// `chip8` must be NULL or come from `chip8_new`.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_step(chip8: *mut Chip8, cycles: u32) -> i32 {
+    guard(CHIP8_ERROR, || {
+        let cpu = &mut handle(chip8)?.cpu;
+        for _ in 0..cycles {
+            cpu.cycle().map_err(|error| error.to_string())?;
+            if cpu.halted().is_some() {
+                break;
+            }
+        }
+        Ok(CHIP8_OK)
+    })
+}
+
+/// Counts the delay and sound timers down by one; call it 60 times a second. They stay as they
+/// are once the program has halted.
+///
+/// # Safety
+///
+/// `chip8` must be NULL or come from `chip8_new`.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_tick_timers(chip8: *mut Chip8) -> i32 {
+    guard(CHIP8_ERROR, || {
+        handle(chip8)?.cpu.tick_timers();
+        Ok(CHIP8_OK)
+    })
+}
+
+/// Presses or releases keypad key `key`, 0 to 0xF.
+///
+/// # Safety
+///
+/// `chip8` must be NULL or come from `chip8_new`.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_set_key(chip8: *mut Chip8, key: u8, pressed: bool) -> i32 {
+    guard(CHIP8_ERROR, || {
+        let keys = handle(chip8)?.cpu.keys_mut();
+        if key > 0xF {
+            return Err(format!("no key {:#X}; the keypad runs from 0 to 0xF", key));
+        }
+        if pressed {
+            keys.press(key);
+        } else {
+            keys.release(key);
+        }
+        Ok(CHIP8_OK)
+    })
+}
+
+/// The display's width in pixels: 64, or 128 once a SUPER-CHIP program switches to hires. 0 if
+/// `chip8` is NULL.
+///
+/// # Safety
+///
+/// `chip8` must be NULL or come from `chip8_new`.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_width(chip8: *mut Chip8) -> usize {
+    guard(0, || Ok(handle(chip8)?.cpu.display().width()))
+}
+
+/// The display's height in pixels: 32, or 64 in hires. 0 if `chip8` is NULL.
+///
+/// # Safety
+///
+/// `chip8` must be NULL or come from `chip8_new`.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_height(chip8: *mut Chip8) -> usize {
+    guard(0, || Ok(handle(chip8)?.cpu.display().height()))
+}
+
+/// Copies the display into `out`, row-major, one byte per pixel holding its lit bitplanes: 0
+/// is off, 1 is on for classic ROMs, and XO-CHIP adds 2 and 3. Returns the number of bytes
+/// written, `chip8_width` times `chip8_height`, or `CHIP8_ERROR` if `out_len` is too small.
+///
+/// # Safety
+///
+/// `chip8` must be NULL or come from `chip8_new`, and `out` must be NULL or point to `out_len`
+/// writable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_framebuffer(chip8: *mut Chip8, out: *mut u8, out_len: usize) -> isize {
+    guard(CHIP8_ERROR as isize, || {
+        let display = handle(chip8)?.cpu.display();
+        let (width, height) = (display.width(), display.height());
+        if out.is_null() {
+            return Err(String::from("out is NULL"));
+        }
+        if out_len < width * height {
+            return Err(format!("out holds {} bytes, but the {}x{} display needs {}", out_len, width, height, width * height));
+        }
+        let out = slice::from_raw_parts_mut(out, width * height);
+        for y in 0..height {
+            for x in 0..width {
+                out[y * width + x] = display.pixels()[x][y];
+            }
+        }
+        Ok((width * height) as isize)
+    })
+}
+
+/// Whether the buzzer should be sounding. False if `chip8` is NULL.
+///
+/// # Safety
+///
+/// `chip8` must be NULL or come from `chip8_new`.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_sound_on(chip8: *mut Chip8) -> bool {
+    guard(false, || Ok(handle(chip8)?.cpu.is_buzzing()))
+}
+
+/// Whether the program has stopped, by 00FD, a jump to itself or an error. False if `chip8` is
+/// NULL.
+///
+/// # Safety
+///
+/// `chip8` must be NULL or come from `chip8_new`.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_is_halted(chip8: *mut Chip8) -> bool {
+    guard(false, || Ok(handle(chip8)?.cpu.halted().is_some()))
+}
+
+/// Why the last call that failed on this thread did, or NULL if none has. The string is owned
+/// by the library and stays valid until the next failure on the same thread.
+#[no_mangle]
+pub extern "C" fn chip8_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| last.borrow().as_ref().map_or(ptr::null(), |message| message.as_ptr()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::CStr;
+
+    use super::*;
+
+    fn last_error() -> String {
+        unsafe { CStr::from_ptr(chip8_last_error()) }.to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn null_handles_and_buffers_fail_instead_of_crashing() {
+        unsafe {
+            assert_eq!(chip8_step(ptr::null_mut(), 1), CHIP8_ERROR);
+            assert_eq!(last_error(), "chip8 is NULL");
+            chip8_free(ptr::null_mut());
+
+            let chip8 = chip8_new();
+            assert_eq!(chip8_load_rom(chip8, ptr::null(), 4), CHIP8_ERROR);
+            assert_eq!(last_error(), "rom is NULL");
+            assert_eq!(chip8_framebuffer(chip8, ptr::null_mut(), 0), -1);
+            let mut small = [0; 16];
+            assert_eq!(chip8_framebuffer(chip8, small.as_mut_ptr(), small.len()), -1);
+            assert_eq!(last_error(), "out holds 16 bytes, but the 64x32 display needs 2048");
+            assert_eq!(chip8_set_key(chip8, 0x10, true), CHIP8_ERROR);
+            chip8_free(chip8);
+        }
+    }
+
+    #[test]
+    fn panics_are_caught_and_reported() {
+        let result = guard(CHIP8_ERROR, || panic!("boom"));
+
+        assert_eq!(result, CHIP8_ERROR);
+        assert_eq!(last_error(), "panicked: boom");
+    }
+}
@@ -0,0 +1,745 @@
+//! A libretro core, so the emulator runs inside RetroArch and other libretro frontends. Build it
+//! with `cargo build --release --features libretro` from this directory and load the library as
+//! a core. The frontend calls the `retro_*` functions below from one thread; they drive a
+//! `RetroCore`, which does the work and is what the tests exercise.
+
+use std::ffi::{CStr, CString};
+use std::os::raw::{c_char, c_uint, c_void};
+use std::path::Path;
+use std::slice;
+use std::sync::{Mutex, OnceLock, PoisonError};
+
+use chip_8_emulator::audio::{AudioOutput, Synth, SAMPLES_PER_FRAME, SAMPLE_RATE};
+use chip_8_emulator::frontend::{self, Frontend};
+use chip_8_emulator::megachip::{MEGA_HEIGHT, MEGA_WIDTH};
+use chip_8_emulator::palette::{self, Palette};
+use chip_8_emulator::phosphor::Phosphor;
+use chip_8_emulator::render;
+use chip_8_emulator::romfile::{self, RomFormat};
+use chip_8_emulator::timing::Timing;
+use chip_8_emulator::{detect_hires_chip8, Cpu, Display, Keys, Memory, Quirks, SaveState, CYCLES_PER_FRAME, DEFAULT_LOAD_ADDRESS, PROFILES};
+
+use crate::guard;
+
+// from libretro.h
+const RETRO_API_VERSION: c_uint = 1;
+const RETRO_DEVICE_JOYPAD: c_uint = 1;
+const RETRO_ENVIRONMENT_SET_PIXEL_FORMAT: c_uint = 10;
+const RETRO_ENVIRONMENT_GET_VARIABLE: c_uint = 15;
+const RETRO_ENVIRONMENT_SET_VARIABLES: c_uint = 16;
+const RETRO_ENVIRONMENT_GET_VARIABLE_UPDATE: c_uint = 17;
+const RETRO_ENVIRONMENT_SET_GEOMETRY: c_uint = 37;
+const RETRO_PIXEL_FORMAT_XRGB8888: c_uint = 1;
+const RETRO_REGION_NTSC: c_uint = 0;
+
+/// The keypad key each RetroPad button holds, indexed by libretro's `RETRO_DEVICE_ID_JOYPAD_*`:
+/// B, Y, Select, Start, up, down, left, right, A, X, L, R, L2, R2, L3 and R3. The D-pad and B
+/// are 2/4/6/8 and 5, as on the windowed frontend's gamepads, and the rest reach every other key.
+pub const BUTTON_KEYS: [u8; 16] = [0x5, 0x1, 0xE, 0xF, 0x2, 0x8, 0x4, 0x6, 0x0, 0x3, 0x7, 0x9, 0xA, 0xB, 0xC, 0xD];
+
+/// Core option keys, as RetroArch stores them in its options file.
+pub const OPTION_PROFILE: &str = "chip8_profile";
+pub const OPTION_SPEED: &str = "chip8_speed";
+pub const OPTION_PALETTE: &str = "chip8_palette";
+
+/// The instructions per frame the speed option offers, the first being the default.
+const SPEEDS: [usize; 9] = [CYCLES_PER_FRAME, 15, 20, 30, 50, 100, 200, 500, 1000];
+
+/// The core options in libretro's `Description; default|other|...` form.
+pub fn variables() -> Vec<(&'static str, String)> {
+    let speeds: Vec<String> = SPEEDS.iter().map(usize::to_string).collect();
+    let palettes: Vec<String> = palette::built_in().into_iter().map(|palette| palette.name).collect();
+    vec![
+        (OPTION_PROFILE, format!("Quirks profile (restart); {}", PROFILES.join("|"))),
+        (OPTION_SPEED, format!("Instructions per frame; {}", speeds.join("|"))),
+        (OPTION_PALETTE, format!("Palette; {}", palettes.join("|"))),
+    ]
+}
+
+/// Everything the frontend hands over and gets back in one `retro_run`.
+struct Io {
+    buttons: [bool; 16],
+    palette: Palette,
+    phosphor: Phosphor,
+    rgba: Vec<u8>,
+    video: Vec<u32>,
+    width: usize,
+    height: usize,
+}
+
+impl Frontend for Io {
+    fn present(&mut self, display: &Display) {
+        render::frame_rgba_into(display, &self.palette, &self.phosphor, &mut self.rgba);
+        self.video.clear();
+        self.video.extend(self.rgba.chunks_exact(4).map(|rgba| u32::from_be_bytes([0, rgba[0], rgba[1], rgba[2]])));
+        self.width = display.width();
+        self.height = display.height();
+    }
+
+    fn poll_keys(&mut self, keys: &mut Keys) {
+        for (&key, &pressed) in BUTTON_KEYS.iter().zip(&self.buttons) {
+            if pressed {
+                keys.press(key);
+            } else {
+                keys.release(key);
+            }
+        }
+    }
+
+    // the sound comes from the synth after the frame
+    fn beep(&mut self, _on: bool) {}
+}
+
+/// Interleaved stereo, the same sample in both channels.
+#[derive(Default)]
+struct Stereo {
+    samples: Vec<i16>,
+}
+
+impl AudioOutput for Stereo {
+    fn queue(&mut self, samples: &[f32]) {
+        for &sample in samples {
+            let sample = (sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16;
+            self.samples.extend_from_slice(&[sample, sample]);
+        }
+    }
+}
+
+/// The emulator as a libretro core sees it: a game to load, one frame to run per call, video
+/// and audio to hand over afterwards, and save states of a fixed size.
+pub struct RetroCore {
+    cpu: Cpu,
+    rom: Option<Vec<u8>>,
+    quirks: Quirks,
+    cycles_per_frame: usize,
+    io: Io,
+    synth: Synth,
+    audio: Stereo,
+    state_size: usize,
+    resized: bool,
+}
+
+impl RetroCore {
+    pub fn new() -> RetroCore {
+        RetroCore {
+            cpu: Cpu::new(Memory::new(), Display::new()),
+            rom: None,
+            quirks: Quirks::default(),
+            cycles_per_frame: CYCLES_PER_FRAME,
+            io: Io {
+                buttons: [false; 16],
+                palette: palette::built_in().remove(0),
+                phosphor: Phosphor::default(),
+                rgba: Vec::new(),
+                video: vec![0; 64 * 32],
+                width: 64,
+                height: 32,
+            },
+            synth: Synth::new(),
+            audio: Stereo::default(),
+            state_size: 0,
+            resized: false,
+        }
+    }
+
+    /// Sets one of the `variables`. A new profile takes effect when the game is next loaded or
+    /// reset, since it can change how much memory there is.
+    pub fn set_option(&mut self, key: &str, value: &str) -> Result<(), String> {
+        match key {
+            OPTION_PROFILE => self.quirks = Quirks::profile(value).ok_or_else(|| format!("no quirks profile called {}", value))?,
+            OPTION_SPEED => {
+                self.cycles_per_frame = value.parse().ok().filter(|&cycles| cycles > 0).ok_or_else(|| format!("{} is not a speed", value))?
+            }
+            OPTION_PALETTE => {
+                self.io.palette = palette::built_in()
+                    .into_iter()
+                    .find(|palette| palette.name == value)
+                    .ok_or_else(|| format!("no palette called {}", value))?
+            }
+            _ => return Err(format!("no core option called {}", key)),
+        }
+        Ok(())
+    }
+
+    /// Starts `rom` on a fresh machine with the current profile, turning on two-page hi-res for
+    /// ROMs that start with its header. A ROM that's turned away leaves the machine as it was.
+    pub fn load_game(&mut self, rom: Vec<u8>) -> Result<(), String> {
+        let mut cpu = Cpu::new(Memory::new(), Display::new());
+        cpu.set_quirks(Quirks { hires_chip8: self.quirks.hires_chip8 || detect_hires_chip8(&rom), ..self.quirks });
+        cpu.load_rom(DEFAULT_LOAD_ADDRESS, &rom).map_err(|error| error.to_string())?;
+        self.state_size = cpu.save_state().to_bytes().len();
+        self.cpu = cpu;
+        self.rom = Some(rom);
+        self.synth = Synth::new();
+        self.io.present(self.cpu.display());
+        self.resized = true;
+        Ok(())
+    }
+
+    /// Starts the loaded game over.
+    pub fn reset(&mut self) {
+        if let Some(rom) = self.rom.take() {
+            // it only fails when a new profile has too little memory for it, which leaves it running
+            let _ = self.load_game(rom.clone());
+            self.rom = Some(rom);
+        }
+    }
+
+    /// Runs one 60 Hz frame with the RetroPad buttons `pressed` says are held: the instructions
+    /// for a frame, a timer tick, the picture and a frame's worth of sound. A ROM that has halted
+    /// on an error keeps showing its last frame.
+    pub fn run(&mut self, pressed: impl Fn(c_uint) -> bool) {
+        if self.rom.is_none() {
+            return;
+        }
+        for (id, button) in self.io.buttons.iter_mut().enumerate() {
+            *button = pressed(id as c_uint);
+        }
+        let size = (self.io.width, self.io.height);
+        // an error halts the machine, which stops later frames running anything
+        let _ = frontend::run_frame(&mut self.cpu, &mut self.io, self.cycles_per_frame, Timing::Modern);
+        self.resized |= size != (self.io.width, self.io.height);
+
+        self.audio.samples.clear();
+        self.synth.play_frame(&self.cpu, &mut self.audio);
+        self.audio.samples.resize(2 * SAMPLES_PER_FRAME, 0);
+    }
+
+    /// The last frame as `0x00RRGGBB` pixels, row by row, with its width and height.
+    pub fn video(&self) -> (&[u32], usize, usize) {
+        (&self.io.video, self.io.width, self.io.height)
+    }
+
+    /// The last frame's sound, left and right interleaved, at `SAMPLE_RATE`.
+    pub fn audio(&self) -> &[i16] {
+        &self.audio.samples
+    }
+
+    /// Whether the screen has changed size since the last call, e.g. on switching to hires.
+    pub fn take_resized(&mut self) -> bool {
+        std::mem::take(&mut self.resized)
+    }
+
+    pub fn cpu(&self) -> &Cpu {
+        &self.cpu
+    }
+
+    /// The bytes a save state takes, the same for the whole time a game is loaded as libretro
+    /// requires; 0 without a game.
+    pub fn serialize_size(&self) -> usize {
+        self.state_size
+    }
+
+    /// Writes a save state to the start of `out`; false if it doesn't fit.
+    pub fn serialize(&self, out: &mut [u8]) -> bool {
+        let state = self.cpu.save_state().to_bytes();
+        match out.get_mut(..state.len()) {
+            Some(out) if self.rom.is_some() => {
+                out.copy_from_slice(&state);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Restores a save state written by `serialize`; false, leaving the machine alone, if it's
+    /// corrupt or belongs to another game.
+    pub fn unserialize(&mut self, data: &[u8]) -> bool {
+        match SaveState::from_bytes(data) {
+            Ok(state) => self.cpu.load_state(&state).is_ok(),
+            Err(_) => false,
+        }
+    }
+
+    fn geometry(&self) -> GameGeometry {
+        GameGeometry {
+            base_width: self.io.width as c_uint,
+            base_height: self.io.height as c_uint,
+            max_width: MEGA_WIDTH as c_uint,
+            max_height: MEGA_HEIGHT as c_uint,
+            aspect_ratio: self.io.width as f32 / self.io.height as f32,
+        }
+    }
+}
+
+impl Default for RetroCore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[repr(C)]
+pub struct SystemInfo {
+    library_name: *const c_char,
+    library_version: *const c_char,
+    valid_extensions: *const c_char,
+    need_fullpath: bool,
+    block_extract: bool,
+}
+
+#[repr(C)]
+pub struct GameGeometry {
+    base_width: c_uint,
+    base_height: c_uint,
+    max_width: c_uint,
+    max_height: c_uint,
+    aspect_ratio: f32,
+}
+
+#[repr(C)]
+pub struct SystemTiming {
+    fps: f64,
+    sample_rate: f64,
+}
+
+#[repr(C)]
+pub struct SystemAvInfo {
+    geometry: GameGeometry,
+    timing: SystemTiming,
+}
+
+#[repr(C)]
+pub struct GameInfo {
+    path: *const c_char,
+    data: *const c_void,
+    size: usize,
+    meta: *const c_char,
+}
+
+#[repr(C)]
+struct Variable {
+    key: *const c_char,
+    value: *const c_char,
+}
+
+type EnvironmentFn = unsafe extern "C" fn(cmd: c_uint, data: *mut c_void) -> bool;
+type VideoRefreshFn = unsafe extern "C" fn(data: *const c_void, width: c_uint, height: c_uint, pitch: usize);
+type AudioSampleFn = unsafe extern "C" fn(left: i16, right: i16);
+type AudioSampleBatchFn = unsafe extern "C" fn(data: *const i16, frames: usize) -> usize;
+type InputPollFn = unsafe extern "C" fn();
+type InputStateFn = unsafe extern "C" fn(port: c_uint, device: c_uint, index: c_uint, id: c_uint) -> i16;
+
+#[derive(Clone, Copy)]
+struct Callbacks {
+    environment: Option<EnvironmentFn>,
+    video_refresh: Option<VideoRefreshFn>,
+    audio_sample_batch: Option<AudioSampleBatchFn>,
+    input_poll: Option<InputPollFn>,
+    input_state: Option<InputStateFn>,
+}
+
+// libretro calls in from one thread; the locks only make the statics safe to have
+static CALLBACKS: Mutex<Callbacks> =
+    Mutex::new(Callbacks { environment: None, video_refresh: None, audio_sample_batch: None, input_poll: None, input_state: None });
+static CORE: Mutex<Option<RetroCore>> = Mutex::new(None);
+// the strings `SET_VARIABLES` points at, which have to outlive it
+static VARIABLES: OnceLock<Vec<(CString, CString)>> = OnceLock::new();
+
+fn callbacks() -> Callbacks {
+    *CALLBACKS.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn with_core<T>(failed: T, body: impl FnOnce(&mut RetroCore) -> Result<T, String>) -> T {
+    guard(failed, || {
+        let mut core = CORE.lock().unwrap_or_else(PoisonError::into_inner);
+        body(core.get_or_insert_with(RetroCore::new))
+    })
+}
+
+unsafe fn environment(cmd: c_uint, data: *mut c_void) -> bool {
+    match callbacks().environment {
+        Some(environment) => environment(cmd, data),
+        None => false,
+    }
+}
+
+/// Reads the options the frontend has set into `core`, skipping any it has no value for.
+unsafe fn update_options(core: &mut RetroCore) {
+    for (key, _) in variables() {
+        let key = CString::new(key).expect("option keys have no NULs");
+        let mut variable = Variable { key: key.as_ptr(), value: std::ptr::null() };
+        if environment(RETRO_ENVIRONMENT_GET_VARIABLE, &mut variable as *mut Variable as *mut c_void) && !variable.value.is_null() {
+            let value = CStr::from_ptr(variable.value).to_string_lossy();
+            // a value from an older version of the core keeps the one before
+            let _ = core.set_option(&key.to_string_lossy(), &value);
+        }
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn retro_api_version() -> c_uint {
+    RETRO_API_VERSION
+}
+
+/// # Safety
+///
+/// `environment` must be a valid libretro environment callback.
+#[no_mangle]
+pub unsafe extern "C" fn retro_set_environment(environment: EnvironmentFn) {
+    CALLBACKS.lock().unwrap_or_else(PoisonError::into_inner).environment = Some(environment);
+    let strings = VARIABLES.get_or_init(|| {
+        variables()
+            .into_iter()
+            .map(|(key, value)| (CString::new(key).expect("option keys have no NULs"), CString::new(value).expect("options have no NULs")))
+            .collect()
+    });
+    let mut variables: Vec<Variable> = strings.iter().map(|(key, value)| Variable { key: key.as_ptr(), value: value.as_ptr() }).collect();
+    variables.push(Variable { key: std::ptr::null(), value: std::ptr::null() });
+    environment(RETRO_ENVIRONMENT_SET_VARIABLES, variables.as_mut_ptr() as *mut c_void);
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_video_refresh(video_refresh: VideoRefreshFn) {
+    CALLBACKS.lock().unwrap_or_else(PoisonError::into_inner).video_refresh = Some(video_refresh);
+}
+
+// every frame's sound goes through the batch callback
+#[no_mangle]
+pub extern "C" fn retro_set_audio_sample(_audio_sample: AudioSampleFn) {}
+
+#[no_mangle]
+pub extern "C" fn retro_set_audio_sample_batch(audio_sample_batch: AudioSampleBatchFn) {
+    CALLBACKS.lock().unwrap_or_else(PoisonError::into_inner).audio_sample_batch = Some(audio_sample_batch);
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_input_poll(input_poll: InputPollFn) {
+    CALLBACKS.lock().unwrap_or_else(PoisonError::into_inner).input_poll = Some(input_poll);
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_input_state(input_state: InputStateFn) {
+    CALLBACKS.lock().unwrap_or_else(PoisonError::into_inner).input_state = Some(input_state);
+}
+
+#[no_mangle]
+pub extern "C" fn retro_init() {
+    with_core((), |_| Ok(()))
+}
+
+#[no_mangle]
+pub extern "C" fn retro_deinit() {
+    *CORE.lock().unwrap_or_else(PoisonError::into_inner) = None;
+}
+
+/// # Safety
+///
+/// `info` must point to a writable `retro_system_info`.
+#[no_mangle]
+pub unsafe extern "C" fn retro_get_system_info(info: *mut SystemInfo) {
+    *info = SystemInfo {
+        library_name: b"CHIP-8 Emulator\0".as_ptr() as *const c_char,
+        library_version: concat!(env!("CARGO_PKG_VERSION"), "\0").as_ptr() as *const c_char,
+        valid_extensions: b"ch8|sc8|xo8|mc8|hex\0".as_ptr() as *const c_char,
+        need_fullpath: false,
+        block_extract: false,
+    };
+}
+
+/// # Safety
+///
+/// `info` must point to a writable `retro_system_av_info`.
+#[no_mangle]
+pub unsafe extern "C" fn retro_get_system_av_info(info: *mut SystemAvInfo) {
+    let geometry = with_core(None, |core| Ok(Some(core.geometry())));
+    if let Some(geometry) = geometry {
+        *info = SystemAvInfo { geometry, timing: SystemTiming { fps: 60.0, sample_rate: SAMPLE_RATE as f64 } };
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_controller_port_device(_port: c_uint, _device: c_uint) {}
+
+#[no_mangle]
+pub extern "C" fn retro_reset() {
+    with_core((), |core| {
+        core.reset();
+        Ok(())
+    })
+}
+
+/// # Safety
+///
+/// The callbacks must have been set, as libretro frontends do before loading a game.
+#[no_mangle]
+pub unsafe extern "C" fn retro_run() {
+    let callbacks = callbacks();
+    let mut updated = false;
+    if environment(RETRO_ENVIRONMENT_GET_VARIABLE_UPDATE, &mut updated as *mut bool as *mut c_void) && updated {
+        with_core((), |core| {
+            update_options(core);
+            Ok(())
+        });
+    }
+    if let Some(input_poll) = callbacks.input_poll {
+        input_poll();
+    }
+    let input_state = callbacks.input_state;
+    with_core((), |core| {
+        core.run(|id| input_state.is_some_and(|input_state| input_state(0, RETRO_DEVICE_JOYPAD, 0, id) != 0));
+        if core.take_resized() {
+            let mut geometry = core.geometry();
+            environment(RETRO_ENVIRONMENT_SET_GEOMETRY, &mut geometry as *mut GameGeometry as *mut c_void);
+        }
+        let (video, width, height) = core.video();
+        if let Some(video_refresh) = callbacks.video_refresh {
+            video_refresh(video.as_ptr() as *const c_void, width as c_uint, height as c_uint, width * 4);
+        }
+        if let Some(audio_sample_batch) = callbacks.audio_sample_batch {
+            audio_sample_batch(core.audio().as_ptr(), core.audio().len() / 2);
+        }
+        Ok(())
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn retro_serialize_size() -> usize {
+    with_core(0, |core| Ok(core.serialize_size()))
+}
+
+/// # Safety
+///
+/// `data` must point to `size` writable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn retro_serialize(data: *mut c_void, size: usize) -> bool {
+    with_core(false, |core| Ok(!data.is_null() && core.serialize(slice::from_raw_parts_mut(data as *mut u8, size))))
+}
+
+/// # Safety
+///
+/// `data` must point to `size` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn retro_unserialize(data: *const c_void, size: usize) -> bool {
+    with_core(false, |core| Ok(!data.is_null() && core.unserialize(slice::from_raw_parts(data as *const u8, size))))
+}
+
+#[no_mangle]
+pub extern "C" fn retro_cheat_reset() {}
+
+#[no_mangle]
+pub extern "C" fn retro_cheat_set(_index: c_uint, _enabled: bool, _code: *const c_char) {}
+
+/// Loads the game's bytes, read as a hex dump when its path says it is one.
+///
+/// # Safety
+///
+/// `game` must be NULL or point to a `retro_game_info` whose `data` holds `size` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn retro_load_game(game: *const GameInfo) -> bool {
+    let mut format = RETRO_PIXEL_FORMAT_XRGB8888;
+    if !environment(RETRO_ENVIRONMENT_SET_PIXEL_FORMAT, &mut format as *mut c_uint as *mut c_void) {
+        return false;
+    }
+    with_core(false, |core| {
+        let game = game.as_ref().filter(|game| !game.data.is_null()).ok_or_else(|| String::from("no game data"))?;
+        let format = if game.path.is_null() {
+            RomFormat::Binary
+        } else {
+            RomFormat::detect(Path::new(&*CStr::from_ptr(game.path).to_string_lossy()))
+        };
+        let bytes = slice::from_raw_parts(game.data as *const u8, game.size).to_vec();
+        let rom = romfile::decode(bytes, format).map_err(|error| error.to_string())?;
+        update_options(core);
+        core.load_game(rom)?;
+        Ok(true)
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn retro_load_game_special(_game_type: c_uint, _info: *const GameInfo, _num_info: usize) -> bool {
+    false
+}
+
+#[no_mangle]
+pub extern "C" fn retro_unload_game() {
+    with_core((), |core| {
+        *core = RetroCore::new();
+        Ok(())
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn retro_get_region() -> c_uint {
+    RETRO_REGION_NTSC
+}
+
+#[no_mangle]
+pub extern "C" fn retro_get_memory_data(_id: c_uint) -> *mut c_void {
+    std::ptr::null_mut()
+}
+
+#[no_mangle]
+pub extern "C" fn retro_get_memory_size(_id: c_uint) -> usize {
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // LD V0, 2; LD ST, V0; LD V1, 30; LD DT, V1; LD V2, K; LD F, V2; DRW V3, V3, 5; JP 0x20E
+    const ROM: [u8; 16] = [0x60, 0x02, 0xF0, 0x18, 0x61, 0x1E, 0xF1, 0x15, 0xF2, 0x0A, 0xF2, 0x29, 0xD3, 0x35, 0x12, 0x0E];
+
+    fn loaded() -> RetroCore {
+        let mut core = RetroCore::new();
+        core.load_game(ROM.to_vec()).unwrap();
+        core
+    }
+
+    #[test]
+    fn every_button_holds_its_own_key() {
+        let mut keys = BUTTON_KEYS.to_vec();
+        keys.sort_unstable();
+
+        assert_eq!(keys, (0..16).collect::<Vec<u8>>());
+    }
+
+    #[test]
+    fn a_frame_is_a_frame_of_instructions_a_timer_tick_and_a_frame_of_sound() {
+        let mut core = loaded();
+
+        core.run(|_| false);
+
+        assert_eq!(core.cpu().instructions_executed(), CYCLES_PER_FRAME as u64);
+        assert_eq!(core.cpu().delay_timer(), 29);
+        assert_eq!(core.audio().len(), 2 * SAMPLES_PER_FRAME);
+        // the sound timer ran through the frame, so there's a beep
+        assert!(core.audio().iter().any(|&sample| sample != 0));
+        for frame in 2..=10 {
+            core.run(|_| false);
+            assert_eq!(core.cpu().delay_timer(), 30 - frame);
+            assert_eq!(core.audio().len(), 2 * SAMPLES_PER_FRAME);
+        }
+        // silence once it's stopped, and the same amount of it
+        assert!(core.audio().iter().all(|&sample| sample == 0));
+    }
+
+    #[test]
+    fn retropad_buttons_press_keypad_keys_and_the_frame_is_shown() {
+        let mut core = loaded();
+        // A holds key 0, so FX0A gets it and the 0 is drawn
+        core.run(|id| id == 8);
+        core.run(|_| false);
+        core.run(|_| false);
+
+        let (video, width, height) = core.video();
+        assert_eq!((video.len(), width, height), (64 * 32, 64, 32));
+        // the top row of 0 is 0xF0, in the classic palette's white
+        assert_eq!(&video[..5], &[0xFF_FFFF, 0xFF_FFFF, 0xFF_FFFF, 0xFF_FFFF, 0]);
+    }
+
+    #[test]
+    fn options_change_the_speed_and_palette() {
+        let mut core = loaded();
+
+        core.set_option(OPTION_SPEED, "20").unwrap();
+        core.set_option(OPTION_PALETTE, "Amber").unwrap();
+        core.run(|_| false);
+
+        assert_eq!(core.cpu().instructions_executed(), 20);
+        assert_eq!(core.video().0[0], 0x1A_0D00);
+        assert!(core.set_option(OPTION_SPEED, "0").is_err());
+        assert!(core.set_option(OPTION_PROFILE, "nes").is_err());
+        assert!(core.set_option("chip8_volume", "1").is_err());
+        for (key, value) in variables() {
+            let default = value.split("; ").nth(1).unwrap().split('|').next().unwrap();
+            core.set_option(key, default).unwrap();
+        }
+    }
+
+    #[test]
+    fn the_profile_applies_from_the_next_reset() {
+        let mut core = loaded();
+
+        core.set_option(OPTION_PROFILE, "xochip").unwrap();
+        assert_eq!(core.cpu().memory().bytes().len(), 4096);
+        core.reset();
+
+        assert_eq!(core.cpu().memory().bytes().len(), 65536);
+        assert_eq!(core.cpu().instructions_executed(), 0);
+    }
+
+    // what the fake frontend below was handed, as (width, height, pitch) and audio frames
+    static VIDEO: Mutex<Vec<(c_uint, c_uint, usize)>> = Mutex::new(Vec::new());
+    static AUDIO: Mutex<Vec<usize>> = Mutex::new(Vec::new());
+
+    unsafe extern "C" fn environment(cmd: c_uint, data: *mut c_void) -> bool {
+        match cmd {
+            RETRO_ENVIRONMENT_GET_VARIABLE => {
+                let variable = &mut *(data as *mut Variable);
+                if CStr::from_ptr(variable.key).to_bytes() == OPTION_SPEED.as_bytes() {
+                    variable.value = b"20\0".as_ptr() as *const c_char;
+                    return true;
+                }
+                false
+            }
+            _ => cmd == RETRO_ENVIRONMENT_SET_PIXEL_FORMAT || cmd == RETRO_ENVIRONMENT_SET_VARIABLES,
+        }
+    }
+
+    unsafe extern "C" fn video_refresh(_data: *const c_void, width: c_uint, height: c_uint, pitch: usize) {
+        VIDEO.lock().unwrap().push((width, height, pitch));
+    }
+
+    unsafe extern "C" fn audio_sample_batch(_data: *const i16, frames: usize) -> usize {
+        AUDIO.lock().unwrap().push(frames);
+        frames
+    }
+
+    unsafe extern "C" fn input_poll() {}
+
+    unsafe extern "C" fn input_state(_port: c_uint, _device: c_uint, _index: c_uint, _id: c_uint) -> i16 {
+        0
+    }
+
+    #[test]
+    fn retro_run_hands_a_frame_of_video_and_audio_to_the_frontend() {
+        unsafe {
+            retro_set_environment(environment);
+            retro_set_video_refresh(video_refresh);
+            retro_set_audio_sample_batch(audio_sample_batch);
+            retro_set_input_poll(input_poll);
+            retro_set_input_state(input_state);
+            retro_init();
+            let game = GameInfo { path: std::ptr::null(), data: ROM.as_ptr() as *const c_void, size: ROM.len(), meta: std::ptr::null() };
+            assert!(retro_load_game(&game));
+            let size = retro_serialize_size();
+            for _ in 0..3 {
+                retro_run();
+            }
+
+            assert_eq!(*VIDEO.lock().unwrap(), vec![(64, 32, 256); 3]);
+            assert_eq!(*AUDIO.lock().unwrap(), vec![SAMPLES_PER_FRAME; 3]);
+            // the speed option was read on loading
+            assert_eq!(with_core(0, |core| Ok(core.cpu().instructions_executed())), 60);
+            assert_eq!(retro_serialize_size(), size);
+            retro_unload_game();
+            retro_deinit();
+        }
+    }
+
+    #[test]
+    fn save_states_have_a_fixed_size_and_round_trip() {
+        let mut core = RetroCore::new();
+        assert_eq!(core.serialize_size(), 0);
+        assert!(!core.serialize(&mut [0; 16]));
+        core.load_game(ROM.to_vec()).unwrap();
+        let size = core.serialize_size();
+        assert!(size > 4096);
+
+        let mut state = vec![0; size];
+        core.run(|_| false);
+        assert!(core.serialize(&mut state));
+        let saved = core.cpu().state_hash();
+        for _ in 0..5 {
+            core.run(|id| id == 8);
+            assert_eq!(core.serialize_size(), size);
+            assert!(!core.serialize(&mut vec![0; size - 1]));
+        }
+        assert_ne!(core.cpu().state_hash(), saved);
+
+        assert!(core.unserialize(&state));
+        assert_eq!(core.cpu().state_hash(), saved);
+        assert!(!core.unserialize(&state[..100]));
+    }
+}
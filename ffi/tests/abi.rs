@@ -0,0 +1,56 @@
+//! Loads the built cdylib the way a program in another language would, and drives a ROM through
+//! the exported symbols.
+
+use std::ffi::CStr;
+use std::os::raw::c_char;
+use std::path::PathBuf;
+
+use libloading::{Library, Symbol};
+
+enum Chip8 {}
+
+/// `libchip8.so` or its equivalent, which `cargo test` builds next to the test binary in
+/// `target/debug/deps`.
+fn library_path() -> PathBuf {
+    let mut path = std::env::current_exe().unwrap();
+    path.pop();
+    path.push(libloading::library_filename("chip8"));
+    path
+}
+
+#[test]
+fn a_rom_runs_through_the_c_abi() {
+    unsafe {
+        let library = Library::new(library_path()).unwrap();
+        let new: Symbol<extern "C" fn() -> *mut Chip8> = library.get(b"chip8_new\0").unwrap();
+        let free: Symbol<unsafe extern "C" fn(*mut Chip8)> = library.get(b"chip8_free\0").unwrap();
+        let load_rom: Symbol<unsafe extern "C" fn(*mut Chip8, *const u8, usize) -> i32> = library.get(b"chip8_load_rom\0").unwrap();
+        let step: Symbol<unsafe extern "C" fn(*mut Chip8, u32) -> i32> = library.get(b"chip8_step\0").unwrap();
+        let tick_timers: Symbol<unsafe extern "C" fn(*mut Chip8) -> i32> = library.get(b"chip8_tick_timers\0").unwrap();
+        let set_key: Symbol<unsafe extern "C" fn(*mut Chip8, u8, bool) -> i32> = library.get(b"chip8_set_key\0").unwrap();
+        let framebuffer: Symbol<unsafe extern "C" fn(*mut Chip8, *mut u8, usize) -> isize> = library.get(b"chip8_framebuffer\0").unwrap();
+        let sound_on: Symbol<unsafe extern "C" fn(*mut Chip8) -> bool> = library.get(b"chip8_sound_on\0").unwrap();
+        let last_error: Symbol<extern "C" fn() -> *const c_char> = library.get(b"chip8_last_error\0").unwrap();
+
+        let chip8 = new();
+        assert!(!chip8.is_null());
+        // LD V0, K; LD ST, V0; LD F, V0; DRW V1, V1, 5; JP 0x20A; JP 0x208, which never halts
+        let rom = [0xF0, 0x0A, 0xF0, 0x18, 0xF0, 0x29, 0xD1, 0x15, 0x12, 0x0A, 0x12, 0x08];
+        assert_eq!(load_rom(chip8, rom.as_ptr(), rom.len()), 0);
+        assert_eq!(set_key(chip8, 0x2, true), 0);
+        assert_eq!(step(chip8, 10), 0);
+        assert!(sound_on(chip8));
+        assert_eq!(tick_timers(chip8), 0);
+        assert_eq!(tick_timers(chip8), 0);
+        assert!(!sound_on(chip8));
+
+        let mut pixels = vec![0; 64 * 32];
+        assert_eq!(framebuffer(chip8, pixels.as_mut_ptr(), pixels.len()), 64 * 32);
+        // the top row of the 2 sprite is lit across
+        assert_eq!(&pixels[..5], &[1, 1, 1, 1, 0]);
+
+        assert_eq!(load_rom(chip8, rom.as_ptr(), 0), -1);
+        assert!(CStr::from_ptr(last_error()).to_str().unwrap().starts_with("ROM is empty"));
+        free(chip8);
+    }
+}
@@ -0,0 +1,38 @@
+//! Runs arbitrary bytes as a ROM; any panic is a bug. Run with `cargo fuzz run execute`.
+//!
+//! The first two bytes pick the held keys, the rest is loaded at 0x200, so the
+//! registers, I and the stack end up in whatever state the random opcodes leave them.
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+use chip_8_emulator::{Cpu, Display, Memory, CYCLES_PER_FRAME};
+
+const MAX_ROM_SIZE: usize = 0x1000 - 0x200;
+const MAX_CYCLES: usize = 10_000;
+
+fuzz_target!(|data: &[u8]| {
+    if data.len() < 2 {
+        return;
+    }
+    let held_keys = u16::from_be_bytes([data[0], data[1]]);
+    let rom = &data[2..data.len().min(2 + MAX_ROM_SIZE)];
+
+    let mut cpu = Cpu::new(Memory::new(), Display::new());
+    cpu.init(rom.to_vec());
+    cpu.seed(0);
+    for key in 0..16 {
+        if held_keys & (1 << key) != 0 {
+            cpu.keys_mut().press(key);
+        }
+    }
+
+    for cycle in 0..MAX_CYCLES {
+        if cpu.cycle().is_err() {
+            break;
+        }
+        if cycle % CYCLES_PER_FRAME == CYCLES_PER_FRAME - 1 {
+            cpu.tick_timers();
+        }
+    }
+});
@@ -0,0 +1,467 @@
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::coverage::Coverage;
+use crate::cpu::DEFAULT_LOAD_ADDRESS;
+use crate::instruction::Instruction;
+
+/// How many bytes of ROM fit in CHIP-8 and SUPER-CHIP's 4 KiB, loaded at 0x200.
+pub const CHIP_8_CAPACITY: usize = 0x1000 - DEFAULT_LOAD_ADDRESS as usize;
+/// How many bytes of ROM fit in XO-CHIP's 64 KiB, loaded at 0x200.
+pub const XO_CHIP_CAPACITY: usize = 0x10000 - DEFAULT_LOAD_ADDRESS as usize;
+
+/// The least capable machine that runs every instruction the analysis reached.
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "kebab-case")]
+pub enum Platform {
+    Chip8,
+    SuperChip,
+    XoChip,
+}
+
+impl fmt::Display for Platform {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Platform::Chip8 => "CHIP-8",
+            Platform::SuperChip => "SUPER-CHIP",
+            Platform::XoChip => "XO-CHIP",
+        })
+    }
+}
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum WarningKind {
+    /// An opcode no platform knows; running it halts.
+    UnknownOpcode,
+    /// A 0NNN call into the original interpreter's machine code, which is skipped, or halts with
+    /// `--strict`.
+    MachineCodeCall,
+    /// A jump or call to an odd address, which is rarely intended.
+    OddTarget,
+    /// A jump or call that leaves the ROM, into the interpreter area or past the end.
+    OutsideRom,
+    /// Execution can fall through the last instruction into whatever follows the ROM.
+    RunsOffEnd,
+    /// A jump to itself: the usual way to stop, though the timers and keypad still run.
+    HaltLoop,
+    /// A BNNN jump, whose targets depend on V0 and aren't followed.
+    ComputedJump,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct Warning {
+    /// Where the instruction the warning is about lives.
+    pub address: u16,
+    pub kind: WarningKind,
+    pub message: String,
+}
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum RegionKind {
+    Code,
+    Data,
+}
+
+/// A run of ROM bytes from `start` to `end`, both inclusive.
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Region {
+    pub start: u16,
+    pub end: u16,
+    pub kind: RegionKind,
+}
+
+/// What `analyze` found out about a ROM.
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct Report {
+    pub size: usize,
+    /// How much fits on `platform`.
+    pub capacity: usize,
+    pub platform: Platform,
+    /// The lowest address of an instruction that needs `platform`; None for plain CHIP-8.
+    pub platform_address: Option<u16>,
+    /// Sorted by address.
+    pub warnings: Vec<Warning>,
+    /// Covers the whole ROM; bytes no path reaches are data.
+    pub regions: Vec<Region>,
+}
+
+impl Report {
+    pub fn fits(&self) -> bool {
+        self.size <= self.capacity
+    }
+
+    /// Whether the ROM loads and never reaches an opcode that would halt the interpreter. The
+    /// other warnings are worth a look, but are allowed.
+    pub fn passed(&self) -> bool {
+        self.fits() && self.warnings.iter().all(|warning| warning.kind != WarningKind::UnknownOpcode)
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.fits() {
+            writeln!(f, "size: {} bytes, fits in {} on {}", self.size, self.capacity, self.platform)?;
+        } else {
+            writeln!(f, "size: {} bytes, more than the {} that fit on {}", self.size, self.capacity, self.platform)?;
+        }
+        match self.platform_address {
+            Some(address) => writeln!(f, "platform: {}, first needed at {:#05X}", self.platform, address)?,
+            None => writeln!(f, "platform: {}", self.platform)?,
+        }
+        if self.warnings.is_empty() {
+            writeln!(f, "warnings: none")?;
+        } else {
+            writeln!(f, "warnings:")?;
+            for warning in &self.warnings {
+                writeln!(f, "  {:#05X}: {}", warning.address, warning.message)?;
+            }
+        }
+        writeln!(f, "regions:")?;
+        for region in &self.regions {
+            let kind = match region.kind {
+                RegionKind::Code => "code",
+                RegionKind::Data => "data",
+            };
+            writeln!(f, "  {:#05X}-{:#05X} {}", region.start, region.end, kind)?;
+        }
+        Ok(())
+    }
+}
+
+fn platform(instruction: Instruction) -> Platform {
+    match instruction {
+        Instruction::ScrollDown(_)
+        | Instruction::ScrollRight
+        | Instruction::ScrollLeft
+        | Instruction::Exit
+        | Instruction::Lores
+        | Instruction::Hires
+        | Instruction::Draw(_, _, 0)
+        | Instruction::LoadBigFont(_)
+        | Instruction::StoreFlags(_)
+        | Instruction::LoadFlags(_) => Platform::SuperChip,
+        Instruction::SaveRange(..)
+        | Instruction::LoadRange(..)
+        | Instruction::LoadLongI
+        | Instruction::Plane(_)
+        | Instruction::Audio
+        | Instruction::Pitch(_) => Platform::XoChip,
+        _ => Platform::Chip8,
+    }
+}
+
+struct Walk<'a> {
+    rom: &'a [u8],
+    load_address: usize,
+    code: Vec<bool>,
+    visited: Vec<bool>,
+    pending: Vec<usize>,
+    warnings: Vec<Warning>,
+}
+
+impl Walk<'_> {
+    fn end(&self) -> usize {
+        self.load_address + self.rom.len()
+    }
+
+    fn warn(&mut self, address: usize, kind: WarningKind, message: String) {
+        let address = address as u16;
+        if !self.warnings.iter().any(|warning| warning.address == address && warning.kind == kind) {
+            self.warnings.push(Warning { address, kind, message });
+        }
+    }
+
+    /// Carries on at `next`, the instruction after the one at `from`.
+    fn fall_through(&mut self, from: usize, next: usize) {
+        if next + 2 > self.end() {
+            self.warn(from, WarningKind::RunsOffEnd, "execution can run past the end of the ROM".to_string());
+        } else {
+            self.pending.push(next);
+        }
+    }
+
+    fn branch(&mut self, from: usize, target: u16, verb: &str) {
+        let target = target as usize;
+        if target % 2 == 1 {
+            self.warn(from, WarningKind::OddTarget, format!("{} odd address {:#05X}", verb, target));
+        }
+        if target < self.load_address || target + 2 > self.end() {
+            self.warn(from, WarningKind::OutsideRom, format!("{} {:#05X}, outside the ROM", verb, target));
+        } else {
+            self.pending.push(target);
+        }
+    }
+
+    fn visit(&mut self, address: usize) {
+        let offset = address - self.load_address;
+        if self.visited[offset] {
+            return;
+        }
+        self.visited[offset] = true;
+        let opcode = u16::from_be_bytes([self.rom[offset], self.rom[offset + 1]]);
+        let length = if opcode == 0xF000 { 4 } else { 2 };
+        for byte in self.code.iter_mut().skip(offset).take(length) {
+            *byte = true;
+        }
+
+        match Instruction::decode(opcode) {
+            None => {
+                self.warn(address, WarningKind::UnknownOpcode, format!("unknown opcode {:04X}", opcode));
+            }
+            Some(Instruction::Sys(target)) => {
+                self.warn(address, WarningKind::MachineCodeCall, format!("calls machine code at {:#05X}, which is skipped", target));
+                self.fall_through(address, address + 2);
+            }
+            Some(Instruction::Jump(target)) if target as usize == address => {
+                self.warn(address, WarningKind::HaltLoop, "jumps to itself, stopping the program".to_string());
+            }
+            Some(Instruction::Jump(target)) => self.branch(address, target, "jumps to"),
+            Some(Instruction::Call(target)) => {
+                self.branch(address, target, "calls");
+                self.fall_through(address, address + 2);
+            }
+            Some(Instruction::JumpV0(base)) => {
+                self.warn(
+                    address,
+                    WarningKind::ComputedJump,
+                    format!("jumps to {:#05X} plus V0; code reached that way shows as data", base),
+                );
+            }
+            Some(Instruction::Ret) | Some(Instruction::Exit) => {}
+            Some(Instruction::SkipEqByte(..))
+            | Some(Instruction::SkipNeByte(..))
+            | Some(Instruction::SkipEqReg(..))
+            | Some(Instruction::SkipNeReg(..))
+            | Some(Instruction::SkipKeyPressed(_))
+            | Some(Instruction::SkipKeyNotPressed(_)) => {
+                // as on XO-CHIP, a skip steps over a following F000 NNNN whole
+                let skipped = if self.rom.get(offset + 2..offset + 4) == Some(&[0xF0, 0x00][..]) { 4 } else { 2 };
+                self.fall_through(address, address + 2);
+                self.fall_through(address, address + 2 + skipped);
+            }
+            Some(Instruction::LoadLongI) if offset + 4 > self.rom.len() => {
+                self.warn(address, WarningKind::RunsOffEnd, "the address after F000 is past the end of the ROM".to_string());
+            }
+            Some(_) => self.fall_through(address, address + length),
+        }
+    }
+}
+
+/// Follows every path through `rom`, loaded at 0x200, from its first instruction: skips take
+/// both ways, calls are assumed to return, and BNNN jumps aren't followed. Bytes no path reaches
+/// are reported as data.
+pub fn analyze(rom: &[u8]) -> Report {
+    let walk = walk(rom);
+    let mut warnings = walk.warnings;
+    warnings.sort_by_key(|warning| warning.address);
+
+    let mut platform_used = Platform::Chip8;
+    let mut platform_address = None;
+    for (offset, _) in walk.visited.iter().enumerate().filter(|(_, visited)| **visited) {
+        let opcode = u16::from_be_bytes([rom[offset], rom[offset + 1]]);
+        if let Some(needed) = Instruction::decode(opcode).map(platform) {
+            if needed > platform_used {
+                platform_used = needed;
+                platform_address = Some((DEFAULT_LOAD_ADDRESS as usize + offset) as u16);
+            }
+        }
+    }
+
+    let mut regions: Vec<Region> = Vec::new();
+    for (offset, &code) in walk.code.iter().enumerate() {
+        let kind = if code { RegionKind::Code } else { RegionKind::Data };
+        let address = (DEFAULT_LOAD_ADDRESS as usize + offset) as u16;
+        match regions.last_mut() {
+            Some(region) if region.kind == kind => region.end = address,
+            _ => regions.push(Region { start: address, end: address, kind }),
+        }
+    }
+
+    Report {
+        size: rom.len(),
+        capacity: if platform_used == Platform::XoChip { XO_CHIP_CAPACITY } else { CHIP_8_CAPACITY },
+        platform: platform_used,
+        platform_address,
+        warnings,
+        regions,
+    }
+}
+
+/// The instructions `analyze` reaches, as coverage of `rom` loaded at 0x200: what it leaves
+/// uncovered is dead code, or data.
+pub fn reachable(rom: &[u8]) -> Coverage {
+    let walk = walk(rom);
+    let mut coverage = Coverage::new(rom, DEFAULT_LOAD_ADDRESS);
+    for (offset, _) in walk.visited.iter().enumerate().filter(|(_, visited)| **visited) {
+        coverage.record((DEFAULT_LOAD_ADDRESS as usize + offset) as u16);
+    }
+    coverage
+}
+
+/// Which offsets into `rom`, loaded at `load_address`, start an instruction on some path from
+/// `entries`, followed the way `analyze` follows them. Entries outside the ROM are ignored.
+pub fn instruction_starts(rom: &[u8], load_address: u16, entries: &[u16]) -> Vec<bool> {
+    walk_from(rom, load_address, entries).visited
+}
+
+fn walk(rom: &[u8]) -> Walk<'_> {
+    walk_from(rom, DEFAULT_LOAD_ADDRESS, &[DEFAULT_LOAD_ADDRESS])
+}
+
+fn walk_from<'a>(rom: &'a [u8], load_address: u16, entries: &[u16]) -> Walk<'a> {
+    let mut walk = Walk {
+        rom,
+        load_address: load_address as usize,
+        code: vec![false; rom.len()],
+        visited: vec![false; rom.len()],
+        pending: Vec::new(),
+        warnings: Vec::new(),
+    };
+    let (start, end) = (walk.load_address, walk.end());
+    walk.pending.extend(entries.iter().map(|&entry| entry as usize).filter(|&entry| entry >= start && entry + 2 <= end));
+    while let Some(address) = walk.pending.pop() {
+        walk.visit(address);
+    }
+    walk
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::coverage;
+
+    fn assemble(opcodes: &[u16]) -> Vec<u8> {
+        opcodes.iter().flat_map(|opcode| opcode.to_be_bytes().to_vec()).collect()
+    }
+
+    fn kinds(report: &Report) -> Vec<(u16, WarningKind)> {
+        report.warnings.iter().map(|warning| (warning.address, warning.kind)).collect()
+    }
+
+    #[test]
+    fn a_clean_rom_has_no_warnings_and_no_data() {
+        // CLS; LD V0, 1; CALL 0x208; JP 0x206; RET
+        let report = analyze(&assemble(&[0x00E0, 0x6001, 0x2208, 0x1206, 0x00EE]));
+
+        assert_eq!(kinds(&report), vec![(0x206, WarningKind::HaltLoop)]);
+        assert_eq!(report.regions, vec![Region { start: 0x200, end: 0x209, kind: RegionKind::Code }]);
+        assert_eq!(report.platform, Platform::Chip8);
+        assert!(report.passed());
+    }
+
+    #[test]
+    fn unreachable_bytes_are_data_and_unknown_opcodes_found_only_by_walking() {
+        // JP 0x204; then a sprite that would decode as an unknown opcode; then JP 0x204
+        let report = analyze(&assemble(&[0x1204, 0xFFFF, 0x1204]));
+
+        assert_eq!(kinds(&report), vec![(0x204, WarningKind::HaltLoop)]);
+        assert_eq!(
+            report.regions,
+            vec![
+                Region { start: 0x200, end: 0x201, kind: RegionKind::Code },
+                Region { start: 0x202, end: 0x203, kind: RegionKind::Data },
+                Region { start: 0x204, end: 0x205, kind: RegionKind::Code },
+            ]
+        );
+    }
+
+    #[test]
+    fn code_no_path_reaches_is_left_uncovered() {
+        // CALL 0x206; JP 0x202; RET after the halt loop, which nothing calls; RET
+        let report = coverage::report(&reachable(&assemble(&[0x2206, 0x1202, 0x00EE, 0x00EE])));
+
+        assert_eq!(report.ranges, vec![(0x200, 0x203), (0x206, 0x207)]);
+        assert_eq!(report.uncovered().collect::<Vec<_>>(), vec!["0x204: 00EE  RET"]);
+    }
+
+    #[test]
+    fn reports_reachable_unknown_opcodes() {
+        // SE V0, 0; 5121 (unknown); 0123 (machine code, skipped); JP 0x206
+        let report = analyze(&assemble(&[0x3000, 0x5121, 0x0123, 0x1206]));
+
+        assert_eq!(
+            kinds(&report),
+            vec![(0x202, WarningKind::UnknownOpcode), (0x204, WarningKind::MachineCodeCall), (0x206, WarningKind::HaltLoop)]
+        );
+        assert_eq!(report.warnings[0].message, "unknown opcode 5121");
+        assert_eq!(report.warnings[1].message, "calls machine code at 0x123, which is skipped");
+        assert!(!report.passed());
+    }
+
+    #[test]
+    fn reports_odd_targets_and_follows_them() {
+        // JP 0x203; at 0x203, CLS and JP 0x205, straddling the even instructions
+        let report = analyze(&[0x12, 0x03, 0x60, 0x00, 0xE0, 0x12, 0x05, 0x00]);
+
+        assert_eq!(kinds(&report), vec![(0x200, WarningKind::OddTarget), (0x205, WarningKind::HaltLoop)]);
+        assert_eq!(report.warnings[0].message, "jumps to odd address 0x203");
+        assert_eq!(report.regions[2], Region { start: 0x203, end: 0x206, kind: RegionKind::Code });
+    }
+
+    #[test]
+    fn reports_targets_outside_the_rom() {
+        // CALL 0x100 (the interpreter area); CALL 0x800 (past the end); JP 0x204
+        let report = analyze(&assemble(&[0x2100, 0x2800, 0x1204]));
+
+        assert_eq!(
+            kinds(&report),
+            vec![(0x200, WarningKind::OutsideRom), (0x202, WarningKind::OutsideRom), (0x204, WarningKind::HaltLoop)]
+        );
+        assert_eq!(report.warnings[0].message, "calls 0x100, outside the ROM");
+    }
+
+    #[test]
+    fn reports_falling_off_the_end() {
+        // SNE V0, 0; JP 0x200 - when V0 is 0, execution skips past the end
+        let report = analyze(&assemble(&[0x4000, 0x1200]));
+
+        assert_eq!(kinds(&report), vec![(0x200, WarningKind::RunsOffEnd)]);
+    }
+
+    #[test]
+    fn skips_step_over_a_long_load_whole() {
+        // SE V0, 0; LD I, LONG 0x1208 (whose address would decode as a jump); JP 0x206
+        let report = analyze(&assemble(&[0x3000, 0xF000, 0x1208, 0x1206]));
+
+        assert_eq!(kinds(&report), vec![(0x206, WarningKind::HaltLoop)]);
+        assert_eq!(report.regions, vec![Region { start: 0x200, end: 0x207, kind: RegionKind::Code }]);
+    }
+
+    #[test]
+    fn reports_computed_jumps_without_following_them() {
+        // JP V0, 0x204; JP 0x204; JP 0x206
+        let report = analyze(&assemble(&[0xB204, 0x1204, 0x1206]));
+
+        assert_eq!(kinds(&report), vec![(0x200, WarningKind::ComputedJump)]);
+        assert_eq!(report.regions[1], Region { start: 0x202, end: 0x205, kind: RegionKind::Data });
+    }
+
+    #[test]
+    fn detects_the_platform_from_reachable_instructions_only() {
+        // HIGH; JP 0x202; then an unreachable F000
+        let super_chip = analyze(&assemble(&[0x00FF, 0x1202, 0xF000, 0x0000]));
+        assert_eq!((super_chip.platform, super_chip.platform_address), (Platform::SuperChip, Some(0x200)));
+        assert_eq!(super_chip.capacity, CHIP_8_CAPACITY);
+
+        // CLS; LD I, LONG 0x0208; JP 0x206 - the long address word is part of the code
+        let xo_chip = analyze(&assemble(&[0x00E0, 0xF000, 0x0208, 0x1206]));
+        assert_eq!((xo_chip.platform, xo_chip.platform_address), (Platform::XoChip, Some(0x202)));
+        assert_eq!(xo_chip.capacity, XO_CHIP_CAPACITY);
+        assert_eq!(xo_chip.regions, vec![Region { start: 0x200, end: 0x207, kind: RegionKind::Code }]);
+    }
+
+    #[test]
+    fn a_rom_too_large_for_chip_8_fails() {
+        let mut rom = assemble(&[0x1200]);
+        rom.resize(CHIP_8_CAPACITY + 1, 0);
+
+        let report = analyze(&rom);
+
+        assert!(!report.fits());
+        assert!(!report.passed());
+        assert!(report.to_string().starts_with("size: 3585 bytes, more than the 3584 that fit on CHIP-8\n"));
+    }
+}
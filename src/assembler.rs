@@ -0,0 +1,333 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+
+use crate::instruction::Instruction;
+
+/// Why a source file doesn't assemble; `line` counts from 1.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AsmError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl Error for AsmError {}
+
+const MNEMONICS: [&str; 31] = [
+    "SYS", "CLS", "RET", "SCD", "SCR", "SCL", "EXIT", "LOW", "HIGH", "JP", "CALL", "SE", "SNE", "SAVE", "LOAD", "LD",
+    "ADD", "OR", "AND", "XOR", "SUB", "SHR", "SUBN", "SHL", "RND", "DRW", "SKP", "SKNP", "PLANE", "AUDIO", "PITCH",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Operand<'a> {
+    Register(u8),
+    I,
+    IndirectI,
+    Delay,
+    Sound,
+    Key,
+    Font,
+    BigFont,
+    Bcd,
+    Flags,
+    Long,
+    LongValue(&'a str),
+    Value(&'a str),
+}
+
+fn operand(text: &str) -> Operand<'_> {
+    let upper = text.to_ascii_uppercase();
+    match upper.as_str() {
+        "I" => return Operand::I,
+        "[I]" => return Operand::IndirectI,
+        "DT" => return Operand::Delay,
+        "ST" => return Operand::Sound,
+        "K" => return Operand::Key,
+        "F" => return Operand::Font,
+        "HF" => return Operand::BigFont,
+        "B" => return Operand::Bcd,
+        "R" => return Operand::Flags,
+        "LONG" => return Operand::Long,
+        _ => {}
+    }
+    if upper.len() == 2 && upper.starts_with('V') {
+        if let Ok(register) = u8::from_str_radix(&upper[1..], 16) {
+            return Operand::Register(register);
+        }
+    }
+    if upper.starts_with("LONG ") {
+        return Operand::LongValue(text[5..].trim());
+    }
+    Operand::Value(text)
+}
+
+enum Statement<'a> {
+    Instruction { mnemonic: String, operands: Vec<Operand<'a>> },
+    Bytes(Vec<&'a str>),
+    Words(Vec<&'a str>),
+}
+
+impl Statement<'_> {
+    fn size(&self) -> usize {
+        match self {
+            Statement::Instruction { operands, .. } if operands.iter().any(|o| matches!(o, Operand::LongValue(_))) => 4,
+            Statement::Instruction { .. } => 2,
+            Statement::Bytes(values) => values.len(),
+            Statement::Words(values) => values.len() * 2,
+        }
+    }
+}
+
+fn list(text: &str) -> Vec<&str> {
+    if text.is_empty() {
+        Vec::new()
+    } else {
+        text.split(',').map(str::trim).collect()
+    }
+}
+
+fn is_label(name: &str) -> bool {
+    let mut chars = name.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && matches!(operand(name), Operand::Value(_))
+}
+
+struct Assembler<'a> {
+    labels: HashMap<&'a str, usize>,
+    line: usize,
+}
+
+impl Assembler<'_> {
+    fn error<T>(&self, message: String) -> Result<T, AsmError> {
+        Err(AsmError { line: self.line, message })
+    }
+
+    fn value(&self, text: &str, max: usize, what: &str) -> Result<u16, AsmError> {
+        let number = if let Some(hex) = text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+            usize::from_str_radix(hex, 16).ok()
+        } else if let Some(binary) = text.strip_prefix("0b").or_else(|| text.strip_prefix("0B")) {
+            usize::from_str_radix(binary, 2).ok()
+        } else if text.starts_with(|c: char| c.is_ascii_digit()) {
+            text.parse().ok()
+        } else if is_label(text) {
+            match self.labels.get(text) {
+                Some(&address) => Some(address),
+                None => return self.error(format!("undefined label {}", text)),
+            }
+        } else {
+            None
+        };
+        match number {
+            Some(number) if number <= max => Ok(number as u16),
+            Some(_) => self.error(format!("{} is out of range for {} (0-{:#X})", text, what, max)),
+            None => self.error(format!("expected a number or a label, not {}", text)),
+        }
+    }
+
+    fn address(&self, text: &str) -> Result<u16, AsmError> {
+        self.value(text, 0xFFF, "an address")
+    }
+
+    fn byte(&self, text: &str) -> Result<u8, AsmError> {
+        self.value(text, 0xFF, "a byte").map(|byte| byte as u8)
+    }
+
+    fn nibble(&self, text: &str) -> Result<u8, AsmError> {
+        self.value(text, 0xF, "a nibble").map(|nibble| nibble as u8)
+    }
+
+    fn instruction(&self, mnemonic: &str, operands: &[Operand]) -> Result<Instruction, AsmError> {
+        use Operand::*;
+        Ok(match (mnemonic, operands) {
+            ("SYS", [Value(a)]) => Instruction::Sys(self.address(a)?),
+            ("CLS", []) => Instruction::Cls,
+            ("RET", []) => Instruction::Ret,
+            ("SCD", [Value(n)]) => Instruction::ScrollDown(self.nibble(n)?),
+            ("SCR", []) => Instruction::ScrollRight,
+            ("SCL", []) => Instruction::ScrollLeft,
+            ("EXIT", []) => Instruction::Exit,
+            ("LOW", []) => Instruction::Lores,
+            ("HIGH", []) => Instruction::Hires,
+            ("JP", [Value(a)]) => Instruction::Jump(self.address(a)?),
+            ("JP", [Register(0), Value(a)]) => Instruction::JumpV0(self.address(a)?),
+            ("CALL", [Value(a)]) => Instruction::Call(self.address(a)?),
+            ("SE", [Register(x), Value(kk)]) => Instruction::SkipEqByte(*x, self.byte(kk)?),
+            ("SE", [Register(x), Register(y)]) => Instruction::SkipEqReg(*x, *y),
+            ("SNE", [Register(x), Value(kk)]) => Instruction::SkipNeByte(*x, self.byte(kk)?),
+            ("SNE", [Register(x), Register(y)]) => Instruction::SkipNeReg(*x, *y),
+            ("SAVE", [Register(x), Register(y)]) => Instruction::SaveRange(*x, *y),
+            ("LOAD", [Register(x), Register(y)]) => Instruction::LoadRange(*x, *y),
+            ("LD", [Register(x), Value(kk)]) => Instruction::LoadByte(*x, self.byte(kk)?),
+            ("LD", [Register(x), Register(y)]) => Instruction::LoadReg(*x, *y),
+            ("LD", [I, Value(a)]) => Instruction::LoadI(self.address(a)?),
+            ("LD", [I, Long]) | ("LD", [I, LongValue(_)]) => Instruction::LoadLongI,
+            ("LD", [Register(x), Delay]) => Instruction::LoadDelay(*x),
+            ("LD", [Register(x), Key]) => Instruction::WaitKey(*x),
+            ("LD", [Delay, Register(x)]) => Instruction::SetDelay(*x),
+            ("LD", [Sound, Register(x)]) => Instruction::SetSound(*x),
+            ("LD", [Font, Register(x)]) => Instruction::LoadFont(*x),
+            ("LD", [BigFont, Register(x)]) => Instruction::LoadBigFont(*x),
+            ("LD", [Bcd, Register(x)]) => Instruction::StoreBcd(*x),
+            ("LD", [IndirectI, Register(x)]) => Instruction::StoreRegisters(*x),
+            ("LD", [Register(x), IndirectI]) => Instruction::LoadRegisters(*x),
+            ("LD", [Flags, Register(x)]) => Instruction::StoreFlags(*x),
+            ("LD", [Register(x), Flags]) => Instruction::LoadFlags(*x),
+            ("ADD", [Register(x), Value(kk)]) => Instruction::AddByte(*x, self.byte(kk)?),
+            ("ADD", [Register(x), Register(y)]) => Instruction::AddReg(*x, *y),
+            ("ADD", [I, Register(x)]) => Instruction::AddI(*x),
+            ("OR", [Register(x), Register(y)]) => Instruction::Or(*x, *y),
+            ("AND", [Register(x), Register(y)]) => Instruction::And(*x, *y),
+            ("XOR", [Register(x), Register(y)]) => Instruction::Xor(*x, *y),
+            ("SUB", [Register(x), Register(y)]) => Instruction::Sub(*x, *y),
+            ("SUBN", [Register(x), Register(y)]) => Instruction::SubN(*x, *y),
+            ("SHR", [Register(x)]) => Instruction::ShiftRight(*x, *x),
+            ("SHR", [Register(x), Register(y)]) => Instruction::ShiftRight(*x, *y),
+            ("SHL", [Register(x)]) => Instruction::ShiftLeft(*x, *x),
+            ("SHL", [Register(x), Register(y)]) => Instruction::ShiftLeft(*x, *y),
+            ("RND", [Register(x), Value(kk)]) => Instruction::Random(*x, self.byte(kk)?),
+            ("DRW", [Register(x), Register(y), Value(n)]) => Instruction::Draw(*x, *y, self.nibble(n)?),
+            ("SKP", [Register(x)]) => Instruction::SkipKeyPressed(*x),
+            ("SKNP", [Register(x)]) => Instruction::SkipKeyNotPressed(*x),
+            ("PLANE", [Value(n)]) => Instruction::Plane(self.nibble(n)?),
+            ("AUDIO", []) => Instruction::Audio,
+            ("PITCH", [Register(x)]) => Instruction::Pitch(*x),
+            _ if MNEMONICS.contains(&mnemonic) => return self.error(format!("invalid operands for {}", mnemonic)),
+            _ => return self.error(format!("unknown mnemonic {}", mnemonic)),
+        })
+    }
+}
+
+/// Assembles `source`, one instruction per line with the mnemonics the disassembler prints, into
+/// bytecode to load at `origin`. A line can start with `label:`, and `;` starts a comment; `.byte`
+/// and `.word` take a comma-separated list of numbers or labels. Numbers are decimal, `0x` hex or
+/// `0b` binary.
+pub fn assemble(source: &str, origin: u16) -> Result<Vec<u8>, AsmError> {
+    let mut assembler = Assembler { labels: HashMap::new(), line: 0 };
+
+    // first pass: parse every line and place the labels
+    let mut statements = Vec::new();
+    let mut address = origin as usize;
+    for (index, line) in source.lines().enumerate() {
+        assembler.line = index + 1;
+        let mut text = line.split(';').next().unwrap_or_default().trim();
+        if let Some((label, rest)) = text.split_once(':') {
+            let label = label.trim();
+            if !is_label(label) {
+                return assembler.error(format!("invalid label {}", label));
+            }
+            if assembler.labels.insert(label, address).is_some() {
+                return assembler.error(format!("duplicate label {}", label));
+            }
+            text = rest.trim();
+        }
+        if text.is_empty() {
+            continue;
+        }
+        let (mnemonic, rest) = text.split_once(char::is_whitespace).unwrap_or((text, ""));
+        let mnemonic = mnemonic.to_ascii_uppercase();
+        let rest = rest.trim();
+        let statement = match mnemonic.as_str() {
+            ".BYTE" => Statement::Bytes(list(rest)),
+            ".WORD" => Statement::Words(list(rest)),
+            // SAVE and LOAD print their range as `V1 - V2`
+            "SAVE" | "LOAD" => Statement::Instruction { mnemonic, operands: rest.split(&[',', '-'][..]).map(|o| operand(o.trim())).collect() },
+            _ => Statement::Instruction { mnemonic, operands: list(rest).into_iter().map(operand).collect() },
+        };
+        address += statement.size();
+        statements.push((assembler.line, statement));
+    }
+
+    // second pass: encode, now that every label has an address
+    let mut bytecode = Vec::with_capacity(address - origin as usize);
+    for (line, statement) in statements {
+        assembler.line = line;
+        match statement {
+            Statement::Instruction { mnemonic, operands } => {
+                let instruction = assembler.instruction(&mnemonic, &operands)?;
+                bytecode.extend_from_slice(&instruction.encode().to_be_bytes());
+                if let [Operand::I, Operand::LongValue(target)] = operands[..] {
+                    bytecode.extend_from_slice(&assembler.value(target, 0xFFFF, "an address")?.to_be_bytes());
+                }
+            }
+            Statement::Bytes(values) => {
+                for value in values {
+                    bytecode.push(assembler.byte(value)?);
+                }
+            }
+            Statement::Words(values) => {
+                for value in values {
+                    bytecode.extend_from_slice(&assembler.value(value, 0xFFFF, "a word")?.to_be_bytes());
+                }
+            }
+        }
+    }
+    Ok(bytecode)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::instruction;
+
+    fn error(source: &str) -> String {
+        assemble(source, 0x200).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn assembles_instructions_labels_and_data() {
+        let source = "
+            ; draws a sprite forever
+            start:  CLS
+                    LD I, sprite   ; forward reference
+                    ld v0, 12
+                    DRW V0, V1, 2
+            loop:   JP loop
+            sprite: .byte 0b11110000, 0x90
+                    .word start, 0xABCD
+        ";
+
+        assert_eq!(
+            assemble(source, 0x200).unwrap(),
+            vec![0x00, 0xE0, 0xA2, 0x0A, 0x60, 0x0C, 0xD0, 0x12, 0x12, 0x08, 0xF0, 0x90, 0x02, 0x00, 0xAB, 0xCD]
+        );
+    }
+
+    #[test]
+    fn labels_count_from_the_origin() {
+        assert_eq!(assemble("here: JP here", 0x600).unwrap(), vec![0x16, 0x00]);
+    }
+
+    #[test]
+    fn long_loads_take_their_address_inline_or_as_the_next_word() {
+        let inline = assemble("LD I, LONG data\nJP 0x206\ndata: .byte 1", 0x200).unwrap();
+        let separate = assemble("LD I, LONG\n.word data\nJP 0x206\ndata: .byte 1", 0x200).unwrap();
+
+        assert_eq!(inline, vec![0xF0, 0x00, 0x02, 0x06, 0x12, 0x06, 0x01]);
+        assert_eq!(separate, inline);
+    }
+
+    #[test]
+    fn round_trips_the_disassembly_of_every_opcode() {
+        let rom: Vec<u8> = (0..=0xFFFFu16).flat_map(|opcode| opcode.to_be_bytes().to_vec()).chain(Some(0xAB)).collect();
+
+        assert_eq!(assemble(&instruction::source(&rom), 0x200).unwrap(), rom);
+    }
+
+    #[test]
+    fn reports_errors_with_their_line() {
+        assert_eq!(error("CLS\nFOO V1"), "line 2: unknown mnemonic FOO");
+        assert_eq!(error("LD V1, 256"), "line 1: 256 is out of range for a byte (0-0xFF)");
+        assert_eq!(error("DRW V0, V1, 16"), "line 1: 16 is out of range for a nibble (0-0xF)");
+        assert_eq!(error("JP 0x1000"), "line 1: 0x1000 is out of range for an address (0-0xFFF)");
+        assert_eq!(error("a: CLS\n\na: RET"), "line 3: duplicate label a");
+        assert_eq!(error("JP nowhere"), "line 1: undefined label nowhere");
+        assert_eq!(error("DRW V0, 1, 2"), "line 1: invalid operands for DRW");
+    }
+}
@@ -0,0 +1,567 @@
+#[cfg(feature = "std")]
+use core::f64::consts::TAU;
+#[cfg(feature = "std")]
+use core::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+#[cfg(feature = "std")]
+use std::sync::{Arc, Mutex};
+
+#[cfg(feature = "std")]
+use crate::cpu::Cpu;
+use crate::prelude::*;
+
+/// Output rate of `Synth`, in samples per second.
+pub const SAMPLE_RATE: u32 = 44_100;
+/// Samples making up one 60 Hz frame of sound.
+pub const SAMPLES_PER_FRAME: usize = SAMPLE_RATE as usize / 60;
+
+/// Bytes in an XO-CHIP sample pattern, played most significant bit first.
+pub const PATTERN_BYTES: usize = 16;
+#[cfg(feature = "std")]
+const PATTERN_BITS: f64 = (PATTERN_BYTES * 8) as f64;
+
+/// FX3A's pitch that plays the pattern at 4000 bits per second.
+pub const DEFAULT_PITCH: u8 = 64;
+
+/// How long `generate` takes to fade the sound in as the gate opens and out as it closes: long
+/// enough that the wave never jumps at the edges, too short to hear as a fade.
+pub const RAMP_SECONDS: f64 = 0.004;
+/// Samples `Stream` generates at a time, about 1.5 ms: how long a change to the gate can take to
+/// be heard, on top of the output's own buffering.
+pub const STREAM_CHUNK: usize = 64;
+
+/// What classic ROMs hear: a square wave of eight bits on, eight off, which is 250 Hz at the
+/// default pitch. XO-CHIP ROMs replace it with F002.
+pub const BEEP_PATTERN: [u8; PATTERN_BYTES] = [
+    0xFF, 0x00, 0xFF, 0x00, 0xFF, 0x00, 0xFF, 0x00, 0xFF, 0x00, 0xFF, 0x00, 0xFF, 0x00, 0xFF, 0x00,
+];
+
+/// The waveforms `--beep-wave` chooses between for the classic beep.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Waveform {
+    Square,
+    Sine,
+    Triangle,
+}
+
+impl Waveform {
+    /// The waveform called `name`: `square`, `sine` or `triangle`.
+    pub fn parse(name: &str) -> Option<Waveform> {
+        match name.to_ascii_lowercase().as_str() {
+            "square" => Some(Waveform::Square),
+            "sine" => Some(Waveform::Sine),
+            "triangle" => Some(Waveform::Triangle),
+            _ => None,
+        }
+    }
+
+    /// The wave's value `phase` of the way through a period, starting from 0 or, for the
+    /// square, the high half.
+    #[cfg(feature = "std")]
+    pub fn sample(self, phase: f64) -> f32 {
+        let value = match self {
+            Waveform::Square if phase < 0.5 => 1.0,
+            Waveform::Square => -1.0,
+            Waveform::Sine => (TAU * phase).sin(),
+            Waveform::Triangle if phase < 0.25 => 4.0 * phase,
+            Waveform::Triangle if phase < 0.75 => 2.0 - 4.0 * phase,
+            Waveform::Triangle => 4.0 * phase - 4.0,
+        };
+        value as f32
+    }
+}
+
+/// How the classic beep sounds, for ROMs that never load a pattern of their own.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Beep {
+    pub frequency: f64,
+    pub waveform: Waveform,
+    /// 0 to 100, the percentage of full scale.
+    pub volume: u8,
+}
+
+impl Default for Beep {
+    fn default() -> Beep {
+        Beep { frequency: 440.0, waveform: Waveform::Square, volume: 100 }
+    }
+}
+
+/// Fills `out` with `beep` at `sample_rate`, starting `phase` of the way through a period, and
+/// returns the phase to carry on from. The volume scales the wave, which never leaves -1 to 1.
+#[cfg(feature = "std")]
+pub fn tone(beep: &Beep, sample_rate: u32, phase: f64, out: &mut [f32]) -> f64 {
+    let step = beep.frequency / sample_rate as f64;
+    let amplitude = f32::from(beep.volume.min(100)) / 100.0;
+    let mut phase = phase;
+    for sample in out.iter_mut() {
+        *sample = (beep.waveform.sample(phase) * amplitude).clamp(-1.0, 1.0);
+        phase = (phase + step).fract();
+    }
+    phase
+}
+
+/// Rate in bits per second at which the sample pattern is played, as defined by XO-CHIP.
+#[cfg(feature = "std")]
+pub fn pattern_rate(pitch: u8) -> f64 {
+    4000.0 * 2f64.powf((pitch as f64 - 64.0) / 48.0)
+}
+
+/// Where the generated samples go; the frontend plays them, tests just collect them.
+pub trait AudioOutput {
+    fn queue(&mut self, samples: &[f32]);
+}
+
+/// Expands a sample pattern into a stream of -1.0/1.0 samples, or plays `Beep` for ROMs
+/// that leave the pattern alone. The position in the pattern carries over between buffers, so
+/// swapping the pattern or pitch mid-note doesn't click. Its sines and powers need std.
+#[cfg(feature = "std")]
+#[derive(Debug, Default)]
+pub struct Synth {
+    position: f64,
+    beep: Beep,
+    phase: f64,
+}
+
+#[cfg(feature = "std")]
+impl Synth {
+    pub fn new() -> Synth {
+        Synth { position: 0.0, beep: Beep::default(), phase: 0.0 }
+    }
+
+    /// Changes the classic beep, from the next frame on.
+    pub fn set_beep(&mut self, beep: Beep) {
+        self.beep = beep;
+    }
+
+    /// Fills `out` with `pattern` played at `pitch`; both are only read here, so a change
+    /// takes effect with the next buffer.
+    pub fn render(&mut self, pattern: &[u8; PATTERN_BYTES], pitch: u8, sample_rate: u32, out: &mut [f32]) {
+        let step = pattern_rate(pitch) / sample_rate as f64;
+        for sample in out.iter_mut() {
+            let bit = self.position as usize;
+            let lit = pattern[bit / 8] & (0x80 >> (bit % 8)) != 0;
+            *sample = if lit { 1.0 } else { -1.0 };
+            self.position = (self.position + step) % PATTERN_BITS;
+        }
+    }
+
+    /// Generates one frame of sound for `cpu` while its sound timer runs, and restarts the
+    /// pattern once it has stopped so every note starts the same way. A ROM still on the
+    /// default pattern and pitch gets the configured beep instead.
+    pub fn play_frame(&mut self, cpu: &Cpu, output: &mut dyn AudioOutput) {
+        if !cpu.is_buzzing() {
+            self.position = 0.0;
+            self.phase = 0.0;
+            return;
+        }
+        let mut samples = [0.0; SAMPLES_PER_FRAME];
+        if *cpu.audio_pattern() == BEEP_PATTERN && cpu.pitch() == DEFAULT_PITCH {
+            self.phase = tone(&self.beep, SAMPLE_RATE, self.phase, &mut samples);
+        } else {
+            self.render(cpu.audio_pattern(), cpu.pitch(), SAMPLE_RATE, &mut samples);
+            let amplitude = f32::from(self.beep.volume.min(100)) / 100.0;
+            for sample in samples.iter_mut() {
+                *sample *= amplitude;
+            }
+        }
+        output.queue(&samples);
+    }
+}
+
+/// What a `Stream` plays while its gate is open.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Voice {
+    Beep(Beep),
+    /// An XO-CHIP pattern at an FX3A pitch, as loud as the beep would be.
+    Pattern { pattern: [u8; PATTERN_BYTES], pitch: u8, volume: u8 },
+}
+
+/// Where `generate` left off: how far through the beep's period, or through the pattern's bits,
+/// and the level of the fade in or out, from 0 to 1.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct StreamState {
+    pub phase: f64,
+    pub level: f32,
+}
+
+/// Fills `out` with `voice` at `sample_rate` while `gate` is open and with silence while it's
+/// closed, fading in and out over `RAMP_SECONDS` at the edges, and returns the state to carry
+/// on from. Once a fade out reaches silence the phase goes back to the start, so every note
+/// starts the same way.
+#[cfg(feature = "std")]
+pub fn generate(gate: bool, voice: &Voice, state: StreamState, sample_rate: u32, out: &mut [f32]) -> StreamState {
+    let ramp_step = (1.0 / (RAMP_SECONDS * sample_rate as f64)) as f32;
+    let (step, period) = match voice {
+        Voice::Beep(beep) => (beep.frequency / sample_rate as f64, 1.0),
+        Voice::Pattern { pitch, .. } => (pattern_rate(*pitch) / sample_rate as f64, PATTERN_BITS),
+    };
+    let mut state = state;
+    for sample in out.iter_mut() {
+        state.level = if gate { (state.level + ramp_step).min(1.0) } else { (state.level - ramp_step).max(0.0) };
+        if state.level == 0.0 {
+            state.phase = 0.0;
+            *sample = 0.0;
+            continue;
+        }
+        let value = match voice {
+            Voice::Beep(beep) => beep.waveform.sample(state.phase) * amplitude(beep.volume),
+            Voice::Pattern { pattern, volume, .. } => {
+                let bit = state.phase as usize;
+                let lit = pattern[bit / 8] & (0x80 >> (bit % 8)) != 0;
+                if lit { amplitude(*volume) } else { -amplitude(*volume) }
+            }
+        };
+        *sample = value * state.level;
+        state.phase = (state.phase + step) % period;
+    }
+    state
+}
+
+#[cfg(feature = "std")]
+fn amplitude(volume: u8) -> f32 {
+    f32::from(volume.min(100)) / 100.0
+}
+
+/// The sound shared between the emulation thread, which sets it after every frame, and the
+/// audio thread's `Stream`, which reads it for every chunk. The gate, pattern and pitch are
+/// atomics, so the emulation thread never waits on the audio thread or allocates to beep; the
+/// beep's settings change rarely enough to sit behind a lock.
+#[cfg(feature = "std")]
+#[derive(Debug, Default)]
+pub struct SoundControl {
+    gate: AtomicBool,
+    pattern: [AtomicU8; PATTERN_BYTES],
+    pitch: AtomicU8,
+    beep: Mutex<Beep>,
+}
+
+#[cfg(feature = "std")]
+impl SoundControl {
+    pub fn new(beep: Beep) -> SoundControl {
+        let control = SoundControl { beep: Mutex::new(beep), ..SoundControl::default() };
+        control.pitch.store(DEFAULT_PITCH, Ordering::Relaxed);
+        control
+    }
+
+    /// Changes the classic beep, from the next chunk on.
+    pub fn set_beep(&self, beep: Beep) {
+        if let Ok(mut current) = self.beep.lock() {
+            *current = beep;
+        }
+    }
+
+    /// Opens the gate while `cpu`'s sound timer runs and `audible` allows, e.g. not while paused,
+    /// and passes on its pattern and pitch.
+    pub fn update(&self, cpu: &Cpu, audible: bool) {
+        self.set_voice(cpu);
+        self.set_gate(audible && cpu.is_buzzing());
+    }
+
+    /// Passes on `cpu`'s pattern and pitch, leaving the gate as it is.
+    pub fn set_voice(&self, cpu: &Cpu) {
+        for (byte, &value) in self.pattern.iter().zip(cpu.audio_pattern().iter()) {
+            byte.store(value, Ordering::Relaxed);
+        }
+        self.pitch.store(cpu.pitch(), Ordering::Relaxed);
+    }
+
+    pub fn set_gate(&self, open: bool) {
+        self.gate.store(open, Ordering::Release);
+    }
+
+    /// Closes the gate until the next `update`.
+    pub fn close(&self) {
+        self.set_gate(false);
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.gate.load(Ordering::Acquire)
+    }
+
+    /// What to play: the configured beep while the ROM is on the default pattern and pitch, the
+    /// ROM's pattern otherwise.
+    pub fn voice(&self) -> Voice {
+        let mut pattern = [0; PATTERN_BYTES];
+        for (value, byte) in pattern.iter_mut().zip(self.pattern.iter()) {
+            *value = byte.load(Ordering::Relaxed);
+        }
+        let pitch = self.pitch.load(Ordering::Relaxed);
+        // the audio thread would rather play the default than wait, and the lock is never held long
+        let beep = self.beep.try_lock().map(|beep| *beep).unwrap_or_default();
+        if pattern == BEEP_PATTERN && pitch == DEFAULT_PITCH {
+            Voice::Beep(beep)
+        } else {
+            Voice::Pattern { pattern, pitch, volume: beep.volume }
+        }
+    }
+}
+
+/// An endless stream of samples at `SAMPLE_RATE` for a continuously playing output, generated a
+/// `STREAM_CHUNK` at a time from a shared `SoundControl`.
+#[cfg(feature = "std")]
+pub struct Stream {
+    control: Arc<SoundControl>,
+    state: StreamState,
+    chunk: [f32; STREAM_CHUNK],
+    position: usize,
+}
+
+#[cfg(feature = "std")]
+impl Stream {
+    pub fn new(control: Arc<SoundControl>) -> Stream {
+        Stream { control, state: StreamState::default(), chunk: [0.0; STREAM_CHUNK], position: STREAM_CHUNK }
+    }
+}
+
+#[cfg(feature = "std")]
+impl Iterator for Stream {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        if self.position == STREAM_CHUNK {
+            let voice = self.control.voice();
+            self.state = generate(self.control.is_open(), &voice, self.state, SAMPLE_RATE, &mut self.chunk);
+            self.position = 0;
+        }
+        self.position += 1;
+        Some(self.chunk[self.position - 1])
+    }
+}
+
+/// Wraps mono samples in a 16-bit PCM WAV file, the format ggez can decode.
+pub fn to_wav(samples: &[f32], sample_rate: u32) -> Vec<u8> {
+    let data_length = samples.len() as u32 * 2;
+    let mut wav = Vec::with_capacity(44 + data_length as usize);
+    wav.extend_from_slice(b"RIFF");
+    wav.extend_from_slice(&(36 + data_length).to_le_bytes());
+    wav.extend_from_slice(b"WAVEfmt ");
+    wav.extend_from_slice(&16u32.to_le_bytes());
+    wav.extend_from_slice(&1u16.to_le_bytes());
+    wav.extend_from_slice(&1u16.to_le_bytes());
+    wav.extend_from_slice(&sample_rate.to_le_bytes());
+    wav.extend_from_slice(&(sample_rate * 2).to_le_bytes());
+    wav.extend_from_slice(&2u16.to_le_bytes());
+    wav.extend_from_slice(&16u16.to_le_bytes());
+    wav.extend_from_slice(b"data");
+    wav.extend_from_slice(&data_length.to_le_bytes());
+    for &sample in samples {
+        wav.extend_from_slice(&((sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16).to_le_bytes());
+    }
+    wav
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::display::Display;
+    use crate::memory::Memory;
+
+    #[test]
+    fn pattern_rate_doubles_every_48_steps() {
+        assert_eq!(pattern_rate(64), 4000.0);
+        assert!((pattern_rate(112) - 8000.0).abs() < 1e-9);
+        assert!((pattern_rate(16) - 2000.0).abs() < 1e-9);
+        assert!((pattern_rate(0) - 1587.401).abs() < 1e-3);
+    }
+
+    #[test]
+    fn render_expands_bits_into_samples() {
+        let mut pattern = [0; PATTERN_BYTES];
+        pattern[0] = 0b1010_0000;
+        pattern[15] = 0b0000_0001;
+        let mut synth = Synth::new();
+        let mut out = [0.0; 6];
+
+        // one bit per sample at 4000 Hz
+        synth.render(&pattern, DEFAULT_PITCH, 4000, &mut out);
+
+        assert_eq!(out, [1.0, -1.0, 1.0, -1.0, -1.0, -1.0]);
+
+        // continues where it left off, at half the rate and wrapping around the end
+        let mut out = [0.0; 246];
+        synth.render(&pattern, 16, 4000, &mut out);
+
+        assert_eq!(&out[240..], &[-1.0, -1.0, 1.0, 1.0, 1.0, 1.0]);
+    }
+
+    struct Recorder {
+        samples: Vec<f32>,
+    }
+
+    impl AudioOutput for Recorder {
+        fn queue(&mut self, samples: &[f32]) {
+            self.samples.extend_from_slice(samples);
+        }
+    }
+
+    #[test]
+    fn play_frame_only_sounds_while_the_timer_runs() {
+        let mut memory = Memory::new();
+        // LD ST, V0 with V0 = 2
+        memory.write_u16(0x200, 0x6002);
+        memory.write_u16(0x202, 0xF018);
+        let mut cpu = Cpu::new(memory, Display::new());
+        let mut synth = Synth::new();
+        let mut recorder = Recorder { samples: Vec::new() };
+
+        synth.play_frame(&cpu, &mut recorder);
+        cpu.cycle().unwrap();
+        cpu.cycle().unwrap();
+        synth.play_frame(&cpu, &mut recorder);
+
+        assert_eq!(recorder.samples.len(), SAMPLES_PER_FRAME);
+        // the default beep starts on its high half
+        assert_eq!(recorder.samples[0], 1.0);
+    }
+
+    fn mean(samples: &[f32]) -> f64 {
+        samples.iter().map(|&sample| f64::from(sample)).sum::<f64>() / samples.len() as f64
+    }
+
+    #[test]
+    fn square_wave_is_high_half_the_time() {
+        let beep = Beep { frequency: 441.0, waveform: Waveform::Square, volume: 100 };
+        let mut out = vec![0.0; 44_100];
+
+        tone(&beep, 44_100, 0.0, &mut out);
+
+        assert_eq!(out.iter().filter(|&&sample| sample > 0.0).count(), 22_050);
+        // each period of 100 samples is 50 high then 50 low
+        assert!(out[..50].iter().all(|&sample| sample == 1.0));
+        assert!(out[50..100].iter().all(|&sample| sample == -1.0));
+    }
+
+    #[test]
+    fn no_waveform_has_a_dc_offset() {
+        for waveform in [Waveform::Square, Waveform::Sine, Waveform::Triangle] {
+            let beep = Beep { frequency: 441.0, waveform, volume: 100 };
+            let mut out = vec![0.0; 44_100];
+
+            tone(&beep, 44_100, 0.0, &mut out);
+
+            assert!(mean(&out).abs() < 1e-4, "{:?} averages {}", waveform, mean(&out));
+            assert!(out.iter().all(|sample| (-1.0..=1.0).contains(sample)));
+        }
+    }
+
+    #[test]
+    fn waveforms_have_their_shapes() {
+        assert_eq!(Waveform::Sine.sample(0.25), 1.0);
+        assert!(Waveform::Sine.sample(0.5).abs() < 1e-6);
+        assert_eq!(Waveform::Triangle.sample(0.0), 0.0);
+        assert_eq!(Waveform::Triangle.sample(0.25), 1.0);
+        assert_eq!(Waveform::Triangle.sample(0.75), -1.0);
+        assert_eq!(Waveform::parse("Sine"), Some(Waveform::Sine));
+        assert_eq!(Waveform::parse("sawtooth"), None);
+    }
+
+    #[test]
+    fn volume_scales_without_clipping_and_the_phase_carries_on() {
+        let beep = Beep { frequency: 11_025.0, waveform: Waveform::Sine, volume: 50 };
+        let mut first = [0.0; 3];
+        let mut second = [0.0; 2];
+
+        let phase = tone(&beep, 44_100, 0.0, &mut first);
+        tone(&beep, 44_100, phase, &mut second);
+
+        // four samples a period: 0, peak, 0, trough
+        assert_eq!(first[1], 0.5);
+        assert_eq!(second[0], -0.5);
+        assert!(second[1].abs() < 1e-6);
+
+        let loud = Beep { volume: 255, ..beep };
+        tone(&loud, 44_100, 0.0, &mut first);
+        assert_eq!(first[1], 1.0);
+    }
+
+    fn largest_jump(samples: &[f32]) -> f32 {
+        samples.windows(2).map(|pair| (pair[1] - pair[0]).abs()).fold(0.0, f32::max)
+    }
+
+    #[test]
+    fn generate_fades_in_and_out_without_jumping() {
+        let sine = Voice::Beep(Beep { frequency: 441.0, waveform: Waveform::Sine, volume: 100 });
+        let mut out = vec![0.0; 1000];
+
+        // open for a quarter of a period and a bit, so it closes near the peak
+        let state = generate(false, &sine, StreamState::default(), 44_100, &mut out[..10]);
+        let state = generate(true, &sine, state, 44_100, &mut out[10..540]);
+        let state = generate(false, &sine, state, 44_100, &mut out[540..]);
+
+        assert!(out[..10].iter().all(|&sample| sample == 0.0));
+        // a sample's worth of the sine itself, 2π·441/44100, is the most it ever moves
+        assert!(largest_jump(&out) < 0.07, "jumps by {}", largest_jump(&out));
+        assert!(out[540..].iter().any(|&sample| sample.abs() > 0.5));
+        assert_eq!(state, StreamState { phase: 0.0, level: 0.0 });
+        assert!(out[540 + 177..].iter().all(|&sample| sample == 0.0));
+    }
+
+    #[test]
+    fn a_square_wave_only_jumps_by_its_own_edges() {
+        let square = Voice::Beep(Beep::default());
+        let ramp = (1.0 / (RAMP_SECONDS * 44_100.0)) as f32;
+        let mut out = vec![0.0; 300];
+
+        let state = generate(true, &square, StreamState::default(), 44_100, &mut out[..150]);
+        generate(false, &square, state, 44_100, &mut out[150..]);
+
+        // the first sample out of silence and the last one into it are one ramp step from zero
+        assert!((out[0] - ramp).abs() < 1e-6);
+        let last = out.iter().rposition(|&sample| sample != 0.0).unwrap();
+        assert!(out[last].abs() <= ramp + 1e-6);
+        // the level moves a step a sample, so between edges of the wave it never jumps more
+        let within_half_periods = out.windows(2).filter(|pair| pair[0].signum() == pair[1].signum());
+        assert!(within_half_periods.map(|pair| (pair[1] - pair[0]).abs()).all(|jump| jump <= ramp + 1e-6));
+    }
+
+    #[test]
+    fn generate_plays_patterns_at_their_pitch() {
+        let mut pattern = [0; PATTERN_BYTES];
+        pattern[0] = 0b1010_0000;
+        let voice = Voice::Pattern { pattern, pitch: DEFAULT_PITCH, volume: 100 };
+        let settled = StreamState { phase: 0.0, level: 1.0 };
+        let mut out = [0.0; 6];
+
+        // one bit per sample at 4000 Hz
+        let state = generate(true, &voice, settled, 4000, &mut out);
+
+        assert_eq!(out, [1.0, -1.0, 1.0, -1.0, -1.0, -1.0]);
+        assert_eq!(state.phase, 6.0);
+    }
+
+    #[test]
+    fn the_stream_follows_the_gate_and_the_roms_pattern() {
+        let mut memory = Memory::new();
+        // LD ST, V0 with V0 = 2
+        memory.write_u16(0x200, 0x6002);
+        memory.write_u16(0x202, 0xF018);
+        let mut cpu = Cpu::new(memory, Display::new());
+        let control = Arc::new(SoundControl::new(Beep::default()));
+        let mut stream = Stream::new(Arc::clone(&control));
+
+        control.update(&cpu, true);
+        assert!((&mut stream).take(STREAM_CHUNK * 4).all(|sample| sample == 0.0));
+
+        cpu.cycle().unwrap();
+        cpu.cycle().unwrap();
+        control.update(&cpu, false);
+        assert!(!control.is_open());
+        control.update(&cpu, true);
+        assert!(control.is_open());
+        assert_eq!(control.voice(), Voice::Beep(Beep::default()));
+        assert!((&mut stream).take(STREAM_CHUNK * 4).any(|sample| sample != 0.0));
+
+        control.close();
+        let fading: Vec<f32> = (&mut stream).take(STREAM_CHUNK * 4).collect();
+        assert_eq!(fading[fading.len() - 1], 0.0);
+    }
+
+    #[test]
+    fn wav_header_describes_the_samples() {
+        let wav = to_wav(&[1.0, -1.0, 0.0], 8000);
+
+        assert_eq!(wav.len(), 44 + 6);
+        assert_eq!(&wav[..4], b"RIFF");
+        assert_eq!(&wav[24..28], &8000u32.to_le_bytes());
+        assert_eq!(&wav[44..], &[0xFF, 0x7F, 0x01, 0x80, 0x00, 0x00]);
+    }
+}
@@ -0,0 +1,151 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::savestate::{SaveState, SaveStateError};
+
+/// Where autosaves are kept under the data directory, well away from the save states F5 writes
+/// next to each ROM.
+pub const AUTOSAVE_DIRECTORY: &str = "autosaves";
+
+const EXTENSION: &str = ".state";
+
+/// The autosave of the ROM called `rom_name` whose bytes hash to `rom_hash`:
+/// `autosaves/<name>.<hash>.state` under `data_dir`, the hash written as the ROM settings
+/// database keys ROMs. The name finds the autosaves of a ROM that has since been rebuilt.
+pub fn path(data_dir: &Path, rom_name: &str, rom_hash: u64) -> PathBuf {
+    data_dir.join(AUTOSAVE_DIRECTORY).join(format!("{}.{:016X}{}", rom_name, rom_hash, EXTENSION))
+}
+
+/// The autosaves under `data_dir` of ROMs called `rom_name`, whatever their bytes.
+fn autosaves_of(data_dir: &Path, rom_name: &str) -> Vec<PathBuf> {
+    let entries = match fs::read_dir(data_dir.join(AUTOSAVE_DIRECTORY)) {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new(),
+    };
+    let prefix = format!("{}.", rom_name);
+    entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            let name = path.file_name().and_then(|name| name.to_str()).unwrap_or_default();
+            let hash = name.strip_prefix(&prefix).and_then(|rest| rest.strip_suffix(EXTENSION)).unwrap_or_default();
+            hash.len() == 16 && hash.chars().all(|digit| digit.is_ascii_hexdigit())
+        })
+        .collect()
+}
+
+/// Writes `state` as the autosave of `rom_name`, replacing any it had from other bytes of the
+/// ROM, and returns where it went.
+pub fn write(data_dir: &Path, rom_name: &str, state: &SaveState) -> Result<PathBuf, SaveStateError> {
+    let path = path(data_dir, rom_name, state.rom_hash());
+    fs::create_dir_all(data_dir.join(AUTOSAVE_DIRECTORY))?;
+    state.write_to(&path)?;
+    for stale in autosaves_of(data_dir, rom_name).into_iter().filter(|stale| *stale != path) {
+        fs::remove_file(stale)?;
+    }
+    Ok(path)
+}
+
+/// The autosave to resume the ROM called `rom_name` from, if it has one for the bytes hashing to
+/// `rom_hash`. Autosaves of the same name for other bytes are left alone, and reported as
+/// `SaveStateError::WrongRom` when they're all there is.
+pub fn read(data_dir: &Path, rom_name: &str, rom_hash: u64) -> Result<Option<SaveState>, SaveStateError> {
+    match SaveState::read_from(&path(data_dir, rom_name, rom_hash)) {
+        Ok(state) if state.rom_hash() == rom_hash => Ok(Some(state)),
+        Ok(_) => Err(SaveStateError::WrongRom),
+        Err(SaveStateError::Io(error)) if error.kind() == io::ErrorKind::NotFound => {
+            if autosaves_of(data_dir, rom_name).is_empty() {
+                Ok(None)
+            } else {
+                Err(SaveStateError::WrongRom)
+            }
+        }
+        Err(error) => Err(error),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cpu::Cpu;
+    use crate::display::Display;
+    use crate::memory::Memory;
+
+    // counts V0 up forever
+    const COUNTER: [u8; 4] = [0x70, 0x01, 0x12, 0x00];
+
+    fn data_dir(test: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("chip-8-emulator-autosave-{}-{}", std::process::id(), test));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn running(rom: &[u8], cycles: usize) -> Cpu {
+        let mut cpu = Cpu::new(Memory::new(), Display::new());
+        cpu.init(rom.to_vec());
+        for _ in 0..cycles {
+            cpu.cycle().unwrap();
+        }
+        cpu
+    }
+
+    #[test]
+    fn autosaves_are_keyed_by_name_and_hash() {
+        let dir = data_dir("keyed");
+        let cpu = running(&COUNTER, 7);
+
+        let written = write(&dir, "counter", &cpu.save_state()).unwrap();
+
+        assert_eq!(written, dir.join("autosaves").join(format!("counter.{:016X}.state", cpu.rom_hash())));
+        assert!(read(&dir, "counter", cpu.rom_hash()).unwrap().is_some());
+        assert!(read(&dir, "other", cpu.rom_hash()).unwrap().is_none());
+        // names that only start the same aren't the same ROM's
+        assert!(read(&dir, "count", 0x1234).unwrap().is_none());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn resuming_picks_up_where_the_rom_was_left() {
+        let dir = data_dir("resume");
+        let left = running(&COUNTER, 41);
+        write(&dir, "counter", &left.save_state()).unwrap();
+
+        let mut resumed = running(&COUNTER, 0);
+        let state = read(&dir, "counter", resumed.rom_hash()).unwrap().unwrap();
+        resumed.load_state(&state).unwrap();
+
+        assert_eq!(resumed.registers()[0], left.registers()[0]);
+        assert_eq!(resumed.state_hash(), left.state_hash());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn an_autosave_of_other_bytes_is_turned_away_and_replaced() {
+        let dir = data_dir("mismatch");
+        let old = running(&COUNTER, 7);
+        write(&dir, "counter", &old.save_state()).unwrap();
+        let rebuilt = running(&[0x70, 0x02, 0x12, 0x00], 7);
+
+        assert!(matches!(read(&dir, "counter", rebuilt.rom_hash()), Err(SaveStateError::WrongRom)));
+        // the old one is still there for the old bytes
+        assert!(read(&dir, "counter", old.rom_hash()).unwrap().is_some());
+
+        write(&dir, "counter", &rebuilt.save_state()).unwrap();
+
+        assert!(read(&dir, "counter", rebuilt.rom_hash()).unwrap().is_some());
+        assert!(read(&dir, "counter", old.rom_hash()).is_err());
+        assert_eq!(fs::read_dir(dir.join(AUTOSAVE_DIRECTORY)).unwrap().count(), 1);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn a_file_under_the_wrong_hash_is_turned_away() {
+        let dir = data_dir("renamed");
+        let cpu = running(&COUNTER, 7);
+        write(&dir, "counter", &cpu.save_state()).unwrap();
+        fs::rename(path(&dir, "counter", cpu.rom_hash()), path(&dir, "counter", 0x1234)).unwrap();
+
+        assert!(matches!(read(&dir, "counter", 0x1234), Err(SaveStateError::WrongRom)));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,66 @@
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Standard base64 (RFC 4648), padded with `=`.
+pub fn encode(bytes: &[u8]) -> String {
+    let mut text = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let group = chunk.iter().enumerate().fold(0u32, |group, (index, &byte)| group | (byte as u32) << (16 - 8 * index));
+        for index in 0..4 {
+            if index <= chunk.len() {
+                text.push(ALPHABET[(group >> (18 - 6 * index)) as usize & 0x3F] as char);
+            } else {
+                text.push('=');
+            }
+        }
+    }
+    text
+}
+
+/// Reverses `encode`, saying where the first character that doesn't belong is.
+pub fn decode(text: &str) -> Result<Vec<u8>, String> {
+    if !text.len().is_multiple_of(4) {
+        return Err(format!("length {} is not a multiple of 4", text.len()));
+    }
+    let mut bytes = Vec::with_capacity(text.len() / 4 * 3);
+    for (group_index, group) in text.as_bytes().chunks(4).enumerate() {
+        let last = (group_index + 1) * 4 == text.len();
+        let padding = group.iter().rev().take_while(|&&character| character == b'=').count();
+        if padding > 2 || (padding > 0 && !last) {
+            let at = group_index * 4 + group.iter().position(|&character| character == b'=').unwrap_or(0);
+            return Err(format!("unexpected '=' at character {}", at));
+        }
+        let mut value = 0u32;
+        for (index, &character) in group[..4 - padding].iter().enumerate() {
+            let digit = ALPHABET
+                .iter()
+                .position(|&known| known == character)
+                .ok_or_else(|| format!("unexpected {:?} at character {}", character as char, group_index * 4 + index))?;
+            value |= (digit as u32) << (18 - 6 * index);
+        }
+        bytes.extend_from_slice(&value.to_be_bytes()[1..4 - padding]);
+    }
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rfc_4648_test_vectors() {
+        for (bytes, text) in [("", ""), ("f", "Zg=="), ("fo", "Zm8="), ("foo", "Zm9v"), ("foob", "Zm9vYg=="), ("fooba", "Zm9vYmE="), ("foobar", "Zm9vYmFy")] {
+            assert_eq!(encode(bytes.as_bytes()), text);
+            assert_eq!(decode(text).unwrap(), bytes.as_bytes());
+        }
+        let every_byte: Vec<u8> = (0..=255).collect();
+        assert_eq!(decode(&encode(&every_byte)).unwrap(), every_byte);
+    }
+
+    #[test]
+    fn mistakes_say_where_they_are() {
+        assert_eq!(decode("Zm9").unwrap_err(), "length 3 is not a multiple of 4");
+        assert_eq!(decode("Zm9v*mFy").unwrap_err(), "unexpected '*' at character 4");
+        assert_eq!(decode("Zg==Zm9v").unwrap_err(), "unexpected '=' at character 2");
+        assert_eq!(decode("Z===").unwrap_err(), "unexpected '=' at character 1");
+    }
+}
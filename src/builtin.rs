@@ -0,0 +1,110 @@
+use std::path::{Path, PathBuf};
+
+use crate::rompicker::RomEntry;
+
+/// What a ROM path starts with to name a built-in ROM instead of a file, e.g. `builtin:ibm`.
+pub const PREFIX: &str = "builtin:";
+
+/// A ROM compiled into the emulator, so it runs without any files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BuiltinRom {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub bytes: &'static [u8],
+}
+
+/// The built-in ROMs, by name. The two besides the IBM logo are assembled from the `.s` files
+/// next to them in `assets/roms`. `self-test` is our own check of the instructions, not the
+/// corax89 opcode test: that ROM isn't vendored here.
+pub const BUILTINS: [BuiltinRom; 3] = [
+    BuiltinRom {
+        name: "ibm",
+        description: "The IBM logo, the classic first test of an emulator's drawing",
+        bytes: include_bytes!("../assets/roms/ibm.ch8"),
+    },
+    BuiltinRom {
+        name: "self-test",
+        description: "Tests the arithmetic, skip and memory instructions; all 16 should get a tick",
+        bytes: include_bytes!("../assets/roms/self-test.ch8"),
+    },
+    BuiltinRom {
+        name: "test-pattern",
+        description: "A checkerboard over the whole screen, for checking scaling and colours",
+        bytes: include_bytes!("../assets/roms/test-pattern.ch8"),
+    },
+];
+
+pub fn find(name: &str) -> Option<&'static BuiltinRom> {
+    BUILTINS.iter().find(|builtin| builtin.name == name)
+}
+
+/// The path that loads the built-in ROM `name`.
+pub fn path(name: &str) -> PathBuf {
+    PathBuf::from(format!("{}{}", PREFIX, name))
+}
+
+/// The built-in ROM `path` names, if it names one.
+pub fn from_path(path: &Path) -> Option<&'static BuiltinRom> {
+    path.to_str().and_then(|path| path.strip_prefix(PREFIX)).and_then(find)
+}
+
+/// The built-in ROMs as the ROM picker lists them.
+pub fn entries() -> Vec<RomEntry> {
+    BUILTINS.iter().map(|builtin| RomEntry { path: path(builtin.name), size: builtin.bytes.len() as u64 }).collect()
+}
+
+/// One line per built-in ROM: its name and what it does.
+pub fn listing() -> Vec<String> {
+    BUILTINS.iter().map(|builtin| format!("{:<14} {}", builtin.name, builtin.description)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assembler::assemble;
+    use crate::headless::{self, KeyScript};
+    use crate::{Cpu, Display, Memory, DEFAULT_LOAD_ADDRESS};
+
+    fn run(name: &str, cycles: u64) -> Cpu {
+        let mut cpu = Cpu::new(Memory::new(), Display::new());
+        cpu.init(find(name).unwrap().bytes.to_vec());
+        headless::run(&mut cpu, cycles, &KeyScript::default()).unwrap();
+        cpu
+    }
+
+    #[test]
+    fn the_ibm_logo_draws_the_logo() {
+        assert_eq!(run("ibm", 100).display().to_ascii(), include_str!("../tests/roms/ibm.txt"));
+    }
+
+    #[test]
+    fn the_self_test_passes_every_test() {
+        let screen = run("self-test", 2000).display().to_ascii();
+        let rows: Vec<&str> = screen.lines().collect();
+
+        // the second row of a tick, under each of the 16 test numbers
+        for grid_row in 0..4 {
+            let row = rows[1 + grid_row * 7 + 1];
+            for column in 0..4 {
+                assert_eq!(&row[column * 16 + 5..column * 16 + 10], "....#", "test {:X}", grid_row * 4 + column);
+            }
+        }
+    }
+
+    #[test]
+    fn assembled_roms_match_their_sources() {
+        let sources =
+            [("self-test", include_str!("../assets/roms/self-test.s")), ("test-pattern", include_str!("../assets/roms/test-pattern.s"))];
+        for (name, source) in sources.iter() {
+            assert_eq!(assemble(source, DEFAULT_LOAD_ADDRESS).unwrap(), find(name).unwrap().bytes, "{}", name);
+        }
+    }
+
+    #[test]
+    fn paths_name_built_in_roms() {
+        assert_eq!(from_path(&path("ibm")).map(|builtin| builtin.name), Some("ibm"));
+        assert_eq!(from_path(Path::new("ibm")), None);
+        assert_eq!(from_path(Path::new("builtin:pong")), None);
+        assert_eq!(entries()[0], RomEntry { path: PathBuf::from("builtin:ibm"), size: 132 });
+    }
+}
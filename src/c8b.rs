@@ -0,0 +1,289 @@
+use std::error::Error;
+use std::fmt;
+
+use crate::palette::{self, Rgb};
+use crate::quirks::Platform;
+use crate::settings::RomSettings;
+
+pub const MAGIC: &[u8; 3] = b"CBF";
+pub const VERSION: u8 = 0;
+
+const HEADER_LENGTH: usize = 8;
+const SEGMENT_ENTRY_LENGTH: usize = 5;
+
+const PLATFORM_CHIP_8: u8 = 0;
+const PLATFORM_SUPER_CHIP: u8 = 1;
+const PLATFORM_XO_CHIP: u8 = 2;
+
+const PROPERTY_TITLE: u8 = 0;
+const PROPERTY_AUTHOR: u8 = 1;
+const PROPERTY_TICK_RATE: u8 = 2;
+const PROPERTY_COLORS: u8 = 3;
+
+/// Why a file that starts like a container can't be loaded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum C8bError {
+    /// Shorter than the header, or without the `CBF` magic.
+    NotAContainer,
+    UnsupportedVersion(u8),
+    /// A table or segment reaches past the end of the file; `what` says which.
+    Truncated { what: &'static str, offset: usize, length: usize, file_length: usize },
+    BadProperty { key: u8, reason: &'static str },
+    /// None of the bytecode segments is for a platform the emulator runs.
+    NoSupportedBytecode,
+}
+
+impl fmt::Display for C8bError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            C8bError::NotAContainer => write!(f, "not a .c8b container: no CBF header"),
+            C8bError::UnsupportedVersion(version) => write!(f, "unsupported .c8b version {}", version),
+            C8bError::Truncated { what, offset, length, file_length } => write!(
+                f,
+                "{} at {:#06X} needs {} bytes, but the file is only {} bytes long",
+                what, offset, length, file_length
+            ),
+            C8bError::BadProperty { key, reason } => write!(f, "property {}: {}", key, reason),
+            C8bError::NoSupportedBytecode => write!(f, "no CHIP-8, SUPER-CHIP or XO-CHIP bytecode in the container"),
+        }
+    }
+}
+
+impl Error for C8bError {}
+
+/// One platform's version of the ROM.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Segment {
+    pub platform: u8,
+    pub bytecode: Vec<u8>,
+}
+
+/// A `.c8b` container: a ROM packaged with its metadata, possibly with versions for several
+/// platforms. All numbers are big-endian, and offsets count from the start of the file.
+///
+/// | Offset | Size | Contents                                               |
+/// |--------|------|--------------------------------------------------------|
+/// | 0      | 3    | `CBF`                                                  |
+/// | 3      | 1    | format version, 0                                      |
+/// | 4      | 2    | offset of the bytecode table                           |
+/// | 6      | 2    | offset of the property table, or 0 if there isn't one  |
+///
+/// The bytecode table is a count byte followed by that many 5-byte entries: platform
+/// (0 CHIP-8, 1 SUPER-CHIP, 2 XO-CHIP; others are ignored), then the offset and length of the
+/// bytecode. The property table is a count byte followed by that many key, length and data
+/// entries; the keys are 0 title, 1 author, 2 tick rate (instructions per frame, two bytes) and
+/// 3 colours (two to four RGB triples: background, plane 1, plane 2, both planes). Unknown keys
+/// are skipped.
+///
+/// The metadata is only what the container chose to include.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Container {
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub tick_rate: Option<u16>,
+    pub colors: Option<[Rgb; 4]>,
+    pub segments: Vec<Segment>,
+}
+
+/// Whether `bytes` start with the container magic; anything else is raw bytecode.
+pub fn is_container(bytes: &[u8]) -> bool {
+    bytes.starts_with(MAGIC)
+}
+
+impl Container {
+    pub fn parse(bytes: &[u8]) -> Result<Container, C8bError> {
+        if bytes.len() < HEADER_LENGTH || !is_container(bytes) {
+            return Err(C8bError::NotAContainer);
+        }
+        if bytes[3] != VERSION {
+            return Err(C8bError::UnsupportedVersion(bytes[3]));
+        }
+        let mut container = Container::default();
+
+        let segments_offset = read_u16(bytes, 4) as usize;
+        let count = slice(bytes, "bytecode table", segments_offset, 1)?[0] as usize;
+        let entries = slice(bytes, "bytecode table", segments_offset + 1, count * SEGMENT_ENTRY_LENGTH)?;
+        for entry in entries.chunks(SEGMENT_ENTRY_LENGTH) {
+            let (offset, length) = (read_u16(entry, 1) as usize, read_u16(entry, 3) as usize);
+            let bytecode = slice(bytes, "bytecode", offset, length)?.to_vec();
+            container.segments.push(Segment { platform: entry[0], bytecode });
+        }
+
+        let properties_offset = read_u16(bytes, 6) as usize;
+        if properties_offset != 0 {
+            let count = slice(bytes, "property table", properties_offset, 1)?[0];
+            let mut position = properties_offset + 1;
+            for _ in 0..count {
+                let header = slice(bytes, "property", position, 2)?;
+                let (key, length) = (header[0], header[1] as usize);
+                let data = slice(bytes, "property", position + 2, length)?;
+                container.read_property(key, data)?;
+                position += 2 + length;
+            }
+        }
+        Ok(container)
+    }
+
+    fn read_property(&mut self, key: u8, data: &[u8]) -> Result<(), C8bError> {
+        let text = || String::from_utf8(data.to_vec()).map_err(|_| C8bError::BadProperty { key, reason: "text isn't UTF-8" });
+        match key {
+            PROPERTY_TITLE => self.title = Some(text()?),
+            PROPERTY_AUTHOR => self.author = Some(text()?),
+            PROPERTY_TICK_RATE => {
+                if data.len() != 2 || read_u16(data, 0) == 0 {
+                    return Err(C8bError::BadProperty { key, reason: "tick rate must be two bytes and above 0" });
+                }
+                self.tick_rate = Some(read_u16(data, 0));
+            }
+            PROPERTY_COLORS => {
+                if !matches!(data.len(), 6 | 9 | 12) {
+                    return Err(C8bError::BadProperty { key, reason: "colours must be 2 to 4 RGB triples" });
+                }
+                // planes the container doesn't colour keep the classic palette's colours
+                let mut colors = palette::built_in()[0].colors;
+                for (color, rgb) in colors.iter_mut().zip(data.chunks(3)) {
+                    *color = Rgb(rgb[0], rgb[1], rgb[2]);
+                }
+                self.colors = Some(colors);
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// The bytecode to run and the platform it's for: XO-CHIP if there is some, since it needs
+    /// the most of the emulator, then SUPER-CHIP, then plain CHIP-8.
+    pub fn bytecode(&self) -> Result<(Platform, &[u8]), C8bError> {
+        let find = |platform| self.segments.iter().find(|segment| segment.platform == platform);
+        if let Some(segment) = find(PLATFORM_XO_CHIP) {
+            return Ok((Platform::XoChip, &segment.bytecode));
+        }
+        find(PLATFORM_SUPER_CHIP)
+            .or_else(|| find(PLATFORM_CHIP_8))
+            .map(|segment| (Platform::SuperChip, segment.bytecode.as_slice()))
+            .ok_or(C8bError::NoSupportedBytecode)
+    }
+
+    /// The metadata as ROM settings, which stored and command line settings override.
+    pub fn settings(&self) -> Result<RomSettings, C8bError> {
+        let (platform, _) = self.bytecode()?;
+        Ok(RomSettings {
+            name: self.title.clone(),
+            platform: Some(platform),
+            cycles_per_frame: self.tick_rate.map(usize::from),
+            colors: self.colors,
+            ..RomSettings::default()
+        })
+    }
+}
+
+fn read_u16(bytes: &[u8], offset: usize) -> u16 {
+    u16::from_be_bytes([bytes[offset], bytes[offset + 1]])
+}
+
+/// `length` bytes of `bytes` from `offset`, or an error naming `what` didn't fit.
+fn slice<'a>(bytes: &'a [u8], what: &'static str, offset: usize, length: usize) -> Result<&'a [u8], C8bError> {
+    bytes
+        .get(offset..offset + length)
+        .ok_or(C8bError::Truncated { what, offset, length, file_length: bytes.len() })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds a container with `segments` and `properties`, laid out one after the other.
+    fn container(segments: &[(u8, &[u8])], properties: &[(u8, &[u8])]) -> Vec<u8> {
+        let segments_offset = HEADER_LENGTH;
+        let properties_offset = segments_offset + 1 + segments.len() * SEGMENT_ENTRY_LENGTH;
+        let properties_length: usize = 1 + properties.iter().map(|(_, data)| 2 + data.len()).sum::<usize>();
+        let mut bytecode_offset = properties_offset + properties_length;
+
+        let mut bytes = MAGIC.to_vec();
+        bytes.push(VERSION);
+        bytes.extend_from_slice(&(segments_offset as u16).to_be_bytes());
+        bytes.extend_from_slice(&(properties_offset as u16).to_be_bytes());
+        bytes.push(segments.len() as u8);
+        for (platform, bytecode) in segments {
+            bytes.push(*platform);
+            bytes.extend_from_slice(&(bytecode_offset as u16).to_be_bytes());
+            bytes.extend_from_slice(&(bytecode.len() as u16).to_be_bytes());
+            bytecode_offset += bytecode.len();
+        }
+        bytes.push(properties.len() as u8);
+        for (key, data) in properties {
+            bytes.push(*key);
+            bytes.push(data.len() as u8);
+            bytes.extend_from_slice(data);
+        }
+        for (_, bytecode) in segments {
+            bytes.extend_from_slice(bytecode);
+        }
+        bytes
+    }
+
+    #[test]
+    fn parses_metadata_and_bytecode() {
+        let bytes = container(
+            &[(PLATFORM_CHIP_8, &[0x00, 0xE0, 0x12, 0x00])],
+            &[(PROPERTY_TITLE, b"Pong"), (PROPERTY_AUTHOR, b"Paul Vervalin"), (PROPERTY_TICK_RATE, &[0x00, 0x0F]), (PROPERTY_COLORS, &[0, 0, 0x20, 0xFF, 0xFF, 0xFF])],
+        );
+
+        let container = Container::parse(&bytes).unwrap();
+
+        assert!(is_container(&bytes));
+        assert_eq!(container.title.as_deref(), Some("Pong"));
+        assert_eq!(container.author.as_deref(), Some("Paul Vervalin"));
+        assert_eq!(container.bytecode().unwrap(), (Platform::SuperChip, &[0x00, 0xE0, 0x12, 0x00][..]));
+        let settings = container.settings().unwrap();
+        assert_eq!(settings.cycles_per_frame, Some(15));
+        assert_eq!(settings.name.as_deref(), Some("Pong"));
+        let colors = settings.colors.unwrap();
+        assert_eq!(&colors[..2], &[Rgb(0, 0, 0x20), Rgb(0xFF, 0xFF, 0xFF)]);
+        assert_eq!(&colors[2..], &palette::built_in()[0].colors[2..]);
+    }
+
+    #[test]
+    fn optional_fields_can_be_missing() {
+        let mut bytes = container(&[(PLATFORM_SUPER_CHIP, &[0x00, 0xFF])], &[]);
+        // no property table at all
+        bytes[6..8].copy_from_slice(&[0, 0]);
+
+        let container = Container::parse(&bytes).unwrap();
+
+        assert_eq!(container.title, None);
+        assert_eq!(container.settings().unwrap(), RomSettings { platform: Some(Platform::SuperChip), ..RomSettings::default() });
+    }
+
+    #[test]
+    fn prefers_the_most_capable_supported_platform() {
+        let both = container(&[(PLATFORM_CHIP_8, &[0x11]), (7, &[0x77]), (PLATFORM_XO_CHIP, &[0x22]), (PLATFORM_SUPER_CHIP, &[0x33])], &[]);
+        assert_eq!(Container::parse(&both).unwrap().bytecode().unwrap(), (Platform::XoChip, &[0x22][..]));
+
+        let classic = container(&[(PLATFORM_CHIP_8, &[0x11]), (PLATFORM_SUPER_CHIP, &[0x33])], &[]);
+        assert_eq!(Container::parse(&classic).unwrap().bytecode().unwrap(), (Platform::SuperChip, &[0x33][..]));
+
+        let unknown = container(&[(7, &[0x77])], &[]);
+        assert_eq!(Container::parse(&unknown).unwrap().bytecode(), Err(C8bError::NoSupportedBytecode));
+    }
+
+    #[test]
+    fn malformed_containers_are_rejected() {
+        let good = container(&[(PLATFORM_CHIP_8, &[0x00, 0xE0])], &[(PROPERTY_TITLE, b"Test")]);
+
+        assert_eq!(Container::parse(b"CBF"), Err(C8bError::NotAContainer));
+        assert_eq!(Container::parse(&[0x00, 0xE0, 0x12, 0x00, 0, 0, 0, 0]), Err(C8bError::NotAContainer));
+        let mut version = good.clone();
+        version[3] = 9;
+        assert_eq!(Container::parse(&version), Err(C8bError::UnsupportedVersion(9)));
+        let truncated = &good[..good.len() - 1];
+        assert_eq!(
+            Container::parse(truncated),
+            Err(C8bError::Truncated { what: "bytecode", offset: good.len() - 2, length: 2, file_length: good.len() - 1 })
+        );
+        let bad_colors = container(&[(PLATFORM_CHIP_8, &[0x00, 0xE0])], &[(PROPERTY_COLORS, &[1, 2, 3])]);
+        assert!(matches!(Container::parse(&bad_colors), Err(C8bError::BadProperty { key: PROPERTY_COLORS, .. })));
+        let bad_text = container(&[(PLATFORM_CHIP_8, &[0x00, 0xE0])], &[(PROPERTY_AUTHOR, &[0xFF])]);
+        assert!(matches!(Container::parse(&bad_text), Err(C8bError::BadProperty { key: PROPERTY_AUTHOR, .. })));
+    }
+}
@@ -0,0 +1,166 @@
+#[cfg(feature = "std")]
+use core::convert::TryFrom;
+use core::fmt;
+
+#[cfg(feature = "std")]
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "std")]
+use crate::cli::parse_address;
+use crate::cpu::Cpu;
+use crate::prelude::*;
+
+/// A byte to write into memory, written `0x3A2=0x05` on the command line and in settings.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize), serde(try_from = "String", into = "String"))]
+pub struct Poke {
+    pub address: u16,
+    pub value: u8,
+}
+
+#[cfg(feature = "std")]
+impl Poke {
+    /// Parses `ADDRESS=VALUE`, both in hex with or without `0x`.
+    pub fn parse(text: &str) -> Result<Poke, String> {
+        let (address, value) = match text.find('=') {
+            Some(index) => (&text[..index], &text[index + 1..]),
+            None => return Err(format!("{} is not ADDRESS=VALUE", text)),
+        };
+        let address = parse_address(address.trim())?;
+        let digits = value.trim().trim_start_matches("0x").trim_start_matches("0X");
+        let value = u8::from_str_radix(digits, 16).map_err(|_| format!("{} is not a byte between 0x00 and 0xFF", value.trim()))?;
+        Ok(Poke { address, value })
+    }
+}
+
+impl fmt::Display for Poke {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "0x{:03X}=0x{:02X}", self.address, self.value)
+    }
+}
+
+#[cfg(feature = "std")]
+impl TryFrom<String> for Poke {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Poke, String> {
+        Poke::parse(&value)
+    }
+}
+
+impl From<Poke> for String {
+    fn from(poke: Poke) -> String {
+        poke.to_string()
+    }
+}
+
+/// Patches to a ROM's memory, as in a `[cheats]` table of its settings:
+///
+/// ```toml
+/// [roms.A1B2C3D4E5F60718.cheats]
+/// poke = ["0x3A0=0x01"]
+/// freeze = ["0x3A2=0x05"]
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+pub struct Cheats {
+    /// Written once, after the ROM is loaded.
+    #[cfg_attr(feature = "std", serde(default, skip_serializing_if = "Vec::is_empty"))]
+    pub poke: Vec<Poke>,
+    /// Written again at the end of every frame, so the ROM can't change them, e.g. to keep the
+    /// number of lives up.
+    #[cfg_attr(feature = "std", serde(default, skip_serializing_if = "Vec::is_empty"))]
+    pub freeze: Vec<Poke>,
+}
+
+impl Cheats {
+    pub fn is_empty(&self) -> bool {
+        self.poke.is_empty() && self.freeze.is_empty()
+    }
+
+    /// Says which cheat, if any, writes past the end of `memory_size` bytes of memory.
+    pub fn check(&self, memory_size: usize) -> Result<(), String> {
+        match self.poke.iter().chain(&self.freeze).find(|poke| poke.address as usize >= memory_size) {
+            Some(poke) => Err(format!("{} is past the end of the {} bytes of memory", poke, memory_size)),
+            None => Ok(()),
+        }
+    }
+
+    /// Writes the one-off pokes and hands the frozen ones to `cpu`; `check` them first.
+    pub fn apply(&self, cpu: &mut Cpu) {
+        for poke in &self.poke {
+            cpu.poke(poke.address, poke.value);
+        }
+        cpu.freeze(self.freeze.clone());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::display::Display;
+    use crate::headless::{self, KeyScript};
+    use crate::memory::Memory;
+
+    // adds one to the byte at 0x300, then waits on the delay timer for the frame to end
+    const COUNTER: [u8; 20] = [
+        0xA3, 0x00, 0xF0, 0x65, 0x70, 0x01, 0xF0, 0x55, 0x61, 0x01, 0xF1, 0x15, 0xF1, 0x07, 0x31, 0x00, 0x12, 0x0C, 0x12, 0x00,
+    ];
+
+    fn run_frames(cheats: &Cheats, frames: u64) -> Cpu {
+        let mut cpu = Cpu::new(Memory::new(), Display::new());
+        cpu.init(COUNTER.to_vec());
+        cheats.apply(&mut cpu);
+        headless::run(&mut cpu, frames * 10, &KeyScript::default()).unwrap();
+        cpu
+    }
+
+    #[test]
+    fn parse_pokes() {
+        assert_eq!(Poke::parse("0x3A2=0x05"), Ok(Poke { address: 0x3A2, value: 5 }));
+        assert_eq!(Poke::parse("3a2 = ff"), Ok(Poke { address: 0x3A2, value: 0xFF }));
+        assert_eq!(Poke::parse("0x3A2").unwrap_err(), "0x3A2 is not ADDRESS=VALUE");
+        assert_eq!(Poke::parse("0x3A2=0x100").unwrap_err(), "0x100 is not a byte between 0x00 and 0xFF");
+        assert_eq!(Poke::parse("0x10000=1").unwrap_err(), "0x10000 is not an address between 0x0000 and 0xFFFF");
+        assert_eq!(Poke { address: 0x3A2, value: 5 }.to_string(), "0x3A2=0x05");
+    }
+
+    #[test]
+    fn cheats_past_the_end_of_memory_are_turned_away() {
+        let cheats = Cheats { freeze: vec![Poke { address: 0x1000, value: 1 }], ..Cheats::default() };
+
+        assert_eq!(cheats.check(4096).unwrap_err(), "0x1000=0x01 is past the end of the 4096 bytes of memory");
+        assert_eq!(cheats.check(65536), Ok(()));
+    }
+
+    #[test]
+    fn a_poke_is_written_once_and_then_left_to_the_rom() {
+        let cpu = run_frames(&Cheats { poke: vec![Poke { address: 0x300, value: 0x50 }], ..Cheats::default() }, 20);
+
+        assert!(cpu.memory().bytes()[0x300] > 0x55, "{}", cpu.memory().bytes()[0x300]);
+    }
+
+    #[test]
+    fn a_frozen_poke_is_written_again_every_frame() {
+        let cheats = Cheats { freeze: vec![Poke { address: 0x300, value: 0x05 }], ..Cheats::default() };
+
+        let cpu = run_frames(&cheats, 20);
+        assert_eq!(cpu.memory().bytes()[0x300], 0x05);
+
+        let mut cpu = run_frames(&cheats, 20);
+        cpu.set_cheats_enabled(false);
+        headless::run(&mut cpu, 200, &KeyScript::default()).unwrap();
+        assert!(cpu.memory().bytes()[0x300] > 0x0A, "{}", cpu.memory().bytes()[0x300]);
+    }
+
+    #[test]
+    fn cheats_survive_a_round_trip_through_toml() {
+        let cheats = Cheats { poke: vec![Poke { address: 0x3A0, value: 1 }], freeze: vec![Poke { address: 0x3A2, value: 5 }] };
+
+        let text = toml::to_string(&cheats).unwrap();
+
+        assert_eq!(text, "poke = [\"0x3A0=0x01\"]\nfreeze = [\"0x3A2=0x05\"]\n");
+        assert_eq!(toml::from_str::<Cheats>(&text).unwrap(), cheats);
+        assert!(toml::from_str::<Cheats>("freeze = [\"0x3A2=0x105\"]").is_err());
+    }
+}
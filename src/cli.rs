@@ -0,0 +1,1004 @@
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use clap::{Arg, ArgMatches, Command, Error, ErrorKind};
+
+use crate::audio::{Beep, Waveform};
+use crate::builtin;
+use crate::cheats::{Cheats, Poke};
+use crate::condition::Condition;
+use crate::configfile::ConfigFile;
+use crate::cpu::{DEFAULT_LOAD_ADDRESS, ETI_660_LOAD_ADDRESS};
+use crate::debugger::{DEFAULT_STEP_LIMIT, DEFAULT_TURBO_MULTIPLIER};
+use crate::error::IllegalOpcodes;
+use crate::font::Font;
+use crate::headless::KeyScript;
+use crate::keymap::{KeyboardLayout, Keymap};
+use crate::memory::{WatchMode, Watchpoint};
+use crate::palette::Rgb;
+use crate::quirks::{Platform, Quirks, PROFILES};
+use crate::render::VisualBuzzer;
+use crate::replay::Recording;
+use crate::romfile::RomFormat;
+use crate::screenshot;
+use crate::settings::{RomSettings, SettingsDatabase};
+use crate::timing::Timing;
+
+const NAME: &str = "chip-8-emulator";
+const SUBCOMMANDS: [&str; 7] = ["run", "disasm", "check", "compare", "asm", "dump", "test-suite"];
+
+/// `compare`'s default budget, enough for a ROM to get past its title screen.
+pub const DEFAULT_COMPARE_CYCLES: u64 = 10_000;
+
+/// What the command line asked for.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Invocation {
+    /// Run a ROM, in a window or headless; also what no subcommand at all means.
+    Run(Box<Config>),
+    /// Print the ROM's instructions, one per line, as if loaded at `load_address`; as plain source
+    /// for the assembler with `source`.
+    Disasm { rom: PathBuf, format: Option<RomFormat>, load_address: u16, source: bool, labels: bool, sprites: bool },
+    /// Analyze the ROM without running it, printing the report as JSON rather than text with `json`;
+    /// with `dead_code`, also a listing marking what no path reaches.
+    Check { rom: PathBuf, format: Option<RomFormat>, json: bool, dead_code: bool },
+    /// Print the names and descriptions of the built-in ROMs.
+    ListBuiltins,
+    /// Assemble `input` into a ROM loaded at `load_address`, written to `out`.
+    Asm { input: PathBuf, out: PathBuf, load_address: u16 },
+    /// Pretty-print the core dump written when a ROM crashed.
+    Dump { path: PathBuf },
+    /// Run every ROM in the manifest headlessly and check its framebuffer; with `update`, record
+    /// the framebuffers as the new expectations instead.
+    TestSuite { manifest: PathBuf, update: bool },
+    /// Run the ROM headless under the `left` and `right` quirk profiles side by side and report
+    /// where they first part ways; with `to_the_end`, carry on for all of `cycles` and count the
+    /// frames that differ.
+    Compare { rom: PathBuf, format: Option<RomFormat>, left: String, right: String, cycles: u64, keys: KeyScript, seed: u64, to_the_end: bool },
+}
+
+/// How to run a ROM: everything `run` takes from the command line. `keymap`, `file`, `file_path`
+/// and `database` come from the config files rather than the command line, and `stdin` from
+/// standard input; all are filled in after parsing.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Config {
+    /// None to choose one in the ROM picker.
+    pub rom: Option<PathBuf>,
+    /// None to go by the ROM's extension.
+    pub format: Option<RomFormat>,
+    /// The ROM read from stdin when `rom` is `-`, kept for resets.
+    pub stdin: Option<Vec<u8>>,
+    pub seed: Option<u64>,
+    pub headless: bool,
+    /// Play in the terminal rather than a window; only with the `frontend-terminal` feature.
+    pub terminal: bool,
+    /// Ring the terminal bell for sound.
+    pub bell: bool,
+    /// A rhai script to run against the ROM headless; only with the `scripting` feature.
+    pub script: Option<PathBuf>,
+    pub cycles: u64,
+    /// Print the state hash every this many instructions headless.
+    pub hash_every: Option<u64>,
+    pub out: Option<PathBuf>,
+    /// Image pixels per display pixel in screenshots.
+    pub screenshot_scale: usize,
+    pub keys: KeyScript,
+    pub rewind_seconds: u32,
+    pub breakpoints: Vec<u16>,
+    /// Breakpoints given as `ADDRESS if CONDITION`.
+    pub conditional_breakpoints: Vec<(u16, Condition)>,
+    /// `--break-when`'s conditions, checked after every instruction.
+    pub break_conditions: Vec<Condition>,
+    pub watchpoints: Vec<Watchpoint>,
+    /// Instructions a step over or run until return gets before giving up.
+    pub step_limit: u64,
+    /// What to do when the ROM runs into a word that's no instruction.
+    pub on_illegal: IllegalOpcodes,
+    pub profile_opcodes: bool,
+    /// Where to write which instructions ran, on exit.
+    pub coverage: Option<PathBuf>,
+    /// A JSON save state to start from, and whether to load it even if it's from another ROM.
+    pub import_state: Option<PathBuf>,
+    pub force: bool,
+    /// Where to write the machine's state as JSON, on exit.
+    pub export_state: Option<PathBuf>,
+    /// Start from the autosave the ROM left when it was last closed, without asking.
+    pub resume: bool,
+    /// Quirks, speed and colours from the command line, which override the database's.
+    pub settings: RomSettings,
+    pub database: SettingsDatabase,
+    pub save_settings: bool,
+    pub close_after_exit: Option<Duration>,
+    pub load_address: u16,
+    pub font: Option<Font>,
+    /// Fraction of a pixel's brightness kept each frame after it's erased; 0 turns the afterglow off.
+    pub phosphor: f32,
+    pub crt: bool,
+    pub fullscreen: bool,
+    pub integer_scale: bool,
+    pub watch_rom: bool,
+    pub pause_on_focus_loss: bool,
+    /// Read debugger commands such as `set V3 0x1F` from stdin while the window runs.
+    pub console: bool,
+    /// How many times faster than normal the machine runs while Tab is held.
+    pub turbo_multiplier: u32,
+    pub beep: Beep,
+    pub visual_buzzer: VisualBuzzer,
+    pub keymap: Keymap,
+    /// Whose keypad block `keymap` starts from, with the config file's `[keymap]` entries on top
+    /// of it rather than replacing it.
+    pub kb_layout: Option<KeyboardLayout>,
+    /// What `chip8.toml` held when it was last read; its quirks and speed go under everything else.
+    pub file: ConfigFile,
+    /// Where `chip8.toml` is, or would be if there were one, to watch for edits.
+    pub file_path: Option<PathBuf>,
+    /// Where `--record` writes the input when the window closes.
+    pub record: Option<PathBuf>,
+    pub replay: Option<Recording>,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            rom: None,
+            format: None,
+            stdin: None,
+            seed: None,
+            headless: false,
+            terminal: false,
+            bell: false,
+            script: None,
+            cycles: 1000,
+            hash_every: None,
+            out: None,
+            screenshot_scale: screenshot::DEFAULT_SCALE,
+            keys: KeyScript::default(),
+            rewind_seconds: 10,
+            breakpoints: Vec::new(),
+            conditional_breakpoints: Vec::new(),
+            break_conditions: Vec::new(),
+            watchpoints: Vec::new(),
+            step_limit: DEFAULT_STEP_LIMIT,
+            on_illegal: IllegalOpcodes::default(),
+            profile_opcodes: false,
+            coverage: None,
+            import_state: None,
+            force: false,
+            export_state: None,
+            resume: false,
+            settings: RomSettings::default(),
+            database: SettingsDatabase::default(),
+            save_settings: false,
+            close_after_exit: None,
+            load_address: DEFAULT_LOAD_ADDRESS,
+            font: None,
+            phosphor: 0.0,
+            crt: false,
+            fullscreen: false,
+            integer_scale: false,
+            watch_rom: false,
+            pause_on_focus_loss: true,
+            console: false,
+            turbo_multiplier: DEFAULT_TURBO_MULTIPLIER,
+            beep: Beep::default(),
+            visual_buzzer: VisualBuzzer::default(),
+            keymap: Keymap::default(),
+            kb_layout: None,
+            file: ConfigFile::default(),
+            file_path: None,
+            record: None,
+            replay: None,
+        }
+    }
+}
+
+/// The command line parser, subcommands and all; `--help` output comes from here.
+pub fn command() -> Command<'static> {
+    Command::new(NAME)
+        .about("A CHIP-8, SUPER-CHIP and XO-CHIP emulator")
+        .subcommand_required(true)
+        .subcommand(run_command())
+        .subcommand(
+            Command::new("disasm")
+                .about("Print a ROM's instructions without running it")
+                .arg(rom_arg().required(true))
+                .arg(format_arg())
+                .arg(load_address_arg())
+                .arg(flag("source", "Print plain source that asm assembles back into the ROM, without addresses"))
+                .arg(
+                    flag("labels", "Follow the ROM's code and print source with labels for jump, call and LD I targets, and unreached bytes as data")
+                        .conflicts_with("source"),
+                )
+                .arg(flag("sprites", "With --labels, draw the data LD I points at as sprite rows in comments").requires("labels")),
+        )
+        .subcommand(
+            Command::new("check")
+                .about("Check that a ROM is fit to run without running it, following its code from 0x200")
+                .arg(rom_arg().required(true))
+                .arg(format_arg())
+                .arg(Arg::new("json").long("json").help("Print the report as JSON"))
+                .arg(flag("dead-code", "Also list the ROM, marking the instructions no path reaches").conflicts_with("json")),
+        )
+        .subcommand(
+            Command::new("compare")
+                .about("Run a ROM headless under two quirk profiles in lockstep and report where they part ways")
+                .arg(rom_arg().required(true))
+                .arg(format_arg())
+                .arg(option("left", "PROFILE", "The first profile: modern, chip8, schip, xochip or megachip").required(true))
+                .arg(option("right", "PROFILE", "The profile to compare it with").required(true))
+                .arg(option("cycles", "N", "Instructions to run at most [default: 10000]"))
+                .arg(option("keys", "SCRIPT", "Keys to press on both sides, e.g. 5@100,release5@160"))
+                .arg(option("seed", "N", "Seed both random number generators with N [default: 0]"))
+                .arg(flag("continue", "Run all the cycles rather than stopping at the first difference, and count the frames that differ")),
+        )
+        .subcommand(
+            Command::new("asm")
+                .about("Assemble CHIP-8 source with the classic mnemonics into a ROM")
+                .arg(Arg::new("input").value_name("SOURCE").required(true).help("The source file"))
+                .arg(Arg::new("out").short('o').long("out").value_name("ROM").required(true).help("Where to write the ROM"))
+                .arg(load_address_arg()),
+        )
+        .subcommand(
+            Command::new("dump")
+                .about("Pretty-print a core dump written when a ROM crashed")
+                .arg(Arg::new("dump").value_name("DUMP").required(true).help("The .dump.json file")),
+        )
+        .subcommand(
+            Command::new("test-suite")
+                .about("Run the ROMs a manifest lists headlessly and check each leaves the screen it expects")
+                .arg(Arg::new("manifest").value_name("MANIFEST").required(true).help("The manifest, a TOML file"))
+                .arg(flag("update", "Record the framebuffers as the expected results rather than checking them")),
+        )
+}
+
+fn rom_arg() -> Arg<'static> {
+    Arg::new("rom").value_name("ROM").help("The ROM to load: raw bytecode, a .c8b container or a hex dump; - reads stdin")
+}
+
+fn format_arg() -> Arg<'static> {
+    option("format", "FORMAT", "Read the ROM as binary or hex, rather than going by its extension (.hex and .txt are hex)")
+}
+
+fn load_address_arg() -> Arg<'static> {
+    Arg::new("load-address").long("load-address").value_name("ADDRESS").help("Load the ROM and start executing at ADDRESS, in hex")
+}
+
+fn flag(name: &'static str, help: &'static str) -> Arg<'static> {
+    Arg::new(name).long(name).help(help)
+}
+
+fn option(name: &'static str, value_name: &'static str, help: &'static str) -> Arg<'static> {
+    Arg::new(name).long(name).value_name(value_name).help(help)
+}
+
+fn run_command() -> Command<'static> {
+    Command::new("run")
+        .about("Run a ROM; the default when no subcommand is given")
+        .arg(rom_arg().help("The ROM to load: raw bytecode, a .c8b container or a hex dump; - reads stdin; without one, pick it in a menu"))
+        .arg(format_arg())
+        .arg(option("builtin", "NAME", "Run one of the ROMs built into the emulator").conflicts_with("rom"))
+        .arg(flag("list-builtins", "List the built-in ROMs and exit"))
+        .arg(option("seed", "N", "Seed the random number generator, for reproducible runs"))
+        .arg(option("cycles-per-frame", "N", "Instructions per 60 Hz frame"))
+        .arg(option("timing", "MODE", "modern runs a flat count of instructions a frame; vip times each as the COSMAC VIP did"))
+        .arg(flag("xochip", "Run as XO-CHIP: 64 KiB of memory and two bitplanes"))
+        .arg(flag("megachip", "Run as MEGA-CHIP: 16 MiB of memory and a 256x192 screen of 256 colours").conflicts_with("xochip"))
+        .arg(flag("half-pixel-scroll", "Scroll lores by half pixels, as SUPER-CHIP 1.1 does"))
+        .arg(flag("tall-lores-sprites", "Draw DXY0 in lores as 8x16, as SUPER-CHIP 1.1 does"))
+        .arg(flag("strict-big-font", "Halt when FX30 asks for a big digit above 9"))
+        .arg(flag("strict", "Halt on 0NNN calls into machine code and on keys or font digits above 0xF"))
+        .arg(option("on-illegal", "POLICY", "What to do with words that are no instruction: halt, skip them, or warn-once and skip them"))
+        .arg(flag("i-overflow-flag", "Set VF when FX1E carries I past 0xFFF, as the Amiga interpreter does"))
+        .arg(flag("display-wait", "End the frame after every DXYN, as the COSMAC VIP does"))
+        .arg(flag("key-release-wait", "Make FX0A wait for the key to be let go of, as the COSMAC VIP does"))
+        .arg(flag("shift-vy", "Make 8XY6 and 8XYE shift VY into VX, as the COSMAC VIP does"))
+        .arg(flag("hires-chip8", "Run as two-page hi-res CHIP-8: a 64x64 screen, starting at 0x2C0"))
+        .arg(load_address_arg().conflicts_with("eti660"))
+        .arg(flag("eti660", "Load the ROM at 0x600, as on the ETI-660"))
+        .arg(option("font", "FILE", "Replace the built-in font with one read from FILE"))
+        .arg(option("plane-colors", "COLORS", "Four RRGGBB colours separated by commas, one per plane combination"))
+        .arg(option("kb-layout", "LAYOUT", "Put the keypad where 1234/QWER/ASDF/ZXCV is on a qwerty, azerty, qwertz or colemak keyboard"))
+        .arg(flag("save-settings", "Store the quirks, speed and colours for this ROM in roms.toml"))
+        .arg(flag("headless", "Run without a window and print the final display"))
+        .arg(option("cycles", "N", "Instructions to run headless").requires("headless"))
+        .arg(option("keys", "SCRIPT", "Keys to press headless, e.g. 5@100,release5@160").requires("headless"))
+        .arg(option("hash-every", "N", "Print the machine state's hash every N instructions headless").requires("headless"))
+        .arg(option("out", "FILE", "Write the final display headless to FILE, as PNG or PBM by extension").requires("headless"))
+        .arg(option("screenshot-scale", "N", "Image pixels per display pixel in screenshots"))
+        .arg(option("rewind-seconds", "SECONDS", "How much history Backspace can rewind through"))
+        .arg(
+            option("break", "ADDRESS", "Pause before executing the instruction at ADDRESS, or with 'ADDRESS if V3==0x1F' only when that holds there")
+                .multiple_occurrences(true),
+        )
+        .arg(
+            option("break-when", "CONDITION", "Pause after whichever instruction makes CONDITION true, e.g. I>0xE00 or mem[0x300]!=0")
+                .multiple_occurrences(true),
+        )
+        .arg(option("watch", "RANGE", "Pause on memory accesses, e.g. 0x300-0x30F:w").multiple_occurrences(true))
+        .arg(option("poke", "ADDRESS=VALUE", "Write a byte into memory once the ROM is loaded, e.g. 0x3A2=0x05").multiple_occurrences(true))
+        .arg(
+            option("freeze", "ADDRESS=VALUE", "Write a byte into memory at the end of every frame, so the ROM can't change it")
+                .multiple_occurrences(true),
+        )
+        .arg(option("step-limit", "N", "Instructions a step over or run until return runs before giving up"))
+        .arg(flag("profile-opcodes", "Count executed instructions and report the busiest on exit"))
+        .arg(option("coverage", "FILE", "Write which instructions ran to FILE on exit, with a listing marking them"))
+        .arg(option("import-state", "FILE", "Start from a save state exported as JSON").conflicts_with_all(&["record", "replay"]))
+        .arg(flag("force", "Import the state even if it was saved from another ROM").requires("import-state"))
+        .arg(option("export-state", "FILE", "Write the machine's state to FILE as JSON on exit"))
+        .arg(flag("resume", "Pick up from where the ROM was when its window was last closed").conflicts_with_all(&["import-state", "record", "replay", "headless"]))
+        .arg(option("record", "FILE", "Record the keypad input to FILE for --replay").conflicts_with_all(&["replay", "headless", "poke", "freeze"]))
+        .arg(option("replay", "FILE", "Replay input recorded with --record").conflicts_with_all(&["keys", "cycles", "hash-every", "poke", "freeze"]))
+        .args(window_args().into_iter().map(|arg| arg.conflicts_with("headless")))
+        .args(terminal_args())
+        .args(script_args())
+}
+
+/// Options for playing in a terminal, which only exist when the terminal frontend is built.
+#[cfg(feature = "frontend-terminal")]
+fn terminal_args() -> Vec<Arg<'static>> {
+    vec![
+        flag("terminal", "Play in the terminal instead of a window, e.g. over SSH").conflicts_with_all(&["headless", "record", "replay"]),
+        flag("bell", "Ring the terminal bell when the sound timer starts").requires("terminal"),
+    ]
+}
+
+#[cfg(not(feature = "frontend-terminal"))]
+fn terminal_args() -> Vec<Arg<'static>> {
+    Vec::new()
+}
+
+/// Options for scripting runs, which only exist when the scripting engine is built.
+#[cfg(feature = "scripting")]
+fn script_args() -> Vec<Arg<'static>> {
+    vec![option("script", "FILE", "Run the rhai script FILE against the ROM as it runs headless").requires("headless").conflicts_with_all(&["replay", "hash-every"])]
+}
+
+#[cfg(not(feature = "scripting"))]
+fn script_args() -> Vec<Arg<'static>> {
+    Vec::new()
+}
+
+/// Options that only make sense with a window.
+fn window_args() -> Vec<Arg<'static>> {
+    vec![
+        flag("fullscreen", "Start in fullscreen"),
+        flag("integer-scale", "Scale the display by whole multiples only"),
+        flag("crt", "Start with the CRT effect on"),
+        option("phosphor", "DECAY", "Fade erased pixels out, keeping this fraction of their brightness each frame"),
+        option("close-after-exit", "SECONDS", "Close the window this long after the ROM exits"),
+        flag("watch-rom", "Reload the ROM whenever its file changes"),
+        flag("no-pause-on-focus-loss", "Keep running while the window is out of focus"),
+        flag("console", "Take debugger commands typed into the terminal while paused: set V3 0x1F, set I 0x300, set pc 0x2A4, setmem 0x300 de ad"),
+        option("turbo-multiplier", "N", "How many times faster to run while Tab is held"),
+        option("beep-freq", "HZ", "The pitch of the beep ROMs without their own sound make"),
+        option("beep-wave", "WAVE", "The shape of the beep: square, sine or triangle"),
+        option("volume", "PERCENT", "How loud the sound is, from 0 to 100"),
+        option("visual-buzzer", "STYLE", "Show the sound playing as a border around the display or a speaker icon: border, icon or off"),
+    ]
+}
+
+/// Parses a full command line, program name first. Without a subcommand, `run` is assumed.
+pub fn parse<I, T>(args: I) -> Result<Invocation, Error>
+where
+    I: IntoIterator<Item = T>,
+    T: Into<OsString>,
+{
+    parse_on(args, Config::default())
+}
+
+/// `parse`, with `base` in place of the defaults for whatever a `run` command line leaves out.
+pub fn parse_on<I, T>(args: I, base: Config) -> Result<Invocation, Error>
+where
+    I: IntoIterator<Item = T>,
+    T: Into<OsString>,
+{
+    let mut args: Vec<OsString> = args.into_iter().map(Into::into).collect();
+    if args.is_empty() {
+        args.push(OsString::from(NAME));
+    }
+    let explicit = args.get(1).and_then(|arg| arg.to_str()).is_some_and(|arg| {
+        SUBCOMMANDS.contains(&arg) || matches!(arg, "help" | "-h" | "--help")
+    });
+    if !explicit {
+        args.insert(1, OsString::from("run"));
+    }
+
+    let matches = command().try_get_matches_from(args)?;
+    match matches.subcommand() {
+        Some(("disasm", matches)) => Ok(Invocation::Disasm {
+            rom: PathBuf::from(matches.value_of("rom").unwrap_or_default()),
+            format: format(matches)?,
+            load_address: match matches.value_of("load-address") {
+                Some(address) => parse_address(address).map_err(invalid)?,
+                None => DEFAULT_LOAD_ADDRESS,
+            },
+            source: matches.is_present("source"),
+            labels: matches.is_present("labels"),
+            sprites: matches.is_present("sprites"),
+        }),
+        Some(("asm", matches)) => Ok(Invocation::Asm {
+            input: PathBuf::from(matches.value_of("input").unwrap_or_default()),
+            out: PathBuf::from(matches.value_of("out").unwrap_or_default()),
+            load_address: match matches.value_of("load-address") {
+                Some(address) => parse_address(address).map_err(invalid)?,
+                None => DEFAULT_LOAD_ADDRESS,
+            },
+        }),
+        Some(("dump", matches)) => Ok(Invocation::Dump { path: PathBuf::from(matches.value_of("dump").unwrap_or_default()) }),
+        Some(("test-suite", matches)) => Ok(Invocation::TestSuite {
+            manifest: PathBuf::from(matches.value_of("manifest").unwrap_or_default()),
+            update: matches.is_present("update"),
+        }),
+        Some(("check", matches)) => Ok(Invocation::Check {
+            rom: PathBuf::from(matches.value_of("rom").unwrap_or_default()),
+            format: format(matches)?,
+            json: matches.is_present("json"),
+            dead_code: matches.is_present("dead-code"),
+        }),
+        Some(("compare", matches)) => {
+            let profile = |side: &str| {
+                let name = matches.value_of(side).unwrap_or_default().to_ascii_lowercase();
+                match Quirks::profile(&name) {
+                    Some(_) => Ok(name),
+                    None => Err(invalid(format!("--{} must be one of {}, not {}", side, PROFILES.join(", "), name))),
+                }
+            };
+            let (mut cycles, mut seed) = (DEFAULT_COMPARE_CYCLES, 0);
+            parse_value(matches, "cycles", &mut cycles, |value| value.parse().ok(), "must be an unsigned integer")?;
+            parse_value(matches, "seed", &mut seed, |value| value.parse().ok(), "must be an unsigned integer")?;
+            Ok(Invocation::Compare {
+                rom: PathBuf::from(matches.value_of("rom").unwrap_or_default()),
+                format: format(matches)?,
+                left: profile("left")?,
+                right: profile("right")?,
+                cycles,
+                keys: KeyScript::parse(matches.value_of("keys").unwrap_or_default()).map_err(invalid)?,
+                seed,
+                to_the_end: matches.is_present("continue"),
+            })
+        }
+        Some((_, matches)) if matches.is_present("list-builtins") => Ok(Invocation::ListBuiltins),
+        Some((_, matches)) => Ok(Invocation::Run(Box::new(config(matches, base)?))),
+        None => unreachable!("a subcommand is required"),
+    }
+}
+
+fn invalid(message: String) -> Error {
+    command().error(ErrorKind::ValueValidation, message)
+}
+
+/// Parses `name`'s value with `parse`, or leaves `target` alone if it wasn't given.
+fn parse_value<T>(matches: &ArgMatches, name: &str, target: &mut T, parse: impl Fn(&str) -> Option<T>, expected: &str) -> Result<(), Error> {
+    if let Some(value) = matches.value_of(name) {
+        *target = parse(value).ok_or_else(|| invalid(format!("--{} {}, not {}", name, expected, value)))?;
+    }
+    Ok(())
+}
+
+fn format(matches: &ArgMatches) -> Result<Option<RomFormat>, Error> {
+    let mut format = None;
+    parse_value(matches, "format", &mut format, |value| RomFormat::parse(value).map(Some), "must be binary or hex")?;
+    Ok(format)
+}
+
+fn config(matches: &ArgMatches, base: Config) -> Result<Config, Error> {
+    let mut config = Config { rom: matches.value_of("rom").map(PathBuf::from), format: format(matches)?, ..base };
+    if let Some(name) = matches.value_of("builtin") {
+        if builtin::find(name).is_none() {
+            let names: Vec<&str> = builtin::BUILTINS.iter().map(|builtin| builtin.name).collect();
+            return Err(invalid(format!("--builtin must be one of {}, not {}", names.join(", "), name)));
+        }
+        config.rom = Some(builtin::path(name));
+    }
+
+    let mut seed = None;
+    parse_value(matches, "seed", &mut seed, |value| value.parse().ok().map(Some), "must be an unsigned integer")?;
+    config.seed = seed;
+    config.headless = matches.is_present("headless");
+    if config.headless && config.rom.is_none() {
+        return Err(command().error(ErrorKind::MissingRequiredArgument, "--headless needs a ROM or --builtin"));
+    }
+    #[cfg(feature = "frontend-terminal")]
+    {
+        config.terminal = matches.is_present("terminal");
+        config.bell = matches.is_present("bell");
+        if config.terminal && config.rom.is_none() {
+            return Err(command().error(ErrorKind::MissingRequiredArgument, "--terminal needs a ROM or --builtin"));
+        }
+    }
+    #[cfg(feature = "scripting")]
+    {
+        config.script = matches.value_of("script").map(PathBuf::from);
+    }
+    config.profile_opcodes = matches.is_present("profile-opcodes");
+    config.coverage = matches.value_of("coverage").map(PathBuf::from);
+    config.import_state = matches.value_of("import-state").map(PathBuf::from);
+    config.force = matches.is_present("force");
+    config.export_state = matches.value_of("export-state").map(PathBuf::from);
+    config.resume = matches.is_present("resume");
+    if matches.is_present("half-pixel-scroll") {
+        config.settings.half_pixel_scroll = Some(true);
+    }
+    if matches.is_present("tall-lores-sprites") {
+        config.settings.tall_lores_sprites = Some(true);
+    }
+    if matches.is_present("strict-big-font") {
+        config.settings.strict_big_font = Some(true);
+    }
+    if matches.is_present("strict") {
+        config.settings.strict_machine_code = Some(true);
+        config.settings.strict_operands = Some(true);
+    }
+    if matches.is_present("i-overflow-flag") {
+        config.settings.i_overflow_flag = Some(true);
+    }
+    if matches.is_present("display-wait") {
+        config.settings.display_wait = Some(true);
+    }
+    if matches.is_present("key-release-wait") {
+        config.settings.key_release_wait = Some(true);
+    }
+    if matches.is_present("shift-vy") {
+        config.settings.shift_vy = Some(true);
+    }
+    if matches.is_present("hires-chip8") {
+        config.settings.hires_chip8 = Some(true);
+    }
+    if matches.is_present("xochip") {
+        config.settings.platform = Some(Platform::XoChip);
+    }
+    if matches.is_present("megachip") {
+        config.settings.platform = Some(Platform::MegaChip);
+    }
+    config.save_settings = matches.is_present("save-settings");
+    parse_value(
+        matches,
+        "cycles-per-frame",
+        &mut config.settings.cycles_per_frame,
+        |value| value.parse().ok().filter(|&cycles| cycles > 0).map(Some),
+        "must be a positive integer",
+    )?;
+    parse_value(matches, "on-illegal", &mut config.on_illegal, IllegalOpcodes::parse, "must be halt, skip or warn-once")?;
+    parse_value(matches, "timing", &mut config.settings.timing, |value| Timing::parse(value).map(Some), "must be modern or vip")?;
+    parse_value(matches, "kb-layout", &mut config.kb_layout, |value| KeyboardLayout::parse(value).map(Some), "must be qwerty, azerty, qwertz or colemak")?;
+    parse_value(matches, "cycles", &mut config.cycles, |value| value.parse().ok(), "must be an unsigned integer")?;
+    parse_value(
+        matches,
+        "hash-every",
+        &mut config.hash_every,
+        |value| value.parse().ok().filter(|&every| every > 0).map(Some),
+        "must be a positive integer",
+    )?;
+    config.out = matches.value_of("out").map(PathBuf::from);
+    parse_value(
+        matches,
+        "screenshot-scale",
+        &mut config.screenshot_scale,
+        |value| value.parse().ok().filter(|scale| (1..=64).contains(scale)),
+        "must be between 1 and 64",
+    )?;
+    if let Some(script) = matches.value_of("keys") {
+        config.keys = KeyScript::parse(script).map_err(invalid)?;
+    }
+    parse_value(
+        matches,
+        "rewind-seconds",
+        &mut config.rewind_seconds,
+        |value| value.parse().ok().filter(|&seconds| seconds <= 600),
+        "must be between 0 and 600",
+    )?;
+    if let Some(colors) = matches.value_of("plane-colors") {
+        config.settings.colors = Some(parse_plane_colors(colors).map_err(invalid)?);
+    }
+    config.crt = matches.is_present("crt");
+    config.fullscreen = matches.is_present("fullscreen");
+    config.integer_scale = matches.is_present("integer-scale");
+    config.watch_rom = matches.is_present("watch-rom");
+    config.pause_on_focus_loss = !matches.is_present("no-pause-on-focus-loss");
+    config.console = matches.is_present("console");
+    parse_value(
+        matches,
+        "turbo-multiplier",
+        &mut config.turbo_multiplier,
+        |value| value.parse().ok().filter(|multiplier| (1..=64).contains(multiplier)),
+        "must be between 1 and 64",
+    )?;
+    parse_value(
+        matches,
+        "beep-freq",
+        &mut config.beep.frequency,
+        |value| value.parse().ok().filter(|frequency| (20.0..=20_000.0).contains(frequency)),
+        "must be between 20 and 20000",
+    )?;
+    parse_value(matches, "beep-wave", &mut config.beep.waveform, Waveform::parse, "must be square, sine or triangle")?;
+    parse_value(
+        matches,
+        "volume",
+        &mut config.beep.volume,
+        |value| value.parse().ok().filter(|&volume| volume <= 100),
+        "must be between 0 and 100",
+    )?;
+    parse_value(matches, "visual-buzzer", &mut config.visual_buzzer, VisualBuzzer::parse, "must be border, icon or off")?;
+    parse_value(
+        matches,
+        "phosphor",
+        &mut config.phosphor,
+        |value| value.parse().ok().filter(|decay| (0.0..1.0).contains(decay)),
+        "must be at least 0 and below 1",
+    )?;
+    parse_value(
+        matches,
+        "close-after-exit",
+        &mut config.close_after_exit,
+        |value| value.parse::<f32>().ok().map(|seconds| Some(Duration::from_secs_f32(seconds.max(0.0)))),
+        "must be a number of seconds",
+    )?;
+    if let Some(address) = matches.value_of("load-address") {
+        config.load_address = parse_address(address).map_err(invalid)?;
+    }
+    if matches.is_present("eti660") {
+        config.load_address = ETI_660_LOAD_ADDRESS;
+    }
+    if let Some(path) = matches.value_of("font") {
+        config.font = Some(read_font(Path::new(path)).map_err(|message| command().error(ErrorKind::Io, message))?);
+    }
+    for breakpoint in matches.values_of("break").into_iter().flatten() {
+        match breakpoint.find(" if ") {
+            Some(index) => {
+                let address = parse_address(breakpoint[..index].trim()).map_err(invalid)?;
+                let condition = Condition::parse(&breakpoint[index + 4..]).map_err(|error| invalid(format!("--break {}: {}", breakpoint, error)))?;
+                config.conditional_breakpoints.push((address, condition));
+            }
+            None => config.breakpoints.push(parse_address(breakpoint).map_err(invalid)?),
+        }
+    }
+    for condition in matches.values_of("break-when").into_iter().flatten() {
+        config.break_conditions.push(Condition::parse(condition).map_err(|error| invalid(format!("--break-when {}: {}", condition, error)))?);
+    }
+    for range in matches.values_of("watch").into_iter().flatten() {
+        config.watchpoints.push(parse_watchpoint(range).map_err(invalid)?);
+    }
+    let mut cheats = Cheats::default();
+    for poke in matches.values_of("poke").into_iter().flatten() {
+        cheats.poke.push(Poke::parse(poke).map_err(invalid)?);
+    }
+    for poke in matches.values_of("freeze").into_iter().flatten() {
+        cheats.freeze.push(Poke::parse(poke).map_err(invalid)?);
+    }
+    if !cheats.is_empty() {
+        config.settings.cheats = Some(cheats);
+    }
+    parse_value(
+        matches,
+        "step-limit",
+        &mut config.step_limit,
+        |value| value.parse().ok().filter(|&limit| limit > 0),
+        "must be a positive integer",
+    )?;
+    config.record = matches.value_of("record").map(PathBuf::from);
+    if let Some(path) = matches.value_of("replay") {
+        let recording = Recording::read_from(Path::new(path)).map_err(|error| command().error(ErrorKind::Io, format!("Problem reading {}: {}", path, error)))?;
+        // the recorded quirks and speed win over the command line's and the database's
+        config.settings = config.settings.merged(&recording.settings);
+        config.replay = Some(recording);
+    }
+    // the recording has to know the seed, so choose it here rather than leave it to the Cpu
+    if config.record.is_some() && config.seed.is_none() {
+        config.seed = Some(rand::random());
+    }
+
+    Ok(config)
+}
+
+fn read_font(path: &Path) -> Result<Font, String> {
+    let bytes = fs::read(path).map_err(|error| format!("Problem reading font {}: {}", path.display(), error))?;
+    Font::from_bytes(&bytes).map_err(|error| format!("Problem loading font {}: {}", path.display(), error))
+}
+
+pub(crate) fn parse_address(value: &str) -> Result<u16, String> {
+    let digits = value.trim_start_matches("0x").trim_start_matches("0X");
+    u16::from_str_radix(digits, 16).map_err(|_| format!("{} is not an address between 0x0000 and 0xFFFF", value))
+}
+
+/// Parses `0x300`, `0x300-0x30F` or either followed by `:r`, `:w` or `:rw` (the default).
+fn parse_watchpoint(value: &str) -> Result<Watchpoint, String> {
+    let (range, mode) = match value.rfind(':') {
+        Some(index) => (&value[..index], &value[index + 1..]),
+        None => (value, "rw"),
+    };
+    let mode = match mode {
+        "r" => WatchMode::Read,
+        "w" => WatchMode::Write,
+        "rw" => WatchMode::ReadWrite,
+        _ => return Err(format!("watchpoint mode must be r, w or rw, not {}", mode)),
+    };
+    let (start, end) = match range.find('-') {
+        Some(index) => (parse_address(&range[..index])?, parse_address(&range[index + 1..])?),
+        None => {
+            let address = parse_address(range)?;
+            (address, address)
+        }
+    };
+    if start > end {
+        return Err(format!("watchpoint range {} is empty", range));
+    }
+    Ok(Watchpoint { start, end, mode })
+}
+
+/// Parses `--plane-colors`: four `RRGGBB` values separated by commas.
+fn parse_plane_colors(value: &str) -> Result<[Rgb; 4], String> {
+    let error = || format!("--plane-colors expects four RRGGBB colours separated by commas, got {}", value);
+    let mut colors = [Rgb(0, 0, 0); 4];
+    let parts: Vec<&str> = value.split(',').collect();
+    if parts.len() != colors.len() {
+        return Err(error());
+    }
+    for (color, part) in colors.iter_mut().zip(parts) {
+        *color = Rgb::parse(part).map_err(|_| error())?;
+    }
+    Ok(colors)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(line: &str) -> Config {
+        match parse(line.split_whitespace()) {
+            Ok(Invocation::Run(config)) => *config,
+            other => panic!("{} parsed as {:?}", line, other),
+        }
+    }
+
+    fn error(line: &str) -> ErrorKind {
+        parse(line.split_whitespace()).unwrap_err().kind()
+    }
+
+    #[test]
+    fn no_subcommand_means_run() {
+        assert_eq!(run("chip-8-emulator"), Config::default());
+        assert_eq!(run("chip-8-emulator roms/PONG.ch8"), Config { rom: Some(PathBuf::from("roms/PONG.ch8")), ..Config::default() });
+        assert_eq!(run("chip-8-emulator run roms/PONG.ch8 --crt"), run("chip-8-emulator roms/PONG.ch8 --crt"));
+    }
+
+    #[test]
+    fn run_options_end_up_in_the_config() {
+        let config = run(
+            "chip-8-emulator --xochip --display-wait --key-release-wait --shift-vy --hires-chip8 --cycles-per-frame 30 --timing vip game.ch8 --break 0x2A0 --break 2B0 --step-limit 5000 --watch 0x300-0x30F:w \
+             --plane-colors 000000,FFFFFF,FF0000,00FF00 --phosphor 0.5 --seed 7 --eti660 --no-pause-on-focus-loss --console --turbo-multiplier 4 \
+             --beep-freq 220 --beep-wave triangle --volume 40 --kb-layout AZERTY",
+        );
+
+        assert_eq!(config.rom, Some(PathBuf::from("game.ch8")));
+        assert_eq!(config.settings.platform, Some(Platform::XoChip));
+        assert_eq!(config.settings.display_wait, Some(true));
+        assert_eq!(config.settings.key_release_wait, Some(true));
+        assert_eq!(config.settings.shift_vy, Some(true));
+        assert_eq!(config.settings.hires_chip8, Some(true));
+        assert_eq!(config.settings.cycles_per_frame, Some(30));
+        assert_eq!(config.settings.timing, Some(Timing::Vip));
+        assert_eq!(config.breakpoints, vec![0x2A0, 0x2B0]);
+        assert_eq!(config.step_limit, 5000);
+        assert_eq!(config.watchpoints, vec![Watchpoint { start: 0x300, end: 0x30F, mode: WatchMode::Write }]);
+        assert_eq!(config.settings.colors.unwrap()[2], Rgb(0xFF, 0, 0));
+        assert_eq!(config.phosphor, 0.5);
+        assert_eq!(config.seed, Some(7));
+        assert_eq!(config.load_address, ETI_660_LOAD_ADDRESS);
+        assert!(!config.pause_on_focus_loss);
+        assert!(config.console);
+        assert_eq!(config.turbo_multiplier, 4);
+        assert_eq!(config.beep, Beep { frequency: 220.0, waveform: Waveform::Triangle, volume: 40 });
+        assert_eq!(run("chip-8-emulator game.ch8 --visual-buzzer icon").visual_buzzer, VisualBuzzer::Icon);
+        assert_eq!(config.kb_layout, Some(KeyboardLayout::Azerty));
+        assert_eq!(config.on_illegal, IllegalOpcodes::Halt);
+        assert_eq!(run("chip-8-emulator game.ch8 --on-illegal skip").on_illegal, IllegalOpcodes::Skip);
+        assert_eq!(run("chip-8-emulator game.ch8 --headless --on-illegal Warn-Once").on_illegal, IllegalOpcodes::WarnOnce);
+    }
+
+    #[test]
+    fn the_command_line_overrides_the_base_it_is_parsed_on() {
+        let base = Config { turbo_multiplier: 4, phosphor: 0.5, load_address: 0x600, ..Config::default() };
+
+        let config = match parse_on("chip-8-emulator game.ch8 --turbo-multiplier 2".split_whitespace(), base) {
+            Ok(Invocation::Run(config)) => *config,
+            other => panic!("parsed as {:?}", other),
+        };
+
+        assert_eq!(config.turbo_multiplier, 2);
+        assert_eq!(config.phosphor, 0.5);
+        assert_eq!(config.load_address, 0x600);
+    }
+
+    #[cfg(feature = "frontend-terminal")]
+    #[test]
+    fn terminal_play_needs_a_rom_and_no_headless() {
+        let config = run("chip-8-emulator --terminal --bell pong.ch8");
+
+        assert!(config.terminal && config.bell);
+        assert_eq!(error("chip-8-emulator --terminal"), ErrorKind::MissingRequiredArgument);
+        assert_eq!(error("chip-8-emulator --terminal --headless pong.ch8"), ErrorKind::ArgumentConflict);
+    }
+
+    #[cfg(feature = "scripting")]
+    #[test]
+    fn scripts_run_headless() {
+        let config = run("chip-8-emulator --headless --script watch.rhai pong.ch8");
+
+        assert_eq!(config.script, Some(PathBuf::from("watch.rhai")));
+        assert_eq!(error("chip-8-emulator --script watch.rhai pong.ch8"), ErrorKind::MissingRequiredArgument);
+    }
+
+    #[test]
+    fn conditional_breakpoints_end_up_in_the_config() {
+        let mut arguments: Vec<&str> = "chip-8-emulator game.ch8 --break 0x2A0 --break-when mem[0x300]!=0 --break".split(' ').collect();
+        arguments.push("0x2A4 if V3 == 0x1F");
+        let config = match parse(arguments) {
+            Ok(Invocation::Run(config)) => *config,
+            other => panic!("{:?}", other),
+        };
+
+        assert_eq!(config.breakpoints, vec![0x2A0]);
+        assert_eq!(config.conditional_breakpoints, vec![(0x2A4, Condition::parse("V3==0x1F").unwrap())]);
+        assert_eq!(config.break_conditions, vec![Condition::parse("mem[0x300]!=0").unwrap()]);
+        assert_eq!(error("chip-8-emulator game.ch8 --break-when V3"), ErrorKind::ValueValidation);
+        assert_eq!(error("chip-8-emulator game.ch8 --break-when V3==0x1F --break-when I=1"), ErrorKind::ValueValidation);
+    }
+
+    #[test]
+    fn cheats_end_up_in_the_settings() {
+        let config = run("chip-8-emulator --poke 0x3A0=1 --freeze 0x3A2=0x05 --freeze 3A4=FF game.ch8");
+
+        let cheats = config.settings.cheats.unwrap();
+        assert_eq!(cheats.poke, vec![Poke { address: 0x3A0, value: 1 }]);
+        assert_eq!(cheats.freeze, vec![Poke { address: 0x3A2, value: 5 }, Poke { address: 0x3A4, value: 0xFF }]);
+        assert_eq!(run("chip-8-emulator game.ch8").settings.cheats, None);
+        assert_eq!(error("chip-8-emulator --freeze 0x3A2=0x100 game.ch8"), ErrorKind::ValueValidation);
+        assert_eq!(error("chip-8-emulator --poke 0x3A2 game.ch8"), ErrorKind::ValueValidation);
+        assert_eq!(error("chip-8-emulator --freeze 0x3A2=5 --record run.json game.ch8"), ErrorKind::ArgumentConflict);
+    }
+
+    #[test]
+    fn headless_options_end_up_in_the_config() {
+        let config = run("chip-8-emulator --headless test.ch8 --cycles 500 --keys 5@100,release5@160 --out final.png --hash-every 100");
+
+        assert!(config.headless);
+        assert_eq!(config.cycles, 500);
+        assert_eq!(config.hash_every, Some(100));
+        assert_eq!(config.keys, KeyScript::parse("5@100,release5@160").unwrap());
+        assert_eq!(config.out, Some(PathBuf::from("final.png")));
+        // recording picks a seed when none is given
+        assert!(run("chip-8-emulator game.ch8 --record inputs.json").seed.is_some());
+        assert_eq!(run("chip-8-emulator game.ch8 --coverage out.txt").coverage, Some(PathBuf::from("out.txt")));
+        assert_eq!(config.coverage, None);
+        let states = run("chip-8-emulator game.ch8 --import-state in.json --force --export-state out.json");
+        assert_eq!((states.import_state, states.force), (Some(PathBuf::from("in.json")), true));
+        assert_eq!(states.export_state, Some(PathBuf::from("out.json")));
+        assert_eq!(error("chip-8-emulator game.ch8 --force"), ErrorKind::MissingRequiredArgument);
+        assert!(run("chip-8-emulator game.ch8 --resume").resume);
+        assert_eq!(error("chip-8-emulator game.ch8 --resume --import-state in.json"), ErrorKind::ArgumentConflict);
+    }
+
+    #[test]
+    fn disasm_and_check_take_a_rom() {
+        assert_eq!(
+            parse("chip-8-emulator disasm game.ch8 --load-address 0x600".split_whitespace()).unwrap(),
+            Invocation::Disasm { rom: PathBuf::from("game.ch8"), format: None, load_address: 0x600, source: false, labels: false, sprites: false }
+        );
+        assert_eq!(
+            parse(["chip-8-emulator", "disasm", "--labels", "--sprites", "game.ch8"]).unwrap(),
+            Invocation::Disasm { rom: PathBuf::from("game.ch8"), format: None, load_address: 0x200, source: false, labels: true, sprites: true }
+        );
+        assert_eq!(error("chip-8-emulator disasm --sprites game.ch8"), ErrorKind::MissingRequiredArgument);
+        assert_eq!(error("chip-8-emulator disasm --source --labels game.ch8"), ErrorKind::ArgumentConflict);
+        assert_eq!(
+            parse(["chip-8-emulator", "check", "game.ch8"]).unwrap(),
+            Invocation::Check { rom: PathBuf::from("game.ch8"), format: None, json: false, dead_code: false }
+        );
+        assert_eq!(
+            parse(["chip-8-emulator", "check", "--dead-code", "game.ch8"]).unwrap(),
+            Invocation::Check { rom: PathBuf::from("game.ch8"), format: None, json: false, dead_code: true }
+        );
+        assert_eq!(
+            parse(["chip-8-emulator", "check", "--json", "--format", "hex", "-"]).unwrap(),
+            Invocation::Check { rom: PathBuf::from("-"), format: Some(RomFormat::Hex), json: true, dead_code: false }
+        );
+        assert_eq!(error("chip-8-emulator check --json --dead-code game.ch8"), ErrorKind::ArgumentConflict);
+        assert_eq!(error("chip-8-emulator check"), ErrorKind::MissingRequiredArgument);
+        assert_eq!(error("chip-8-emulator check --format elf game.ch8"), ErrorKind::ValueValidation);
+    }
+
+    #[test]
+    fn compare_takes_two_profiles() {
+        assert_eq!(
+            parse("chip-8-emulator compare --left chip8 --right SCHIP game.ch8".split_whitespace()).unwrap(),
+            Invocation::Compare {
+                rom: PathBuf::from("game.ch8"),
+                format: None,
+                left: String::from("chip8"),
+                right: String::from("schip"),
+                cycles: DEFAULT_COMPARE_CYCLES,
+                keys: KeyScript::default(),
+                seed: 0,
+                to_the_end: false,
+            }
+        );
+        assert_eq!(
+            parse("chip-8-emulator compare --left vip --right modern --cycles 500 --keys 5@10 --seed 3 --continue game.ch8".split_whitespace()).unwrap(),
+            Invocation::Compare {
+                rom: PathBuf::from("game.ch8"),
+                format: None,
+                left: String::from("vip"),
+                right: String::from("modern"),
+                cycles: 500,
+                keys: KeyScript::parse("5@10").unwrap(),
+                seed: 3,
+                to_the_end: true,
+            }
+        );
+        assert_eq!(error("chip-8-emulator compare --left chip8 game.ch8"), ErrorKind::MissingRequiredArgument);
+        assert_eq!(error("chip-8-emulator compare --left chip8 --right eti660 game.ch8"), ErrorKind::ValueValidation);
+        assert_eq!(error("chip-8-emulator compare --left chip8 --right schip --keys 5 game.ch8"), ErrorKind::ValueValidation);
+    }
+
+    #[test]
+    fn builtins_stand_in_for_the_rom() {
+        assert_eq!(run("chip-8-emulator --builtin ibm --headless").rom, Some(PathBuf::from("builtin:ibm")));
+        assert_eq!(parse(["chip-8-emulator", "--list-builtins"]).unwrap(), Invocation::ListBuiltins);
+        assert_eq!(error("chip-8-emulator --builtin pong"), ErrorKind::ValueValidation);
+        assert_eq!(error("chip-8-emulator game.ch8 --builtin ibm"), ErrorKind::ArgumentConflict);
+    }
+
+    #[test]
+    fn asm_needs_an_output() {
+        assert_eq!(
+            parse("chip-8-emulator asm game.s -o game.ch8".split_whitespace()).unwrap(),
+            Invocation::Asm { input: PathBuf::from("game.s"), out: PathBuf::from("game.ch8"), load_address: 0x200 }
+        );
+        assert_eq!(error("chip-8-emulator asm game.s"), ErrorKind::MissingRequiredArgument);
+    }
+
+    #[test]
+    fn dump_takes_a_file() {
+        assert_eq!(
+            parse("chip-8-emulator dump dumps/PONG.dump.json".split_whitespace()).unwrap(),
+            Invocation::Dump { path: PathBuf::from("dumps/PONG.dump.json") }
+        );
+        assert_eq!(error("chip-8-emulator dump"), ErrorKind::MissingRequiredArgument);
+    }
+
+    #[test]
+    fn test_suite_takes_a_manifest() {
+        assert_eq!(
+            parse("chip-8-emulator test-suite roms/manifest.toml".split_whitespace()).unwrap(),
+            Invocation::TestSuite { manifest: PathBuf::from("roms/manifest.toml"), update: false }
+        );
+        assert_eq!(
+            parse("chip-8-emulator test-suite --update manifest.toml".split_whitespace()).unwrap(),
+            Invocation::TestSuite { manifest: PathBuf::from("manifest.toml"), update: true }
+        );
+        assert_eq!(error("chip-8-emulator test-suite"), ErrorKind::MissingRequiredArgument);
+    }
+
+    #[test]
+    fn bad_command_lines_are_rejected() {
+        assert_eq!(error("chip-8-emulator game.ch8 --turbo"), ErrorKind::UnknownArgument);
+        assert_eq!(error("chip-8-emulator --headless --fullscreen game.ch8"), ErrorKind::ArgumentConflict);
+        assert_eq!(error("chip-8-emulator --eti660 --load-address 0x300 game.ch8"), ErrorKind::ArgumentConflict);
+        assert_eq!(error("chip-8-emulator --headless"), ErrorKind::MissingRequiredArgument);
+        assert_eq!(error("chip-8-emulator game.ch8 --cycles 10"), ErrorKind::MissingRequiredArgument);
+        assert_eq!(error("chip-8-emulator game.ch8 --seed soon"), ErrorKind::ValueValidation);
+        assert_eq!(error("chip-8-emulator game.ch8 --phosphor 1.5"), ErrorKind::ValueValidation);
+        assert_eq!(error("chip-8-emulator game.ch8 --turbo-multiplier 0"), ErrorKind::ValueValidation);
+        assert_eq!(error("chip-8-emulator game.ch8 --beep-wave sawtooth"), ErrorKind::ValueValidation);
+        assert_eq!(error("chip-8-emulator game.ch8 --visual-buzzer flash"), ErrorKind::ValueValidation);
+        assert_eq!(error("chip-8-emulator game.ch8 --timing eti660"), ErrorKind::ValueValidation);
+        assert_eq!(error("chip-8-emulator game.ch8 --kb-layout dvorak"), ErrorKind::ValueValidation);
+        assert_eq!(error("chip-8-emulator game.ch8 --volume 101"), ErrorKind::ValueValidation);
+        assert_eq!(error("chip-8-emulator game.ch8 --on-illegal ignore"), ErrorKind::ValueValidation);
+        assert_eq!(error("chip-8-emulator --help"), ErrorKind::DisplayHelp);
+        let message = parse("chip-8-emulator game.ch8 --seed soon".split_whitespace()).unwrap_err().to_string();
+        assert!(message.contains("--seed must be an unsigned integer, not soon"));
+    }
+}
@@ -0,0 +1,283 @@
+use std::fmt;
+
+use crate::cpu::{Cpu, Flow, Halt, CYCLES_PER_FRAME};
+use crate::headless::KeyScript;
+use crate::instruction::disassemble;
+
+/// One way the left and right machines disagree.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Difference {
+    /// V0 to VF, I, PC, or whether FX0A is waiting for a key.
+    Register { name: String, left: u16, right: u16 },
+    /// The first and last addresses that differ, and everything between them.
+    Memory { start: u16, end: u16 },
+    /// How many pixels differ; the resolution or selected planes when none do.
+    Framebuffer { pixels: usize },
+    Stack,
+    /// The delay and sound timers, in that order.
+    Timers { left: [u8; 2], right: [u8; 2] },
+    RplFlags,
+    /// XO-CHIP's audio pattern and pitch.
+    Audio,
+    /// One machine has stopped and the other hasn't, or they stopped for different reasons.
+    Halted { left: Option<Halt>, right: Option<Halt> },
+}
+
+/// The first instruction after which the two machines' states no longer match.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Divergence {
+    /// How many instructions each had run, counting the one that made the difference.
+    pub instruction: u64,
+    /// Where that instruction was, the same on both sides since they agreed until then.
+    pub pc: u16,
+    pub opcode: u16,
+    pub differences: Vec<Difference>,
+}
+
+/// What `compare` found.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Report {
+    /// The instructions each side ran before the comparison ended.
+    pub instructions: u64,
+    /// Whole frames of `CYCLES_PER_FRAME` instructions run.
+    pub frames: u64,
+    /// Of those, how many ended with the two states differing.
+    pub divergent_frames: u64,
+    pub first: Option<Divergence>,
+}
+
+/// Runs `left` and `right` side by side, an instruction at a time, feeding both the same scripted
+/// keys, and reports the first instruction after which their states differ. Set up the two the
+/// same way, quirks apart, and give them the same seed; each ticks its own timers as `headless::run`
+/// does. The comparison stops at that first difference unless `to_the_end` is set, in which case
+/// it goes on for all of `cycles` to count the frames that end differing. It stops early once
+/// either machine halts.
+pub fn compare(left: &mut Cpu, right: &mut Cpu, cycles: u64, script: &KeyScript, to_the_end: bool) -> Report {
+    let mut report = Report { instructions: 0, frames: 0, divergent_frames: 0, first: None };
+    let (mut left_side, mut right_side) = (Side::default(), Side::default());
+    for cycle in 0..cycles {
+        let pc = left.pc();
+        let opcode = left.opcode_at(pc);
+        left_side.step(left, cycle, script);
+        right_side.step(right, cycle, script);
+        report.instructions += 1;
+
+        if report.first.is_none() {
+            let differences = differences(left, right);
+            if !differences.is_empty() {
+                report.first = Some(Divergence { instruction: report.instructions, pc, opcode, differences });
+            }
+        }
+        if report.instructions.is_multiple_of(CYCLES_PER_FRAME as u64) {
+            report.frames += 1;
+            if left.state_hash() != right.state_hash() {
+                report.divergent_frames += 1;
+            }
+        }
+        if left.halted().is_some() || right.halted().is_some() || (report.first.is_some() && !to_the_end) {
+            break;
+        }
+    }
+    report
+}
+
+// where one machine is in its frame and the key script
+#[derive(Default)]
+struct Side {
+    next_event: usize,
+    in_frame: usize,
+}
+
+impl Side {
+    fn step(&mut self, cpu: &mut Cpu, cycle: u64, script: &KeyScript) {
+        script.apply(cycle, &mut self.next_event, cpu.keys_mut());
+        // an error halts the machine, which `halted` reports
+        let flow = cpu.cycle();
+        self.in_frame += 1;
+        if self.in_frame == CYCLES_PER_FRAME || flow == Ok(Flow::FrameBoundary) {
+            cpu.tick_timers();
+            self.in_frame = 0;
+        }
+    }
+}
+
+/// Everything that differs between `left` and `right`, component by component.
+pub fn differences(left: &Cpu, right: &Cpu) -> Vec<Difference> {
+    let (before, after) = (left.state_digest(), right.state_digest());
+    let mut differences = Vec::new();
+    if before.registers != after.registers {
+        let mut registers: Vec<(String, u16, u16)> = left
+            .registers()
+            .iter()
+            .zip(right.registers().iter())
+            .enumerate()
+            .map(|(x, (left, right))| (format!("V{:X}", x), left as u16, right as u16))
+            .collect();
+        registers.push((String::from("I"), left.i(), right.i()));
+        registers.push((String::from("PC"), left.pc(), right.pc()));
+        registers.push((String::from("FX0A wait"), left.is_waiting_for_key() as u16, right.is_waiting_for_key() as u16));
+        differences.extend(
+            registers
+                .into_iter()
+                .filter(|(_, left, right)| left != right)
+                .map(|(name, left, right)| Difference::Register { name, left, right }),
+        );
+        if left.rpl_flags() != right.rpl_flags() {
+            differences.push(Difference::RplFlags);
+        }
+    }
+    if before.memory != after.memory {
+        let differing: Vec<usize> = (0..left.memory().bytes().len().max(right.memory().bytes().len()))
+            .filter(|&address| left.memory().bytes().get(address) != right.memory().bytes().get(address))
+            .collect();
+        if let (Some(&start), Some(&end)) = (differing.first(), differing.last()) {
+            differences.push(Difference::Memory { start: start as u16, end: end as u16 });
+        }
+    }
+    if before.display != after.display {
+        let (display, other) = (left.display(), right.display());
+        let pixels = if display.resolution() == other.resolution() {
+            (0..display.height())
+                .flat_map(|y| (0..display.width()).map(move |x| (x, y)))
+                .filter(|&(x, y)| display.pixel(x, y) != other.pixel(x, y))
+                .count()
+        } else {
+            0
+        };
+        differences.push(Difference::Framebuffer { pixels });
+    }
+    if before.stack != after.stack {
+        differences.push(Difference::Stack);
+    }
+    if before.timers != after.timers {
+        differences.push(Difference::Timers {
+            left: [left.delay_timer(), left.sound_timer()],
+            right: [right.delay_timer(), right.sound_timer()],
+        });
+    }
+    if before.audio != after.audio {
+        differences.push(Difference::Audio);
+    }
+    if left.halted() != right.halted() {
+        differences.push(Difference::Halted { left: left.halted(), right: right.halted() });
+    }
+    differences
+}
+
+fn halt(halt: &Option<Halt>) -> String {
+    match halt {
+        None => String::from("running"),
+        Some(Halt::Exited) => String::from("exited"),
+        Some(Halt::Looped(address)) => format!("looping at 0x{:03X}", address),
+        Some(Halt::Error(error)) => error.to_string(),
+    }
+}
+
+impl fmt::Display for Difference {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Difference::Register { name, left, right } if name.starts_with('V') => {
+                write!(f, "{}: 0x{:02X} on the left, 0x{:02X} on the right", name, left, right)
+            }
+            Difference::Register { name, left, right } => write!(f, "{}: 0x{:03X} on the left, 0x{:03X} on the right", name, left, right),
+            Difference::Memory { start, end } if start == end => write!(f, "memory at 0x{:03X}", start),
+            Difference::Memory { start, end } => write!(f, "memory from 0x{:03X} to 0x{:03X}", start, end),
+            Difference::Framebuffer { pixels: 0 } => write!(f, "the resolution or selected planes"),
+            Difference::Framebuffer { pixels } => write!(f, "the framebuffer: {} pixels", pixels),
+            Difference::Stack => write!(f, "the stack"),
+            Difference::Timers { left, right } => write!(
+                f,
+                "the timers: delay {} and sound {} on the left, delay {} and sound {} on the right",
+                left[0], left[1], right[0], right[1]
+            ),
+            Difference::RplFlags => write!(f, "the RPL flags"),
+            Difference::Audio => write!(f, "the audio pattern or pitch"),
+            Difference::Halted { left, right } => write!(f, "the left is {}, the right {}", halt(left), halt(right)),
+        }
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.first {
+            Some(first) => {
+                writeln!(
+                    f,
+                    "Diverged at instruction {}, 0x{:03X}: {:04X} {}",
+                    first.instruction,
+                    first.pc,
+                    first.opcode,
+                    disassemble(first.opcode)
+                )?;
+                for difference in &first.differences {
+                    writeln!(f, "  {}", difference)?;
+                }
+            }
+            None => writeln!(f, "No divergence in {} instructions", self.instructions)?,
+        }
+        writeln!(f, "{} of {} frames diverged", self.divergent_frames, self.frames)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::display::Display;
+    use crate::memory::Memory;
+    use crate::quirks::Quirks;
+
+    // LD V1, 2; LD V2, 6; SHR V1, V2; LD I, 0x300; LD [I], V1; ADD V0, 1; JP 0x20A
+    const SHIFT: [u8; 14] = [0x61, 0x02, 0x62, 0x06, 0x81, 0x26, 0xA3, 0x00, 0xF1, 0x55, 0x70, 0x01, 0x12, 0x0A];
+
+    fn machine(profile: &str) -> Cpu {
+        let mut cpu = Cpu::new(Memory::new(), Display::new());
+        cpu.set_quirks(Quirks::profile(profile).unwrap());
+        cpu.init(SHIFT.to_vec());
+        cpu.seed(7);
+        cpu
+    }
+
+    #[test]
+    fn the_shift_quirk_is_found_at_the_shift() {
+        let report = compare(&mut machine("chip8"), &mut machine("schip"), 1000, &KeyScript::default(), false);
+
+        let first = report.first.unwrap();
+        assert_eq!((first.instruction, first.pc, first.opcode), (3, 0x204, 0x8126));
+        assert_eq!(first.differences, vec![Difference::Register { name: String::from("V1"), left: 3, right: 1 }]);
+        assert_eq!(report.instructions, 3);
+    }
+
+    #[test]
+    fn going_on_to_the_end_counts_the_divergent_frames() {
+        let report = compare(&mut machine("chip8"), &mut machine("modern"), 100, &KeyScript::default(), true);
+
+        assert_eq!(report.instructions, 100);
+        assert_eq!((report.divergent_frames, report.frames), (10, 10));
+        let mut left = machine("chip8");
+        let mut right = machine("modern");
+        compare(&mut left, &mut right, 5, &KeyScript::default(), true);
+        assert_eq!(differences(&left, &right), vec![
+            Difference::Register { name: String::from("V1"), left: 3, right: 1 },
+            Difference::Memory { start: 0x301, end: 0x301 },
+        ]);
+    }
+
+    #[test]
+    fn profiles_that_agree_on_a_rom_find_nothing() {
+        let report = compare(&mut machine("schip"), &mut machine("modern"), 100, &KeyScript::default(), true);
+
+        assert_eq!(report.first, None);
+        assert_eq!((report.divergent_frames, report.frames), (0, 10));
+        assert_eq!(report.to_string(), "No divergence in 100 instructions\n0 of 10 frames diverged\n");
+    }
+
+    #[test]
+    fn the_report_names_the_instruction_and_the_differences() {
+        let report = compare(&mut machine("chip8"), &mut machine("schip"), 1000, &KeyScript::default(), false);
+
+        assert_eq!(
+            report.to_string(),
+            "Diverged at instruction 3, 0x204: 8126 SHR V1, V2\n  V1: 0x03 on the left, 0x01 on the right\n0 of 0 frames diverged\n"
+        );
+    }
+}
@@ -0,0 +1,357 @@
+use std::fmt;
+
+use crate::cpu::Cpu;
+
+/// Something a condition can look at: a number, a register or a byte of memory, or the sum or
+/// difference of two of those.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Expr {
+    Literal(u32),
+    V(u8),
+    I,
+    Pc,
+    Sp,
+    Delay,
+    Sound,
+    /// The byte at the address; past the end of memory reads as 0.
+    Mem(Box<Expr>),
+    Add(Box<Expr>, Box<Expr>),
+    Sub(Box<Expr>, Box<Expr>),
+}
+
+impl Expr {
+    pub fn evaluate(&self, cpu: &Cpu) -> u32 {
+        match self {
+            Expr::Literal(value) => *value,
+            Expr::V(x) => cpu.registers()[*x] as u32,
+            Expr::I => cpu.i() as u32,
+            Expr::Pc => cpu.pc() as u32,
+            Expr::Sp => cpu.sp() as u32,
+            Expr::Delay => cpu.delay_timer() as u32,
+            Expr::Sound => cpu.sound_timer() as u32,
+            Expr::Mem(address) => {
+                let address = address.evaluate(cpu) as usize;
+                *cpu.memory().bytes().get(address).unwrap_or(&0) as u32
+            }
+            Expr::Add(left, right) => left.evaluate(cpu).wrapping_add(right.evaluate(cpu)),
+            Expr::Sub(left, right) => left.evaluate(cpu).wrapping_sub(right.evaluate(cpu)),
+        }
+    }
+
+    /// `name = value` for every register and byte of memory in the expression, in the order
+    /// they're written.
+    fn values(&self, cpu: &Cpu, values: &mut Vec<String>) {
+        let value = self.evaluate(cpu);
+        match self {
+            Expr::Literal(_) => {}
+            Expr::V(_) | Expr::Sp | Expr::Delay | Expr::Sound => values.push(format!("{} = {:#04X}", self, value)),
+            Expr::I | Expr::Pc => values.push(format!("{} = {:#05X}", self, value)),
+            Expr::Mem(address) => {
+                address.values(cpu, values);
+                values.push(format!("mem[{:#05X}] = {:#04X}", address.evaluate(cpu), value));
+            }
+            Expr::Add(left, right) | Expr::Sub(left, right) => {
+                left.values(cpu, values);
+                right.values(cpu, values);
+            }
+        }
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Expr::Literal(value) => write!(f, "{:#X}", value),
+            Expr::V(x) => write!(f, "V{:X}", x),
+            Expr::I => write!(f, "I"),
+            Expr::Pc => write!(f, "pc"),
+            Expr::Sp => write!(f, "sp"),
+            Expr::Delay => write!(f, "delay"),
+            Expr::Sound => write!(f, "sound"),
+            Expr::Mem(address) => write!(f, "mem[{}]", address),
+            Expr::Add(left, right) => write!(f, "{}+{}", left, right),
+            Expr::Sub(left, right) => write!(f, "{}-{}", left, right),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison {
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+impl Comparison {
+    const ALL: [(&'static str, Comparison); 6] = [
+        ("==", Comparison::Equal),
+        ("!=", Comparison::NotEqual),
+        ("<=", Comparison::LessOrEqual),
+        (">=", Comparison::GreaterOrEqual),
+        ("<", Comparison::Less),
+        (">", Comparison::Greater),
+    ];
+
+    fn holds(self, left: u32, right: u32) -> bool {
+        match self {
+            Comparison::Equal => left == right,
+            Comparison::NotEqual => left != right,
+            Comparison::Less => left < right,
+            Comparison::LessOrEqual => left <= right,
+            Comparison::Greater => left > right,
+            Comparison::GreaterOrEqual => left >= right,
+        }
+    }
+}
+
+impl fmt::Display for Comparison {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let symbol = Comparison::ALL.iter().find(|(_, comparison)| comparison == self).map_or("", |(symbol, _)| symbol);
+        f.write_str(symbol)
+    }
+}
+
+/// A comparison between two expressions, such as `V3==0x1F`, `I>0xE00` or `mem[I+1]!=0`, for
+/// breaking on. Registers are V0 to VF, I, pc, sp, delay and sound; numbers are decimal, or hex
+/// with `0x`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Condition {
+    pub left: Expr,
+    pub comparison: Comparison,
+    pub right: Expr,
+}
+
+impl Condition {
+    pub fn parse(text: &str) -> Result<Condition, String> {
+        let tokens = tokenize(text)?;
+        let mut parser = Parser { tokens: &tokens, position: 0 };
+        let left = parser.expr()?;
+        let comparison = match parser.next() {
+            Some(Token::Compare(comparison)) => comparison,
+            Some(token) => return Err(format!("expected a comparison such as == after {}, found {}", left, token)),
+            None => return Err(format!("expected a comparison such as == after {}", left)),
+        };
+        let right = parser.expr()?;
+        if let Some(token) = parser.next() {
+            return Err(format!("unexpected {} after {}", token, right));
+        }
+        Ok(Condition { left, comparison, right })
+    }
+
+    pub fn holds(&self, cpu: &Cpu) -> bool {
+        self.comparison.holds(self.left.evaluate(cpu), self.right.evaluate(cpu))
+    }
+
+    /// The registers and memory the condition looks at, e.g. `V3 = 0x1F, mem[0x300] = 0x00`.
+    pub fn values(&self, cpu: &Cpu) -> String {
+        let mut values = Vec::new();
+        self.left.values(cpu, &mut values);
+        self.right.values(cpu, &mut values);
+        values.join(", ")
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}{}", self.left, self.comparison, self.right)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Number(u32),
+    Name(String),
+    Compare(Comparison),
+    Plus,
+    Minus,
+    Open,
+    Close,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Number(value) => write!(f, "{}", value),
+            Token::Name(name) => write!(f, "{}", name),
+            Token::Compare(comparison) => write!(f, "{}", comparison),
+            Token::Plus => write!(f, "+"),
+            Token::Minus => write!(f, "-"),
+            Token::Open => write!(f, "["),
+            Token::Close => write!(f, "]"),
+        }
+    }
+}
+
+fn tokenize(text: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut rest = text.trim_start();
+    while let Some(character) = rest.chars().next() {
+        let length = if let Some((symbol, comparison)) = Comparison::ALL.iter().find(|(symbol, _)| rest.starts_with(symbol)) {
+            tokens.push(Token::Compare(*comparison));
+            symbol.len()
+        } else if character.is_ascii_alphanumeric() {
+            let word = &rest[..rest.find(|c: char| !c.is_ascii_alphanumeric()).unwrap_or(rest.len())];
+            tokens.push(if character.is_ascii_digit() { Token::Number(number(word)?) } else { Token::Name(word.to_ascii_lowercase()) });
+            word.len()
+        } else {
+            tokens.push(match character {
+                '+' => Token::Plus,
+                '-' => Token::Minus,
+                '[' => Token::Open,
+                ']' => Token::Close,
+                _ => return Err(format!("unexpected {:?} in {}", character, text.trim())),
+            });
+            1
+        };
+        rest = rest[length..].trim_start();
+    }
+    Ok(tokens)
+}
+
+fn number(word: &str) -> Result<u32, String> {
+    let value = match word.strip_prefix("0x").or_else(|| word.strip_prefix("0X")) {
+        Some(digits) => u32::from_str_radix(digits, 16),
+        None => word.parse(),
+    };
+    value.map_err(|_| format!("{} is not a decimal or 0x-prefixed hex number", word))
+}
+
+struct Parser<'a> {
+    tokens: &'a [Token],
+    position: usize,
+}
+
+impl Parser<'_> {
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    // terms joined by + and -, left to right
+    fn expr(&mut self) -> Result<Expr, String> {
+        let mut expr = self.term()?;
+        loop {
+            expr = match self.peek() {
+                Some(Token::Plus) => {
+                    self.position += 1;
+                    Expr::Add(Box::new(expr), Box::new(self.term()?))
+                }
+                Some(Token::Minus) => {
+                    self.position += 1;
+                    Expr::Sub(Box::new(expr), Box::new(self.term()?))
+                }
+                _ => return Ok(expr),
+            };
+        }
+    }
+
+    fn term(&mut self) -> Result<Expr, String> {
+        let name = match self.next() {
+            Some(Token::Number(value)) => return Ok(Expr::Literal(value)),
+            Some(Token::Name(name)) => name,
+            Some(token) => return Err(format!("expected a register, number or mem[...], found {}", token)),
+            None => return Err(String::from("expected a register, number or mem[...] at the end")),
+        };
+        match name.as_str() {
+            "i" => Ok(Expr::I),
+            "pc" => Ok(Expr::Pc),
+            "sp" => Ok(Expr::Sp),
+            "delay" | "dt" => Ok(Expr::Delay),
+            "sound" | "st" => Ok(Expr::Sound),
+            "mem" => {
+                if self.next() != Some(Token::Open) {
+                    return Err(String::from("expected [ after mem"));
+                }
+                let address = self.expr()?;
+                if self.next() != Some(Token::Close) {
+                    return Err(format!("expected ] after mem[{}", address));
+                }
+                Ok(Expr::Mem(Box::new(address)))
+            }
+            _ => match name.strip_prefix('v').filter(|digit| digit.len() == 1).and_then(|digit| u8::from_str_radix(digit, 16).ok()) {
+                Some(x) => Ok(Expr::V(x)),
+                None => Err(format!("{} is not a register; use V0 to VF, I, pc, sp, delay or sound", name)),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::display::Display;
+    use crate::memory::Memory;
+
+    fn cpu() -> Cpu {
+        // LD V3, 0x1F; LD I, 0x300; LD [I], V3 (stores V0 to V3)
+        let mut cpu = Cpu::new(Memory::new(), Display::new());
+        cpu.init(vec![0x63, 0x1F, 0xA3, 0x00, 0xF3, 0x55]);
+        for _ in 0..3 {
+            cpu.cycle().unwrap();
+        }
+        cpu
+    }
+
+    fn holds(text: &str) -> bool {
+        Condition::parse(text).unwrap().holds(&cpu())
+    }
+
+    #[test]
+    fn parse_conditions() {
+        let condition = Condition::parse("v3 == 0x1F").unwrap();
+        assert_eq!(condition, Condition { left: Expr::V(3), comparison: Comparison::Equal, right: Expr::Literal(0x1F) });
+        assert_eq!(condition.to_string(), "V3==0x1F");
+
+        let condition = Condition::parse("mem[I + 3]!=0").unwrap();
+        assert_eq!(condition.left, Expr::Mem(Box::new(Expr::Add(Box::new(Expr::I), Box::new(Expr::Literal(3))))));
+        assert_eq!(condition.to_string(), "mem[I+0x3]!=0x0");
+
+        assert_eq!(Condition::parse("pc>=512").unwrap().right, Expr::Literal(512));
+        assert_eq!(Condition::parse("delay<sound-1").unwrap().to_string(), "delay<sound-0x1");
+        assert_eq!(Condition::parse("sp<=2").unwrap().comparison, Comparison::LessOrEqual);
+    }
+
+    #[test]
+    fn parse_errors_say_what_was_expected() {
+        assert_eq!(Condition::parse("V3").unwrap_err(), "expected a comparison such as == after V3");
+        assert_eq!(Condition::parse("V3 = 1").unwrap_err(), "unexpected '=' in V3 = 1");
+        assert_eq!(Condition::parse("VG==1").unwrap_err(), "vg is not a register; use V0 to VF, I, pc, sp, delay or sound");
+        assert_eq!(Condition::parse("V10==1").unwrap_err(), "v10 is not a register; use V0 to VF, I, pc, sp, delay or sound");
+        assert_eq!(Condition::parse("I>0xZZ").unwrap_err(), "0xZZ is not a decimal or 0x-prefixed hex number");
+        assert_eq!(Condition::parse("mem[0x300==1").unwrap_err(), "expected ] after mem[0x300");
+        assert_eq!(Condition::parse("mem 0x300==1").unwrap_err(), "expected [ after mem");
+        assert_eq!(Condition::parse("V1==2 V3").unwrap_err(), "unexpected v3 after 0x2");
+        assert_eq!(Condition::parse("==2").unwrap_err(), "expected a register, number or mem[...], found ==");
+        assert_eq!(Condition::parse("V1==").unwrap_err(), "expected a register, number or mem[...] at the end");
+    }
+
+    #[test]
+    fn conditions_look_at_the_machine() {
+        assert!(holds("V3==0x1F"));
+        assert!(holds("V3==31"));
+        assert!(!holds("V3!=0x1F"));
+        assert!(holds("I>0x2FF") && holds("I>=0x300") && !holds("I>0x300"));
+        assert!(holds("pc==0x206"));
+        assert!(holds("mem[0x303]==V3"));
+        assert!(holds("mem[I+3]-1==0x1E"));
+        assert!(holds("mem[0xFFFFF]==0"));
+        assert!(holds("sp==0") && holds("delay<1") && holds("sound<=0"));
+    }
+
+    #[test]
+    fn values_name_what_the_condition_looked_at() {
+        let cpu = cpu();
+
+        assert_eq!(Condition::parse("V3==0x1F").unwrap().values(&cpu), "V3 = 0x1F");
+        assert_eq!(Condition::parse("mem[I+3]!=V0").unwrap().values(&cpu), "I = 0x300, mem[0x303] = 0x1F, V0 = 0x00");
+        assert_eq!(Condition::parse("1==1").unwrap().values(&cpu), "");
+    }
+}
@@ -0,0 +1,286 @@
+use std::error::Error;
+use std::fmt;
+
+use serde::Deserialize;
+
+use crate::cli::{self, Config};
+use crate::keymap::{KeyboardLayout, Keymap, KeymapError};
+use crate::settings::RomSettings;
+
+/// What `chip8.toml` holds besides the keymap. All of it is optional, and the command line and
+/// `roms.toml` override it:
+///
+/// ```toml
+/// palette = "amber"
+/// cycles_per_frame = 20
+/// turbo_multiplier = 4
+/// phosphor = 0.5
+/// load_address = "0x600"
+///
+/// [quirks]
+/// platform = "superchip"
+/// display_wait = true
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FileSettings {
+    /// The name of a built-in palette or one in `palettes.toml`.
+    pub palette: Option<String>,
+    pub cycles_per_frame: Option<usize>,
+    pub turbo_multiplier: Option<u32>,
+    pub phosphor: Option<f32>,
+    pub load_address: Option<u16>,
+    /// The quirk settings `roms.toml` takes; anything else in `[quirks]` is ignored.
+    pub quirks: RomSettings,
+}
+
+#[derive(Deserialize)]
+struct RawSettings {
+    palette: Option<String>,
+    cycles_per_frame: Option<usize>,
+    turbo_multiplier: Option<u32>,
+    phosphor: Option<f32>,
+    load_address: Option<String>,
+    quirks: Option<RomSettings>,
+}
+
+impl FileSettings {
+    /// The settings in a `chip8.toml` config file, checked the way the command line checks them.
+    pub fn parse(text: &str) -> Result<FileSettings, ConfigError> {
+        let raw: RawSettings = toml::from_str(text).map_err(|error| ConfigError::Toml(error.to_string()))?;
+        if raw.cycles_per_frame == Some(0) {
+            return Err(ConfigError::invalid("cycles_per_frame", 0, "a positive integer"));
+        }
+        if let Some(multiplier) = raw.turbo_multiplier.filter(|multiplier| !(1..=64).contains(multiplier)) {
+            return Err(ConfigError::invalid("turbo_multiplier", multiplier, "between 1 and 64"));
+        }
+        if let Some(decay) = raw.phosphor.filter(|decay| !(0.0..1.0).contains(decay)) {
+            return Err(ConfigError::invalid("phosphor", decay, "at least 0 and below 1"));
+        }
+        let load_address = match &raw.load_address {
+            Some(address) => Some(cli::parse_address(address).map_err(|_| ConfigError::invalid("load_address", address, "an address between 0x0000 and 0xFFFF"))?),
+            None => None,
+        };
+        let quirks = raw.quirks.unwrap_or_default();
+        Ok(FileSettings {
+            palette: raw.palette,
+            cycles_per_frame: raw.cycles_per_frame,
+            turbo_multiplier: raw.turbo_multiplier,
+            phosphor: raw.phosphor,
+            load_address,
+            quirks: RomSettings { name: None, cycles_per_frame: None, timing: None, colors: None, keymap: None, ..quirks },
+        })
+    }
+
+    /// The quirks and speed, as the layer of ROM settings that everything else goes on top of.
+    pub fn rom_settings(&self) -> RomSettings {
+        RomSettings { cycles_per_frame: self.cycles_per_frame, ..self.quirks.clone() }
+    }
+
+    /// Puts the settings that have a command line option into `config`, so that parsing the
+    /// command line on top of it overrides them.
+    pub fn apply_to(&self, config: &mut Config) {
+        if let Some(multiplier) = self.turbo_multiplier {
+            config.turbo_multiplier = multiplier;
+        }
+        if let Some(decay) = self.phosphor {
+            config.phosphor = decay;
+        }
+        if let Some(address) = self.load_address {
+            config.load_address = address;
+        }
+    }
+}
+
+/// Everything in `chip8.toml`, as the emulator last read it.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ConfigFile {
+    pub settings: FileSettings,
+    pub keymap: Keymap,
+}
+
+impl ConfigFile {
+    /// Parses the whole of a `chip8.toml`, keymap on top of `layout`'s preset if there is one; an
+    /// empty file gives the defaults.
+    pub fn parse(text: &str, layout: Option<KeyboardLayout>) -> Result<ConfigFile, ConfigError> {
+        let settings = FileSettings::parse(text)?;
+        let keymap = match layout {
+            Some(layout) => Keymap::from_config_on(text, layout),
+            None => Keymap::from_config(text),
+        };
+        Ok(ConfigFile { settings, keymap: keymap.map_err(ConfigError::Keymap)? })
+    }
+
+    /// What's different in `newer`, in the order of `Change`.
+    pub fn changes(&self, newer: &ConfigFile) -> Vec<Change> {
+        let (old, new) = (&self.settings, &newer.settings);
+        let differs = [
+            (Change::Palette, old.palette != new.palette),
+            (Change::Speed, old.cycles_per_frame != new.cycles_per_frame),
+            (Change::Keymap, self.keymap != newer.keymap),
+            (Change::TurboMultiplier, old.turbo_multiplier != new.turbo_multiplier),
+            (Change::Phosphor, old.phosphor != new.phosphor),
+            (Change::Quirks, old.quirks != new.quirks),
+            (Change::LoadAddress, old.load_address != new.load_address),
+        ];
+        differs.iter().filter(|(_, differs)| *differs).map(|&(change, _)| change).collect()
+    }
+}
+
+/// One thing an edit to `chip8.toml` can change.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Change {
+    Palette,
+    Speed,
+    Keymap,
+    TurboMultiplier,
+    Phosphor,
+    Quirks,
+    LoadAddress,
+}
+
+impl Change {
+    /// Whether the change can be made to the ROM that's running. Quirks and the load address
+    /// only make sense from the start, so they wait for the next reset.
+    pub fn is_live(self) -> bool {
+        !matches!(self, Change::Quirks | Change::LoadAddress)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Change::Palette => "palette",
+            Change::Speed => "speed",
+            Change::Keymap => "keymap",
+            Change::TurboMultiplier => "turbo multiplier",
+            Change::Phosphor => "phosphor",
+            Change::Quirks => "quirks",
+            Change::LoadAddress => "load address",
+        }
+    }
+}
+
+/// The note to show when a reload makes `changes`, which says what waits for a reset.
+pub fn describe(changes: &[Change]) -> String {
+    let names = |live: bool| changes.iter().filter(|change| change.is_live() == live).map(|change| change.name()).collect::<Vec<_>>().join(", ");
+    let (live, deferred) = (names(true), names(false));
+    match (live.is_empty(), deferred.is_empty()) {
+        (true, true) => String::from("Config reloaded, nothing changed"),
+        (false, true) => format!("Config reloaded: {}", live),
+        (true, false) => format!("Config reloaded; {} apply on the next reset", deferred),
+        (false, false) => format!("Config reloaded: {}; {} apply on the next reset", live, deferred),
+    }
+}
+
+/// Why a `chip8.toml` was rejected.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConfigError {
+    /// The file isn't valid TOML, or a value has the wrong type.
+    Toml(String),
+    Invalid { key: &'static str, value: String, expected: &'static str },
+    Keymap(KeymapError),
+}
+
+impl ConfigError {
+    fn invalid(key: &'static str, value: impl fmt::Display, expected: &'static str) -> ConfigError {
+        ConfigError::Invalid { key, value: value.to_string(), expected }
+    }
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Toml(error) => write!(f, "{}", error),
+            ConfigError::Invalid { key, value, expected } => write!(f, "{} = {}: expected {}", key, value, expected),
+            ConfigError::Keymap(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl Error for ConfigError {}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::PathBuf;
+    use std::time::Instant;
+
+    use super::*;
+    use crate::romwatch::{RomWatcher, POLL_INTERVAL};
+    use crate::Platform;
+
+    const CONFIG: &str = "palette = \"amber\"\ncycles_per_frame = 20\nload_address = \"0x600\"\n\n[quirks]\nplatform = \"xochip\"\n\n[keymap]\nSpace = \"5\"\n";
+
+    fn temp_config(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("chip-8-emulator-{}-{}.toml", name, std::process::id()))
+    }
+
+    #[test]
+    fn settings_are_read_and_checked() {
+        let file = ConfigFile::parse(CONFIG, None).unwrap();
+
+        assert_eq!(file.settings.palette.as_deref(), Some("amber"));
+        assert_eq!(file.settings.load_address, Some(0x600));
+        assert_eq!(file.settings.rom_settings().cycles_per_frame, Some(20));
+        assert_eq!(file.settings.rom_settings().quirks().platform, Platform::XoChip);
+        assert_eq!(file.keymap.key("Space"), Some(0x5));
+        assert_eq!(ConfigFile::parse("", None).unwrap(), ConfigFile::default());
+
+        let mut config = Config::default();
+        FileSettings::parse("turbo_multiplier = 4\nphosphor = 0.5\n").unwrap().apply_to(&mut config);
+        assert_eq!((config.turbo_multiplier, config.phosphor), (4, 0.5));
+
+        assert_eq!(
+            FileSettings::parse("turbo_multiplier = 65\n"),
+            Err(ConfigError::Invalid { key: "turbo_multiplier", value: String::from("65"), expected: "between 1 and 64" })
+        );
+        assert!(FileSettings::parse("phosphor = 1.0\n").is_err());
+        assert!(FileSettings::parse("cycles_per_frame = 0\n").is_err());
+        assert!(FileSettings::parse("load_address = \"0xG00\"\n").is_err());
+    }
+
+    #[test]
+    fn quirks_and_the_load_address_wait_for_a_reset() {
+        let old = ConfigFile::parse(CONFIG, None).unwrap();
+        let new = ConfigFile::parse(
+            "palette = \"green\"\ncycles_per_frame = 20\nphosphor = 0.3\nload_address = \"0x200\"\n\n[quirks]\nplatform = \"superchip\"\n\n[keymap]\nSpace = \"6\"\n",
+            None,
+        )
+        .unwrap();
+
+        let changes = old.changes(&new);
+
+        assert_eq!(changes, vec![Change::Palette, Change::Keymap, Change::Phosphor, Change::Quirks, Change::LoadAddress]);
+        let live: Vec<Change> = changes.iter().copied().filter(|change| change.is_live()).collect();
+        assert_eq!(live, vec![Change::Palette, Change::Keymap, Change::Phosphor]);
+        assert_eq!(describe(&changes), "Config reloaded: palette, keymap, phosphor; quirks, load address apply on the next reset");
+        assert_eq!(describe(&[Change::Speed, Change::TurboMultiplier]), "Config reloaded: speed, turbo multiplier");
+        assert_eq!(describe(&[Change::Quirks]), "Config reloaded; quirks apply on the next reset");
+        assert!(old.changes(&old.clone()).is_empty());
+    }
+
+    #[test]
+    fn a_broken_rewrite_is_rejected_whole() {
+        let path = temp_config("reload");
+        fs::write(&path, CONFIG).unwrap();
+        let start = Instant::now();
+        let mut watcher = RomWatcher::new(&path, start);
+        let current = ConfigFile::parse(&fs::read_to_string(&path).unwrap(), None).unwrap();
+
+        // the speed and palette are fine, but the keymap isn't, so none of it is taken
+        fs::write(&path, "palette = \"green\"\ncycles_per_frame = 30\n\n[keymap]\nSpace = \"X\"\n").unwrap();
+        let text = String::from_utf8(watcher.poll(start + POLL_INTERVAL).unwrap()).unwrap();
+        let reloaded = ConfigFile::parse(&text, None);
+
+        assert!(matches!(reloaded, Err(ConfigError::Keymap(KeymapError::InvalidDigit { .. }))));
+        assert_eq!(current.settings.cycles_per_frame, Some(20));
+
+        fs::write(&path, "cycles_per_frame = 30\nphosphor = \"bright\"\n").unwrap();
+        let text = String::from_utf8(watcher.poll(start + POLL_INTERVAL * 2).unwrap()).unwrap();
+        assert!(matches!(ConfigFile::parse(&text, None), Err(ConfigError::Toml(_))));
+
+        fs::write(&path, "cycles_per_frame = 30\n").unwrap();
+        let text = String::from_utf8(watcher.poll(start + POLL_INTERVAL * 3).unwrap()).unwrap();
+        let reloaded = ConfigFile::parse(&text, None).unwrap();
+        assert_eq!(current.changes(&reloaded), vec![Change::Palette, Change::Speed, Change::Keymap, Change::Quirks, Change::LoadAddress]);
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,267 @@
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::SystemTime;
+
+use serde::{Deserialize, Serialize};
+
+use crate::cpu::{Cpu, Halt};
+use crate::history::HistoryEntry;
+use crate::instruction::disassemble;
+use crate::screenshot;
+
+/// Where the window and the other frontends write core dumps, relative to the working directory.
+pub const DUMP_DIRECTORY: &str = "dumps";
+
+/// The whole machine at the moment a ROM crashed, for working out why offline. Stored as JSON:
+///
+/// ```json
+/// {
+///   "rom": "PONG",
+///   "error": "stack overflow at 0x2A4",
+///   "instructions_executed": 48213,
+///   "pc": 676, "i": 938, "sp": 16,
+///   "registers": [0, 12, ...],
+///   "stack": [514, 676, ...],
+///   "delay_timer": 0, "sound_timer": 3,
+///   "history": [{ "pc": 674, "opcode": 8804 }, ...],
+///   "display": ["....##..", ...],
+///   "memory": "F090909..."
+/// }
+/// ```
+///
+/// `error` is null for a dump taken on request. `registers` runs V0 to VF, and `stack` holds the
+/// return addresses in use, oldest first, as `history` holds the last instructions executed.
+/// `display` has a string per row with a character per pixel: `.` off, `#` plane 1, `+` plane 2
+/// and `@` both. `memory` is every byte as two hex digits, from address 0.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct CoreDump {
+    pub rom: String,
+    pub error: Option<String>,
+    pub instructions_executed: u64,
+    pub pc: u16,
+    pub i: u16,
+    pub sp: u8,
+    pub registers: [u8; 16],
+    pub stack: Vec<u16>,
+    pub delay_timer: u8,
+    pub sound_timer: u8,
+    pub history: Vec<HistoryEntry>,
+    pub display: Vec<String>,
+    pub memory: String,
+}
+
+#[derive(Debug)]
+pub enum CoreDumpError {
+    Io(io::Error),
+    Invalid(String),
+}
+
+impl fmt::Display for CoreDumpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CoreDumpError::Io(error) => write!(f, "could not access core dump: {}", error),
+            CoreDumpError::Invalid(reason) => write!(f, "not a valid core dump: {}", reason),
+        }
+    }
+}
+
+impl Error for CoreDumpError {}
+
+impl From<io::Error> for CoreDumpError {
+    fn from(error: io::Error) -> Self {
+        CoreDumpError::Io(error)
+    }
+}
+
+impl CoreDump {
+    /// Everything about `cpu` as it stands, with the error it halted on if it did.
+    pub fn capture(cpu: &Cpu, rom: &str) -> CoreDump {
+        let mut registers = [0; 16];
+        registers.copy_from_slice(cpu.registers().as_slice());
+        let error = match cpu.halted() {
+            Some(Halt::Error(error)) => Some(error.to_string()),
+            _ => None,
+        };
+        CoreDump {
+            rom: String::from(rom),
+            error,
+            instructions_executed: cpu.instructions_executed(),
+            pc: cpu.pc(),
+            i: cpu.i(),
+            sp: cpu.sp(),
+            registers,
+            stack: cpu.stack().to_vec(),
+            delay_timer: cpu.delay_timer(),
+            sound_timer: cpu.sound_timer(),
+            history: cpu.history().iter().copied().collect(),
+            display: cpu.display().to_ascii().lines().map(String::from).collect(),
+            memory: cpu.memory().bytes().iter().map(|byte| format!("{:02X}", byte)).collect(),
+        }
+    }
+
+    pub fn parse(text: &str) -> Result<CoreDump, CoreDumpError> {
+        let dump: CoreDump = serde_json::from_str(text).map_err(|error| CoreDumpError::Invalid(error.to_string()))?;
+        dump.memory_bytes()?;
+        Ok(dump)
+    }
+
+    pub fn to_json(&self) -> Result<String, CoreDumpError> {
+        serde_json::to_string_pretty(self).map_err(|error| CoreDumpError::Invalid(error.to_string()))
+    }
+
+    /// Writes the dump, creating the directory it goes in if need be. Fails rather than
+    /// panicking whatever goes wrong, as it runs when things already have.
+    pub fn write_to(&self, path: &Path) -> Result<(), CoreDumpError> {
+        if let Some(directory) = path.parent() {
+            fs::create_dir_all(directory)?;
+        }
+        fs::write(path, self.to_json()?)?;
+        Ok(())
+    }
+
+    pub fn read_from(path: &Path) -> Result<CoreDump, CoreDumpError> {
+        CoreDump::parse(&fs::read_to_string(path)?)
+    }
+
+    /// `memory` decoded back into bytes.
+    pub fn memory_bytes(&self) -> Result<Vec<u8>, CoreDumpError> {
+        if !self.memory.len().is_multiple_of(2) {
+            return Err(CoreDumpError::Invalid(String::from("memory has an odd number of hex digits")));
+        }
+        (0..self.memory.len())
+            .step_by(2)
+            .map(|at| {
+                let digits = self.memory.get(at..at + 2).unwrap_or_default();
+                u8::from_str_radix(digits, 16).map_err(|_| CoreDumpError::Invalid(format!("memory at {:#05X} is not hex: {}", at / 2, digits)))
+            })
+            .collect()
+    }
+}
+
+/// The pretty-printed form `dump` shows: the error, registers, stack and history, the display,
+/// and memory 16 bytes to a line, with runs of zero lines left out.
+impl fmt::Display for CoreDump {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.error {
+            Some(error) => writeln!(f, "{} halted after {} instructions: {}", self.rom, self.instructions_executed, error)?,
+            None => writeln!(f, "{} after {} instructions", self.rom, self.instructions_executed)?,
+        }
+        let memory = self.memory_bytes().unwrap_or_default();
+        let byte = |at: u16| u16::from(*memory.get(at as usize).unwrap_or(&0));
+        let opcode = byte(self.pc) << 8 | byte(self.pc.wrapping_add(1));
+        writeln!(f, "{:#05X}: {:04X}  {}", self.pc, opcode, disassemble(opcode))?;
+        for (register, value) in self.registers.iter().enumerate() {
+            let separator = if register % 8 == 7 { '\n' } else { ' ' };
+            write!(f, "V{:X}={:02X}{}", register, value, separator)?;
+        }
+        writeln!(f, "I={:#05X} SP={} DT={:02X} ST={:02X}", self.i, self.sp, self.delay_timer, self.sound_timer)?;
+        let stack: Vec<String> = self.stack.iter().map(|address| format!("{:#05X}", address)).collect();
+        writeln!(f, "Stack: {}", if stack.is_empty() { String::from("empty") } else { stack.join(" ") })?;
+
+        writeln!(f, "\nLast {} instructions:", self.history.len())?;
+        for entry in &self.history {
+            writeln!(f, "  {}", entry)?;
+        }
+
+        writeln!(f, "\nDisplay:")?;
+        for row in &self.display {
+            writeln!(f, "{}", row)?;
+        }
+
+        writeln!(f, "\nMemory:")?;
+        let mut skipping = false;
+        for (line, bytes) in memory.chunks(16).enumerate() {
+            if bytes.iter().all(|&byte| byte == 0) {
+                if !skipping {
+                    writeln!(f, "*")?;
+                }
+                skipping = true;
+                continue;
+            }
+            skipping = false;
+            let hex: Vec<String> = bytes.iter().map(|byte| format!("{:02X}", byte)).collect();
+            writeln!(f, "{:#06X}: {}", line * 16, hex.join(" "))?;
+        }
+        Ok(())
+    }
+}
+
+/// The file name for a dump of `rom_name` taken at `time`, e.g. `PONG-2024-05-01T12-33-07.dump.json`.
+pub fn file_name(rom_name: &str, time: SystemTime) -> String {
+    screenshot::timestamped_name(rom_name, time, "dump.json")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::display::Display;
+    use crate::error::Chip8Error;
+    use crate::memory::Memory;
+
+    /// Calls a subroutine, draws a digit and then returns once too often.
+    fn crashed() -> Cpu {
+        // CALL 0x206; LD V1, 0x07; RET; LD F, V1; DRW V0, V0, 5; RET
+        let rom = vec![0x22, 0x06, 0x61, 0x07, 0x00, 0xEE, 0xF1, 0x29, 0xD0, 0x05, 0x00, 0xEE];
+        let mut cpu = Cpu::new(Memory::new(), Display::new());
+        cpu.init(rom);
+        let mut result = Ok(());
+        for _ in 0..10 {
+            result = cpu.cycle().map(|_| ());
+            if result.is_err() {
+                break;
+            }
+        }
+        assert_eq!(result, Err(Chip8Error::StackUnderflow { pc: 0x204 }));
+        cpu
+    }
+
+    #[test]
+    fn a_dump_of_a_crash_survives_a_round_trip() {
+        let cpu = crashed();
+        let dump = CoreDump::capture(&cpu, "CRASH");
+
+        assert_eq!(dump.error, Some(Chip8Error::StackUnderflow { pc: 0x204 }.to_string()));
+        assert_eq!((dump.pc, dump.sp, dump.registers[1]), (0x206, 0, 0x07));
+        assert_eq!(dump.history.last(), Some(&HistoryEntry { pc: 0x204, opcode: 0x00EE, edit: None }));
+        assert_eq!(dump.display[0], format!("{}{}", "####", ".".repeat(60)));
+        assert_eq!(dump.memory_bytes().unwrap(), cpu.memory().bytes());
+
+        let parsed = CoreDump::parse(&dump.to_json().unwrap()).unwrap();
+        assert_eq!(parsed, dump);
+    }
+
+    #[test]
+    fn the_pretty_print_has_the_error_registers_history_and_memory() {
+        let text = CoreDump::capture(&crashed(), "CRASH").to_string();
+
+        assert!(text.starts_with(&format!("CRASH halted after 6 instructions: {}\n", Chip8Error::StackUnderflow { pc: 0x204 })));
+        assert!(text.contains("V0=00 V1=07 "));
+        assert!(text.contains("Stack: empty"));
+        assert!(text.contains("  0x204: 00EE  RET\n"));
+        assert!(text.contains("0x0200: 22 06 61 07 00 EE F1 29 D0 05 00 EE 00 00 00 00\n*\n"));
+    }
+
+    #[test]
+    fn writing_and_reading_a_dump_file() {
+        let path = std::env::temp_dir().join(format!("chip-8-emulator-dump-{}", std::process::id())).join("crash.dump.json");
+        let dump = CoreDump::capture(&crashed(), "CRASH");
+
+        dump.write_to(&path).unwrap();
+        let read = CoreDump::read_from(&path);
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+
+        assert_eq!(read.unwrap(), dump);
+    }
+
+    #[test]
+    fn bad_dumps_are_rejected() {
+        let mut dump = CoreDump::capture(&crashed(), "CRASH");
+        dump.memory = String::from("00G1");
+
+        assert!(matches!(CoreDump::parse(&dump.to_json().unwrap()), Err(CoreDumpError::Invalid(_))));
+        assert!(matches!(CoreDump::parse("{}"), Err(CoreDumpError::Invalid(_))));
+    }
+}
@@ -0,0 +1,186 @@
+use core::fmt;
+
+use crate::prelude::*;
+
+use crate::instruction;
+
+// enough for XO-CHIP's 64 KiB
+const ADDRESSES: usize = 0x10000;
+const WORDS: usize = ADDRESSES / 64;
+
+/// Which addresses instructions were fetched from, one bit each, and the ROM they were loaded
+/// from so the report can point into it. Recording is a single bit set per instruction.
+#[derive(Clone)]
+pub struct Coverage {
+    bits: Box<[u64; WORDS]>,
+    rom: Vec<u8>,
+    load_address: u16,
+}
+
+impl Coverage {
+    /// Coverage of `rom` as loaded at `load_address`, with nothing executed yet.
+    pub fn new(rom: &[u8], load_address: u16) -> Coverage {
+        Coverage { bits: Box::new([0; WORDS]), rom: rom.to_vec(), load_address }
+    }
+
+    #[inline]
+    pub fn record(&mut self, pc: u16) {
+        self.bits[pc as usize / 64] |= 1 << (pc % 64);
+    }
+
+    /// Whether an instruction was fetched from `address`.
+    pub fn contains(&self, address: u16) -> bool {
+        self.bits[address as usize / 64] & (1 << (address % 64)) != 0
+    }
+
+    pub fn rom(&self) -> &[u8] {
+        &self.rom
+    }
+
+    pub fn load_address(&self) -> u16 {
+        self.load_address
+    }
+
+    // every byte of an instruction counts: two, or four for F000 and the address after it
+    fn covered_bytes(&self) -> Vec<bool> {
+        let mut covered = vec![false; ADDRESSES];
+        for address in (0..=u16::MAX).filter(|&address| self.contains(address)) {
+            let length = match self.rom_offset(address) {
+                Some(offset) if self.rom[offset..].starts_with(&[0xF0, 0x00]) => 4,
+                _ => 2,
+            };
+            for byte in 0..length {
+                covered[(address as usize + byte) % ADDRESSES] = true;
+            }
+        }
+        covered
+    }
+
+    fn rom_offset(&self, address: u16) -> Option<usize> {
+        let offset = address.checked_sub(self.load_address)? as usize;
+        if offset < self.rom.len() {
+            Some(offset)
+        } else {
+            None
+        }
+    }
+}
+
+/// What `report` found: the address ranges covered, how much of the ROM that is, and its
+/// disassembly marked line by line.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CoverageReport {
+    /// Both ends inclusive, in address order; may include code run from outside the ROM.
+    pub ranges: Vec<(u16, u16)>,
+    pub rom_bytes_covered: usize,
+    pub rom_size: usize,
+    /// `instruction::listing`'s lines, each with whether its first byte was covered.
+    pub listing: Vec<(bool, String)>,
+}
+
+impl CoverageReport {
+    pub fn percent(&self) -> f64 {
+        if self.rom_size == 0 {
+            0.0
+        } else {
+            self.rom_bytes_covered as f64 * 100.0 / self.rom_size as f64
+        }
+    }
+
+    /// The listing lines nothing covered, which for a static analysis is the dead code.
+    pub fn uncovered(&self) -> impl Iterator<Item = &str> {
+        self.listing.iter().filter(|(covered, _)| !covered).map(|(_, line)| line.as_str())
+    }
+}
+
+/// Sums up `coverage`. The listing is the same straight sweep `disasm` prints, so an instruction
+/// at an odd address shows up only through the line it sits in the middle of.
+pub fn report(coverage: &Coverage) -> CoverageReport {
+    let covered = coverage.covered_bytes();
+
+    let mut ranges: Vec<(u16, u16)> = Vec::new();
+    for (address, _) in covered.iter().enumerate().filter(|(_, &covered)| covered) {
+        let address = address as u16;
+        match ranges.last_mut() {
+            Some((_, end)) if end.wrapping_add(1) == address => *end = address,
+            _ => ranges.push((address, address)),
+        }
+    }
+
+    let load_address = coverage.load_address as usize;
+    let rom_covered = |offset: usize| covered[(load_address + offset) % ADDRESSES];
+    let listing = instruction::listing(&coverage.rom, coverage.load_address)
+        .into_iter()
+        .enumerate()
+        .map(|(index, line)| (rom_covered(index * 2), line))
+        .collect();
+
+    CoverageReport {
+        ranges,
+        rom_bytes_covered: (0..coverage.rom.len()).filter(|&offset| rom_covered(offset)).count(),
+        rom_size: coverage.rom.len(),
+        listing,
+    }
+}
+
+impl fmt::Display for CoverageReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let ranges: Vec<String> = self.ranges.iter().map(|(start, end)| format!("{:#05X}-{:#05X}", start, end)).collect();
+        writeln!(f, "covered: {}", if ranges.is_empty() { String::from("nothing") } else { ranges.join(", ") })?;
+        writeln!(f, "{} of {} ROM bytes covered ({:.1}%)", self.rom_bytes_covered, self.rom_size, self.percent())?;
+        writeln!(f)?;
+        for (covered, line) in &self.listing {
+            writeln!(f, "{} {}", if *covered { '+' } else { '-' }, line)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cpu::{Cpu, DEFAULT_LOAD_ADDRESS};
+    use crate::display::Display;
+    use crate::headless::{self, KeyScript};
+    use crate::memory::Memory;
+
+    fn assemble(opcodes: &[u16]) -> Vec<u8> {
+        opcodes.iter().flat_map(|opcode| opcode.to_be_bytes().to_vec()).collect()
+    }
+
+    #[test]
+    fn the_branch_not_taken_shows_as_uncovered() {
+        // LD V0, 1; SE V0, 1; JP 0x20A, never taken; LD V1, 2; JP 0x208; LD V2, 3
+        let rom = assemble(&[0x6001, 0x3001, 0x120A, 0x6102, 0x1208, 0x6203]);
+        let mut cpu = Cpu::new(Memory::new(), Display::new());
+        cpu.init(rom.clone());
+        cpu.enable_coverage(Coverage::new(&rom, DEFAULT_LOAD_ADDRESS));
+
+        headless::run(&mut cpu, 100, &KeyScript::default()).unwrap();
+        let report = report(cpu.coverage().unwrap());
+
+        assert_eq!(report.ranges, vec![(0x200, 0x203), (0x206, 0x209)]);
+        assert_eq!((report.rom_bytes_covered, report.rom_size), (8, 12));
+        assert_eq!(report.uncovered().collect::<Vec<_>>(), vec!["0x204: 120A  JP 0x20A", "0x20A: 6203  LD V2, 0x03"]);
+        let text = report.to_string();
+        assert!(text.starts_with("covered: 0x200-0x203, 0x206-0x209\n8 of 12 ROM bytes covered (66.7%)\n\n"), "{}", text);
+        assert!(text.contains("+ 0x202: 3001  SE V0, 0x01\n- 0x204: 120A  JP 0x20A\n"), "{}", text);
+    }
+
+    #[test]
+    fn long_loads_cover_their_address_and_code_outside_the_rom_is_listed() {
+        // LD I, LONG 0x1234; JP 0x204
+        let rom = assemble(&[0xF000, 0x1234, 0x1204]);
+        let mut coverage = Coverage::new(&rom, DEFAULT_LOAD_ADDRESS);
+
+        coverage.record(0x200);
+        coverage.record(0x300);
+        let report = report(&coverage);
+
+        assert!(coverage.contains(0x300) && !coverage.contains(0x202));
+        assert_eq!(report.ranges, vec![(0x200, 0x203), (0x300, 0x301)]);
+        assert_eq!(report.rom_bytes_covered, 4);
+        assert_eq!(report.listing.iter().map(|(covered, _)| *covered).collect::<Vec<_>>(), vec![true, true, false]);
+        assert_eq!(super::report(&Coverage::new(&[], DEFAULT_LOAD_ADDRESS)).to_string(), "covered: nothing\n0 of 0 ROM bytes covered (0.0%)\n\n");
+    }
+}
@@ -0,0 +1,3225 @@
+use alloc::collections::BTreeSet;
+use log::{trace, warn};
+use rand::rngs::SmallRng;
+use rand::SeedableRng;
+use core::fmt;
+
+use crate::audio::{BEEP_PATTERN, DEFAULT_PITCH, PATTERN_BYTES};
+use crate::cheats::Poke;
+use crate::coverage::Coverage;
+use crate::display::{Display, Rect, Resolution, PLANE_1, PLANE_2};
+use crate::edit::{Change, Edit, Target};
+use crate::error::{Chip8Error, IllegalOpcodes, RomError, RomTooLarge};
+use crate::font::{Font, FontMap};
+use crate::hash::{fnv1a, Fnv1a};
+use crate::heatmap::HeatMap;
+use crate::hooks::EmulatorHooks;
+use crate::history::History;
+use crate::instruction::Instruction;
+use crate::keys::Keys;
+use crate::megachip::{MegaRegisters, MegaSound, MEGA_CHIP_MEMORY_SIZE};
+use crate::memory::{Memory, MemoryAccess, Watchpoint, XO_CHIP_MEMORY_SIZE};
+use crate::prelude::*;
+use crate::profiler::Profile;
+use crate::quirks::{BigFontDigits, LoresBigSprite, MachineCodeCalls, NibbleOperands, Platform, Quirks};
+use crate::random::{self, RandomSource};
+use crate::registers::{Registers, VF};
+use crate::rplflags::RPL_FLAGS;
+#[cfg(feature = "std")]
+use crate::megachip::MegaScreen;
+#[cfg(feature = "std")]
+use crate::savestate::{SaveState, SaveStateError};
+
+/// Instructions executed per 60 Hz frame, i.e. between two timer ticks.
+pub const CYCLES_PER_FRAME: usize = 10;
+
+/// Where ROMs are loaded and start executing, unless told otherwise.
+pub const DEFAULT_LOAD_ADDRESS: u16 = 0x200;
+/// Where ETI-660 ROMs expect to be loaded.
+pub const ETI_660_LOAD_ADDRESS: u16 = 0x600;
+/// How far into the ROM two-page hi-res CHIP-8 starts executing: 0x2C0 for a ROM at 0x200.
+pub const HIRES_CHIP8_ENTRY_OFFSET: u16 = 0xC0;
+
+/// FX75/FX85 transfer at most V0..V7 outside XO-CHIP, as on the HP-48; XO-CHIP has all 16.
+const SUPER_CHIP_RPL_FLAGS: u8 = 8;
+
+/// Why the CPU has stopped executing instructions for good.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Halt {
+    /// The ROM ran 00FD.
+    Exited,
+    /// The ROM jumped to the jump at this address, the usual way to end, so all it can do is
+    /// keep jumping there.
+    Looped(u16),
+    /// An instruction failed; `cycle` keeps returning the same error.
+    Error(Chip8Error),
+}
+
+/// What `cycle` says about the instruction it just executed, for whatever is running frames.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Flow {
+    /// Carry on with the rest of the frame.
+    Continue,
+    /// DXYN drew with the display-wait quirk on: the frame ends here, as the COSMAC VIP waited
+    /// for the next vertical blank before drawing.
+    FrameBoundary,
+    /// FX0A is waiting for a key, and will run again on the next cycle.
+    WaitingForKey,
+}
+
+/// The 0NNN machine-code calls skipped since they were last taken: the first one, and how many
+/// there were in all. A ROM that loops over one gets a single entry with a growing count.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SkippedCalls {
+    pub pc: u16,
+    pub address: u16,
+    pub count: u64,
+}
+
+impl fmt::Display for SkippedCalls {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.count == 1 {
+            write!(f, "skipped a call to machine code at {:#05X} from {:#05X}", self.address, self.pc)
+        } else {
+            write!(f, "skipped {} calls to machine code, the first to {:#05X} from {:#05X}", self.count, self.address, self.pc)
+        }
+    }
+}
+
+/// Stable hashes of each part of the machine, for telling which part two runs disagree on.
+/// `Cpu::state_hash` combines them. The keypad, the breakpoints and anything else the
+/// frontend drives are left out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StateDigest {
+    pub memory: u64,
+    /// V0 to VF, I, PC, the RPL flags and whether FX0A is waiting.
+    pub registers: u64,
+    /// The pixels, the resolution and the selected planes.
+    pub display: u64,
+    /// The return addresses and SP.
+    pub stack: u64,
+    pub timers: u64,
+    /// XO-CHIP's audio pattern and pitch.
+    pub audio: u64,
+}
+
+impl StateDigest {
+    /// The components folded into one hash.
+    pub fn combined(&self) -> u64 {
+        let mut hash = Fnv1a::new();
+        for component in [self.memory, self.registers, self.display, self.stack, self.timers, self.audio].iter() {
+            hash.write(&component.to_le_bytes());
+        }
+        hash.finish()
+    }
+}
+
+type FrameCallback = Box<dyn FnMut(&Display) + Send>;
+
+pub struct Cpu {
+    i: u16,
+    pc: u16,
+    stack: [u16; 16],
+    // consider using Vec
+    sp: u8,
+    delay: u8,
+    sound: u8,
+    registers: Registers,
+    memory: Memory,
+    keys: Keys,
+    waiting_for_input: bool,
+    // with the FX0A release quirk, the key FX0A saw go down and is waiting to see let go of
+    pressed_key: Option<u8>,
+    display: Display,
+    rng: Box<dyn RandomSource>,
+    rom_hash: u64,
+    history: History,
+    profile: Option<Box<Profile>>,
+    coverage: Option<Box<Coverage>>,
+    quirks: Quirks,
+    rpl_flags: [u8; RPL_FLAGS],
+    halted: Option<Halt>,
+    audio_pattern: [u8; PATTERN_BYTES],
+    pitch: u8,
+    font_map: FontMap,
+    instructions_executed: u64,
+    skipped_calls: Option<SkippedCalls>,
+    illegal_opcodes: IllegalOpcodes,
+    // the (pc, opcode) pairs `IllegalOpcodes::WarnOnce` has already warned about
+    warned_illegal: BTreeSet<(u16, u16)>,
+    frame_callback: Option<FrameCallback>,
+    hooks: Option<Box<dyn EmulatorHooks>>,
+    // written again at the end of every frame while the cheats are on
+    frozen: Vec<Poke>,
+    cheats_enabled: bool,
+    megachip: MegaRegisters,
+    // where the ROM was last loaded, and where `reset` starts executing
+    load_address: u16,
+    // the instruction count at the first edit made by hand since the last reset
+    edited_at: Option<u64>,
+}
+
+impl Cpu {
+    pub fn new(memory: Memory, display: Display) -> Cpu {
+        Cpu {
+            i: 0,
+            pc: DEFAULT_LOAD_ADDRESS,
+            stack: [0; 16],
+            sp: 0,
+            delay: 0,
+            sound: 0,
+            registers: Default::default(),
+            memory,
+            keys: Keys::new(),
+            waiting_for_input: false,
+            pressed_key: None,
+            display,
+            rng: Box::new(random::entropy_rng()),
+            rom_hash: fnv1a(&[]),
+            history: History::new(),
+            profile: None,
+            coverage: None,
+            quirks: Quirks::default(),
+            rpl_flags: [0; RPL_FLAGS],
+            halted: None,
+            audio_pattern: BEEP_PATTERN,
+            pitch: DEFAULT_PITCH,
+            font_map: FontMap::default(),
+            instructions_executed: 0,
+            skipped_calls: None,
+            illegal_opcodes: IllegalOpcodes::default(),
+            warned_illegal: BTreeSet::new(),
+            frame_callback: None,
+            hooks: None,
+            frozen: Vec::new(),
+            cheats_enabled: true,
+            megachip: MegaRegisters::default(),
+            load_address: DEFAULT_LOAD_ADDRESS,
+            edited_at: None,
+        }
+    }
+
+    /// Reseeds the CXKK generator so runs with the same seed and inputs are reproducible.
+    pub fn seed(&mut self, seed: u64) {
+        self.rng = Box::new(SmallRng::seed_from_u64(seed));
+    }
+
+    pub fn set_random_source(&mut self, source: Box<dyn RandomSource>) {
+        self.rng = source;
+    }
+
+    /// Replaces the built-in font; call it before `init`, which copies the font into memory.
+    pub fn set_font(&mut self, font: Font) {
+        self.font_map.set_font(font);
+    }
+
+    /// Moves the fonts to `base`, from `DEFAULT_FONT_BASE`; call it before `init`, which copies
+    /// them there. Both fonts have to fit below the load address.
+    pub fn relocate_font(&mut self, base: u16) {
+        self.font_map.relocate(base);
+    }
+
+    /// Where the fonts are and what they hold.
+    pub fn font_map(&self) -> &FontMap {
+        &self.font_map
+    }
+
+    /// Loads the fonts and a ROM at `DEFAULT_LOAD_ADDRESS`.
+    ///
+    /// # Panics
+    ///
+    /// If the ROM doesn't fit in memory; use `init_at` to handle that.
+    pub fn init(&mut self, buffer: Vec<u8>) {
+        if let Err(error) = self.init_at(DEFAULT_LOAD_ADDRESS, buffer) {
+            panic!("{}", error);
+        }
+    }
+
+    /// How many bytes of ROM fit in memory when loaded at `load_address`; set the quirks first, as
+    /// XO-CHIP has more memory.
+    pub fn rom_capacity(&self, load_address: u16) -> usize {
+        self.memory.bytes().len().saturating_sub(load_address as usize)
+    }
+
+    /// Loads the fonts and a ROM at `load_address`, where execution starts. The fonts stay
+    /// where the `FontMap` has them whatever the load address. With the `hires_chip8` quirk, set the
+    /// quirks first: execution starts 0xC0 bytes in, on the 64x64 screen.
+    pub fn init_at(&mut self, load_address: u16, buffer: Vec<u8>) -> Result<(), RomTooLarge> {
+        let capacity = self.rom_capacity(load_address);
+        if buffer.len() > capacity {
+            return Err(RomTooLarge { size: buffer.len(), load_address, capacity });
+        }
+
+        self.load_fonts();
+        self.memory.load(load_address, &buffer);
+        self.load_address = load_address;
+        self.power_on_entry();
+
+        self.rom_hash = fnv1a(&buffer);
+        Ok(())
+    }
+
+    /// Puts the machine back as it was at power-on, without a ROM: registers, I, the stack and
+    /// its pointer, the timers, the keys, the screen and everything the ROM wrote are cleared,
+    /// the fonts written again over zeroed memory, and execution starts over at the last load
+    /// address. What the machine was set up with stays: its quirks, font, random source, RPL
+    /// flags, cheats, watchpoints and any profiling or coverage.
+    pub fn reset(&mut self) {
+        self.i = 0;
+        self.stack = [0; 16];
+        self.sp = 0;
+        self.delay = 0;
+        self.sound = 0;
+        self.registers = Default::default();
+        self.memory.bytes_mut().iter_mut().for_each(|byte| *byte = 0);
+        self.load_fonts();
+        self.keys = Keys::new();
+        self.waiting_for_input = false;
+        self.pressed_key = None;
+        self.display = Display::new();
+        self.rom_hash = fnv1a(&[]);
+        self.history = History::new();
+        self.halted = None;
+        self.audio_pattern = BEEP_PATTERN;
+        self.pitch = DEFAULT_PITCH;
+        self.instructions_executed = 0;
+        self.skipped_calls = None;
+        self.warned_illegal.clear();
+        self.megachip = MegaRegisters::default();
+        self.edited_at = None;
+        self.power_on_entry();
+    }
+
+    /// Resets the machine and loads `rom` at `load_address`, the way every ROM should be started
+    /// on a machine that may have run another. Also turns away empty files and ones that are
+    /// plainly something else, which would otherwise only fail once they run; a ROM that's
+    /// turned away leaves the machine as it was.
+    pub fn load_rom(&mut self, load_address: u16, rom: &[u8]) -> Result<(), RomError> {
+        let capacity = self.rom_capacity(load_address);
+        if rom.is_empty() {
+            return Err(RomError::Empty { capacity });
+        }
+        if let Some(kind) = file_kind(rom) {
+            return Err(RomError::NotBytecode(kind));
+        }
+        if rom.len() > capacity {
+            return Err(RomTooLarge { size: rom.len(), load_address, capacity }.into());
+        }
+        self.load_address = load_address;
+        self.reset();
+        Ok(self.init_at(load_address, rom.to_vec())?)
+    }
+
+    fn load_fonts(&mut self) {
+        self.font_map.write_to(&mut self.memory);
+    }
+
+    // where execution starts from `load_address`, on the screen the ROM expects
+    fn power_on_entry(&mut self) {
+        self.pc = self.load_address;
+        if self.quirks.hires_chip8 {
+            self.display.set_resolution(Resolution::TwoPage);
+            self.pc = self.load_address.wrapping_add(HIRES_CHIP8_ENTRY_OFFSET);
+        }
+    }
+
+    /// A digest of the machine state that's the same on every platform and run, cheap enough to
+    /// take every frame.
+    pub fn state_hash(&self) -> u64 {
+        self.state_digest().combined()
+    }
+
+    /// `state_hash` broken down by component. Taking it doesn't allocate.
+    pub fn state_digest(&self) -> StateDigest {
+        let mut registers = Fnv1a::new();
+        registers.write(self.registers.as_slice());
+        registers.write(&self.i.to_be_bytes());
+        registers.write(&self.pc.to_be_bytes());
+        registers.write(&self.rpl_flags);
+        registers.write(&[self.waiting_for_input as u8]);
+        let mut display = Fnv1a::new();
+        for column in self.display.pixels.iter() {
+            display.write(column);
+        }
+        // Lores and Hires hash as the 0 and 1 they did before the other modes came in
+        display.write(&[self.display.resolution as u8, self.display.planes]);
+        // only MEGA-CHIP adds to these, so the hashes of other ROMs stay as they were
+        if let Some(mega) = self.display.megachip() {
+            mega.hash_into(&mut display);
+        }
+        if self.quirks.platform == Platform::MegaChip {
+            let MegaRegisters { i_high, sprite_width, sprite_height, collision_color, sound } = self.megachip;
+            registers.write(&[i_high, sprite_width as u8, sprite_height as u8, collision_color, sound.is_some() as u8]);
+        }
+        // a run edited by hand can't match one that wasn't, even where the values are the same
+        if self.edited_at.is_some() {
+            registers.write(&[1]);
+        }
+        let mut stack = Fnv1a::new();
+        for address in self.stack.iter() {
+            stack.write(&address.to_be_bytes());
+        }
+        stack.write(&[self.sp]);
+        let mut audio = Fnv1a::new();
+        audio.write(&self.audio_pattern);
+        audio.write(&[self.pitch]);
+
+        StateDigest {
+            memory: fnv1a(self.memory.bytes()),
+            registers: registers.finish(),
+            display: display.finish(),
+            stack: stack.finish(),
+            timers: fnv1a(&[self.delay, self.sound]),
+            audio: audio.finish(),
+        }
+    }
+
+    #[cfg(feature = "std")]
+    pub fn save_state(&self) -> SaveState {
+        let mut registers = [0; 16];
+        registers.copy_from_slice(self.registers.as_slice());
+
+        SaveState {
+            rom_hash: self.rom_hash,
+            registers,
+            i: self.i,
+            pc: self.pc,
+            stack: self.stack,
+            sp: self.sp,
+            delay: self.delay,
+            sound: self.sound,
+            memory: self.memory.bytes().to_vec(),
+            pixels: self.display.pixels.iter().flatten().copied().collect(),
+            hires: self.display.resolution == Resolution::Hires,
+            planes: self.display.planes,
+            audio_pattern: self.audio_pattern,
+            pitch: self.pitch,
+            keys: self.keys.keys,
+            waiting_for_input: self.waiting_for_input,
+            megachip: self.megachip,
+            mega_screen: self.display.mega.as_ref().map(|mega| mega.bytes()),
+        }
+    }
+
+    /// Restores a snapshot taken with `save_state`; the Cpu is left untouched if the snapshot
+    /// belongs to another ROM or doesn't fit this machine.
+    #[cfg(feature = "std")]
+    pub fn load_state(&mut self, state: &SaveState) -> Result<(), SaveStateError> {
+        if state.rom_hash != self.rom_hash {
+            return Err(SaveStateError::WrongRom);
+        }
+        if state.memory.len() != self.memory.bytes().len() {
+            return Err(SaveStateError::Corrupt(format!("expected {} bytes of memory, found {}", self.memory.bytes().len(), state.memory.len())));
+        }
+        let columns = self.display.pixels.len();
+        let rows = self.display.pixels[0].len();
+        if state.pixels.len() != columns * rows {
+            return Err(SaveStateError::Corrupt(format!("expected {} pixels, found {}", columns * rows, state.pixels.len())));
+        }
+        if state.sp as usize > self.stack.len() {
+            return Err(SaveStateError::Corrupt(format!("stack pointer {} is out of range", state.sp)));
+        }
+        let mega = match &state.mega_screen {
+            Some(bytes) => {
+                let screen = MegaScreen::from_bytes(bytes);
+                Some(screen.ok_or_else(|| SaveStateError::Corrupt(format!("expected a MEGA-CHIP screen, found {} bytes", bytes.len())))?)
+            }
+            None => None,
+        };
+
+        self.registers = Registers::from(state.registers);
+        self.i = state.i;
+        self.pc = state.pc;
+        self.stack = state.stack;
+        self.sp = state.sp;
+        self.delay = state.delay;
+        self.sound = state.sound;
+        self.memory.bytes_mut().copy_from_slice(&state.memory);
+        for (column, pixels) in self.display.pixels.iter_mut().zip(state.pixels.chunks(rows)) {
+            column.copy_from_slice(pixels);
+        }
+        self.display.resolution = match (state.hires, self.quirks.hires_chip8, mega.is_some()) {
+            (_, _, true) => Resolution::Mega,
+            (true, _, false) => Resolution::Hires,
+            (false, true, false) => Resolution::TwoPage,
+            (false, false, false) => Resolution::Lores,
+        };
+        self.display.mega = mega.map(Box::new);
+        self.display.select_planes(state.planes);
+        self.display.mark_all_dirty();
+        self.audio_pattern = state.audio_pattern;
+        self.pitch = state.pitch;
+        self.keys.keys = state.keys;
+        self.keys.end_frame();
+        self.waiting_for_input = state.waiting_for_input;
+        self.megachip = state.megachip;
+        self.pressed_key = None;
+        self.halted = None;
+
+        Ok(())
+    }
+
+    pub fn display(&self) -> &Display {
+        &self.display
+    }
+
+    /// The region of the display changed since the last call, if any; see `Display::take_dirty`.
+    pub fn take_dirty(&mut self) -> Option<Rect> {
+        self.display.take_dirty()
+    }
+
+    pub fn memory(&self) -> &Memory {
+        &self.memory
+    }
+
+    pub fn pc(&self) -> u16 {
+        self.pc
+    }
+
+    pub fn i(&self) -> u16 {
+        self.i
+    }
+
+    pub fn sp(&self) -> u8 {
+        self.sp
+    }
+
+    /// Return addresses currently on the call stack, oldest first.
+    pub fn stack(&self) -> &[u16] {
+        &self.stack[..self.sp as usize]
+    }
+
+    pub fn delay_timer(&self) -> u8 {
+        self.delay
+    }
+
+    /// Sets the delay timer from outside the program, e.g. from a test harness.
+    pub fn set_delay_timer(&mut self, value: u8) {
+        self.delay = value;
+    }
+
+    pub fn sound_timer(&self) -> u8 {
+        self.sound
+    }
+
+    /// Whether the buzzer sounds: the sound timer runs and the machine hasn't halted, which
+    /// stops the timers where they are.
+    pub fn is_buzzing(&self) -> bool {
+        self.sound > 0 && self.halted.is_none()
+    }
+
+    /// The opcode stored at `location`, without executing it; bytes past the end of memory read as 0.
+    pub fn opcode_at(&self, location: u16) -> u16 {
+        let bytes = self.memory.bytes();
+        let byte = |at: u16| *bytes.get(at as usize).unwrap_or(&0) as u16;
+        byte(location) << 8 | byte(location.wrapping_add(1))
+    }
+
+    /// Whether FX0A is waiting for a key, and runs again on the next cycle.
+    pub fn is_waiting_for_key(&self) -> bool {
+        self.waiting_for_input
+    }
+
+    #[deprecated(note = "use `is_waiting_for_key`")]
+    pub fn is_waiting_for_input(&self) -> bool {
+        self.is_waiting_for_key()
+    }
+
+    pub fn registers(&self) -> &Registers {
+        &self.registers
+    }
+
+    /// Sets Vx from outside the program, e.g. from a script.
+    ///
+    /// # Panics
+    ///
+    /// If `x` is above 0xF.
+    pub fn set_register(&mut self, x: u8, value: u8) {
+        self.registers[x] = value;
+    }
+
+    /// Writes `pokes` at the end of every frame from now on, so the ROM can't change them; they
+    /// replace any frozen before.
+    ///
+    /// # Panics
+    ///
+    /// At the end of the frame, if one is past the end of memory; see `Cheats::check`.
+    pub fn freeze(&mut self, pokes: Vec<Poke>) {
+        self.frozen = pokes;
+    }
+
+    pub fn frozen(&self) -> &[Poke] {
+        &self.frozen
+    }
+
+    /// Stops or restarts writing the frozen pokes, without forgetting them.
+    pub fn set_cheats_enabled(&mut self, enabled: bool) {
+        self.cheats_enabled = enabled;
+    }
+
+    pub fn cheats_enabled(&self) -> bool {
+        self.cheats_enabled
+    }
+
+    /// Writes a byte from outside the program, without tripping watchpoints.
+    ///
+    /// # Panics
+    ///
+    /// If `address` is past the end of memory.
+    pub fn poke(&mut self, address: u16, value: u8) {
+        self.memory.bytes_mut()[address as usize] = value;
+    }
+
+    /// Makes a debugger edit to the machine, checking its addresses against memory first, and
+    /// returns what it changed. Every change goes into the history, and the run is marked as
+    /// edited, which `state_hash` takes in, so replays can tell. An edit gets a ROM stuck in a
+    /// loop going again.
+    pub fn apply_edit(&mut self, edit: &Edit) -> Result<Vec<Change>, String> {
+        let size = self.memory.bytes().len();
+        let last = size - 1;
+        let changes = match edit {
+            Edit::Set(target, value) => {
+                let value = *value;
+                let old = match *target {
+                    Target::V(x) => u16::from(self.registers.get(x).ok_or_else(|| format!("V{:X} is not a register", x))?),
+                    Target::I if value as usize > last => return Err(format!("{:#05X} is past the end of memory at {:#05X}", value, last)),
+                    Target::I => self.i,
+                    // both of the instruction's bytes have to be there
+                    Target::Pc if value as usize >= last => return Err(format!("{:#05X} is past the end of memory at {:#05X}", value, last)),
+                    Target::Pc => self.pc,
+                    Target::Mem(address) if address as usize > last => return Err(format!("{:#05X} is past the end of memory at {:#05X}", address, last)),
+                    Target::Mem(address) => u16::from(self.memory.bytes()[address as usize]),
+                };
+                vec![Change { target: *target, old, new: value }]
+            }
+            Edit::SetMem(address, bytes) => {
+                let end = *address as usize + bytes.len();
+                if end > size {
+                    return Err(format!("{:#05X} to {:#05X} is past the end of memory at {:#05X}", address, end - 1, last));
+                }
+                let old = &self.memory.bytes()[*address as usize..end];
+                let targets = (*address..).map(Target::Mem);
+                targets.zip(old.iter().zip(bytes)).map(|(target, (&old, &new))| Change { target, old: u16::from(old), new: u16::from(new) }).collect()
+            }
+        };
+        let pc = self.pc;
+        for change in &changes {
+            match change.target {
+                Target::V(x) => self.registers[x] = change.new as u8,
+                Target::I => self.i = change.new,
+                Target::Pc => self.pc = change.new,
+                Target::Mem(address) => self.poke(address, change.new as u8),
+            }
+            self.history.record_edit(pc, *change);
+        }
+        self.edited_at.get_or_insert(self.instructions_executed);
+        if let Some(Halt::Looped(_)) = self.halted {
+            self.halted = None;
+        }
+        Ok(changes)
+    }
+
+    /// The instruction count at the first edit made with `apply_edit` since the machine was last
+    /// reset, if any.
+    pub fn edited_at(&self) -> Option<u64> {
+        self.edited_at
+    }
+
+    pub fn add_watchpoint(&mut self, watchpoint: Watchpoint) {
+        self.memory.add_watchpoint(watchpoint);
+    }
+
+    /// The first watched memory access since the last call, if any.
+    pub fn take_watch_hit(&mut self) -> Option<MemoryAccess> {
+        self.memory.take_watch_hit()
+    }
+
+    /// The machine-code calls skipped since the last call, if any. Frontends take these now and
+    /// then rather than after every instruction, so a ROM looping over 0NNN warns once in a while.
+    pub fn take_skipped_calls(&mut self) -> Option<SkippedCalls> {
+        self.skipped_calls.take()
+    }
+
+    pub fn illegal_opcodes(&self) -> IllegalOpcodes {
+        self.illegal_opcodes
+    }
+
+    /// Sets what happens when the ROM runs into a word that's no instruction; halting is the default.
+    pub fn set_illegal_opcodes(&mut self, policy: IllegalOpcodes) {
+        self.illegal_opcodes = policy;
+    }
+
+    pub fn keys(&self) -> &Keys {
+        &self.keys
+    }
+
+    pub fn keys_mut(&mut self) -> &mut Keys {
+        &mut self.keys
+    }
+
+    /// The most recently executed instructions, including one that failed.
+    pub fn history(&self) -> &History {
+        &self.history
+    }
+
+    /// Hash of the loaded ROM, used to key files that belong to it.
+    pub fn rom_hash(&self) -> u64 {
+        self.rom_hash
+    }
+
+    /// The HP-48 "RPL user flags" written by FX75 and read by FX85.
+    pub fn rpl_flags(&self) -> &[u8; RPL_FLAGS] {
+        &self.rpl_flags
+    }
+
+    /// Restores flags saved by an earlier run, so high scores survive a restart.
+    pub fn set_rpl_flags(&mut self, flags: [u8; RPL_FLAGS]) {
+        self.rpl_flags = flags;
+    }
+
+    /// How many registers FX75 and FX85 reach, from V0 up.
+    fn rpl_flag_registers(&self) -> u8 {
+        if self.quirks.platform == Platform::XoChip {
+            RPL_FLAGS as u8
+        } else {
+            SUPER_CHIP_RPL_FLAGS
+        }
+    }
+
+    pub fn quirks(&self) -> Quirks {
+        self.quirks
+    }
+
+    /// Switching to XO-CHIP also grows memory to 64 KiB, and to MEGA-CHIP to 16 MiB, so set the
+    /// quirks before `init` to load ROMs that need the room.
+    pub fn set_quirks(&mut self, quirks: Quirks) {
+        self.quirks = quirks;
+        match quirks.platform {
+            Platform::XoChip => self.memory.grow(XO_CHIP_MEMORY_SIZE),
+            Platform::MegaChip => self.memory.grow(MEGA_CHIP_MEMORY_SIZE),
+            Platform::SuperChip => {}
+        }
+    }
+
+    /// The registers MEGA-CHIP adds, among them the sound 060N is playing.
+    pub fn megachip(&self) -> &MegaRegisters {
+        &self.megachip
+    }
+
+    /// Starts counting executed instructions per family and address; see `profile`.
+    pub fn enable_profiling(&mut self) {
+        self.profile = Some(Box::new(Profile::new()));
+    }
+
+    pub fn profile(&self) -> Option<&Profile> {
+        self.profile.as_deref()
+    }
+
+    /// Starts marking the address of every instruction executed in `coverage`; see `coverage`.
+    pub fn enable_coverage(&mut self, coverage: Coverage) {
+        self.coverage = Some(Box::new(coverage));
+    }
+
+    pub fn coverage(&self) -> Option<&Coverage> {
+        self.coverage.as_deref()
+    }
+
+    /// Starts counting reads, writes and executes per address of the first 4 KB; see `heat_map`.
+    pub fn enable_heat_map(&mut self) {
+        self.memory.enable_heat_map();
+    }
+
+    pub fn disable_heat_map(&mut self) {
+        self.memory.disable_heat_map();
+    }
+
+    pub fn heat_map(&self) -> Option<&HeatMap> {
+        self.memory.heat_map()
+    }
+
+    /// Cools the heat map, if there is one, by a frame's worth.
+    pub fn decay_heat_map(&mut self) {
+        if let Some(heat_map) = self.memory.heat_map_mut() {
+            heat_map.decay();
+        }
+    }
+
+    /// The 1-bit sample pattern played while the sound timer runs, set by XO-CHIP's F002.
+    pub fn audio_pattern(&self) -> &[u8; PATTERN_BYTES] {
+        &self.audio_pattern
+    }
+
+    /// The playback pitch set by XO-CHIP's FX3A; see `audio::pattern_rate`.
+    pub fn pitch(&self) -> u8 {
+        self.pitch
+    }
+
+    pub fn halted(&self) -> Option<Halt> {
+        self.halted
+    }
+
+    pub fn is_halted(&self) -> bool {
+        self.halted.is_some()
+    }
+
+    /// Whether the ROM exited cleanly with 00FD.
+    pub fn is_finished(&self) -> bool {
+        self.halted == Some(Halt::Exited)
+    }
+
+    /// How many instructions `cycle` has executed since the CPU was created; restoring a save state
+    /// doesn't wind it back. Wraps around to 0 after `u64::MAX`, centuries from now at any speed.
+    pub fn instructions_executed(&self) -> u64 {
+        self.instructions_executed
+    }
+
+    /// Executes one instruction; once halted this does nothing, or repeats the error that halted it.
+    pub fn cycle(&mut self) -> Result<Flow, Chip8Error> {
+        match self.halted {
+            Some(Halt::Exited) | Some(Halt::Looped(_)) => return Ok(Flow::Continue),
+            Some(Halt::Error(error)) => return Err(error),
+            None => {}
+        }
+
+        let pc = self.pc;
+        let opcode: u16 = self.fetch(pc);
+        self.memory.executed(pc);
+        self.history.record(self.pc, opcode);
+        if let Some(profile) = &mut self.profile {
+            profile.record(self.pc, opcode);
+        }
+        if let Some(coverage) = &mut self.coverage {
+            coverage.record(self.pc);
+        }
+
+        self.pc = self.pc.wrapping_add(2);
+        self.instructions_executed = self.instructions_executed.wrapping_add(1);
+
+        let result = self.decode_and_execute(opcode);
+        if let Err(error) = result {
+            self.halted = Some(Halt::Error(error));
+        }
+        if let Some(hooks) = self.hooks.as_mut() {
+            hooks.on_instruction(pc, opcode);
+            if let Some(halt) = &self.halted {
+                hooks.on_halt(halt);
+                // the timers stop with the machine, and with them the buzzer
+                if self.sound > 0 {
+                    hooks.on_sound(false);
+                }
+            }
+        }
+        result
+    }
+
+    /// Decrements the delay and sound timers; call it at 60 Hz, and not while paused. It ends a
+    /// frame, so the keys latched during it are let go of, the frozen pokes are written again and
+    /// the frame callback is handed the display afterwards. Once halted, for whatever reason, the
+    /// timers stay as they were when it happened, and the buzzer is off.
+    pub fn tick_timers(&mut self) {
+        if self.halted.is_none() {
+            self.delay = self.delay.saturating_sub(1);
+            self.set_sound_timer(self.sound.saturating_sub(1));
+        }
+        self.keys.end_frame();
+        if self.cheats_enabled {
+            for poke in &self.frozen {
+                self.memory.bytes_mut()[poke.address as usize] = poke.value;
+            }
+        }
+        if let Some(callback) = self.frame_callback.as_mut() {
+            callback(&self.display);
+        }
+    }
+
+    /// Calls `callback` with the display after each completed frame, that is each `tick_timers`,
+    /// replacing any callback set before. Pair it with `Display::to_rgba` to stream frames out.
+    pub fn set_frame_callback(&mut self, callback: impl FnMut(&Display) + Send + 'static) {
+        self.frame_callback = Some(Box::new(callback));
+    }
+
+    /// Hands `hooks` every event from now on, replacing any hooks set before.
+    pub fn set_hooks(&mut self, hooks: Box<dyn EmulatorHooks>) {
+        self.hooks = Some(hooks);
+    }
+
+    /// Stops calling the hooks, handing them back.
+    pub fn take_hooks(&mut self) -> Option<Box<dyn EmulatorHooks>> {
+        self.hooks.take()
+    }
+
+    /// Sets the sound timer from outside the program, e.g. from a test harness; the hooks hear
+    /// about it starting or stopping the buzzer as they do when the ROM sets it.
+    pub fn set_sound_timer(&mut self, value: u8) {
+        let was_on = self.sound > 0;
+        self.sound = value;
+        if was_on != (value > 0) {
+            if let Some(hooks) = self.hooks.as_mut() {
+                hooks.on_sound(value > 0);
+            }
+        }
+    }
+
+    fn display_cleared(&mut self) {
+        if let Some(hooks) = self.hooks.as_mut() {
+            hooks.on_display_cleared();
+        }
+    }
+
+    // goes through `opcode_at` so instruction fetches never trigger read watchpoints
+    fn fetch(&self, location: u16) -> u16 {
+        self.opcode_at(location)
+    }
+
+    // the skips step over XO-CHIP's F000 NNNN whole, rather than landing on its address and
+    // running that as an instruction
+    fn skip_next_instruction(&mut self) {
+        let length = if self.quirks.platform == Platform::XoChip && self.fetch(self.pc) == 0xF000 { 4 } else { 2 };
+        self.pc = self.pc.wrapping_add(length);
+    }
+
+    // I with MEGA-CHIP's bits 16 to 23
+    fn long_i(&self) -> usize {
+        (self.megachip.i_high as usize) << 16 | self.i as usize
+    }
+
+    // `length` bytes from `address`, wrapping around at the end of memory
+    fn bytes_at(&self, address: usize, length: usize) -> Vec<u8> {
+        let bytes = self.memory.bytes();
+        (0..length).map(|offset| bytes[(address + offset) % bytes.len()]).collect()
+    }
+
+    fn half_pixel_scroll(&self) -> bool {
+        self.quirks.half_pixel_lores_scroll && self.display.resolution() == Resolution::Lores
+    }
+
+    // the opcode just fetched is no instruction; PC is already past it, so skipping it is doing nothing
+    fn illegal_opcode(&mut self, opcode: u16) -> Result<Flow, Chip8Error> {
+        let pc = self.pc.wrapping_sub(2);
+        match self.illegal_opcodes {
+            IllegalOpcodes::Halt => return Err(Chip8Error::UnknownOpcode { pc, opcode }),
+            IllegalOpcodes::Skip => {}
+            IllegalOpcodes::WarnOnce => {
+                if self.warned_illegal.insert((pc, opcode)) {
+                    warn!("skipped unsupported opcode {:#06X} at {:#05X}", opcode, pc);
+                }
+            }
+        }
+        Ok(Flow::Continue)
+    }
+
+    fn decode_and_execute(&mut self, opcode: u16) -> Result<Flow, Chip8Error> {
+        let x: u8 = ((opcode & 0x0F00) >> 8) as u8;
+        let y: u8 = ((opcode & 0x00F0) >> 4) as u8;
+        let kk: u8 = (opcode & 0x00FF) as u8;
+        let nnn: u16 = opcode & 0x0FFF;
+        let n: u8 = (opcode & 0x000F) as u8;
+        let mega = self.quirks.platform == Platform::MegaChip;
+
+        // only formatted when tracing is on, e.g. with RUST_LOG=trace
+        trace!("opcode {:#X?}", opcode);
+
+        if Instruction::decode_on(opcode, self.quirks.platform).is_none() {
+            return self.illegal_opcode(opcode);
+        }
+
+        match opcode {
+            // 0x0nnn - ignored by modern interpreters
+            0x00E0 => {
+                self.display.clear();
+                self.display_cleared();
+            }
+            0x00C0..=0x00CF => {
+                let rows = if self.half_pixel_scroll() { n as usize / 2 } else { n as usize };
+                self.display.scroll_down(rows);
+            }
+            0x00FB => {
+                if self.half_pixel_scroll() {
+                    self.display.scroll_right(2);
+                } else {
+                    self.display.scroll_right4();
+                }
+            }
+            0x00FC => {
+                if self.half_pixel_scroll() {
+                    self.display.scroll_left(2);
+                } else {
+                    self.display.scroll_left4();
+                }
+            }
+            0x00FD => {
+                // stay on 00FD, so a state saved after exiting exits again when loaded
+                self.pc = self.pc.wrapping_sub(2);
+                self.halted = Some(Halt::Exited);
+            }
+            0x00FE => {
+                self.display.set_resolution(Resolution::Lores);
+            }
+            0x00FF => {
+                self.display.set_resolution(Resolution::Hires);
+            }
+            0x00EE => {
+                if self.sp == 0 {
+                    return Err(Chip8Error::StackUnderflow { pc: self.pc.wrapping_sub(2) });
+                }
+                self.pc = self.stack[self.sp as usize - 1];
+                self.sp -= 1;
+            }
+            // two-page hi-res CHIP-8 clears its 64x64 screen with a call into the patched interpreter
+            0x0230 if self.quirks.hires_chip8 => {
+                self.display.clear();
+                self.display_cleared();
+            }
+            // MEGA-CHIP takes over some of the machine-code calls
+            0x0010 if mega => {
+                self.display.set_resolution(Resolution::Lores);
+            }
+            0x0011 if mega => {
+                self.display.set_resolution(Resolution::Mega);
+            }
+            0x00B0..=0x00BF if mega => {
+                self.display.scroll_up(n as usize);
+            }
+            // 01NN NNNN: NN is the top byte of I and the word after the opcode the rest; it's skipped over
+            0x0100..=0x01FF if mega => {
+                self.megachip.i_high = kk;
+                self.i = self.fetch(self.pc);
+                self.pc = self.pc.wrapping_add(2);
+            }
+            0x0200..=0x02FF if mega => {
+                let colors = self.bytes_at(self.long_i(), kk as usize * 4);
+                if let Some(screen) = self.display.megachip_mut() {
+                    screen.load_palette(&colors);
+                }
+            }
+            0x0300..=0x03FF if mega => {
+                self.megachip.sprite_width = if kk == 0 { 256 } else { kk as usize };
+            }
+            0x0400..=0x04FF if mega => {
+                self.megachip.sprite_height = if kk == 0 { 256 } else { kk as usize };
+            }
+            0x0500..=0x05FF if mega => {
+                if let Some(screen) = self.display.megachip_mut() {
+                    screen.set_alpha(kk);
+                }
+            }
+            0x0600..=0x0601 if mega => {
+                self.megachip.sound = Some(MegaSound::at(self.memory.bytes(), self.long_i(), n == 0));
+            }
+            0x0700 if mega => {
+                self.megachip.sound = None;
+            }
+            0x0900..=0x09FF if mega => {
+                self.megachip.collision_color = kk;
+            }
+            0x0000..=0x0FFF => {
+                let pc = self.pc.wrapping_sub(2);
+                if self.quirks.machine_code_calls == MachineCodeCalls::Error {
+                    return Err(Chip8Error::MachineCodeCall { pc, address: nnn });
+                }
+                self.skipped_calls.get_or_insert(SkippedCalls { pc, address: nnn, count: 0 }).count += 1;
+            }
+            0x1000..=0x1FFF => {
+                // nothing runs between a jump and itself, so nothing that could end the loop can
+                // happen; a loop that polls the timers or keys has other instructions in it
+                if nnn == self.pc.wrapping_sub(2) {
+                    self.halted = Some(Halt::Looped(nnn));
+                }
+                self.pc = nnn;
+            }
+            0x2000..=0x2FFF => {
+                if self.sp as usize == self.stack.len() {
+                    return Err(Chip8Error::StackOverflow { pc: self.pc.wrapping_sub(2) });
+                }
+                self.sp += 1;
+                self.stack[self.sp as usize - 1] = self.pc;
+                self.pc = nnn;
+            }
+            0x3000..=0x3FFF => {
+                if self.registers[x] == kk {
+                    self.skip_next_instruction();
+                }
+            }
+            0x4000..=0x4FFF => {
+                if self.registers[x] != kk {
+                    self.skip_next_instruction();
+                }
+            }
+            0x5000..=0x5FFF if n == 0 => {
+                if self.registers[x] == self.registers[y] {
+                    self.skip_next_instruction();
+                }
+            }
+            // XO-CHIP's 5XY2/5XY3 save and load Vx..Vy, counting down when x > y; I stays put
+            0x5000..=0x5FFF if (n == 2 || n == 3) && self.quirks.platform == Platform::XoChip => {
+                let count = x.max(y) - x.min(y) + 1;
+                for offset in 0..count {
+                    let register = if x <= y { x + offset } else { x - offset };
+                    let address = self.i.wrapping_add(offset as u16);
+                    if n == 2 {
+                        self.memory.write_u8(address, self.registers[register]);
+                    } else {
+                        self.registers[register] = self.memory.read_u8(address);
+                    }
+                }
+            }
+            0x6000..=0x6FFF => {
+                self.registers[x] = kk;
+            }
+            0x7000..=0x7FFF => {
+                let value: u16 = self.registers[x] as u16 + kk as u16;
+                self.registers[x] = value as u8;
+            }
+            0x8000..=0x8FFE => {
+                let operation = opcode & 0x000F;
+                match operation {
+                    0 => self.registers[x] = self.registers[y],
+                    1 => self.registers[x] |= self.registers[y],
+                    2 => self.registers[x] &= self.registers[y],
+                    3 => self.registers[x] ^= self.registers[y],
+                    // the flag comes from the operands as they were, and is written last, so it
+                    // wins when Vx is VF
+                    4 | 5 | 6 | 7 | 0xE => {
+                        let vx = match operation {
+                            6 | 0xE if self.quirks.shift_uses_vy => self.registers[y],
+                            _ => self.registers[x],
+                        };
+                        let (result, flag) = flag_arithmetic(operation, vx, self.registers[y]);
+                        self.registers[x] = result;
+                        self.registers[VF] = flag;
+                    }
+                    _ => return self.illegal_opcode(opcode),
+                }
+            }
+            0x9000..=0x9FF0 => {
+                if self.registers[x] != self.registers[y] {
+                    self.skip_next_instruction();
+                }
+            }
+            0xA000..=0xAFFF => {
+                self.i = nnn;
+                self.megachip.i_high = 0;
+            }
+            0xB000..=0xBFFF => {
+                self.pc = nnn + self.registers[0] as u16;
+            }
+            0xC000..=0xCFFF => {
+                self.registers[x] = self.rng.next_byte() & kk;
+            }
+            0xD000..=0xDFFF if self.display.resolution() == Resolution::Mega => {
+                let (sprite_x, sprite_y) = (self.registers[x] as usize, self.registers[y] as usize);
+                let MegaRegisters { i_high, sprite_width, sprite_height, collision_color, .. } = self.megachip;
+                // the fonts have no colours of their own, so they're drawn the classic way in
+                // FONT_COLOR
+                let font = i_high == 0 && self.font_map.range().contains(&self.i);
+                let (width, height) = match n {
+                    _ if !font => (sprite_width, sprite_height),
+                    0 => (16, 16),
+                    _ => (8, n as usize),
+                };
+                let bytes = self.bytes_at(self.long_i(), if font { width / 8 * height } else { width * height });
+                let screen = self.display.megachip_mut().expect("the MEGA-CHIP screen is there in its resolution");
+                let rows: Vec<u16> = if font {
+                    bytes.chunks(width / 8).map(|row| (row[0] as u16) << 8 | *row.get(1).unwrap_or(&0) as u16).collect()
+                } else {
+                    Vec::new()
+                };
+                let collision = if font {
+                    screen.draw_bitmap(sprite_x, sprite_y, &rows, width, collision_color)
+                } else {
+                    screen.blit(sprite_x, sprite_y, width, height, &bytes, collision_color)
+                };
+                self.registers[VF] = collision as u8;
+                if let Some(hooks) = self.hooks.as_mut() {
+                    hooks.on_sprite_drawn(sprite_x, sprite_y, &rows, collision);
+                }
+                if self.quirks.display_wait {
+                    return Ok(Flow::FrameBoundary);
+                }
+            }
+            0xD000..=0xDFFF => {
+                // DXY0 draws a 16x16 sprite, two bytes per row, except for SUPER-CHIP's 8x16 in lores
+                let (row_count, width) = match n {
+                    0 if self.display.resolution() == Resolution::Lores
+                        && self.quirks.lores_big_sprite == LoresBigSprite::Tall8x16 => (16, 8),
+                    0 => (16, 16),
+                    _ => (n as u16, 8),
+                };
+                let bytes_per_row = width as u16 / 8;
+                let (sprite_x, sprite_y) = (self.registers[x] as usize, self.registers[y] as usize);
+                // with both XO-CHIP planes selected, plane 2's sprite follows plane 1's in memory
+                let mut address = self.i;
+                let mut collision = false;
+                let selected = self.display.selected_planes();
+                let mut rows = [0u16; 16];
+                for &plane in [PLANE_1, PLANE_2].iter().filter(|&&plane| selected & plane != 0) {
+                    for row in rows.iter_mut().take(row_count as usize) {
+                        *row = (self.memory.read_u8(address) as u16) << 8;
+                        if bytes_per_row == 2 {
+                            *row |= self.memory.read_u8(address.wrapping_add(1)) as u16;
+                        }
+                        address = address.wrapping_add(bytes_per_row);
+                    }
+                    collision |= self.display.draw_sprite_on(plane, sprite_x, sprite_y, &rows[..row_count as usize], width);
+                }
+                self.registers[VF] = collision as u8;
+                if let Some(hooks) = self.hooks.as_mut() {
+                    hooks.on_sprite_drawn(sprite_x, sprite_y, &rows[..row_count as usize], collision);
+                }
+                if self.quirks.display_wait {
+                    return Ok(Flow::FrameBoundary);
+                }
+            }
+            0xE000..=0xEFFF => {
+                let operation = kk;
+                match operation {
+                    0x9E | 0xA1 => {
+                        let key = self.registers[x];
+                        if key > 0xF && self.quirks.nibble_operands == NibbleOperands::Error {
+                            return Err(Chip8Error::InvalidKey { pc: self.pc.wrapping_sub(2), key });
+                        }
+                        // only the low nibble of Vx names a key; a tap earlier in the frame counts
+                        if self.keys.was_pressed(key & 0xF) == (operation == 0x9E) {
+                            self.skip_next_instruction();
+                        }
+                    }
+                    _ => return self.illegal_opcode(opcode),
+                }
+            }
+            _ if opcode & 0xF0FF == 0xF001 && self.quirks.platform == Platform::XoChip => {
+                self.display.select_planes(x);
+            }
+            // F000 NNNN: the address is the word after the opcode, which is skipped over
+            0xF000 if self.quirks.platform == Platform::XoChip => {
+                self.i = self.fetch(self.pc);
+                self.pc = self.pc.wrapping_add(2);
+            }
+            0xF002 if self.quirks.platform == Platform::XoChip => {
+                for (offset, byte) in self.audio_pattern.iter_mut().enumerate() {
+                    *byte = self.memory.read_u8(self.i.wrapping_add(offset as u16));
+                }
+            }
+            _ if opcode & 0xF0FF == 0xF03A && self.quirks.platform == Platform::XoChip => {
+                self.pitch = self.registers[x];
+            }
+            0xF007..=0xFF85 => {
+                let operation = opcode & 0x00FF;
+                match operation {
+                    0x07 => self.registers[x] = self.delay,
+                    0x0A => {
+                        // wait for a key by executing this instruction again until one is pressed,
+                        // or with the release quirk pressed and let go of
+                        let key = match self.pressed_key {
+                            Some(key) if self.keys.is_pressed(key) => None,
+                            Some(key) => Some(key),
+                            None => match self.keys.any_was_pressed() {
+                                // tapped since the frame began: pressed and already let go of
+                                Some(key) if self.quirks.fx0a_waits_for_release && self.keys.is_pressed(key) => {
+                                    self.pressed_key = Some(key);
+                                    None
+                                }
+                                key => key,
+                            },
+                        };
+                        match key {
+                            Some(key) => {
+                                self.registers[x] = key;
+                                self.keys.take_latch(key);
+                                self.pressed_key = None;
+                                self.waiting_for_input = false;
+                            }
+                            None => {
+                                self.waiting_for_input = true;
+                                self.pc = self.pc.wrapping_sub(2);
+                                return Ok(Flow::WaitingForKey);
+                            }
+                        }
+                    }
+                    0x15 => self.delay = self.registers[x],
+                    0x18 => self.set_sound_timer(self.registers[x]),
+                    0x1E => {
+                        let sum = self.i.wrapping_add(self.registers[x] as u16);
+                        if self.quirks.i_overflow_sets_vf {
+                            self.registers[VF] = (sum > 0xFFF) as u8;
+                            self.i = sum & 0xFFF;
+                        } else {
+                            self.i = sum;
+                        }
+                    }
+                    0x29 => {
+                        let digit = self.registers[x];
+                        if digit > 0xF && self.quirks.nibble_operands == NibbleOperands::Error {
+                            return Err(Chip8Error::InvalidDigit { pc: self.pc.wrapping_sub(2), digit });
+                        }
+                        self.i = self.font_map.addr_of_digit(digit);
+                        self.megachip.i_high = 0;
+                    }
+                    0x30 => {
+                        let digit = self.registers[x];
+                        if digit > 9 && self.quirks.big_font_digits == BigFontDigits::Error {
+                            return Err(Chip8Error::InvalidBigDigit { pc: self.pc.wrapping_sub(2), digit });
+                        }
+                        self.i = self.font_map.addr_of_big_digit(digit);
+                        self.megachip.i_high = 0;
+                    }
+                    0x33 => {
+                        let value = self.registers[x];
+                        self.memory.write_u8(self.i, value / 100);
+                        self.memory.write_u8(self.i.wrapping_add(1), (value % 100) / 10);
+                        self.memory.write_u8(self.i.wrapping_add(2), value % 10);
+                    }
+                    0x55 => {
+                        for register in 0..(x + 1) {
+                            self.memory.write_u8(self.i.wrapping_add(register as u16), self.registers[register]);
+                        }
+                    }
+                    0x65 => {
+                        for register in 0..(x + 1) {
+                            self.registers[register] = self.memory.read_u8(self.i.wrapping_add(register as u16));
+                        }
+                    }
+                    0x75 => {
+                        for register in 0..(x + 1).min(self.rpl_flag_registers()) {
+                            self.rpl_flags[register as usize] = self.registers[register];
+                        }
+                    }
+                    0x85 => {
+                        for register in 0..(x + 1).min(self.rpl_flag_registers()) {
+                            self.registers[register] = self.rpl_flags[register as usize];
+                        }
+                    }
+                    // XO-CHIP's FX01 and FX3A land here on the other platforms
+                    _ => return self.illegal_opcode(opcode),
+                }
+            }
+            // what decodes but isn't on this platform, such as XO-CHIP's 5XY2 or F002 elsewhere
+            _ => return self.illegal_opcode(opcode),
+        }
+
+        Ok(Flow::Continue)
+    }
+}
+
+/// The result and VF of 8XY4, 8XY5, 8XY6, 8XY7 or 8XYE, picked by `operation`, on `vx` and `vy`:
+/// the carry, no borrow, or the bit shifted out. The shifts work on `vx` alone; the caller hands
+/// them Vy there for the shift quirk.
+fn flag_arithmetic(operation: u16, vx: u8, vy: u8) -> (u8, u8) {
+    match operation {
+        4 => {
+            let (sum, carried) = vx.overflowing_add(vy);
+            (sum, carried as u8)
+        }
+        5 => (vx.wrapping_sub(vy), (vx >= vy) as u8),
+        6 => (vx >> 1, vx & 1),
+        7 => (vy.wrapping_sub(vx), (vy >= vx) as u8),
+        0xE => (vx << 1, vx >> 7),
+        _ => unreachable!("8XY{:X} sets no flag", operation),
+    }
+}
+
+/// What kind of file `buffer` plainly is, when it isn't bytecode. Text has to be all printable
+/// and span lines, which real ROMs never manage: nearly all of them hold 0x00 or bytes above 0x7F.
+fn file_kind(buffer: &[u8]) -> Option<&'static str> {
+    if buffer.starts_with(b"PK\x03\x04") || buffer.starts_with(b"PK\x05\x06") {
+        Some("a zip archive")
+    } else if buffer.starts_with(&[0x1F, 0x8B]) {
+        Some("a gzip file")
+    } else if buffer.contains(&b'\n') && buffer.iter().all(|&byte| matches!(byte, b'\t' | b'\n' | b'\r' | 0x20..=0x7E)) {
+        Some("text, such as a hex dump")
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+    use crate::font::{BIG_FONT_BYTES, FONT_BYTES, GLYPH_ROWS};
+    use crate::megachip::FONT_COLOR;
+
+    #[test]
+    fn clear_display() {
+        let mut memory: Memory = Memory::new();
+        let mut display: Display = Display::new();
+        display.pixels[0][0] = 1;
+        display.pixels[63][31] = 1;
+        memory.write_u16(0x200, 0x00E0);
+        let mut cpu = Cpu::new(memory, display);
+
+        cpu.cycle().unwrap();
+
+        assert_eq!(cpu.display.pixels[0][0], 0);
+        assert_eq!(cpu.display.pixels[63][31], 0);
+    }
+
+    #[test]
+    fn return_from_a_subroutine() {
+        let mut memory: Memory = Memory::new();
+        let display: Display = Display::new();
+        memory.write_u16(0x200, 0x0EE);
+        let mut cpu = Cpu::new(memory, display);
+        cpu.stack[0] = 0x0001;
+        cpu.sp = 1;
+
+        cpu.cycle().unwrap();
+
+        assert_eq!(cpu.sp, 0);
+        assert_eq!(cpu.pc, 0x0001);
+    }
+
+    #[test]
+    fn jump_to_location() {
+        let mut memory: Memory = Memory::new();
+        let display: Display = Display::new();
+        memory.write_u16(0x200, 0x1234);
+        let mut cpu = Cpu::new(memory, display);
+
+        cpu.cycle().unwrap();
+
+        assert_eq!(cpu.pc, 0x234);
+    }
+
+    #[test]
+    fn call_subroutine() {
+        let mut memory: Memory = Memory::new();
+        let display: Display = Display::new();
+        memory.write_u16(0x200, 0x2312);
+        let mut cpu = Cpu::new(memory, display);
+
+        cpu.cycle().unwrap();
+
+        assert_eq!(cpu.sp, 1);
+        assert_eq!(cpu.stack[0], 0x200 + 2);
+        assert_eq!(cpu.pc, 0x312);
+    }
+
+    #[test]
+    fn skip_next_instruction_if_vx_equals_kk() {
+        let mut memory: Memory = Memory::new();
+        let display: Display = Display::new();
+        memory.write_u16(0x200, 0x3144);
+        let mut cpu = Cpu::new(memory, display);
+        cpu.registers[1] = 0x44;
+
+        cpu.cycle().unwrap();
+
+        assert_eq!(cpu.pc, 0x200 + 4);
+    }
+
+    #[test]
+    fn skip_next_instruction_if_vx_not_equals_kk() {
+        let mut memory: Memory = Memory::new();
+        let display: Display = Display::new();
+        memory.write_u16(0x200, 0x4144);
+        let mut cpu = Cpu::new(memory, display);
+        cpu.registers[1] = 0x43;
+
+        cpu.cycle().unwrap();
+
+        assert_eq!(cpu.pc, 0x200 + 4);
+    }
+
+    #[test]
+    fn skip_next_instruction_if_vx_equals_vy() {
+        let mut memory: Memory = Memory::new();
+        let display: Display = Display::new();
+        memory.write_u16(0x200, 0x5120);
+        let mut cpu = Cpu::new(memory, display);
+        cpu.registers[1] = 0x44;
+        cpu.registers[2] = 0x44;
+
+        cpu.cycle().unwrap();
+
+        assert_eq!(cpu.pc, 0x200 + 4);
+    }
+
+    #[test]
+    fn set_vx_to_kk() {
+        let mut memory: Memory = Memory::new();
+        let display: Display = Display::new();
+        memory.write_u16(0x200, 0x6622);
+        let mut cpu = Cpu::new(memory, display);
+
+        cpu.cycle().unwrap();
+
+        assert_eq!(cpu.registers[6], 0x22);
+    }
+
+    #[test]
+    fn set_vx_to_vx_plus_kk() {
+        let mut memory: Memory = Memory::new();
+        let display: Display = Display::new();
+        memory.write_u16(0x200, 0x7422);
+        let mut cpu = Cpu::new(memory, display);
+        cpu.registers[4] = 0x22;
+
+        cpu.cycle().unwrap();
+
+        assert_eq!(cpu.registers[4], 0x22 + 0x22);
+    }
+
+    #[test]
+    fn set_vx_to_vy() {
+        let mut memory: Memory = Memory::new();
+        let display: Display = Display::new();
+        memory.write_u16(0x200, 0x8420);
+        let mut cpu = Cpu::new(memory, display);
+        cpu.registers[2] = 0x22;
+
+        cpu.cycle().unwrap();
+
+        assert_eq!(cpu.registers[4], 0x22);
+    }
+
+    #[test]
+    fn set_vx_to_vx_or_vy() {
+        let mut memory: Memory = Memory::new();
+        let display: Display = Display::new();
+        memory.write_u16(0x200, 0x8011);
+        let mut cpu = Cpu::new(memory, display);
+        cpu.registers[0] = 0x22;
+        cpu.registers[1] = 0x11;
+
+        cpu.cycle().unwrap();
+
+        assert_eq!(cpu.registers[0], 51);
+    }
+
+    #[test]
+    fn set_vx_to_vx_and_vy() {
+        let mut memory: Memory = Memory::new();
+        let display: Display = Display::new();
+        memory.write_u16(0x200, 0x8452);
+        let mut cpu = Cpu::new(memory, display);
+        cpu.registers[4] = 0x12;
+        cpu.registers[5] = 0x11;
+
+        cpu.cycle().unwrap();
+
+        assert_eq!(cpu.registers[4], 16);
+    }
+
+    #[test]
+    fn set_vx_to_vx_xor_vy() {
+        let mut memory: Memory = Memory::new();
+        let display: Display = Display::new();
+        memory.write_u16(0x200, 0x8453);
+        let mut cpu = Cpu::new(memory, display);
+        cpu.registers[4] = 0x12;
+        cpu.registers[5] = 0x11;
+
+        cpu.cycle().unwrap();
+
+        assert_eq!(cpu.registers[4], 3);
+    }
+
+    #[test]
+    fn set_vx_to_vx_plus_vy() {
+        let mut memory: Memory = Memory::new();
+        let display: Display = Display::new();
+        memory.write_u16(0x200, 0x8454);
+        memory.write_u16(0x400, 0x8124);
+
+        let mut cpu = Cpu::new(memory, display);
+        cpu.registers[4] = 0x12;
+        cpu.registers[5] = 0x11;
+
+        cpu.cycle().unwrap();
+
+        assert_eq!(cpu.registers[4], 35);
+        assert_eq!(cpu.registers[VF], 0);
+
+        cpu.registers[1] = 0xFF;
+        cpu.registers[2] = 0xFF;
+        cpu.pc = 0x400;
+
+        cpu.cycle().unwrap();
+
+        assert_eq!(cpu.registers[VF], 1);
+    }
+
+    #[test]
+    fn set_vx_to_vx_minus_vy() {
+        let mut memory: Memory = Memory::new();
+        let display: Display = Display::new();
+        memory.write_u16(0x200, 0x8455);
+        memory.write_u16(0x400, 0x8125);
+
+        let mut cpu = Cpu::new(memory, display);
+        cpu.registers[4] = 0x12;
+        cpu.registers[5] = 0x11;
+
+        cpu.cycle().unwrap();
+
+        assert_eq!(cpu.registers[4], 1);
+        assert_eq!(cpu.registers[VF], 1);
+
+        // equal operands don't borrow
+        cpu.registers[1] = 0xFF;
+        cpu.registers[2] = 0xFF;
+        cpu.pc = 0x400;
+
+        cpu.cycle().unwrap();
+
+        assert_eq!(cpu.registers[1], 0);
+        assert_eq!(cpu.registers[VF], 1);
+    }
+
+    #[test]
+    fn set_vx_to_vx_shr_1() {
+        let mut memory: Memory = Memory::new();
+        let display: Display = Display::new();
+        memory.write_u16(0x200, 0x8456);
+        memory.write_u16(0x400, 0x8126);
+
+        let mut cpu = Cpu::new(memory, display);
+        cpu.registers[4] = 0x12;
+
+        cpu.cycle().unwrap();
+
+        assert_eq!(cpu.registers[VF], 0);
+        assert_eq!(cpu.registers[4], 9);
+
+        cpu.registers[1] = 0xFF;
+        cpu.pc = 0x400;
+
+        cpu.cycle().unwrap();
+
+        assert_eq!(cpu.registers[VF], 1);
+        assert_eq!(cpu.registers[1], 127);
+    }
+
+    #[test]
+    fn set_vx_to_vx_shl_1() {
+        let mut memory: Memory = Memory::new();
+        let display: Display = Display::new();
+        memory.write_u16(0x200, 0x845E);
+        memory.write_u16(0x400, 0x812E);
+
+        let mut cpu = Cpu::new(memory, display);
+        cpu.registers[4] = 0x01;
+
+        cpu.cycle().unwrap();
+
+        assert_eq!(cpu.registers[VF], 0);
+        assert_eq!(cpu.registers[4], 2);
+
+        cpu.registers[1] = 0xFF;
+        cpu.pc = 0x400;
+
+        cpu.cycle().unwrap();
+
+        assert_eq!(cpu.registers[VF], 1);
+    }
+
+    #[test]
+    fn skip_next_instruction_if_vx_not_equals_vy() {
+        let mut memory: Memory = Memory::new();
+        let display: Display = Display::new();
+        memory.write_u16(0x200, 0x9450);
+        memory.write_u16(0x400, 0x9120);
+
+        let mut cpu = Cpu::new(memory, display);
+        cpu.registers[4] = 0x01;
+        cpu.registers[5] = 0x01;
+
+        cpu.cycle().unwrap();
+
+        assert_eq!(cpu.pc, 0x200 + 2);
+
+        cpu.registers[1] = 0x12;
+        cpu.registers[2] = 0x13;
+        cpu.pc = 0x400;
+
+        cpu.cycle().unwrap();
+
+        assert_eq!(cpu.pc, 0x400 + 4);
+    }
+
+    #[test]
+    fn set_i_to_nnn() {
+        let mut memory: Memory = Memory::new();
+        let display: Display = Display::new();
+        memory.write_u16(0x200, 0xA123);
+        let mut cpu = Cpu::new(memory, display);
+
+        cpu.cycle().unwrap();
+
+        assert_eq!(cpu.i, 0x123);
+    }
+
+    #[test]
+    fn jump_to_location_nnn_plus_v0() {
+        let mut memory: Memory = Memory::new();
+        let display: Display = Display::new();
+        memory.write_u16(0x200, 0xB123);
+        let mut cpu = Cpu::new(memory, display);
+        cpu.registers[0] = 1;
+
+        cpu.cycle().unwrap();
+
+        assert_eq!(cpu.pc, 0x124);
+    }
+
+    struct FixedRandom(u8);
+
+    impl RandomSource for FixedRandom {
+        fn next_byte(&mut self) -> u8 {
+            self.0
+        }
+    }
+
+    #[test]
+    fn set_vx_to_random_and_kk() {
+        let mut memory: Memory = Memory::new();
+        let display: Display = Display::new();
+        memory.write_u16(0x200, 0xC30F);
+        memory.write_u16(0x202, 0xC4FF);
+        let mut cpu = Cpu::new(memory, display);
+        cpu.set_random_source(Box::new(FixedRandom(0xFF)));
+
+        cpu.cycle().unwrap();
+        cpu.cycle().unwrap();
+
+        assert_eq!(cpu.registers[3], 0x0F);
+        assert_eq!(cpu.registers[4], 0xFF);
+    }
+
+    #[test]
+    fn same_seed_gives_same_random_values() {
+        let run = |seed: u64| {
+            let mut memory: Memory = Memory::new();
+            let display: Display = Display::new();
+            for register in 0..16u16 {
+                memory.write_u16(0x200 + register * 2, 0xC0FF | register << 8);
+            }
+            let mut cpu = Cpu::new(memory, display);
+            cpu.seed(seed);
+            for _ in 0..16 {
+                cpu.cycle().unwrap();
+            }
+            (0..16).map(|register| cpu.registers[register]).collect::<Vec<u8>>()
+        };
+
+        assert_eq!(run(42), run(42));
+        assert_ne!(run(42), run(43));
+    }
+
+    #[test]
+    fn unsupported_opcode() {
+        let mut memory: Memory = Memory::new();
+        let display: Display = Display::new();
+        memory.write_u16(0x200, 0xFFFF);
+        let mut cpu = Cpu::new(memory, display);
+
+        assert_eq!(cpu.cycle(), Err(Chip8Error::UnknownOpcode { pc: 0x200, opcode: 0xFFFF }));
+    }
+
+    #[test]
+    fn return_with_an_empty_stack() {
+        let mut memory: Memory = Memory::new();
+        let display: Display = Display::new();
+        memory.write_u16(0x200, 0x00EE);
+        let mut cpu = Cpu::new(memory, display);
+
+        assert_eq!(cpu.cycle(), Err(Chip8Error::StackUnderflow { pc: 0x200 }));
+    }
+
+    #[test]
+    fn call_with_a_full_stack() {
+        let mut memory: Memory = Memory::new();
+        let display: Display = Display::new();
+        memory.write_u16(0x200, 0x2200);
+        let mut cpu = Cpu::new(memory, display);
+
+        for _ in 0..16 {
+            cpu.cycle().unwrap();
+        }
+
+        assert_eq!(cpu.cycle(), Err(Chip8Error::StackOverflow { pc: 0x200 }));
+    }
+
+    #[test]
+    fn history_leads_up_to_the_failing_instruction() {
+        let mut memory: Memory = Memory::new();
+        let display: Display = Display::new();
+        memory.write_u16(0x200, 0x6005);
+        memory.write_u16(0x202, 0x2206);
+        memory.write_u16(0x206, 0xFFFF);
+        let mut cpu = Cpu::new(memory, display);
+
+        cpu.cycle().unwrap();
+        cpu.cycle().unwrap();
+        assert!(cpu.cycle().is_err());
+
+        let history: Vec<(u16, u16)> = cpu.history().iter().map(|entry| (entry.pc, entry.opcode)).collect();
+        assert_eq!(history, vec![(0x200, 0x6005), (0x202, 0x2206), (0x206, 0xFFFF)]);
+    }
+
+    #[test]
+    fn tick_timers() {
+        let memory: Memory = Memory::new();
+        let display: Display = Display::new();
+        let mut cpu = Cpu::new(memory, display);
+        cpu.delay = 2;
+        cpu.sound = 1;
+
+        cpu.tick_timers();
+
+        assert_eq!(cpu.delay, 1);
+        assert_eq!(cpu.sound, 0);
+
+        cpu.tick_timers();
+
+        assert_eq!(cpu.delay, 0);
+        assert_eq!(cpu.sound, 0);
+    }
+
+    #[test]
+    fn timers_stop_once_halted_whatever_halted_it() {
+        // LD V0, 2, then RET with nothing to return to, EXIT or JP to itself
+        let roms = [vec![0x60, 0x02, 0x00, 0xEE], vec![0x60, 0x02, 0x00, 0xFD], vec![0x60, 0x02, 0x12, 0x02]];
+        for rom in roms {
+            let mut cpu = Cpu::new(Memory::new(), Display::new());
+            cpu.init(rom);
+            cpu.set_delay_timer(5);
+            cpu.set_sound_timer(3);
+            cpu.cycle().unwrap();
+            assert!(cpu.is_buzzing());
+            let _ = cpu.cycle();
+            assert!(cpu.is_halted());
+
+            cpu.tick_timers();
+            cpu.tick_timers();
+
+            assert_eq!((cpu.delay_timer(), cpu.sound_timer()), (5, 3), "halted {:?}", cpu.halted());
+            assert!(!cpu.is_buzzing());
+        }
+    }
+
+    #[test]
+    fn reset_clears_the_timers_the_wait_and_the_halt() {
+        // LD V0, K
+        let mut cpu = Cpu::new(Memory::new(), Display::new());
+        cpu.init(vec![0xF0, 0x0A]);
+        cpu.set_delay_timer(10);
+        cpu.set_sound_timer(10);
+        assert_eq!(cpu.cycle(), Ok(Flow::WaitingForKey));
+        cpu.halted = Some(Halt::Exited);
+
+        cpu.reset();
+
+        assert_eq!((cpu.delay_timer(), cpu.sound_timer()), (0, 0));
+        assert!(!cpu.is_waiting_for_key());
+        assert!(!cpu.is_halted());
+    }
+
+    #[test]
+    fn the_frame_callback_sees_each_completed_frame() {
+        let mut cpu = Cpu::new(Memory::new(), Display::new());
+        // CLS, then draw the font's 0 at (0, 0)
+        cpu.init(vec![0x00, 0xE0, 0xF0, 0x29, 0xD0, 0x05]);
+        let frames = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let seen = frames.clone();
+        cpu.set_frame_callback(move |display| {
+            let mut rgba = Vec::new();
+            display.to_rgba([0xFF; 4], [0; 4], &mut rgba);
+            seen.lock().unwrap().push(rgba[..4].to_vec());
+        });
+
+        cpu.cycle().unwrap();
+        cpu.tick_timers();
+        cpu.cycle().unwrap();
+        cpu.cycle().unwrap();
+        cpu.tick_timers();
+
+        assert_eq!(*frames.lock().unwrap(), vec![vec![0; 4], vec![0xFF; 4]]);
+    }
+
+    // some test are missing
+
+    #[test]
+    fn set_vx_to_delay() {
+        let mut memory: Memory = Memory::new();
+        let display: Display = Display::new();
+        memory.write_u16(0x200, 0xF107);
+        let mut cpu = Cpu::new(memory, display);
+        cpu.delay = 0x76;
+
+        cpu.cycle().unwrap();
+
+        assert_eq!(cpu.registers[1], 0x76);
+    }
+
+    #[test]
+    fn wait_for_key_press() {
+        let mut memory: Memory = Memory::new();
+        let display: Display = Display::new();
+        memory.write_u16(0x200, 0xF30A);
+        let mut cpu = Cpu::new(memory, display);
+
+        assert_eq!(cpu.cycle(), Ok(Flow::WaitingForKey));
+        assert_eq!(cpu.cycle(), Ok(Flow::WaitingForKey));
+
+        assert!(cpu.waiting_for_input);
+        assert_eq!(cpu.pc, 0x200);
+
+        cpu.keys.press(0xB);
+        assert_eq!(cpu.cycle(), Ok(Flow::Continue));
+
+        assert!(!cpu.waiting_for_input);
+        assert_eq!(cpu.registers[3], 0xB);
+        assert_eq!(cpu.pc, 0x202);
+    }
+
+    #[test]
+    fn wait_for_key_takes_a_tap_within_the_frame_once() {
+        let mut cpu = Cpu::new(Memory::new(), Display::new());
+        // LD V3, K; LD V4, K
+        cpu.init(vec![0xF3, 0x0A, 0xF4, 0x0A]);
+
+        assert_eq!(cpu.cycle(), Ok(Flow::WaitingForKey));
+        cpu.keys.press(0x6);
+        cpu.keys.release(0x6);
+
+        assert_eq!(cpu.cycle(), Ok(Flow::Continue));
+        assert_eq!(cpu.registers[3], 0x6);
+        // the tap went to the first wait
+        assert_eq!(cpu.cycle(), Ok(Flow::WaitingForKey));
+    }
+
+    #[test]
+    fn wait_for_key_release_quirk_stores_the_key_once_let_go_of() {
+        let mut cpu = Cpu::new(Memory::new(), Display::new());
+        cpu.set_quirks(Quirks { fx0a_waits_for_release: true, ..Quirks::default() });
+        // LD V3, K; LD V4, K
+        cpu.init(vec![0xF3, 0x0A, 0xF4, 0x0A]);
+
+        cpu.keys.press(0xB);
+        assert_eq!(cpu.cycle(), Ok(Flow::WaitingForKey));
+        cpu.tick_timers();
+        // another key going down meanwhile doesn't change which one is awaited
+        cpu.keys.press(0x2);
+        assert_eq!(cpu.cycle(), Ok(Flow::WaitingForKey));
+        assert!(cpu.is_waiting_for_key());
+
+        cpu.keys.release(0xB);
+        assert_eq!(cpu.cycle(), Ok(Flow::Continue));
+        assert_eq!(cpu.registers[3], 0xB);
+        assert!(!cpu.is_waiting_for_key());
+
+        // a key pressed and let go of within the frame is taken at once
+        cpu.keys.release(0x2);
+        cpu.tick_timers();
+        cpu.keys.press(0x7);
+        cpu.keys.release(0x7);
+        assert_eq!(cpu.cycle(), Ok(Flow::Continue));
+        assert_eq!(cpu.registers[4], 0x7);
+    }
+
+    #[test]
+    fn skip_if_key_sees_a_tap_until_the_frame_ends() {
+        let mut cpu = Cpu::new(Memory::new(), Display::new());
+        // SKP V0; JP 0x200; JP 0x204
+        cpu.init(vec![0xE0, 0x9E, 0x12, 0x00, 0x12, 0x04]);
+        cpu.keys.press(0x0);
+        cpu.keys.release(0x0);
+
+        cpu.cycle().unwrap();
+        assert_eq!(cpu.pc, 0x204);
+
+        cpu.pc = 0x200;
+        cpu.tick_timers();
+        cpu.cycle().unwrap();
+        assert_eq!(cpu.pc, 0x202);
+    }
+
+    #[test]
+    fn set_delay_to_vx() {
+        let mut memory: Memory = Memory::new();
+        let display: Display = Display::new();
+        memory.write_u16(0x200, 0xF115);
+        let mut cpu = Cpu::new(memory, display);
+        cpu.registers[1] = 0x76;
+
+        cpu.cycle().unwrap();
+
+        assert_eq!(cpu.delay, 0x76);
+    }
+
+    #[test]
+    fn set_sound_to_vx() {
+        let mut memory: Memory = Memory::new();
+        let display: Display = Display::new();
+        memory.write_u16(0x200, 0xF818);
+        let mut cpu = Cpu::new(memory, display);
+        cpu.registers[8] = 0x11;
+
+        cpu.cycle().unwrap();
+
+        assert_eq!(cpu.sound, 0x11);
+    }
+
+    #[test]
+    fn set_i_to_i_plus_vx() {
+        let mut memory: Memory = Memory::new();
+        let display: Display = Display::new();
+        memory.write_u16(0x200, 0xF31E);
+        let mut cpu = Cpu::new(memory, display);
+        cpu.i = 0x05;
+        cpu.registers[3] = 0x11;
+
+        cpu.cycle().unwrap();
+
+        assert_eq!(cpu.i, 22);
+    }
+
+    #[test]
+    fn draw_wraps_the_start_column_on_every_row() {
+        let mut memory: Memory = Memory::new();
+        let display: Display = Display::new();
+        memory.write_u16(0x200, 0xD012);
+        memory.write_u16(0x300, 0x8080);
+        let mut cpu = Cpu::new(memory, display);
+        cpu.registers[0] = 65;
+        cpu.i = 0x300;
+
+        cpu.cycle().unwrap();
+
+        assert_eq!(cpu.display.pixels[1][0], 1);
+        assert_eq!(cpu.display.pixels[1][1], 1);
+    }
+
+    #[test]
+    fn skip_if_key_uses_the_low_nibble_of_vx() {
+        let mut memory: Memory = Memory::new();
+        let display: Display = Display::new();
+        memory.write_u16(0x200, 0xE39E);
+        let mut cpu = Cpu::new(memory, display);
+        cpu.registers[3] = 0xF5;
+        cpu.keys.press(0x5);
+
+        cpu.cycle().unwrap();
+
+        assert_eq!(cpu.pc, 0x204);
+    }
+
+    proptest! {
+        #[test]
+        fn arbitrary_opcodes_execute_or_fail_without_panicking(
+            opcode in any::<u16>(),
+            registers in any::<[u8; 16]>(),
+            i in any::<u16>(),
+            pc in 0u16..0x1000,
+            sp in 0u8..=16,
+            stack in any::<[u16; 16]>(),
+            keys in any::<[bool; 16]>(),
+            strict in any::<bool>(),
+        ) {
+            let mut memory = Memory::new();
+            memory.write_u8(pc, (opcode >> 8) as u8);
+            memory.write_u8(pc + 1, opcode as u8);
+            let mut cpu = Cpu::new(memory, Display::new());
+            if strict {
+                cpu.set_quirks(Quirks {
+                    big_font_digits: BigFontDigits::Error,
+                    machine_code_calls: MachineCodeCalls::Error,
+                    nibble_operands: NibbleOperands::Error,
+                    ..Quirks::default()
+                });
+            }
+            for (index, &value) in registers.iter().enumerate() {
+                cpu.registers[index as u8] = value;
+            }
+            cpu.i = i;
+            cpu.pc = pc;
+            cpu.sp = sp;
+            // return addresses pushed by 2NNN always lie within memory
+            for (slot, &address) in cpu.stack.iter_mut().zip(stack.iter()) {
+                *slot = address & 0xFFF;
+            }
+            cpu.keys.keys = keys;
+
+            match cpu.cycle() {
+                Ok(_) => {}
+                Err(Chip8Error::UnknownOpcode { pc: at, opcode: reported }) => {
+                    prop_assert_eq!(at, pc);
+                    prop_assert_eq!(reported, cpu.opcode_at(pc));
+                }
+                Err(Chip8Error::StackOverflow { pc: at })
+                | Err(Chip8Error::StackUnderflow { pc: at })
+                | Err(Chip8Error::InvalidBigDigit { pc: at, .. })
+                | Err(Chip8Error::InvalidKey { pc: at, .. })
+                | Err(Chip8Error::InvalidDigit { pc: at, .. })
+                | Err(Chip8Error::MachineCodeCall { pc: at, .. }) => prop_assert_eq!(at, pc),
+            }
+        }
+
+        #[test]
+        fn arithmetic_sets_vx_and_vf_from_the_original_operands(
+            registers in any::<[u8; 16]>(),
+            x in prop_oneof![Just(0xFu8), 0u8..16],
+            y in prop_oneof![Just(0xFu8), 0u8..16],
+            same in any::<bool>(),
+            operation in prop_oneof![Just(4u16), Just(5u16), Just(6u16), Just(7u16), Just(0xEu16)],
+        ) {
+            let y = if same { x } else { y };
+            let mut memory = Memory::new();
+            memory.write_u16(0x200, 0x8000 | (x as u16) << 8 | (y as u16) << 4 | operation);
+            let mut cpu = Cpu::new(memory, Display::new());
+            cpu.registers = Registers::from(registers);
+            let (vx, vy) = (registers[x as usize] as i16, registers[y as usize] as i16);
+
+            cpu.cycle().unwrap();
+
+            // the arithmetic done wide, then wrapped back into a byte
+            let (result, flag) = match operation {
+                4 => (vx + vy, vx + vy > 0xFF),
+                5 => (vx - vy, vx >= vy),
+                6 => (vx / 2, vx % 2 == 1),
+                7 => (vy - vx, vy >= vx),
+                _ => (vx * 2, vx >= 0x80),
+            };
+            let mut expected = registers;
+            expected[x as usize] = result.rem_euclid(0x100) as u8;
+            expected[0xF] = flag as u8;
+            prop_assert_eq!(cpu.registers.as_slice(), &expected[..]);
+        }
+    }
+
+    #[test]
+    fn one_pixel_or_register_changes_the_state_hash_and_only_its_component() {
+        let mut cpu = Cpu::new(Memory::new(), Display::new());
+        cpu.init(vec![0x60, 0x01, 0xD0, 0x05]);
+        let (hash, digest) = (cpu.state_hash(), cpu.state_digest());
+        assert_eq!(Cpu::new(Memory::new(), Display::new()).state_hash(), Cpu::new(Memory::new(), Display::new()).state_hash());
+
+        cpu.display.pixels[63][31] ^= PLANE_1;
+        assert_ne!(cpu.state_hash(), hash);
+        assert_eq!(cpu.state_digest(), StateDigest { display: cpu.state_digest().display, ..digest });
+        cpu.display.pixels[63][31] ^= PLANE_1;
+        assert_eq!(cpu.state_hash(), hash);
+
+        cpu.registers[0xA] = 1;
+        assert_ne!(cpu.state_hash(), hash);
+        assert_eq!(cpu.state_digest(), StateDigest { registers: cpu.state_digest().registers, ..digest });
+        cpu.registers[0xA] = 0;
+
+        // what the frontend holds down isn't machine state
+        cpu.keys.press(0x5);
+        assert_eq!(cpu.state_hash(), hash);
+    }
+
+    #[test]
+    fn every_resolution_hashes_differently() {
+        let mut cpu = Cpu::new(Memory::new(), Display::new());
+        let hashes: Vec<u64> = [Resolution::Lores, Resolution::Hires, Resolution::TwoPage, Resolution::Mega]
+            .iter()
+            .map(|&resolution| {
+                cpu.display.set_resolution(resolution);
+                cpu.state_hash()
+            })
+            .collect();
+
+        for (i, hash) in hashes.iter().enumerate() {
+            assert!(!hashes[i + 1..].contains(hash), "{:?}", hashes);
+        }
+    }
+
+    #[test]
+    fn switch_resolution_and_clear() {
+        let mut memory: Memory = Memory::new();
+        let mut display: Display = Display::new();
+        display.pixels[0][0] = 1;
+        memory.write_u16(0x200, 0x00FF);
+        memory.write_u16(0x202, 0x00FE);
+        let mut cpu = Cpu::new(memory, display);
+
+        cpu.cycle().unwrap();
+        assert_eq!(cpu.display.resolution(), Resolution::Hires);
+        assert_eq!(cpu.display.pixels[0][0], 0);
+
+        cpu.display.pixels[127][63] = 1;
+        cpu.cycle().unwrap();
+        assert_eq!(cpu.display.resolution(), Resolution::Lores);
+        assert_eq!(cpu.display.pixels[127][63], 0);
+    }
+
+    #[test]
+    fn draw_in_both_resolutions() {
+        let mut memory: Memory = Memory::new();
+        let display: Display = Display::new();
+        // draw a 1-row sprite at (100, 40): wrapped to (36, 8) in lores, as is in hires
+        memory.write_u16(0x200, 0xD011);
+        memory.write_u16(0x202, 0x00FF);
+        memory.write_u16(0x204, 0xD011);
+        memory.write_u16(0x300, 0xC000);
+        let mut cpu = Cpu::new(memory, display);
+        cpu.registers[0] = 100;
+        cpu.registers[1] = 40;
+        cpu.i = 0x300;
+
+        cpu.cycle().unwrap();
+        assert_eq!(cpu.display.pixels[36][8], 1);
+        assert_eq!(cpu.display.pixels[37][8], 1);
+
+        cpu.cycle().unwrap();
+        cpu.cycle().unwrap();
+        assert_eq!(cpu.display.pixels[36][8], 0);
+        assert_eq!(cpu.display.pixels[100][40], 1);
+        assert_eq!(cpu.display.pixels[101][40], 1);
+        assert_eq!(cpu.display.pixels[102][40], 0);
+    }
+
+    #[test]
+    fn hires_chip8_starts_at_0x2c0_on_a_64x64_screen() {
+        let mut rom = vec![0x12, 0x60];
+        rom.resize(0xC0, 0);
+        // LD V0, 60; LD V1, 40; LD I, 0x2D0; DRW V0, V1, 8; JP 0x2C8; sprite
+        rom.extend_from_slice(&[0x60, 0x3C, 0x61, 0x28, 0xA2, 0xD0, 0xD0, 0x18, 0x12, 0xC8, 0, 0, 0, 0, 0, 0]);
+        rom.extend_from_slice(&[0xFF; 8]);
+        let mut cpu = Cpu::new(Memory::new(), Display::new());
+        cpu.set_quirks(Quirks { hires_chip8: true, ..Quirks::default() });
+        cpu.init(rom);
+
+        assert_eq!(cpu.pc, 0x2C0);
+        for _ in 0..4 {
+            cpu.cycle().unwrap();
+        }
+
+        let ascii = cpu.display.to_ascii();
+        let rows: Vec<&str> = ascii.lines().collect();
+        assert_eq!(rows.len(), 64);
+        assert!(rows.iter().all(|row| row.len() == 64));
+        // rows 40 to 47 are drawn rather than wrapped to the top, and the sprite clips at x = 64
+        assert!(rows[..40].iter().all(|row| !row.contains('#')));
+        assert!(rows[40..48].iter().all(|row| row.ends_with(&format!("{}####", ".".repeat(60)))));
+        assert!(rows[48..].iter().all(|row| !row.contains('#')));
+
+        cpu.memory.write_u16(cpu.pc, 0x0230);
+        cpu.cycle().unwrap();
+        assert!(!cpu.display.to_ascii().contains('#'));
+        assert_eq!(cpu.display.resolution(), Resolution::TwoPage);
+    }
+
+    #[test]
+    fn normal_roms_are_unaffected_by_the_hires_chip8_header() {
+        let mut cpu = Cpu::new(Memory::new(), Display::new());
+        cpu.init(vec![0x12, 0x60]);
+
+        assert_eq!(cpu.pc, 0x200);
+        assert_eq!(cpu.display.resolution(), Resolution::Lores);
+    }
+
+    #[test]
+    fn hires_sprites_clip_at_the_right_edge() {
+        let mut memory: Memory = Memory::new();
+        let display: Display = Display::new();
+        memory.write_u16(0x200, 0x00FF);
+        memory.write_u16(0x202, 0xD011);
+        memory.write_u16(0x300, 0xFF00);
+        let mut cpu = Cpu::new(memory, display);
+        cpu.registers[0] = 124;
+        cpu.i = 0x300;
+
+        cpu.cycle().unwrap();
+        cpu.cycle().unwrap();
+
+        assert_eq!(cpu.display.pixels[127][0], 1);
+        assert_eq!(cpu.display.pixels[0][0], 0);
+    }
+
+    #[test]
+    fn scroll_opcodes() {
+        let mut memory: Memory = Memory::new();
+        let mut display: Display = Display::new();
+        display.pixels[10][10] = 1;
+        memory.write_u16(0x200, 0x00C3);
+        memory.write_u16(0x202, 0x00FB);
+        memory.write_u16(0x204, 0x00FC);
+        memory.write_u16(0x206, 0x00FC);
+        let mut cpu = Cpu::new(memory, display);
+
+        cpu.cycle().unwrap();
+        assert_eq!(cpu.display.pixels[10][13], 1);
+        cpu.cycle().unwrap();
+        assert_eq!(cpu.display.pixels[14][13], 1);
+        cpu.cycle().unwrap();
+        cpu.cycle().unwrap();
+        assert_eq!(cpu.display.pixels[6][13], 1);
+        assert_eq!(cpu.display.to_ascii().matches('#').count(), 1);
+    }
+
+    #[test]
+    fn half_pixel_scroll_quirk_only_applies_in_lores() {
+        let mut memory: Memory = Memory::new();
+        let mut display: Display = Display::new();
+        display.pixels[10][10] = 1;
+        memory.write_u16(0x200, 0x00C3);
+        memory.write_u16(0x202, 0x00FB);
+        memory.write_u16(0x204, 0x00FF);
+        memory.write_u16(0x206, 0x00C3);
+        let mut cpu = Cpu::new(memory, display);
+        cpu.set_quirks(Quirks { half_pixel_lores_scroll: true, ..Quirks::default() });
+
+        cpu.cycle().unwrap();
+        cpu.cycle().unwrap();
+        assert_eq!(cpu.display.pixels[12][11], 1);
+
+        cpu.cycle().unwrap();
+        cpu.display.pixels[10][10] = 1;
+        cpu.cycle().unwrap();
+        assert_eq!(cpu.display.pixels[10][13], 1);
+    }
+
+    #[test]
+    fn draw_16x16_sprite_in_hires() {
+        let mut memory: Memory = Memory::new();
+        let display: Display = Display::new();
+        memory.write_u16(0x200, 0x00FF);
+        memory.write_u16(0x202, 0xD010);
+        memory.write_u16(0x204, 0xD010);
+        for row in 0..16 {
+            memory.write_u16(0x300 + row * 2, 0x8001);
+        }
+        let mut cpu = Cpu::new(memory, display);
+        cpu.i = 0x300;
+
+        cpu.cycle().unwrap();
+        cpu.cycle().unwrap();
+        assert_eq!(cpu.registers[VF], 0);
+        assert_eq!(cpu.display.pixels[0][15], 1);
+        assert_eq!(cpu.display.pixels[15][15], 1);
+        assert_eq!(cpu.display.pixels[16][15], 0);
+        assert_eq!(cpu.display.pixels[0][16], 0);
+
+        cpu.cycle().unwrap();
+        assert_eq!(cpu.registers[VF], 1);
+        assert_eq!(cpu.display.to_ascii().matches('#').count(), 0);
+    }
+
+    #[test]
+    fn draw_big_sprite_in_lores_follows_the_quirk() {
+        let mut memory: Memory = Memory::new();
+        memory.write_u16(0x200, 0xD010);
+        for row in 0..16 {
+            memory.write_u16(0x300 + row * 2, 0xFFFF);
+        }
+        let mut wide = Cpu::new(memory, Display::new());
+        wide.i = 0x300;
+        let mut memory: Memory = Memory::new();
+        memory.write_u16(0x200, 0xD010);
+        for row in 0..16 {
+            memory.write_u16(0x300 + row * 2, 0xFFFF);
+        }
+        let mut tall = Cpu::new(memory, Display::new());
+        tall.i = 0x300;
+        tall.set_quirks(Quirks { lores_big_sprite: LoresBigSprite::Tall8x16, ..Quirks::default() });
+
+        wide.cycle().unwrap();
+        tall.cycle().unwrap();
+
+        assert_eq!(wide.display.to_ascii().matches('#').count(), 16 * 16);
+        assert_eq!(tall.display.to_ascii().matches('#').count(), 8 * 16);
+    }
+
+    #[test]
+    fn fx29_points_at_each_digit_where_the_font_map_put_it() {
+        for &base in &[0x000, 0x050] {
+            // LD F, V0 for every digit
+            let mut cpu = Cpu::new(Memory::new(), Display::new());
+            cpu.relocate_font(base);
+            cpu.init([0xF0, 0x29].repeat(16));
+
+            for digit in 0..16 {
+                cpu.registers[0] = digit;
+                cpu.cycle().unwrap();
+
+                let map = cpu.font_map();
+                assert_eq!(cpu.i, map.addr_of_digit(digit));
+                assert_eq!(cpu.i, base + u16::from(digit) * GLYPH_ROWS);
+                assert_eq!(&cpu.memory().bytes()[cpu.i as usize..][..GLYPH_ROWS as usize], map.glyph(digit));
+            }
+        }
+    }
+
+    #[test]
+    fn a_relocated_font_is_drawn_and_survives_a_reset() {
+        // LD V0, 8; LD F, V0; DRW V1, V1, 5; LD HF, V0
+        let rom = [0x60, 0x08, 0xF0, 0x29, 0xD1, 0x15, 0xF0, 0x30];
+        let mut moved = Cpu::new(Memory::new(), Display::new());
+        moved.relocate_font(0x100);
+        moved.init(rom.to_vec());
+        let mut plain = Cpu::new(Memory::new(), Display::new());
+        plain.init(rom.to_vec());
+
+        for _ in 0..3 {
+            moved.cycle().unwrap();
+            plain.cycle().unwrap();
+        }
+        assert_eq!(moved.i, 0x100 + 8 * GLYPH_ROWS);
+        assert_eq!(moved.display().to_ascii(), plain.display().to_ascii());
+        moved.cycle().unwrap();
+        assert_eq!(moved.i, 0x100 + FONT_BYTES as u16 + 8 * 10);
+        assert_eq!(moved.memory().bytes()[moved.i as usize], 0x3C);
+        // nothing was left where the font used to be
+        assert!(moved.memory().bytes()[..0x100].iter().all(|&byte| byte == 0));
+
+        moved.reset();
+        assert_eq!(&moved.memory().bytes()[0x100..0x100 + FONT_BYTES], &Font::default().small[..]);
+        assert_eq!(moved.font_map().range(), 0x100..0x100 + (FONT_BYTES + BIG_FONT_BYTES) as u16);
+    }
+
+    #[test]
+    fn font_addresses() {
+        let mut cpu = Cpu::new(Memory::new(), Display::new());
+        // LD F, V0; LD HF, V0; LD F, V1; LD HF, V1
+        cpu.init(vec![0xF0, 0x29, 0xF0, 0x30, 0xF1, 0x29, 0xF1, 0x30]);
+        cpu.registers[0] = 0x0;
+        cpu.registers[1] = 0x9;
+
+        let mut addresses = Vec::new();
+        for _ in 0..4 {
+            cpu.cycle().unwrap();
+            addresses.push(cpu.i);
+        }
+
+        assert_eq!(addresses, vec![0x000, 0x050, 0x02D, 0x0AA]);
+        assert_eq!(cpu.memory.read_u8(0x0AA), 0x3C);
+    }
+
+    #[test]
+    fn draw_big_digit_renders_ten_rows() {
+        let mut cpu = Cpu::new(Memory::new(), Display::new());
+        // V0 = 8; LD HF, V0; DRW V1, V1, 10
+        cpu.init(vec![0x60, 0x08, 0xF0, 0x30, 0xD1, 0x1A]);
+        for _ in 0..3 {
+            cpu.cycle().unwrap();
+        }
+
+        let ascii = cpu.display.to_ascii();
+        let rows: Vec<&str> = ascii.lines().map(|line| &line[..8]).collect();
+
+        assert_eq!(&rows[..11], &[
+            "..####..", ".######.", "##....##", "##....##", ".######.",
+            ".######.", "##....##", "##....##", ".######.", "..####..", "........",
+        ]);
+    }
+
+    #[test]
+    fn add_i_overflow_follows_the_quirk() {
+        let add_i = |i: u16, quirk: bool| {
+            let mut cpu = Cpu::new(Memory::new(), Display::new());
+            cpu.init(vec![0xF1, 0x1E]);
+            cpu.set_quirks(Quirks { i_overflow_sets_vf: quirk, ..Quirks::default() });
+            cpu.i = i;
+            cpu.registers[1] = 1;
+            cpu.registers[VF] = 0x55;
+            cpu.cycle().unwrap();
+            (cpu.i, cpu.registers[VF])
+        };
+
+        // reaching 0xFFF isn't an overflow
+        assert_eq!(add_i(0xFFE, false), (0xFFF, 0x55));
+        assert_eq!(add_i(0xFFE, true), (0xFFF, 0));
+        // going past it is
+        assert_eq!(add_i(0xFFF, false), (0x1000, 0x55));
+        assert_eq!(add_i(0xFFF, true), (0x000, 1));
+    }
+
+    #[test]
+    fn shifts_follow_the_quirk() {
+        let shift = |opcode: u16, quirk: bool| {
+            let mut cpu = Cpu::new(Memory::new(), Display::new());
+            cpu.init(opcode.to_be_bytes().to_vec());
+            cpu.set_quirks(Quirks { shift_uses_vy: quirk, ..Quirks::default() });
+            cpu.registers[1] = 0b0000_0110;
+            cpu.registers[2] = 0b1000_0001;
+            cpu.cycle().unwrap();
+            (cpu.registers[1], cpu.registers[2], cpu.registers[VF])
+        };
+
+        // SHR V1, V2
+        assert_eq!(shift(0x8126, false), (0b0000_0011, 0b1000_0001, 0));
+        assert_eq!(shift(0x8126, true), (0b0100_0000, 0b1000_0001, 1));
+        // SHL V1, V2
+        assert_eq!(shift(0x812E, false), (0b0000_1100, 0b1000_0001, 0));
+        assert_eq!(shift(0x812E, true), (0b0000_0010, 0b1000_0001, 1));
+    }
+
+    #[test]
+    fn add_i_overflow_reads_vf_before_setting_it() {
+        let mut cpu = Cpu::new(Memory::new(), Display::new());
+        cpu.init(vec![0xFF, 0x1E]);
+        cpu.set_quirks(Quirks { i_overflow_sets_vf: true, ..Quirks::default() });
+        cpu.i = 0xFF0;
+        cpu.registers[VF] = 0x20;
+
+        cpu.cycle().unwrap();
+
+        assert_eq!((cpu.i, cpu.registers[VF]), (0x010, 1));
+    }
+
+    #[test]
+    fn machine_code_calls_are_skipped_and_counted() {
+        let mut cpu = Cpu::new(Memory::new(), Display::new());
+        // 0123 (machine code); ADD V0, 1; JP 0x200
+        cpu.init(vec![0x01, 0x23, 0x70, 0x01, 0x12, 0x00]);
+
+        for _ in 0..30 {
+            cpu.cycle().unwrap();
+        }
+
+        assert_eq!(cpu.registers[0], 10);
+        assert_eq!(cpu.take_skipped_calls(), Some(SkippedCalls { pc: 0x200, address: 0x123, count: 10 }));
+        assert_eq!(cpu.take_skipped_calls(), None);
+        assert_eq!(
+            SkippedCalls { pc: 0x200, address: 0x123, count: 10 }.to_string(),
+            "skipped 10 calls to machine code, the first to 0x123 from 0x200"
+        );
+    }
+
+    #[test]
+    fn strict_machine_code_calls_halt() {
+        let mut cpu = Cpu::new(Memory::new(), Display::new());
+        cpu.set_quirks(Quirks { machine_code_calls: MachineCodeCalls::Error, ..Quirks::default() });
+        cpu.init(vec![0x00, 0xE0, 0x01, 0x23]);
+
+        cpu.cycle().unwrap();
+
+        assert_eq!(cpu.cycle(), Err(Chip8Error::MachineCodeCall { pc: 0x202, address: 0x123 }));
+        assert_eq!(cpu.halted(), Some(Halt::Error(Chip8Error::MachineCodeCall { pc: 0x202, address: 0x123 })));
+        assert_eq!(cpu.take_skipped_calls(), None);
+    }
+
+    #[test]
+    fn keys_and_digits_above_0xf_are_masked_or_halt_when_strict() {
+        let strict = Quirks { nibble_operands: NibbleOperands::Error, ..Quirks::default() };
+        for &value in &[0x1F, 0xFF] {
+            // SKP V0 and SKNP V0 with key F held, where V0 masks to F
+            for &(opcode, skips) in &[(0xE09E, true), (0xE0A1, false)] {
+                let mut cpu = Cpu::new(Memory::new(), Display::new());
+                cpu.init(vec![(opcode >> 8) as u8, opcode as u8]);
+                cpu.registers[0] = value;
+                cpu.keys.press(0xF);
+                let mut strict_cpu = Cpu::new(Memory::new(), Display::new());
+                strict_cpu.set_quirks(strict);
+                strict_cpu.init(vec![(opcode >> 8) as u8, opcode as u8]);
+                strict_cpu.registers[0] = value;
+
+                cpu.cycle().unwrap();
+
+                assert_eq!(cpu.pc, if skips { 0x204 } else { 0x202 }, "{:04X} with V0 = {:#04X}", opcode, value);
+                assert_eq!(strict_cpu.cycle(), Err(Chip8Error::InvalidKey { pc: 0x200, key: value }));
+            }
+
+            let mut cpu = Cpu::new(Memory::new(), Display::new());
+            cpu.init(vec![0xF0, 0x29]);
+            cpu.registers[0] = value;
+            let mut strict_cpu = Cpu::new(Memory::new(), Display::new());
+            strict_cpu.set_quirks(strict);
+            strict_cpu.init(vec![0xF0, 0x29]);
+            strict_cpu.registers[0] = value;
+
+            cpu.cycle().unwrap();
+
+            assert_eq!(cpu.i, cpu.font_map().addr_of_digit(0xF));
+            assert_eq!(strict_cpu.cycle(), Err(Chip8Error::InvalidDigit { pc: 0x200, digit: value }));
+        }
+    }
+
+    #[test]
+    fn big_digits_above_nine_follow_the_quirk() {
+        let mut wrapping = Cpu::new(Memory::new(), Display::new());
+        wrapping.init(vec![0xF0, 0x30]);
+        wrapping.registers[0] = 13;
+        let mut strict = Cpu::new(Memory::new(), Display::new());
+        strict.init(vec![0xF0, 0x30]);
+        strict.registers[0] = 13;
+        strict.set_quirks(Quirks { big_font_digits: BigFontDigits::Error, ..Quirks::default() });
+
+        wrapping.cycle().unwrap();
+
+        assert_eq!(wrapping.i, 0x050 + 3 * 10);
+        assert_eq!(strict.cycle(), Err(Chip8Error::InvalidBigDigit { pc: 0x200, digit: 13 }));
+    }
+
+    #[test]
+    fn rpl_flags_round_trip() {
+        let mut cpu = Cpu::new(Memory::new(), Display::new());
+        // LD R, V2; then clear V0..V2 and LD V2, R
+        cpu.init(vec![0xF2, 0x75, 0x60, 0x00, 0x61, 0x00, 0x62, 0x00, 0xF2, 0x85]);
+        cpu.registers[0] = 10;
+        cpu.registers[1] = 20;
+        cpu.registers[2] = 30;
+        cpu.registers[3] = 40;
+
+        for _ in 0..5 {
+            cpu.cycle().unwrap();
+        }
+
+        assert_eq!(&cpu.rpl_flags()[..4], &[10, 20, 30, 0]);
+        assert_eq!((cpu.registers[0], cpu.registers[1], cpu.registers[2], cpu.registers[3]), (10, 20, 30, 40));
+    }
+
+    #[test]
+    fn rpl_flags_stop_at_v7() {
+        let mut cpu = Cpu::new(Memory::new(), Display::new());
+        // LD R, VF; LD VF, R
+        cpu.init(vec![0xFF, 0x75, 0xFF, 0x85]);
+        for register in 0..16 {
+            cpu.registers[register] = register + 1;
+        }
+
+        cpu.cycle().unwrap();
+        cpu.registers[0xF] = 0xAA;
+        cpu.set_rpl_flags([0x55; RPL_FLAGS]);
+        cpu.cycle().unwrap();
+
+        assert_eq!(cpu.registers[7], 0x55);
+        assert_eq!(cpu.registers[8], 9);
+        assert_eq!(cpu.registers[0xF], 0xAA);
+    }
+
+    #[test]
+    fn rpl_flags_reach_vf_only_on_xo_chip() {
+        for &(profile, flags) in &[("schip", 8), ("xochip", 16)] {
+            let mut cpu = Cpu::new(Memory::new(), Display::new());
+            cpu.set_quirks(Quirks::profile(profile).unwrap());
+            // LD R, VF; LD VF, R
+            cpu.init(vec![0xFF, 0x75, 0xFF, 0x85]);
+            for register in 0..16 {
+                cpu.registers[register] = register + 1;
+            }
+
+            cpu.cycle().unwrap();
+            for register in 0..16 {
+                cpu.registers[register] = 0xAA;
+            }
+            cpu.cycle().unwrap();
+
+            for register in 0..16u8 {
+                let expected = if register < flags { register + 1 } else { 0xAA };
+                assert_eq!(cpu.registers[register], expected, "{} V{:X}", profile, register);
+                assert_eq!(cpu.rpl_flags()[register as usize], if register < flags { register + 1 } else { 0 }, "{}", profile);
+            }
+        }
+    }
+
+    #[test]
+    fn exit_finishes_and_stops_executing() {
+        let mut memory: Memory = Memory::new();
+        memory.write_u16(0x200, 0x6001);
+        memory.write_u16(0x202, 0x00FD);
+        memory.write_u16(0x204, 0x6002);
+        let mut cpu = Cpu::new(memory, Display::new());
+
+        cpu.cycle().unwrap();
+        cpu.cycle().unwrap();
+        cpu.cycle().unwrap();
+        cpu.cycle().unwrap();
+
+        assert!(cpu.is_finished());
+        assert_eq!(cpu.halted(), Some(Halt::Exited));
+        assert_eq!(cpu.pc, 0x202);
+        assert_eq!(cpu.registers[0], 1);
+        assert_eq!(cpu.history().len(), 2);
+        assert_eq!(cpu.instructions_executed(), 2);
+    }
+
+    #[test]
+    fn a_jump_to_itself_halts() {
+        let mut cpu = Cpu::new(Memory::new(), Display::new());
+        // CLS; JP 0x202
+        cpu.init(vec![0x00, 0xE0, 0x12, 0x02]);
+
+        for _ in 0..10 {
+            cpu.cycle().unwrap();
+        }
+
+        assert_eq!(cpu.halted(), Some(Halt::Looped(0x202)));
+        assert!(!cpu.is_finished());
+        assert_eq!(cpu.pc, 0x202);
+        assert_eq!(cpu.instructions_executed(), 2);
+    }
+
+    #[test]
+    fn a_loop_polling_the_delay_timer_keeps_running() {
+        let mut cpu = Cpu::new(Memory::new(), Display::new());
+        // LD V0, 3; LD DT, V0; loop: LD V0, DT; SE V0, 0; JP loop; LD V1, 1; JP 0x20C
+        cpu.init(vec![0x60, 0x03, 0xF0, 0x15, 0xF0, 0x07, 0x30, 0x00, 0x12, 0x04, 0x61, 0x01, 0x12, 0x0C]);
+
+        for _ in 0..50 {
+            cpu.cycle().unwrap();
+        }
+        assert_eq!(cpu.halted(), None);
+        assert_eq!(cpu.registers[1], 0);
+
+        for _ in 0..3 {
+            cpu.tick_timers();
+            cpu.cycle().unwrap();
+        }
+        for _ in 0..5 {
+            cpu.cycle().unwrap();
+        }
+
+        assert_eq!(cpu.registers[1], 1);
+        assert_eq!(cpu.halted(), Some(Halt::Looped(0x20C)));
+    }
+
+    #[test]
+    fn errors_halt_the_cpu() {
+        let mut memory: Memory = Memory::new();
+        memory.write_u16(0x200, 0x00EE);
+        let mut cpu = Cpu::new(memory, Display::new());
+        let error = Chip8Error::StackUnderflow { pc: 0x200 };
+
+        assert_eq!(cpu.cycle(), Err(error));
+        assert_eq!(cpu.cycle(), Err(error));
+
+        assert_eq!(cpu.halted(), Some(Halt::Error(error)));
+        assert!(!cpu.is_finished());
+        assert_eq!(cpu.pc, 0x202);
+        assert_eq!(cpu.history().len(), 1);
+    }
+
+    #[test]
+    fn plane_select_needs_the_xochip_platform() {
+        let mut memory: Memory = Memory::new();
+        memory.write_u16(0x200, 0xF201);
+        let mut classic = Cpu::new(memory, Display::new());
+        let mut memory: Memory = Memory::new();
+        memory.write_u16(0x200, 0xF201);
+        let mut xochip = Cpu::new(memory, Display::new());
+        xochip.set_quirks(Quirks { platform: Platform::XoChip, ..Quirks::default() });
+
+        assert_eq!(classic.cycle(), Err(Chip8Error::UnknownOpcode { pc: 0x200, opcode: 0xF201 }));
+        xochip.cycle().unwrap();
+
+        assert_eq!(classic.display.selected_planes(), PLANE_1);
+        assert_eq!(xochip.display.selected_planes(), PLANE_2);
+    }
+
+    #[test]
+    fn dual_plane_draw_reads_a_sprite_per_plane() {
+        let mut memory: Memory = Memory::new();
+        // plane 3, draw 1 row twice at (0, 0)
+        memory.write_u16(0x200, 0xF301);
+        memory.write_u16(0x202, 0xD001);
+        memory.write_u16(0x204, 0xD001);
+        memory.write_u16(0x300, 0xC0A0);
+        let mut cpu = Cpu::new(memory, Display::new());
+        cpu.set_quirks(Quirks { platform: Platform::XoChip, ..Quirks::default() });
+        cpu.i = 0x300;
+
+        cpu.cycle().unwrap();
+        cpu.cycle().unwrap();
+
+        assert_eq!(&cpu.display.to_ascii()[..4], "@#+.");
+        assert_eq!(cpu.registers[VF], 0);
+
+        cpu.cycle().unwrap();
+
+        assert_eq!(&cpu.display.to_ascii()[..4], "....");
+        assert_eq!(cpu.registers[VF], 1);
+    }
+
+    fn megachip(program: &[u16]) -> Cpu {
+        let mut cpu = Cpu::new(Memory::new(), Display::new());
+        cpu.set_quirks(Quirks::profile("megachip").unwrap());
+        cpu.init(program.iter().flat_map(|opcode| opcode.to_be_bytes()).collect());
+        cpu
+    }
+
+    #[test]
+    fn megachip_opcodes_need_the_megachip_platform() {
+        let mut memory: Memory = Memory::new();
+        memory.write_u16(0x200, 0x0011);
+        let mut classic = Cpu::new(memory, Display::new());
+        let mut mega = megachip(&[0x0011, 0x0010]);
+
+        classic.cycle().unwrap();
+        mega.cycle().unwrap();
+
+        assert_eq!(classic.display.resolution(), Resolution::Lores);
+        assert_eq!(mega.display.resolution(), Resolution::Mega);
+        assert_eq!(mega.memory.bytes().len(), MEGA_CHIP_MEMORY_SIZE);
+
+        mega.cycle().unwrap();
+
+        assert_eq!(mega.display.resolution(), Resolution::Lores);
+        assert!(mega.display.megachip().is_none());
+    }
+
+    #[test]
+    fn megachip_loads_a_24_bit_i() {
+        let mut cpu = megachip(&[0x0112, 0x3456, 0xA300]);
+
+        cpu.cycle().unwrap();
+
+        assert_eq!((cpu.megachip().i_high, cpu.i, cpu.pc), (0x12, 0x3456, 0x204));
+        assert_eq!(cpu.long_i(), 0x12_3456);
+
+        cpu.cycle().unwrap();
+
+        assert_eq!(cpu.long_i(), 0x300);
+    }
+
+    #[test]
+    fn megachip_sets_sprite_size_collision_colour_and_sound() {
+        let mut cpu = megachip(&[0x0310, 0x0400, 0x0905, 0xA300, 0x0601, 0x0700]);
+        cpu.memory.load(0x300, &[0x1F, 0x40, 0x00, 0x00, 0x10, 0x00]);
+
+        for _ in 0..5 {
+            cpu.cycle().unwrap();
+        }
+
+        assert_eq!((cpu.megachip().sprite_width, cpu.megachip().sprite_height), (16, 256));
+        assert_eq!(cpu.megachip().collision_color, 5);
+        assert_eq!(cpu.megachip().sound, Some(MegaSound { address: 0x306, rate: 8000, length: 16, looping: false }));
+
+        cpu.cycle().unwrap();
+
+        assert_eq!(cpu.megachip().sound, None);
+    }
+
+    #[test]
+    fn megachip_draws_colour_sprites_and_shows_them_on_00e0() {
+        // two 2x1 sprites, the second over the first's colour 2
+        let mut cpu = megachip(&[0x0011, 0x0302, 0x0401, 0x0902, 0xA300, 0xD010, 0xD010, 0x00E0, 0x05C0, 0x00B1]);
+        cpu.memory.load(0x300, &[1, 2]);
+
+        for _ in 0..6 {
+            cpu.cycle().unwrap();
+        }
+        let screen = cpu.display.megachip().unwrap();
+        assert_eq!((screen.drawn_pixel(0, 0), screen.drawn_pixel(1, 0)), (1, 2));
+        assert_eq!(screen.pixel(0, 0), 0);
+        assert_eq!(cpu.registers[VF], 0);
+
+        cpu.cycle().unwrap();
+        assert_eq!(cpu.registers[VF], 1);
+
+        cpu.cycle().unwrap();
+        assert_eq!((cpu.display.pixel(0, 0), cpu.display.pixel(1, 0)), (1, 2));
+
+        cpu.cycle().unwrap();
+        assert_eq!(cpu.display.megachip().unwrap().alpha(), 0xC0);
+
+        // scrolling moves what's being drawn, which 00E0 has just blanked
+        cpu.cycle().unwrap();
+        assert_eq!(cpu.display.pixel(0, 0), 1);
+    }
+
+    #[test]
+    fn megachip_draws_the_font_in_a_colour_of_its_own() {
+        let mut cpu = megachip(&[0x0011, 0x6000, 0xF029, 0xD005, 0x00E0]);
+
+        for _ in 0..5 {
+            cpu.cycle().unwrap();
+        }
+
+        // the top row of 0 is 0xF0
+        let top: Vec<u8> = (0..8).map(|x| cpu.display.pixel(x, 0)).collect();
+        assert_eq!(top, vec![FONT_COLOR, FONT_COLOR, FONT_COLOR, FONT_COLOR, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn audio_pattern_and_pitch_need_the_xochip_platform() {
+        let program = |memory: &mut Memory| {
+            // AUDIO; PITCH V1
+            memory.write_u16(0x200, 0xF002);
+            memory.write_u16(0x202, 0xF13A);
+            for offset in 0..16 {
+                memory.write_u8(0x300 + offset, offset as u8);
+            }
+        };
+        let mut memory: Memory = Memory::new();
+        program(&mut memory);
+        let mut classic = Cpu::new(memory, Display::new());
+        let mut memory: Memory = Memory::new();
+        program(&mut memory);
+        let mut xochip = Cpu::new(memory, Display::new());
+        xochip.set_quirks(Quirks { platform: Platform::XoChip, ..Quirks::default() });
+        for cpu in [&mut classic, &mut xochip].iter_mut() {
+            cpu.i = 0x300;
+            cpu.registers[1] = 100;
+        }
+
+        assert_eq!(classic.cycle(), Err(Chip8Error::UnknownOpcode { pc: 0x200, opcode: 0xF002 }));
+        xochip.cycle().unwrap();
+        xochip.cycle().unwrap();
+
+        assert_eq!(classic.audio_pattern(), &crate::audio::BEEP_PATTERN);
+        assert_eq!(xochip.audio_pattern()[..4], [0, 1, 2, 3]);
+        assert_eq!(xochip.pitch(), 100);
+    }
+
+    fn xochip_cpu(opcodes: &[u16]) -> Cpu {
+        let mut memory: Memory = Memory::new();
+        for (index, &opcode) in opcodes.iter().enumerate() {
+            memory.write_u16(0x200 + index as u16 * 2, opcode);
+        }
+        let mut cpu = Cpu::new(memory, Display::new());
+        cpu.set_quirks(Quirks { platform: Platform::XoChip, ..Quirks::default() });
+        cpu
+    }
+
+    #[test]
+    fn save_and_load_register_ranges() {
+        // SAVE V1 - V3; SAVE V3 - V1 at I + 3 is reached through ADD I
+        let mut cpu = xochip_cpu(&[0x5132, 0xF41E, 0x5312, 0x6100, 0x6200, 0x6300, 0x5133]);
+        cpu.i = 0x300;
+        cpu.registers[1] = 0x11;
+        cpu.registers[2] = 0x22;
+        cpu.registers[3] = 0x33;
+        cpu.registers[4] = 3;
+
+        for _ in 0..3 {
+            cpu.cycle().unwrap();
+        }
+
+        assert_eq!(&cpu.memory.bytes()[0x300..0x306], &[0x11, 0x22, 0x33, 0x33, 0x22, 0x11]);
+        assert_eq!(cpu.i, 0x303);
+
+        for _ in 0..4 {
+            cpu.cycle().unwrap();
+        }
+
+        assert_eq!((cpu.registers[1], cpu.registers[2], cpu.registers[3]), (0x33, 0x22, 0x11));
+        assert_eq!(cpu.i, 0x303);
+    }
+
+    #[test]
+    fn load_range_counting_down() {
+        let mut cpu = xochip_cpu(&[0x5A83]);
+        cpu.i = 0x300;
+        cpu.memory.load(0x300, &[1, 2, 3]);
+
+        cpu.cycle().unwrap();
+
+        assert_eq!((cpu.registers[0xA], cpu.registers[9], cpu.registers[8]), (1, 2, 3));
+    }
+
+    #[test]
+    fn register_ranges_need_the_xochip_platform() {
+        let mut memory: Memory = Memory::new();
+        memory.write_u16(0x200, 0x5122);
+        let mut cpu = Cpu::new(memory, Display::new());
+
+        assert_eq!(cpu.cycle(), Err(Chip8Error::UnknownOpcode { pc: 0x200, opcode: 0x5122 }));
+    }
+
+    // 5121; 812F; FA4B; LD V0, 7; JP 0x200
+    const ILLEGAL_ROM: [u8; 10] = [0x51, 0x21, 0x81, 0x2F, 0xFA, 0x4B, 0x60, 0x07, 0x12, 0x00];
+
+    fn run_illegal_rom(policy: IllegalOpcodes) -> (Cpu, Result<(), Chip8Error>) {
+        let mut cpu = Cpu::new(Memory::new(), Display::new());
+        cpu.init(ILLEGAL_ROM.to_vec());
+        cpu.set_illegal_opcodes(policy);
+        let result = (0..10).try_for_each(|_| cpu.cycle().map(|_| ()));
+        (cpu, result)
+    }
+
+    #[test]
+    fn illegal_opcodes_halt_by_default() {
+        let (cpu, result) = run_illegal_rom(IllegalOpcodes::default());
+
+        assert_eq!(result, Err(Chip8Error::UnknownOpcode { pc: 0x200, opcode: 0x5121 }));
+        assert_eq!(cpu.halted(), Some(Halt::Error(Chip8Error::UnknownOpcode { pc: 0x200, opcode: 0x5121 })));
+        assert_eq!(cpu.pc(), 0x202);
+    }
+
+    #[test]
+    fn illegal_opcodes_can_be_skipped_as_two_byte_no_ops() {
+        let (cpu, result) = run_illegal_rom(IllegalOpcodes::Skip);
+
+        assert_eq!(result, Ok(()));
+        assert_eq!(cpu.halted(), None);
+        assert_eq!(cpu.registers()[0], 7);
+        assert_eq!(cpu.registers()[1], 0);
+        // round the loop twice and back at its start
+        assert_eq!(cpu.pc(), 0x200);
+        assert!(cpu.warned_illegal.is_empty());
+    }
+
+    #[test]
+    fn warn_once_skips_and_remembers_each_opcode_at_each_address() {
+        let (mut cpu, result) = run_illegal_rom(IllegalOpcodes::WarnOnce);
+
+        assert_eq!(result, Ok(()));
+        assert_eq!(cpu.registers()[0], 7);
+        // twice round the loop, but each word was only warned about once
+        assert_eq!(cpu.warned_illegal.iter().copied().collect::<Vec<_>>(), vec![(0x200, 0x5121), (0x202, 0x812F), (0x204, 0xFA4B)]);
+
+        cpu.reset();
+        assert!(cpu.warned_illegal.is_empty());
+        assert_eq!(cpu.illegal_opcodes(), IllegalOpcodes::WarnOnce);
+    }
+
+    #[test]
+    fn xo_chip_plane_and_pitch_are_illegal_elsewhere() {
+        for platform in [Platform::SuperChip, Platform::MegaChip] {
+            for opcode in [0xF101, 0xF13A] {
+                let mut memory: Memory = Memory::new();
+                memory.write_u16(0x200, opcode);
+                let mut cpu = Cpu::new(memory, Display::new());
+                cpu.set_quirks(Quirks { platform, ..Quirks::default() });
+                cpu.set_illegal_opcodes(IllegalOpcodes::Halt);
+
+                assert_eq!(cpu.cycle(), Err(Chip8Error::UnknownOpcode { pc: 0x200, opcode }), "{:04X} on {:?}", opcode, platform);
+            }
+        }
+    }
+
+    #[test]
+    fn the_policy_covers_instructions_from_another_platform() {
+        let mut memory: Memory = Memory::new();
+        memory.write_u16(0x200, 0x5122);
+        let mut cpu = Cpu::new(memory, Display::new());
+        cpu.set_illegal_opcodes(IllegalOpcodes::Skip);
+
+        assert_eq!(cpu.cycle(), Ok(Flow::Continue));
+        assert_eq!(cpu.pc(), 0x202);
+    }
+
+    #[test]
+    fn long_address_reaches_past_4k() {
+        // LD I, 0xABCD; LD V0, [I]
+        let mut cpu = xochip_cpu(&[0xF000, 0xABCD, 0xF065]);
+        cpu.memory.write_u8(0xABCD, 42);
+
+        cpu.cycle().unwrap();
+
+        assert_eq!(cpu.i, 0xABCD);
+        assert_eq!(cpu.pc, 0x204);
+
+        cpu.cycle().unwrap();
+
+        assert_eq!(cpu.registers[0], 42);
+    }
+
+    #[test]
+    fn skips_step_over_a_long_load_whole_on_xo_chip_only() {
+        // each skip with V1, V2 and whether key 5 is held, then whether that takes the skip
+        let cases = [
+            (0x3144, 0x44, 0, false, true),
+            (0x3144, 0x43, 0, false, false),
+            (0x4144, 0x43, 0, false, true),
+            (0x4144, 0x44, 0, false, false),
+            (0x5120, 0x44, 0x44, false, true),
+            (0x5120, 0x44, 0x45, false, false),
+            (0x9120, 0x44, 0x45, false, true),
+            (0x9120, 0x44, 0x44, false, false),
+            (0xE19E, 5, 0, true, true),
+            (0xE19E, 5, 0, false, false),
+            (0xE1A1, 5, 0, false, true),
+            (0xE1A1, 5, 0, true, false),
+        ];
+        for &(opcode, v1, v2, held, taken) in &cases {
+            for &platform in &[Platform::XoChip, Platform::SuperChip] {
+                // the skip; LD I, LONG 0x1234, whose address would run as a jump if landed on
+                let mut cpu = xochip_cpu(&[opcode, 0xF000, 0x1234]);
+                cpu.set_quirks(Quirks { platform, ..Quirks::default() });
+                cpu.registers[1] = v1;
+                cpu.registers[2] = v2;
+                if held {
+                    cpu.keys.press(5);
+                }
+
+                cpu.cycle().unwrap();
+
+                let expected = match (taken, platform) {
+                    (false, _) => 0x202,
+                    (true, Platform::XoChip) => 0x206,
+                    (true, _) => 0x204,
+                };
+                assert_eq!(cpu.pc, expected, "{:04X} on {:?}, taken: {}", opcode, platform, taken);
+            }
+        }
+    }
+
+    #[test]
+    fn rom_loaded_at_the_eti_660_address() {
+        let mut cpu = Cpu::new(Memory::new(), Display::new());
+        // V0 = 5; JP 0x602
+        cpu.init_at(ETI_660_LOAD_ADDRESS, vec![0x60, 0x05, 0x16, 0x02]).unwrap();
+
+        cpu.cycle().unwrap();
+        cpu.cycle().unwrap();
+
+        assert_eq!(cpu.registers[0], 5);
+        assert_eq!(cpu.pc, 0x602);
+        assert_eq!(cpu.opcode_at(0x200), 0x0000);
+        assert_eq!(cpu.memory.bytes()[..5], [0xF0, 0x90, 0x90, 0x90, 0xF0]);
+    }
+
+    #[test]
+    fn oversized_rom_is_rejected() {
+        let mut cpu = Cpu::new(Memory::new(), Display::new());
+
+        let error = cpu.init_at(ETI_660_LOAD_ADDRESS, vec![0; 0xA01]).unwrap_err();
+
+        assert_eq!(error, RomTooLarge { size: 0xA01, load_address: 0x600, capacity: 0xA00 });
+        assert_eq!(error.to_string(), "ROM is 2561 bytes, but only 2560 fit when loading at 0x600");
+        assert!(cpu.init_at(ETI_660_LOAD_ADDRESS, vec![0; 0xA00]).is_ok());
+    }
+
+    #[test]
+    fn load_rom_checks_the_size() {
+        let load = |size: usize| Cpu::new(Memory::new(), Display::new()).load_rom(DEFAULT_LOAD_ADDRESS, &vec![0; size]);
+
+        assert_eq!(load(3583), Ok(()));
+        assert_eq!(load(3584), Ok(()));
+        let too_large = load(3585).unwrap_err();
+        assert_eq!(too_large, RomError::TooLarge(RomTooLarge { size: 3585, load_address: 0x200, capacity: 3584 }));
+        assert_eq!(too_large.to_string(), "ROM is 3585 bytes, but only 3584 fit when loading at 0x200");
+        let empty = load(0).unwrap_err();
+        assert_eq!(empty, RomError::Empty { capacity: 3584 });
+        assert_eq!(empty.to_string(), "ROM is empty: 0 bytes, where up to 3584 fit");
+    }
+
+    #[test]
+    fn load_rom_turns_away_other_kinds_of_file() {
+        let mut cpu = Cpu::new(Memory::new(), Display::new());
+
+        assert_eq!(cpu.load_rom(0x200, b"PK\x03\x04\x14\x00"), Err(RomError::NotBytecode("a zip archive")));
+        assert_eq!(
+            cpu.load_rom(0x200, b"0x00 0xE0\n0x12 0x00\n").unwrap_err().to_string(),
+            "not CHIP-8 bytecode: it looks like text, such as a hex dump"
+        );
+        // printable, but a single line: could be bytecode, so it's let through
+        assert_eq!(cpu.load_rom(0x200, b"jA"), Ok(()));
+        assert_eq!(cpu.opcode_at(0x200), 0x6A41);
+    }
+
+    // CALL 0x206, then from there: LD V3, 0x42; LD I, 0x345; LD DT, V3; LD ST, V3; LD [I], V3;
+    // LD I, 0; DRW V0, V0, 5; LD I, 0x3FF; LD V0, K
+    const EVERYTHING: [u8; 24] = [
+        0x22, 0x06, 0x00, 0x00, 0x00, 0x00, 0x63, 0x42, 0xA3, 0x45, 0xF3, 0x15, 0xF3, 0x18, 0xF3, 0x55, 0xA0, 0x00, 0xD0, 0x05,
+        0xA3, 0xFF, 0xF0, 0x0A,
+    ];
+
+    // a machine that has run `EVERYTHING` up to its FX0A, with a key held
+    fn used() -> Cpu {
+        let mut cpu = Cpu::new(Memory::new(), Display::new());
+        cpu.init(EVERYTHING.to_vec());
+        for _ in 0..10 {
+            cpu.cycle().unwrap();
+        }
+        cpu.keys_mut().press(0x7);
+        assert!(cpu.is_waiting_for_key());
+        cpu
+    }
+
+    #[test]
+    fn reset_clears_every_part_of_the_machine() {
+        let mut cpu = used();
+        assert_ne!((cpu.sp(), cpu.i(), cpu.stack()[0]), (0, 0, 0));
+
+        cpu.reset();
+
+        assert_eq!(cpu.pc(), DEFAULT_LOAD_ADDRESS);
+        assert_eq!(cpu.i(), 0);
+        assert_eq!(cpu.sp(), 0);
+        assert_eq!(cpu.stack, [0; 16]);
+        assert_eq!(cpu.registers().as_slice(), &[0; 16]);
+        assert_eq!((cpu.delay_timer(), cpu.sound_timer()), (0, 0));
+        assert_eq!(cpu.keys().any_pressed(), None);
+        assert_eq!(cpu.keys().any_was_pressed(), None);
+        assert!(!cpu.is_waiting_for_key());
+        assert_eq!(cpu.pressed_key, None);
+        assert!(cpu.display().pixels().iter().flatten().all(|&pixel| pixel == 0));
+        assert_eq!(cpu.display().resolution(), Resolution::Lores);
+        assert_eq!(&cpu.memory().bytes()[..FONT_BYTES], &Font::default().small[..]);
+        assert_eq!(&cpu.memory().bytes()[FONT_BYTES..][..BIG_FONT_BYTES], &Font::default().big[..]);
+        let above_the_fonts = cpu.font_map().range().end as usize;
+        assert!(cpu.memory().bytes()[above_the_fonts..].iter().all(|&byte| byte == 0));
+        assert_eq!(cpu.rom_hash(), fnv1a(&[]));
+        assert!(cpu.history().is_empty());
+        assert_eq!(cpu.halted(), None);
+        assert_eq!(cpu.instructions_executed(), 0);
+        assert_eq!((cpu.audio_pattern(), cpu.pitch()), (&BEEP_PATTERN, DEFAULT_PITCH));
+        assert_eq!(cpu.megachip(), &MegaRegisters::default());
+    }
+
+    #[test]
+    fn reset_leaves_a_machine_like_a_new_one() {
+        let mut fresh = Cpu::new(Memory::new(), Display::new());
+        fresh.init(Vec::new());
+        let mut cpu = used();
+
+        cpu.reset();
+
+        assert_eq!(cpu.state_digest(), fresh.state_digest());
+        assert_eq!(cpu.save_state(), fresh.save_state());
+    }
+
+    #[test]
+    fn reset_keeps_what_the_machine_was_set_up_with() {
+        let mut cpu = Cpu::new(Memory::new(), Display::new());
+        cpu.set_quirks(Quirks { hires_chip8: true, ..Quirks::profile("xochip").unwrap() });
+        cpu.init_at(ETI_660_LOAD_ADDRESS, vec![0x12, 0x00]).unwrap();
+        cpu.set_rpl_flags([1; RPL_FLAGS]);
+        cpu.cycle().unwrap();
+
+        cpu.reset();
+
+        assert_eq!(cpu.pc(), ETI_660_LOAD_ADDRESS + HIRES_CHIP8_ENTRY_OFFSET);
+        assert_eq!(cpu.display().resolution(), Resolution::TwoPage);
+        assert_eq!(cpu.memory().bytes().len(), XO_CHIP_MEMORY_SIZE);
+        assert_eq!(cpu.rpl_flags(), &[1; RPL_FLAGS]);
+        assert!(cpu.quirks().hires_chip8);
+    }
+
+    #[test]
+    fn load_rom_starts_the_new_rom_from_scratch() {
+        let mut cpu = used();
+        let mut fresh = Cpu::new(Memory::new(), Display::new());
+        fresh.load_rom(DEFAULT_LOAD_ADDRESS, &[0x12, 0x00]).unwrap();
+
+        cpu.load_rom(DEFAULT_LOAD_ADDRESS, &[0x12, 0x00]).unwrap();
+
+        assert_eq!(cpu.save_state(), fresh.save_state());
+        assert_eq!(cpu.rom_hash(), fnv1a(&[0x12, 0x00]));
+        // nothing is left of the last ROM past the new one's end
+        assert_eq!(cpu.opcode_at(0x206), 0x0000);
+    }
+
+    #[test]
+    fn a_rom_that_is_turned_away_leaves_the_machine_as_it_was() {
+        let mut cpu = used();
+        let before = cpu.save_state();
+
+        assert!(cpu.load_rom(DEFAULT_LOAD_ADDRESS, &[]).is_err());
+        assert!(cpu.load_rom(DEFAULT_LOAD_ADDRESS, &vec![0; 3585]).is_err());
+
+        assert_eq!(cpu.save_state(), before);
+        assert!(cpu.is_waiting_for_key());
+    }
+
+    #[test]
+    fn custom_font_is_loaded_and_drawn() {
+        let mut bytes = [0; FONT_BYTES];
+        // a hollow square in place of "1"
+        bytes[5..10].copy_from_slice(&[0xF8, 0x88, 0x88, 0x88, 0xF8]);
+        let mut cpu = Cpu::new(Memory::new(), Display::new());
+        cpu.set_font(Font::from_bytes(&bytes).unwrap());
+        // V0 = 1; LD F, V0; DRW V1, V1, 5
+        cpu.init(vec![0x60, 0x01, 0xF0, 0x29, 0xD1, 0x15]);
+
+        for _ in 0..3 {
+            cpu.cycle().unwrap();
+        }
+
+        assert_eq!(cpu.i, 0x005);
+        assert_eq!(&cpu.memory.bytes()[0x005..0x00A], &[0xF8, 0x88, 0x88, 0x88, 0xF8]);
+        // the big font is still the built-in one
+        assert_eq!(cpu.memory.bytes()[0x050], 0x3C);
+        let ascii = cpu.display.to_ascii();
+        let rows: Vec<&str> = ascii.lines().take(6).map(|line| &line[..6]).collect();
+        assert_eq!(rows, vec!["#####.", "#...#.", "#...#.", "#...#.", "#####.", "......"]);
+    }
+}
@@ -0,0 +1,1226 @@
+use std::collections::BTreeSet;
+use std::fmt;
+
+use crate::condition::Condition;
+use crate::cpu::{Cpu, CYCLES_PER_FRAME};
+use crate::error::Chip8Error;
+use crate::frontend::{self, FrameEnd, FrameObserver};
+use crate::instruction::disassemble_on;
+use crate::memory::{AccessKind, MemoryAccess};
+use crate::timing::{FrameBudget, Timing};
+
+/// How many times faster than normal the machine runs while turbo is held.
+pub const DEFAULT_TURBO_MULTIPLIER: u32 = 8;
+
+/// How many instructions a step over or run until return gets before giving up on the
+/// subroutine ever returning: nearly half an hour of a classic ROM at the default speed.
+pub const DEFAULT_STEP_LIMIT: u64 = 1_000_000;
+
+/// How fast the machine runs against the 60 Hz ticks it's driven at. Turbo and slow motion are
+/// one setting, so turning either on turns the other off.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pace {
+    Normal,
+    /// Several whole frames a tick, as many as the turbo multiplier, while the turbo key is held.
+    Turbo,
+    Slow(SlowMotion),
+}
+
+/// The slow-motion speeds, fastest first. Each spreads a frame over several ticks, its timer
+/// tick included, so the timers slow down with the instructions and the ROM sees no difference.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SlowMotion {
+    Half,
+    Tenth,
+    Sixtieth,
+    /// One instruction a tick; the timers tick once a frame's worth have run.
+    OneInstruction,
+}
+
+impl SlowMotion {
+    /// How many ticks a frame is spread over, or `None` for one instruction a tick, where that
+    /// depends on how many instructions the frame runs.
+    pub fn ticks_per_frame(self) -> Option<u32> {
+        match self {
+            SlowMotion::Half => Some(2),
+            SlowMotion::Tenth => Some(10),
+            SlowMotion::Sixtieth => Some(60),
+            SlowMotion::OneInstruction => None,
+        }
+    }
+
+    /// The next speed down; one instruction a tick is as slow as it goes.
+    pub fn slower(self) -> SlowMotion {
+        match self {
+            SlowMotion::Half => SlowMotion::Tenth,
+            SlowMotion::Tenth => SlowMotion::Sixtieth,
+            SlowMotion::Sixtieth | SlowMotion::OneInstruction => SlowMotion::OneInstruction,
+        }
+    }
+
+    /// The next speed up, or `None` for full speed.
+    pub fn faster(self) -> Option<SlowMotion> {
+        match self {
+            SlowMotion::Half => None,
+            SlowMotion::Tenth => Some(SlowMotion::Half),
+            SlowMotion::Sixtieth => Some(SlowMotion::Tenth),
+            SlowMotion::OneInstruction => Some(SlowMotion::Sixtieth),
+        }
+    }
+}
+
+impl fmt::Display for SlowMotion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SlowMotion::Half => write!(f, "1/2x"),
+            SlowMotion::Tenth => write!(f, "1/10x"),
+            SlowMotion::Sixtieth => write!(f, "1/60x"),
+            SlowMotion::OneInstruction => write!(f, "1 instruction per frame"),
+        }
+    }
+}
+
+/// Why the debugger stopped execution in the middle of a frame.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Stop {
+    /// `pc` reached a breakpoint; the instruction at that address has not run yet.
+    Breakpoint(u16),
+    /// `pc` reached a conditional breakpoint while its condition held; `values` are what the
+    /// condition looked at.
+    ConditionalBreakpoint { pc: u16, condition: String, values: String },
+    /// A `break_when` condition became true after the instruction at `pc` ran.
+    Condition { pc: u16, condition: String, values: String },
+    /// The instruction at `pc` touched a watched address; it has already completed.
+    Watchpoint { pc: u16, access: MemoryAccess },
+    /// A step over or run until return ran `instructions` instructions without the subroutine
+    /// returning, and stopped before the one at `pc`.
+    StepLimit { pc: u16, instructions: u64 },
+}
+
+impl fmt::Display for Stop {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Stop::Breakpoint(address) => write!(f, "Breakpoint at {:#05X}", address),
+            Stop::ConditionalBreakpoint { pc, condition, values } => {
+                write!(f, "Breakpoint at {:#05X} with {}: {}", pc, condition, values)
+            }
+            Stop::Condition { pc, condition, values } => write!(f, "{} after {:#05X}: {}", condition, pc, values),
+            Stop::Watchpoint { pc, access } => match access.kind {
+                AccessKind::Read => write!(f, "{:#05X} read {:#05X}: {:02X}", pc, access.address, access.new),
+                AccessKind::Write => write!(
+                    f,
+                    "{:#05X} wrote {:#05X}: {:02X} -> {:02X}",
+                    pc, access.address, access.old, access.new
+                ),
+            },
+            Stop::StepLimit { pc, instructions } => {
+                write!(f, "Gave up at {:#05X} after {} instructions without a return", pc, instructions)
+            }
+        }
+    }
+}
+
+/// Whether the emulator is running and, if not, who paused it. The user's pause, which
+/// breakpoints and watchpoints also set, outlasts focus changes; a pause for losing focus ends
+/// when focus comes back.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PauseState {
+    #[default]
+    Running,
+    PausedByUser,
+    PausedByFocus,
+}
+
+impl PauseState {
+    pub fn is_paused(self) -> bool {
+        self != PauseState::Running
+    }
+
+    /// The user pressing pause: resumes from either kind of pause.
+    pub fn toggled(self) -> PauseState {
+        match self {
+            PauseState::Running => PauseState::PausedByUser,
+            PauseState::PausedByUser | PauseState::PausedByFocus => PauseState::Running,
+        }
+    }
+
+    pub fn focus_lost(self) -> PauseState {
+        match self {
+            PauseState::Running => PauseState::PausedByFocus,
+            paused => paused,
+        }
+    }
+
+    pub fn focus_gained(self) -> PauseState {
+        match self {
+            PauseState::PausedByFocus => PauseState::Running,
+            state => state,
+        }
+    }
+}
+
+/// Execution control layered on top of the Cpu: pausing, single-stepping, frame advance and breakpoints.
+pub struct Debugger {
+    pause: PauseState,
+    cycles_per_frame: usize,
+    timing: Timing,
+    turbo_multiplier: u32,
+    pace: Pace,
+    // in slow motion, the frame that's part way through and how many ticks it has had
+    partial_frame: Option<(FrameBudget, u32)>,
+    step_limit: u64,
+    breakpoints: BTreeSet<u16>,
+    conditional_breakpoints: Vec<(u16, Condition)>,
+    // each with whether it held after the last instruction, so it stops only when it becomes true
+    break_conditions: Vec<(Condition, bool)>,
+    // the breakpoint we last stopped at, so resuming runs its instruction instead of stopping again
+    resume_from: Option<u16>,
+}
+
+impl Default for Debugger {
+    fn default() -> Debugger {
+        Debugger::new()
+    }
+}
+
+impl Debugger {
+    pub fn new() -> Debugger {
+        Debugger {
+            pause: PauseState::Running,
+            cycles_per_frame: CYCLES_PER_FRAME,
+            timing: Timing::Modern,
+            turbo_multiplier: DEFAULT_TURBO_MULTIPLIER,
+            pace: Pace::Normal,
+            partial_frame: None,
+            step_limit: DEFAULT_STEP_LIMIT,
+            breakpoints: BTreeSet::new(),
+            conditional_breakpoints: Vec::new(),
+            break_conditions: Vec::new(),
+            resume_from: None,
+        }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.pause.is_paused()
+    }
+
+    pub fn pause_state(&self) -> PauseState {
+        self.pause
+    }
+
+    /// Pauses or resumes as the user would.
+    pub fn set_paused(&mut self, paused: bool) {
+        self.pause = if paused { PauseState::PausedByUser } else { PauseState::Running };
+    }
+
+    pub fn toggle_pause(&mut self) {
+        self.pause = self.pause.toggled();
+    }
+
+    /// Pauses while the window doesn't have focus, leaving a pause by the user alone.
+    pub fn focus_changed(&mut self, gained: bool) {
+        self.pause = if gained { self.pause.focus_gained() } else { self.pause.focus_lost() };
+    }
+
+    /// How many instructions a frame runs; `CYCLES_PER_FRAME` unless a ROM wants another speed.
+    pub fn set_cycles_per_frame(&mut self, cycles: usize) {
+        self.cycles_per_frame = cycles.max(1);
+    }
+
+    /// How a frame's instructions are counted; with `Timing::Vip` the cycles per frame are ignored.
+    pub fn set_timing(&mut self, timing: Timing) {
+        self.timing = timing;
+    }
+
+    /// How many times faster than normal turbo runs; `DEFAULT_TURBO_MULTIPLIER` unless set.
+    pub fn set_turbo_multiplier(&mut self, multiplier: u32) {
+        self.turbo_multiplier = multiplier.max(1);
+    }
+
+    /// Fast-forwards while the turbo key is held, leaving slow motion, and goes back to normal
+    /// speed when it's released.
+    pub fn set_turbo(&mut self, held: bool) {
+        if held {
+            self.pace = Pace::Turbo;
+        } else if self.pace == Pace::Turbo {
+            self.pace = Pace::Normal;
+        }
+    }
+
+    pub fn is_turbo(&self) -> bool {
+        self.pace == Pace::Turbo
+    }
+
+    /// Slows down to `speed`, leaving turbo, or with `None` goes back to full speed. A frame
+    /// slow motion left part way through is finished by the next one run at whatever speed.
+    pub fn set_slow_motion(&mut self, speed: Option<SlowMotion>) {
+        match speed {
+            Some(speed) => self.pace = Pace::Slow(speed),
+            None if self.slow_motion().is_some() => self.pace = Pace::Normal,
+            None => {}
+        }
+    }
+
+    pub fn slow_motion(&self) -> Option<SlowMotion> {
+        match self.pace {
+            Pace::Slow(speed) => Some(speed),
+            _ => None,
+        }
+    }
+
+    pub fn pace(&self) -> Pace {
+        self.pace
+    }
+
+    /// How many times to call `run_frame` in the next 60th of a second: the turbo multiplier
+    /// while turbo is held, one otherwise. Turbo runs whole frames, so the timers speed up along
+    /// with the instructions, and there's nothing owed to catch up on once it's released; in
+    /// slow motion each call runs a share of a frame instead.
+    pub fn frames_per_tick(&self) -> u32 {
+        if self.is_turbo() {
+            self.turbo_multiplier
+        } else {
+            1
+        }
+    }
+
+    /// How many instructions `step_over` and `run_until_return` run at most.
+    pub fn set_step_limit(&mut self, instructions: u64) {
+        self.step_limit = instructions.max(1);
+    }
+
+    pub fn add_breakpoint(&mut self, address: u16) {
+        self.breakpoints.insert(address);
+    }
+
+    pub fn remove_breakpoint(&mut self, address: u16) -> bool {
+        self.breakpoints.remove(&address)
+    }
+
+    pub fn breakpoints(&self) -> impl Iterator<Item = u16> + '_ {
+        self.breakpoints.iter().copied()
+    }
+
+    /// Breaks at `address` only when `condition` holds there.
+    pub fn add_conditional_breakpoint(&mut self, address: u16, condition: Condition) {
+        self.conditional_breakpoints.push((address, condition));
+    }
+
+    /// Breaks after whichever instruction makes `condition` true, wherever it is.
+    pub fn break_when(&mut self, condition: Condition) {
+        self.break_conditions.push((condition, false));
+    }
+
+    /// The breakpoint at `pc` that stops execution before the instruction there, if any.
+    fn breakpoint_at(&self, cpu: &Cpu, pc: u16) -> Option<Stop> {
+        if self.breakpoints.contains(&pc) {
+            return Some(Stop::Breakpoint(pc));
+        }
+        let (_, condition) = self.conditional_breakpoints.iter().find(|(address, condition)| *address == pc && condition.holds(cpu))?;
+        Some(Stop::ConditionalBreakpoint { pc, condition: condition.to_string(), values: condition.values(cpu) })
+    }
+
+    /// The first `break_when` condition the instruction at `pc` made true, if any.
+    fn condition_after(&mut self, cpu: &Cpu, pc: u16) -> Option<Stop> {
+        let mut stop = None;
+        for (condition, held) in &mut self.break_conditions {
+            let holds = condition.holds(cpu);
+            if holds && !*held && stop.is_none() {
+                stop = Some(Stop::Condition { pc, condition: condition.to_string(), values: condition.values(cpu) });
+            }
+            *held = holds;
+        }
+        stop
+    }
+
+    /// Runs one 60 Hz frame: `cycles_per_frame` instructions, or as many as the VIP timing's
+    /// budget allows, followed by a timer tick; fewer when the display-wait quirk ends the frame
+    /// at a DXYN. Does nothing while paused, so the timers are frozen too. Hitting a breakpoint
+    /// pauses in the middle of the frame, before the instruction at that address and before the
+    /// tick; a watchpoint pauses right after the instruction that touched the watched range. In
+    /// slow motion it runs this tick's share of the frame, and ticks the timers only once the
+    /// whole frame has run.
+    pub fn run_frame(&mut self, cpu: &mut Cpu) -> Result<Option<Stop>, Chip8Error> {
+        if self.is_paused() {
+            return Ok(None);
+        }
+        match self.pace {
+            Pace::Slow(speed) => self.slow_frame(cpu, speed),
+            Pace::Normal | Pace::Turbo => self.frame(cpu),
+        }
+    }
+
+    /// Executes exactly one instruction, ignoring breakpoints and watchpoints; only available while paused.
+    pub fn step(&mut self, cpu: &mut Cpu) -> Result<(), Chip8Error> {
+        if !self.is_paused() {
+            return Ok(());
+        }
+        self.resume_from = None;
+        let result = cpu.cycle();
+        cpu.take_watch_hit();
+        result.map(|_| ())
+    }
+
+    /// Executes one instruction like `step`, except that a 2NNN call runs the whole subroutine,
+    /// stopping once it has returned to the instruction after the call. Only available while
+    /// paused.
+    pub fn step_over(&mut self, cpu: &mut Cpu) -> Result<Option<Stop>, Chip8Error> {
+        if cpu.opcode_at(cpu.pc()) & 0xF000 != 0x2000 {
+            return self.step(cpu).map(|()| None);
+        }
+        let (return_address, depth) = (cpu.pc().wrapping_add(2), cpu.sp());
+        // a recursive call passes the return address again, but deeper in the stack
+        self.run_until(cpu, |cpu| cpu.pc() == return_address && cpu.sp() == depth)
+    }
+
+    /// Runs until the 00EE that returns from the current subroutine has executed. Does nothing
+    /// outside a subroutine; only available while paused.
+    pub fn run_until_return(&mut self, cpu: &mut Cpu) -> Result<Option<Stop>, Chip8Error> {
+        let depth = cpu.sp();
+        if depth == 0 {
+            return Ok(None);
+        }
+        self.run_until(cpu, |cpu| cpu.sp() < depth)
+    }
+
+    /// Runs until `done` is true after an instruction, the program halts or `step_limit`
+    /// instructions have run, whichever comes first; breakpoints and watchpoints still stop it.
+    /// The timers tick at the end of every frame's worth of instructions, so a subroutine waiting
+    /// on the delay timer gets somewhere.
+    fn run_until(&mut self, cpu: &mut Cpu, done: impl Fn(&Cpu) -> bool) -> Result<Option<Stop>, Chip8Error> {
+        if !self.is_paused() {
+            return Ok(None);
+        }
+        self.resume_from = None;
+        let (timing, cycles_per_frame) = (self.timing, self.cycles_per_frame);
+        let mut observer = Until { debugger: self, done, executed: 0 };
+        loop {
+            match frontend::run_instructions(cpu, &mut FrameBudget::new(timing, cycles_per_frame), &mut observer)? {
+                FrameEnd::Stopped(stop) => return Ok(stop),
+                FrameEnd::Finished if cpu.halted().is_some() => return Ok(None),
+                FrameEnd::Finished => {}
+            }
+        }
+    }
+
+    /// Runs one full frame, timer tick included, and stays paused; only available while paused.
+    pub fn advance_frame(&mut self, cpu: &mut Cpu) -> Result<Option<Stop>, Chip8Error> {
+        if !self.is_paused() {
+            return Ok(None);
+        }
+        self.frame(cpu)
+    }
+
+    fn frame(&mut self, cpu: &mut Cpu) -> Result<Option<Stop>, Chip8Error> {
+        let mut budget = match self.partial_frame.take() {
+            Some((budget, _)) => budget,
+            None => FrameBudget::new(self.timing, self.cycles_per_frame),
+        };
+        match self.run_slice(cpu, &mut budget, Slice::Whole)? {
+            Progress::Stopped(stop) => Ok(Some(stop)),
+            Progress::FrameOver | Progress::SliceOver => Ok(None),
+        }
+    }
+
+    // one tick of slow motion: the share of the frame `speed` gives it
+    fn slow_frame(&mut self, cpu: &mut Cpu, speed: SlowMotion) -> Result<Option<Stop>, Chip8Error> {
+        let (mut budget, ticks) = self.partial_frame.take().unwrap_or_else(|| (FrameBudget::new(self.timing, self.cycles_per_frame), 0));
+        let slice = match speed.ticks_per_frame() {
+            Some(ticks_per_frame) => Slice::Share(ticks + 1, ticks_per_frame),
+            None => Slice::OneInstruction,
+        };
+        match self.run_slice(cpu, &mut budget, slice)? {
+            Progress::SliceOver => {
+                self.partial_frame = Some((budget, ticks + 1));
+                Ok(None)
+            }
+            Progress::FrameOver => Ok(None),
+            Progress::Stopped(stop) => Ok(Some(stop)),
+        }
+    }
+
+    /// Runs the frame `budget` is what's left of until `slice` is done or the frame is, and
+    /// then ticks the timers. Stopping at a breakpoint, watchpoint or condition pauses and
+    /// abandons the rest of the frame.
+    fn run_slice(&mut self, cpu: &mut Cpu, budget: &mut FrameBudget, slice: Slice) -> Result<Progress, Chip8Error> {
+        match frontend::run_instructions(cpu, budget, &mut SliceRun { debugger: self, slice, executed: 0 })? {
+            FrameEnd::Finished => Ok(Progress::FrameOver),
+            FrameEnd::Stopped(progress) => Ok(progress),
+        }
+    }
+}
+
+// `run_slice` looking on as the frame loop runs its slice
+struct SliceRun<'a> {
+    debugger: &'a mut Debugger,
+    slice: Slice,
+    executed: u32,
+}
+
+impl FrameObserver for SliceRun<'_> {
+    type Stop = Progress;
+    type Error = Chip8Error;
+
+    fn before(&mut self, cpu: &mut Cpu, budget: &FrameBudget) -> Result<Option<Progress>, Chip8Error> {
+        match self.slice {
+            Slice::Share(numerator, denominator) if budget.has_spent(numerator, denominator) => return Ok(Some(Progress::SliceOver)),
+            Slice::OneInstruction if self.executed == 1 => return Ok(Some(Progress::SliceOver)),
+            _ => {}
+        }
+        self.executed += 1;
+        let pc = cpu.pc();
+        if self.debugger.resume_from.take() != Some(pc) {
+            if let Some(stop) = self.debugger.breakpoint_at(cpu, pc) {
+                self.debugger.pause = PauseState::PausedByUser;
+                self.debugger.resume_from = Some(pc);
+                return Ok(Some(Progress::Stopped(stop)));
+            }
+        }
+        Ok(None)
+    }
+
+    fn after(&mut self, cpu: &mut Cpu, pc: u16, _frame_ended: bool) -> Result<Option<Progress>, Chip8Error> {
+        let stop = match cpu.take_watch_hit() {
+            Some(access) => Some(Stop::Watchpoint { pc, access }),
+            None => self.debugger.condition_after(cpu, pc),
+        };
+        if stop.is_some() {
+            self.debugger.pause = PauseState::PausedByUser;
+        }
+        Ok(stop.map(Progress::Stopped))
+    }
+}
+
+// `run_until` looking on as the frame loop runs instruction after instruction; it stops with
+// `None` once `done`
+struct Until<'a, F> {
+    debugger: &'a mut Debugger,
+    done: F,
+    executed: u64,
+}
+
+impl<F: Fn(&Cpu) -> bool> FrameObserver for Until<'_, F> {
+    type Stop = Option<Stop>;
+    type Error = Chip8Error;
+
+    fn before(&mut self, cpu: &mut Cpu, _budget: &FrameBudget) -> Result<Option<Option<Stop>>, Chip8Error> {
+        let pc = cpu.pc();
+        if self.executed == self.debugger.step_limit {
+            return Ok(Some(Some(Stop::StepLimit { pc, instructions: self.debugger.step_limit })));
+        }
+        // like resuming, it runs the instruction it's paused at even if that's a breakpoint
+        if self.executed > 0 {
+            if let Some(stop) = self.debugger.breakpoint_at(cpu, pc) {
+                self.debugger.resume_from = Some(pc);
+                return Ok(Some(Some(stop)));
+            }
+        }
+        self.executed += 1;
+        Ok(None)
+    }
+
+    fn after(&mut self, cpu: &mut Cpu, pc: u16, _frame_ended: bool) -> Result<Option<Option<Stop>>, Chip8Error> {
+        if let Some(access) = cpu.take_watch_hit() {
+            return Ok(Some(Some(Stop::Watchpoint { pc, access })));
+        }
+        if let Some(stop) = self.debugger.condition_after(cpu, pc) {
+            return Ok(Some(Some(stop)));
+        }
+        Ok((self.done)(cpu).then_some(None))
+    }
+}
+
+/// How much of a frame `run_slice` runs.
+#[derive(Debug, Clone, Copy)]
+enum Slice {
+    Whole,
+    /// Up to where `numerator / denominator` of the frame has been spent.
+    Share(u32, u32),
+    OneInstruction,
+}
+
+// where `run_slice` got to
+enum Progress {
+    FrameOver,
+    SliceOver,
+    Stopped(Stop),
+}
+
+/// Describes where execution stopped: the disassembled instruction at `pc` and the register file.
+pub fn describe(cpu: &Cpu) -> String {
+    let opcode = cpu.opcode_at(cpu.pc());
+    let platform = cpu.quirks().platform;
+    let mut description = format!("{:#05X}: {:04X}  {}\n", cpu.pc(), opcode, disassemble_on(opcode, platform));
+    for register in 0..16u8 {
+        let separator = if register % 8 == 7 { '\n' } else { ' ' };
+        description.push_str(&format!("V{:X}={:02X}{}", register, cpu.registers()[register], separator));
+    }
+    description.push_str(&format!(
+        "I={:#05X} SP={} DT={:02X} ST={:02X}\n",
+        cpu.i(),
+        cpu.sp(),
+        cpu.delay_timer(),
+        cpu.sound_timer()
+    ));
+    description
+}
+
+/// The lines of the on-screen debug overlay: pc, I and the font glyph it points at if any, sp,
+/// timers, registers, the call stack and the current and next instruction.
+pub fn overlay(cpu: &Cpu) -> Vec<String> {
+    let font = cpu.font_map();
+    let glyph = match (font.digit_at(cpu.i()), font.big_digit_at(cpu.i())) {
+        (Some(digit), _) => format!(" (digit {:X})", digit),
+        (_, Some(digit)) => format!(" (big digit {})", digit),
+        (None, None) => String::new(),
+    };
+    let mut lines = vec![
+        format!("PC {:#05X}  I {:#05X}{}", cpu.pc(), cpu.i(), glyph),
+        format!("SP {:<4} DT {:02X} ST {:02X}", cpu.sp(), cpu.delay_timer(), cpu.sound_timer()),
+    ];
+    for row in 0..4u8 {
+        let registers: Vec<String> = (row * 4..row * 4 + 4)
+            .map(|register| format!("V{:X} {:02X}", register, cpu.registers()[register]))
+            .collect();
+        lines.push(registers.join(" "));
+    }
+
+    if cpu.stack().is_empty() {
+        lines.push(String::from("Stack empty"));
+    } else {
+        lines.push(String::from("Stack"));
+        for addresses in cpu.stack().chunks(4) {
+            let addresses: Vec<String> = addresses.iter().map(|address| format!("{:#05X}", address)).collect();
+            lines.push(format!("  {}", addresses.join(" ")));
+        }
+    }
+
+    for (marker, address) in [('>', cpu.pc()), (' ', cpu.pc().wrapping_add(2))].iter() {
+        let opcode = cpu.opcode_at(*address);
+        lines.push(format!("{} {:#05X} {:04X} {}", marker, address, opcode, disassemble_on(opcode, cpu.quirks().platform)));
+    }
+    lines
+}
+
+/// The overlay's history page: a header and the last `rows` executed instructions and edits made
+/// by hand, newest last.
+pub fn history_page(cpu: &Cpu, rows: usize) -> Vec<String> {
+    let history = cpu.history();
+    let mut lines = vec![String::from("History")];
+    lines.extend(
+        history
+            .iter()
+            .skip(history.len().saturating_sub(rows))
+            .map(|entry| match entry.edit {
+                Some(change) => format!("{:#05X} set {} by hand", entry.pc, change),
+                None => format!("{:#05X} {}", entry.pc, disassemble_on(entry.opcode, cpu.quirks().platform)),
+            }),
+    );
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::display::Display;
+    use crate::memory::{Memory, WatchMode, Watchpoint};
+    use crate::quirks::Quirks;
+
+    fn cpu() -> Cpu {
+        // sets the delay timer, then counts V1 up forever
+        let rom = vec![0x60, 0x30, 0xF0, 0x15, 0x71, 0x01, 0x12, 0x04];
+        let mut cpu = Cpu::new(Memory::new(), Display::new());
+        cpu.init(rom);
+        cpu
+    }
+
+    #[test]
+    fn focus_pauses_only_what_it_paused() {
+        let mut debugger = Debugger::new();
+
+        debugger.focus_changed(false);
+        assert_eq!(debugger.pause_state(), PauseState::PausedByFocus);
+        debugger.focus_changed(false);
+        assert_eq!(debugger.pause_state(), PauseState::PausedByFocus);
+        debugger.focus_changed(true);
+        assert_eq!(debugger.pause_state(), PauseState::Running);
+
+        // a pause by the user survives losing and regaining focus
+        debugger.toggle_pause();
+        debugger.focus_changed(false);
+        debugger.focus_changed(true);
+        assert_eq!(debugger.pause_state(), PauseState::PausedByUser);
+        debugger.toggle_pause();
+        assert_eq!(debugger.pause_state(), PauseState::Running);
+
+        // pausing by hand while unfocused resumes rather than stacking another pause
+        debugger.focus_changed(false);
+        debugger.toggle_pause();
+        assert_eq!(debugger.pause_state(), PauseState::Running);
+        debugger.focus_changed(true);
+        assert_eq!(debugger.pause_state(), PauseState::Running);
+    }
+
+    fn run_tick(debugger: &mut Debugger, cpu: &mut Cpu) {
+        for _ in 0..debugger.frames_per_tick() {
+            debugger.run_frame(cpu).unwrap();
+        }
+    }
+
+    #[test]
+    fn display_wait_ends_the_frame_at_each_draw() {
+        // DRW V0, V0, 5 twice, then loop
+        let draw_twice = || {
+            let mut cpu = Cpu::new(Memory::new(), Display::new());
+            cpu.init(vec![0xD0, 0x05, 0xD0, 0x05, 0x12, 0x04]);
+            cpu
+        };
+        let mut debugger = Debugger::new();
+        debugger.set_cycles_per_frame(10);
+
+        // without the quirk both draws happen in the first frame
+        let mut plain = draw_twice();
+        debugger.run_frame(&mut plain).unwrap();
+        assert!(plain.instructions_executed() > 2);
+
+        let mut cpu = draw_twice();
+        cpu.set_quirks(Quirks { display_wait: true, ..Quirks::default() });
+        debugger.run_frame(&mut cpu).unwrap();
+        assert_eq!((cpu.instructions_executed(), cpu.pc()), (1, 0x202));
+        assert!(cpu.display().pixels().iter().flatten().any(|&pixel| pixel != 0));
+        debugger.run_frame(&mut cpu).unwrap();
+        assert_eq!((cpu.instructions_executed(), cpu.pc()), (2, 0x204));
+        // the second draw erased the first
+        assert!(cpu.display().pixels().iter().flatten().all(|&pixel| pixel == 0));
+    }
+
+    #[test]
+    fn vip_timing_runs_what_fits_in_the_frame_and_still_ticks_at_60_hz() {
+        let mut cpu = cpu();
+        let mut debugger = Debugger::new();
+        debugger.set_timing(Timing::Vip);
+
+        for _ in 0..30 {
+            debugger.run_frame(&mut cpu).unwrap();
+        }
+
+        // 29333 cycles a frame at 4540 an instruction, the last running over
+        assert_eq!(cpu.instructions_executed(), 30 * 7);
+        assert_eq!(cpu.delay_timer(), 0x30 - 30);
+    }
+
+    #[test]
+    fn turbo_runs_the_instructions_and_timers_faster() {
+        let mut cpu = cpu();
+        let mut debugger = Debugger::new();
+        debugger.set_cycles_per_frame(10);
+        debugger.set_turbo_multiplier(4);
+        run_tick(&mut debugger, &mut cpu);
+        assert_eq!(cpu.delay_timer(), 0x2F);
+
+        debugger.set_turbo(true);
+        assert_eq!(debugger.frames_per_tick(), 4);
+        run_tick(&mut debugger, &mut cpu);
+
+        assert_eq!(cpu.instructions_executed(), 50);
+        assert_eq!(cpu.delay_timer(), 0x2B);
+    }
+
+    #[test]
+    fn releasing_turbo_goes_straight_back_to_normal_speed() {
+        let mut cpu = cpu();
+        let mut debugger = Debugger::new();
+        debugger.set_cycles_per_frame(10);
+        debugger.set_turbo(true);
+        for _ in 0..3 {
+            run_tick(&mut debugger, &mut cpu);
+        }
+        let (instructions, delay) = (cpu.instructions_executed(), cpu.delay_timer());
+
+        debugger.set_turbo(false);
+        assert_eq!(debugger.frames_per_tick(), 1);
+        run_tick(&mut debugger, &mut cpu);
+
+        // no backlog from the turbo frames: exactly one frame's worth
+        assert_eq!(cpu.instructions_executed(), instructions + 10);
+        assert_eq!(cpu.delay_timer(), delay - 1);
+    }
+
+    #[test]
+    fn slow_motion_spreads_a_frame_and_its_timer_tick_over_several_ticks() {
+        for &(speed, ticks_per_frame) in &[(SlowMotion::Half, 2), (SlowMotion::Tenth, 10), (SlowMotion::Sixtieth, 60), (SlowMotion::OneInstruction, 10)] {
+            let mut cpu = cpu();
+            let mut debugger = Debugger::new();
+            debugger.set_cycles_per_frame(10);
+            debugger.set_slow_motion(Some(speed));
+            assert_eq!(debugger.frames_per_tick(), 1);
+
+            for _ in 0..ticks_per_frame / 2 {
+                run_tick(&mut debugger, &mut cpu);
+            }
+            assert_eq!(cpu.instructions_executed(), 5, "{}", speed);
+            assert_eq!(cpu.delay_timer(), 0x30, "{}", speed);
+
+            for _ in 0..ticks_per_frame / 2 {
+                run_tick(&mut debugger, &mut cpu);
+            }
+            assert_eq!(cpu.instructions_executed(), 10, "{}", speed);
+            assert_eq!(cpu.delay_timer(), 0x2F, "{}", speed);
+
+            for _ in 0..ticks_per_frame {
+                run_tick(&mut debugger, &mut cpu);
+            }
+            assert_eq!((cpu.instructions_executed(), cpu.delay_timer()), (20, 0x2E), "{}", speed);
+        }
+    }
+
+    #[test]
+    fn one_instruction_a_tick_follows_the_frame_length() {
+        let mut cpu = cpu();
+        let mut debugger = Debugger::new();
+        debugger.set_cycles_per_frame(4);
+        debugger.set_slow_motion(Some(SlowMotion::OneInstruction));
+
+        for _ in 0..3 {
+            run_tick(&mut debugger, &mut cpu);
+        }
+        assert_eq!((cpu.instructions_executed(), cpu.delay_timer()), (3, 0x30));
+
+        run_tick(&mut debugger, &mut cpu);
+        assert_eq!((cpu.instructions_executed(), cpu.delay_timer()), (4, 0x2F));
+    }
+
+    #[test]
+    fn turbo_and_slow_motion_replace_each_other() {
+        let mut debugger = Debugger::new();
+        debugger.set_slow_motion(Some(SlowMotion::Tenth));
+
+        debugger.set_turbo(true);
+        assert_eq!(debugger.pace(), Pace::Turbo);
+        assert_eq!(debugger.slow_motion(), None);
+
+        debugger.set_slow_motion(Some(SlowMotion::Half));
+        assert_eq!(debugger.pace(), Pace::Slow(SlowMotion::Half));
+        assert!(!debugger.is_turbo());
+        assert_eq!(debugger.frames_per_tick(), 1);
+
+        // releasing the turbo key once slow motion took over leaves slow motion on
+        debugger.set_turbo(false);
+        assert_eq!(debugger.pace(), Pace::Slow(SlowMotion::Half));
+
+        // and going back to full speed leaves turbo alone
+        debugger.set_turbo(true);
+        debugger.set_slow_motion(None);
+        assert_eq!(debugger.pace(), Pace::Turbo);
+        debugger.set_turbo(false);
+        assert_eq!(debugger.pace(), Pace::Normal);
+    }
+
+    #[test]
+    fn slow_motion_steps_through_its_speeds() {
+        assert_eq!(SlowMotion::Half.slower(), SlowMotion::Tenth);
+        assert_eq!(SlowMotion::Sixtieth.slower(), SlowMotion::OneInstruction);
+        assert_eq!(SlowMotion::OneInstruction.slower(), SlowMotion::OneInstruction);
+        assert_eq!(SlowMotion::OneInstruction.faster(), Some(SlowMotion::Sixtieth));
+        assert_eq!(SlowMotion::Half.faster(), None);
+        assert_eq!(SlowMotion::Tenth.to_string(), "1/10x");
+    }
+
+    #[test]
+    fn leaving_slow_motion_finishes_the_frame_without_catching_up() {
+        let mut cpu = cpu();
+        let mut debugger = Debugger::new();
+        debugger.set_cycles_per_frame(10);
+        debugger.set_slow_motion(Some(SlowMotion::Tenth));
+        for _ in 0..23 {
+            run_tick(&mut debugger, &mut cpu);
+        }
+        assert_eq!((cpu.instructions_executed(), cpu.delay_timer()), (23, 0x2E));
+
+        debugger.set_slow_motion(None);
+        run_tick(&mut debugger, &mut cpu);
+
+        // the rest of the frame slow motion was part way through, and nothing more
+        assert_eq!((cpu.instructions_executed(), cpu.delay_timer()), (30, 0x2D));
+        run_tick(&mut debugger, &mut cpu);
+        assert_eq!((cpu.instructions_executed(), cpu.delay_timer()), (40, 0x2C));
+    }
+
+    /// A paused debugger on a ROM whose main program calls a subroutine that calls another.
+    fn nested_calls() -> (Debugger, Cpu) {
+        let rom = vec![
+            0x22, 0x06, // 0x200 CALL 0x206
+            0x61, 0x01, // 0x202 LD V1, 0x01
+            0x12, 0x04, // 0x204 JP 0x204
+            0x22, 0x0C, // 0x206 CALL 0x20C
+            0x72, 0x01, // 0x208 ADD V2, 0x01
+            0x00, 0xEE, // 0x20A RET
+            0x73, 0x01, // 0x20C ADD V3, 0x01
+            0x00, 0xEE, // 0x20E RET
+        ];
+        let mut cpu = Cpu::new(Memory::new(), Display::new());
+        cpu.init(rom);
+        let mut debugger = Debugger::new();
+        debugger.set_paused(true);
+        (debugger, cpu)
+    }
+
+    #[test]
+    fn step_over_runs_the_whole_call() {
+        let (mut debugger, mut cpu) = nested_calls();
+
+        assert_eq!(debugger.step_over(&mut cpu).unwrap(), None);
+        assert_eq!((cpu.pc(), cpu.sp()), (0x202, 0));
+        assert_eq!((cpu.registers()[2], cpu.registers()[3]), (1, 1));
+
+        // anything but a call is a single step
+        assert_eq!(debugger.step_over(&mut cpu).unwrap(), None);
+        assert_eq!(cpu.pc(), 0x204);
+        assert!(debugger.is_paused());
+    }
+
+    #[test]
+    fn run_until_return_finishes_the_current_subroutine() {
+        let (mut debugger, mut cpu) = nested_calls();
+        debugger.step(&mut cpu).unwrap();
+
+        assert_eq!(debugger.run_until_return(&mut cpu).unwrap(), None);
+        assert_eq!((cpu.pc(), cpu.sp()), (0x202, 0));
+        assert_eq!(cpu.registers()[2], 1);
+
+        // there's nothing to return from in the main program
+        let executed = cpu.instructions_executed();
+        assert_eq!(debugger.run_until_return(&mut cpu).unwrap(), None);
+        assert_eq!(cpu.instructions_executed(), executed);
+    }
+
+    #[test]
+    fn breakpoints_inside_the_subroutine_win() {
+        let (mut debugger, mut cpu) = nested_calls();
+        debugger.add_breakpoint(0x20C);
+
+        assert_eq!(debugger.step_over(&mut cpu).unwrap(), Some(Stop::Breakpoint(0x20C)));
+        assert_eq!((cpu.pc(), cpu.sp()), (0x20C, 2));
+
+        // and from there, run until return carries on past the breakpoint
+        assert_eq!(debugger.run_until_return(&mut cpu).unwrap(), None);
+        assert_eq!((cpu.pc(), cpu.sp()), (0x208, 1));
+    }
+
+    #[test]
+    fn a_subroutine_that_never_returns_hits_the_step_limit() {
+        // CALL 0x204; ADD V0, 0x01 and JP 0x204 forever
+        let mut cpu = Cpu::new(Memory::new(), Display::new());
+        cpu.init(vec![0x22, 0x04, 0x00, 0xE0, 0x70, 0x01, 0x12, 0x04]);
+        let mut debugger = Debugger::new();
+        debugger.set_paused(true);
+        debugger.set_step_limit(101);
+
+        assert_eq!(debugger.step_over(&mut cpu).unwrap(), Some(Stop::StepLimit { pc: 0x204, instructions: 101 }));
+        assert_eq!(cpu.instructions_executed(), 101);
+        assert_eq!(cpu.registers()[0], 50);
+        assert!(debugger.is_paused());
+    }
+
+    #[test]
+    fn a_turbo_multiplier_below_one_still_runs() {
+        let mut debugger = Debugger::new();
+        debugger.set_turbo_multiplier(0);
+        debugger.set_turbo(true);
+
+        assert_eq!(debugger.frames_per_tick(), 1);
+    }
+
+    #[test]
+    fn breakpoints_pause_like_the_user() {
+        let mut cpu = cpu();
+        let mut debugger = Debugger::new();
+        debugger.add_breakpoint(0x204);
+
+        debugger.run_frame(&mut cpu).unwrap();
+        debugger.focus_changed(false);
+        debugger.focus_changed(true);
+
+        assert_eq!(debugger.pause_state(), PauseState::PausedByUser);
+    }
+
+    #[test]
+    fn run_frame_executes_a_frame_and_ticks_timers() {
+        let mut cpu = cpu();
+        let mut debugger = Debugger::new();
+
+        debugger.run_frame(&mut cpu).unwrap();
+
+        assert_eq!(cpu.registers()[1], 4);
+        assert_eq!(cpu.delay_timer(), 0x2F);
+    }
+
+    #[test]
+    fn frames_run_the_configured_number_of_cycles() {
+        let mut cpu = cpu();
+        let mut debugger = Debugger::new();
+        debugger.set_cycles_per_frame(20);
+
+        debugger.run_frame(&mut cpu).unwrap();
+
+        // two setup instructions, then V1 counts up every other one
+        assert_eq!(cpu.registers()[1], 9);
+        assert_eq!(cpu.delay_timer(), 0x2F);
+    }
+
+    #[test]
+    fn paused_frames_do_nothing() {
+        let mut cpu = cpu();
+        let mut debugger = Debugger::new();
+        debugger.run_frame(&mut cpu).unwrap();
+        debugger.toggle_pause();
+
+        debugger.run_frame(&mut cpu).unwrap();
+        debugger.run_frame(&mut cpu).unwrap();
+
+        assert!(debugger.is_paused());
+        assert_eq!(cpu.registers()[1], 4);
+        assert_eq!(cpu.delay_timer(), 0x2F);
+    }
+
+    #[test]
+    fn step_executes_one_instruction_while_paused() {
+        let mut cpu = cpu();
+        let mut debugger = Debugger::new();
+
+        debugger.step(&mut cpu).unwrap();
+        assert_eq!(cpu.pc(), 0x200);
+
+        debugger.set_paused(true);
+        debugger.step(&mut cpu).unwrap();
+        debugger.step(&mut cpu).unwrap();
+
+        assert_eq!(cpu.pc(), 0x204);
+        assert_eq!(cpu.delay_timer(), 0x30);
+    }
+
+    #[test]
+    fn advance_frame_runs_one_frame_and_stays_paused() {
+        let mut cpu = cpu();
+        let mut debugger = Debugger::new();
+        debugger.set_paused(true);
+
+        debugger.advance_frame(&mut cpu).unwrap();
+
+        assert!(debugger.is_paused());
+        assert_eq!(cpu.registers()[1], 4);
+        assert_eq!(cpu.delay_timer(), 0x2F);
+    }
+
+    #[test]
+    fn resuming_continues_where_stepping_left_off() {
+        let mut cpu = cpu();
+        let mut debugger = Debugger::new();
+        debugger.set_paused(true);
+        debugger.step(&mut cpu).unwrap();
+        debugger.step(&mut cpu).unwrap();
+
+        debugger.toggle_pause();
+        debugger.run_frame(&mut cpu).unwrap();
+
+        assert_eq!(cpu.registers()[1], CYCLES_PER_FRAME as u8 / 2);
+        assert_eq!(cpu.delay_timer(), 0x2F);
+    }
+
+    #[test]
+    fn breakpoint_stops_before_the_instruction_runs() {
+        let mut cpu = cpu();
+        let mut debugger = Debugger::new();
+        debugger.add_breakpoint(0x206);
+
+        let stop = debugger.run_frame(&mut cpu).unwrap();
+
+        assert_eq!(stop, Some(Stop::Breakpoint(0x206)));
+        assert!(debugger.is_paused());
+        assert_eq!(cpu.pc(), 0x206);
+        assert_eq!(cpu.registers()[1], 1);
+        // stopped mid-frame, so the timers were not ticked
+        assert_eq!(cpu.delay_timer(), 0x30);
+    }
+
+    #[test]
+    fn resuming_runs_the_instruction_and_stops_at_the_next_hit() {
+        let mut cpu = cpu();
+        let mut debugger = Debugger::new();
+        debugger.add_breakpoint(0x204);
+        debugger.run_frame(&mut cpu).unwrap();
+        assert_eq!(cpu.registers()[1], 0);
+
+        debugger.toggle_pause();
+        let stop = debugger.run_frame(&mut cpu).unwrap();
+
+        assert_eq!(stop, Some(Stop::Breakpoint(0x204)));
+        assert_eq!(cpu.pc(), 0x204);
+        assert_eq!(cpu.registers()[1], 1);
+    }
+
+    #[test]
+    fn step_ignores_breakpoints() {
+        let mut cpu = cpu();
+        let mut debugger = Debugger::new();
+        debugger.add_breakpoint(0x202);
+        debugger.set_paused(true);
+
+        debugger.step(&mut cpu).unwrap();
+        debugger.step(&mut cpu).unwrap();
+
+        assert_eq!(cpu.pc(), 0x204);
+        assert_eq!(cpu.delay_timer(), 0x30);
+    }
+
+    #[test]
+    fn conditional_breakpoints_stop_only_when_the_condition_holds() {
+        let mut cpu = cpu();
+        let mut debugger = Debugger::new();
+        debugger.add_conditional_breakpoint(0x204, Condition::parse("V1==3").unwrap());
+
+        let stop = debugger.run_frame(&mut cpu).unwrap();
+
+        assert_eq!(stop, Some(Stop::ConditionalBreakpoint { pc: 0x204, condition: String::from("V1==0x3"), values: String::from("V1 = 0x03") }));
+        assert_eq!(stop.unwrap().to_string(), "Breakpoint at 0x204 with V1==0x3: V1 = 0x03");
+        assert!(debugger.is_paused());
+        assert_eq!(cpu.pc(), 0x204);
+        assert_eq!(cpu.registers()[1], 3);
+
+        // resuming runs past it, and it doesn't hold again
+        debugger.toggle_pause();
+        assert_eq!(debugger.run_frame(&mut cpu).unwrap(), None);
+    }
+
+    #[test]
+    fn break_when_stops_after_the_instruction_that_makes_it_true() {
+        let mut cpu = cpu();
+        let mut debugger = Debugger::new();
+        debugger.break_when(Condition::parse("V1>=2").unwrap());
+
+        let stop = debugger.run_frame(&mut cpu).unwrap();
+
+        assert_eq!(stop.unwrap().to_string(), "V1>=0x2 after 0x204: V1 = 0x02");
+        assert_eq!(cpu.pc(), 0x206);
+        assert_eq!(cpu.delay_timer(), 0x30);
+
+        // it stays true, which doesn't stop it again
+        debugger.toggle_pause();
+        assert_eq!(debugger.run_frame(&mut cpu).unwrap(), None);
+        assert!(cpu.registers()[1] > 2);
+    }
+
+    #[test]
+    fn describe_shows_the_instruction_and_registers() {
+        let mut cpu = cpu();
+        let mut debugger = Debugger::new();
+        debugger.add_breakpoint(0x202);
+        debugger.run_frame(&mut cpu).unwrap();
+
+        let description = describe(&cpu);
+
+        assert!(description.starts_with("0x202: F015  LD DT, V0\n"));
+        assert!(description.contains("V0=30 V1=00"));
+        assert!(description.contains("I=0x000 SP=0 DT=00 ST=00"));
+    }
+
+    #[test]
+    fn write_watchpoint_triggered_by_store_registers() {
+        // V0 = 5, V1 = 6, I = 0x300, FX55 with X = 1
+        let rom = vec![0x60, 0x05, 0x61, 0x06, 0xA3, 0x00, 0xF1, 0x55, 0x12, 0x08];
+        let mut cpu = Cpu::new(Memory::new(), Display::new());
+        cpu.init(rom);
+        cpu.add_watchpoint(Watchpoint { start: 0x301, end: 0x30F, mode: WatchMode::Write });
+        let mut debugger = Debugger::new();
+
+        let stop = debugger.run_frame(&mut cpu).unwrap();
+
+        let access = MemoryAccess { address: 0x301, kind: AccessKind::Write, old: 0, new: 6 };
+        assert_eq!(stop, Some(Stop::Watchpoint { pc: 0x206, access }));
+        assert!(debugger.is_paused());
+        assert_eq!(cpu.pc(), 0x208);
+    }
+
+    #[test]
+    fn read_watchpoint_triggered_by_draw() {
+        // I = 0x00A (the font's "2"), draw it, then loop
+        let rom = vec![0xA0, 0x0A, 0xD0, 0x05, 0x12, 0x04];
+        let mut cpu = Cpu::new(Memory::new(), Display::new());
+        cpu.init(rom);
+        cpu.add_watchpoint(Watchpoint { start: 0x000, end: 0x04F, mode: WatchMode::Read });
+        let mut debugger = Debugger::new();
+
+        let stop = debugger.run_frame(&mut cpu).unwrap();
+
+        let access = MemoryAccess { address: 0x00A, kind: AccessKind::Read, old: 0xF0, new: 0xF0 };
+        assert_eq!(stop, Some(Stop::Watchpoint { pc: 0x202, access }));
+        assert_eq!(cpu.pc(), 0x204);
+    }
+
+    #[test]
+    fn font_loading_does_not_trigger_watchpoints() {
+        let mut cpu = Cpu::new(Memory::new(), Display::new());
+        cpu.add_watchpoint(Watchpoint { start: 0x000, end: 0x04F, mode: WatchMode::Write });
+
+        cpu.init(vec![0x12, 0x00]);
+
+        assert_eq!(cpu.take_watch_hit(), None);
+    }
+
+    #[test]
+    fn overlay_lines() {
+        // V0 = 0x30, call 0x206, which sets the delay timer
+        let rom = vec![0x60, 0x30, 0x22, 0x06, 0x12, 0x04, 0xF0, 0x15, 0x00, 0xEE];
+        let mut cpu = Cpu::new(Memory::new(), Display::new());
+        cpu.init(rom);
+        cpu.cycle().unwrap();
+        cpu.cycle().unwrap();
+
+        assert_eq!(
+            overlay(&cpu),
+            vec![
+                "PC 0x206  I 0x000 (digit 0)",
+                "SP 1    DT 00 ST 00",
+                "V0 30 V1 00 V2 00 V3 00",
+                "V4 00 V5 00 V6 00 V7 00",
+                "V8 00 V9 00 VA 00 VB 00",
+                "VC 00 VD 00 VE 00 VF 00",
+                "Stack",
+                "  0x204",
+                "> 0x206 F015 LD DT, V0",
+                "  0x208 00EE RET",
+            ]
+        );
+    }
+
+    #[test]
+    fn overlay_names_the_glyph_i_points_at() {
+        // LD V0, 0xB; LD F, V0; LD HF, V0; ADD I, V0
+        let mut cpu = Cpu::new(Memory::new(), Display::new());
+        cpu.init(vec![0x60, 0x0B, 0xF0, 0x29, 0xF0, 0x30, 0xF0, 0x1E]);
+        let mut first_lines = Vec::new();
+        for _ in 0..4 {
+            cpu.cycle().unwrap();
+            first_lines.push(overlay(&cpu).remove(0));
+        }
+
+        assert_eq!(first_lines, vec!["PC 0x202  I 0x000 (digit 0)", "PC 0x204  I 0x037 (digit B)", "PC 0x206  I 0x05A (big digit 1)", "PC 0x208  I 0x065"]);
+    }
+
+    #[test]
+    fn overlay_with_an_empty_stack() {
+        let cpu = cpu();
+
+        assert!(overlay(&cpu).contains(&String::from("Stack empty")));
+    }
+
+    #[test]
+    fn history_page_shows_the_latest_instructions() {
+        let mut cpu = cpu();
+        for _ in 0..6 {
+            cpu.cycle().unwrap();
+        }
+
+        assert_eq!(history_page(&cpu, 3), vec!["History", "0x206 JP 0x204", "0x204 ADD V1, 0x01", "0x206 JP 0x204"]);
+    }
+}
@@ -0,0 +1,627 @@
+use crate::megachip::{MegaScreen, MEGA_HEIGHT, MEGA_WIDTH};
+use crate::prelude::*;
+
+/// Largest framebuffer of bitplanes, used by SUPER-CHIP's high-resolution mode.
+pub const MAX_WIDTH: usize = 128;
+pub const MAX_HEIGHT: usize = 64;
+
+/// Bits of a pixel that hold XO-CHIP's two bitplanes; classic ROMs only ever draw plane 1.
+pub const PLANE_1: u8 = 0b01;
+pub const PLANE_2: u8 = 0b10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resolution {
+    /// The original 64x32 screen.
+    Lores,
+    /// SUPER-CHIP's 128x64 screen, entered with 00FF.
+    Hires,
+    /// The 64x64 screen of the two-page hi-res CHIP-8 hack, which a handful of VIP games such as
+    /// Hi-Res Invaders were written for.
+    TwoPage,
+    /// MEGA-CHIP's 256x192 screen of palette indices, entered with 0011; see `MegaScreen`.
+    Mega,
+}
+
+impl Resolution {
+    pub fn width(self) -> usize {
+        match self {
+            Resolution::Lores | Resolution::TwoPage => 64,
+            Resolution::Hires => MAX_WIDTH,
+            Resolution::Mega => MEGA_WIDTH,
+        }
+    }
+
+    pub fn height(self) -> usize {
+        match self {
+            Resolution::Lores => 32,
+            Resolution::Hires | Resolution::TwoPage => MAX_HEIGHT,
+            Resolution::Mega => MEGA_HEIGHT,
+        }
+    }
+}
+
+/// A rectangle of the screen, in display pixels from the top-left corner.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rect {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Rect {
+    /// The smallest rectangle holding both.
+    pub fn union(self, other: Rect) -> Rect {
+        let (left, top) = (self.x.min(other.x), self.y.min(other.y));
+        let right = (self.x + self.width).max(other.x + other.width);
+        let bottom = (self.y + self.height).max(other.y + other.height);
+        Rect { x: left, y: top, width: right - left, height: bottom - top }
+    }
+}
+
+/// The framebuffer; only the top-left `width()` x `height()` pixels of `pixels` are in use.
+/// Each pixel holds one bit per bitplane, so it is 0..=3. In MEGA-CHIP mode `pixels` is blank
+/// and the screen is `megachip()` instead; `pixel` reads whichever is in use.
+///
+/// Everything that changes the screen notes the region it may have changed, which `take_dirty`
+/// hands to frontends that would rather not redraw what hasn't; the pixels are right whether or
+/// not anyone asks.
+#[derive(Clone)]
+pub struct Display {
+    pub(crate) pixels: [[u8; MAX_HEIGHT]; MAX_WIDTH],
+    pub(crate) resolution: Resolution,
+    // the planes 00E0, the scrolls and XO-CHIP's DXYN act on, as set by FN01
+    pub(crate) planes: u8,
+    // there exactly when the resolution is `Mega`
+    pub(crate) mega: Option<Box<MegaScreen>>,
+    // what changed since the last `take_dirty`
+    dirty: Option<Rect>,
+}
+
+impl Display {
+    pub fn new() -> Display {
+        Display {
+            pixels: [[0; MAX_HEIGHT]; MAX_WIDTH],
+            resolution: Resolution::Lores,
+            planes: PLANE_1,
+            mega: None,
+            // nothing has been shown yet
+            dirty: Some(Rect { x: 0, y: 0, width: Resolution::Lores.width(), height: Resolution::Lores.height() }),
+        }
+    }
+
+    /// The region changed since the last call, if anything was, and starts afresh. Clears and
+    /// scrolls count as changing the whole screen, as does switching resolution, where the
+    /// region is the new screen.
+    pub fn take_dirty(&mut self) -> Option<Rect> {
+        self.dirty.take()
+    }
+
+    fn mark_dirty(&mut self, rect: Rect) {
+        self.dirty = Some(match self.dirty {
+            Some(dirty) => dirty.union(rect),
+            None => rect,
+        });
+    }
+
+    pub(crate) fn mark_all_dirty(&mut self) {
+        self.mark_dirty(Rect { x: 0, y: 0, width: self.width(), height: self.height() });
+    }
+
+    /// Clears the selected planes, leaving the others as they are; in MEGA-CHIP mode, shows
+    /// what's been drawn and starts afresh, as `MegaScreen` describes.
+    pub fn clear(&mut self) {
+        self.mark_all_dirty();
+        if let Some(mega) = self.mega.as_mut() {
+            mega.clear();
+            return;
+        }
+        let keep = !self.planes;
+        for pixel in self.pixels.iter_mut().flat_map(|column| column.iter_mut()) {
+            *pixel &= keep;
+        }
+    }
+
+    pub fn selected_planes(&self) -> u8 {
+        self.planes
+    }
+
+    /// Selects the planes later draws, clears and scrolls act on, as FN01 does with N.
+    pub fn select_planes(&mut self, planes: u8) {
+        self.planes = planes & (PLANE_1 | PLANE_2);
+    }
+
+    pub fn pixels(&self) -> &[[u8; MAX_HEIGHT]; MAX_WIDTH] {
+        &self.pixels
+    }
+
+    /// The pixel at (`x`, `y`): its planes, or in MEGA-CHIP mode the palette index shown there.
+    pub fn pixel(&self, x: usize, y: usize) -> u8 {
+        match &self.mega {
+            Some(mega) => mega.pixel(x, y),
+            None => self.pixels[x][y],
+        }
+    }
+
+    /// The MEGA-CHIP screen, while in that mode.
+    pub fn megachip(&self) -> Option<&MegaScreen> {
+        self.mega.as_deref()
+    }
+
+    // whatever the caller does to the MEGA-CHIP screen, it's taken to change all of it
+    pub(crate) fn megachip_mut(&mut self) -> Option<&mut MegaScreen> {
+        if self.mega.is_some() {
+            self.mark_all_dirty();
+        }
+        self.mega.as_deref_mut()
+    }
+
+    pub fn resolution(&self) -> Resolution {
+        self.resolution
+    }
+
+    /// Switches resolution and clears the screen, every plane included, as 00FE/00FF do.
+    /// Switching to `Mega` starts a blank MEGA-CHIP screen with the default palette.
+    pub fn set_resolution(&mut self, resolution: Resolution) {
+        self.resolution = resolution;
+        self.pixels = [[0; MAX_HEIGHT]; MAX_WIDTH];
+        self.mega = (resolution == Resolution::Mega).then(|| Box::new(MegaScreen::new()));
+        self.dirty = None;
+        self.mark_all_dirty();
+    }
+
+    pub fn width(&self) -> usize {
+        self.resolution.width()
+    }
+
+    pub fn height(&self) -> usize {
+        self.resolution.height()
+    }
+
+    /// XORs a sprite onto plane 1 with its top-left corner at (`x`, `y`), which wraps around
+    /// the screen, while the sprite itself is clipped at the right and bottom edges. Each row is
+    /// `width` (8 or 16) pixels taken from the most significant bits of a `u16`. Returns whether
+    /// any lit pixel was switched off.
+    pub fn draw_sprite(&mut self, x: usize, y: usize, rows: &[u16], width: usize) -> bool {
+        self.draw_sprite_on(PLANE_1, x, y, rows, width)
+    }
+
+    /// Like `draw_sprite`, but XORs onto `plane` (`PLANE_1` or `PLANE_2`) whether or not it is selected.
+    pub fn draw_sprite_on(&mut self, plane: u8, x: usize, y: usize, rows: &[u16], width: usize) -> bool {
+        let start_x = x % self.width();
+        let start_y = y % self.height();
+        let (clipped_width, clipped_height) = (width.min(self.width() - start_x), rows.len().min(self.height() - start_y));
+        if clipped_width > 0 && clipped_height > 0 {
+            self.mark_dirty(Rect { x: start_x, y: start_y, width: clipped_width, height: clipped_height });
+        }
+        let mut collision = false;
+        for (row_index, &row) in rows.iter().enumerate() {
+            let pixel_y = start_y + row_index;
+            if pixel_y >= self.height() {
+                break;
+            }
+            for bit in 0..width {
+                let pixel_x = start_x + bit;
+                if pixel_x >= self.width() {
+                    break;
+                }
+                if (row >> (15 - bit)) & 1 == 0 {
+                    continue;
+                }
+                let pixel = &mut self.pixels[pixel_x][pixel_y];
+                if *pixel & plane != 0 {
+                    collision = true;
+                }
+                *pixel ^= plane;
+            }
+        }
+        collision
+    }
+
+    /// Shifts the selected planes down by `n` rows; the rows scrolled in at the top are blank.
+    pub fn scroll_down(&mut self, n: usize) {
+        self.mark_all_dirty();
+        if let Some(mega) = self.mega.as_mut() {
+            mega.scroll(0, n as isize);
+            return;
+        }
+        let (height, planes) = (self.height(), self.planes);
+        for column in self.pixels.iter_mut().take(self.resolution.width()) {
+            for y in (0..height).rev() {
+                let moved = if y >= n { column[y - n] } else { 0 };
+                column[y] = (column[y] & !planes) | (moved & planes);
+            }
+        }
+    }
+
+    /// Shifts the selected planes up by `n` rows, as MEGA-CHIP's 00BN does; the rows scrolled in
+    /// at the bottom are blank.
+    pub fn scroll_up(&mut self, n: usize) {
+        self.mark_all_dirty();
+        if let Some(mega) = self.mega.as_mut() {
+            mega.scroll(0, -(n as isize));
+            return;
+        }
+        let (height, planes) = (self.height(), self.planes);
+        for column in self.pixels.iter_mut().take(self.resolution.width()) {
+            for y in 0..height {
+                let moved = if y + n < height { column[y + n] } else { 0 };
+                column[y] = (column[y] & !planes) | (moved & planes);
+            }
+        }
+    }
+
+    /// Shifts the selected planes right by `n` columns; the columns scrolled in on the left are blank.
+    pub fn scroll_right(&mut self, n: usize) {
+        self.mark_all_dirty();
+        if let Some(mega) = self.mega.as_mut() {
+            mega.scroll(n as isize, 0);
+            return;
+        }
+        for x in (0..self.width()).rev() {
+            let moved = if x >= n { self.pixels[x - n] } else { [0; MAX_HEIGHT] };
+            self.replace_column(x, &moved);
+        }
+    }
+
+    /// Shifts the selected planes left by `n` columns; the columns scrolled in on the right are blank.
+    pub fn scroll_left(&mut self, n: usize) {
+        self.mark_all_dirty();
+        if let Some(mega) = self.mega.as_mut() {
+            mega.scroll(-(n as isize), 0);
+            return;
+        }
+        let width = self.width();
+        for x in 0..width {
+            let moved = if x + n < width { self.pixels[x + n] } else { [0; MAX_HEIGHT] };
+            self.replace_column(x, &moved);
+        }
+    }
+
+    // overwrites the selected planes of column `x` with those of `moved`
+    fn replace_column(&mut self, x: usize, moved: &[u8; MAX_HEIGHT]) {
+        let planes = self.planes;
+        for (pixel, &moved) in self.pixels[x].iter_mut().zip(moved.iter()) {
+            *pixel = (*pixel & !planes) | (moved & planes);
+        }
+    }
+
+    pub fn scroll_right4(&mut self) {
+        self.scroll_right(4);
+    }
+
+    pub fn scroll_left4(&mut self) {
+        self.scroll_left(4);
+    }
+
+    /// Renders the framebuffer as text, one line per row: `#` for a pixel lit in plane 1, `+` for
+    /// plane 2, `@` for both and `.` otherwise. In MEGA-CHIP mode every colour but 0 is `#`.
+    pub fn to_ascii(&self) -> String {
+        let mut ascii = String::with_capacity((self.width() + 1) * self.height());
+        for y in 0..self.height() {
+            for x in 0..self.width() {
+                let pixel = match self.mega {
+                    Some(_) => (self.pixel(x, y) != 0) as u8,
+                    None => self.pixels[x][y],
+                };
+                ascii.push(['.', '#', '+', '@'][pixel as usize & 0b11]);
+            }
+            ascii.push('\n');
+        }
+        ascii
+    }
+
+    /// Renders the framebuffer as a plain (P1) PBM image; a pixel lit in any plane is black.
+    pub fn to_pbm(&self) -> String {
+        let mut rgba = Vec::new();
+        self.to_rgba([1; 4], [0; 4], &mut rgba);
+        let mut pbm = format!("P1\n{} {}\n", self.width(), self.height());
+        for row in rgba.chunks(self.width() * 4) {
+            let row: Vec<&str> = row.chunks(4).map(|pixel| if pixel[0] != 0 { "1" } else { "0" }).collect();
+            pbm.push_str(&row.join(" "));
+            pbm.push('\n');
+        }
+        pbm
+    }
+
+    /// Writes the framebuffer into `out` as tightly packed RGBA bytes, `width()` x `height()` of
+    /// them, row-major from the top-left corner: `fg` for pixels lit in any plane and `bg` for the
+    /// rest. `out` is cleared first and only grows if it's too small, so reusing it each frame
+    /// doesn't allocate.
+    pub fn to_rgba(&self, fg: [u8; 4], bg: [u8; 4], out: &mut Vec<u8>) {
+        self.to_rgba_with(out, |x, y| if self.pixel(x, y) != 0 { fg } else { bg });
+    }
+
+    /// Like `to_rgba`, with `color` picking the RGBA bytes for the pixel at each (x, y).
+    pub fn to_rgba_with(&self, out: &mut Vec<u8>, mut color: impl FnMut(usize, usize) -> [u8; 4]) {
+        out.clear();
+        out.reserve(self.width() * self.height() * 4);
+        for y in 0..self.height() {
+            for x in 0..self.width() {
+                out.extend_from_slice(&color(x, y));
+            }
+        }
+    }
+}
+
+impl Default for Display {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn to_ascii() {
+        let mut display = Display::new();
+        display.pixels[0][0] = 1;
+        display.pixels[63][31] = 1;
+
+        let ascii = display.to_ascii();
+        let lines: Vec<&str> = ascii.lines().collect();
+
+        assert_eq!(lines.len(), 32);
+        assert_eq!(lines[0], format!("#{}", ".".repeat(63)));
+        assert_eq!(lines[31], format!("{}#", ".".repeat(63)));
+    }
+
+    #[test]
+    fn to_pbm() {
+        let mut display = Display::new();
+        display.pixels[1][0] = 1;
+
+        let pbm = display.to_pbm();
+        let lines: Vec<&str> = pbm.lines().collect();
+
+        assert_eq!(lines.len(), 34);
+        assert_eq!(lines[0], "P1");
+        assert_eq!(lines[1], "64 32");
+        assert!(lines[2].starts_with("0 1 0 0"));
+        assert_eq!(lines[3], vec!["0"; 64].join(" "));
+    }
+
+    const WHITE: [u8; 4] = [0xFF, 0xFF, 0xFF, 0xFF];
+    const BLACK: [u8; 4] = [0, 0, 0, 0xFF];
+
+    #[test]
+    fn to_rgba() {
+        let mut display = Display::new();
+        display.pixels[1][0] = 1;
+        display.pixels[0][1] = 1;
+        let mut rgba = Vec::new();
+
+        display.to_rgba(WHITE, BLACK, &mut rgba);
+
+        assert_eq!(rgba.len(), 64 * 32 * 4);
+        assert_eq!(&rgba[0..8], &[0, 0, 0, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF]);
+        assert_eq!(&rgba[64 * 4..64 * 4 + 4], &[0xFF, 0xFF, 0xFF, 0xFF]);
+    }
+
+    #[test]
+    fn to_rgba_is_row_major_from_the_top_left_and_substitutes_both_colors() {
+        let (fg, bg) = ([10, 20, 30, 40], [50, 60, 70, 80]);
+        let mut display = Display::new();
+        display.set_resolution(Resolution::Hires);
+        display.pixels[2][0] = PLANE_1;
+        display.pixels[0][3] = PLANE_2;
+        display.pixels[127][63] = PLANE_1 | PLANE_2;
+        let mut rgba = Vec::new();
+
+        display.to_rgba(fg, bg, &mut rgba);
+
+        let pixel = |x: usize, y: usize| &rgba[(y * 128 + x) * 4..][..4];
+        assert_eq!(rgba.len(), 128 * 64 * 4);
+        assert_eq!((pixel(0, 0), pixel(1, 0), pixel(2, 0)), (&bg[..], &bg[..], &fg[..]));
+        assert_eq!((pixel(0, 3), pixel(3, 0)), (&fg[..], &bg[..]));
+        assert_eq!(pixel(127, 63), &fg[..]);
+        assert_eq!(rgba.chunks(4).filter(|&pixel| pixel == fg).count(), 3);
+    }
+
+    #[test]
+    fn to_rgba_reuses_the_buffer_it_is_given() {
+        let mut display = Display::new();
+        display.pixels[5][5] = 1;
+        let mut rgba = vec![0xAB; 128 * 64 * 4];
+        let buffer = rgba.as_ptr();
+
+        display.to_rgba(WHITE, BLACK, &mut rgba);
+        display.to_rgba(BLACK, WHITE, &mut rgba);
+
+        assert_eq!(rgba.as_ptr(), buffer);
+        assert_eq!(rgba.len(), 64 * 32 * 4);
+        assert_eq!(&rgba[..4], &WHITE);
+        assert_eq!(&rgba[(5 * 64 + 5) * 4..][..4], &BLACK);
+    }
+
+    #[test]
+    fn switching_resolution_clears_the_screen() {
+        let mut display = Display::new();
+        display.pixels[3][3] = 1;
+
+        display.set_resolution(Resolution::Hires);
+
+        assert_eq!((display.width(), display.height()), (128, 64));
+        assert_eq!(display.pixels[3][3], 0);
+        assert_eq!(display.to_ascii().lines().count(), 64);
+        assert!(display.to_pbm().starts_with("P1\n128 64\n"));
+
+        display.pixels[100][50] = 1;
+        display.set_resolution(Resolution::Lores);
+
+        assert_eq!(display.pixels[100][50], 0);
+        let mut rgba = Vec::new();
+        display.to_rgba(WHITE, BLACK, &mut rgba);
+        assert_eq!(rgba.len(), 64 * 32 * 4);
+    }
+
+    fn display_from(rows: &[&str]) -> Display {
+        let mut display = Display::new();
+        for (y, row) in rows.iter().enumerate() {
+            for (x, pixel) in row.chars().enumerate() {
+                display.pixels[x][y] = (pixel == '#') as u8;
+            }
+        }
+        display
+    }
+
+    fn top_left(display: &Display, width: usize, height: usize) -> Vec<String> {
+        (0..height).map(|y| (0..width).map(|x| if display.pixels[x][y] == 1 { '#' } else { '.' }).collect()).collect()
+    }
+
+    #[test]
+    fn scroll_down() {
+        let mut display = display_from(&["#..#", ".##.", "#..."]);
+
+        display.scroll_down(2);
+
+        assert_eq!(top_left(&display, 4, 5), vec!["....", "....", "#..#", ".##.", "#..."]);
+    }
+
+    #[test]
+    fn scroll_down_drops_rows_past_the_bottom() {
+        let mut display = Display::new();
+        display.pixels[0][30] = 1;
+        display.pixels[0][31] = 1;
+
+        display.scroll_down(1);
+
+        assert_eq!(display.pixels[0][31], 1);
+        assert_eq!(display.pixels[0][32], 0);
+    }
+
+    #[test]
+    fn scroll_right4() {
+        let mut display = display_from(&["#.#.", ".#.#"]);
+        display.pixels[62][0] = 1;
+
+        display.scroll_right4();
+
+        assert_eq!(top_left(&display, 8, 2), vec!["....#.#.", ".....#.#"]);
+        assert_eq!(display.pixels[66][0], 0);
+    }
+
+    #[test]
+    fn scroll_left4() {
+        let mut display = display_from(&["#...#.#.", ".....#.#"]);
+        display.pixels[63][1] = 1;
+
+        display.scroll_left4();
+
+        assert_eq!(top_left(&display, 4, 2), vec!["#.#.", ".#.#"]);
+        assert_eq!(display.pixels[59][1], 1);
+        assert_eq!(display.pixels[63][1], 0);
+    }
+
+    #[test]
+    fn scrolling_in_hires_uses_the_whole_screen() {
+        let mut display = Display::new();
+        display.set_resolution(Resolution::Hires);
+        display.pixels[120][60] = 1;
+
+        display.scroll_right4();
+        display.scroll_down(3);
+
+        assert_eq!(display.pixels[124][63], 1);
+    }
+
+    #[test]
+    fn draw_sprite_reports_collisions() {
+        let mut display = Display::new();
+
+        assert!(!display.draw_sprite(0, 0, &[0xF000], 8));
+        assert!(display.draw_sprite(2, 0, &[0xC000], 8));
+        assert_eq!(top_left(&display, 6, 1), vec!["##...."]);
+    }
+
+    #[test]
+    fn wide_sprites_clip_the_second_byte_at_the_right_edge() {
+        let mut display = Display::new();
+
+        display.draw_sprite(60, 0, &[0xFFFF], 16);
+
+        assert_eq!(display.to_ascii().lines().next().unwrap(), format!("{}####", ".".repeat(60)));
+    }
+
+    #[test]
+    fn sprite_start_position_wraps() {
+        let mut display = Display::new();
+
+        display.draw_sprite(66, 33, &[0x8000], 8);
+
+        assert_eq!(display.pixels[2][1], 1);
+    }
+
+    #[test]
+    fn clear_and_scroll_only_touch_selected_planes() {
+        let mut display = display_from(&["#.", "#."]);
+        display.pixels[1][0] = PLANE_2;
+        display.pixels[1][1] = PLANE_1 | PLANE_2;
+
+        display.select_planes(PLANE_2);
+        display.scroll_down(1);
+
+        assert_eq!(display.to_ascii().lines().take(3).map(|line| &line[..2]).collect::<Vec<_>>(), vec!["#.", "#@", ".+"]);
+
+        display.clear();
+
+        assert_eq!(display.to_ascii().lines().take(3).map(|line| &line[..2]).collect::<Vec<_>>(), vec!["#.", "##", ".."]);
+    }
+
+    #[test]
+    fn a_sprite_dirties_exactly_its_bounding_box() {
+        let mut display = Display::new();
+        display.take_dirty();
+
+        display.draw_sprite(10, 5, &[0xFF00, 0x8000, 0x0100], 8);
+
+        assert_eq!(display.take_dirty(), Some(Rect { x: 10, y: 5, width: 8, height: 3 }));
+        assert_eq!(display.take_dirty(), None);
+    }
+
+    #[test]
+    fn dirty_regions_add_up_until_taken() {
+        let mut display = Display::new();
+        assert_eq!(display.take_dirty(), Some(Rect { x: 0, y: 0, width: 64, height: 32 }));
+
+        display.draw_sprite(10, 5, &[0xFF00; 3], 8);
+        display.draw_sprite(2, 20, &[0xFF00; 5], 8);
+        // clipped at the bottom right rather than wrapped
+        display.draw_sprite(60, 30, &[0xFF00; 5], 8);
+
+        assert_eq!(display.take_dirty(), Some(Rect { x: 2, y: 5, width: 62, height: 27 }));
+        display.draw_sprite(0, 0, &[], 8);
+        assert_eq!(display.take_dirty(), None);
+    }
+
+    #[test]
+    fn clears_scrolls_and_resolution_switches_dirty_the_whole_screen() {
+        let mut display = Display::new();
+        let lores = Some(Rect { x: 0, y: 0, width: 64, height: 32 });
+        display.take_dirty();
+
+        display.clear();
+        assert_eq!(display.take_dirty(), lores);
+        display.scroll_down(2);
+        assert_eq!(display.take_dirty(), lores);
+        display.scroll_left4();
+        assert_eq!(display.take_dirty(), lores);
+        display.set_resolution(Resolution::Hires);
+        assert_eq!(display.take_dirty(), Some(Rect { x: 0, y: 0, width: 128, height: 64 }));
+        display.scroll_up(1);
+        display.scroll_right4();
+        assert_eq!(display.take_dirty(), Some(Rect { x: 0, y: 0, width: 128, height: 64 }));
+    }
+
+    #[test]
+    fn collisions_are_per_plane() {
+        let mut display = Display::new();
+        display.draw_sprite(0, 0, &[0xF000], 8);
+
+        assert!(!display.draw_sprite_on(PLANE_2, 0, 0, &[0xF000], 8));
+        assert!(display.draw_sprite_on(PLANE_2, 2, 0, &[0x8000], 8));
+        assert_eq!(&display.to_ascii()[..4], "@@#@");
+    }
+}
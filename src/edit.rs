@@ -0,0 +1,204 @@
+use core::fmt;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::prelude::*;
+
+/// Something a hand edit can change.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Target {
+    V(u8),
+    I,
+    Pc,
+    /// The byte at the address.
+    Mem(u16),
+}
+
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Target::V(x) => write!(f, "V{:X}", x),
+            Target::I => write!(f, "I"),
+            Target::Pc => write!(f, "pc"),
+            Target::Mem(address) => write!(f, "mem[{:#05X}]", address),
+        }
+    }
+}
+
+/// One value changed by hand, as the confirmation shows it and the instruction history keeps it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Change {
+    pub target: Target,
+    pub old: u16,
+    pub new: u16,
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.target {
+            Target::V(_) | Target::Mem(_) => write!(f, "{} {:#04X} -> {:#04X}", self.target, self.old, self.new),
+            Target::I | Target::Pc => write!(f, "{} {:#05X} -> {:#05X}", self.target, self.old, self.new),
+        }
+    }
+}
+
+/// A debugger command that changes the paused machine: `set V3 0x1F`, `set I 0x300`,
+/// `set pc 0x2A4` or `setmem 0x300 de ad be ef`. Numbers are hex, with or without `0x`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Edit {
+    Set(Target, u16),
+    /// Bytes written from the address on.
+    SetMem(u16, Vec<u8>),
+}
+
+impl Edit {
+    /// Parses a command, turning away registers that don't exist and values too big for what
+    /// they're stored in. Whether addresses fit the machine's memory is up to `Cpu::apply_edit`.
+    pub fn parse(text: &str) -> Result<Edit, String> {
+        let mut words = text.split_whitespace();
+        match words.next() {
+            Some("set") => {
+                let (name, value) = match (words.next(), words.next(), words.next()) {
+                    (Some(name), Some(value), None) => (name, value),
+                    _ => return Err(String::from("expected set REGISTER VALUE, e.g. set V3 0x1F")),
+                };
+                let target = register(name)?;
+                let value = hex(value)?;
+                if matches!(target, Target::V(_)) && value > 0xFF {
+                    return Err(format!("{:#X} doesn't fit in {}, which holds 0x00 to 0xFF", value, target));
+                }
+                Ok(Edit::Set(target, value))
+            }
+            Some("setmem") => {
+                let address = words.next().ok_or_else(|| String::from("expected setmem ADDRESS BYTE..., e.g. setmem 0x300 de ad"))?;
+                let address = hex(address)?;
+                let bytes = words.map(byte).collect::<Result<Vec<u8>, String>>()?;
+                if bytes.is_empty() {
+                    return Err(format!("expected bytes to write at {:#05X}", address));
+                }
+                Ok(Edit::SetMem(address, bytes))
+            }
+            Some(command) => Err(format!("{} is not a command; use set or setmem", command)),
+            None => Err(String::from("expected a command")),
+        }
+    }
+}
+
+fn register(name: &str) -> Result<Target, String> {
+    let lower = name.to_ascii_lowercase();
+    match lower.as_str() {
+        "i" => Ok(Target::I),
+        "pc" => Ok(Target::Pc),
+        _ => match lower.strip_prefix('v').filter(|digit| digit.len() == 1).and_then(|digit| u8::from_str_radix(digit, 16).ok()) {
+            Some(x) => Ok(Target::V(x)),
+            None => Err(format!("{} is not a register; use V0 to VF, I or pc", name)),
+        },
+    }
+}
+
+fn hex(word: &str) -> Result<u16, String> {
+    let digits = word.trim_start_matches("0x").trim_start_matches("0X");
+    u16::from_str_radix(digits, 16).map_err(|_| format!("{} is not a hex number between 0x0000 and 0xFFFF", word))
+}
+
+fn byte(word: &str) -> Result<u8, String> {
+    let digits = word.trim_start_matches("0x").trim_start_matches("0X");
+    u8::from_str_radix(digits, 16).map_err(|_| format!("{} is not a byte between 0x00 and 0xFF", word))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cpu::Cpu;
+    use crate::display::Display;
+    use crate::memory::Memory;
+
+    fn edited(cpu: &mut Cpu, command: &str) -> Result<Vec<String>, String> {
+        let changes = cpu.apply_edit(&Edit::parse(command)?)?;
+        Ok(changes.iter().map(Change::to_string).collect())
+    }
+
+    #[test]
+    fn parse_commands() {
+        assert_eq!(Edit::parse("set V3 0x1F"), Ok(Edit::Set(Target::V(3), 0x1F)));
+        assert_eq!(Edit::parse("set vf ff"), Ok(Edit::Set(Target::V(0xF), 0xFF)));
+        assert_eq!(Edit::parse("set I 0x300"), Ok(Edit::Set(Target::I, 0x300)));
+        assert_eq!(Edit::parse("  set PC 2a4 "), Ok(Edit::Set(Target::Pc, 0x2A4)));
+        assert_eq!(Edit::parse("setmem 0x300 de ad be ef"), Ok(Edit::SetMem(0x300, vec![0xDE, 0xAD, 0xBE, 0xEF])));
+        assert_eq!(Edit::parse("setmem FFFF 0x01"), Ok(Edit::SetMem(0xFFFF, vec![0x01])));
+    }
+
+    #[test]
+    fn parse_turns_away_what_cant_be_set() {
+        assert_eq!(Edit::parse("set VG 1").unwrap_err(), "VG is not a register; use V0 to VF, I or pc");
+        assert_eq!(Edit::parse("set V10 1").unwrap_err(), "V10 is not a register; use V0 to VF, I or pc");
+        assert_eq!(Edit::parse("set sp 1").unwrap_err(), "sp is not a register; use V0 to VF, I or pc");
+        assert_eq!(Edit::parse("set V3 0x100").unwrap_err(), "0x100 doesn't fit in V3, which holds 0x00 to 0xFF");
+        assert_eq!(Edit::parse("set I 0x10000").unwrap_err(), "0x10000 is not a hex number between 0x0000 and 0xFFFF");
+        assert_eq!(Edit::parse("set V3").unwrap_err(), "expected set REGISTER VALUE, e.g. set V3 0x1F");
+        assert_eq!(Edit::parse("set V3 1 2").unwrap_err(), "expected set REGISTER VALUE, e.g. set V3 0x1F");
+        assert_eq!(Edit::parse("setmem 0x300 de 1ad").unwrap_err(), "1ad is not a byte between 0x00 and 0xFF");
+        assert_eq!(Edit::parse("setmem 0x300").unwrap_err(), "expected bytes to write at 0x300");
+        assert_eq!(Edit::parse("poke 0x300 1").unwrap_err(), "poke is not a command; use set or setmem");
+        assert_eq!(Edit::parse("").unwrap_err(), "expected a command");
+    }
+
+    #[test]
+    fn edits_echo_old_and_new_values() {
+        // LD V3, 0x1F; LD I, 0x300
+        let mut cpu = Cpu::new(Memory::new(), Display::new());
+        cpu.init(vec![0x63, 0x1F, 0xA3, 0x00]);
+        cpu.cycle().unwrap();
+        cpu.cycle().unwrap();
+
+        assert_eq!(edited(&mut cpu, "set V3 0"), Ok(vec![String::from("V3 0x1F -> 0x00")]));
+        assert_eq!(edited(&mut cpu, "set I 0x310"), Ok(vec![String::from("I 0x300 -> 0x310")]));
+        assert_eq!(edited(&mut cpu, "set pc 0x200"), Ok(vec![String::from("pc 0x204 -> 0x200")]));
+        assert_eq!(edited(&mut cpu, "setmem 0x310 de ad"), Ok(vec![String::from("mem[0x310] 0x00 -> 0xDE"), String::from("mem[0x311] 0x00 -> 0xAD")]));
+
+        assert_eq!(cpu.registers()[3], 0);
+        assert_eq!((cpu.i(), cpu.pc()), (0x310, 0x200));
+        assert_eq!(&cpu.memory().bytes()[0x310..0x312], &[0xDE, 0xAD]);
+        // the ROM carries on from the edit
+        cpu.cycle().unwrap();
+        assert_eq!(cpu.registers()[3], 0x1F);
+    }
+
+    #[test]
+    fn edits_past_memory_change_nothing() {
+        let mut cpu = Cpu::new(Memory::new(), Display::new());
+        cpu.init(vec![0x12, 0x00]);
+
+        assert_eq!(edited(&mut cpu, "setmem 0xFFE 1 2 3"), Err(String::from("0xFFE to 0x1000 is past the end of memory at 0xFFF")));
+        assert_eq!(edited(&mut cpu, "set I 0x1000"), Err(String::from("0x1000 is past the end of memory at 0xFFF")));
+        assert_eq!(edited(&mut cpu, "set pc 0xFFF"), Err(String::from("0xFFF is past the end of memory at 0xFFF")));
+        assert_eq!(cpu.memory().bytes()[0xFFE], 0);
+        assert_eq!(cpu.edited_at(), None);
+        assert_eq!(edited(&mut cpu, "setmem 0xFFE 1 2"), Ok(vec![String::from("mem[0xFFE] 0x00 -> 0x01"), String::from("mem[0xFFF] 0x00 -> 0x02")]));
+        assert_eq!(cpu.edited_at(), Some(0));
+    }
+
+    #[test]
+    fn edits_show_in_the_history_and_taint_the_state_hash() {
+        // LD V3, 0x1F; JP 0x202
+        let mut cpu = Cpu::new(Memory::new(), Display::new());
+        cpu.init(vec![0x63, 0x1F, 0x12, 0x02]);
+        cpu.cycle().unwrap();
+        let untouched = cpu.state_hash();
+
+        cpu.apply_edit(&Edit::Set(Target::V(3), 0x00)).unwrap();
+        cpu.apply_edit(&Edit::Set(Target::V(3), 0x1F)).unwrap();
+        cpu.cycle().unwrap();
+
+        let history: Vec<String> = cpu.history().iter().map(|entry| entry.to_string()).collect();
+        assert_eq!(history, vec!["0x200: 631F  LD V3, 0x1F", "0x202: set V3 0x1F -> 0x00 by hand", "0x202: set V3 0x00 -> 0x1F by hand", "0x202: 1202  JP 0x202"]);
+        // back as it was, but not the same run
+        assert_eq!(cpu.edited_at(), Some(1));
+        assert_ne!(cpu.state_hash(), untouched);
+        cpu.reset();
+        assert_eq!(cpu.edited_at(), None);
+    }
+}
@@ -0,0 +1,212 @@
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// Whatever `EmulationThread` drives: a CPU plus everything that has to keep step with it.
+pub trait Machine: Send + 'static {
+    /// What's handed to the render thread after each frame.
+    type Frame: Send + 'static;
+
+    /// Runs one frame's worth of emulation, or nothing while paused, and describes the result.
+    fn run_frame(&mut self) -> Self::Frame;
+
+    /// Folds a frame the render thread never picked up into the newer one replacing it, for
+    /// what mustn't be lost with it, like audio. By default it's just dropped.
+    fn coalesce(_dropped: Self::Frame, _next: &mut Self::Frame) {}
+}
+
+enum Command<M> {
+    Run(Box<dyn FnOnce(&mut M) + Send>),
+    Stop,
+}
+
+/// Runs a `Machine` on its own thread at a fixed frame rate, so however long a frame takes the
+/// window keeps drawing and reading input. Only the latest frame is kept for the render thread:
+/// a slow reader skips frames rather than holding the emulation up. Everything else reaches the
+/// machine as commands, run between frames in the order they were sent. Dropping it stops and
+/// joins the thread.
+pub struct EmulationThread<M: Machine> {
+    commands: Sender<Command<M>>,
+    latest: Arc<Mutex<Option<M::Frame>>>,
+    thread: Option<JoinHandle<M>>,
+}
+
+impl<M: Machine> EmulationThread<M> {
+    /// Starts running `machine`, one frame every `frame_duration`.
+    pub fn spawn(machine: M, frame_duration: Duration) -> EmulationThread<M> {
+        let (commands, received) = mpsc::channel();
+        let latest = Arc::new(Mutex::new(None));
+        let published = Arc::clone(&latest);
+        let thread = thread::Builder::new()
+            .name(String::from("emulation"))
+            .spawn(move || run(machine, received, &published, frame_duration))
+            .expect("couldn't start the emulation thread");
+        EmulationThread { commands, latest, thread: Some(thread) }
+    }
+
+    /// Has `command` run on the machine before its next frame, without waiting for it.
+    pub fn send(&self, command: impl FnOnce(&mut M) + Send + 'static) {
+        // the thread only ends when told to, or by panicking, which `with` and `stop` report
+        let _ = self.commands.send(Command::Run(Box::new(command)));
+    }
+
+    /// Runs `query` on the machine before its next frame and waits for the answer.
+    ///
+    /// # Panics
+    ///
+    /// If the emulation thread has panicked.
+    pub fn with<R: Send + 'static>(&self, query: impl FnOnce(&mut M) -> R + Send + 'static) -> R {
+        let (answer, answered) = mpsc::channel();
+        self.send(move |machine| {
+            let _ = answer.send(query(machine));
+        });
+        answered.recv().expect("the emulation thread panicked")
+    }
+
+    /// The latest frame, or `None` if there hasn't been a new one since the last call.
+    pub fn take_frame(&self) -> Option<M::Frame> {
+        self.latest.lock().unwrap_or_else(PoisonError::into_inner).take()
+    }
+
+    /// Stops the thread after any commands already sent, and hands the machine back.
+    ///
+    /// # Panics
+    ///
+    /// If the emulation thread has panicked.
+    pub fn stop(mut self) -> M {
+        let thread = self.thread.take().expect("only stop and drop take the thread");
+        let _ = self.commands.send(Command::Stop);
+        thread.join().expect("the emulation thread panicked")
+    }
+}
+
+impl<M: Machine> Drop for EmulationThread<M> {
+    fn drop(&mut self) {
+        if let Some(thread) = self.thread.take() {
+            let _ = self.commands.send(Command::Stop);
+            // a panic has already been reported on stderr, and panicking again in drop would abort
+            let _ = thread.join();
+        }
+    }
+}
+
+fn run<M: Machine>(mut machine: M, commands: Receiver<Command<M>>, latest: &Mutex<Option<M::Frame>>, frame_duration: Duration) -> M {
+    let mut next_frame = Instant::now();
+    loop {
+        // commands that are already waiting run even when the frame is overdue
+        match commands.recv_timeout(next_frame.saturating_duration_since(Instant::now())) {
+            Ok(Command::Run(command)) => {
+                command(&mut machine);
+                continue;
+            }
+            Ok(Command::Stop) | Err(RecvTimeoutError::Disconnected) => return machine,
+            Err(RecvTimeoutError::Timeout) => {}
+        }
+
+        let mut frame = machine.run_frame();
+        let mut latest = latest.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(dropped) = latest.take() {
+            M::coalesce(dropped, &mut frame);
+        }
+        *latest = Some(frame);
+        drop(latest);
+
+        next_frame += frame_duration;
+        let now = Instant::now();
+        if next_frame < now {
+            // too slow to keep up, so don't try to catch up later
+            next_frame = now;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    use super::*;
+
+    /// Counts its frames, each of which carries the numbers of every frame it stands for.
+    struct Counter {
+        frames: u32,
+        paused: bool,
+        dropped: Arc<AtomicBool>,
+    }
+
+    impl Counter {
+        fn new() -> Counter {
+            Counter { frames: 0, paused: false, dropped: Arc::new(AtomicBool::new(false)) }
+        }
+    }
+
+    impl Machine for Counter {
+        type Frame = Vec<u32>;
+
+        fn run_frame(&mut self) -> Vec<u32> {
+            if self.paused {
+                return Vec::new();
+            }
+            self.frames += 1;
+            vec![self.frames]
+        }
+
+        fn coalesce(dropped: Vec<u32>, next: &mut Vec<u32>) {
+            next.splice(0..0, dropped);
+        }
+    }
+
+    impl Drop for Counter {
+        fn drop(&mut self) {
+            self.dropped.store(true, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn a_slow_reader_gets_every_frame_coalesced_and_never_blocks_the_thread() {
+        let emulation = EmulationThread::spawn(Counter::new(), Duration::from_micros(50));
+        let mut seen = Vec::new();
+
+        for _ in 0..50 {
+            thread::sleep(Duration::from_millis(2));
+            if let Some(frames) = emulation.take_frame() {
+                seen.extend(frames);
+            }
+            // queries interleaved with the frames get answered, however far behind the reader is
+            let frames = emulation.with(|counter| counter.frames);
+            assert!(frames >= seen.len() as u32);
+        }
+        let counter = emulation.stop();
+
+        let expected: Vec<u32> = (1..=seen.len() as u32).collect();
+        assert_eq!(seen, expected);
+        assert!(counter.frames >= seen.len() as u32);
+        assert!(seen.len() > 50);
+    }
+
+    #[test]
+    fn commands_run_in_order_between_frames() {
+        let emulation = EmulationThread::spawn(Counter::new(), Duration::from_millis(1));
+
+        emulation.send(|counter| counter.paused = true);
+        let paused_at = emulation.with(|counter| counter.frames);
+        thread::sleep(Duration::from_millis(20));
+
+        assert_eq!(emulation.with(|counter| counter.frames), paused_at);
+        emulation.send(|counter| counter.paused = false);
+        thread::sleep(Duration::from_millis(20));
+        assert!(emulation.with(|counter| counter.frames) > paused_at);
+    }
+
+    #[test]
+    fn dropping_it_stops_and_joins_the_thread() {
+        let counter = Counter::new();
+        let dropped = Arc::clone(&counter.dropped);
+        let emulation = EmulationThread::spawn(counter, Duration::from_millis(1));
+        thread::sleep(Duration::from_millis(5));
+
+        drop(emulation);
+
+        assert!(dropped.load(Ordering::SeqCst));
+    }
+}
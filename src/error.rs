@@ -0,0 +1,108 @@
+use core::error::Error;
+use core::fmt;
+
+/// Reasons the interpreter stops executing a ROM.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Chip8Error {
+    UnknownOpcode { pc: u16, opcode: u16 },
+    StackOverflow { pc: u16 },
+    StackUnderflow { pc: u16 },
+    /// FX30 asked for a big digit above 9 while `Quirks::big_font_digits` is `BigFontDigits::Error`.
+    InvalidBigDigit { pc: u16, digit: u8 },
+    /// EX9E or EXA1 named a key above 0xF while `Quirks::nibble_operands` is `NibbleOperands::Error`.
+    InvalidKey { pc: u16, key: u8 },
+    /// FX29 asked for a digit above 0xF while `Quirks::nibble_operands` is `NibbleOperands::Error`.
+    InvalidDigit { pc: u16, digit: u8 },
+    /// A 0NNN call into the original interpreter's machine code while `Quirks::machine_code_calls`
+    /// is `MachineCodeCalls::Error`.
+    MachineCodeCall { pc: u16, address: u16 },
+}
+
+impl fmt::Display for Chip8Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Chip8Error::UnknownOpcode { pc, opcode } => write!(f, "unsupported opcode {:#06X} at {:#05X}", opcode, pc),
+            Chip8Error::StackOverflow { pc } => write!(f, "stack overflow at {:#05X}", pc),
+            Chip8Error::StackUnderflow { pc } => write!(f, "return with an empty stack at {:#05X}", pc),
+            Chip8Error::InvalidBigDigit { pc, digit } => write!(f, "no big font digit for {:#04X} at {:#05X}", digit, pc),
+            Chip8Error::InvalidKey { pc, key } => write!(f, "no key {:#04X} at {:#05X}", key, pc),
+            Chip8Error::InvalidDigit { pc, digit } => write!(f, "no font digit for {:#04X} at {:#05X}", digit, pc),
+            Chip8Error::MachineCodeCall { pc, address } => {
+                write!(f, "call to machine code at {:#05X} from {:#05X}, which can't run here", address, pc)
+            }
+        }
+    }
+}
+
+impl Error for Chip8Error {}
+
+/// What the interpreter does with a word that decodes to no instruction, such as 5XY1, 8XYF or
+/// FX4B.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum IllegalOpcodes {
+    /// Stop with `Chip8Error::UnknownOpcode`.
+    #[default]
+    Halt,
+    /// Step over the word as a 2-byte no-op.
+    Skip,
+    /// `Skip`, logging a warning the first time each opcode turns up at each address.
+    WarnOnce,
+}
+
+impl IllegalOpcodes {
+    pub fn parse(name: &str) -> Option<IllegalOpcodes> {
+        match name.to_ascii_lowercase().as_str() {
+            "halt" => Some(IllegalOpcodes::Halt),
+            "skip" => Some(IllegalOpcodes::Skip),
+            "warn-once" => Some(IllegalOpcodes::WarnOnce),
+            _ => None,
+        }
+    }
+}
+
+/// A ROM that doesn't fit between its load address and the end of memory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RomTooLarge {
+    pub size: usize,
+    pub load_address: u16,
+    pub capacity: usize,
+}
+
+impl fmt::Display for RomTooLarge {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "ROM is {} bytes, but only {} fit when loading at {:#05X}",
+            self.size, self.capacity, self.load_address
+        )
+    }
+}
+
+impl Error for RomTooLarge {}
+
+/// Why a file can't be loaded as a ROM, found before any of it is copied into memory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RomError {
+    Empty { capacity: usize },
+    TooLarge(RomTooLarge),
+    /// The bytes look like another kind of file, described by the string, rather than bytecode.
+    NotBytecode(&'static str),
+}
+
+impl fmt::Display for RomError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RomError::Empty { capacity } => write!(f, "ROM is empty: 0 bytes, where up to {} fit", capacity),
+            RomError::TooLarge(error) => error.fmt(f),
+            RomError::NotBytecode(kind) => write!(f, "not CHIP-8 bytecode: it looks like {}", kind),
+        }
+    }
+}
+
+impl Error for RomError {}
+
+impl From<RomTooLarge> for RomError {
+    fn from(error: RomTooLarge) -> RomError {
+        RomError::TooLarge(error)
+    }
+}
@@ -0,0 +1,245 @@
+use core::error::Error;
+use core::fmt;
+use core::ops::Range;
+
+use crate::memory::Memory;
+
+/// Bytes of the hex digit font FX29 points into: 16 glyphs of 5 rows.
+pub const FONT_BYTES: usize = 80;
+/// Bytes of the SUPER-CHIP big digits FX30 points into: 10 glyphs of 10 rows.
+pub const BIG_FONT_BYTES: usize = 100;
+/// Rows, and so bytes, in a glyph of each font.
+pub const GLYPH_ROWS: u16 = 5;
+pub const BIG_GLYPH_ROWS: u16 = 10;
+/// Where the fonts go unless they're moved: the small font at 0, the big one right after it.
+pub const DEFAULT_FONT_BASE: u16 = 0x000;
+
+const BUILT_IN_FONT: [u8; FONT_BYTES] = [
+    0xF0, 0x90, 0x90, 0x90, 0xF0,
+    0x20, 0x60, 0x20, 0x20, 0x70,
+    0xF0, 0x10, 0xF0, 0x80, 0xF0,
+    0xF0, 0x10, 0xF0, 0x10, 0xF0,
+    0x90, 0x90, 0xF0, 0x10, 0x10,
+    0xF0, 0x80, 0xF0, 0x10, 0xF0,
+    0xF0, 0x80, 0xF0, 0x90, 0xF0,
+    0xF0, 0x10, 0x20, 0x40, 0x40,
+    0xF0, 0x90, 0xF0, 0x90, 0xF0,
+    0xF0, 0x90, 0xF0, 0x10, 0xF0,
+    0xF0, 0x90, 0xF0, 0x90, 0x90,
+    0xE0, 0x90, 0xE0, 0x90, 0xE0,
+    0xF0, 0x80, 0x80, 0x80, 0xF0,
+    0xE0, 0x90, 0x90, 0x90, 0xE0,
+    0xF0, 0x80, 0xF0, 0x80, 0xF0,
+    0xF0, 0x80, 0xF0, 0x80, 0x80
+];
+
+const BUILT_IN_BIG_FONT: [u8; BIG_FONT_BYTES] = [
+    0x3C, 0x7E, 0xE7, 0xC3, 0xC3, 0xC3, 0xC3, 0xE7, 0x7E, 0x3C,
+    0x18, 0x38, 0x58, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x3C,
+    0x3E, 0x7F, 0xC3, 0x06, 0x0C, 0x18, 0x30, 0x60, 0xFF, 0xFF,
+    0x3C, 0x7E, 0xC3, 0x03, 0x0E, 0x0E, 0x03, 0xC3, 0x7E, 0x3C,
+    0x06, 0x0E, 0x1E, 0x36, 0x66, 0xC6, 0xFF, 0xFF, 0x06, 0x06,
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFC, 0xFE, 0x03, 0xC3, 0x7E, 0x3C,
+    0x3E, 0x7C, 0xC0, 0xC0, 0xFC, 0xFE, 0xC3, 0xC3, 0x7E, 0x3C,
+    0xFF, 0xFF, 0x03, 0x06, 0x0C, 0x18, 0x30, 0x60, 0x60, 0x60,
+    0x3C, 0x7E, 0xC3, 0xC3, 0x7E, 0x7E, 0xC3, 0xC3, 0x7E, 0x3C,
+    0x3C, 0x7E, 0xC3, 0xC3, 0x7F, 0x3F, 0x03, 0x03, 0x3E, 0x7C
+];
+
+/// The glyphs `Cpu::init` copies into low memory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Font {
+    pub(crate) small: [u8; FONT_BYTES],
+    pub(crate) big: [u8; BIG_FONT_BYTES],
+}
+
+/// A font file that is neither `FONT_BYTES` nor `FONT_BYTES + BIG_FONT_BYTES` long.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InvalidFontSize {
+    pub size: usize,
+}
+
+impl fmt::Display for InvalidFontSize {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "a font is {} bytes (the 16 hex digits, 5 rows each) or {} bytes (followed by the 10 big digits, 10 rows each), not {}",
+            FONT_BYTES,
+            FONT_BYTES + BIG_FONT_BYTES,
+            self.size
+        )
+    }
+}
+
+impl Error for InvalidFontSize {}
+
+impl Font {
+    /// Reads a font file: the hex digits, optionally followed by the big digits. Whatever is
+    /// missing comes from the built-in font.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Font, InvalidFontSize> {
+        let mut font = Font::default();
+        match bytes.len() {
+            FONT_BYTES => font.small.copy_from_slice(bytes),
+            size if size == FONT_BYTES + BIG_FONT_BYTES => {
+                font.small.copy_from_slice(&bytes[..FONT_BYTES]);
+                font.big.copy_from_slice(&bytes[FONT_BYTES..]);
+            }
+            size => return Err(InvalidFontSize { size }),
+        }
+        Ok(font)
+    }
+}
+
+impl Default for Font {
+    fn default() -> Self {
+        Font { small: BUILT_IN_FONT, big: BUILT_IN_BIG_FONT }
+    }
+}
+
+/// Where the fonts live in memory and what they hold: the small font at `base`, a glyph every
+/// `GLYPH_ROWS` bytes, and the big font right after it, a glyph every `BIG_GLYPH_ROWS`. FX29 and
+/// FX30 find their digits through it, and `Cpu::init` and `Cpu::reset` write it into memory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FontMap {
+    font: Font,
+    base: u16,
+}
+
+impl FontMap {
+    pub fn new(font: Font, base: u16) -> FontMap {
+        FontMap { font, base }
+    }
+
+    pub fn font(&self) -> &Font {
+        &self.font
+    }
+
+    pub fn set_font(&mut self, font: Font) {
+        self.font = font;
+    }
+
+    /// Moves the fonts to `base`; they're only written there by the next `write_to`.
+    pub fn relocate(&mut self, base: u16) {
+        self.base = base;
+    }
+
+    pub fn base(&self) -> u16 {
+        self.base
+    }
+
+    pub fn big_base(&self) -> u16 {
+        self.base + FONT_BYTES as u16
+    }
+
+    /// The addresses both fonts take up.
+    pub fn range(&self) -> Range<u16> {
+        self.base..self.big_base() + BIG_FONT_BYTES as u16
+    }
+
+    /// Where FX29 points I for `digit`; only its low nibble counts.
+    pub fn addr_of_digit(&self, digit: u8) -> u16 {
+        self.base + u16::from(digit & 0xF) * GLYPH_ROWS
+    }
+
+    /// Where FX30 points I for `digit`; there are only big glyphs for 0 to 9, so it's taken
+    /// modulo 10.
+    pub fn addr_of_big_digit(&self, digit: u8) -> u16 {
+        self.big_base() + u16::from(digit % 10) * BIG_GLYPH_ROWS
+    }
+
+    /// The rows of `digit`'s glyph, its low nibble counting as for FX29.
+    pub fn glyph(&self, digit: u8) -> &[u8] {
+        let start = usize::from(digit & 0xF) * GLYPH_ROWS as usize;
+        &self.font.small[start..start + GLYPH_ROWS as usize]
+    }
+
+    /// The rows of `digit`'s big glyph, taken modulo 10 as for FX30.
+    pub fn big_glyph(&self, digit: u8) -> &[u8] {
+        let start = usize::from(digit % 10) * BIG_GLYPH_ROWS as usize;
+        &self.font.big[start..start + BIG_GLYPH_ROWS as usize]
+    }
+
+    /// The digit whose small glyph starts at `address`, if one does, e.g. for naming what I
+    /// points at after FX29.
+    pub fn digit_at(&self, address: u16) -> Option<u8> {
+        let offset = address.checked_sub(self.base)?;
+        (offset < FONT_BYTES as u16 && offset % GLYPH_ROWS == 0).then_some((offset / GLYPH_ROWS) as u8)
+    }
+
+    /// The digit whose big glyph starts at `address`, if one does.
+    pub fn big_digit_at(&self, address: u16) -> Option<u8> {
+        let offset = address.checked_sub(self.big_base())?;
+        (offset < BIG_FONT_BYTES as u16 && offset % BIG_GLYPH_ROWS == 0).then_some((offset / BIG_GLYPH_ROWS) as u8)
+    }
+
+    /// Copies both fonts into `memory` where they belong, without triggering watchpoints.
+    pub fn write_to(&self, memory: &mut Memory) {
+        memory.load(self.base, &self.font.small);
+        memory.load(self.big_base(), &self.font.big);
+    }
+}
+
+impl Default for FontMap {
+    fn default() -> Self {
+        FontMap::new(Font::default(), DEFAULT_FONT_BASE)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_bytes_accepts_both_sizes() {
+        let small_only = Font::from_bytes(&[0xAA; FONT_BYTES]).unwrap();
+        let both = Font::from_bytes(&[0x55; FONT_BYTES + BIG_FONT_BYTES]).unwrap();
+
+        assert_eq!(small_only.small, [0xAA; FONT_BYTES]);
+        assert_eq!(small_only.big, BUILT_IN_BIG_FONT);
+        assert_eq!(both.small, [0x55; FONT_BYTES]);
+        assert_eq!(both.big, [0x55; BIG_FONT_BYTES]);
+    }
+
+    #[test]
+    fn the_font_bytes_land_where_the_map_says() {
+        for &base in &[DEFAULT_FONT_BASE, 0x050, 0x123] {
+            let map = FontMap::new(Font::default(), base);
+            let mut memory = Memory::new();
+
+            map.write_to(&mut memory);
+
+            for digit in 0..16 {
+                let address = map.addr_of_digit(digit) as usize;
+                assert_eq!(&memory.bytes()[address..address + GLYPH_ROWS as usize], map.glyph(digit));
+                assert_eq!(map.digit_at(address as u16), Some(digit));
+            }
+            for digit in 0..10 {
+                let address = map.addr_of_big_digit(digit) as usize;
+                assert_eq!(&memory.bytes()[address..address + BIG_GLYPH_ROWS as usize], map.big_glyph(digit));
+                assert_eq!(map.big_digit_at(address as u16), Some(digit));
+            }
+            let range = map.range();
+            assert_eq!(range.len(), FONT_BYTES + BIG_FONT_BYTES);
+            assert!(memory.bytes().iter().enumerate().all(|(address, &byte)| byte == 0 || range.contains(&(address as u16))));
+        }
+    }
+
+    #[test]
+    fn digits_wrap_as_fx29_and_fx30_take_them() {
+        let map = FontMap::default();
+
+        assert_eq!(map.addr_of_digit(0x1A), map.addr_of_digit(0xA));
+        assert_eq!(map.addr_of_big_digit(12), map.addr_of_big_digit(2));
+        assert_eq!(map.glyph(0x10), &[0xF0, 0x90, 0x90, 0x90, 0xF0]);
+        // between glyphs, and past the fonts, there's no digit
+        assert_eq!(map.digit_at(map.addr_of_digit(3) + 1), None);
+        assert_eq!(map.digit_at(map.big_base()), None);
+        assert_eq!(map.big_digit_at(map.range().end), None);
+    }
+
+    #[test]
+    fn from_bytes_rejects_other_sizes() {
+        assert_eq!(Font::from_bytes(&[0; 160]), Err(InvalidFontSize { size: 160 }));
+        assert!(Font::from_bytes(&[]).unwrap_err().to_string().contains("80 bytes"));
+    }
+}
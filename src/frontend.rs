@@ -0,0 +1,232 @@
+use core::convert::Infallible;
+
+use crate::cpu::{Cpu, Flow};
+use crate::display::{Display, Rect};
+use crate::error::Chip8Error;
+use crate::keys::Keys;
+use crate::timing::{FrameBudget, Timing};
+
+/// What the interpreter needs from whatever shows it: somewhere to draw, a keypad and a buzzer.
+/// `run_frame` drives a `Cpu` through one, so a new frontend doesn't need to know the core's
+/// internals. The binary's ggez window, in its `frontends::ggez` module behind the
+/// `frontend-ggez` feature, is one; `terminal::TerminalFrontend` is another.
+pub trait Frontend {
+    /// Shows the display as it stands at the end of a frame.
+    fn present(&mut self, display: &Display);
+    /// Like `present`, also told the region changed since the last frame, or that nothing was,
+    /// for frontends that can skip redrawing the rest. By default the hint goes unused.
+    fn present_dirty(&mut self, display: &Display, _dirty: Option<Rect>) {
+        self.present(display);
+    }
+    /// Brings `keys` up to date with what's held down; called before every frame.
+    fn poll_keys(&mut self, keys: &mut Keys);
+    /// Switches the buzzer on or off; called after every frame with whether the sound timer runs.
+    fn beep(&mut self, on: bool);
+}
+
+/// Looks on as `run_instructions` runs a frame, for whoever needs to stop partway through it:
+/// the debugger at a breakpoint, or a headless run at its instruction limit. `()` looks on
+/// without ever stopping.
+pub trait FrameObserver {
+    /// Why the frame stopped early.
+    type Stop;
+    type Error: From<Chip8Error>;
+
+    /// Called before each instruction with what's left of the frame; stopping leaves the
+    /// instruction and the timer tick for later.
+    fn before(&mut self, _cpu: &mut Cpu, _budget: &FrameBudget) -> Result<Option<Self::Stop>, Self::Error> {
+        Ok(None)
+    }
+
+    /// Called after each instruction with the address it was at and whether it ended the frame;
+    /// stopping leaves the timer tick for later.
+    fn after(&mut self, _cpu: &mut Cpu, _pc: u16, _frame_ended: bool) -> Result<Option<Self::Stop>, Self::Error> {
+        Ok(None)
+    }
+}
+
+impl FrameObserver for () {
+    type Stop = Infallible;
+    type Error = Chip8Error;
+}
+
+/// How `run_instructions` left the frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameEnd<S> {
+    /// Run to its end, which the timer tick marks.
+    Finished,
+    /// Stopped early by the observer.
+    Stopped(S),
+}
+
+/// The frame loop everything that runs frames shares: executes instructions while `budget` has
+/// time, ending the frame early when the CPU halts or the display-wait quirk ends it at a DXYN,
+/// then ticks the timers. `observer` sees every instruction and can stop the frame short of its
+/// tick, in which case `budget` keeps what's left for picking the frame up again.
+pub fn run_instructions<O: FrameObserver + ?Sized>(cpu: &mut Cpu, budget: &mut FrameBudget, observer: &mut O) -> Result<FrameEnd<O::Stop>, O::Error> {
+    while budget.has_time() {
+        if let Some(stop) = observer.before(cpu, budget)? {
+            return Ok(FrameEnd::Stopped(stop));
+        }
+        let pc = cpu.pc();
+        let opcode = cpu.opcode_at(pc);
+        let flow = cpu.cycle()?;
+        budget.spend(opcode);
+        let frame_ended = flow == Flow::FrameBoundary || !budget.has_time();
+        if let Some(stop) = observer.after(cpu, pc, frame_ended)? {
+            return Ok(FrameEnd::Stopped(stop));
+        }
+        if frame_ended || cpu.halted().is_some() {
+            break;
+        }
+    }
+    cpu.tick_timers();
+    Ok(FrameEnd::Finished)
+}
+
+/// Runs one 60 Hz frame through `frontend`: polls the keys, executes `cycles_per_frame`
+/// instructions or however many `timing` allows with `run_instructions`, then sounds the buzzer
+/// and presents the display. Returns the error if the CPU halted on one.
+pub fn run_frame<F: Frontend + ?Sized>(cpu: &mut Cpu, frontend: &mut F, cycles_per_frame: usize, timing: Timing) -> Result<(), Chip8Error> {
+    frontend.poll_keys(cpu.keys_mut());
+    match run_instructions(cpu, &mut FrameBudget::new(timing, cycles_per_frame), &mut ())? {
+        FrameEnd::Finished => {}
+        FrameEnd::Stopped(never) => match never {},
+    }
+    frontend.beep(cpu.is_buzzing());
+    let dirty = cpu.take_dirty();
+    frontend.present_dirty(cpu.display(), dirty);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cpu::Halt;
+    use crate::memory::Memory;
+    use crate::render::VisualBuzzer;
+    use crate::CYCLES_PER_FRAME;
+
+    /// Holds one key down and remembers what it was shown and told.
+    #[derive(Default)]
+    struct Recorder {
+        frames: Vec<String>,
+        dirty: Vec<Option<Rect>>,
+        beeps: Vec<bool>,
+    }
+
+    impl Frontend for Recorder {
+        fn present(&mut self, display: &Display) {
+            self.frames.push(display.to_ascii());
+        }
+
+        fn present_dirty(&mut self, display: &Display, dirty: Option<Rect>) {
+            self.dirty.push(dirty);
+            self.present(display);
+        }
+
+        fn poll_keys(&mut self, keys: &mut Keys) {
+            keys.press(0x7);
+        }
+
+        fn beep(&mut self, on: bool) {
+            self.beeps.push(on);
+        }
+    }
+
+    // stops before the instruction at `at`
+    struct StopAt {
+        at: u16,
+        seen: Vec<(u16, bool)>,
+    }
+
+    impl FrameObserver for StopAt {
+        type Stop = u16;
+        type Error = Chip8Error;
+
+        fn before(&mut self, cpu: &mut Cpu, _budget: &FrameBudget) -> Result<Option<u16>, Chip8Error> {
+            Ok((cpu.pc() == self.at).then_some(cpu.pc()))
+        }
+
+        fn after(&mut self, _cpu: &mut Cpu, pc: u16, frame_ended: bool) -> Result<Option<u16>, Chip8Error> {
+            self.seen.push((pc, frame_ended));
+            Ok(None)
+        }
+    }
+
+    #[test]
+    fn an_observer_stops_the_frame_short_of_its_tick() {
+        let mut cpu = Cpu::new(Memory::new(), Display::new());
+        // LD V0, 9; LD DT, V0; ADD V1, 1; JP 0x204
+        cpu.init(vec![0x60, 0x09, 0xF0, 0x15, 0x71, 0x01, 0x12, 0x04]);
+        let mut observer = StopAt { at: 0x206, seen: Vec::new() };
+        let mut budget = FrameBudget::new(Timing::Modern, 4);
+
+        let end = run_instructions(&mut cpu, &mut budget, &mut observer).unwrap();
+
+        assert_eq!(end, FrameEnd::Stopped(0x206));
+        assert_eq!(observer.seen, vec![(0x200, false), (0x202, false), (0x204, false)]);
+        assert_eq!(cpu.delay_timer(), 9);
+        assert!(budget.has_time());
+
+        observer.at = 0;
+        assert_eq!(run_instructions(&mut cpu, &mut budget, &mut observer).unwrap(), FrameEnd::Finished);
+        // the fourth instruction used up the budget and ended the frame, which the tick closed
+        assert_eq!(observer.seen[3], (0x206, true));
+        assert_eq!(cpu.delay_timer(), 8);
+    }
+
+    #[test]
+    fn a_frontend_without_a_window_runs_a_rom() {
+        let mut cpu = Cpu::new(Memory::new(), Display::new());
+        // LD V0, K; LD ST, V0; LD F, V0; DRW V1, V1, 5; JP 0x20A; JP 0x208, which never halts
+        cpu.init(vec![0xF0, 0x0A, 0xF0, 0x18, 0xF0, 0x29, 0xD1, 0x15, 0x12, 0x0A, 0x12, 0x08]);
+        let mut frontend = Recorder::default();
+
+        for _ in 0..8 {
+            run_frame(&mut cpu, &mut frontend, CYCLES_PER_FRAME, Timing::Modern).unwrap();
+        }
+
+        assert_eq!(cpu.registers()[0], 7);
+        // the sound timer starts at 7 and is ticked once per frame
+        assert_eq!(frontend.beeps, vec![true, true, true, true, true, true, false, false]);
+        assert_eq!(frontend.frames.len(), 8);
+        assert_eq!(&frontend.frames[0][..5], "####.");
+        // only the first frame drew anything, on top of the blank screen nothing had shown yet
+        assert_eq!(frontend.dirty[0], Some(Rect { x: 0, y: 0, width: 64, height: 32 }));
+        assert!(frontend.dirty[1..].iter().all(Option::is_none));
+    }
+
+    #[test]
+    fn the_visual_buzzer_shows_exactly_the_frames_the_beep_sounds() {
+        let mut cpu = Cpu::new(Memory::new(), Display::new());
+        // LD V0, 3; LD ST, V0; JP 0x206; JP 0x204
+        cpu.init(vec![0x60, 0x03, 0xF0, 0x18, 0x12, 0x06, 0x12, 0x04]);
+        let mut frontend = Recorder::default();
+        let mut shown = Vec::new();
+
+        for _ in 0..5 {
+            run_frame(&mut cpu, &mut frontend, CYCLES_PER_FRAME, Timing::Modern).unwrap();
+            shown.push(VisualBuzzer::Border.shows(cpu.sound_timer()));
+        }
+
+        assert_eq!(shown, vec![true, true, false, false, false]);
+        assert_eq!(shown, frontend.beeps);
+    }
+
+    #[test]
+    fn the_timers_stay_still_and_the_buzzer_off_once_halted() {
+        let mut cpu = Cpu::new(Memory::new(), Display::new());
+        // LD V0, 5; LD DT, V0; LD ST, V0; JP 0x206
+        cpu.init(vec![0x60, 0x05, 0xF0, 0x15, 0xF0, 0x18, 0x12, 0x06]);
+        let mut frontend = Recorder::default();
+
+        for _ in 0..4 {
+            run_frame(&mut cpu, &mut frontend, CYCLES_PER_FRAME, Timing::Modern).unwrap();
+        }
+
+        assert_eq!(cpu.halted(), Some(Halt::Looped(0x206)));
+        assert_eq!((cpu.delay_timer(), cpu.sound_timer()), (5, 5));
+        assert_eq!(frontend.beeps, vec![false; 4]);
+    }
+}
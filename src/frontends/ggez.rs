@@ -0,0 +1,1918 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, BufRead};
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+use ggez::{Context, ContextBuilder, event, GameError, GameResult, timer};
+use ggez::conf::{FullscreenType, WindowMode, WindowSetup};
+use ggez::event::{Axis, Button, EventHandler, GamepadId, KeyCode, KeyMods, MouseButton};
+use ggez::graphics;
+use ggez::graphics::{Color, DrawParam};
+use log::{info, warn};
+use rodio::{OutputStream, Source};
+
+use chip_8_emulator::{Chip8Error, Cpu, Display, Halt, Keys, Memory, Rect, SaveState, SaveStateError, CYCLES_PER_FRAME};
+use chip_8_emulator::audio::{SoundControl, Stream, SAMPLE_RATE};
+use chip_8_emulator::autosave;
+use chip_8_emulator::builtin;
+use chip_8_emulator::cli::Config;
+use chip_8_emulator::configfile::{self, Change, ConfigFile};
+use chip_8_emulator::debugger::{self, Debugger, SlowMotion, Stop};
+use chip_8_emulator::edit::{self, Edit};
+use chip_8_emulator::emulation::{EmulationThread, Machine};
+use chip_8_emulator::frontend::Frontend;
+use chip_8_emulator::gamepad::{AxisDirection, HeldKeys, StickAxis};
+use chip_8_emulator::heatmap::{self, GRID_HEIGHT, GRID_WIDTH};
+use chip_8_emulator::hexview::HexView;
+use chip_8_emulator::keymap::{self, Keymap, LayoutHint};
+use chip_8_emulator::palette::{self, Palette, Palettes};
+use chip_8_emulator::pausemenu::{MenuAction, MenuEntry, MenuInput, PauseMenu};
+use chip_8_emulator::phosphor::Phosphor;
+use chip_8_emulator::recent::RecentRoms;
+use chip_8_emulator::render::{self, KeypadPanel, VisualBuzzer};
+use chip_8_emulator::replay::{Player, Recorder};
+use chip_8_emulator::rewind::Rewind;
+use chip_8_emulator::romfile;
+use chip_8_emulator::rompicker::{self, RomPicker};
+use chip_8_emulator::romwatch::RomWatcher;
+use chip_8_emulator::rplflags::RPL_FLAGS;
+use chip_8_emulator::screenshot;
+use chip_8_emulator::settings::RomSettings;
+use chip_8_emulator::speed::{self, SpeedMeter};
+use chip_8_emulator::spriteview::{self, SpriteView};
+
+use crate::{beside_rom, config_dir, data_dir, export_state, import_state, is_file, load_rom, load_rom_bytes, print_history, print_profile, rom_name, rpl_flags_path, save_rom_settings, save_rpl_flags, write_core_dump, write_coverage, LoadedRom, CONFIG_FILE_NAME, CUSTOM_PALETTE_NAME};
+
+const MESSAGE_DURATION: Duration = Duration::from_secs(3);
+// set by Ctrl+C in the terminal, which closes the window the way closing it does
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+// the most often skipped 0NNN calls are reported, so a ROM looping over one doesn't flood stderr
+const SKIPPED_CALLS_INTERVAL: Duration = Duration::from_secs(5);
+const FRAMES_PER_SECOND: u64 = 60;
+const FRAME_DURATION: Duration = Duration::from_nanos(1_000_000_000 / FRAMES_PER_SECOND);
+// restore a snapshot every other frame while rewinding, i.e. rewind at five times real speed
+const FRAMES_PER_REWIND_STEP: u32 = 2;
+const WINDOW_WIDTH: f32 = 640.0;
+const WINDOW_HEIGHT: f32 = 320.0;
+// with the debug overlay shown the play area gives up a column this wide on the right
+const OVERLAY_WIDTH: f32 = 192.0;
+// and with the on-screen keypad shown, a column this wide to the left of the overlay's
+const KEYPAD_WIDTH: f32 = 160.0;
+// how thick the visual buzzer's border around the play area is
+const BUZZER_BORDER_WIDTH: f32 = 6.0;
+// of the pause menu's box, which is centred in the window
+const MENU_WIDTH: f32 = 200.0;
+const OVERLAY_LINE_HEIGHT: f32 = 18.0;
+// each address of the heat map is drawn this many pixels square
+const HEAT_MAP_CELL: f32 = 4.0;
+// the region under I outlined on the heat map, as long as the longest sprite it can point at
+const HEAT_MAP_I_BYTES: u16 = 16;
+// each sprite pixel of the sprite viewer is drawn this many pixels square
+const SPRITE_VIEW_SCALE: f32 = 8.0;
+// wide enough for a line of the sprite viewer's hex
+const SPRITE_VIEW_WIDTH: f32 = 240.0;
+const OVERLAY_HISTORY_ROWS: usize = 16;
+const SOUND_VOLUME: f32 = 0.25;
+// where the ROM picker looks, and how many of its entries fit in the window
+const ROM_DIRECTORIES: [&str; 2] = ["roms", "."];
+const PICKER_ROWS: usize = 15;
+const CRT_VERTEX_SHADER: &str = include_str!("../../assets/crt_150.glslv");
+const CRT_FRAGMENT_SHADER: &str = include_str!("../../assets/crt_150.glslf");
+// the recently loaded ROMs, in the data directory
+const RECENT_FILE_NAME: &str = "recent.toml";
+// relative to the current directory
+const SCREENSHOT_DIRECTORY: &str = "screenshots";
+
+gfx_defines! {
+    constant Crt {
+        resolution: [f32; 2] = "u_Resolution",
+        time: f32 = "u_Time",
+    }
+}
+
+/// The beep as a rodio source that never ends: it plays silence while the gate is closed, so
+/// sounding is only a matter of the emulation thread opening it.
+struct Beeper(Stream);
+
+impl Iterator for Beeper {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        self.0.next()
+    }
+}
+
+impl Source for Beeper {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        1
+    }
+
+    fn sample_rate(&self) -> u32 {
+        SAMPLE_RATE
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+}
+
+/// Starts the beep's stream on the default output, which plays for as long as the returned
+/// handle lives. Without a working output the emulator runs silently.
+fn open_beeper(sound: &Arc<SoundControl>) -> Option<OutputStream> {
+    let (stream, handle) = OutputStream::try_default().map_err(|error| warn!("No sound: {}", error)).ok()?;
+    handle.play_raw(Beeper(Stream::new(Arc::clone(sound))).amplify(SOUND_VOLUME)).map_err(|error| warn!("No sound: {}", error)).ok()?;
+    Some(stream)
+}
+
+/// The window as the emulation thread sees it: the `Frontend` a `Session` runs its frames
+/// through. The window itself lives on the main thread, so it only hands over the keys it holds
+/// and picks up what was presented with the next `Frame`.
+struct WindowFrontend {
+    // the keypad as the keyboard, controllers and mouse hold it
+    held: [bool; 16],
+    // opened while the sound timer runs, shared with the window's beep stream
+    sound: Arc<SoundControl>,
+    // what was presented since the window was last handed a frame
+    display: Display,
+    dirty: Option<Rect>,
+}
+
+impl WindowFrontend {
+    fn new(sound: Arc<SoundControl>) -> WindowFrontend {
+        WindowFrontend { held: [false; 16], sound, display: Display::new(), dirty: None }
+    }
+
+    fn hold(&mut self, key: u8, pressed: bool) {
+        if let Some(held) = self.held.get_mut(key as usize) {
+            *held = pressed;
+        }
+    }
+
+    /// Passes on the ROM's XO-CHIP pattern and pitch for the beep to play.
+    fn tune(&self, cpu: &Cpu) {
+        self.sound.set_voice(cpu);
+    }
+
+    /// The display as last presented and what changed on it since the last call.
+    fn take_presented(&mut self) -> (Display, Option<Rect>) {
+        (mem::replace(&mut self.display, Display::new()), self.dirty.take())
+    }
+}
+
+impl Frontend for WindowFrontend {
+    fn present(&mut self, display: &Display) {
+        self.display.clone_from(display);
+    }
+
+    fn present_dirty(&mut self, display: &Display, dirty: Option<Rect>) {
+        self.present(display);
+        self.dirty = match (self.dirty, dirty) {
+            (Some(presented), Some(dirty)) => Some(presented.union(dirty)),
+            (presented, dirty) => presented.or(dirty),
+        };
+    }
+
+    fn poll_keys(&mut self, keys: &mut Keys) {
+        for (key, &held) in self.held.iter().enumerate() {
+            match (held, keys.is_pressed(key as u8)) {
+                (true, false) => keys.press(key as u8),
+                (false, true) => keys.release(key as u8),
+                _ => {}
+            }
+        }
+    }
+
+    fn beep(&mut self, on: bool) {
+        self.sound.set_gate(on);
+    }
+}
+
+/// The machine and everything that keeps step with it frame by frame. It lives on the
+/// emulation thread, so however long a frame takes the window stays responsive; the window
+/// reaches it through the thread's commands.
+struct Session {
+    cpu: Cpu,
+    debugger: Debugger,
+    // with --record, logs the keypad input; with --replay, supplies it until the recording ends
+    recorder: Option<Recorder>,
+    player: Option<Player>,
+    rewind: Rewind,
+    rewinding: bool,
+    frames_since_rewind_step: u32,
+    hex_view: HexView,
+    sprite_view: SpriteView,
+    // what the window is shown, the keys it holds and the beep it plays
+    window: WindowFrontend,
+    // the messages the window hasn't been handed yet
+    messages: Vec<String>,
+    skipped_calls_reported: Option<Instant>,
+    // what the CPU halted on; the window closes once it sees it
+    error: Option<Chip8Error>,
+}
+
+/// What the window is handed after a frame: what it needs to draw, play and title it without
+/// waiting on the emulation thread.
+struct Frame {
+    display: Display,
+    // what changed on the display since the last frame the window was handed
+    dirty: Option<Rect>,
+    // the keypad keys held down, lit up on the on-screen keypad
+    keys: [bool; 16],
+    // for the visual buzzer, which shows while it's above zero; 0 once halted, as the beep stops
+    sound_timer: u8,
+    // whether frozen cheats are being written, which the window says
+    cheats: bool,
+    instructions: u64,
+    halted: Option<Halt>,
+    paused: bool,
+    // shown in the title and overlay while it's on
+    slow_motion: Option<SlowMotion>,
+    messages: Vec<String>,
+    error: Option<Chip8Error>,
+}
+
+impl Frame {
+    fn is_finished(&self) -> bool {
+        self.halted == Some(Halt::Exited)
+    }
+}
+
+impl Session {
+    fn new(cpu: Cpu, debugger: Debugger, recorder: Option<Recorder>, player: Option<Player>, rewind: Rewind, sound: Arc<SoundControl>) -> Session {
+        Session {
+            cpu,
+            debugger,
+            recorder,
+            player,
+            rewind,
+            rewinding: false,
+            frames_since_rewind_step: 0,
+            hex_view: HexView::new(),
+            sprite_view: SpriteView::new(),
+            window: WindowFrontend::new(sound),
+            messages: Vec::new(),
+            skipped_calls_reported: None,
+            error: None,
+        }
+    }
+
+    /// Presents the display and hands the window a copy of what it needs, with the messages
+    /// gathered since the last one.
+    fn frame(&mut self) -> Frame {
+        let dirty = self.cpu.take_dirty();
+        self.window.present_dirty(self.cpu.display(), dirty);
+        let (display, dirty) = self.window.take_presented();
+        Frame {
+            display,
+            dirty,
+            keys: self.keys(),
+            sound_timer: if self.cpu.is_buzzing() { self.cpu.sound_timer() } else { 0 },
+            cheats: self.cpu.cheats_enabled() && !self.cpu.frozen().is_empty(),
+            instructions: self.cpu.instructions_executed(),
+            halted: self.cpu.halted(),
+            paused: self.debugger.is_paused(),
+            slow_motion: self.debugger.slow_motion(),
+            messages: mem::take(&mut self.messages),
+            error: self.error,
+        }
+    }
+
+    fn keys(&self) -> [bool; 16] {
+        let mut keys = [false; 16];
+        for (key, held) in keys.iter_mut().enumerate() {
+            *held = self.cpu.keys().is_pressed(key as u8);
+        }
+        keys
+    }
+
+    /// Presses or releases keypad `key` for the keyboard, a controller or the mouse. It reaches
+    /// the keypad straight away rather than with the next frame, so a single step sees it too.
+    fn set_key(&mut self, key: u8, pressed: bool) {
+        self.window.hold(key, pressed);
+        self.poll_keys();
+    }
+
+    /// Brings the keypad up to the keys the window holds, logging each change when recording.
+    /// While replaying, the recording has the keypad to itself.
+    fn poll_keys(&mut self) {
+        if self.player.is_some() {
+            return;
+        }
+        let before = self.keys();
+        self.window.poll_keys(self.cpu.keys_mut());
+        if let Some(recorder) = &mut self.recorder {
+            for (key, &was_pressed) in before.iter().enumerate() {
+                let pressed = self.cpu.keys().is_pressed(key as u8);
+                if pressed != was_pressed {
+                    recorder.key(&self.cpu, key as u8, pressed);
+                }
+            }
+        }
+    }
+
+    /// Runs a `--console` command such as `set V3 0x1F` and says what it changed. The machine
+    /// has to be paused, and a replay can't be edited; an edit made while recording is noted
+    /// in the recording, which can't be checked past it.
+    fn console_command(&mut self, line: &str) -> String {
+        if self.player.is_some() {
+            return String::from("Not available while replaying");
+        }
+        if !self.debugger.is_paused() {
+            return String::from("Pause first, with P");
+        }
+        match Edit::parse(line).and_then(|edit| self.cpu.apply_edit(&edit)) {
+            Ok(changes) => changes.iter().map(edit::Change::to_string).collect::<Vec<String>>().join("\n"),
+            Err(error) => format!("Error: {}", error),
+        }
+    }
+
+    /// What's going on that would stop the machine being taken somewhere a recording can't
+    /// follow, if anything.
+    fn activity(&self) -> Option<&'static str> {
+        match (&self.recorder, &self.player) {
+            (Some(_), _) => Some("recording"),
+            (_, Some(_)) => Some("replaying"),
+            (None, None) => None,
+        }
+    }
+
+    /// Feeds the replay's input in before a frame runs.
+    fn before_frame(&mut self) {
+        if let Some(player) = &mut self.player {
+            player.apply(&mut self.cpu);
+        }
+    }
+
+    /// Records a checkpoint, or checks the replay against one, after a frame has run. A replay
+    /// that diverges or reaches its end hands the keypad back.
+    fn after_frame(&mut self) {
+        self.cpu.decay_heat_map();
+        if let Some(recorder) = &mut self.recorder {
+            recorder.frame(&self.cpu);
+        }
+        let (result, finished) = match &mut self.player {
+            Some(player) => (player.check(&self.cpu), player.is_finished(&self.cpu)),
+            None => return,
+        };
+        match result {
+            Err(error) => {
+                eprintln!("{}", error);
+                self.player = None;
+                self.messages.push(format!("Replay stopped: {}", error));
+            }
+            Ok(()) if finished => {
+                self.player = None;
+                self.messages.push(String::from("Replay finished"));
+            }
+            Ok(()) => {}
+        }
+    }
+
+    fn report_halt(&mut self, result: Result<(), Chip8Error>) {
+        if let Err(error) = result {
+            print_history(&self.cpu);
+            self.messages.push(format!("CPU halted: {}", error));
+        }
+    }
+
+    fn report_stop(&mut self, stop: Option<Stop>) {
+        if let Some(stop) = stop {
+            print!("{}\n{}", stop, debugger::describe(&self.cpu));
+            self.messages.push(stop.to_string());
+        }
+    }
+
+    fn step(&mut self) {
+        let result = self.debugger.step(&mut self.cpu);
+        self.report_halt(result);
+    }
+
+    fn step_over(&mut self) {
+        match self.debugger.step_over(&mut self.cpu) {
+            Ok(stop) => self.report_stop(stop),
+            Err(error) => self.report_halt(Err(error)),
+        }
+    }
+
+    fn run_until_return(&mut self) {
+        if self.debugger.is_paused() && self.cpu.sp() == 0 {
+            self.messages.push(String::from("Not in a subroutine"));
+            return;
+        }
+        match self.debugger.run_until_return(&mut self.cpu) {
+            Ok(stop) => self.report_stop(stop),
+            Err(error) => self.report_halt(Err(error)),
+        }
+    }
+
+    fn advance_frame(&mut self) {
+        self.before_frame();
+        let result = self.debugger.advance_frame(&mut self.cpu);
+        self.after_frame();
+        match result {
+            Ok(stop) => self.report_stop(stop),
+            Err(error) => self.report_halt(Err(error)),
+        }
+    }
+}
+
+impl Machine for Session {
+    type Frame = Frame;
+
+    fn run_frame(&mut self) -> Frame {
+        if self.rewinding {
+            self.frames_since_rewind_step += 1;
+            if self.frames_since_rewind_step >= FRAMES_PER_REWIND_STEP {
+                self.frames_since_rewind_step = 0;
+                self.rewind.rewind(&mut self.cpu);
+            }
+        } else {
+            for _ in 0..self.debugger.frames_per_tick() {
+                if self.error.is_some() || self.debugger.is_paused() {
+                    break;
+                }
+                self.before_frame();
+                self.poll_keys();
+                match self.debugger.run_frame(&mut self.cpu) {
+                    Ok(stop) => {
+                        self.after_frame();
+                        self.report_stop(stop);
+                        self.rewind.on_frame(&self.cpu);
+                    }
+                    Err(error) => {
+                        print_history(&self.cpu);
+                        self.error = Some(error);
+                    }
+                }
+            }
+        }
+        // turbo is silent rather than squeezing several frames of sound into one
+        let audible = !self.rewinding && !self.debugger.is_paused() && !self.debugger.is_turbo() && self.error.is_none();
+        self.window.tune(&self.cpu);
+        self.window.beep(audible && self.cpu.is_buzzing());
+        if self.skipped_calls_reported.is_none_or(|reported| reported.elapsed() >= SKIPPED_CALLS_INTERVAL) {
+            if let Some(skipped) = self.cpu.take_skipped_calls() {
+                warn!("{}", skipped);
+                self.skipped_calls_reported = Some(Instant::now());
+            }
+        }
+        self.frame()
+    }
+
+    /// Frames the window was too slow to pick up still get their messages shown, and what they
+    /// drew redrawn.
+    fn coalesce(mut dropped: Frame, next: &mut Frame) {
+        next.dirty = match (dropped.dirty, next.dirty) {
+            (Some(dropped), Some(dirty)) => Some(dropped.union(dirty)),
+            (dropped, dirty) => dropped.or(dirty),
+        };
+        dropped.messages.append(&mut next.messages);
+        next.messages = dropped.messages;
+    }
+}
+
+struct Emulator {
+    config: Config,
+    // runs the ROM; replacing or dropping it stops and joins its thread
+    machine: EmulationThread<Session>,
+    // the latest frame the machine published
+    frame: Frame,
+    // None until a ROM is picked
+    rom: Option<PathBuf>,
+    picker: Option<RomPicker>,
+    // Esc's menu, drawn over the game while it's paused
+    pause_menu: Option<PauseMenu>,
+    recent: RecentRoms,
+    // with --watch-rom, reloads the ROM when it's rebuilt
+    watcher: Option<RomWatcher>,
+    // applies edits to chip8.toml
+    config_watcher: Option<RomWatcher>,
+    // shown in the window title
+    rom_name: String,
+    // what the ROM's settings are stored under, and the settings it's running with
+    rom_key: String,
+    settings: RomSettings,
+    keymap: Keymap,
+    layout_hint: LayoutHint,
+    speed: SpeedMeter,
+    state_path: PathBuf,
+    // where Shift+F5 exports the state as JSON and Shift+F7 imports it from
+    json_state_path: PathBuf,
+    flags_path: PathBuf,
+    // the RPL flags as last read from or written to `flags_path`
+    saved_flags: [u8; RPL_FLAGS],
+    // the ROM's autosave and when it was offered; Enter resumes from it while the offer is shown
+    resume_offer: Option<(SaveState, Instant)>,
+    message: Option<(String, Instant)>,
+    overlay_visible: bool,
+    overlay_shows_history: bool,
+    keypad_visible: bool,
+    // the on-screen key the mouse is holding down, released with the button wherever it is then
+    mouse_key: Option<u8>,
+    // one Text per overlay line, rebuilt only when that line's contents change
+    overlay_text: Vec<(String, graphics::Text)>,
+    hex_view_visible: bool,
+    hex_view_text: Vec<(String, graphics::Text)>,
+    // Ctrl+F6's memory heat map, which the machine only counts accesses for while it's shown
+    heat_map_visible: bool,
+    heat_map_legend: Vec<(String, graphics::Text)>,
+    // Ctrl+F7's preview of the memory at I as a sprite, with its header and raw hex
+    sprite_view_visible: bool,
+    sprite_view_text: Vec<(String, graphics::Text)>,
+    started: Instant,
+    // when the ROM ran 00FD, and how long to keep showing the screen before closing the window
+    exited_at: Option<Instant>,
+    palettes: Palettes,
+    phosphor: Phosphor,
+    // the frame's texels, kept so drawing doesn't allocate a new buffer every frame
+    rgba: Vec<u8>,
+    // the display's texture, uploaded again only when the display or how it's coloured changes
+    display_image: Option<graphics::Image>,
+    display_stale: bool,
+    display_image_palette: String,
+    // None if the platform couldn't build the shader, in which case the display is drawn plainly
+    crt: Option<graphics::Shader<Crt>>,
+    crt_enabled: bool,
+    fullscreen: bool,
+    integer_scale: bool,
+    // what to go back to when leaving fullscreen
+    windowed_size: (f32, f32),
+    // the gate the running session opens and closes, and the stream playing what it lets through
+    sound: Arc<SoundControl>,
+    _beeper: Option<OutputStream>,
+    // keypad keys held by controller buttons and sticks, by controller and input name
+    pad_keys: HeldKeys<(GamepadId, String)>,
+    sticks: HashMap<(GamepadId, Axis), StickAxis>,
+    // screenshots are encoded and written on worker threads, which report back here
+    screenshot_sender: Sender<String>,
+    screenshot_results: Receiver<String>,
+    // with --console, the lines typed into the terminal, read on a thread of their own
+    console: Option<Receiver<String>>,
+}
+
+/// Brings `cache` in line with `lines`, creating Text only for lines whose contents changed.
+fn refresh_text(cache: &mut Vec<(String, graphics::Text)>, lines: Vec<String>) {
+    cache.truncate(lines.len());
+    for (index, line) in lines.into_iter().enumerate() {
+        match cache.get_mut(index) {
+            Some((cached, _)) if *cached == line => {}
+            Some(entry) => *entry = (line.clone(), graphics::Text::new(line)),
+            None => cache.push((line.clone(), graphics::Text::new(line))),
+        }
+    }
+}
+
+impl Emulator {
+    /// Starts running `rom` if there is one, and the ROM picker otherwise.
+    fn new(ctx: &mut Context, config: Config, rom: Option<(PathBuf, LoadedRom)>) -> Emulator {
+        // idle until there's a ROM to run
+        let mut debugger = Debugger::new();
+        debugger.set_paused(true);
+        let sound = Arc::new(SoundControl::new(config.beep));
+        let mut session = Session::new(Cpu::new(Memory::new(), Display::new()), debugger, None, None, Rewind::with_seconds(0), Arc::clone(&sound));
+        let (screenshot_sender, screenshot_results) = mpsc::channel();
+        let mut emulator = Emulator {
+            frame: session.frame(),
+            machine: EmulationThread::spawn(session, FRAME_DURATION),
+            rom: None,
+            picker: None,
+            pause_menu: None,
+            recent: load_recent(),
+            watcher: None,
+            config_watcher: config.file_path.as_deref().map(|path| RomWatcher::new(path, Instant::now())),
+            rom_name: String::new(),
+            rom_key: String::new(),
+            settings: RomSettings::default(),
+            keymap: config.keymap.clone(),
+            layout_hint: LayoutHint::default(),
+            speed: SpeedMeter::new(Instant::now(), 0),
+            state_path: PathBuf::new(),
+            json_state_path: PathBuf::new(),
+            flags_path: PathBuf::new(),
+            saved_flags: [0; RPL_FLAGS],
+            resume_offer: None,
+            message: None,
+            overlay_visible: false,
+            overlay_shows_history: false,
+            keypad_visible: false,
+            mouse_key: None,
+            overlay_text: Vec::new(),
+            hex_view_visible: false,
+            hex_view_text: Vec::new(),
+            heat_map_visible: false,
+            heat_map_legend: Vec::new(),
+            sprite_view_visible: false,
+            sprite_view_text: Vec::new(),
+            started: Instant::now(),
+            exited_at: None,
+            palettes: load_palettes(config.file.settings.palette.as_deref()),
+            phosphor: Phosphor::new(config.phosphor),
+            crt: load_crt_shader(ctx),
+            crt_enabled: config.crt,
+            fullscreen: config.fullscreen,
+            integer_scale: config.integer_scale,
+            windowed_size: (WINDOW_WIDTH, WINDOW_HEIGHT),
+            _beeper: open_beeper(&sound),
+            sound,
+            pad_keys: HeldKeys::new(),
+            sticks: HashMap::new(),
+            rgba: Vec::new(),
+            display_image: None,
+            display_stale: true,
+            display_image_palette: String::new(),
+            screenshot_sender,
+            screenshot_results,
+            console: config.console.then(read_console),
+            config,
+        };
+        match rom {
+            Some((path, loaded)) => emulator.start(&path, loaded),
+            None => emulator.open_picker(),
+        }
+        emulator
+    }
+
+    /// Switches to running the ROM just loaded from `rom`; display settings carry over unless
+    /// the ROM's settings choose colours.
+    fn start(&mut self, rom: &Path, loaded: LoadedRom) {
+        let LoadedRom { mut cpu, key, settings, keymap, recorder, player } = loaded;
+        // resets and reloads carry on with the same ROM, anything else leaves the last one
+        let fresh = self.rom.as_deref() != Some(rom);
+        if fresh {
+            self.autosave();
+        }
+        let mut debugger = Debugger::new();
+        debugger.set_cycles_per_frame(settings.cycles_per_frame.unwrap_or(CYCLES_PER_FRAME));
+        debugger.set_timing(settings.timing.unwrap_or_default());
+        debugger.set_turbo_multiplier(self.config.turbo_multiplier);
+        debugger.set_step_limit(self.config.step_limit);
+        for &address in &self.config.breakpoints {
+            debugger.add_breakpoint(address);
+        }
+        for (address, condition) in &self.config.conditional_breakpoints {
+            debugger.add_conditional_breakpoint(*address, condition.clone());
+        }
+        for condition in &self.config.break_conditions {
+            debugger.break_when(condition.clone());
+        }
+        if let Some(colors) = settings.colors {
+            self.palettes.add(Palette::new(CUSTOM_PALETTE_NAME, colors));
+            self.palettes.select(CUSTOM_PALETTE_NAME);
+        }
+
+        self.remember_recent(rom);
+        self.rom = Some(rom.to_path_buf());
+        self.picker = None;
+        if self.config.watch_rom && is_file(rom) {
+            self.watcher = Some(RomWatcher::new(rom, Instant::now()));
+        }
+        self.rom_name = rom_name(rom);
+        self.rom_key = key;
+        self.settings = settings;
+        self.keymap = keymap;
+        self.speed = SpeedMeter::new(Instant::now(), cpu.instructions_executed());
+        self.state_path = beside_rom(rom, ".state");
+        self.json_state_path = beside_rom(rom, ".state.json");
+        self.flags_path = rpl_flags_path(rom);
+        self.saved_flags = *cpu.rpl_flags();
+        self.resume_offer = None;
+        if fresh {
+            self.offer_resume(&mut cpu);
+        }
+        self.exited_at = None;
+        self.started = Instant::now();
+        if self.heat_map_visible {
+            cpu.enable_heat_map();
+        }
+        self.sound.set_beep(self.config.beep);
+        let mut session = Session::new(cpu, debugger, recorder, player, Rewind::with_seconds(self.config.rewind_seconds), Arc::clone(&self.sound));
+        self.frame = session.frame();
+        self.display_stale = true;
+        self.machine = EmulationThread::spawn(session, FRAME_DURATION);
+    }
+
+    fn open_picker(&mut self) {
+        let dirs: Vec<&Path> = ROM_DIRECTORIES.iter().map(Path::new).collect();
+        let load_address = self.config.load_address;
+        let capacity = self.machine.with(move |session| session.cpu.rom_capacity(load_address)) as u64;
+        let mut entries = rompicker::scan(&dirs);
+        entries.extend(builtin::entries());
+        self.prune_recent();
+        // the recent ROMs take rows from the listing, but leave it a few
+        let rows = PICKER_ROWS.saturating_sub(self.recent_lines().len()).max(4);
+        self.picker = Some(RomPicker::new(entries, rows, capacity));
+    }
+
+    /// Puts `rom` at the top of the recent ROMs and saves the list. Stdin can't be loaded again,
+    /// so it isn't listed.
+    fn remember_recent(&mut self, rom: &Path) {
+        if romfile::is_stdin(rom) {
+            return;
+        }
+        let rom = if builtin::from_path(rom).is_some() { rom.to_path_buf() } else { fs::canonicalize(rom).unwrap_or_else(|_| rom.to_path_buf()) };
+        self.recent.insert(&rom);
+        save_recent(&self.recent);
+    }
+
+    /// Drops the recent ROMs whose files have gone.
+    fn prune_recent(&mut self) {
+        if self.recent.prune(|rom| builtin::from_path(rom).is_some() || is_file(rom)) {
+            save_recent(&self.recent);
+        }
+    }
+
+    /// The recent ROMs as the picker shows them, with a blank line after them if there are any.
+    fn recent_lines(&self) -> Vec<String> {
+        let mut lines = self.recent.lines();
+        if !lines.is_empty() {
+            lines.push(String::new());
+        }
+        lines
+    }
+
+    /// Loads the recent ROM for digit key `keycode`, if it is one and there's a ROM for it.
+    fn open_recent(&mut self, keycode: KeyCode) {
+        if let Some(path) = recent_index(keycode).and_then(|index| self.recent.get(index)).map(Path::to_path_buf) {
+            self.open(&path);
+        }
+    }
+
+    /// Loads the ROM at `path` in place of the current one, or says why it couldn't.
+    fn open(&mut self, path: &Path) {
+        match load_rom(path, &self.config) {
+            Ok(loaded) => self.start(path, loaded),
+            Err(error) => self.show_message(error),
+        }
+    }
+
+    /// Starts the ROM over from a fresh machine, with its bytes read from disk again; `buffer`
+    /// holds them when the watcher already has.
+    fn reset(&mut self, buffer: Option<Vec<u8>>) {
+        if self.refuse_while_recording() {
+            return;
+        }
+        let rom = match self.rom.clone() {
+            Some(rom) => rom,
+            None => return,
+        };
+        self.write_rpl_flags();
+        let reloaded = buffer.is_some();
+        let result = match buffer {
+            Some(buffer) => load_rom_bytes(&rom, buffer, &self.config),
+            None => load_rom(&rom, &self.config),
+        };
+        match result {
+            Ok(loaded) => {
+                self.start(&rom, loaded);
+                self.show_message(String::from(if reloaded { "ROM changed, reloaded" } else { "Reset" }));
+            }
+            Err(error) => self.show_message(error),
+        }
+    }
+
+    /// Takes in `chip8.toml` as just rewritten: what can change under a running ROM does now, the
+    /// rest with the next reset. A file with anything wrong in it is turned away whole, leaving
+    /// the settings as they were.
+    fn reload_config(&mut self, bytes: Vec<u8>) {
+        let parsed = String::from_utf8(bytes)
+            .map_err(|error| error.to_string())
+            .and_then(|text| ConfigFile::parse(&text, self.config.kb_layout).map_err(|error| error.to_string()));
+        let file = match parsed {
+            Ok(file) => match &file.settings.palette {
+                Some(name) if !self.palettes.contains(name) => Err(format!("there's no palette called {}", name)),
+                _ => Ok(file),
+            },
+            Err(error) => Err(error),
+        };
+        let file = match file {
+            Ok(file) => file,
+            Err(error) => return self.show_message(format!("{} not reloaded: {}", CONFIG_FILE_NAME, error)),
+        };
+
+        let changes = self.config.file.changes(&file);
+        let settings = &file.settings;
+        for &change in changes.iter().filter(|change| change.is_live()) {
+            match change {
+                Change::Palette => {
+                    if let Some(name) = &settings.palette {
+                        self.palettes.select(name);
+                    }
+                }
+                Change::Speed => {
+                    if let Some(cycles) = settings.cycles_per_frame {
+                        self.settings.cycles_per_frame = Some(cycles);
+                        self.machine.send(move |session| session.debugger.set_cycles_per_frame(cycles));
+                    }
+                }
+                Change::Keymap => {
+                    self.config.keymap = file.keymap.clone();
+                    // the ROM's own keys were fine on top of the old keymap, so they are on this one
+                    self.keymap = match &self.settings.keymap {
+                        Some(table) => file.keymap.with_keys(table).unwrap_or_else(|_| file.keymap.clone()),
+                        None => file.keymap.clone(),
+                    };
+                }
+                Change::TurboMultiplier => {
+                    if let Some(multiplier) = settings.turbo_multiplier {
+                        self.config.turbo_multiplier = multiplier;
+                        self.machine.send(move |session| session.debugger.set_turbo_multiplier(multiplier));
+                    }
+                }
+                Change::Phosphor => {
+                    if let Some(decay) = settings.phosphor {
+                        self.config.phosphor = decay;
+                        self.phosphor = Phosphor::new(decay);
+                        self.display_stale = true;
+                    }
+                }
+                Change::Quirks | Change::LoadAddress => {}
+            }
+        }
+        // the quirks come from `config.file` when the ROM is next loaded
+        if let Some(address) = settings.load_address {
+            self.config.load_address = address;
+        }
+        self.config.file = file;
+        self.show_message(configfile::describe(&changes));
+    }
+
+    /// Stores the settings the ROM is running with, palette included, under its hash.
+    fn save_settings(&mut self) {
+        if self.rom.is_none() {
+            return;
+        }
+        let settings = RomSettings {
+            name: Some(self.rom_name.clone()),
+            colors: Some(self.palettes.current().colors),
+            ..self.settings.clone()
+        };
+        match save_rom_settings(&self.rom_key, &settings) {
+            Ok(path) => {
+                self.config.database.insert(&self.rom_key, settings);
+                self.show_message(format!("Settings saved to {}", path.display()));
+            }
+            Err(error) => self.show_message(error),
+        }
+    }
+
+    /// Presses or releases keypad `key` for the keyboard, a controller or the mouse.
+    fn set_key(&self, key: u8, pressed: bool) {
+        self.machine.send(move |session| session.set_key(key, pressed));
+    }
+
+    /// Whether what's about to happen has to be refused, because it would take the machine
+    /// somewhere a recording can't follow; says so if it does.
+    /// Steps slow motion one speed slower, or with `slower` false one faster, back up to full
+    /// speed. The frame part way through when it goes back to full speed is just finished off.
+    fn change_slow_motion(&mut self, slower: bool) {
+        let speed = self.machine.with(move |session| {
+            let speed = match (session.debugger.slow_motion(), slower) {
+                (None, true) => Some(SlowMotion::Half),
+                (None, false) => None,
+                (Some(speed), true) => Some(speed.slower()),
+                (Some(speed), false) => speed.faster(),
+            };
+            session.debugger.set_slow_motion(speed);
+            speed
+        });
+        self.show_message(match speed {
+            Some(speed) => format!("Slow motion: {}", speed),
+            None => String::from("Full speed"),
+        });
+    }
+
+    fn refuse_while_recording(&mut self) -> bool {
+        match self.machine.with(|session| session.activity()) {
+            Some(activity) => {
+                self.show_message(format!("Not available while {}", activity));
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Runs the commands typed into the console since the last update, printing what each did.
+    fn run_console_commands(&mut self) {
+        let lines: Vec<String> = match &self.console {
+            Some(console) => console.try_iter().collect(),
+            None => return,
+        };
+        for line in lines.into_iter().filter(|line| !line.trim().is_empty()) {
+            println!("{}", self.machine.with(move |session| session.console_command(&line)));
+        }
+    }
+
+    /// Writes the RPL flags back if the ROM changed them.
+    fn write_rpl_flags(&self) {
+        let (path, saved) = (self.flags_path.clone(), self.saved_flags);
+        self.machine.with(move |session| save_rpl_flags(&session.cpu, &path, &saved));
+    }
+
+    /// Writes what `--record` has logged so far.
+    fn save_recording(&mut self) {
+        let path = match &self.config.record {
+            Some(path) => path,
+            None => return,
+        };
+        let recording = match self.machine.with(|session| session.recorder.take().map(|recorder| recorder.finish(&session.cpu))) {
+            Some(recording) => recording,
+            None => return,
+        };
+        match recording.write_to(path) {
+            Ok(()) => println!("Recorded the input to {}", path.display()),
+            Err(error) => eprintln!("Problem writing {}: {}", path.display(), error),
+        }
+    }
+
+    /// The controller input `source` starts pressing whatever the button called `button` is bound to.
+    fn pad_press(&mut self, id: GamepadId, source: String, button: &str) {
+        if let Some(key) = self.keymap.button(button) {
+            if self.pad_keys.press((id, source), key) {
+                self.set_key(key, true);
+            }
+        }
+    }
+
+    fn pad_release(&mut self, id: GamepadId, source: String) {
+        if let Some(key) = self.pad_keys.release(&(id, source)) {
+            self.set_key(key, false);
+        }
+    }
+
+    /// Pauses the game, sound and rewinding included, and brings up the pause menu over it.
+    fn open_menu(&mut self) {
+        self.prune_recent();
+        let was_paused = self.machine.with(|session| {
+            let was_paused = session.debugger.is_paused();
+            session.debugger.set_paused(true);
+            session.rewinding = false;
+            was_paused
+        });
+        self.sound.close();
+        self.pause_menu = Some(PauseMenu::new(was_paused, &self.recent));
+    }
+
+    /// Takes the menu down and goes back to running, or to being paused if the game was before.
+    /// The emulation thread doesn't catch up on frames, so the time spent in the menu isn't owed.
+    fn close_menu(&mut self) {
+        if let Some(menu) = self.pause_menu.take() {
+            let paused = menu.was_paused();
+            self.machine.send(move |session| session.debugger.set_paused(paused));
+        }
+    }
+
+    fn menu_input(&mut self, ctx: &mut Context, input: MenuInput) {
+        let action = match &mut self.pause_menu {
+            Some(menu) => menu.update(input),
+            None => return,
+        };
+        match action {
+            MenuAction::None => {}
+            MenuAction::Resume => self.close_menu(),
+            MenuAction::Reset => {
+                self.close_menu();
+                self.reset(None);
+            }
+            MenuAction::OpenRom => {
+                // the game stays paused behind the picker
+                self.pause_menu = None;
+                self.open_picker();
+            }
+            MenuAction::ToggleFullscreen => self.toggle_fullscreen(ctx),
+            MenuAction::Quit => {
+                self.pause_menu = None;
+                self.quit(ctx);
+            }
+            MenuAction::OpenRecent(index) => {
+                self.close_menu();
+                if let Some(path) = self.recent.get(index).map(Path::to_path_buf) {
+                    self.open(&path);
+                }
+            }
+        }
+    }
+
+    /// Where the pause menu's first entry goes in a window this size.
+    fn menu_origin(window_width: f32, window_height: f32, entries: usize) -> (f32, f32) {
+        ((window_width - MENU_WIDTH) / 2.0, (window_height - entries as f32 * OVERLAY_LINE_HEIGHT) / 2.0)
+    }
+
+    /// The index of the pause menu entry at window position `x`, `y`, if there's one there.
+    fn menu_entry_at(&self, ctx: &Context, x: f32, y: f32) -> Option<usize> {
+        let lines = self.pause_menu.as_ref()?.lines().len();
+        let (window_width, window_height) = graphics::drawable_size(ctx);
+        let (left, top) = Emulator::menu_origin(window_width, window_height, lines);
+        if x < left || x >= left + MENU_WIDTH || y < top {
+            return None;
+        }
+        // the recent ROMs below the entries are loaded with the digit keys rather than clicked
+        Some(((y - top) / OVERLAY_LINE_HEIGHT) as usize).filter(|&index| index < MenuEntry::ALL.len())
+    }
+
+    /// Dims the game and draws the pause menu over it, the selected entry in yellow.
+    fn draw_menu(&mut self, ctx: &mut Context) -> Result<(), GameError> {
+        let lines = match &self.pause_menu {
+            Some(menu) => menu.lines(),
+            None => return Ok(()),
+        };
+        let (window_width, window_height) = graphics::drawable_size(ctx);
+        let dim = graphics::Rect::new(0.0, 0.0, window_width, window_height);
+        let mesh = graphics::Mesh::new_rectangle(ctx, graphics::DrawMode::fill(), dim, Color::new(0.0, 0.0, 0.0, 0.7))?;
+        graphics::draw(ctx, &mesh, DrawParam::default())?;
+        let (left, top) = Emulator::menu_origin(window_width, window_height, lines.len());
+        for (index, line) in lines.iter().enumerate() {
+            let color = if line.starts_with('>') { Color::YELLOW } else { Color::WHITE };
+            let position = ggez::mint::Point2 { x: left, y: top + index as f32 * OVERLAY_LINE_HEIGHT };
+            graphics::draw(ctx, &graphics::Text::new(line.as_str()), (position, color))?;
+        }
+        Ok(())
+    }
+
+    fn picker_key_down(&mut self, ctx: &mut Context, keycode: KeyCode) {
+        let picker = match &mut self.picker {
+            Some(picker) => picker,
+            None => return,
+        };
+        match keycode {
+            KeyCode::Escape => self.quit(ctx),
+            KeyCode::Up => picker.up(),
+            KeyCode::Down => picker.down(),
+            KeyCode::PageUp => picker.page_up(),
+            KeyCode::PageDown => picker.page_down(),
+            KeyCode::Return => {
+                if let Some(path) = picker.selected().map(|entry| entry.path.clone()) {
+                    self.open(&path);
+                }
+            }
+            _ => self.open_recent(keycode),
+        }
+    }
+
+    fn draw_picker(&mut self, ctx: &mut Context) -> Result<(), GameError> {
+        graphics::clear(ctx, Color::BLACK);
+        let title = graphics::Text::new("Choose a ROM, or a built-in one at the end: arrow keys to move, Enter to load, Esc to quit");
+        graphics::draw(ctx, &title, (ggez::mint::Point2 { x: 4.0, y: 4.0 }, Color::YELLOW))?;
+        if let Some(picker) = &self.picker {
+            let mut lines = self.recent_lines();
+            lines.extend(picker.lines());
+            refresh_text(&mut self.overlay_text, lines);
+        }
+        for (index, (line, text)) in self.overlay_text.iter().enumerate() {
+            let y = 4.0 + (index + 2) as f32 * OVERLAY_LINE_HEIGHT;
+            let color = if line.starts_with('>') { Color::YELLOW } else { Color::WHITE };
+            graphics::draw(ctx, text, (ggez::mint::Point2 { x: 4.0, y }, color))?;
+        }
+        self.draw_message(ctx, 4.0 + (self.overlay_text.len() + 2) as f32 * OVERLAY_LINE_HEIGHT)?;
+        graphics::present(ctx)
+    }
+
+    /// Shows the latest message at `y` until it's `MESSAGE_DURATION` old.
+    fn draw_message(&mut self, ctx: &mut Context, y: f32) -> Result<(), GameError> {
+        if let Some((message, shown_at)) = &self.message {
+            if shown_at.elapsed() < MESSAGE_DURATION {
+                let text = graphics::Text::new(message.as_str());
+                graphics::draw(ctx, &text, (ggez::mint::Point2 { x: 4.0, y }, Color::YELLOW))?;
+            } else {
+                self.message = None;
+            }
+        }
+        Ok(())
+    }
+
+    /// How the window is split: the play area's width, then the on-screen keypad when shown, then
+    /// the debug overlay.
+    fn layout(&self, window_width: f32, window_height: f32) -> (f32, Option<KeypadPanel>) {
+        let keypad_width = if self.keypad_visible { KEYPAD_WIDTH } else { 0.0 };
+        let overlay_width = if self.overlay_visible { OVERLAY_WIDTH } else { 0.0 };
+        let play_area_width = (window_width - keypad_width - overlay_width).max(0.0);
+        let keypad = KeypadPanel::fit(play_area_width + 8.0, 8.0, KEYPAD_WIDTH - 16.0, window_height - 16.0);
+        (play_area_width, Some(keypad).filter(|_| self.keypad_visible))
+    }
+
+    /// Draws the on-screen keypad, with the keys held down by any means lit up.
+    fn draw_keypad(&self, ctx: &mut Context, keypad: &KeypadPanel) -> GameResult {
+        for (key, x, y) in keypad.cells() {
+            let pressed = self.frame.keys[key as usize];
+            let fill = if pressed { Color::YELLOW } else { Color::new(0.25, 0.25, 0.25, 1.0) };
+            let cell = graphics::Rect::new(x + 2.0, y + 2.0, (keypad.cell - 4.0).max(0.0), (keypad.cell - 4.0).max(0.0));
+            let mesh = graphics::Mesh::new_rectangle(ctx, graphics::DrawMode::fill(), cell, fill)?;
+            graphics::draw(ctx, &mesh, DrawParam::default())?;
+            let label = graphics::Text::new(format!("{:X}", key));
+            let position = ggez::mint::Point2 {
+                x: x + (keypad.cell - label.width(ctx)) / 2.0,
+                y: y + (keypad.cell - label.height(ctx)) / 2.0,
+            };
+            graphics::draw(ctx, &label, (position, if pressed { Color::BLACK } else { Color::WHITE }))?;
+        }
+        Ok(())
+    }
+
+    /// Draws the visual buzzer over the play area, `width` wide, while the sound timer runs.
+    fn draw_buzzer(&self, ctx: &mut Context, width: f32, height: f32) -> GameResult {
+        let style = self.config.visual_buzzer;
+        if !style.shows(self.frame.sound_timer) {
+            return Ok(());
+        }
+        let mesh = match style {
+            VisualBuzzer::Border => {
+                let half = BUZZER_BORDER_WIDTH / 2.0;
+                let area = graphics::Rect::new(half, half, (width - BUZZER_BORDER_WIDTH).max(0.0), (height - BUZZER_BORDER_WIDTH).max(0.0));
+                graphics::Mesh::new_rectangle(ctx, graphics::DrawMode::stroke(BUZZER_BORDER_WIDTH), area, Color::YELLOW)?
+            }
+            VisualBuzzer::Icon => {
+                // a speaker in the top-left corner: the magnet, then the cone opening to the right
+                let (x, y) = (8.0, 8.0);
+                let cone = [
+                    ggez::mint::Point2 { x: x + 6.0, y: y + 6.0 },
+                    ggez::mint::Point2 { x: x + 16.0, y },
+                    ggez::mint::Point2 { x: x + 16.0, y: y + 20.0 },
+                    ggez::mint::Point2 { x: x + 6.0, y: y + 14.0 },
+                ];
+                graphics::MeshBuilder::new()
+                    .rectangle(graphics::DrawMode::fill(), graphics::Rect::new(x, y + 6.0, 6.0, 8.0), Color::YELLOW)?
+                    .polygon(graphics::DrawMode::fill(), &cone, Color::YELLOW)?
+                    .build(ctx)?
+            }
+            VisualBuzzer::Off => return Ok(()),
+        };
+        graphics::draw(ctx, &mesh, DrawParam::default())
+    }
+
+    /// Stops writing the frozen cheats, or starts again.
+    fn toggle_cheats(&mut self) {
+        let enabled = self.machine.with(|session| {
+            if session.cpu.frozen().is_empty() {
+                return None;
+            }
+            let enabled = !session.cpu.cheats_enabled();
+            session.cpu.set_cheats_enabled(enabled);
+            Some(enabled)
+        });
+        let message = match enabled {
+            Some(true) => "Cheats on",
+            Some(false) => "Cheats off",
+            None => "No cheats are frozen for this ROM",
+        };
+        self.show_message(String::from(message));
+    }
+
+    fn show_message(&mut self, message: String) {
+        self.message = Some((message, Instant::now()));
+    }
+
+    fn save_state(&mut self) {
+        match self.machine.with(|session| session.cpu.save_state()).write_to(&self.state_path) {
+            Ok(()) => self.show_message(String::from("State saved")),
+            Err(error) => self.show_message(format!("Save failed: {}", error)),
+        }
+    }
+
+    /// Leaves the running ROM's state in its autosave, for `--resume` or Enter to pick up from
+    /// next time. Replays, and ROMs that have run to the end, leave none.
+    fn autosave(&mut self) {
+        if self.rom.is_none() || self.config.replay.is_some() || self.frame.is_finished() {
+            return;
+        }
+        let dir = match data_dir() {
+            Some(dir) => dir,
+            None => return,
+        };
+        let state = self.machine.with(|session| session.cpu.save_state());
+        match autosave::write(&dir, &self.rom_name, &state) {
+            Ok(path) => info!("Autosaved to {}", path.display()),
+            Err(error) => warn!("Problem writing the autosave of {}: {}", self.rom_name, error),
+        }
+    }
+
+    /// Looks for an autosave of the ROM about to start in `cpu`: `--resume` loads it straight
+    /// away, otherwise it's offered for a few seconds. One left by other bytes of the ROM is only
+    /// mentioned.
+    fn offer_resume(&mut self, cpu: &mut Cpu) {
+        if self.config.record.is_some() || self.config.replay.is_some() || self.config.import_state.is_some() {
+            return;
+        }
+        let dir = match data_dir() {
+            Some(dir) => dir,
+            None => return,
+        };
+        match autosave::read(&dir, &self.rom_name, cpu.rom_hash()) {
+            Ok(Some(state)) if self.config.resume => match cpu.load_state(&state) {
+                Ok(()) => self.show_message(String::from("Resumed from the autosave")),
+                Err(error) => self.show_message(format!("Resume failed: {}", error)),
+            },
+            Ok(Some(state)) => {
+                self.show_message(String::from("Press Enter to resume where you left off"));
+                self.resume_offer = Some((state, Instant::now()));
+            }
+            Ok(None) if self.config.resume => self.show_message(String::from("No autosave to resume from")),
+            Ok(None) => {}
+            Err(SaveStateError::WrongRom) => {
+                info!("Ignoring the autosave of {}, which an earlier version of the ROM left", self.rom_name);
+                self.show_message(String::from("Autosave ignored: the ROM has changed since"));
+            }
+            Err(error) => warn!("Problem reading the autosave of {}: {}", self.rom_name, error),
+        }
+    }
+
+    /// Whether the autosave offered at the start is still on offer.
+    fn resume_offered(&self) -> bool {
+        self.resume_offer.as_ref().is_some_and(|(_, offered_at)| offered_at.elapsed() < MESSAGE_DURATION)
+    }
+
+    /// Picks up from the autosave on offer.
+    fn resume(&mut self) {
+        if let Some((state, _)) = self.resume_offer.take() {
+            match self.machine.with(move |session| session.cpu.load_state(&state)) {
+                Ok(()) => self.show_message(String::from("Resumed from the autosave")),
+                Err(error) => self.show_message(format!("Resume failed: {}", error)),
+            }
+        }
+    }
+
+    /// Reports and persists what should outlive the window, then closes it.
+    fn quit(&mut self, ctx: &mut Context) {
+        self.quit_event(ctx);
+        event::quit(ctx);
+    }
+
+    /// Switches to the next palette and remembers it for the next launch.
+    fn cycle_palette(&mut self) {
+        let name = self.palettes.cycle().name.clone();
+        save_palette_name(&name);
+        self.show_message(format!("Palette: {}", name));
+    }
+
+    fn toggle_crt(&mut self) {
+        if self.crt.is_none() {
+            self.show_message(String::from("CRT effect unavailable"));
+            return;
+        }
+        self.crt_enabled = !self.crt_enabled;
+        self.show_message(String::from(if self.crt_enabled { "CRT effect on" } else { "CRT effect off" }));
+    }
+
+    /// Shows or hides the memory heat map, counting from scratch each time it's shown.
+    fn toggle_heat_map(&mut self) {
+        self.heat_map_visible = !self.heat_map_visible;
+        let visible = self.heat_map_visible;
+        self.machine.send(move |session| {
+            if visible {
+                session.cpu.enable_heat_map();
+            } else {
+                session.cpu.disable_heat_map();
+            }
+        });
+    }
+
+    /// Draws the heat map in the top-right corner of the play area, `width` wide: a cell per
+    /// address of the first 4 KB, with pc and the region under I outlined and a legend beneath.
+    fn draw_heat_map(&mut self, ctx: &mut Context, width: f32) -> GameResult {
+        let snapshot = self.machine.with(|session| session.cpu.heat_map().map(|map| (map.rgba(), session.cpu.i(), session.cpu.pc())));
+        let (rgba, i, pc) = match snapshot {
+            Some(snapshot) => snapshot,
+            None => return Ok(()),
+        };
+        let (grid_width, grid_height) = (GRID_WIDTH as f32 * HEAT_MAP_CELL, GRID_HEIGHT as f32 * HEAT_MAP_CELL);
+        let (x, y) = ((width - grid_width - 8.0).max(0.0), 8.0);
+        let legend_height = heatmap::LEGEND.len() as f32 * OVERLAY_LINE_HEIGHT;
+        let background = graphics::Rect::new(x - 4.0, y - 4.0, grid_width + 8.0, grid_height + legend_height + 12.0);
+        let mesh = graphics::Mesh::new_rectangle(ctx, graphics::DrawMode::fill(), background, Color::new(0.0, 0.0, 0.0, 0.85))?;
+        graphics::draw(ctx, &mesh, DrawParam::default())?;
+
+        let mut image = graphics::Image::from_rgba8(ctx, GRID_WIDTH as u16, GRID_HEIGHT as u16, &rgba)?;
+        image.set_filter(graphics::FilterMode::Nearest);
+        let param = DrawParam::default()
+            .dest(ggez::mint::Point2 { x, y })
+            .scale(ggez::mint::Vector2 { x: HEAT_MAP_CELL, y: HEAT_MAP_CELL });
+        graphics::draw(ctx, &image, param)?;
+
+        let mut outlines = graphics::MeshBuilder::new();
+        let highlights = (0..HEAT_MAP_I_BYTES).map(|offset| (i.wrapping_add(offset), Color::YELLOW)).chain([(pc, Color::WHITE), (pc.wrapping_add(1), Color::WHITE)]);
+        for (address, color) in highlights {
+            if let Some((column, row)) = heatmap::cell_of(address) {
+                let cell = graphics::Rect::new(x + column as f32 * HEAT_MAP_CELL, y + row as f32 * HEAT_MAP_CELL, HEAT_MAP_CELL, HEAT_MAP_CELL);
+                outlines.rectangle(graphics::DrawMode::stroke(1.0), cell, color)?;
+            }
+        }
+        // with I and pc both past 4 KB, in XO-CHIP's or MEGA-CHIP's memory, there's nothing to outline
+        if let Ok(mesh) = outlines.build(ctx) {
+            graphics::draw(ctx, &mesh, DrawParam::default())?;
+        }
+
+        refresh_text(&mut self.heat_map_legend, heatmap::LEGEND.iter().map(|line| line.to_string()).collect());
+        for (index, (_, text)) in self.heat_map_legend.iter().enumerate() {
+            let y = y + grid_height + 4.0 + index as f32 * OVERLAY_LINE_HEIGHT;
+            graphics::draw(ctx, text, (ggez::mint::Point2 { x, y }, Color::WHITE))?;
+        }
+        Ok(())
+    }
+
+    /// Draws the sprite viewer in the bottom-right corner of the play area, `width` wide and
+    /// ending above `bottom`: the memory it looks at as a magnified sprite, with its raw hex
+    /// beneath.
+    fn draw_sprite_view(&mut self, ctx: &mut Context, width: f32, bottom: f32) -> GameResult {
+        let (preview, following_i, rows) = self.machine.with(|session| {
+            let view = &session.sprite_view;
+            (view.preview(&session.cpu), view.is_following_i(), view.rows())
+        });
+        let size = if rows == spriteview::BIG_SPRITE { String::from("16x16") } else { format!("8x{}", rows) };
+        let source = if following_i { "I" } else { "cursor" };
+        let mut lines = vec![format!("Sprite {} at {:#05X} ({})", size, preview.address, source)];
+        lines.extend(preview.hex_lines());
+        refresh_text(&mut self.sprite_view_text, lines);
+
+        let image_height = preview.height as f32 * SPRITE_VIEW_SCALE;
+        let height = image_height + self.sprite_view_text.len() as f32 * OVERLAY_LINE_HEIGHT + 12.0;
+        let (x, y) = ((width - SPRITE_VIEW_WIDTH - 8.0).max(0.0), (bottom - height - 4.0).max(0.0));
+        let background = graphics::Rect::new(x - 4.0, y - 4.0, SPRITE_VIEW_WIDTH + 8.0, height + 8.0);
+        let mesh = graphics::Mesh::new_rectangle(ctx, graphics::DrawMode::fill(), background, Color::new(0.0, 0.0, 0.0, 0.85))?;
+        graphics::draw(ctx, &mesh, DrawParam::default())?;
+
+        let (header, hex) = self.sprite_view_text.split_at(1);
+        graphics::draw(ctx, &header[0].1, (ggez::mint::Point2 { x, y }, Color::YELLOW))?;
+        let mut image = graphics::Image::from_rgba8(ctx, preview.width as u16, preview.height as u16, &preview.rgba())?;
+        image.set_filter(graphics::FilterMode::Nearest);
+        let param = DrawParam::default()
+            .dest(ggez::mint::Point2 { x, y: y + OVERLAY_LINE_HEIGHT + 4.0 })
+            .scale(ggez::mint::Vector2 { x: SPRITE_VIEW_SCALE, y: SPRITE_VIEW_SCALE });
+        graphics::draw(ctx, &image, param)?;
+        for (index, (_, text)) in hex.iter().enumerate() {
+            let y = y + OVERLAY_LINE_HEIGHT + image_height + 8.0 + index as f32 * OVERLAY_LINE_HEIGHT;
+            graphics::draw(ctx, text, (ggez::mint::Point2 { x, y }, Color::WHITE))?;
+        }
+        Ok(())
+    }
+
+    fn toggle_fullscreen(&mut self, ctx: &mut Context) {
+        let result = if self.fullscreen {
+            let (width, height) = self.windowed_size;
+            graphics::set_fullscreen(ctx, FullscreenType::Windowed).and_then(|()| graphics::set_drawable_size(ctx, width, height))
+        } else {
+            self.windowed_size = graphics::drawable_size(ctx);
+            graphics::set_fullscreen(ctx, FullscreenType::Desktop)
+        };
+        match result {
+            Ok(()) => self.fullscreen = !self.fullscreen,
+            Err(error) => self.show_message(format!("Fullscreen failed: {}", error)),
+        }
+    }
+
+    /// Saves the display as a PNG in the screenshots directory. Only the colouring happens here;
+    /// scaling, encoding and writing happen on another thread so the emulation doesn't stall.
+    fn take_screenshot(&mut self) {
+        let display = &self.frame.display;
+        let rgba = render::frame_rgba(display, self.palettes.current(), &Phosphor::default());
+        let (width, height, scale) = (display.width(), display.height(), self.config.screenshot_scale);
+        let path = Path::new(SCREENSHOT_DIRECTORY).join(screenshot::file_name(&self.rom_name, SystemTime::now()));
+        let results = self.screenshot_sender.clone();
+        thread::spawn(move || {
+            let png = screenshot::encode_png(&rgba, width, height, scale);
+            let message = match fs::create_dir_all(SCREENSHOT_DIRECTORY).and_then(|()| fs::write(&path, png)) {
+                Ok(()) => format!("Screenshot saved to {}", path.display()),
+                Err(error) => format!("Screenshot failed: {}", error),
+            };
+            // the window may have closed in the meantime
+            let _ = results.send(message);
+        });
+    }
+
+    fn load_state(&mut self) {
+        if self.refuse_while_recording() {
+            return;
+        }
+        let result = SaveState::read_from(&self.state_path).and_then(|state| self.machine.with(move |session| session.cpu.load_state(&state)));
+        match result {
+            Ok(()) => self.show_message(String::from("State loaded")),
+            Err(error) => self.show_message(format!("Load failed: {}", error)),
+        }
+    }
+
+    fn export_state(&mut self) {
+        match self.machine.with(|session| session.cpu.save_state()).write_json_to(&self.json_state_path) {
+            Ok(()) => self.show_message(format!("State exported to {}", self.json_state_path.display())),
+            Err(error) => self.show_message(format!("Export failed: {}", error)),
+        }
+    }
+
+    fn import_state(&mut self) {
+        if self.refuse_while_recording() {
+            return;
+        }
+        let (path, force) = (self.json_state_path.clone(), self.config.force);
+        match self.machine.with(move |session| import_state(&mut session.cpu, &path, force)) {
+            Ok(()) => self.show_message(String::from("State imported")),
+            Err(error) => self.show_message(format!("Import failed: {}", error)),
+        }
+    }
+}
+
+impl EventHandler<GameError> for Emulator {
+    fn quit_event(&mut self, _ctx: &mut Context) -> bool {
+        self.autosave();
+        let elapsed = self.started.elapsed();
+        let (coverage, export) = (self.config.coverage.clone(), self.config.export_state.clone());
+        self.machine.with(move |session| {
+            print_profile(&session.cpu, elapsed);
+            write_coverage(&session.cpu, coverage.as_deref());
+            export_state(&session.cpu, export.as_deref());
+        });
+        self.save_recording();
+        // a replay's flags came from the recording, so they're not the player's to keep
+        if self.config.replay.is_none() {
+            self.write_rpl_flags();
+        }
+        false
+    }
+
+    fn update(&mut self, ctx: &mut Context) -> Result<(), GameError> {
+        if INTERRUPTED.load(Ordering::SeqCst) {
+            self.quit(ctx);
+            return Ok(());
+        }
+        // the emulation thread keeps its own time; this only picks up what it has published
+        if let Some(mut frame) = self.machine.take_frame() {
+            if let Some(error) = frame.error {
+                let rom_name = self.rom_name.clone();
+                eprintln!("{}", self.machine.with(move |session| write_core_dump(&session.cpu, &rom_name)));
+                return Err(GameError::CustomError(error.to_string()));
+            }
+            self.phosphor.update(&frame.display);
+            self.display_stale |= frame.dirty.is_some();
+            for message in frame.messages.drain(..) {
+                self.show_message(message);
+            }
+            self.frame = frame;
+        }
+
+        while let Ok(message) = self.screenshot_results.try_recv() {
+            self.show_message(message);
+        }
+        self.run_console_commands();
+        if let Some(bytes) = self.config_watcher.as_mut().and_then(|watcher| watcher.poll(Instant::now())) {
+            self.reload_config(bytes);
+        }
+        if self.picker.is_some() {
+            return Ok(());
+        }
+        if let Some(buffer) = self.watcher.as_mut().and_then(|watcher| watcher.poll(Instant::now())) {
+            self.reset(Some(buffer));
+        }
+        if self.speed.update(Instant::now(), self.frame.instructions) {
+            graphics::set_window_title(ctx, &speed::title(&self.rom_name, &self.speed, self.frame.paused, self.frame.slow_motion));
+        }
+
+        if self.frame.is_finished() {
+            let exited_at = *self.exited_at.get_or_insert_with(Instant::now);
+            if self.config.close_after_exit.is_some_and(|delay| exited_at.elapsed() >= delay) {
+                self.quit(ctx);
+            }
+        } else {
+            // rewinding past 00FD resumes the ROM, so the exit is forgotten again
+            self.exited_at = None;
+        }
+        Ok(())
+    }
+
+    fn draw(&mut self, ctx: &mut Context) -> Result<(), GameError> {
+        if self.picker.is_some() {
+            return self.draw_picker(ctx);
+        }
+        let palette = self.palettes.current();
+        graphics::clear(ctx, Color::BLACK);
+        // scale to the active resolution so 128x64 hires fills the same area as 64x32 lores
+        let display = &self.frame.display;
+        let (window_width, window_height) = graphics::drawable_size(ctx);
+        let (play_area_width, keypad) = self.layout(window_width, window_height);
+        let overlay_x = play_area_width + if self.keypad_visible { KEYPAD_WIDTH } else { 0.0 } + 8.0;
+        let bottom_line_y = window_height - 22.0;
+        let viewport = render::fit(play_area_width, window_height, display.width(), display.height(), self.integer_scale);
+        // an afterglow fades on frames that change nothing, so it's uploaded every frame
+        let stale = self.display_stale || self.phosphor.is_enabled() || self.display_image_palette != palette.name;
+        let image = match self.display_image.take() {
+            Some(image) if !stale => image,
+            _ => {
+                render::frame_rgba_into(display, palette, &self.phosphor, &mut self.rgba);
+                let mut image = graphics::Image::from_rgba8(ctx, display.width() as u16, display.height() as u16, &self.rgba)?;
+                image.set_filter(graphics::FilterMode::Nearest);
+                self.display_stale = false;
+                self.display_image_palette = palette.name.clone();
+                image
+            }
+        };
+        let scale = DrawParam::default()
+            .dest(ggez::mint::Point2 { x: viewport.x, y: viewport.y })
+            .scale(ggez::mint::Vector2 { x: viewport.scale, y: viewport.scale });
+        match self.crt.as_ref().filter(|_| self.crt_enabled) {
+            Some(shader) => {
+                // the lock restores the default shader when dropped, so the overlays below stay crisp
+                let _lock = graphics::use_shader(ctx, shader);
+                let time = timer::time_since_start(ctx).as_secs_f32();
+                shader.send(ctx, Crt { resolution: [display.width() as f32, display.height() as f32], time })?;
+                graphics::draw(ctx, &image, scale)?;
+            }
+            None => graphics::draw(ctx, &image, scale)?,
+        }
+        self.display_image = Some(image);
+
+        if self.hex_view_visible {
+            let lines = self.machine.with(|session| session.hex_view.lines(&session.cpu));
+            refresh_text(&mut self.hex_view_text, lines);
+            let height = self.hex_view_text.len() as f32 * OVERLAY_LINE_HEIGHT + 8.0;
+            let background = graphics::Rect::new(0.0, 0.0, overlay_x - 4.0, height);
+            let mesh = graphics::Mesh::new_rectangle(ctx, graphics::DrawMode::fill(), background, Color::new(0.0, 0.0, 0.0, 0.85))?;
+            graphics::draw(ctx, &mesh, DrawParam::default())?;
+            for (index, (line, text)) in self.hex_view_text.iter().enumerate() {
+                let y = 4.0 + index as f32 * OVERLAY_LINE_HEIGHT;
+                // rows holding I or pc are drawn in yellow; the byte itself is marked with * or >
+                let color = if line.contains(&['*', '>'][..]) { Color::YELLOW } else { Color::WHITE };
+                graphics::draw(ctx, text, (ggez::mint::Point2 { x: 4.0, y }, color))?;
+            }
+        }
+
+        if self.heat_map_visible {
+            self.draw_heat_map(ctx, play_area_width)?;
+        }
+        if self.sprite_view_visible {
+            self.draw_sprite_view(ctx, play_area_width, bottom_line_y)?;
+        }
+
+        if self.overlay_visible {
+            let lines = if self.overlay_shows_history {
+                self.machine.with(|session| debugger::history_page(&session.cpu, OVERLAY_HISTORY_ROWS))
+            } else {
+                let mut lines = self.machine.with(|session| debugger::overlay(&session.cpu));
+                lines.extend(speed::overlay(&self.speed, self.frame.instructions, self.frame.slow_motion));
+                lines.push(String::from("Keypad:"));
+                lines.extend(self.keymap.overlay());
+                lines
+            };
+            refresh_text(&mut self.overlay_text, lines);
+            for (index, (_, text)) in self.overlay_text.iter().enumerate() {
+                let y = 4.0 + index as f32 * OVERLAY_LINE_HEIGHT;
+                graphics::draw(ctx, text, (ggez::mint::Point2 { x: overlay_x, y }, Color::WHITE))?;
+            }
+        }
+
+        if let Some(keypad) = keypad {
+            self.draw_keypad(ctx, &keypad)?;
+        }
+        self.draw_buzzer(ctx, play_area_width, window_height)?;
+
+        if self.frame.paused {
+            let text = graphics::Text::new("PAUSED");
+            let position = if self.overlay_visible {
+                ggez::mint::Point2 { x: 4.0, y: bottom_line_y }
+            } else {
+                ggez::mint::Point2 { x: window_width - 60.0, y: 4.0 }
+            };
+            graphics::draw(ctx, &text, (position, Color::YELLOW))?;
+        }
+        if self.frame.cheats {
+            let text = graphics::Text::new("CHEATS");
+            graphics::draw(ctx, &text, (ggez::mint::Point2 { x: (play_area_width - 60.0).max(4.0), y: bottom_line_y }, Color::GREEN))?;
+        }
+
+        if self.frame.is_finished() {
+            let text = graphics::Text::new("Program exited, press any key to close");
+            graphics::draw(ctx, &text, (ggez::mint::Point2 { x: 4.0, y: bottom_line_y }, Color::YELLOW))?;
+        } else if let Some(Halt::Looped(pc)) = self.frame.halted {
+            let text = graphics::Text::new(format!("Program halted at {:#05X}", pc));
+            graphics::draw(ctx, &text, (ggez::mint::Point2 { x: 4.0, y: bottom_line_y }, Color::new(0.6, 0.6, 0.6, 1.0)))?;
+        }
+
+        self.draw_menu(ctx)?;
+        self.draw_message(ctx, 4.0)?;
+
+        self.speed.frame();
+        graphics::present(ctx)
+    }
+
+    fn focus_event(&mut self, _ctx: &mut Context, gained: bool) {
+        if !self.config.pause_on_focus_loss {
+            return;
+        }
+        // the emulation thread never catches up on missed frames, so the time away isn't owed
+        self.machine.send(move |session| session.debugger.focus_changed(gained));
+        if !gained {
+            self.sound.close();
+        }
+    }
+
+    fn resize_event(&mut self, ctx: &mut Context, width: f32, height: f32) {
+        // keep one unit per window pixel rather than stretching the original 640x320
+        if let Err(error) = graphics::set_screen_coordinates(ctx, graphics::Rect::new(0.0, 0.0, width, height)) {
+            warn!("Problem resizing to {}x{}: {}", width, height, error);
+        }
+    }
+
+    fn key_down_event(&mut self, ctx: &mut Context, keycode: KeyCode, keymods: KeyMods, repeat: bool) {
+        if self.picker.is_some() {
+            self.picker_key_down(ctx, keycode);
+            return;
+        }
+        if self.pause_menu.is_some() {
+            let input = match keycode {
+                KeyCode::Up => MenuInput::Up,
+                KeyCode::Down => MenuInput::Down,
+                KeyCode::Return | KeyCode::NumpadEnter if !repeat => MenuInput::Confirm,
+                KeyCode::Escape if !repeat => MenuInput::Cancel,
+                _ if !repeat => match recent_index(keycode) {
+                    Some(index) => MenuInput::Recent(index),
+                    None => return,
+                },
+                _ => return,
+            };
+            self.menu_input(ctx, input);
+            return;
+        }
+        match keycode {
+            KeyCode::Escape if !repeat => self.open_menu(),
+            KeyCode::Return if !repeat && self.resume_offered() => self.resume(),
+            // Backspace still rewinds, anything else closes the window once the ROM has exited
+            _ if self.frame.is_finished() && keycode != KeyCode::Back && !repeat => self.quit(ctx),
+            // while paused Shift+F10 steps over a call and Shift+F11 runs to the end of a subroutine
+            KeyCode::F10 if keymods.contains(KeyMods::SHIFT) && self.refuse_while_recording() => {}
+            KeyCode::F10 if keymods.contains(KeyMods::SHIFT) => self.machine.send(Session::step_over),
+            KeyCode::F11 if keymods.contains(KeyMods::SHIFT) && self.refuse_while_recording() => {}
+            KeyCode::F11 if keymods.contains(KeyMods::SHIFT) => self.machine.send(Session::run_until_return),
+            KeyCode::F10 if !repeat => self.reset(None),
+            KeyCode::F12 if !repeat && keymods.contains(KeyMods::SHIFT) => self.save_settings(),
+            KeyCode::F12 if !repeat => self.take_screenshot(),
+            KeyCode::F9 if !repeat && keymods.contains(KeyMods::SHIFT) => {
+                let rom_name = self.rom_name.clone();
+                let message = self.machine.with(move |session| write_core_dump(&session.cpu, &rom_name));
+                self.show_message(message);
+            }
+            KeyCode::F9 if !repeat => {
+                let elapsed = self.started.elapsed();
+                self.machine.send(move |session| print_profile(&session.cpu, elapsed));
+            }
+            KeyCode::F5 if keymods.contains(KeyMods::SHIFT) => self.export_state(),
+            KeyCode::F5 => self.save_state(),
+            KeyCode::F6 if !repeat && keymods.contains(KeyMods::SHIFT) => self.toggle_cheats(),
+            KeyCode::F6 if !repeat && keymods.contains(KeyMods::CTRL) => self.toggle_heat_map(),
+            KeyCode::F6 if !repeat => self.toggle_crt(),
+            KeyCode::F11 if !repeat => self.toggle_fullscreen(ctx),
+            KeyCode::F8 if !repeat => self.cycle_palette(),
+            KeyCode::F7 if !repeat && keymods.contains(KeyMods::CTRL) => self.sprite_view_visible = !self.sprite_view_visible,
+            KeyCode::F7 if keymods.contains(KeyMods::SHIFT) => self.import_state(),
+            KeyCode::F7 => self.load_state(),
+            KeyCode::F1 if !repeat => self.overlay_visible = !self.overlay_visible,
+            KeyCode::F4 if !repeat && keymods.contains(KeyMods::SHIFT) => self.keypad_visible = !self.keypad_visible,
+            // F4 shows the overlay's history page, or switches back to the registers
+            KeyCode::F4 if !repeat => {
+                self.overlay_shows_history = !(self.overlay_visible && self.overlay_shows_history);
+                self.overlay_visible = true;
+            }
+            // with the sprite viewer shown Ctrl+Left and Ctrl+Right move it a byte off I, Ctrl+Home
+            // goes back to I, and Ctrl+Up and Ctrl+Down change how many rows it reads
+            KeyCode::Left | KeyCode::Right if self.sprite_view_visible && keymods.contains(KeyMods::CTRL) => {
+                let offset = if keycode == KeyCode::Left { -1 } else { 1 };
+                self.machine.send(move |session| session.sprite_view.scrub(offset, &session.cpu));
+            }
+            KeyCode::Home if self.sprite_view_visible && keymods.contains(KeyMods::CTRL) => self.machine.send(|session| session.sprite_view.follow_i()),
+            KeyCode::Up if self.sprite_view_visible && keymods.contains(KeyMods::CTRL) => self.machine.send(|session| session.sprite_view.more_rows()),
+            KeyCode::Down if self.sprite_view_visible && keymods.contains(KeyMods::CTRL) => self.machine.send(|session| session.sprite_view.fewer_rows()),
+            KeyCode::F2 if !repeat => self.hex_view_visible = !self.hex_view_visible,
+            KeyCode::F3 if !repeat && self.hex_view_visible => self.machine.send(|session| session.hex_view.toggle_follow_i()),
+            KeyCode::PageUp if self.hex_view_visible => self.machine.send(|session| session.hex_view.page_up()),
+            KeyCode::PageDown if self.hex_view_visible => self.machine.send(|session| {
+                let size = session.cpu.memory().bytes().len();
+                session.hex_view.page_down(size);
+            }),
+            KeyCode::Home if self.hex_view_visible => self.machine.send(|session| {
+                let size = session.cpu.memory().bytes().len();
+                session.hex_view.jump_to(session.cpu.i(), size);
+            }),
+            KeyCode::Back if self.refuse_while_recording() => {}
+            KeyCode::Back => self.machine.send(|session| session.rewinding = true),
+            // P pauses unless the keymap gives it to the keypad, as the Colemak preset does
+            KeyCode::P if self.keymap.key("P").is_none() => {
+                if !repeat {
+                    self.machine.send(|session| session.debugger.toggle_pause());
+                }
+            }
+            KeyCode::Space if self.refuse_while_recording() => {}
+            KeyCode::Space => self.machine.send(Session::step),
+            // while paused F advances a frame instead of pressing keypad E
+            KeyCode::F if self.frame.paused => self.machine.send(Session::advance_frame),
+            // Tab fast-forwards while it's held, unless the keymap gives it to the keypad
+            KeyCode::Tab if self.keymap.key("Tab").is_none() => self.machine.send(|session| session.debugger.set_turbo(true)),
+            // [ and ] step slow motion down and back up, unless the keymap gives them to the keypad
+            KeyCode::LBracket | KeyCode::RBracket if self.keymap.key(&keymap::keycode_name(keycode)).is_none() => {
+                if !repeat && !self.refuse_while_recording() {
+                    self.change_slow_motion(keycode == KeyCode::LBracket);
+                }
+            }
+            _ => {
+                let name = keymap::keycode_name(keycode);
+                if let Some(key) = self.keymap.key(&name) {
+                    self.set_key(key, true);
+                } else if let Some(hint) = self.layout_hint.key_pressed(&self.keymap, &name) {
+                    warn!("{}", hint);
+                }
+            }
+        }
+    }
+
+    fn key_up_event(&mut self, _ctx: &mut Context, keycode: KeyCode, _keymods: KeyMods) {
+        if keycode == KeyCode::Back {
+            self.machine.send(|session| session.rewinding = false);
+        } else if keycode == KeyCode::Tab && self.keymap.key("Tab").is_none() {
+            self.machine.send(|session| session.debugger.set_turbo(false));
+        } else if let Some(key) = self.keymap.key(&keymap::keycode_name(keycode)) {
+            self.set_key(key, false);
+        }
+    }
+
+    fn mouse_button_down_event(&mut self, ctx: &mut Context, button: MouseButton, x: f32, y: f32) {
+        if button != MouseButton::Left || self.picker.is_some() {
+            return;
+        }
+        if self.pause_menu.is_some() {
+            if let Some(index) = self.menu_entry_at(ctx, x, y) {
+                self.menu_input(ctx, MenuInput::Click(index));
+            }
+            return;
+        }
+        let (window_width, window_height) = graphics::drawable_size(ctx);
+        if let Some(key) = self.layout(window_width, window_height).1.and_then(|keypad| keypad.key_at(x, y)) {
+            self.mouse_key = Some(key);
+            self.set_key(key, true);
+        }
+    }
+
+    fn mouse_motion_event(&mut self, ctx: &mut Context, x: f32, y: f32, _dx: f32, _dy: f32) {
+        if let Some(index) = self.menu_entry_at(ctx, x, y) {
+            self.menu_input(ctx, MenuInput::Hover(index));
+        }
+    }
+
+    fn mouse_button_up_event(&mut self, _ctx: &mut Context, button: MouseButton, _x: f32, _y: f32) {
+        if button != MouseButton::Left {
+            return;
+        }
+        if let Some(key) = self.mouse_key.take() {
+            self.set_key(key, false);
+        }
+    }
+
+    fn gamepad_button_down_event(&mut self, _ctx: &mut Context, button: Button, id: GamepadId) {
+        let name = format!("{:?}", button);
+        self.pad_press(id, name.clone(), &name);
+    }
+
+    fn gamepad_button_up_event(&mut self, _ctx: &mut Context, button: Button, id: GamepadId) {
+        self.pad_release(id, format!("{:?}", button));
+    }
+
+    /// Sticks, and D-pads that report as axes, press the D-pad's bindings once pushed far enough.
+    fn gamepad_axis_event(&mut self, _ctx: &mut Context, axis: Axis, value: f32, id: GamepadId) {
+        let (negative, positive) = match axis {
+            Axis::LeftStickX | Axis::DPadX => ("DPadLeft", "DPadRight"),
+            Axis::LeftStickY | Axis::DPadY => ("DPadDown", "DPadUp"),
+            _ => return,
+        };
+        let stick = self.sticks.entry((id, axis)).or_default();
+        let before = stick.direction();
+        let after = stick.update(value);
+        if after == before {
+            return;
+        }
+        let source = format!("{:?}", axis);
+        self.pad_release(id, source.clone());
+        match after {
+            AxisDirection::Negative => self.pad_press(id, source, negative),
+            AxisDirection::Positive => self.pad_press(id, source, positive),
+            AxisDirection::Centre => {}
+        }
+    }
+}
+
+/// Reads stdin line by line on a thread of its own for `--console`, handing each line over.
+fn read_console() -> Receiver<String> {
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        for line in io::stdin().lock().lines() {
+            let sent = line.map(|line| sender.send(line).is_ok());
+            if sent.ok() != Some(true) {
+                break;
+            }
+        }
+    });
+    receiver
+}
+
+fn window_mode(fullscreen: bool) -> WindowMode {
+    let mode = WindowMode::default().dimensions(WINDOW_WIDTH, WINDOW_HEIGHT).resizable(true);
+    if fullscreen {
+        mode.fullscreen_type(FullscreenType::Desktop)
+    } else {
+        mode
+    }
+}
+
+/// Builds the CRT shader, or explains why the display will be drawn without it.
+fn load_crt_shader(ctx: &mut Context) -> Option<graphics::Shader<Crt>> {
+    let initial = Crt { resolution: [64.0, 32.0], time: 0.0 };
+    let result = graphics::Shader::from_u8(ctx, CRT_VERTEX_SHADER.as_bytes(), CRT_FRAGMENT_SHADER.as_bytes(), initial, "Crt", None);
+    match result {
+        Ok(shader) => Some(shader),
+        Err(error) => {
+            warn!("CRT effect unavailable, drawing without it: {}", error);
+            None
+        }
+    }
+}
+
+/// The recently loaded ROMs, or none if they can't be read; a broken list is only worth a warning.
+fn load_recent() -> RecentRoms {
+    let path = match data_dir() {
+        Some(dir) => dir.join(RECENT_FILE_NAME),
+        None => return RecentRoms::default(),
+    };
+    match fs::read_to_string(&path).map(|text| RecentRoms::parse(&text)) {
+        Ok(Ok(recent)) => recent,
+        Ok(Err(error)) => {
+            warn!("Problem reading {}: {}", path.display(), error);
+            RecentRoms::default()
+        }
+        Err(_) => RecentRoms::default(),
+    }
+}
+
+/// Writes the recent ROMs, warning rather than failing if that doesn't work.
+fn save_recent(recent: &RecentRoms) {
+    if let Some(dir) = data_dir() {
+        let path = dir.join(RECENT_FILE_NAME);
+        if let Err(error) = fs::create_dir_all(&dir).and_then(|()| fs::write(&path, recent.to_toml())) {
+            warn!("Problem writing {}: {}", path.display(), error);
+        }
+    }
+}
+
+/// Which recent ROM digit key `keycode` loads: 1 the newest, up to 9.
+fn recent_index(keycode: KeyCode) -> Option<usize> {
+    let digit: usize = keymap::keycode_name(keycode).parse().ok()?;
+    (1..=9).contains(&digit).then(|| digit - 1)
+}
+
+/// The built-in palettes plus those in `palettes.toml`, starting with `chosen` if there is one
+/// and otherwise the one used last time.
+fn load_palettes(chosen: Option<&str>) -> Palettes {
+    let dir = config_dir();
+    let mut user = Vec::new();
+    if let Some(path) = dir.as_ref().map(|dir| dir.join("palettes.toml")) {
+        if let Ok(text) = fs::read_to_string(&path) {
+            match palette::parse(&text) {
+                Ok(palettes) => user = palettes,
+                Err(error) => warn!("Problem reading {}: {}", path.display(), error),
+            }
+        }
+    }
+
+    let mut palettes = Palettes::new(user);
+    if let Some(name) = dir.and_then(|dir| fs::read_to_string(dir.join("palette")).ok()) {
+        palettes.select(name.trim());
+    }
+    if let Some(name) = chosen.filter(|name| !palettes.select(name)) {
+        warn!("There's no palette called {} to start with", name);
+    }
+    palettes
+}
+
+fn save_palette_name(name: &str) {
+    if let Some(dir) = config_dir() {
+        let result = fs::create_dir_all(&dir).and_then(|()| fs::write(dir.join("palette"), name));
+        if let Err(error) = result {
+            warn!("Problem saving the palette choice in {}: {}", dir.display(), error);
+        }
+    }
+}
+
+/// Opens the window and runs `rom` in it, or the ROM picker if there isn't one, until it's closed.
+pub fn run(config: Config, rom: Option<(PathBuf, LoadedRom)>) -> GameResult {
+    let title = match &rom {
+        Some((path, _)) => format!("CHIP-8 — {}", rom_name(path)),
+        None => String::from("CHIP-8"),
+    };
+    let context_builder = ContextBuilder::new("chip-8-emulator", "Ziem")
+        .window_setup(WindowSetup::default().title(&title))
+        .window_mode(window_mode(config.fullscreen));
+    let (mut context, event_loop) = context_builder.build()?;
+    if let Err(error) = ctrlc::set_handler(|| INTERRUPTED.store(true, Ordering::SeqCst)) {
+        warn!("Ctrl+C will close the window without autosaving: {}", error);
+    }
+    let emulator = Emulator::new(&mut context, config, rom);
+    event::run(context, event_loop, emulator)
+}
@@ -0,0 +1,136 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+
+/// How far an analog stick has to be pushed before it counts as a D-pad press.
+pub const PRESS_THRESHOLD: f32 = 0.5;
+/// How far back it has to come before the press ends. The gap between the two keeps a stick
+/// resting near the threshold from pressing and releasing every frame.
+pub const RELEASE_THRESHOLD: f32 = 0.3;
+
+/// Which way an analog axis is pushed, seen as a digital direction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AxisDirection {
+    Negative,
+    #[default]
+    Centre,
+    Positive,
+}
+
+/// Turns one analog axis into a digital direction, with hysteresis.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StickAxis {
+    direction: AxisDirection,
+}
+
+impl StickAxis {
+    pub fn direction(&self) -> AxisDirection {
+        self.direction
+    }
+
+    /// Takes a new reading between -1 and 1 and returns the direction it now counts as.
+    pub fn update(&mut self, value: f32) -> AxisDirection {
+        let held = match self.direction {
+            AxisDirection::Negative => value <= -RELEASE_THRESHOLD,
+            AxisDirection::Positive => value >= RELEASE_THRESHOLD,
+            AxisDirection::Centre => false,
+        };
+        if !held {
+            self.direction = if value >= PRESS_THRESHOLD {
+                AxisDirection::Positive
+            } else if value <= -PRESS_THRESHOLD {
+                AxisDirection::Negative
+            } else {
+                AxisDirection::Centre
+            };
+        }
+        self.direction
+    }
+}
+
+/// Keypad keys held by controller inputs, each `Source` being one button or stick direction on
+/// one controller. Any number of sources can hold the same key, so several controllers share
+/// the keypad: a key goes down with the first of them and up with the last.
+#[derive(Debug, Clone)]
+pub struct HeldKeys<Source> {
+    held: HashMap<Source, u8>,
+}
+
+impl<Source: Eq + Hash> HeldKeys<Source> {
+    pub fn new() -> HeldKeys<Source> {
+        HeldKeys { held: HashMap::new() }
+    }
+
+    /// `source` starts holding `key`; returns whether the key has just gone down.
+    pub fn press(&mut self, source: Source, key: u8) -> bool {
+        let was_down = self.is_held(key);
+        self.held.insert(source, key);
+        !was_down
+    }
+
+    /// `source` lets go; returns the key it held if nothing else still holds it.
+    pub fn release(&mut self, source: &Source) -> Option<u8> {
+        let key = self.held.remove(source)?;
+        if self.is_held(key) {
+            None
+        } else {
+            Some(key)
+        }
+    }
+
+    pub fn is_held(&self, key: u8) -> bool {
+        self.held.values().any(|&held| held == key)
+    }
+}
+
+impl<Source: Eq + Hash> Default for HeldKeys<Source> {
+    fn default() -> HeldKeys<Source> {
+        HeldKeys::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stick_presses_past_the_press_threshold() {
+        let mut axis = StickAxis::default();
+
+        assert_eq!(axis.update(0.4), AxisDirection::Centre);
+        assert_eq!(axis.update(0.6), AxisDirection::Positive);
+        assert_eq!(axis.update(1.0), AxisDirection::Positive);
+        assert_eq!(axis.update(-0.7), AxisDirection::Negative);
+        assert_eq!(axis.update(0.0), AxisDirection::Centre);
+    }
+
+    #[test]
+    fn stick_wobbling_around_the_threshold_does_not_chatter() {
+        let mut axis = StickAxis::default();
+        axis.update(0.55);
+
+        let directions: Vec<AxisDirection> = [0.45, 0.52, 0.35, 0.49].iter().map(|&value| axis.update(value)).collect();
+
+        assert_eq!(directions, vec![AxisDirection::Positive; 4]);
+        assert_eq!(axis.update(0.25), AxisDirection::Centre);
+        // and coming back up it needs the full press threshold again
+        assert_eq!(axis.update(0.45), AxisDirection::Centre);
+        assert_eq!(axis.update(-0.45), AxisDirection::Centre);
+        assert_eq!(axis.update(-0.5), AxisDirection::Negative);
+        assert_eq!(axis.update(-0.31), AxisDirection::Negative);
+        assert_eq!(axis.direction(), AxisDirection::Negative);
+    }
+
+    #[test]
+    fn a_key_stays_down_until_every_controller_lets_go() {
+        let mut held = HeldKeys::new();
+
+        assert!(held.press((0, "DPadUp"), 0x2));
+        assert!(!held.press((1, "LeftStick"), 0x2));
+        assert!(held.press((1, "South"), 0x5));
+        assert_eq!(held.release(&(0, "DPadUp")), None);
+        assert!(held.is_held(0x2));
+        assert_eq!(held.release(&(1, "LeftStick")), Some(0x2));
+        assert_eq!(held.release(&(1, "LeftStick")), None);
+        assert_eq!(held.release(&(1, "South")), Some(0x5));
+    }
+}
@@ -0,0 +1,157 @@
+use crate::display::Display;
+
+/// The display as terminal cells, two pixels to a cell: each row of cells covers two rows of
+/// pixels, drawn with the upper and lower half blocks.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Cells {
+    width: usize,
+    height: usize,
+    cells: Vec<char>,
+}
+
+impl Cells {
+    pub fn new(display: &Display) -> Cells {
+        let (width, height) = (display.width(), display.height() / 2);
+        let lit = |x: usize, y: usize| display.pixel(x, y) != 0;
+        let mut cells = Vec::with_capacity(width * height);
+        for row in 0..height {
+            for x in 0..width {
+                cells.push(match (lit(x, row * 2), lit(x, row * 2 + 1)) {
+                    (false, false) => ' ',
+                    (true, false) => '▀',
+                    (false, true) => '▄',
+                    (true, true) => '█',
+                });
+            }
+        }
+        Cells { width, height, cells }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn row(&self, row: usize) -> &[char] {
+        &self.cells[row * self.width..(row + 1) * self.width]
+    }
+
+    /// Where the top-left cell goes to centre these cells in a `columns` x `rows` terminal, or
+    /// None if they don't fit.
+    pub fn origin(&self, columns: u16, rows: u16) -> Option<(u16, u16)> {
+        let (columns, rows) = (columns as usize, rows as usize);
+        if columns < self.width || rows < self.height {
+            return None;
+        }
+        Some((((columns - self.width) / 2) as u16, ((rows - self.height) / 2) as u16))
+    }
+}
+
+/// A run of cells to rewrite: `text` starting at `column` on `row`, counted in cells.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Change {
+    pub column: usize,
+    pub row: usize,
+    pub text: String,
+}
+
+/// What has to be written to turn the cells on screen, `previous`, into `next`: each run of
+/// cells that differ, row by row. Everything is written when nothing is on screen yet or the
+/// resolution changed.
+pub fn diff(previous: Option<&Cells>, next: &Cells) -> Vec<Change> {
+    let previous = previous.filter(|previous| previous.width == next.width && previous.height == next.height);
+    let mut changes = Vec::new();
+    for row in 0..next.height {
+        let cells = next.row(row);
+        let changed = |column: usize| previous.is_none_or(|previous| previous.row(row)[column] != cells[column]);
+        let mut column = 0;
+        while column < next.width {
+            if !changed(column) {
+                column += 1;
+                continue;
+            }
+            let start = column;
+            while column < next.width && changed(column) {
+                column += 1;
+            }
+            changes.push(Change { column: start, row, text: cells[start..column].iter().collect() });
+        }
+    }
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn display(lit: &[(usize, usize)]) -> Display {
+        let mut display = Display::new();
+        for &(x, y) in lit {
+            display.pixels[x][y] = 1;
+        }
+        display
+    }
+
+    #[test]
+    fn two_pixel_rows_share_a_cell() {
+        let cells = Cells::new(&display(&[(0, 0), (1, 1), (2, 0), (2, 1), (63, 31)]));
+
+        assert_eq!((cells.width(), cells.height()), (64, 16));
+        assert_eq!(&cells.row(0)[..4], &['▀', '▄', '█', ' ']);
+        assert_eq!(cells.row(15)[63], '▄');
+    }
+
+    #[test]
+    fn the_first_frame_is_written_whole() {
+        let cells = Cells::new(&display(&[(5, 0)]));
+
+        let changes = diff(None, &cells);
+
+        assert_eq!(changes.len(), 16);
+        assert_eq!(changes[0], Change { column: 0, row: 0, text: format!("     ▀{}", " ".repeat(58)) });
+        assert!(changes.iter().all(|change| change.column == 0 && change.text.chars().count() == 64));
+    }
+
+    #[test]
+    fn only_changed_runs_are_rewritten() {
+        let before = Cells::new(&display(&[(0, 0), (10, 4)]));
+        // (1, 0) and (2, 1) extend the first cell into a run; (10, 5) joins (10, 4) in its cell
+        let after = Cells::new(&display(&[(0, 0), (1, 0), (2, 1), (10, 4), (10, 5), (40, 30)]));
+
+        assert_eq!(
+            diff(Some(&before), &after),
+            vec![
+                Change { column: 1, row: 0, text: String::from("▀▄") },
+                Change { column: 10, row: 2, text: String::from("█") },
+                Change { column: 40, row: 15, text: String::from("▀") },
+            ]
+        );
+        assert_eq!(diff(Some(&after), &after), Vec::new());
+    }
+
+    #[test]
+    fn a_resolution_change_rewrites_everything() {
+        let lores = Cells::new(&Display::new());
+        let mut hires = Display::new();
+        hires.set_resolution(crate::display::Resolution::Hires);
+
+        let changes = diff(Some(&lores), &Cells::new(&hires));
+
+        assert_eq!(changes.len(), 32);
+        assert_eq!(changes[0].text.chars().count(), 128);
+    }
+
+    #[test]
+    fn cells_are_centred_or_do_not_fit() {
+        let cells = Cells::new(&Display::new());
+
+        assert_eq!(cells.origin(64, 16), Some((0, 0)));
+        assert_eq!(cells.origin(80, 24), Some((8, 4)));
+        assert_eq!(cells.origin(81, 25), Some((8, 4)));
+        assert_eq!(cells.origin(63, 24), None);
+        assert_eq!(cells.origin(80, 15), None);
+    }
+}
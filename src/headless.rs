@@ -0,0 +1,235 @@
+use crate::cpu::{Cpu, CYCLES_PER_FRAME};
+use crate::error::Chip8Error;
+use crate::frontend::{self, FrameEnd, FrameObserver};
+use crate::keys::Keys;
+use crate::timing::{FrameBudget, Timing};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyEvent {
+    pub cycle: u64,
+    pub key: u8,
+    pub pressed: bool,
+}
+
+/// Scripted key input for headless runs, e.g. `5@100,release5@160` presses key 5 before
+/// the 100th instruction and releases it before the 160th.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct KeyScript {
+    events: Vec<KeyEvent>,
+}
+
+impl KeyScript {
+    pub fn parse(script: &str) -> Result<KeyScript, String> {
+        let mut events = Vec::new();
+        for entry in script.split(',').map(str::trim).filter(|entry| !entry.is_empty()) {
+            let (key, cycle) = match entry.find('@') {
+                Some(index) => (&entry[..index], &entry[index + 1..]),
+                None => return Err(format!("missing '@cycle' in key event '{}'", entry)),
+            };
+            let (key, pressed) = match key.strip_prefix("release") {
+                Some(key) => (key, false),
+                None => (key, true),
+            };
+            let key = match u8::from_str_radix(key, 16) {
+                Ok(key) if key <= 0xF => key,
+                _ => return Err(format!("invalid key '{}' in key event '{}'", key, entry)),
+            };
+            let cycle = cycle.parse::<u64>().map_err(|_| format!("invalid cycle '{}' in key event '{}'", cycle, entry))?;
+            events.push(KeyEvent { cycle, key, pressed });
+        }
+        events.sort_by_key(|event| event.cycle);
+
+        Ok(KeyScript { events })
+    }
+
+    pub fn events(&self) -> &[KeyEvent] {
+        &self.events
+    }
+
+    pub(crate) fn apply(&self, cycle: u64, next_event: &mut usize, keys: &mut Keys) {
+        while let Some(event) = self.events.get(*next_event) {
+            if event.cycle > cycle {
+                break;
+            }
+            if event.pressed {
+                keys.press(event.key);
+            } else {
+                keys.release(event.key);
+            }
+            *next_event += 1;
+        }
+    }
+}
+
+/// Executes `cycles` instructions, feeding scripted keys and ticking the timers once every
+/// `CYCLES_PER_FRAME` instructions, the same rate the windowed frontend uses, or sooner when the
+/// display-wait quirk ends a frame at a DXYN. Returns early once the ROM exits with 00FD or stops
+/// in a jump to itself.
+pub fn run(cpu: &mut Cpu, cycles: u64, script: &KeyScript) -> Result<(), Chip8Error> {
+    run_hashing(cpu, cycles, script, 0, |_, _| {})
+}
+
+/// `run`, handing `report` the number of instructions run so far and `Cpu::state_hash` after
+/// every `every` instructions; 0 never does.
+pub fn run_hashing(cpu: &mut Cpu, cycles: u64, script: &KeyScript, every: u64, mut report: impl FnMut(u64, u64)) -> Result<(), Chip8Error> {
+    let mut executed: u64 = 0;
+    run_observed(cpu, cycles, script, |cpu, _| {
+        executed += 1;
+        if executed.is_multiple_of(every) {
+            report(executed, cpu.state_hash());
+        }
+        Ok::<(), Chip8Error>(())
+    })
+}
+
+/// `run`, calling `observe` after every instruction with whether it ended the frame; a frame's
+/// observer sees the machine before its timers tick. An error from `observe` stops the run.
+pub fn run_observed<E: From<Chip8Error>>(
+    cpu: &mut Cpu,
+    cycles: u64,
+    script: &KeyScript,
+    observe: impl FnMut(&mut Cpu, bool) -> Result<(), E>,
+) -> Result<(), E> {
+    let mut run = Scripted { script, cycles, cycle: 0, next_event: 0, observe };
+    while cpu.halted().is_none() {
+        let budget = &mut FrameBudget::new(Timing::Modern, CYCLES_PER_FRAME);
+        if let FrameEnd::Stopped(()) = frontend::run_instructions(cpu, budget, &mut run)? {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+// a headless run looking on as the frame loop runs: it feeds in the scripted keys and stops once
+// `cycles` instructions have run
+struct Scripted<'a, F> {
+    script: &'a KeyScript,
+    cycles: u64,
+    cycle: u64,
+    next_event: usize,
+    observe: F,
+}
+
+impl<E: From<Chip8Error>, F: FnMut(&mut Cpu, bool) -> Result<(), E>> FrameObserver for Scripted<'_, F> {
+    type Stop = ();
+    type Error = E;
+
+    fn before(&mut self, cpu: &mut Cpu, _budget: &FrameBudget) -> Result<Option<()>, E> {
+        if self.cycle == self.cycles {
+            return Ok(Some(()));
+        }
+        self.script.apply(self.cycle, &mut self.next_event, cpu.keys_mut());
+        self.cycle += 1;
+        Ok(None)
+    }
+
+    fn after(&mut self, cpu: &mut Cpu, _pc: u16, frame_ended: bool) -> Result<Option<()>, E> {
+        (self.observe)(cpu, frame_ended)?;
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::display::Display;
+    use crate::memory::Memory;
+
+    #[test]
+    fn parse_key_script() {
+        let script = KeyScript::parse("release5@160, 5@100,a@0").unwrap();
+
+        assert_eq!(script.events(), &[
+            KeyEvent { cycle: 0, key: 0xA, pressed: true },
+            KeyEvent { cycle: 100, key: 5, pressed: true },
+            KeyEvent { cycle: 160, key: 5, pressed: false },
+        ]);
+    }
+
+    #[test]
+    fn parse_invalid_key_script() {
+        assert!(KeyScript::parse("5").is_err());
+        assert!(KeyScript::parse("10@5").is_err());
+        assert!(KeyScript::parse("5@soon").is_err());
+    }
+
+    #[test]
+    fn run_presses_scripted_keys() {
+        let mut memory = Memory::new();
+        // skip the following instruction while key 5 is not pressed
+        memory.write_u16(0x200, 0x6505);
+        memory.write_u16(0x202, 0xE5A1);
+        memory.write_u16(0x204, 0x1204);
+        memory.write_u16(0x206, 0x1206);
+        let mut cpu = Cpu::new(memory, Display::new());
+
+        run(&mut cpu, 2, &KeyScript::parse("5@1").unwrap()).unwrap();
+
+        assert_eq!(cpu.pc(), 0x204);
+    }
+
+    #[test]
+    fn run_stops_on_error() {
+        let mut memory = Memory::new();
+        memory.write_u16(0x200, 0x00EE);
+        let mut cpu = Cpu::new(memory, Display::new());
+
+        let result = run(&mut cpu, 10, &KeyScript::default());
+
+        assert_eq!(result, Err(Chip8Error::StackUnderflow { pc: 0x200 }));
+    }
+
+    #[test]
+    fn run_stops_when_the_rom_exits() {
+        let mut memory = Memory::new();
+        memory.write_u16(0x200, 0x600A);
+        memory.write_u16(0x202, 0xF015);
+        memory.write_u16(0x204, 0x00FD);
+        let mut cpu = Cpu::new(memory, Display::new());
+
+        run(&mut cpu, CYCLES_PER_FRAME as u64 * 3, &KeyScript::default()).unwrap();
+
+        assert!(cpu.is_finished());
+        assert_eq!(cpu.delay_timer(), 10);
+    }
+
+    #[test]
+    fn run_ticks_timers() {
+        let mut memory = Memory::new();
+        memory.write_u16(0x200, 0x600A);
+        memory.write_u16(0x202, 0xF015);
+        // two jumps back and forth, since a jump to itself would end the run
+        memory.write_u16(0x204, 0x1206);
+        memory.write_u16(0x206, 0x1204);
+        let mut cpu = Cpu::new(memory, Display::new());
+
+        run(&mut cpu, CYCLES_PER_FRAME as u64 * 3, &KeyScript::default()).unwrap();
+
+        assert_eq!(cpu.delay_timer(), 7);
+    }
+
+    fn hashes(seed: u64) -> Vec<(u64, u64)> {
+        let mut memory = Memory::new();
+        // draws random digits down the screen forever
+        for (index, &opcode) in [0xC00F, 0xF029, 0xD115, 0x7101, 0x1200].iter().enumerate() {
+            memory.write_u16(0x200 + 2 * index as u16, opcode);
+        }
+        let mut cpu = Cpu::new(memory, Display::new());
+        cpu.seed(seed);
+        let mut hashes = Vec::new();
+
+        run_hashing(&mut cpu, 100, &KeyScript::default(), 10, |instruction, hash| hashes.push((instruction, hash))).unwrap();
+
+        hashes
+    }
+
+    #[test]
+    fn identical_runs_hash_identically() {
+        let first = hashes(7);
+
+        assert_eq!(first.iter().map(|&(instruction, _)| instruction).collect::<Vec<_>>(), (1..=10).map(|n| n * 10).collect::<Vec<_>>());
+        assert_eq!(hashes(7), first);
+        assert_ne!(hashes(8), first);
+    }
+}
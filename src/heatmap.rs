@@ -0,0 +1,183 @@
+use crate::prelude::*;
+
+/// The addresses tracked: the original 4 KB, one cell each of a `GRID_WIDTH`-wide grid.
+pub const HEAT_MAP_SIZE: usize = 0x1000;
+pub const GRID_WIDTH: usize = 64;
+pub const GRID_HEIGHT: usize = HEAT_MAP_SIZE / GRID_WIDTH;
+
+/// How much hotter an address gets each time it's accessed, up to 255.
+const HEAT_PER_ACCESS: u8 = 64;
+/// Cells that show no activity are drawn this bright, so the grid stays visible.
+const COLD: u8 = 24;
+
+/// What the colours mean, for the overlay's legend.
+pub const LEGEND: [&str; 5] = ["red: written", "green: read", "blue: executed", "white outline: pc", "yellow outline: I and the 16 bytes after it"];
+
+/// Recent reads, writes and executes per address of the first 4 KB, each a counter that accesses
+/// heat up and `decay` cools down. Accesses past 4 KB aren't counted.
+pub struct HeatMap {
+    reads: [u8; HEAT_MAP_SIZE],
+    writes: [u8; HEAT_MAP_SIZE],
+    executes: [u8; HEAT_MAP_SIZE],
+}
+
+impl HeatMap {
+    pub fn new() -> HeatMap {
+        HeatMap { reads: [0; HEAT_MAP_SIZE], writes: [0; HEAT_MAP_SIZE], executes: [0; HEAT_MAP_SIZE] }
+    }
+
+    pub fn read(&mut self, address: u16) {
+        heat(&mut self.reads, address);
+    }
+
+    pub fn written(&mut self, address: u16) {
+        heat(&mut self.writes, address);
+    }
+
+    pub fn executed(&mut self, address: u16) {
+        heat(&mut self.executes, address);
+    }
+
+    /// Cools every counter by a sixteenth, and at least by one, so a busy address fades out over
+    /// about a second when called once per frame.
+    pub fn decay(&mut self) {
+        for counter in self.reads.iter_mut().chain(self.writes.iter_mut()).chain(self.executes.iter_mut()) {
+            *counter -= (*counter / 16).max(u8::from(*counter > 0));
+        }
+    }
+
+    /// The read, write and execute counters of `address`; all zero past 4 KB.
+    pub fn counters(&self, address: u16) -> (u8, u8, u8) {
+        let address = address as usize;
+        if address >= HEAT_MAP_SIZE {
+            return (0, 0, 0);
+        }
+        (self.reads[address], self.writes[address], self.executes[address])
+    }
+
+    /// The grid as RGBA, a pixel per address in rows of `GRID_WIDTH`, coloured by `cell_color`.
+    pub fn rgba(&self) -> Vec<u8> {
+        let mut rgba = Vec::with_capacity(HEAT_MAP_SIZE * 4);
+        for address in 0..HEAT_MAP_SIZE as u16 {
+            let (read, write, execute) = self.counters(address);
+            let [red, green, blue] = cell_color(read, write, execute);
+            rgba.extend_from_slice(&[red, green, blue, 0xFF]);
+        }
+        rgba
+    }
+}
+
+impl Default for HeatMap {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn heat(counters: &mut [u8; HEAT_MAP_SIZE], address: u16) {
+    if let Some(counter) = counters.get_mut(address as usize) {
+        *counter = counter.saturating_add(HEAT_PER_ACCESS);
+    }
+}
+
+/// The colour of a cell with the given counters: writes in red, reads in green and executes in
+/// blue, each as bright as its counter, mixing where an address is used more than one way.
+/// Unused cells are dark grey rather than black.
+pub fn cell_color(read: u8, write: u8, execute: u8) -> [u8; 3] {
+    [write.max(COLD), read.max(COLD), execute.max(COLD)]
+}
+
+/// The column and row of `address`'s cell, if it's within the grid.
+pub fn cell_of(address: u16) -> Option<(usize, usize)> {
+    let address = address as usize;
+    (address < HEAT_MAP_SIZE).then_some((address % GRID_WIDTH, address / GRID_WIDTH))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unused_cells_are_dark_grey() {
+        assert_eq!(cell_color(0, 0, 0), [COLD, COLD, COLD]);
+        assert_eq!(cell_color(COLD - 1, 1, 0), [COLD, COLD, COLD]);
+    }
+
+    #[test]
+    fn each_kind_of_access_has_its_own_channel() {
+        assert_eq!(cell_color(200, 0, 0), [COLD, 200, COLD]);
+        assert_eq!(cell_color(0, 200, 0), [200, COLD, COLD]);
+        assert_eq!(cell_color(0, 0, 200), [COLD, COLD, 200]);
+        // code that reads itself shows cyan, data written and read back yellow
+        assert_eq!(cell_color(255, 0, 255), [COLD, 255, 255]);
+        assert_eq!(cell_color(128, 255, 0), [255, 128, COLD]);
+    }
+
+    #[test]
+    fn accesses_heat_up_and_decay_cools_down() {
+        let mut map = HeatMap::new();
+
+        map.read(0x300);
+        map.written(0x301);
+        map.written(0x301);
+        for _ in 0..10 {
+            map.executed(0x200);
+        }
+        // past 4 KB, and ignored
+        map.written(0x1000);
+
+        assert_eq!(map.counters(0x300), (HEAT_PER_ACCESS, 0, 0));
+        assert_eq!(map.counters(0x301), (0, 2 * HEAT_PER_ACCESS, 0));
+        assert_eq!(map.counters(0x200), (0, 0, 255));
+        assert_eq!(map.counters(0x1000), (0, 0, 0));
+
+        map.decay();
+        assert_eq!(map.counters(0x300), (60, 0, 0));
+        assert_eq!(map.counters(0x200), (0, 0, 240));
+
+        // the hottest an address gets fades out in 67 frames
+        for _ in 0..65 {
+            map.decay();
+        }
+        assert_eq!(map.counters(0x200), (0, 0, 1));
+        map.decay();
+        assert_eq!(map.counters(0x200), (0, 0, 0));
+        assert_eq!(map.counters(0x301), (0, 0, 0));
+    }
+
+    #[test]
+    fn the_cpu_feeds_the_heat_map_through_memory() {
+        use crate::cpu::Cpu;
+        use crate::display::Display;
+        use crate::memory::Memory;
+
+        // LD I, 0x300; LD V0, 7; LD [I], V0; LD V0, [I]
+        let mut cpu = Cpu::new(Memory::new(), Display::new());
+        cpu.init(vec![0xA3, 0x00, 0x60, 0x07, 0xF0, 0x55, 0xF0, 0x65]);
+        cpu.enable_heat_map();
+        for _ in 0..4 {
+            cpu.cycle().unwrap();
+        }
+
+        let map = cpu.heat_map().unwrap();
+        assert_eq!(map.counters(0x300), (HEAT_PER_ACCESS, HEAT_PER_ACCESS, 0));
+        assert!((0x200..0x208).all(|address| map.counters(address) == (0, 0, HEAT_PER_ACCESS)));
+        assert_eq!(map.counters(0x208), (0, 0, 0));
+
+        cpu.decay_heat_map();
+        assert_eq!(cpu.heat_map().unwrap().counters(0x200), (0, 0, 60));
+    }
+
+    #[test]
+    fn the_grid_is_laid_out_in_rows_of_64() {
+        let mut map = HeatMap::new();
+        map.written(0x041);
+
+        let rgba = map.rgba();
+
+        assert_eq!(rgba.len(), GRID_WIDTH * GRID_HEIGHT * 4);
+        assert_eq!(&rgba[0x41 * 4..0x42 * 4], &[HEAT_PER_ACCESS, COLD, COLD, 0xFF]);
+        assert_eq!(cell_of(0x041), Some((1, 1)));
+        assert_eq!(cell_of(0xFFF), Some((63, 63)));
+        assert_eq!(cell_of(0x1000), None);
+    }
+}
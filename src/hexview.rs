@@ -0,0 +1,147 @@
+use crate::cpu::Cpu;
+
+pub const BYTES_PER_ROW: usize = 16;
+/// Rows visible at once; PageUp/PageDown scroll by this much.
+pub const VISIBLE_ROWS: usize = 16;
+
+/// Formats `rows` rows of `bytes` starting at `first_row`, sixteen bytes per row with the
+/// address in front. The byte at `i` is marked with `*` and the two bytes at `pc` with `>`.
+pub fn hex_dump(bytes: &[u8], first_row: usize, rows: usize, i: u16, pc: u16) -> Vec<String> {
+    bytes
+        .chunks(BYTES_PER_ROW)
+        .enumerate()
+        .skip(first_row)
+        .take(rows)
+        .map(|(row, chunk)| {
+            let address = row * BYTES_PER_ROW;
+            let mut line = format!("{:#05X}", address);
+            for (offset, byte) in chunk.iter().enumerate() {
+                let location = address + offset;
+                let marker = if location == pc as usize || location == pc as usize + 1 {
+                    '>'
+                } else if location == i as usize {
+                    '*'
+                } else {
+                    ' '
+                };
+                line.push_str(&format!("{}{:02X}", marker, byte));
+            }
+            line
+        })
+        .collect()
+}
+
+/// Scroll state of the memory hex viewer.
+#[derive(Default)]
+pub struct HexView {
+    first_row: usize,
+    follow_i: bool,
+}
+
+impl HexView {
+    pub fn new() -> HexView {
+        HexView { first_row: 0, follow_i: false }
+    }
+
+    pub fn first_row(&self) -> usize {
+        self.first_row
+    }
+
+    pub fn is_following_i(&self) -> bool {
+        self.follow_i
+    }
+
+    pub fn toggle_follow_i(&mut self) {
+        self.follow_i = !self.follow_i;
+    }
+
+    pub fn page_up(&mut self) {
+        self.first_row = self.first_row.saturating_sub(VISIBLE_ROWS);
+    }
+
+    pub fn page_down(&mut self, memory_size: usize) {
+        self.first_row = (self.first_row + VISIBLE_ROWS).min(last_first_row(memory_size));
+    }
+
+    /// Scrolls so the row holding `address` is at the top, as far as the end of memory allows.
+    pub fn jump_to(&mut self, address: u16, memory_size: usize) {
+        self.first_row = (address as usize / BYTES_PER_ROW).min(last_first_row(memory_size));
+    }
+
+    /// The visible rows of the cpu's memory; when following `I`, scrolls first if `I` is off screen.
+    pub fn lines(&mut self, cpu: &Cpu) -> Vec<String> {
+        let bytes = cpu.memory().bytes();
+        let row_of_i = cpu.i() as usize / BYTES_PER_ROW;
+        if self.follow_i && (row_of_i < self.first_row || row_of_i >= self.first_row + VISIBLE_ROWS) {
+            self.jump_to(cpu.i(), bytes.len());
+        }
+        hex_dump(bytes, self.first_row, VISIBLE_ROWS, cpu.i(), cpu.pc())
+    }
+}
+
+fn last_first_row(memory_size: usize) -> usize {
+    let rows = memory_size.div_ceil(BYTES_PER_ROW);
+    rows.saturating_sub(VISIBLE_ROWS)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dump_marks_i_and_pc() {
+        let bytes: Vec<u8> = (0..48).collect();
+
+        let lines = hex_dump(&bytes, 1, 2, 0x11, 0x1E);
+
+        assert_eq!(
+            lines,
+            vec![
+                "0x010 10*11 12 13 14 15 16 17 18 19 1A 1B 1C 1D>1E>1F",
+                "0x020 20 21 22 23 24 25 26 27 28 29 2A 2B 2C 2D 2E 2F",
+            ]
+        );
+    }
+
+    #[test]
+    fn dump_stops_at_the_end_of_memory() {
+        let bytes = [0xAB; 20];
+
+        let lines = hex_dump(&bytes, 1, 16, 0x100, 0x200);
+
+        assert_eq!(lines, vec!["0x010 AB AB AB AB"]);
+    }
+
+    #[test]
+    fn scrolling_is_clamped() {
+        let mut view = HexView::new();
+
+        view.page_up();
+        assert_eq!(view.first_row(), 0);
+
+        for _ in 0..100 {
+            view.page_down(0x1000);
+        }
+        assert_eq!(view.first_row(), 256 - VISIBLE_ROWS);
+
+        view.jump_to(0x234, 0x1000);
+        assert_eq!(view.first_row(), 0x23);
+    }
+
+    #[test]
+    fn following_i_scrolls_to_it() {
+        use crate::display::Display;
+        use crate::memory::Memory;
+
+        let mut cpu = Cpu::new(Memory::new(), Display::new());
+        cpu.init(vec![0xA3, 0x45]);
+        cpu.cycle().unwrap();
+        let mut view = HexView::new();
+
+        view.toggle_follow_i();
+        let lines = view.lines(&cpu);
+
+        assert_eq!(view.first_row(), 0x34);
+        assert!(lines[0].starts_with("0x340 00 00 00 00 00*00"));
+    }
+}
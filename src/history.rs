@@ -0,0 +1,116 @@
+use core::fmt;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::edit::Change;
+use crate::instruction::disassemble;
+
+/// Number of executed instructions kept by `History`.
+pub const HISTORY_LENGTH: usize = 64;
+
+/// An executed instruction, or a value changed by hand while the machine stood at `pc`, in
+/// which case `opcode` is 0.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct HistoryEntry {
+    pub pc: u16,
+    pub opcode: u16,
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "Option::is_none"))]
+    pub edit: Option<Change>,
+}
+
+impl fmt::Display for HistoryEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.edit {
+            Some(change) => write!(f, "{:#05X}: set {} by hand", self.pc, change),
+            None => write!(f, "{:#05X}: {:04X}  {}", self.pc, self.opcode, disassemble(self.opcode)),
+        }
+    }
+}
+
+/// Fixed-size circular buffer of the most recently executed instructions, and of the edits made
+/// by hand between them; recording never allocates.
+pub struct History {
+    entries: [HistoryEntry; HISTORY_LENGTH],
+    next: usize,
+    len: usize,
+}
+
+impl History {
+    pub fn new() -> History {
+        History {
+            entries: [HistoryEntry::default(); HISTORY_LENGTH],
+            next: 0,
+            len: 0,
+        }
+    }
+
+    pub fn record(&mut self, pc: u16, opcode: u16) {
+        self.push(HistoryEntry { pc, opcode, edit: None });
+    }
+
+    /// Notes `change`, made by hand while the machine stood at `pc`.
+    pub fn record_edit(&mut self, pc: u16, change: Change) {
+        self.push(HistoryEntry { pc, opcode: 0, edit: Some(change) });
+    }
+
+    fn push(&mut self, entry: HistoryEntry) {
+        self.entries[self.next] = entry;
+        self.next = (self.next + 1) % HISTORY_LENGTH;
+        self.len = (self.len + 1).min(HISTORY_LENGTH);
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn clear(&mut self) {
+        self.next = 0;
+        self.len = 0;
+    }
+
+    /// The recorded instructions, oldest first.
+    pub fn iter(&self) -> impl Iterator<Item = &HistoryEntry> + '_ {
+        let start = (self.next + HISTORY_LENGTH - self.len) % HISTORY_LENGTH;
+        (0..self.len).map(move |offset| &self.entries[(start + offset) % HISTORY_LENGTH])
+    }
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::edit::Target;
+
+    #[test]
+    fn keeps_the_most_recent_entries_in_order() {
+        let mut history = History::new();
+        for pc in 0..100 {
+            history.record(pc, 0x1000 | pc);
+        }
+
+        let pcs: Vec<u16> = history.iter().map(|entry| entry.pc).collect();
+
+        assert_eq!(history.len(), HISTORY_LENGTH);
+        assert_eq!(pcs, (36..100).collect::<Vec<u16>>());
+    }
+
+    #[test]
+    fn entry_display() {
+        let entry = HistoryEntry { pc: 0x2A4, opcode: 0x8124, edit: None };
+        let edit = HistoryEntry { pc: 0x2A4, opcode: 0, edit: Some(Change { target: Target::V(3), old: 0x1F, new: 0 }) };
+
+        assert_eq!(entry.to_string(), "0x2A4: 8124  ADD V1, V2");
+        assert_eq!(edit.to_string(), "0x2A4: set V3 0x1F -> 0x00 by hand");
+    }
+}
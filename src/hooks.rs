@@ -0,0 +1,226 @@
+#[cfg(feature = "std")]
+use std::sync::{Arc, Mutex};
+
+use log::trace;
+
+use crate::coverage::Coverage;
+use crate::cpu::Halt;
+use crate::history::History;
+use crate::instruction::disassemble;
+use crate::profiler::Profile;
+
+/// Observes a `Cpu` as it runs, for embedders that want to react to what the ROM does without
+/// patching the interpreter; hand one to `Cpu::set_hooks`. Each method is called once the change
+/// it describes has been made, so the machine already reflects it, and does nothing unless
+/// overridden.
+pub trait EmulatorHooks: Send {
+    /// An instruction ran: the one at `pc`, whatever it did to PC. FX0A runs again every cycle
+    /// it waits.
+    fn on_instruction(&mut self, _pc: u16, _opcode: u16) {}
+
+    /// 00E0 cleared the screen, or in MEGA-CHIP mode showed what had been drawn.
+    fn on_display_cleared(&mut self) {}
+
+    /// DXYN drew at (`x`, `y`), before any wrapping, with VF set to `collision`. `rows` are the
+    /// sprite's rows as `Display::draw_sprite` takes them, of the last plane drawn on; MEGA-CHIP's
+    /// colour sprites have no rows of bits, so they come with none.
+    fn on_sprite_drawn(&mut self, _x: usize, _y: usize, _rows: &[u16], _collision: bool) {}
+
+    /// The sound timer started or stopped the buzzer: set above zero while it was silent, or set
+    /// to or counted down to zero while it was sounding.
+    fn on_sound(&mut self, _on: bool) {}
+
+    /// The machine halted, for good or on an error; `cycle` runs nothing more after it.
+    fn on_halt(&mut self, _halt: &Halt) {}
+}
+
+/// Hooks shared with the rest of the program, which can look at them while the machine runs.
+#[cfg(feature = "std")]
+impl<H: EmulatorHooks> EmulatorHooks for Arc<Mutex<H>> {
+    fn on_instruction(&mut self, pc: u16, opcode: u16) {
+        if let Ok(mut hooks) = self.lock() {
+            hooks.on_instruction(pc, opcode);
+        }
+    }
+
+    fn on_display_cleared(&mut self) {
+        if let Ok(mut hooks) = self.lock() {
+            hooks.on_display_cleared();
+        }
+    }
+
+    fn on_sprite_drawn(&mut self, x: usize, y: usize, rows: &[u16], collision: bool) {
+        if let Ok(mut hooks) = self.lock() {
+            hooks.on_sprite_drawn(x, y, rows, collision);
+        }
+    }
+
+    fn on_sound(&mut self, on: bool) {
+        if let Ok(mut hooks) = self.lock() {
+            hooks.on_sound(on);
+        }
+    }
+
+    fn on_halt(&mut self, halt: &Halt) {
+        if let Ok(mut hooks) = self.lock() {
+            hooks.on_halt(halt);
+        }
+    }
+}
+
+/// The `RUST_LOG=trace` instruction log as hooks, naming each instruction as it runs.
+pub struct TraceLog;
+
+impl EmulatorHooks for TraceLog {
+    fn on_instruction(&mut self, pc: u16, opcode: u16) {
+        trace!("{:#05X}: {:04X}  {}", pc, opcode, disassemble(opcode));
+    }
+
+    fn on_halt(&mut self, halt: &Halt) {
+        trace!("halted: {:?}", halt);
+    }
+}
+
+impl EmulatorHooks for Profile {
+    fn on_instruction(&mut self, pc: u16, opcode: u16) {
+        self.record(pc, opcode);
+    }
+}
+
+impl EmulatorHooks for Coverage {
+    fn on_instruction(&mut self, pc: u16, _opcode: u16) {
+        self.record(pc);
+    }
+}
+
+impl EmulatorHooks for History {
+    fn on_instruction(&mut self, pc: u16, opcode: u16) {
+        self.record(pc, opcode);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cpu::Cpu;
+    use crate::display::Display;
+    use crate::error::Chip8Error;
+    use crate::memory::Memory;
+
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    enum Event {
+        Instruction(u16, u16),
+        Cleared,
+        Sprite(usize, usize, Vec<u16>, bool),
+        Sound(bool),
+        Halt(Halt),
+    }
+
+    #[derive(Default)]
+    struct Recorder {
+        events: Vec<Event>,
+    }
+
+    impl EmulatorHooks for Recorder {
+        fn on_instruction(&mut self, pc: u16, opcode: u16) {
+            self.events.push(Event::Instruction(pc, opcode));
+        }
+
+        fn on_display_cleared(&mut self) {
+            self.events.push(Event::Cleared);
+        }
+
+        fn on_sprite_drawn(&mut self, x: usize, y: usize, rows: &[u16], collision: bool) {
+            self.events.push(Event::Sprite(x, y, rows.to_vec(), collision));
+        }
+
+        fn on_sound(&mut self, on: bool) {
+            self.events.push(Event::Sound(on));
+        }
+
+        fn on_halt(&mut self, halt: &Halt) {
+            self.events.push(Event::Halt(*halt));
+        }
+    }
+
+    fn hooked(rom: &[u8]) -> (Cpu, Arc<Mutex<Recorder>>) {
+        let mut cpu = Cpu::new(Memory::new(), Display::new());
+        cpu.init(rom.to_vec());
+        let recorder = Arc::new(Mutex::new(Recorder::default()));
+        cpu.set_hooks(Box::new(Arc::clone(&recorder)));
+        (cpu, recorder)
+    }
+
+    #[test]
+    fn events_come_in_the_order_the_rom_causes_them() {
+        // CLS; LD V0, 2; LD ST, V0; LD I, 0 (the font's 0); DRW V0, V0, 5; DRW V0, V0, 5; JP 0x20C
+        let (mut cpu, recorder) = hooked(&[0x00, 0xE0, 0x60, 0x02, 0xF0, 0x18, 0xA0, 0x00, 0xD0, 0x05, 0xD0, 0x05, 0x12, 0x0C]);
+        for _ in 0..7 {
+            cpu.cycle().unwrap();
+        }
+        cpu.tick_timers();
+        cpu.tick_timers();
+
+        let zero = vec![0xF000, 0x9000, 0x9000, 0x9000, 0xF000];
+        assert_eq!(recorder.lock().unwrap().events, vec![
+            Event::Cleared,
+            Event::Instruction(0x200, 0x00E0),
+            Event::Instruction(0x202, 0x6002),
+            Event::Sound(true),
+            Event::Instruction(0x204, 0xF018),
+            Event::Instruction(0x206, 0xA000),
+            Event::Sprite(2, 2, zero.clone(), false),
+            Event::Instruction(0x208, 0xD005),
+            Event::Sprite(2, 2, zero, true),
+            Event::Instruction(0x20A, 0xD005),
+            Event::Instruction(0x20C, 0x120C),
+            Event::Halt(Halt::Looped(0x20C)),
+            Event::Sound(false),
+        ]);
+    }
+
+    #[test]
+    fn errors_are_reported_once() {
+        // RET with nothing to return to
+        let (mut cpu, recorder) = hooked(&[0x00, 0xEE]);
+        let error = Chip8Error::StackUnderflow { pc: 0x200 };
+
+        assert_eq!(cpu.cycle(), Err(error));
+        assert_eq!(cpu.cycle(), Err(error));
+        assert_eq!(recorder.lock().unwrap().events, vec![Event::Instruction(0x200, 0x00EE), Event::Halt(Halt::Error(error))]);
+    }
+
+    #[test]
+    fn the_profiler_coverage_and_history_can_run_on_the_hooks() {
+        // LD V0, 5; ADD V0, 0xFF; SE V0, 0; JP 0x202; JP 0x208
+        let rom = [0x60, 0x05, 0x70, 0xFF, 0x30, 0x00, 0x12, 0x02, 0x12, 0x08];
+        let mut cpu = Cpu::new(Memory::new(), Display::new());
+        cpu.init(rom.to_vec());
+        cpu.enable_profiling();
+        cpu.enable_coverage(Coverage::new(&rom, 0x200));
+        let profile = Arc::new(Mutex::new(Profile::new()));
+        let coverage = Arc::new(Mutex::new(Coverage::new(&rom, 0x200)));
+        let history = Arc::new(Mutex::new(History::new()));
+        let hooks = vec![Box::new(Arc::clone(&profile)) as Box<dyn EmulatorHooks>, Box::new(Arc::clone(&coverage)), Box::new(Arc::clone(&history))];
+        let mut machines: Vec<Cpu> = hooks
+            .into_iter()
+            .map(|hooks| {
+                let mut machine = Cpu::new(Memory::new(), Display::new());
+                machine.init(rom.to_vec());
+                machine.set_hooks(hooks);
+                machine
+            })
+            .collect();
+
+        for _ in 0..20 {
+            cpu.cycle().unwrap();
+            machines.iter_mut().for_each(|machine| machine.cycle().map(|_| ()).unwrap());
+        }
+
+        let built_in = cpu.profile().unwrap();
+        assert_eq!(profile.lock().unwrap().families(), built_in.families());
+        assert_eq!(profile.lock().unwrap().addresses(), built_in.addresses());
+        assert!((0x200..0x20A).step_by(2).all(|address| coverage.lock().unwrap().contains(address) == cpu.coverage().unwrap().contains(address)));
+        assert!(history.lock().unwrap().iter().eq(cpu.history().iter()));
+    }
+}
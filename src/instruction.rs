@@ -0,0 +1,518 @@
+use core::fmt;
+
+use crate::prelude::*;
+use crate::quirks::Platform;
+
+/// Clock cycles of the COSMAC VIP's 1.76 MHz CDP1802 that most instructions took, fetch and
+/// decode by the interpreter included.
+pub const VIP_INSTRUCTION_CYCLES: u32 = 4540;
+/// What each row of a DXYN sprite adds to it: shifting the byte into place and XORing it onto
+/// the screen.
+pub const VIP_SPRITE_ROW_CYCLES: u32 = 1800;
+
+/// A decoded CHIP-8 instruction; registers are indices 0x0..=0xF.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Instruction {
+    Sys(u16),
+    Cls,
+    Ret,
+    ScrollDown(u8),
+    ScrollRight,
+    ScrollLeft,
+    Exit,
+    Lores,
+    Hires,
+    Jump(u16),
+    Call(u16),
+    SkipEqByte(u8, u8),
+    SkipNeByte(u8, u8),
+    SkipEqReg(u8, u8),
+    SaveRange(u8, u8),
+    LoadRange(u8, u8),
+    LoadByte(u8, u8),
+    AddByte(u8, u8),
+    LoadReg(u8, u8),
+    Or(u8, u8),
+    And(u8, u8),
+    Xor(u8, u8),
+    AddReg(u8, u8),
+    Sub(u8, u8),
+    ShiftRight(u8, u8),
+    SubN(u8, u8),
+    ShiftLeft(u8, u8),
+    SkipNeReg(u8, u8),
+    LoadI(u16),
+    JumpV0(u16),
+    Random(u8, u8),
+    Draw(u8, u8, u8),
+    SkipKeyPressed(u8),
+    SkipKeyNotPressed(u8),
+    LoadLongI,
+    Plane(u8),
+    Audio,
+    Pitch(u8),
+    LoadDelay(u8),
+    WaitKey(u8),
+    SetDelay(u8),
+    SetSound(u8),
+    AddI(u8),
+    LoadFont(u8),
+    LoadBigFont(u8),
+    StoreBcd(u8),
+    StoreRegisters(u8),
+    LoadRegisters(u8),
+    StoreFlags(u8),
+    LoadFlags(u8),
+    // MEGA-CHIP's, which only `decode_on` its platform returns
+    MegaOff,
+    MegaOn,
+    ScrollUp(u8),
+    LoadHighI(u8),
+    LoadPalette(u8),
+    SpriteWidth(u8),
+    SpriteHeight(u8),
+    ScreenAlpha(u8),
+    PlaySound(u8),
+    StopSound,
+    CollisionColor(u8),
+}
+
+impl Instruction {
+    pub fn decode(opcode: u16) -> Option<Instruction> {
+        let x = ((opcode & 0x0F00) >> 8) as u8;
+        let y = ((opcode & 0x00F0) >> 4) as u8;
+        let kk = (opcode & 0x00FF) as u8;
+        let nnn = opcode & 0x0FFF;
+        let n = (opcode & 0x000F) as u8;
+
+        let instruction = match opcode >> 12 {
+            0x0 => match opcode {
+                0x00E0 => Instruction::Cls,
+                0x00EE => Instruction::Ret,
+                0x00C0..=0x00CF => Instruction::ScrollDown(n),
+                0x00FB => Instruction::ScrollRight,
+                0x00FC => Instruction::ScrollLeft,
+                0x00FD => Instruction::Exit,
+                0x00FE => Instruction::Lores,
+                0x00FF => Instruction::Hires,
+                _ => Instruction::Sys(nnn),
+            },
+            0x1 => Instruction::Jump(nnn),
+            0x2 => Instruction::Call(nnn),
+            0x3 => Instruction::SkipEqByte(x, kk),
+            0x4 => Instruction::SkipNeByte(x, kk),
+            0x5 => match n {
+                0x0 => Instruction::SkipEqReg(x, y),
+                0x2 => Instruction::SaveRange(x, y),
+                0x3 => Instruction::LoadRange(x, y),
+                _ => return None,
+            },
+            0x6 => Instruction::LoadByte(x, kk),
+            0x7 => Instruction::AddByte(x, kk),
+            0x8 => match n {
+                0x0 => Instruction::LoadReg(x, y),
+                0x1 => Instruction::Or(x, y),
+                0x2 => Instruction::And(x, y),
+                0x3 => Instruction::Xor(x, y),
+                0x4 => Instruction::AddReg(x, y),
+                0x5 => Instruction::Sub(x, y),
+                0x6 => Instruction::ShiftRight(x, y),
+                0x7 => Instruction::SubN(x, y),
+                0xE => Instruction::ShiftLeft(x, y),
+                _ => return None,
+            },
+            0x9 if n == 0 => Instruction::SkipNeReg(x, y),
+            0xA => Instruction::LoadI(nnn),
+            0xB => Instruction::JumpV0(nnn),
+            0xC => Instruction::Random(x, kk),
+            0xD => Instruction::Draw(x, y, n),
+            0xE => match kk {
+                0x9E => Instruction::SkipKeyPressed(x),
+                0xA1 => Instruction::SkipKeyNotPressed(x),
+                _ => return None,
+            },
+            0xF => match kk {
+                0x00 if x == 0 => Instruction::LoadLongI,
+                0x01 => Instruction::Plane(x),
+                0x02 if x == 0 => Instruction::Audio,
+                0x3A => Instruction::Pitch(x),
+                0x07 => Instruction::LoadDelay(x),
+                0x0A => Instruction::WaitKey(x),
+                0x15 => Instruction::SetDelay(x),
+                0x18 => Instruction::SetSound(x),
+                0x1E => Instruction::AddI(x),
+                0x29 => Instruction::LoadFont(x),
+                0x30 => Instruction::LoadBigFont(x),
+                0x33 => Instruction::StoreBcd(x),
+                0x55 => Instruction::StoreRegisters(x),
+                0x65 => Instruction::LoadRegisters(x),
+                0x75 => Instruction::StoreFlags(x),
+                0x85 => Instruction::LoadFlags(x),
+                _ => return None,
+            },
+            _ => return None,
+        };
+
+        Some(instruction)
+    }
+
+    /// `decode`, with the instructions only `platform` has: MEGA-CHIP's take over some of the
+    /// 0NNN machine-code calls.
+    pub fn decode_on(opcode: u16, platform: Platform) -> Option<Instruction> {
+        let kk = (opcode & 0x00FF) as u8;
+        let n = (opcode & 0x000F) as u8;
+        if platform != Platform::MegaChip {
+            return Instruction::decode(opcode);
+        }
+        let instruction = match opcode {
+            0x0010 => Instruction::MegaOff,
+            0x0011 => Instruction::MegaOn,
+            0x00B0..=0x00BF => Instruction::ScrollUp(n),
+            0x0100..=0x01FF => Instruction::LoadHighI(kk),
+            0x0200..=0x02FF => Instruction::LoadPalette(kk),
+            0x0300..=0x03FF => Instruction::SpriteWidth(kk),
+            0x0400..=0x04FF => Instruction::SpriteHeight(kk),
+            0x0500..=0x05FF => Instruction::ScreenAlpha(kk),
+            0x0600..=0x0601 => Instruction::PlaySound(n),
+            0x0700 => Instruction::StopSound,
+            0x0900..=0x09FF => Instruction::CollisionColor(kk),
+            _ => return Instruction::decode(opcode),
+        };
+        Some(instruction)
+    }
+
+    /// Roughly how many clock cycles the COSMAC VIP's interpreter spent on this instruction, for
+    /// `Timing::Vip`. The instructions that loop over memory cost more the more they touch.
+    pub fn vip_cycles(self) -> u32 {
+        match self {
+            // DXY0 is a 16-row sprite
+            Instruction::Draw(_, _, 0) => VIP_INSTRUCTION_CYCLES + 16 * VIP_SPRITE_ROW_CYCLES,
+            Instruction::Draw(_, _, n) => VIP_INSTRUCTION_CYCLES + n as u32 * VIP_SPRITE_ROW_CYCLES,
+            // clears the 256 bytes of display memory one at a time
+            Instruction::Cls => VIP_INSTRUCTION_CYCLES + 256 * 24,
+            Instruction::StoreRegisters(x) | Instruction::LoadRegisters(x) => VIP_INSTRUCTION_CYCLES + (x as u32 + 1) * 112,
+            // hundreds, tens and units by repeated subtraction
+            Instruction::StoreBcd(_) => VIP_INSTRUCTION_CYCLES + 2000,
+            Instruction::Random(..) => VIP_INSTRUCTION_CYCLES + 288,
+            _ => VIP_INSTRUCTION_CYCLES,
+        }
+    }
+
+    /// The opcode that decodes to this instruction. `LoadLongI` is only the F000 and `LoadHighI`
+    /// the 01NN; the address word after them is up to the caller.
+    pub fn encode(self) -> u16 {
+        let xy = |x: u8, y: u8| (x as u16) << 8 | (y as u16) << 4;
+        let xkk = |x: u8, kk: u8| (x as u16) << 8 | kk as u16;
+        match self {
+            Instruction::Sys(nnn) => nnn & 0x0FFF,
+            Instruction::Cls => 0x00E0,
+            Instruction::Ret => 0x00EE,
+            Instruction::ScrollDown(n) => 0x00C0 | n as u16,
+            Instruction::ScrollRight => 0x00FB,
+            Instruction::ScrollLeft => 0x00FC,
+            Instruction::Exit => 0x00FD,
+            Instruction::Lores => 0x00FE,
+            Instruction::Hires => 0x00FF,
+            Instruction::Jump(nnn) => 0x1000 | nnn,
+            Instruction::Call(nnn) => 0x2000 | nnn,
+            Instruction::SkipEqByte(x, kk) => 0x3000 | xkk(x, kk),
+            Instruction::SkipNeByte(x, kk) => 0x4000 | xkk(x, kk),
+            Instruction::SkipEqReg(x, y) => 0x5000 | xy(x, y),
+            Instruction::SaveRange(x, y) => 0x5002 | xy(x, y),
+            Instruction::LoadRange(x, y) => 0x5003 | xy(x, y),
+            Instruction::LoadByte(x, kk) => 0x6000 | xkk(x, kk),
+            Instruction::AddByte(x, kk) => 0x7000 | xkk(x, kk),
+            Instruction::LoadReg(x, y) => 0x8000 | xy(x, y),
+            Instruction::Or(x, y) => 0x8001 | xy(x, y),
+            Instruction::And(x, y) => 0x8002 | xy(x, y),
+            Instruction::Xor(x, y) => 0x8003 | xy(x, y),
+            Instruction::AddReg(x, y) => 0x8004 | xy(x, y),
+            Instruction::Sub(x, y) => 0x8005 | xy(x, y),
+            Instruction::ShiftRight(x, y) => 0x8006 | xy(x, y),
+            Instruction::SubN(x, y) => 0x8007 | xy(x, y),
+            Instruction::ShiftLeft(x, y) => 0x800E | xy(x, y),
+            Instruction::SkipNeReg(x, y) => 0x9000 | xy(x, y),
+            Instruction::LoadI(nnn) => 0xA000 | nnn,
+            Instruction::JumpV0(nnn) => 0xB000 | nnn,
+            Instruction::Random(x, kk) => 0xC000 | xkk(x, kk),
+            Instruction::Draw(x, y, n) => 0xD000 | xy(x, y) | n as u16,
+            Instruction::SkipKeyPressed(x) => 0xE09E | xkk(x, 0),
+            Instruction::SkipKeyNotPressed(x) => 0xE0A1 | xkk(x, 0),
+            Instruction::LoadLongI => 0xF000,
+            Instruction::Plane(x) => 0xF001 | xkk(x, 0),
+            Instruction::Audio => 0xF002,
+            Instruction::Pitch(x) => 0xF03A | xkk(x, 0),
+            Instruction::LoadDelay(x) => 0xF007 | xkk(x, 0),
+            Instruction::WaitKey(x) => 0xF00A | xkk(x, 0),
+            Instruction::SetDelay(x) => 0xF015 | xkk(x, 0),
+            Instruction::SetSound(x) => 0xF018 | xkk(x, 0),
+            Instruction::AddI(x) => 0xF01E | xkk(x, 0),
+            Instruction::LoadFont(x) => 0xF029 | xkk(x, 0),
+            Instruction::LoadBigFont(x) => 0xF030 | xkk(x, 0),
+            Instruction::StoreBcd(x) => 0xF033 | xkk(x, 0),
+            Instruction::StoreRegisters(x) => 0xF055 | xkk(x, 0),
+            Instruction::LoadRegisters(x) => 0xF065 | xkk(x, 0),
+            Instruction::StoreFlags(x) => 0xF075 | xkk(x, 0),
+            Instruction::LoadFlags(x) => 0xF085 | xkk(x, 0),
+            Instruction::MegaOff => 0x0010,
+            Instruction::MegaOn => 0x0011,
+            Instruction::ScrollUp(n) => 0x00B0 | n as u16,
+            Instruction::LoadHighI(kk) => 0x0100 | kk as u16,
+            Instruction::LoadPalette(kk) => 0x0200 | kk as u16,
+            Instruction::SpriteWidth(kk) => 0x0300 | kk as u16,
+            Instruction::SpriteHeight(kk) => 0x0400 | kk as u16,
+            Instruction::ScreenAlpha(kk) => 0x0500 | kk as u16,
+            Instruction::PlaySound(n) => 0x0600 | n as u16,
+            Instruction::StopSound => 0x0700,
+            Instruction::CollisionColor(kk) => 0x0900 | kk as u16,
+        }
+    }
+}
+
+/// Formats the instruction with the classic (Cowgod) mnemonics, e.g. `LD V3, 0x1F`.
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Instruction::Sys(nnn) => write!(f, "SYS {:#05X}", nnn),
+            Instruction::Cls => write!(f, "CLS"),
+            Instruction::Ret => write!(f, "RET"),
+            Instruction::ScrollDown(n) => write!(f, "SCD {}", n),
+            Instruction::ScrollRight => write!(f, "SCR"),
+            Instruction::ScrollLeft => write!(f, "SCL"),
+            Instruction::Exit => write!(f, "EXIT"),
+            Instruction::Lores => write!(f, "LOW"),
+            Instruction::Hires => write!(f, "HIGH"),
+            Instruction::Jump(nnn) => write!(f, "JP {:#05X}", nnn),
+            Instruction::Call(nnn) => write!(f, "CALL {:#05X}", nnn),
+            Instruction::SkipEqByte(x, kk) => write!(f, "SE V{:X}, {:#04X}", x, kk),
+            Instruction::SkipNeByte(x, kk) => write!(f, "SNE V{:X}, {:#04X}", x, kk),
+            Instruction::SkipEqReg(x, y) => write!(f, "SE V{:X}, V{:X}", x, y),
+            Instruction::SaveRange(x, y) => write!(f, "SAVE V{:X} - V{:X}", x, y),
+            Instruction::LoadRange(x, y) => write!(f, "LOAD V{:X} - V{:X}", x, y),
+            Instruction::LoadByte(x, kk) => write!(f, "LD V{:X}, {:#04X}", x, kk),
+            Instruction::AddByte(x, kk) => write!(f, "ADD V{:X}, {:#04X}", x, kk),
+            Instruction::LoadReg(x, y) => write!(f, "LD V{:X}, V{:X}", x, y),
+            Instruction::Or(x, y) => write!(f, "OR V{:X}, V{:X}", x, y),
+            Instruction::And(x, y) => write!(f, "AND V{:X}, V{:X}", x, y),
+            Instruction::Xor(x, y) => write!(f, "XOR V{:X}, V{:X}", x, y),
+            Instruction::AddReg(x, y) => write!(f, "ADD V{:X}, V{:X}", x, y),
+            Instruction::Sub(x, y) => write!(f, "SUB V{:X}, V{:X}", x, y),
+            Instruction::ShiftRight(x, y) => write!(f, "SHR V{:X}, V{:X}", x, y),
+            Instruction::SubN(x, y) => write!(f, "SUBN V{:X}, V{:X}", x, y),
+            Instruction::ShiftLeft(x, y) => write!(f, "SHL V{:X}, V{:X}", x, y),
+            Instruction::SkipNeReg(x, y) => write!(f, "SNE V{:X}, V{:X}", x, y),
+            Instruction::LoadI(nnn) => write!(f, "LD I, {:#05X}", nnn),
+            Instruction::JumpV0(nnn) => write!(f, "JP V0, {:#05X}", nnn),
+            Instruction::Random(x, kk) => write!(f, "RND V{:X}, {:#04X}", x, kk),
+            Instruction::Draw(x, y, n) => write!(f, "DRW V{:X}, V{:X}, {}", x, y, n),
+            Instruction::SkipKeyPressed(x) => write!(f, "SKP V{:X}", x),
+            Instruction::SkipKeyNotPressed(x) => write!(f, "SKNP V{:X}", x),
+            Instruction::LoadLongI => write!(f, "LD I, LONG"),
+            Instruction::Plane(n) => write!(f, "PLANE {}", n),
+            Instruction::Audio => write!(f, "AUDIO"),
+            Instruction::Pitch(x) => write!(f, "PITCH V{:X}", x),
+            Instruction::LoadDelay(x) => write!(f, "LD V{:X}, DT", x),
+            Instruction::WaitKey(x) => write!(f, "LD V{:X}, K", x),
+            Instruction::SetDelay(x) => write!(f, "LD DT, V{:X}", x),
+            Instruction::SetSound(x) => write!(f, "LD ST, V{:X}", x),
+            Instruction::AddI(x) => write!(f, "ADD I, V{:X}", x),
+            Instruction::LoadFont(x) => write!(f, "LD F, V{:X}", x),
+            Instruction::LoadBigFont(x) => write!(f, "LD HF, V{:X}", x),
+            Instruction::StoreBcd(x) => write!(f, "LD B, V{:X}", x),
+            Instruction::StoreRegisters(x) => write!(f, "LD [I], V{:X}", x),
+            Instruction::LoadRegisters(x) => write!(f, "LD V{:X}, [I]", x),
+            Instruction::StoreFlags(x) => write!(f, "LD R, V{:X}", x),
+            Instruction::LoadFlags(x) => write!(f, "LD V{:X}, R", x),
+            // Mega8's mnemonics
+            Instruction::MegaOff => write!(f, "MEGAOFF"),
+            Instruction::MegaOn => write!(f, "MEGAON"),
+            Instruction::ScrollUp(n) => write!(f, "SCU {}", n),
+            Instruction::LoadHighI(kk) => write!(f, "LDHI I, {:#04X}", kk),
+            Instruction::LoadPalette(kk) => write!(f, "LDPAL {}", kk),
+            Instruction::SpriteWidth(kk) => write!(f, "SPRW {}", kk),
+            Instruction::SpriteHeight(kk) => write!(f, "SPRH {}", kk),
+            Instruction::ScreenAlpha(kk) => write!(f, "ALPHA {:#04X}", kk),
+            Instruction::PlaySound(n) => write!(f, "DIGISND {}", n),
+            Instruction::StopSound => write!(f, "STOPSND"),
+            Instruction::CollisionColor(kk) => write!(f, "CCOL {:#04X}", kk),
+        }
+    }
+}
+
+/// Disassembles a single opcode, falling back to a data word for anything undecodable.
+pub fn disassemble(opcode: u16) -> String {
+    disassemble_on(opcode, Platform::SuperChip)
+}
+
+/// `disassemble`, knowing the instructions only `platform` has.
+pub fn disassemble_on(opcode: u16, platform: Platform) -> String {
+    match Instruction::decode_on(opcode, platform) {
+        Some(instruction) => instruction.to_string(),
+        None => format!(".word {:#06X}", opcode),
+    }
+}
+
+// Sweeps the ROM a word at a time, pairing each word's hex with its disassembly.
+fn sweep(rom: &[u8]) -> Vec<(String, String)> {
+    let mut words = Vec::with_capacity(rom.len() / 2 + 1);
+    let mut long_address_next = false;
+    for word in rom.chunks(2) {
+        words.push(match *word {
+            [high, low] => {
+                let opcode = (high as u16) << 8 | low as u16;
+                let text = if long_address_next { format!(".word {:#06X}", opcode) } else { disassemble(opcode) };
+                long_address_next = !long_address_next && Instruction::decode(opcode) == Some(Instruction::LoadLongI);
+                (format!("{:04X}", opcode), text)
+            }
+            [byte] => (format!("{:02X}  ", byte), format!(".byte {:#04X}", byte)),
+            _ => unreachable!("chunks of two"),
+        });
+    }
+    words
+}
+
+/// Disassembles a whole ROM as loaded at `load_address`, one line per instruction, e.g.
+/// `0x200: 00E0  CLS`. It's a straight sweep, so data between the code comes out as instructions
+/// too, except for the address word after `LD I, LONG`.
+pub fn listing(rom: &[u8], load_address: u16) -> Vec<String> {
+    sweep(rom)
+        .into_iter()
+        .enumerate()
+        .map(|(index, (hex, text))| format!("{:#05X}: {}  {}", load_address.wrapping_add(index as u16 * 2), hex, text))
+        .collect()
+}
+
+/// The same sweep as `listing`, as source the assembler turns back into exactly `rom`.
+pub fn source(rom: &[u8]) -> String {
+    sweep(rom).into_iter().map(|(_, text)| text + "\n").collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode() {
+        assert_eq!(Instruction::decode(0x00E0), Some(Instruction::Cls));
+        assert_eq!(Instruction::decode(0x00EE), Some(Instruction::Ret));
+        assert_eq!(Instruction::decode(0x0123), Some(Instruction::Sys(0x123)));
+        assert_eq!(Instruction::decode(0x2A4C), Some(Instruction::Call(0xA4C)));
+        assert_eq!(Instruction::decode(0x8AB6), Some(Instruction::ShiftRight(0xA, 0xB)));
+        assert_eq!(Instruction::decode(0xD125), Some(Instruction::Draw(1, 2, 5)));
+        assert_eq!(Instruction::decode(0xF965), Some(Instruction::LoadRegisters(9)));
+    }
+
+    #[test]
+    fn decode_xochip() {
+        assert_eq!(Instruction::decode(0x5122), Some(Instruction::SaveRange(1, 2)));
+        assert_eq!(Instruction::decode(0x5A33), Some(Instruction::LoadRange(0xA, 3)));
+        assert_eq!(Instruction::decode(0xF000), Some(Instruction::LoadLongI));
+        assert_eq!(Instruction::decode(0xF100), None);
+        assert_eq!(disassemble(0x5122), "SAVE V1 - V2");
+        assert_eq!(disassemble(0x5A33), "LOAD VA - V3");
+        assert_eq!(disassemble(0xF000), "LD I, LONG");
+    }
+
+    #[test]
+    fn decode_megachip() {
+        let decode = |opcode| Instruction::decode_on(opcode, Platform::MegaChip);
+
+        assert_eq!(decode(0x0010), Some(Instruction::MegaOff));
+        assert_eq!(decode(0x0011), Some(Instruction::MegaOn));
+        assert_eq!(decode(0x00B4), Some(Instruction::ScrollUp(4)));
+        assert_eq!(decode(0x0112), Some(Instruction::LoadHighI(0x12)));
+        assert_eq!(decode(0x0210), Some(Instruction::LoadPalette(0x10)));
+        assert_eq!(decode(0x0320), Some(Instruction::SpriteWidth(0x20)));
+        assert_eq!(decode(0x0400), Some(Instruction::SpriteHeight(0)));
+        assert_eq!(decode(0x0580), Some(Instruction::ScreenAlpha(0x80)));
+        assert_eq!(decode(0x0601), Some(Instruction::PlaySound(1)));
+        assert_eq!(decode(0x0700), Some(Instruction::StopSound));
+        assert_eq!(decode(0x0905), Some(Instruction::CollisionColor(5)));
+        // the rest are as elsewhere
+        assert_eq!(decode(0x00E0), Some(Instruction::Cls));
+        assert_eq!(decode(0x0602), Some(Instruction::Sys(0x602)));
+        assert_eq!(decode(0xD125), Some(Instruction::Draw(1, 2, 5)));
+        // and other platforms still see machine-code calls
+        assert_eq!(Instruction::decode_on(0x0210, Platform::XoChip), Some(Instruction::Sys(0x210)));
+        assert_eq!(disassemble(0x0011), "SYS 0x011");
+        assert_eq!(disassemble_on(0x0011, Platform::MegaChip), "MEGAON");
+        assert_eq!(disassemble_on(0x0112, Platform::MegaChip), "LDHI I, 0x12");
+        assert_eq!(disassemble_on(0x0905, Platform::MegaChip), "CCOL 0x05");
+    }
+
+    #[test]
+    fn encode_inverts_decode_on_megachip() {
+        for opcode in 0..=0x0FFF {
+            if let Some(instruction) = Instruction::decode_on(opcode, Platform::MegaChip) {
+                assert_eq!(instruction.encode(), opcode, "{}", instruction);
+            }
+        }
+    }
+
+    #[test]
+    fn decode_rejects_invalid_encodings() {
+        assert_eq!(Instruction::decode(0x5121), None);
+        assert_eq!(Instruction::decode(0x812F), None);
+        assert_eq!(Instruction::decode(0x9128), None);
+        assert_eq!(Instruction::decode(0xE19F), None);
+        assert_eq!(Instruction::decode(0xF14B), None);
+    }
+
+    #[test]
+    fn disassemble_mnemonics() {
+        assert_eq!(disassemble(0x00E0), "CLS");
+        assert_eq!(disassemble(0x00FF), "HIGH");
+        assert_eq!(disassemble(0x00C4), "SCD 4");
+        assert_eq!(disassemble(0x00FB), "SCR");
+        assert_eq!(disassemble(0x00FD), "EXIT");
+        assert_eq!(disassemble(0x12A4), "JP 0x2A4");
+        assert_eq!(disassemble(0x3A1F), "SE VA, 0x1F");
+        assert_eq!(disassemble(0x8124), "ADD V1, V2");
+        assert_eq!(disassemble(0xA050), "LD I, 0x050");
+        assert_eq!(disassemble(0xD01F), "DRW V0, V1, 15");
+        assert_eq!(disassemble(0xF30A), "LD V3, K");
+        assert_eq!(disassemble(0xF301), "PLANE 3");
+        assert_eq!(disassemble(0xF002), "AUDIO");
+        assert_eq!(disassemble(0xF53A), "PITCH V5");
+        assert_eq!(disassemble(0xF430), "LD HF, V4");
+        assert_eq!(disassemble(0xF255), "LD [I], V2");
+        assert_eq!(disassemble(0xF775), "LD R, V7");
+        assert_eq!(disassemble(0xFFFF), ".word 0xFFFF");
+    }
+
+    #[test]
+    fn encode_inverts_decode() {
+        for opcode in 0..=0xFFFF {
+            if let Some(instruction) = Instruction::decode(opcode) {
+                assert_eq!(instruction.encode(), opcode, "{}", instruction);
+            }
+        }
+    }
+
+    #[test]
+    fn listing_covers_the_whole_rom() {
+        let rom = [0x00, 0xE0, 0xF0, 0x00, 0x12, 0x34, 0x12, 0x00, 0xAB];
+
+        assert_eq!(
+            listing(&rom, 0x200),
+            vec![
+                "0x200: 00E0  CLS",
+                "0x202: F000  LD I, LONG",
+                "0x204: 1234  .word 0x1234",
+                "0x206: 1200  JP 0x200",
+                "0x208: AB    .byte 0xAB",
+            ]
+        );
+    }
+
+    #[test]
+    fn vip_draw_costs_scale_with_the_sprite_height() {
+        let draw = |n| Instruction::Draw(0, 1, n).vip_cycles();
+
+        assert_eq!(draw(1), VIP_INSTRUCTION_CYCLES + VIP_SPRITE_ROW_CYCLES);
+        assert_eq!(draw(15) - draw(5), 10 * VIP_SPRITE_ROW_CYCLES);
+        assert_eq!(draw(0), draw(15) + VIP_SPRITE_ROW_CYCLES);
+        assert!(draw(1) > Instruction::AddByte(0, 1).vip_cycles());
+        assert_eq!(Instruction::Jump(0x200).vip_cycles(), VIP_INSTRUCTION_CYCLES);
+    }
+}
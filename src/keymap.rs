@@ -0,0 +1,405 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+
+use serde::Deserialize;
+
+/// Names of the physical keys a keymap can bind, as ggez spells them apart from the digit row,
+/// which is `0` to `9` rather than `Key0` to `Key9`. Function keys, Escape and Backspace stay
+/// with the emulator, as do P and Tab unless a keymap binds them. Names are matched without
+/// regard to case.
+pub const KEY_NAMES: [&str; 79] = [
+    "0", "1", "2", "3", "4", "5", "6", "7", "8", "9",
+    "A", "B", "C", "D", "E", "F", "G", "H", "I", "J", "K", "L", "M",
+    "N", "O", "P", "Q", "R", "S", "T", "U", "V", "W", "X", "Y", "Z",
+    "Up", "Down", "Left", "Right", "Space", "Return", "Tab",
+    "LShift", "RShift", "LControl", "RControl", "LAlt", "RAlt",
+    "Numpad0", "Numpad1", "Numpad2", "Numpad3", "Numpad4", "Numpad5", "Numpad6", "Numpad7", "Numpad8", "Numpad9",
+    "NumpadAdd", "NumpadSubtract", "NumpadMultiply", "NumpadDivide", "NumpadDecimal", "NumpadEnter",
+    "Comma", "Period", "Slash", "Semicolon", "Apostrophe", "LBracket", "RBracket", "Minus", "Equals", "Backslash", "Grave",
+    "Insert", "Delete", "End",
+];
+
+/// Names of the controller buttons a keymap can bind, as gilrs spells them: `South` is A on an
+/// Xbox pad and cross on a PlayStation one. The left stick counts as the D-pad.
+pub const BUTTON_NAMES: [&str; 19] = [
+    "South", "East", "North", "West", "C", "Z",
+    "LeftTrigger", "LeftTrigger2", "RightTrigger", "RightTrigger2",
+    "Select", "Start", "Mode", "LeftThumb", "RightThumb",
+    "DPadUp", "DPadDown", "DPadLeft", "DPadRight",
+];
+
+/// The keypad as laid out on the COSMAC VIP, row by row; the overlay shows bindings in this order
+/// and the on-screen keypad its keys.
+pub const KEYPAD_LAYOUT: [u8; 16] = [0x1, 0x2, 0x3, 0xC, 0x4, 0x5, 0x6, 0xD, 0x7, 0x8, 0x9, 0xE, 0xA, 0x0, 0xB, 0xF];
+
+/// How many presses of keys the keymap leaves unbound, but another layout binds, before
+/// `LayoutHint` suggests that layout.
+const HINT_AFTER: u32 = 3;
+
+/// Keyboard layouts with a preset for the keypad block, so it sits where 1234/QWER/ASDF/ZXCV
+/// does on a QWERTY keyboard whatever the letters on the keys.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum KeyboardLayout {
+    #[default]
+    Qwerty,
+    Azerty,
+    Qwertz,
+    Colemak,
+}
+
+impl KeyboardLayout {
+    pub const ALL: [KeyboardLayout; 4] = [KeyboardLayout::Qwerty, KeyboardLayout::Azerty, KeyboardLayout::Qwertz, KeyboardLayout::Colemak];
+
+    pub fn parse(name: &str) -> Option<KeyboardLayout> {
+        KeyboardLayout::ALL.iter().copied().find(|layout| layout.name().eq_ignore_ascii_case(name))
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            KeyboardLayout::Qwerty => "qwerty",
+            KeyboardLayout::Azerty => "azerty",
+            KeyboardLayout::Qwertz => "qwertz",
+            KeyboardLayout::Colemak => "colemak",
+        }
+    }
+
+    /// The keys of the block, in `KEYPAD_LAYOUT` order.
+    pub fn keys(self) -> [&'static str; 16] {
+        match self {
+            KeyboardLayout::Qwerty => ["1", "2", "3", "4", "Q", "W", "E", "R", "A", "S", "D", "F", "Z", "X", "C", "V"],
+            KeyboardLayout::Azerty => ["1", "2", "3", "4", "A", "Z", "E", "R", "Q", "S", "D", "F", "W", "X", "C", "V"],
+            KeyboardLayout::Qwertz => ["1", "2", "3", "4", "Q", "W", "E", "R", "A", "S", "D", "F", "Y", "X", "C", "V"],
+            KeyboardLayout::Colemak => ["1", "2", "3", "4", "Q", "W", "F", "P", "A", "R", "S", "T", "Z", "X", "C", "V"],
+        }
+    }
+}
+
+/// Why a `[keymap]` or `[gamepad]` table was rejected; `table` says which.
+#[derive(Debug, Clone, PartialEq)]
+pub enum KeymapError {
+    /// The file isn't valid TOML, or a table isn't a table of strings.
+    Toml(String),
+    UnknownKey { table: &'static str, key: String },
+    /// The same key or button appears twice, spelled with different case.
+    DuplicateKey { table: &'static str, key: String },
+    InvalidDigit { table: &'static str, key: String, value: String },
+}
+
+impl fmt::Display for KeymapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KeymapError::Toml(error) => write!(f, "{}", error),
+            KeymapError::UnknownKey { table, key } => write!(f, "unknown name {:?} in [{}]", key, table),
+            KeymapError::DuplicateKey { table, key } => write!(f, "{} is bound more than once in [{}]", key, table),
+            KeymapError::InvalidDigit { table, key, value } => {
+                write!(f, "{} = {:?} in [{}]: expected a single hex digit, 0 to F", key, value, table)
+            }
+        }
+    }
+}
+
+impl Error for KeymapError {}
+
+#[derive(Deserialize)]
+struct ConfigFile {
+    keymap: Option<BTreeMap<String, String>>,
+    gamepad: Option<BTreeMap<String, String>>,
+}
+
+/// Which physical keys and controller buttons press which hex keypad keys. The default keys are
+/// the usual 1234/QWER/ASDF/ZXCV block, or its equivalent for a `KeyboardLayout`, and the default
+/// buttons the D-pad for 2/4/6/8 with South for 5; `[keymap]` and `[gamepad]` tables in
+/// `chip8.toml` replace them, or with a layout preset adjust its keys:
+///
+/// ```toml
+/// [keymap]
+/// Up = "1"
+/// Down = "4"
+///
+/// [gamepad]
+/// DPadUp = "1"
+/// DPadDown = "4"
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Keymap {
+    // canonical key name to keypad value
+    bindings: BTreeMap<&'static str, u8>,
+    // canonical button name to keypad value
+    buttons: BTreeMap<&'static str, u8>,
+}
+
+impl Keymap {
+    /// The keymap in a `chip8.toml` config file, with the defaults for whichever of `[keymap]`
+    /// and `[gamepad]` it doesn't have.
+    pub fn from_config(text: &str) -> Result<Keymap, KeymapError> {
+        let file: ConfigFile = toml::from_str(text).map_err(|error| KeymapError::Toml(error.to_string()))?;
+        let mut keymap = Keymap::default();
+        if let Some(table) = file.keymap {
+            keymap.bindings = parse_table(&table, "keymap", &KEY_NAMES)?;
+        }
+        if let Some(table) = file.gamepad {
+            keymap.buttons = parse_table(&table, "gamepad", &BUTTON_NAMES)?;
+        }
+        Ok(keymap)
+    }
+
+    /// The keymap in a `chip8.toml` config file on top of `layout`'s preset: a `[keymap]` table
+    /// rebinds the keys it names and leaves the preset's others alone, rather than replacing
+    /// them all.
+    pub fn from_config_on(text: &str, layout: KeyboardLayout) -> Result<Keymap, KeymapError> {
+        let file: ConfigFile = toml::from_str(text).map_err(|error| KeymapError::Toml(error.to_string()))?;
+        let mut keymap = Keymap::for_layout(layout);
+        if let Some(table) = file.keymap {
+            keymap.bindings.extend(parse_table(&table, "keymap", &KEY_NAMES)?);
+        }
+        if let Some(table) = file.gamepad {
+            keymap.buttons = parse_table(&table, "gamepad", &BUTTON_NAMES)?;
+        }
+        Ok(keymap)
+    }
+
+    /// The default keymap with `layout`'s keys.
+    pub fn for_layout(layout: KeyboardLayout) -> Keymap {
+        let bindings = layout.keys().iter().zip(KEYPAD_LAYOUT.iter()).map(|(&name, &digit)| (name, digit)).collect();
+        let buttons = [("DPadUp", 0x2), ("DPadLeft", 0x4), ("DPadRight", 0x6), ("DPadDown", 0x8), ("South", 0x5)];
+        Keymap { bindings, buttons: buttons.iter().copied().collect() }
+    }
+
+    /// This keymap with its keys replaced by the bindings in a `[keymap]` table, e.g. one stored
+    /// for a particular ROM.
+    pub fn with_keys(&self, table: &BTreeMap<String, String>) -> Result<Keymap, KeymapError> {
+        Ok(Keymap { bindings: parse_table(table, "keymap", &KEY_NAMES)?, buttons: self.buttons.clone() })
+    }
+
+    /// The keypad key that the physical key called `name` presses, if any.
+    pub fn key(&self, name: &str) -> Option<u8> {
+        canonical_name(name, &KEY_NAMES).and_then(|name| self.bindings.get(name).copied())
+    }
+
+    /// The keypad key that the controller button called `name` presses, if any.
+    pub fn button(&self, name: &str) -> Option<u8> {
+        canonical_name(name, &BUTTON_NAMES).and_then(|name| self.buttons.get(name).copied())
+    }
+
+    /// The bindings for the debug overlay, one keypad row per line: `1:1 2:2 3:3 C:4`, with the
+    /// physical keys after each keypad key and `-` where nothing presses it.
+    pub fn overlay(&self) -> Vec<String> {
+        KEYPAD_LAYOUT
+            .chunks(4)
+            .map(|row| {
+                let cells: Vec<String> = row
+                    .iter()
+                    .map(|&digit| {
+                        let names: Vec<&str> = self.bindings.iter().filter(|&(_, &bound)| bound == digit).map(|(&name, _)| name).collect();
+                        let names = if names.is_empty() { String::from("-") } else { names.join("/") };
+                        format!("{:X}:{}", digit, names)
+                    })
+                    .collect();
+                cells.join(" ")
+            })
+            .collect()
+    }
+}
+
+impl Default for Keymap {
+    fn default() -> Keymap {
+        Keymap::for_layout(KeyboardLayout::Qwerty)
+    }
+}
+
+/// Notices when the keys being pressed suggest the keyboard isn't laid out the way the keymap
+/// assumes: keys it leaves unbound that another layout's keypad block uses, like Y for QWERTZ.
+/// AZERTY's block has the same letters as QWERTY's, so it can't be told apart this way.
+#[derive(Debug, Default)]
+pub struct LayoutHint {
+    misses: BTreeMap<KeyboardLayout, u32>,
+    hinted: bool,
+}
+
+impl LayoutHint {
+    /// Notes a press of the key called `name`, and after a few that point to the same layout
+    /// returns a suggestion to switch to it, once.
+    pub fn key_pressed(&mut self, keymap: &Keymap, name: &str) -> Option<String> {
+        if self.hinted || keymap.key(name).is_some() {
+            return None;
+        }
+        let layout = KeyboardLayout::ALL.iter().copied().find(|layout| layout.keys().iter().any(|key| key.eq_ignore_ascii_case(name)))?;
+        let misses = self.misses.entry(layout).or_insert(0);
+        *misses += 1;
+        if *misses < HINT_AFTER {
+            return None;
+        }
+        self.hinted = true;
+        Some(format!("{} isn't bound to the keypad, but the {} layout binds it; if that's your keyboard, try --kb-layout {}", name, layout.name(), layout.name()))
+    }
+}
+
+/// The name a keymap uses for `keycode`: its ggez name, except the digit row's `Key1` is `1`.
+#[cfg(feature = "frontend-ggez")]
+pub fn keycode_name(keycode: ggez::event::KeyCode) -> String {
+    let name = format!("{:?}", keycode);
+    match name.strip_prefix("Key") {
+        Some(digit) if digit.len() == 1 => String::from(digit),
+        _ => name,
+    }
+}
+
+/// Reads the bindings in `table`, whose names have to be among `names`.
+fn parse_table(table: &BTreeMap<String, String>, table_name: &'static str, names: &[&'static str]) -> Result<BTreeMap<&'static str, u8>, KeymapError> {
+    let mut bindings = BTreeMap::new();
+    for (key, value) in table {
+        let name = canonical_name(key, names).ok_or_else(|| KeymapError::UnknownKey { table: table_name, key: key.clone() })?;
+        let digit = parse_digit(value).ok_or_else(|| KeymapError::InvalidDigit { table: table_name, key: key.clone(), value: value.clone() })?;
+        if bindings.insert(name, digit).is_some() {
+            return Err(KeymapError::DuplicateKey { table: table_name, key: String::from(name) });
+        }
+    }
+    Ok(bindings)
+}
+
+fn canonical_name(name: &str, names: &[&'static str]) -> Option<&'static str> {
+    names.iter().copied().find(|known| known.eq_ignore_ascii_case(name))
+}
+
+fn parse_digit(value: &str) -> Option<u8> {
+    let value = value.trim();
+    if value.len() != 1 {
+        return None;
+    }
+    u8::from_str_radix(value, 16).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keymap_table_replaces_the_default_layout() {
+        let keymap = Keymap::from_config("[keymap]\nUp = \"1\"\ndown = \"4\"\nnumpad5 = \"c\"\n").unwrap();
+
+        assert_eq!(keymap.key("Up"), Some(0x1));
+        assert_eq!(keymap.key("Down"), Some(0x4));
+        assert_eq!(keymap.key("Numpad5"), Some(0xC));
+        assert_eq!(keymap.key("1"), None);
+        assert_eq!(Keymap::from_config("# no keymap here\n").unwrap(), Keymap::default());
+    }
+
+    #[test]
+    fn errors_name_the_offending_entry() {
+        assert_eq!(
+            Keymap::from_config("[keymap]\nJoystick = \"5\"\n"),
+            Err(KeymapError::UnknownKey { table: "keymap", key: String::from("Joystick") })
+        );
+        assert_eq!(
+            Keymap::from_config("[keymap]\nA = \"7\"\na = \"8\"\n"),
+            Err(KeymapError::DuplicateKey { table: "keymap", key: String::from("A") })
+        );
+        assert_eq!(
+            Keymap::from_config("[keymap]\nUp = \"G\"\n"),
+            Err(KeymapError::InvalidDigit { table: "keymap", key: String::from("Up"), value: String::from("G") })
+        );
+        assert!(matches!(Keymap::from_config("[keymap]\nUp = \"10\"\n"), Err(KeymapError::InvalidDigit { .. })));
+        assert!(matches!(Keymap::from_config("[keymap\n"), Err(KeymapError::Toml(_))));
+        assert_eq!(
+            KeymapError::InvalidDigit { table: "keymap", key: String::from("Up"), value: String::from("G") }.to_string(),
+            "Up = \"G\" in [keymap]: expected a single hex digit, 0 to F"
+        );
+    }
+
+    #[test]
+    fn gamepad_table_replaces_the_default_buttons() {
+        assert_eq!(Keymap::default().button("DPadLeft"), Some(0x4));
+        assert_eq!(Keymap::default().button("South"), Some(0x5));
+
+        let keymap = Keymap::from_config("[gamepad]\ndpadup = \"1\"\nEast = \"C\"\n").unwrap();
+
+        assert_eq!(keymap.button("DPadUp"), Some(0x1));
+        assert_eq!(keymap.button("East"), Some(0xC));
+        assert_eq!(keymap.button("South"), None);
+        // the keyboard keeps its default layout
+        assert_eq!(keymap.key("Q"), Some(0x4));
+        assert_eq!(
+            Keymap::from_config("[gamepad]\nUp = \"2\"\n"),
+            Err(KeymapError::UnknownKey { table: "gamepad", key: String::from("Up") })
+        );
+    }
+
+    #[test]
+    fn key_events_press_the_mapped_keypad_keys() {
+        let keymap = Keymap::from_config("[keymap]\nUp = \"2\"\nW = \"2\"\nSpace = \"5\"\n").unwrap();
+        let mut keys = crate::Keys::new();
+
+        for name in ["W", "Space", "Escape"] {
+            if let Some(key) = keymap.key(name) {
+                keys.press(key);
+            }
+        }
+
+        assert!(keys.is_pressed(0x2));
+        assert!(keys.is_pressed(0x5));
+        assert_eq!(Keymap::default().key("v"), Some(0xF));
+        assert_eq!(keymap.overlay()[0], "1:- 2:Up/W 3:- C:-");
+    }
+
+    #[cfg(feature = "frontend-ggez")]
+    #[test]
+    fn each_preset_puts_the_keypad_under_the_same_fingers() {
+        use ggez::event::KeyCode;
+
+        let key = |layout, keycode| Keymap::for_layout(layout).key(&keycode_name(keycode));
+
+        for &layout in &KeyboardLayout::ALL {
+            assert_eq!(key(layout, KeyCode::Key1), Some(0x1), "{:?}", layout);
+            assert_eq!(key(layout, KeyCode::Key4), Some(0xC), "{:?}", layout);
+            assert_eq!(key(layout, KeyCode::X), Some(0x0), "{:?}", layout);
+            assert_eq!(key(layout, KeyCode::V), Some(0xF), "{:?}", layout);
+        }
+        assert_eq!(key(KeyboardLayout::Qwerty, KeyCode::Q), Some(0x4));
+        assert_eq!(key(KeyboardLayout::Qwerty, KeyCode::Z), Some(0xA));
+        assert_eq!(key(KeyboardLayout::Azerty, KeyCode::A), Some(0x4));
+        assert_eq!(key(KeyboardLayout::Azerty, KeyCode::Z), Some(0x5));
+        assert_eq!(key(KeyboardLayout::Azerty, KeyCode::Q), Some(0x7));
+        assert_eq!(key(KeyboardLayout::Azerty, KeyCode::W), Some(0xA));
+        assert_eq!(key(KeyboardLayout::Qwertz, KeyCode::Y), Some(0xA));
+        assert_eq!(key(KeyboardLayout::Qwertz, KeyCode::Z), None);
+        assert_eq!(key(KeyboardLayout::Colemak, KeyCode::F), Some(0x6));
+        assert_eq!(key(KeyboardLayout::Colemak, KeyCode::P), Some(0xD));
+        assert_eq!(key(KeyboardLayout::Colemak, KeyCode::T), Some(0xE));
+        assert_eq!(key(KeyboardLayout::Colemak, KeyCode::E), None);
+        assert_eq!(Keymap::for_layout(KeyboardLayout::Qwerty), Keymap::default());
+        assert_eq!(KeyboardLayout::parse("QWERTZ"), Some(KeyboardLayout::Qwertz));
+        assert_eq!(KeyboardLayout::parse("dvorak"), None);
+    }
+
+    #[test]
+    fn keymap_entries_win_over_the_preset_they_sit_on() {
+        let keymap = Keymap::from_config_on("[keymap]
+Space = \"5\"
+W = \"C\"
+", KeyboardLayout::Azerty).unwrap();
+
+        assert_eq!(keymap.key("Space"), Some(0x5));
+        assert_eq!(keymap.key("W"), Some(0xC));
+        // the preset's other keys stay bound
+        assert_eq!(keymap.key("Z"), Some(0x5));
+        assert_eq!(keymap.key("A"), Some(0x4));
+        assert_eq!(Keymap::from_config_on("", KeyboardLayout::Colemak).unwrap(), Keymap::for_layout(KeyboardLayout::Colemak));
+        assert!(matches!(Keymap::from_config_on("[keymap]\nUp = \"G\"\n", KeyboardLayout::Qwertz), Err(KeymapError::InvalidDigit { .. })));
+    }
+
+    #[test]
+    fn unbound_keys_another_layout_uses_suggest_it_once() {
+        let keymap = Keymap::default();
+        let mut hint = LayoutHint::default();
+
+        // bound keys and keys no preset uses say nothing
+        for name in ["Q", "Escape", "Y", "Q", "Y"] {
+            assert_eq!(hint.key_pressed(&keymap, name), None);
+        }
+        let suggestion = hint.key_pressed(&keymap, "Y").unwrap();
+        assert!(suggestion.contains("--kb-layout qwertz"), "{}", suggestion);
+        assert_eq!(hint.key_pressed(&keymap, "Y"), None);
+        assert_eq!(hint.key_pressed(&keymap, "P"), None);
+    }
+}
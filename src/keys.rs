@@ -0,0 +1,116 @@
+/// The keypad. Besides which keys are held, it latches every key pressed since the last frame
+/// ended, so a tap that's pressed and released between two EX9Es still counts. The latches are
+/// only cleared when a frame completes: a tap while paused, or between single steps, is there for
+/// the next instruction that looks.
+pub struct Keys {
+    pub(crate) keys: [bool; 16],
+    latched: [bool; 16],
+}
+
+impl Keys {
+    pub fn new() -> Keys {
+        Keys {
+            keys: [false; 16],
+            latched: [false; 16],
+        }
+    }
+
+    /// Whether `key` is held down; there are no keys above 0xF, so those never are.
+    pub fn is_pressed(&self, key: u8) -> bool {
+        self.get(key).unwrap_or(false)
+    }
+
+    /// Whether `key` is held down, or `None` if there's no such key.
+    pub fn get(&self, key: u8) -> Option<bool> {
+        self.keys.get(key as usize).copied()
+    }
+
+    /// Returns the lowest key that is currently held down.
+    pub fn any_pressed(&self) -> Option<u8> {
+        self.keys.iter().position(|&pressed| pressed).map(|key| key as u8)
+    }
+
+    /// Whether `key` is held down or was pressed at some point since the last frame ended.
+    pub fn was_pressed(&self, key: u8) -> bool {
+        self.is_pressed(key) || self.latched.get(key as usize).copied().unwrap_or(false)
+    }
+
+    /// The lowest key that `was_pressed`.
+    pub fn any_was_pressed(&self) -> Option<u8> {
+        (0..16).find(|&key| self.was_pressed(key))
+    }
+
+    /// Forgets that `key` was pressed this frame, once FX0A has taken the press, so one tap
+    /// doesn't satisfy two waits. A key still held stays pressed.
+    pub fn take_latch(&mut self, key: u8) {
+        if let Some(latched) = self.latched.get_mut(key as usize) {
+            *latched = false;
+        }
+    }
+
+    /// Starts a new frame: only the keys still held count as pressed in it.
+    pub fn end_frame(&mut self) {
+        self.latched = self.keys;
+    }
+
+    /// Holds `key` down; keys above 0xF are ignored.
+    pub fn press(&mut self, key: u8) {
+        if let Some(pressed) = self.keys.get_mut(key as usize) {
+            *pressed = true;
+            self.latched[key as usize] = true;
+        }
+    }
+
+    /// Lets go of `key`; keys above 0xF are ignored.
+    pub fn release(&mut self, key: u8) {
+        if let Some(pressed) = self.keys.get_mut(key as usize) {
+            *pressed = false;
+        }
+    }
+}
+
+impl Default for Keys {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys_above_0xf_are_never_pressed() {
+        let mut keys = Keys::new();
+        keys.press(0xF);
+        keys.press(0x1F);
+        keys.release(0xFF);
+
+        assert_eq!(keys.get(0xF), Some(true));
+        assert_eq!(keys.get(0x10), None);
+        assert!(!keys.is_pressed(0x1F));
+        assert_eq!(keys.any_pressed(), Some(0xF));
+        assert!(!keys.was_pressed(0x1F));
+    }
+
+    #[test]
+    fn taps_are_latched_until_the_frame_ends() {
+        let mut keys = Keys::new();
+        keys.press(0x5);
+        keys.release(0x5);
+        keys.press(0x9);
+
+        assert!(!keys.is_pressed(0x5));
+        assert!(keys.was_pressed(0x5));
+        assert_eq!(keys.any_was_pressed(), Some(0x5));
+
+        keys.end_frame();
+        assert!(!keys.was_pressed(0x5));
+        assert!(keys.was_pressed(0x9));
+
+        keys.take_latch(0x9);
+        assert!(keys.was_pressed(0x9), "a held key stays pressed");
+        keys.release(0x9);
+        assert_eq!(keys.any_was_pressed(), None);
+    }
+}
@@ -0,0 +1,208 @@
+use std::collections::BTreeMap;
+
+use crate::analysis;
+use crate::instruction::{self, Instruction};
+
+// data that isn't drawn as sprites goes this many bytes to a `.byte` line
+const BYTES_PER_LINE: usize = 8;
+
+/// What a labelled address is used as; when it's used several ways the greatest wins.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum LabelKind {
+    /// Pointed at by LD I.
+    Data,
+    /// Jumped to by JP, or the base of a JP V0 table.
+    Jump,
+    /// Called by CALL.
+    Subroutine,
+}
+
+fn label_name(kind: LabelKind, address: usize) -> String {
+    match kind {
+        LabelKind::Data => format!("DATA_{:04X}", address),
+        LabelKind::Jump => format!("L_{:04X}", address),
+        LabelKind::Subroutine => format!("SUB_{:04X}", address),
+    }
+}
+
+/// Disassembles `rom`, loaded at `load_address`, into source that `assembler::assemble` turns back
+/// into exactly `rom`. Code is found by following every path from the first instruction, and from
+/// the base of each JP V0 table, the way `check` does. Jump and call targets and what LD I points
+/// at get labels such as `L_0228`, `SUB_030C` and `DATA_0250`, which the instructions refer to.
+/// Bytes no path reaches are `.byte` data; with `sprites`, data that LD I points at is written a
+/// byte to a line with its pixels in a comment.
+pub fn labelled_source(rom: &[u8], load_address: u16, sprites: bool) -> String {
+    let starts = instruction_starts(rom, load_address);
+    let labels = labels(rom, load_address, &starts);
+    let base = load_address as usize;
+    let label = |offset: usize| labels.get(&offset).map(|&kind| label_name(kind, base + offset));
+
+    let mut lines = Vec::new();
+    let mut offset = 0;
+    while offset < rom.len() {
+        if let Some(label) = label(offset) {
+            lines.push(format!("{}:", label));
+        }
+        let length = instruction_length(rom, offset);
+        // an instruction that overlaps another, or a label, can only be written as data
+        let overlapped = (offset + 1..offset + length).any(|inner| starts[inner] || labels.contains_key(&inner));
+        if starts[offset] && !overlapped {
+            let opcode = u16::from_be_bytes([rom[offset], rom[offset + 1]]);
+            let text = match Instruction::decode(opcode) {
+                Some(Instruction::LoadLongI) if length == 4 => {
+                    let target = u16::from_be_bytes([rom[offset + 2], rom[offset + 3]]) as usize;
+                    format!("LD I, LONG {}", symbol(target, base, &label))
+                }
+                Some(instruction) => labelled(instruction, base, &label),
+                None => instruction::disassemble(opcode),
+            };
+            lines.push(format!("    {}", text));
+            offset += length;
+            continue;
+        }
+
+        let mut end = offset + 1;
+        while end < rom.len() && !starts[end] && !labels.contains_key(&end) {
+            end += 1;
+        }
+        let data = &rom[offset..end];
+        if sprites && labels.get(&offset) == Some(&LabelKind::Data) {
+            lines.extend(data.iter().map(|byte| format!("    .byte {:#04X}  ; {}", byte, pixels(*byte))));
+        } else {
+            for run in data.chunks(BYTES_PER_LINE) {
+                let bytes: Vec<String> = run.iter().map(|byte| format!("{:#04X}", byte)).collect();
+                lines.push(format!("    .byte {}", bytes.join(", ")));
+            }
+        }
+        offset = end;
+    }
+    lines.into_iter().map(|line| line + "\n").collect()
+}
+
+// the walk from the first instruction, widened to the JP V0 tables it finds until there are no new ones
+fn instruction_starts(rom: &[u8], load_address: u16) -> Vec<bool> {
+    let mut entries = vec![load_address];
+    loop {
+        let starts = analysis::instruction_starts(rom, load_address, &entries);
+        let tables: Vec<u16> = (0..rom.len())
+            .filter(|&offset| starts[offset])
+            .filter_map(|offset| match Instruction::decode(u16::from_be_bytes([rom[offset], rom[offset + 1]])) {
+                Some(Instruction::JumpV0(base)) if !entries.contains(&base) => Some(base),
+                _ => None,
+            })
+            .collect();
+        // a table outside the ROM adds nothing, so stop once every one found has been tried
+        let before = entries.len();
+        entries.extend(tables);
+        entries.sort_unstable();
+        entries.dedup();
+        if entries.len() == before {
+            return starts;
+        }
+    }
+}
+
+// F000 takes the address word after it along, if the ROM has one
+fn instruction_length(rom: &[u8], offset: usize) -> usize {
+    if rom[offset..].starts_with(&[0xF0, 0x00]) && offset + 4 <= rom.len() {
+        4
+    } else {
+        2.min(rom.len() - offset)
+    }
+}
+
+// the labels every reached instruction refers to, by offset into the ROM
+fn labels(rom: &[u8], load_address: u16, starts: &[bool]) -> BTreeMap<usize, LabelKind> {
+    let base = load_address as usize;
+    let mut labels = BTreeMap::new();
+    for offset in (0..rom.len()).filter(|&offset| starts[offset]) {
+        let opcode = u16::from_be_bytes([rom[offset], rom[offset + 1]]);
+        let (target, kind) = match Instruction::decode(opcode) {
+            Some(Instruction::Jump(target)) | Some(Instruction::JumpV0(target)) => (target as usize, LabelKind::Jump),
+            Some(Instruction::Call(target)) => (target as usize, LabelKind::Subroutine),
+            Some(Instruction::LoadI(target)) => (target as usize, LabelKind::Data),
+            Some(Instruction::LoadLongI) if instruction_length(rom, offset) == 4 => {
+                (u16::from_be_bytes([rom[offset + 2], rom[offset + 3]]) as usize, LabelKind::Data)
+            }
+            _ => continue,
+        };
+        if target >= base && target < base + rom.len() {
+            let label = labels.entry(target - base).or_insert(kind);
+            *label = (*label).max(kind);
+        }
+    }
+    labels
+}
+
+// `target` by its label, or as a number if it has none
+fn symbol(target: usize, base: usize, label: &impl Fn(usize) -> Option<String>) -> String {
+    target.checked_sub(base).and_then(label).unwrap_or_else(|| format!("{:#05X}", target))
+}
+
+fn labelled(instruction: Instruction, base: usize, label: &impl Fn(usize) -> Option<String>) -> String {
+    match instruction {
+        Instruction::Jump(target) => format!("JP {}", symbol(target as usize, base, label)),
+        Instruction::JumpV0(target) => format!("JP V0, {}", symbol(target as usize, base, label)),
+        Instruction::Call(target) => format!("CALL {}", symbol(target as usize, base, label)),
+        Instruction::LoadI(target) => format!("LD I, {}", symbol(target as usize, base, label)),
+        instruction => instruction.to_string(),
+    }
+}
+
+// a sprite row, `#` for each set bit
+fn pixels(byte: u8) -> String {
+    (0..8).map(|bit| if byte & (0x80 >> bit) != 0 { '#' } else { '.' }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assembler;
+
+    fn assemble(opcodes: &[u16]) -> Vec<u8> {
+        opcodes.iter().flat_map(|opcode| opcode.to_be_bytes().to_vec()).collect()
+    }
+
+    // CALL 0x20A, forward; LD I, 0x208; DRW V0, V1, 2; JP 0x200, backward; a two-row sprite;
+    // LD V0, 5; RET
+    const ROM: [u16; 7] = [0x220A, 0xA208, 0xD012, 0x1200, 0xF090, 0x6005, 0x00EE];
+
+    #[test]
+    fn targets_get_labels_and_unreached_bytes_are_data() {
+        let rom = assemble(&ROM);
+
+        let source = labelled_source(&rom, 0x200, false);
+
+        assert_eq!(
+            source,
+            "L_0200:\n    CALL SUB_020A\n    LD I, DATA_0208\n    DRW V0, V1, 2\n    JP L_0200\nDATA_0208:\n    .byte 0xF0, 0x90\nSUB_020A:\n    LD V0, 0x05\n    RET\n"
+        );
+        assert_eq!(assembler::assemble(&source, 0x200).unwrap(), rom);
+    }
+
+    #[test]
+    fn sprites_can_be_drawn_in_comments() {
+        let rom = assemble(&ROM);
+
+        let source = labelled_source(&rom, 0x200, true);
+
+        assert!(source.contains("DATA_0208:\n    .byte 0xF0  ; ####....\n    .byte 0x90  ; #..#....\nSUB_020A:\n"), "{}", source);
+        assert_eq!(assembler::assemble(&source, 0x200).unwrap(), rom);
+    }
+
+    #[test]
+    fn jump_tables_long_loads_and_overlaps_still_assemble_back() {
+        // at 0x600: LD I, LONG 0x60E; JP V0, 0x608; unreached bytes; the table, whose first entry
+        // jumps to 0x60D; JP 0x20D there, out of the ROM, overlapping the data at 0x60E
+        let rom = assemble(&[0xF000, 0x060E, 0xB608, 0x1606, 0x160D, 0x160C, 0x0012, 0x0D78]);
+
+        let source = labelled_source(&rom, 0x600, false);
+
+        assert_eq!(
+            source,
+            "    LD I, LONG DATA_060E\n    JP V0, L_0608\n    .byte 0x16, 0x06\nL_0608:\n    JP L_060D\n    .byte 0x16, 0x0C, 0x00\nL_060D:\n    .byte 0x12\nDATA_060E:\n    .byte 0x0D, 0x78\n"
+        );
+        assert_eq!(assembler::assemble(&source, 0x600).unwrap(), rom);
+        assert_eq!(labelled_source(&[0x00], 0x200, false), "    .byte 0x00\n");
+    }
+}
@@ -0,0 +1,148 @@
+//! A CHIP-8, SUPER-CHIP, XO-CHIP and MEGA-CHIP interpreter, with the desktop frontend and its
+//! tools built on top.
+//!
+//! Without the default `std` feature only the interpreter core builds, as `no_std` with `alloc`:
+//! `Cpu`, `Memory`, `Display`, `Keys`, the instruction decoder and the hooks, which is enough to
+//! step a ROM on a microcontroller and draw its screen. What needs files, the clock or the OS goes
+//! away with `std`: save states (`Cpu::save_state` and `Cpu::load_state`), reading and writing
+//! RPL flag files, the cheat and settings parsers, the sound synthesiser, the text formats of the
+//! display, and every module from `analysis` to `timing` that isn't listed above. A new `Cpu`
+//! starts its CXKK generator from a fixed seed there; call `Cpu::seed` or
+//! `Cpu::set_random_source` with something better. The serde derives on the core's types, such
+//! as `Registers` and `Quirks`, come with the `serde` feature, which `std` turns on and which
+//! also builds without it.
+//!
+//! The core isn't built from fixed-size buffers alone, so the target needs a global allocator.
+//! Memory is a `Vec` because its size follows the platform, from 4 KiB up to XO-CHIP's 64 KiB
+//! and MEGA-CHIP's 16 MiB, and MEGA-CHIP's screen is allocated when 0011 enters it; the random
+//! source and the hooks are boxed trait objects. Those allocations happen when a `Cpu` is built
+//! or reconfigured. Once a CHIP-8, SUPER-CHIP or XO-CHIP ROM is loaded, stepping it, drawing and
+//! taking `Cpu::state_hash` don't allocate, except that `IllegalOpcodes::WarnOnce` remembers
+//! each opcode it has warned about.
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+extern crate alloc;
+
+#[cfg(feature = "std")]
+pub mod analysis;
+#[cfg(feature = "std")]
+pub mod assembler;
+pub mod audio;
+#[cfg(feature = "std")]
+pub mod autosave;
+#[cfg(feature = "std")]
+mod base64;
+#[cfg(feature = "std")]
+pub mod builtin;
+#[cfg(feature = "std")]
+pub mod c8b;
+pub mod cheats;
+#[cfg(feature = "std")]
+pub mod cli;
+#[cfg(feature = "std")]
+pub mod compare;
+#[cfg(feature = "std")]
+pub mod condition;
+#[cfg(feature = "std")]
+pub mod configfile;
+#[cfg(feature = "std")]
+pub mod coredump;
+pub mod coverage;
+mod cpu;
+#[cfg(feature = "std")]
+pub mod debugger;
+mod display;
+pub mod edit;
+#[cfg(feature = "std")]
+pub mod emulation;
+mod error;
+mod font;
+#[cfg(feature = "std")]
+pub mod frontend;
+#[cfg(feature = "std")]
+pub mod gamepad;
+#[cfg(feature = "std")]
+pub mod halfblock;
+mod hash;
+#[cfg(feature = "std")]
+pub mod headless;
+pub mod heatmap;
+pub mod history;
+mod hooks;
+#[cfg(feature = "std")]
+pub mod hexview;
+pub mod instruction;
+#[cfg(feature = "std")]
+pub mod keymap;
+mod keys;
+#[cfg(feature = "std")]
+pub mod labels;
+mod memory;
+pub mod megachip;
+#[cfg(feature = "std")]
+pub mod palette;
+#[cfg(feature = "std")]
+pub mod pausemenu;
+#[cfg(feature = "std")]
+pub mod phosphor;
+pub mod profiler;
+mod quirks;
+mod random;
+#[cfg(feature = "std")]
+pub mod recent;
+mod registers;
+#[cfg(feature = "std")]
+pub mod render;
+#[cfg(feature = "std")]
+pub mod replay;
+#[cfg(feature = "std")]
+pub mod rewind;
+#[cfg(feature = "std")]
+pub mod romfile;
+#[cfg(feature = "std")]
+pub mod rompicker;
+#[cfg(feature = "std")]
+pub mod romwatch;
+pub mod rplflags;
+#[cfg(feature = "std")]
+mod savestate;
+#[cfg(feature = "std")]
+pub mod screenshot;
+#[cfg(feature = "scripting")]
+pub mod script;
+#[cfg(feature = "std")]
+pub mod settings;
+#[cfg(feature = "std")]
+pub mod speed;
+pub mod spriteview;
+#[cfg(feature = "std")]
+mod statejson;
+#[cfg(feature = "frontend-terminal")]
+pub mod terminal;
+#[cfg(feature = "std")]
+pub mod testsuite;
+#[cfg(feature = "std")]
+pub mod timing;
+
+pub use cpu::{Cpu, Flow, Halt, SkippedCalls, StateDigest, CYCLES_PER_FRAME, DEFAULT_LOAD_ADDRESS, ETI_660_LOAD_ADDRESS};
+pub use display::{Display, Rect, Resolution, PLANE_1, PLANE_2};
+pub use error::{Chip8Error, IllegalOpcodes, RomError, RomTooLarge};
+pub use font::{Font, FontMap, InvalidFontSize, DEFAULT_FONT_BASE};
+pub use hooks::{EmulatorHooks, TraceLog};
+pub use keys::Keys;
+pub use memory::{AccessKind, Memory, MemoryAccess, WatchMode, Watchpoint};
+pub use quirks::{detect_hires_chip8, BigFontDigits, LoresBigSprite, MachineCodeCalls, NibbleOperands, Platform, Quirks, PROFILES};
+pub use random::RandomSource;
+pub use registers::{Registers, VF};
+#[cfg(feature = "std")]
+pub use savestate::{SaveState, SaveStateError};
+#[cfg(feature = "std")]
+pub use statejson::STATE_JSON_VERSION;
+
+// what the std prelude has and a no_std build has to take from `alloc`, for the core's modules
+mod prelude {
+    pub use alloc::boxed::Box;
+    pub use alloc::string::{String, ToString};
+    pub use alloc::vec::Vec;
+    pub use alloc::{format, vec};
+}
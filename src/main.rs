@@ -1,856 +1,758 @@
-use std::env;
-use std::fs::File;
-use std::io::Read;
-use std::ops::{Index, IndexMut};
-
-use ggez::{Context, ContextBuilder, event, GameError, GameResult};
-use ggez::conf::{WindowMode, WindowSetup};
-use ggez::event::EventHandler;
-use ggez::graphics;
-use ggez::graphics::{Color, DrawParam};
-use rand::Rng;
+#[macro_use]
+extern crate gfx;
 
-#[derive(Default)]
-struct Registers {
-    v0: u8,
-    v1: u8,
-    v2: u8,
-    v3: u8,
-    v4: u8,
-    v5: u8,
-    v6: u8,
-    v7: u8,
-    v8: u8,
-    v9: u8,
-    va: u8,
-    vb: u8,
-    vc: u8,
-    vd: u8,
-    ve: u8,
-    vf: u8, // carry flag
+use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::{Duration, Instant, SystemTime};
+
+use ggez::GameResult;
+use log::{info, warn};
+
+use chip_8_emulator::{detect_hires_chip8, Cpu, Display, Memory, Quirks, SaveState, SaveStateError, DEFAULT_LOAD_ADDRESS};
+#[cfg(feature = "frontend-terminal")]
+use chip_8_emulator::CYCLES_PER_FRAME;
+use chip_8_emulator::analysis;
+use chip_8_emulator::assembler;
+use chip_8_emulator::builtin;
+use chip_8_emulator::compare;
+use chip_8_emulator::c8b::{self, Container};
+use chip_8_emulator::coredump::{self, CoreDump};
+use chip_8_emulator::coverage::{self, Coverage};
+use chip_8_emulator::cli::{self, Config, Invocation};
+use chip_8_emulator::configfile::{ConfigFile, FileSettings};
+use chip_8_emulator::headless;
+use chip_8_emulator::instruction;
+use chip_8_emulator::keymap::Keymap;
+use chip_8_emulator::labels;
+use chip_8_emulator::palette::{self, Palette};
+use chip_8_emulator::profiler;
+use chip_8_emulator::replay::{self, Player, Recorder, ReplayError};
+use chip_8_emulator::romfile::{self, RomFormat};
+use chip_8_emulator::rplflags::{self, RPL_FLAGS};
+use chip_8_emulator::screenshot;
+#[cfg(feature = "scripting")]
+use chip_8_emulator::script::{self, Script, ScriptError};
+use chip_8_emulator::settings::{self, RomSettings, SettingsDatabase};
+use chip_8_emulator::speed;
+#[cfg(feature = "frontend-terminal")]
+use chip_8_emulator::terminal::{self, TerminalError};
+use chip_8_emulator::testsuite::{self, CaseReport, Manifest, Verdict};
+
+mod frontends {
+    pub mod ggez;
 }
 
-impl Index<u8> for Registers {
-    type Output = u8;
+const PROFILE_REPORT_ROWS: usize = 10;
+// what --plane-colors or a ROM's stored colours add to the palettes and select
+const CUSTOM_PALETTE_NAME: &str = "Custom";
+const CONFIG_FILE_NAME: &str = "chip8.toml";
+// the per-ROM settings database, in the config directory
+const SETTINGS_FILE_NAME: &str = "roms.toml";
 
-    fn index(&self, index: u8) -> &Self::Output {
-        match index {
-            0 => &self.v0,
-            1 => &self.v1,
-            2 => &self.v2,
-            3 => &self.v3,
-            4 => &self.v4,
-            5 => &self.v5,
-            6 => &self.v6,
-            7 => &self.v7,
-            8 => &self.v8,
-            9 => &self.v9,
-            0xA => &self.va,
-            0xB => &self.vb,
-            0xC => &self.vc,
-            0xD => &self.vd,
-            0xE => &self.ve,
-            0xF => &self.vf,
-            _ => panic!("Unsupported register"),
-        }
-    }
-}
-
-impl IndexMut<u8> for Registers {
-    fn index_mut(&mut self, index: u8) -> &mut Self::Output {
-        match index {
-            0 => &mut self.v0,
-            1 => &mut self.v1,
-            2 => &mut self.v2,
-            3 => &mut self.v3,
-            4 => &mut self.v4,
-            5 => &mut self.v5,
-            6 => &mut self.v6,
-            7 => &mut self.v7,
-            8 => &mut self.v8,
-            9 => &mut self.v9,
-            0xA => &mut self.va,
-            0xB => &mut self.vb,
-            0xC => &mut self.vc,
-            0xD => &mut self.vd,
-            0xE => &mut self.ve,
-            0xF => &mut self.vf,
-            _ => panic!("Unsupported register"),
-        }
-    }
-}
-
-struct Memory {
-    memory: [u8; 0xFFF],
-}
-
-impl Memory {
-    fn new() -> Memory {
-        Memory {
-            memory: [0; 0xFFF],
-        }
-    }
-
-    fn read_u8(&mut self, location: u16) -> u8 {
-        self.memory[location as usize]
+/// Prints the opcode profile when `--profile-opcodes` is on; does nothing otherwise.
+fn print_profile(cpu: &Cpu, elapsed: Duration) {
+    if let Some(profile) = cpu.profile() {
+        eprint!("{}", profiler::report(profile, elapsed, PROFILE_REPORT_ROWS));
     }
+}
 
-    fn read_u16(&mut self, location: u16) -> u8 {
-        self.memory[location as usize]
+/// Writes the coverage report to `path` when `--coverage` is on; does nothing otherwise.
+fn write_coverage(cpu: &Cpu, path: Option<&Path>) {
+    if let (Some(coverage), Some(path)) = (cpu.coverage(), path) {
+        let report = coverage::report(coverage);
+        match fs::write(path, report.to_string()) {
+            Ok(()) => info!("{:.1}% of the ROM ran; the coverage report is in {}", report.percent(), path.display()),
+            Err(error) => eprintln!("Problem writing {}: {}", path.display(), error),
+        }
     }
+}
 
-    fn write_u8(&mut self, location: u16, value: u8) {
-        self.memory[location as usize] = value;
+/// Loads the JSON save state at `path` into `cpu`; with `force`, even one saved from another ROM.
+fn import_state(cpu: &mut Cpu, path: &Path, force: bool) -> Result<(), SaveStateError> {
+    let mut state = SaveState::read_json_from(path)?;
+    if force {
+        state = state.with_rom_hash(cpu.rom_hash());
     }
+    cpu.load_state(&state)
+}
 
-    fn write_u16(&mut self, location: u16, value: u16) {
-        let bytes = value.to_be_bytes();
-        self.memory[location as usize] = bytes[0];
-        self.memory[location as usize + 1] = bytes[1];
+/// Writes the state as JSON to `path` when `--export-state` is on; does nothing otherwise.
+fn export_state(cpu: &Cpu, path: Option<&Path>) {
+    if let Some(path) = path {
+        match cpu.save_state().write_json_to(path) {
+            Ok(()) => info!("The state is in {}", path.display()),
+            Err(error) => eprintln!("Problem writing {}: {}", path.display(), error),
+        }
     }
 }
 
-struct Keys {
-    keys: [bool; 16],
+/// Dumps the recently executed instructions to stderr, oldest first, after the CPU halts.
+fn print_history(cpu: &Cpu) {
+    eprintln!("Last {} instructions:", cpu.history().len());
+    for entry in cpu.history().iter() {
+        eprintln!("  {}", entry);
+    }
 }
 
-impl Keys {
-    fn new() -> Keys {
-        Keys {
-            keys: [false; 16]
-        }
+/// Writes a core dump of `cpu` into the dumps directory, and says where, or why it couldn't.
+fn write_core_dump(cpu: &Cpu, rom_name: &str) -> String {
+    let path = Path::new(coredump::DUMP_DIRECTORY).join(coredump::file_name(rom_name, SystemTime::now()));
+    match CoreDump::capture(cpu, rom_name).write_to(&path) {
+        Ok(()) => format!("Core dump written to {}", path.display()),
+        Err(error) => format!("Core dump failed: {}", error),
     }
+}
 
-    fn is_pressed(&self, key: u8) -> bool {
-        self.keys[key as usize]
+/// The ROM's file name without its extension, e.g. `PONG` for `roms/PONG.ch8`.
+fn rom_name(rom: &Path) -> String {
+    if romfile::is_stdin(rom) {
+        return String::from("stdin");
+    }
+    if let Some(builtin) = builtin::from_path(rom) {
+        return String::from(builtin.name);
     }
+    rom.file_stem().unwrap_or(rom.as_os_str()).to_string_lossy().into_owned()
 }
 
-struct Cpu {
-    i: u16,
-    pc: u16,
-    stack: [u16; 16],
-    // consider using Vec
-    sp: u8,
-    delay: u8,
-    sound: u8,
-    registers: Registers,
-    memory: Memory,
-    keys: Keys,
-    waiting_for_input: bool,
-    display: Display,
+/// Whether `rom` is a file, rather than stdin or a built-in ROM.
+fn is_file(rom: &Path) -> bool {
+    !romfile::is_stdin(rom) && builtin::from_path(rom).is_none()
 }
 
-impl Cpu {
-    fn new(memory: Memory, display: Display) -> Cpu {
-        Cpu {
-            i: 0,
-            pc: 0x200,
-            stack: [0; 16],
-            sp: 0,
-            delay: 0,
-            sound: 0,
-            registers: Default::default(),
-            memory,
-            keys: Keys::new(),
-            waiting_for_input: false,
-            display,
-        }
-    }
-
-    fn init(&mut self, buffer: Vec<u8>) {
-        let font: [u8; 80] = [
-            0xF0, 0x90, 0x90, 0x90, 0xF0,
-            0x20, 0x60, 0x20, 0x20, 0x70,
-            0xF0, 0x10, 0xF0, 0x80, 0xF0,
-            0xF0, 0x10, 0xF0, 0x10, 0xF0,
-            0x90, 0x90, 0xF0, 0x10, 0x10,
-            0xF0, 0x80, 0xF0, 0x10, 0xF0,
-            0xF0, 0x80, 0xF0, 0x90, 0xF0,
-            0xF0, 0x10, 0x20, 0x40, 0x40,
-            0xF0, 0x90, 0xF0, 0x90, 0xF0,
-            0xF0, 0x90, 0xF0, 0x10, 0xF0,
-            0xF0, 0x90, 0xF0, 0x90, 0x90,
-            0xE0, 0x90, 0xE0, 0x90, 0xE0,
-            0xF0, 0x80, 0x80, 0x80, 0xF0,
-            0xE0, 0x90, 0x90, 0x90, 0xE0,
-            0xF0, 0x80, 0xF0, 0x80, 0xF0,
-            0xF0, 0x80, 0xF0, 0x80, 0x80
-        ];
+/// A file kept next to the ROM, named after it with `suffix` added; ROMs that aren't files keep
+/// them in the current directory, named after `rom_name`.
+fn beside_rom(rom: &Path, suffix: &str) -> PathBuf {
+    let mut path = if is_file(rom) { rom.as_os_str().to_owned() } else { OsString::from(rom_name(rom)) };
+    path.push(suffix);
+    PathBuf::from(path)
+}
 
-        for (i, &item) in font.iter().enumerate() {
-            self.memory.write_u8(i as u16, item);
-        }
+/// Where the RPL flags of `rom` are kept between runs: next to the ROM, like save states.
+fn rpl_flags_path(rom: &Path) -> PathBuf {
+    beside_rom(rom, ".flags")
+}
 
-        for (i, &item) in buffer.iter().enumerate() {
-            self.memory.write_u8(0x200 + i as u16, item);
+/// Writes the RPL flags back if the ROM changed them, so ROMs that never use FX75 leave no file behind.
+fn save_rpl_flags(cpu: &Cpu, path: &Path, saved: &[u8; RPL_FLAGS]) {
+    if cpu.rpl_flags() != saved {
+        if let Err(error) = rplflags::write(path, cpu.rom_hash(), cpu.rpl_flags()) {
+            warn!("Problem writing {}: {}", path.display(), error);
         }
     }
+}
 
-    fn cycle(&mut self) {
-        let opcode: u16 = self.fetch(self.pc);
+/// Where settings shared by all ROMs live: `$XDG_CONFIG_HOME/chip-8-emulator`, falling back to
+/// `~/.config/chip-8-emulator`, or `%APPDATA%\chip-8-emulator` on Windows.
+fn config_dir() -> Option<PathBuf> {
+    let base = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".config")))
+        .or_else(|| env::var_os("APPDATA").map(PathBuf::from))?;
+    Some(base.join("chip-8-emulator"))
+}
 
-        self.pc += 2;
+/// Where what the emulator keeps track of lives: `$XDG_DATA_HOME/chip-8-emulator`, falling back
+/// to `~/.local/share/chip-8-emulator`, or `%LOCALAPPDATA%\chip-8-emulator` on Windows.
+fn data_dir() -> Option<PathBuf> {
+    let base = env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".local").join("share")))
+        .or_else(|| env::var_os("LOCALAPPDATA").map(PathBuf::from))?;
+    Some(base.join("chip-8-emulator"))
+}
 
-        self.decode_and_execute(opcode);
+/// Where `chip8.toml` is looked for, first match wins: next to the executable, then in the
+/// config directory.
+fn config_file_paths() -> Vec<PathBuf> {
+    let mut paths = Vec::new();
+    if let Some(dir) = env::current_exe().ok().and_then(|exe| exe.parent().map(Path::to_path_buf)) {
+        paths.push(dir.join(CONFIG_FILE_NAME));
     }
-
-    fn fetch(&mut self, location: u16) -> u16 {
-        let first_part: u16 = self.memory.read_u16(location) as u16;
-        let second_part: u16 = self.memory.read_u16(location + 1) as u16;
-        let opcode: u16 = first_part << 8 | second_part;
-
-        opcode
-    }
-
-    fn decode_and_execute(&mut self, opcode: u16) {
-        let x: u8 = ((opcode & 0x0F00) >> 8) as u8;
-        let y: u8 = ((opcode & 0x00F0) >> 4) as u8;
-        let kk: u8 = (opcode & 0x00FF) as u8;
-        let nnn: u16 = opcode & 0x0FFF;
-        let n: u8 = (opcode & 0x000F) as u8;
-
-        let mut random = rand::thread_rng();
-
-        println!("opcode {:#X?}", opcode);
-
-        match opcode {
-            // 0x0nnn - ignored by modern interpreters
-            0x00E0 => {
-                self.display.clear();
-            }
-            0x00EE => {
-                self.pc = self.stack[self.sp as usize - 1];
-                self.sp -= 1;
-            }
-            0x1000..=0x1FFF => {
-                self.pc = opcode & 0x0FFF;
-            }
-            0x2000..=0x2FFF => {
-                self.sp += 1;
-                self.stack[self.sp as usize - 1] = self.pc;
-                self.pc = nnn;
-            }
-            0x3000..=0x3FFF => {
-                if self.registers[x] == kk {
-                    self.pc += 2;
-                }
-            }
-            0x4000..=0x4FFF => {
-                if self.registers[x] != kk {
-                    self.pc += 2;
-                }
-            }
-            0x5000..=0x5FF0 => {
-                if self.registers[x] == self.registers[y] {
-                    self.pc += 2;
-                }
-            }
-            0x6000..=0x6FFF => {
-                self.registers[x] = kk;
-            }
-            0x7000..=0x7FFF => {
-                let value: u16 = self.registers[x] as u16 + kk as u16;
-                self.registers[x] = value as u8;
-            }
-            0x8000..=0x8FFE => {
-                let operation = opcode & 0x000F;
-                match operation {
-                    0 => self.registers[x] = self.registers[y],
-                    1 => self.registers[x] |= self.registers[y],
-                    2 => self.registers[x] &= self.registers[y],
-                    3 => self.registers[x] ^= self.registers[y],
-                    4 => {
-                        let value: u16 = self.registers[x] as u16 + self.registers[y] as u16;
-                        self.registers[x] = value as u8;
-                        if value > 255 {
-                            self.registers.vf = 1;
-                        } else {
-                            self.registers.vf = 0;
-                        }
-                    }
-                    5 => {
-                        if self.registers[x] > self.registers[y] {
-                            self.registers.vf = 1;
-                        } else {
-                            self.registers.vf = 0;
-                        }
-                        self.registers[x] = self.registers[x].wrapping_sub(self.registers[y]);
-                    }
-                    6 => {
-                        if self.registers[x] & 1 == 1 {
-                            self.registers.vf = 1;
-                        } else {
-                            self.registers.vf = 0;
-                        }
-
-                        self.registers[x] /= 2;
-                    }
-                    7 => {
-                        self.registers[x] = self.registers[y].wrapping_sub(self.registers[x]);
-
-                        if self.registers[y] > self.registers[x] {
-                            self.registers.vf = 1;
-                        } else {
-                            self.registers.vf = 0;
-                        }
-                    }
-                    0xE => {
-                        if self.registers[x] & (1 << 7) != 0 {
-                            self.registers.vf = 1;
-                        } else {
-                            self.registers.vf = 0;
-                        }
-
-                        let value: u16 = (self.registers[x] as u16) * 2;
-                        self.registers[x] = value as u8;
-                    }
-                    _ => {}
-                }
-            }
-            0x9000..=0x9FF0 => {
-                if self.registers[x] != self.registers[y] {
-                    self.pc += 2;
-                }
-            }
-            0xA000..=0xAFFF => {
-                self.i = nnn;
-            }
-            0xB000..=0xBFFF => {
-                self.pc = nnn + self.registers.v0 as u16;
-            }
-            0xC000..=0xCFFF => {
-                self.registers[x] = random.gen_range(0, 255) & kk;
-            }
-            0xD000..=0xDFFF => {
-                self.registers.vf = 0;
-                let mut sprite_x = self.registers[x] % 64;
-                let mut sprite_y = self.registers[y] % 32;
-                for i in self.i..(self.i + n as u16) {
-                    let byte = self.memory.read_u8(i);
-                    for index in 0..8 {
-                        let value = (byte & (0b1000_0000 >> index)) >> (7 - index);
-                        if self.registers.vf == 0 && value == 1 && self.display.pixels[sprite_x as usize][sprite_y as usize] == 1 {
-                            self.registers.vf = 1;
-                        }
-
-                        self.display.pixels[sprite_x as usize][sprite_y as usize] ^= value;
-                        sprite_x += 1;
-
-                        if sprite_x > 63 {
-                            break;
-                        }
-                    }
-                    sprite_x = self.registers[x];
-                    sprite_y += 1;
-
-                    if sprite_y > 31 {
-                        break;
-                    }
-                }
-            }
-            0xE000..=0xEFFF => {
-                let operation = kk;
-                match operation {
-                    0x9E => {
-                        if self.keys.is_pressed(self.registers[x]) {
-                            self.pc += 2;
-                        }
-                    }
-                    0xA1 => {
-                        if !self.keys.is_pressed(self.registers[x]) {
-                            self.pc += 2;
-                        }
-                    }
-                    _ => {}
-                }
-            }
-            0xF007..=0xFF65 => {
-                let operation = opcode & 0x00FF;
-                match operation {
-                    0x07 => self.registers[x] = self.delay,
-                    0x0A => {
-                        // wait for a key press
-                        self.waiting_for_input = true;
-                        // todo
-                    }
-                    0x15 => self.delay = self.registers[x],
-                    0x18 => self.sound = self.registers[x],
-                    0x1E => self.i += self.registers[x] as u16,
-                    0x29 => self.i = self.registers[x] as u16 * 5,
-                    0x33 => {
-                        let value = self.registers[x];
-                        self.memory.write_u8(self.i, value / 100);
-                        self.memory.write_u8(self.i + 1, (value % 100) / 10);
-                        self.memory.write_u8(self.i + 2, value % 10);
-                    }
-                    0x55 => {
-                        for register in 0..(x + 1) {
-                            self.memory.write_u8(self.i + register as u16, self.registers[register]);
-                        }
-                    }
-                    0x65 => {
-                        for register in 0..(x + 1) {
-                            self.registers[register] = self.memory.read_u8(self.i + register as u16);
-                        }
-                    }
-                    _ => {}
-                }
-            }
-            _ => {
-                panic!("unsupported opcode");
-            }
-        }
+    if let Some(dir) = config_dir() {
+        paths.push(dir.join(CONFIG_FILE_NAME));
     }
+    paths
 }
 
-struct Display {
-    pixels: [[u8; 32]; 64],
+/// The first `chip8.toml` found and what's in it, if there is one.
+fn find_config_file() -> Option<(PathBuf, String)> {
+    config_file_paths().into_iter().find_map(|path| fs::read_to_string(&path).ok().map(|text| (path, text)))
 }
 
-impl Display {
-    fn new() -> Display {
-        Display {
-            pixels: [[0; 32]; 64]
-        }
-    }
-
-    fn clear(&mut self) {
-        self.pixels = [[0; 32]; 64]
+/// The per-ROM settings in `roms.toml`, or none if there's no such file.
+fn load_settings_database() -> Result<SettingsDatabase, String> {
+    let path = match config_dir() {
+        Some(dir) => dir.join(SETTINGS_FILE_NAME),
+        None => return Ok(SettingsDatabase::default()),
+    };
+    match fs::read_to_string(&path) {
+        Ok(text) => SettingsDatabase::parse(&text).map_err(|error| format!("Problem reading {}: {}", path.display(), error)),
+        Err(_) => Ok(SettingsDatabase::default()),
     }
 }
 
-impl EventHandler<GameError> for Cpu {
-    fn update(&mut self, _ctx: &mut Context) -> Result<(), GameError> {
-        self.cycle();
-        Ok(())
-    }
-
-    fn draw(&mut self, ctx: &mut Context) -> Result<(), GameError> {
-        graphics::clear(ctx, [0.0, 0.0, 0.0, 10.0].into());
-        let pixel_size = 10.0;
-
-        for y in 0..32 {
-            for x in 0..64 {
-                if self.display.pixels[x as usize][y as usize] == 1 {
-                    let float_x = x as f32;
-                    let float_y = y as f32;
-                    let rect = graphics::Rect::new(float_x * pixel_size, float_y * pixel_size, pixel_size, pixel_size);
-                    let mesh = graphics::Mesh::new_rectangle(ctx, graphics::DrawMode::fill(), rect, Color::WHITE)?;
-                    graphics::draw(ctx, &mesh, DrawParam::default())?;
-                }
-            }
-        }
-
-        graphics::present(ctx)
-    }
+/// Stores `settings` under `key` in `roms.toml`, keeping the other ROMs' entries, and returns
+/// where it went.
+fn save_rom_settings(key: &str, settings: &RomSettings) -> Result<PathBuf, String> {
+    let dir = config_dir().ok_or("Nowhere to save settings: no config directory")?;
+    let path = dir.join(SETTINGS_FILE_NAME);
+    // read afresh, so entries saved by another window aren't lost
+    let mut database = match fs::read_to_string(&path) {
+        Ok(text) => SettingsDatabase::parse(&text).map_err(|error| format!("Not overwriting {}: {}", path.display(), error))?,
+        Err(_) => SettingsDatabase::default(),
+    };
+    database.insert(key, settings.clone());
+    fs::create_dir_all(&dir)
+        .and_then(|()| fs::write(&path, database.to_toml()))
+        .map_err(|error| format!("Problem writing {}: {}", path.display(), error))?;
+    Ok(path)
 }
 
-fn main() -> GameResult {
-    let path = env::current_dir();
-    println!("The current directory is {}", path.unwrap().display());
+/// A ROM ready to run, with the settings it runs with.
+struct LoadedRom {
+    cpu: Cpu,
+    // the ROM's key in the settings database
+    key: String,
+    settings: RomSettings,
+    keymap: Keymap,
+    recorder: Option<Recorder>,
+    player: Option<Player>,
+}
 
-    let file = File::open("IBM");
-    let mut buffer = Vec::new();
+/// Reads `rom`, which may also be `-` for stdin or a built-in ROM's path, and sets up a CPU to run
+/// it the way `config` and the settings database say. The command line, the ROM picker and resets
+/// all load ROMs through here.
+fn load_rom(rom: &Path, config: &Config) -> Result<LoadedRom, String> {
+    let buffer = match (builtin::from_path(rom), &config.stdin) {
+        (Some(builtin), _) => builtin.bytes.to_vec(),
+        (None, Some(buffer)) if romfile::is_stdin(rom) => buffer.clone(),
+        (None, _) => romfile::read(rom).map_err(|error| format!("Problem reading {}: {}", rom.display(), error))?,
+    };
+    load_rom_bytes(rom, buffer, config)
+}
 
-    let mut file = match file {
-        Ok(file) => file,
-        Err(error) => panic!("Problem opening the file: {:?}", error),
+/// `load_rom` for bytes already read from `rom`, which may be raw bytecode, a `.c8b` container
+/// or a hex dump.
+fn load_rom_bytes(rom: &Path, buffer: Vec<u8>, config: &Config) -> Result<LoadedRom, String> {
+    let buffer = decode_rom(rom, buffer, config.format)?;
+    let (buffer, embedded) = if c8b::is_container(&buffer) || has_extension(rom, "c8b") {
+        unpack_container(&buffer).map_err(|error| format!("Problem loading {}: {}", rom.display(), error))?
+    } else {
+        (buffer, RomSettings::default())
     };
-    let result = file.read_to_end(&mut buffer);
-    match result {
-        Ok(result) => result,
-        Err(error) => panic!("Problem reading the file: {:?}", error),
+    let key = settings::rom_key(&buffer);
+    if let Some(stored) = config.database.get(&key) {
+        info!("Applying the saved settings for {}", stored.name.as_deref().unwrap_or(&key));
+    }
+    // chip8.toml counts for least, then the container's own settings
+    let settings = config.file.settings.rom_settings().merged(&embedded).merged(&config.database.resolve(&key, &config.settings));
+    let keymap = match &settings.keymap {
+        Some(table) => config.keymap.with_keys(table).map_err(|error| format!("Problem with the saved settings for {}: {}", key, error))?,
+        None => config.keymap.clone(),
     };
 
     let mut cpu = Cpu::new(Memory::new(), Display::new());
-    cpu.init(buffer);
-
-    let context_builder = ContextBuilder::new("chip-8-emulator", "Ziem")
-        .window_setup(WindowSetup::default().title("Chip 8 emulator"))
-        .window_mode(WindowMode::default().dimensions(640.0, 320.0));
-    let (context, event_loop) = context_builder.build()?;
-    event::run(context, event_loop, cpu)
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    #[test]
-    fn clear_display() {
-        let mut memory: Memory = Memory::new();
-        let mut display: Display = Display::new();
-        display.pixels[0][0] = 1;
-        display.pixels[63][31] = 1;
-        memory.write_u16(0x200, 0x00E0);
-        let mut cpu = Cpu::new(memory, display);
-
-        cpu.cycle();
-
-        assert_eq!(cpu.display.pixels[0][0], 0);
-        assert_eq!(cpu.display.pixels[63][31], 0);
+    // before `init`, since XO-CHIP ROMs may need more than 4 KiB
+    let quirks = settings.quirks_for(&buffer);
+    if quirks.hires_chip8 && settings.hires_chip8.is_none() {
+        info!("{} starts like a two-page hi-res CHIP-8 ROM, so it gets a 64x64 screen", rom.display());
+    }
+    cpu.set_quirks(quirks);
+    if let Some(font) = config.font.clone() {
+        cpu.set_font(font);
+    }
+    let coverage = config.coverage.as_ref().map(|_| Coverage::new(&buffer, config.load_address));
+    cpu.load_rom(config.load_address, &buffer).map_err(|error| format!("Problem loading {}: {}", rom.display(), error))?;
+    if let Some(seed) = config.seed {
+        cpu.seed(seed);
+    }
+    cpu.set_rpl_flags(rplflags::read(&rpl_flags_path(rom), cpu.rom_hash()));
+    if let Some(cheats) = &settings.cheats {
+        cheats.check(cpu.memory().bytes().len()).map_err(|error| format!("Problem with the cheats for {}: {}", rom.display(), error))?;
+        // a recording only holds the keypad input, so it couldn't be played back with them
+        if config.record.is_some() || config.replay.is_some() {
+            warn!("Leaving the cheats for {} off while recording or replaying", rom.display());
+        } else {
+            cheats.apply(&mut cpu);
+        }
     }
-
-    #[test]
-    fn return_from_a_subroutine() {
-        let mut memory: Memory = Memory::new();
-        let display: Display = Display::new();
-        memory.write_u16(0x200, 0x0EE);
-        let mut cpu = Cpu::new(memory, display);
-        cpu.stack[0] = 0x0001;
-        cpu.sp = 1;
-
-        cpu.cycle();
-
-        assert_eq!(cpu.sp, 0);
-        assert_eq!(cpu.pc, 0x0001);
+    if config.profile_opcodes {
+        cpu.enable_profiling();
     }
-
-    #[test]
-    fn jump_to_location() {
-        let mut memory: Memory = Memory::new();
-        let display: Display = Display::new();
-        memory.write_u16(0x200, 0x1234);
-        let mut cpu = Cpu::new(memory, display);
-
-        cpu.cycle();
-
-        assert_eq!(cpu.pc, 0x234);
+    if let Some(coverage) = coverage {
+        cpu.enable_coverage(coverage);
     }
-
-    #[test]
-    fn call_subroutine() {
-        let mut memory: Memory = Memory::new();
-        let display: Display = Display::new();
-        memory.write_u16(0x200, 0x2312);
-        let mut cpu = Cpu::new(memory, display);
-
-        cpu.cycle();
-
-        assert_eq!(cpu.sp, 1);
-        assert_eq!(cpu.stack[0], 0x200 + 2);
-        assert_eq!(cpu.pc, 0x312);
+    for &watchpoint in &config.watchpoints {
+        cpu.add_watchpoint(watchpoint);
     }
+    cpu.set_illegal_opcodes(config.on_illegal);
+    let recorder = config.record.as_ref().map(|_| Recorder::new(&cpu, config.seed.unwrap_or_default(), &settings));
+    let player = match &config.replay {
+        Some(recording) => {
+            Some(Player::new(recording.clone(), &mut cpu).map_err(|error| format!("Can't replay on {}: {}", rom.display(), error))?)
+        }
+        None => None,
+    };
+    Ok(LoadedRom { cpu, key, settings, keymap, recorder, player })
+}
 
-    #[test]
-    fn skip_next_instruction_if_vx_equals_kk() {
-        let mut memory: Memory = Memory::new();
-        let display: Display = Display::new();
-        memory.write_u16(0x200, 0x3144);
-        let mut cpu = Cpu::new(memory, display);
-        cpu.registers.v1 = 0x44;
-
-        cpu.cycle();
-
-        assert_eq!(cpu.pc, 0x200 + 4);
+/// The bytecode in a `.c8b` container, with its metadata as settings.
+fn unpack_container(bytes: &[u8]) -> Result<(Vec<u8>, RomSettings), c8b::C8bError> {
+    let container = Container::parse(bytes)?;
+    let (_, bytecode) = container.bytecode()?;
+    if let Some(title) = &container.title {
+        match &container.author {
+            Some(author) => info!("{} by {}", title, author),
+            None => info!("{}", title),
+        }
     }
+    Ok((bytecode.to_vec(), container.settings()?))
+}
 
-    #[test]
-    fn skip_next_instruction_if_vx_not_equals_kk() {
-        let mut memory: Memory = Memory::new();
-        let display: Display = Display::new();
-        memory.write_u16(0x200, 0x4144);
-        let mut cpu = Cpu::new(memory, display);
-        cpu.registers.v1 = 0x43;
-
-        cpu.cycle();
+fn has_extension(path: &Path, extension: &str) -> bool {
+    path.extension().and_then(|found| found.to_str()).is_some_and(|found| found.eq_ignore_ascii_case(extension))
+}
 
-        assert_eq!(cpu.pc, 0x200 + 4);
+/// Runs without a window, or plays `player`'s recording back to its end; `palette` colours a
+/// PNG written with `--out`.
+fn run_headless(mut cpu: Cpu, player: Option<Player>, rom: &Path, palette: &Palette, config: &Config) -> i32 {
+    let started = Instant::now();
+    let instructions_at_start = cpu.instructions_executed();
+    let saved_flags = *cpu.rpl_flags();
+    let replaying = player.is_some();
+    let result = match player {
+        Some(mut player) => replay::run(&mut cpu, &mut player).map_err(|error| (matches!(error, ReplayError::Halted(_)), error.to_string())),
+        None => run_unattended(&mut cpu, config),
+    };
+    print_profile(&cpu, started.elapsed());
+    write_coverage(&cpu, config.coverage.as_deref());
+    export_state(&cpu, config.export_state.as_deref());
+    eprintln!("{}", speed::summary(cpu.instructions_executed().wrapping_sub(instructions_at_start), started.elapsed()));
+    if let Some(skipped) = cpu.take_skipped_calls() {
+        warn!("{}", skipped);
+    }
+    if !replaying {
+        save_rpl_flags(&cpu, &rpl_flags_path(rom), &saved_flags);
+    }
+    if let Err((crashed, message)) = result {
+        if crashed {
+            print_history(&cpu);
+            eprintln!("{}", write_core_dump(&cpu, &rom_name(rom)));
+        }
+        eprintln!("{}", message);
+        return 1;
+    }
+
+    match &config.out {
+        Some(path) => {
+            let image = if has_extension(path, "png") {
+                screenshot::to_png(cpu.display(), palette, config.screenshot_scale)
+            } else {
+                cpu.display().to_pbm().into_bytes()
+            };
+            if let Err(error) = fs::write(path, image) {
+                eprintln!("Problem writing {}: {}", path.display(), error);
+                return 1;
+            }
+        }
+        None => print!("{}", cpu.display().to_ascii()),
     }
 
-    #[test]
-    fn skip_next_instruction_if_vx_equals_vy() {
-        let mut memory: Memory = Memory::new();
-        let display: Display = Display::new();
-        memory.write_u16(0x200, 0x5120);
-        let mut cpu = Cpu::new(memory, display);
-        cpu.registers.v1 = 0x44;
-        cpu.registers.v2 = 0x44;
+    0
+}
 
-        cpu.cycle();
+/// Runs `config.cycles` instructions with the scripted keys, and with `--script`'s hooks. The
+/// error says whether the ROM crashed, and why the run stopped.
+fn run_unattended(cpu: &mut Cpu, config: &Config) -> Result<(), (bool, String)> {
+    #[cfg(feature = "scripting")]
+    if let Some(path) = &config.script {
+        let mut script = fs::read_to_string(path)
+            .map_err(|error| error.to_string())
+            .and_then(|source| Script::compile(&source).map_err(|error| error.to_string()))
+            .map_err(|error| (false, format!("Problem loading {}: {}", path.display(), error)))?;
+        return script::run(cpu, config.cycles, &config.keys, &mut script).map_err(|error| (matches!(error, ScriptError::Cpu(_)), error.to_string()));
+    }
+    headless::run_hashing(cpu, config.cycles, &config.keys, config.hash_every.unwrap_or(0), |instruction, hash| {
+        println!("{} {:016X}", instruction, hash)
+    })
+    .map_err(|error| (true, ReplayError::Halted(error).to_string()))
+}
 
-        assert_eq!(cpu.pc, 0x200 + 4);
+/// Plays the loaded ROM in the terminal until it exits, halts or Esc is pressed.
+#[cfg(feature = "frontend-terminal")]
+fn run_terminal(loaded: LoadedRom, rom: &Path, config: &Config) -> i32 {
+    let mut cpu = loaded.cpu;
+    let saved_flags = *cpu.rpl_flags();
+    let cycles_per_frame = loaded.settings.cycles_per_frame.unwrap_or(CYCLES_PER_FRAME);
+    let timing = loaded.settings.timing.unwrap_or_default();
+    let result = terminal::run(&mut cpu, &loaded.keymap, cycles_per_frame, timing, config.bell);
+    write_coverage(&cpu, config.coverage.as_deref());
+    export_state(&cpu, config.export_state.as_deref());
+    if let Some(skipped) = cpu.take_skipped_calls() {
+        warn!("{}", skipped);
+    }
+    save_rpl_flags(&cpu, &rpl_flags_path(rom), &saved_flags);
+    match result {
+        Ok(()) => 0,
+        Err(error) => {
+            if let TerminalError::Halted(_) = error {
+                print_history(&cpu);
+                eprintln!("{}", write_core_dump(&cpu, &rom_name(rom)));
+            }
+            eprintln!("{}", error);
+            1
+        }
     }
+}
 
-    #[test]
-    fn set_vx_to_kk() {
-        let mut memory: Memory = Memory::new();
-        let display: Display = Display::new();
-        memory.write_u16(0x200, 0x6622);
-        let mut cpu = Cpu::new(memory, display);
-
-        cpu.cycle();
+/// The bytes of a hex dump, or `buffer` as it is for binary ROMs; `format` None goes by the
+/// extension.
+fn decode_rom(rom: &Path, buffer: Vec<u8>, format: Option<RomFormat>) -> Result<Vec<u8>, String> {
+    let format = format.unwrap_or_else(|| RomFormat::detect(rom));
+    romfile::decode(buffer, format).map_err(|error| format!("Problem loading {}: {}", rom.display(), error))
+}
 
-        assert_eq!(cpu.registers.v6, 0x22);
+/// The bytecode in `rom`, decoded from hex or unpacked from a `.c8b` container as needed.
+fn read_bytecode(rom: &Path, format: Option<RomFormat>) -> Result<Vec<u8>, String> {
+    let buffer = romfile::read(rom).map_err(|error| format!("Problem reading {}: {}", rom.display(), error))?;
+    let buffer = decode_rom(rom, buffer, format)?;
+    if c8b::is_container(&buffer) || has_extension(rom, "c8b") {
+        let (bytecode, _) = unpack_container(&buffer).map_err(|error| format!("Problem loading {}: {}", rom.display(), error))?;
+        Ok(bytecode)
+    } else {
+        Ok(buffer)
     }
+}
 
-    #[test]
-    fn set_vx_to_vx_plus_kk() {
-        let mut memory: Memory = Memory::new();
-        let display: Display = Display::new();
-        memory.write_u16(0x200, 0x7422);
-        let mut cpu = Cpu::new(memory, display);
-        cpu.registers.v4 = 0x22;
-
-        cpu.cycle();
-
-        assert_eq!(cpu.registers.v4, 0x22 + 0x22);
+/// The `disasm` subcommand: prints every instruction in `rom`. With `labels` it follows the code
+/// and prints labelled source instead, drawing sprite data in comments with `sprites`.
+fn run_disasm(rom: &Path, format: Option<RomFormat>, load_address: u16, source: bool, labels: bool, sprites: bool) -> i32 {
+    match read_bytecode(rom, format) {
+        Ok(bytecode) if labels => {
+            print!("{}", labels::labelled_source(&bytecode, load_address, sprites));
+            0
+        }
+        Ok(bytecode) if source => {
+            print!("{}", instruction::source(&bytecode));
+            0
+        }
+        Ok(bytecode) => {
+            for line in instruction::listing(&bytecode, load_address) {
+                println!("{}", line);
+            }
+            0
+        }
+        Err(error) => {
+            eprintln!("{}", error);
+            1
+        }
     }
+}
 
-    #[test]
-    fn set_vx_to_vy() {
-        let mut memory: Memory = Memory::new();
-        let display: Display = Display::new();
-        memory.write_u16(0x200, 0x8420);
-        let mut cpu = Cpu::new(memory, display);
-        cpu.registers.v2 = 0x22;
-
-        cpu.cycle();
-
-        assert_eq!(cpu.registers.v4, 0x22);
+/// The `check` subcommand: analyzes `rom` and prints the report, failing if it can't run. With
+/// `dead_code` the ROM's listing follows, marking what no path reaches.
+fn run_check(rom: &Path, format: Option<RomFormat>, json: bool, dead_code: bool) -> i32 {
+    let bytecode = match read_bytecode(rom, format) {
+        Ok(bytecode) => bytecode,
+        Err(error) => {
+            eprintln!("{}", error);
+            return 1;
+        }
+    };
+    let report = analysis::analyze(&bytecode);
+    if json {
+        match serde_json::to_string_pretty(&report) {
+            Ok(text) => println!("{}", text),
+            Err(error) => {
+                eprintln!("{}", error);
+                return 1;
+            }
+        }
+    } else {
+        println!("{}", rom.display());
+        print!("{}", report);
     }
-
-    #[test]
-    fn set_vx_to_vx_or_vy() {
-        let mut memory: Memory = Memory::new();
-        let display: Display = Display::new();
-        memory.write_u16(0x200, 0x8011);
-        let mut cpu = Cpu::new(memory, display);
-        cpu.registers.v0 = 0x22;
-        cpu.registers.v1 = 0x11;
-
-        cpu.cycle();
-
-        assert_eq!(cpu.registers.v0, 51);
+    if dead_code {
+        // what no path reaches is data, or code nothing runs
+        println!();
+        print!("{}", coverage::report(&analysis::reachable(&bytecode)));
     }
-
-    #[test]
-    fn set_vx_to_vx_and_vy() {
-        let mut memory: Memory = Memory::new();
-        let display: Display = Display::new();
-        memory.write_u16(0x200, 0x8452);
-        let mut cpu = Cpu::new(memory, display);
-        cpu.registers.v4 = 0x12;
-        cpu.registers.v5 = 0x11;
-
-        cpu.cycle();
-
-        assert_eq!(cpu.registers.v4, 16);
+    if report.passed() {
+        0
+    } else {
+        1
     }
+}
 
-    #[test]
-    fn set_vx_to_vx_xor_vy() {
-        let mut memory: Memory = Memory::new();
-        let display: Display = Display::new();
-        memory.write_u16(0x200, 0x8453);
-        let mut cpu = Cpu::new(memory, display);
-        cpu.registers.v4 = 0x12;
-        cpu.registers.v5 = 0x11;
-
-        cpu.cycle();
-
-        assert_eq!(cpu.registers.v4, 3);
+/// The `asm` subcommand: assembles `input` and writes the ROM to `out`.
+fn run_asm(input: &Path, out: &Path, load_address: u16) -> i32 {
+    let source = match fs::read_to_string(input) {
+        Ok(source) => source,
+        Err(error) => {
+            eprintln!("Problem reading {}: {}", input.display(), error);
+            return 1;
+        }
+    };
+    let rom = match assembler::assemble(&source, load_address) {
+        Ok(rom) => rom,
+        Err(error) => {
+            eprintln!("{}: {}", input.display(), error);
+            return 1;
+        }
+    };
+    match fs::write(out, &rom) {
+        Ok(()) => {
+            println!("Wrote {} bytes to {}", rom.len(), out.display());
+            0
+        }
+        Err(error) => {
+            eprintln!("Problem writing {}: {}", out.display(), error);
+            1
+        }
     }
+}
 
-    #[test]
-    fn set_vx_to_vx_plus_vy() {
-        let mut memory: Memory = Memory::new();
-        let display: Display = Display::new();
-        memory.write_u16(0x200, 0x8454);
-        memory.write_u16(0x400, 0x8124);
-
-        let mut cpu = Cpu::new(memory, display);
-        cpu.registers.v4 = 0x12;
-        cpu.registers.v5 = 0x11;
-
-        cpu.cycle();
-
-        assert_eq!(cpu.registers.v4, 35);
-        assert_eq!(cpu.registers.vf, 0);
-
-        cpu.registers.v1 = 0xFF;
-        cpu.registers.v2 = 0xFF;
-        cpu.pc = 0x400;
-
-        cpu.cycle();
-
-        assert_eq!(cpu.registers.vf, 1);
+/// The `dump` subcommand: pretty-prints a core dump.
+fn run_dump(path: &Path) -> i32 {
+    match CoreDump::read_from(path) {
+        Ok(dump) => {
+            print!("{}", dump);
+            0
+        }
+        Err(error) => {
+            eprintln!("Problem reading {}: {}", path.display(), error);
+            1
+        }
     }
+}
 
-    #[test]
-    fn set_vx_to_vx_minus_vy() {
-        let mut memory: Memory = Memory::new();
-        let display: Display = Display::new();
-        memory.write_u16(0x200, 0x8455);
-        memory.write_u16(0x400, 0x8125);
-
-        let mut cpu = Cpu::new(memory, display);
-        cpu.registers.v4 = 0x12;
-        cpu.registers.v5 = 0x11;
-
-        cpu.cycle();
-
-        assert_eq!(cpu.registers.v4, 1);
-        assert_eq!(cpu.registers.vf, 1);
-
-        cpu.registers.v1 = 0xFF;
-        cpu.registers.v2 = 0xFF;
-        cpu.pc = 0x400;
-
-        cpu.cycle();
+/// The `compare` subcommand: runs the ROM under both quirk profiles in lockstep and prints where
+/// they part ways.
+fn run_compare(rom: &Path, format: Option<RomFormat>, profiles: (&str, &str), cycles: u64, keys: &headless::KeyScript, seed: u64, to_the_end: bool) -> i32 {
+    let bytecode = match read_bytecode(rom, format) {
+        Ok(bytecode) => bytecode,
+        Err(error) => {
+            eprintln!("{}", error);
+            return 1;
+        }
+    };
+    let machine = |profile: &str| -> Result<Cpu, String> {
+        let mut quirks = Quirks::profile(profile).unwrap_or_default();
+        quirks.hires_chip8 = detect_hires_chip8(&bytecode);
+        let mut cpu = Cpu::new(Memory::new(), Display::new());
+        // before loading, since XO-CHIP ROMs may need more than 4 KiB
+        cpu.set_quirks(quirks);
+        cpu.load_rom(DEFAULT_LOAD_ADDRESS, &bytecode).map_err(|error| format!("Problem loading {}: {}", rom.display(), error))?;
+        cpu.seed(seed);
+        Ok(cpu)
+    };
+    let (mut left, mut right) = match (machine(profiles.0), machine(profiles.1)) {
+        (Ok(left), Ok(right)) => (left, right),
+        (Err(error), _) | (_, Err(error)) => {
+            eprintln!("{}", error);
+            return 1;
+        }
+    };
+    println!("{}: {} on the left, {} on the right", rom.display(), profiles.0, profiles.1);
+    print!("{}", compare::compare(&mut left, &mut right, cycles, keys, to_the_end));
+    0
+}
 
-        assert_eq!(cpu.registers.vf, 0);
+/// The `test-suite` subcommand: runs every ROM in the manifest and prints a table of the results.
+/// A failing ROM's framebuffer is written next to the manifest, as text and PBM. With `update`
+/// the framebuffers become the expected results, in the manifest or its PBM files.
+fn run_test_suite(manifest_path: &Path, update: bool) -> i32 {
+    let text = match fs::read_to_string(manifest_path) {
+        Ok(text) => text,
+        Err(error) => {
+            eprintln!("Problem reading {}: {}", manifest_path.display(), error);
+            return 1;
+        }
+    };
+    let mut manifest = match Manifest::parse(&text) {
+        Ok(manifest) => manifest,
+        Err(error) => {
+            eprintln!("Problem with {}: {}", manifest_path.display(), error);
+            return 1;
+        }
+    };
+    let dir = manifest_path.parent().unwrap_or_else(|| Path::new(""));
+    let mut reports = Vec::new();
+    let mut manifest_changed = false;
+    for case in &mut manifest.cases {
+        let started = Instant::now();
+        let (display, result) = match read_bytecode(&dir.join(&case.path), None) {
+            Ok(bytecode) => {
+                let (display, result) = testsuite::run_case(case, bytecode);
+                (Some(display), result)
+            }
+            Err(error) => (None, Err(error)),
+        };
+        let elapsed = started.elapsed();
+        let verdict = match (display, result) {
+            (Some(display), Ok(())) if update => match &case.pbm {
+                Some(pbm) => match fs::write(dir.join(pbm), display.to_pbm()) {
+                    Ok(()) => Verdict::Updated,
+                    Err(error) => Verdict::Fail(format!("problem writing {}: {}", pbm.display(), error)),
+                },
+                None => {
+                    case.hash = Some(testsuite::format_hash(testsuite::framebuffer_hash(&display)));
+                    manifest_changed = true;
+                    Verdict::Updated
+                }
+            },
+            (Some(display), result) => {
+                let expected_pbm = match &case.pbm {
+                    Some(pbm) => fs::read_to_string(dir.join(pbm)).map(Some).map_err(|error| format!("problem reading {}: {}", pbm.display(), error)),
+                    None => Ok(None),
+                };
+                let checked = result.and(expected_pbm).and_then(|pbm| testsuite::compare(case, &display, pbm.as_deref()));
+                match checked {
+                    Ok(()) => Verdict::Pass,
+                    Err(reason) => {
+                        write_actual_framebuffer(dir, &case.path, &display);
+                        Verdict::Fail(reason)
+                    }
+                }
+            }
+            (None, result) => Verdict::Fail(result.err().unwrap_or_default()),
+        };
+        reports.push(CaseReport { rom: case.path.clone(), elapsed, verdict });
     }
 
-    #[test]
-    fn set_vx_to_vx_shr_1() {
-        let mut memory: Memory = Memory::new();
-        let display: Display = Display::new();
-        memory.write_u16(0x200, 0x8456);
-        memory.write_u16(0x400, 0x8126);
-
-        let mut cpu = Cpu::new(memory, display);
-        cpu.registers.v4 = 0x12;
-
-        cpu.cycle();
-
-        assert_eq!(cpu.registers.vf, 0);
-        assert_eq!(cpu.registers.v4, 9);
-
-        cpu.registers.v1 = 0xFF;
-        cpu.pc = 0x400;
-
-        cpu.cycle();
-
-        assert_eq!(cpu.registers.vf, 1);
-        assert_eq!(cpu.registers.v1, 127);
+    for line in testsuite::table(&reports) {
+        println!("{}", line);
     }
-
-    #[test]
-    fn set_vx_to_vx_shl_1() {
-        let mut memory: Memory = Memory::new();
-        let display: Display = Display::new();
-        memory.write_u16(0x200, 0x845E);
-        memory.write_u16(0x400, 0x812E);
-
-        let mut cpu = Cpu::new(memory, display);
-        cpu.registers.v4 = 0x01;
-
-        cpu.cycle();
-
-        assert_eq!(cpu.registers.vf, 0);
-        assert_eq!(cpu.registers.v4, 2);
-
-        cpu.registers.v1 = 0xFF;
-        cpu.pc = 0x400;
-
-        cpu.cycle();
-
-        assert_eq!(cpu.registers.vf, 1);
+    if manifest_changed {
+        if let Err(error) = fs::write(manifest_path, manifest.to_toml()) {
+            eprintln!("Problem writing {}: {}", manifest_path.display(), error);
+            return 1;
+        }
     }
-
-    #[test]
-    fn skip_next_instruction_if_vx_not_equals_vy() {
-        let mut memory: Memory = Memory::new();
-        let display: Display = Display::new();
-        memory.write_u16(0x200, 0x9450);
-        memory.write_u16(0x400, 0x9120);
-
-        let mut cpu = Cpu::new(memory, display);
-        cpu.registers.v4 = 0x01;
-        cpu.registers.v5 = 0x01;
-
-        cpu.cycle();
-
-        assert_eq!(cpu.pc, 0x200 + 2);
-
-        cpu.registers.v1 = 0x12;
-        cpu.registers.v2 = 0x13;
-        cpu.pc = 0x400;
-
-        cpu.cycle();
-
-        assert_eq!(cpu.pc, 0x400 + 4);
+    if reports.iter().any(|report| matches!(report.verdict, Verdict::Fail(_))) {
+        1
+    } else {
+        0
     }
+}
 
-    #[test]
-    fn set_i_to_nnn() {
-        let mut memory: Memory = Memory::new();
-        let display: Display = Display::new();
-        memory.write_u16(0x200, 0xA123);
-        let mut cpu = Cpu::new(memory, display);
-
-        cpu.cycle();
-
-        assert_eq!(cpu.i, 0x123);
+fn write_actual_framebuffer(dir: &Path, rom: &Path, display: &Display) {
+    for (extension, contents) in [("txt", display.to_ascii()), ("pbm", display.to_pbm())] {
+        let path = testsuite::actual_path(dir, rom, extension);
+        if let Err(error) = fs::write(&path, contents) {
+            eprintln!("Problem writing {}: {}", path.display(), error);
+        }
     }
+}
 
-    #[test]
-    fn jump_to_location_nnn_plus_v0() {
-        let mut memory: Memory = Memory::new();
-        let display: Display = Display::new();
-        memory.write_u16(0x200, 0xB123);
-        let mut cpu = Cpu::new(memory, display);
-        cpu.registers.v0 = 1;
-
-        cpu.cycle();
+fn main() -> GameResult {
+    // RUST_LOG=warn quietens the startup information; RUST_LOG=trace logs every instruction
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info"))
+        .format_timestamp(None)
+        .format_target(false)
+        .init();
+    // the file's settings go under the command line's; if it's broken, that's reported below
+    let found = find_config_file();
+    let mut base = Config::default();
+    if let Some(settings) = found.as_ref().and_then(|(_, text)| FileSettings::parse(text).ok()) {
+        settings.apply_to(&mut base);
+    }
+    let mut config = match cli::parse_on(env::args_os(), base) {
+        Ok(Invocation::Run(config)) => *config,
+        Ok(Invocation::Disasm { rom, format, load_address, source, labels, sprites }) => {
+            process::exit(run_disasm(&rom, format, load_address, source, labels, sprites))
+        }
+        Ok(Invocation::Check { rom, format, json, dead_code }) => process::exit(run_check(&rom, format, json, dead_code)),
+        Ok(Invocation::ListBuiltins) => {
+            for line in builtin::listing() {
+                println!("{}", line);
+            }
+            process::exit(0);
+        }
+        Ok(Invocation::Asm { input, out, load_address }) => process::exit(run_asm(&input, &out, load_address)),
+        Ok(Invocation::Dump { path }) => process::exit(run_dump(&path)),
+        Ok(Invocation::TestSuite { manifest, update }) => process::exit(run_test_suite(&manifest, update)),
+        Ok(Invocation::Compare { rom, format, left, right, cycles, keys, seed, to_the_end }) => {
+            process::exit(run_compare(&rom, format, (&left, &right), cycles, &keys, seed, to_the_end))
+        }
+        Err(error) => error.exit(),
+    };
+    config.file = match ConfigFile::parse(found.as_ref().map_or("", |(_, text)| text), config.kb_layout) {
+        Ok(file) => file,
+        Err(error) => {
+            let path = found.map(|(path, _)| path).unwrap_or_default();
+            eprintln!("Problem reading {}: {}", path.display(), error);
+            process::exit(1);
+        }
+    };
+    config.keymap = config.file.keymap.clone();
+    config.file_path = found.map(|(path, _)| path).or_else(|| config_dir().map(|dir| dir.join(CONFIG_FILE_NAME)));
+    config.database = match load_settings_database() {
+        Ok(database) => database,
+        Err(error) => {
+            eprintln!("{}", error);
+            process::exit(1);
+        }
+    };
 
-        assert_eq!(cpu.pc, 0x124);
+    if let Some(rom) = config.rom.as_deref().filter(|rom| romfile::is_stdin(rom)) {
+        config.stdin = match romfile::read(rom) {
+            Ok(buffer) => Some(buffer),
+            Err(error) => {
+                eprintln!("Problem reading the ROM from stdin: {}", error);
+                process::exit(1);
+            }
+        };
     }
 
-    // some test are missing
-
-    #[test]
-    fn set_vx_to_delay() {
-        let mut memory: Memory = Memory::new();
-        let display: Display = Display::new();
-        memory.write_u16(0x200, 0xF107);
-        let mut cpu = Cpu::new(memory, display);
-        cpu.delay = 0x76;
-
-        cpu.cycle();
-
-        assert_eq!(cpu.registers.v1, 0x76);
+    let path = env::current_dir();
+    info!("The current directory is {}", path.unwrap().display());
+
+    let mut rom = match &config.rom {
+        Some(path) => match load_rom(path, &config) {
+            Ok(loaded) => Some((path.clone(), loaded)),
+            Err(error) => {
+                eprintln!("{}", error);
+                process::exit(1);
+            }
+        },
+        None => None,
+    };
+    if let (Some(path), Some((_, loaded))) = (&config.import_state, rom.as_mut()) {
+        if let Err(error) = import_state(&mut loaded.cpu, path, config.force) {
+            eprintln!("Problem importing {}: {}", path.display(), error);
+            process::exit(1);
+        }
     }
 
-    #[test]
-    fn set_delay_to_vx() {
-        let mut memory: Memory = Memory::new();
-        let display: Display = Display::new();
-        memory.write_u16(0x200, 0xF115);
-        let mut cpu = Cpu::new(memory, display);
-        cpu.registers.v1 = 0x76;
-
-        cpu.cycle();
-
-        assert_eq!(cpu.delay, 0x76);
+    if config.save_settings {
+        if let Some((path, loaded)) = &rom {
+            let settings = RomSettings { name: Some(rom_name(path)), ..loaded.settings.clone() };
+            match save_rom_settings(&loaded.key, &settings) {
+                Ok(saved) => println!("Saved the settings for {} to {}", rom_name(path), saved.display()),
+                Err(error) => eprintln!("{}", error),
+            }
+        }
     }
 
-    #[test]
-    fn set_sound_to_vx() {
-        let mut memory: Memory = Memory::new();
-        let display: Display = Display::new();
-        memory.write_u16(0x200, 0xF818);
-        let mut cpu = Cpu::new(memory, display);
-        cpu.registers.v8 = 0x11;
-
-        cpu.cycle();
-
-        assert_eq!(cpu.sound, 0x11);
+    if config.headless {
+        if let Some((path, loaded)) = rom {
+            let palette = match loaded.settings.colors {
+                Some(colors) => Palette::new(CUSTOM_PALETTE_NAME, colors),
+                None => palette::built_in().remove(0),
+            };
+            process::exit(run_headless(loaded.cpu, loaded.player, &path, &palette, &config));
+        }
     }
-
-    #[test]
-    fn set_i_to_i_plus_vx() {
-        let mut memory: Memory = Memory::new();
-        let display: Display = Display::new();
-        memory.write_u16(0x200, 0xF31E);
-        let mut cpu = Cpu::new(memory, display);
-        cpu.i = 0x05;
-        cpu.registers.v3 = 0x11;
-
-        cpu.cycle();
-
-        assert_eq!(cpu.i, 22);
+    #[cfg(feature = "frontend-terminal")]
+    if config.terminal {
+        if let Some((path, loaded)) = rom {
+            process::exit(run_terminal(loaded, &path, &config));
+        }
     }
-}
\ No newline at end of file
+
+    frontends::ggez::run(config, rom)
+}
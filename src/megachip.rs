@@ -0,0 +1,284 @@
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::hash::Fnv1a;
+use crate::prelude::*;
+
+/// MEGA-CHIP's screen, entered with 0011.
+pub const MEGA_WIDTH: usize = 256;
+pub const MEGA_HEIGHT: usize = 192;
+/// Memory a MEGA-CHIP ROM can address with the 24-bit I that 01NN NNNN loads.
+pub const MEGA_CHIP_MEMORY_SIZE: usize = 0x100_0000;
+/// The palette entry a font sprite's lit pixels are drawn with in MEGA-CHIP mode, as they have
+/// no colours of their own.
+pub const FONT_COLOR: u8 = 0xFF;
+/// Bytes before the samples of a digitised sound: the sample rate as a big-endian word, the
+/// number of samples in three bytes, and one unused.
+pub const SOUND_HEADER_BYTES: usize = 6;
+// what `MegaScreen::bytes` comes to: both buffers, the palette and the alpha
+#[cfg(feature = "std")]
+const SCREEN_BYTES: usize = 2 * MEGA_WIDTH * MEGA_HEIGHT + 4 * 256 + 1;
+
+/// MEGA-CHIP's screen: a byte per pixel, indexing a palette of 256 colours, of which 0 is
+/// transparent. Sprites are drawn into one buffer while the other is shown, and 00E0 swaps them,
+/// showing what was drawn and clearing the screen for the next frame, as the Mega8 interpreter
+/// does.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MegaScreen {
+    // both row-major, MEGA_WIDTH x MEGA_HEIGHT
+    drawing: Vec<u8>,
+    shown: Vec<u8>,
+    // RGBA, whatever order 02NN loads them in
+    palette: [[u8; 4]; 256],
+    alpha: u8,
+}
+
+impl MegaScreen {
+    /// A blank screen whose palette, until 02NN loads one, is white for every colour but 0.
+    pub fn new() -> MegaScreen {
+        let mut palette = [[0xFF; 4]; 256];
+        palette[0] = [0, 0, 0, 0];
+        MegaScreen {
+            drawing: vec![0; MEGA_WIDTH * MEGA_HEIGHT],
+            shown: vec![0; MEGA_WIDTH * MEGA_HEIGHT],
+            palette,
+            alpha: 0xFF,
+        }
+    }
+
+    /// The palette index of the pixel at (`x`, `y`) on the shown screen.
+    pub fn pixel(&self, x: usize, y: usize) -> u8 {
+        self.shown[y * MEGA_WIDTH + x]
+    }
+
+    /// The palette index of the pixel at (`x`, `y`) among what's been drawn since the last 00E0.
+    pub fn drawn_pixel(&self, x: usize, y: usize) -> u8 {
+        self.drawing[y * MEGA_WIDTH + x]
+    }
+
+    pub fn palette(&self) -> &[[u8; 4]; 256] {
+        &self.palette
+    }
+
+    /// The screen's opacity, set by 05NN.
+    pub fn alpha(&self) -> u8 {
+        self.alpha
+    }
+
+    /// The RGBA colour of the shown pixel at (`x`, `y`), its palette entry's alpha scaled by the
+    /// screen's.
+    pub fn color(&self, x: usize, y: usize) -> [u8; 4] {
+        let [red, green, blue, alpha] = self.palette[self.pixel(x, y) as usize];
+        [red, green, blue, (alpha as u16 * self.alpha as u16 / 0xFF) as u8]
+    }
+
+    /// Feeds `bytes` to `hash` without collecting them first.
+    pub(crate) fn hash_into(&self, hash: &mut Fnv1a) {
+        hash.write(&self.drawing);
+        hash.write(&self.shown);
+        for color in self.palette.iter() {
+            hash.write(color);
+        }
+        hash.write(&[self.alpha]);
+    }
+
+    /// Everything that makes up the screen, for save states.
+    #[cfg(feature = "std")]
+    pub(crate) fn bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(SCREEN_BYTES);
+        bytes.extend_from_slice(&self.drawing);
+        bytes.extend_from_slice(&self.shown);
+        bytes.extend(self.palette.iter().flatten());
+        bytes.push(self.alpha);
+        bytes
+    }
+
+    /// The screen `bytes` made, or None if there are too few or too many of them.
+    #[cfg(feature = "std")]
+    pub(crate) fn from_bytes(bytes: &[u8]) -> Option<MegaScreen> {
+        if bytes.len() != SCREEN_BYTES {
+            return None;
+        }
+        let (drawing, rest) = bytes.split_at(MEGA_WIDTH * MEGA_HEIGHT);
+        let (shown, rest) = rest.split_at(MEGA_WIDTH * MEGA_HEIGHT);
+        let mut palette = [[0; 4]; 256];
+        for (entry, color) in palette.iter_mut().zip(rest.chunks_exact(4)) {
+            entry.copy_from_slice(color);
+        }
+        Some(MegaScreen { drawing: drawing.to_vec(), shown: shown.to_vec(), palette, alpha: rest[rest.len() - 1] })
+    }
+
+    pub(crate) fn set_alpha(&mut self, alpha: u8) {
+        self.alpha = alpha;
+    }
+
+    /// Loads palette entries from 1 on out of `colors`, four bytes each: alpha, red, green and
+    /// blue, as 02NN does.
+    pub(crate) fn load_palette(&mut self, colors: &[u8]) {
+        for (entry, color) in self.palette.iter_mut().skip(1).zip(colors.chunks_exact(4)) {
+            *entry = [color[1], color[2], color[3], color[0]];
+        }
+    }
+
+    /// 00E0: shows what's been drawn and starts the next frame on a blank screen.
+    pub(crate) fn clear(&mut self) {
+        self.shown.copy_from_slice(&self.drawing);
+        self.drawing.iter_mut().for_each(|pixel| *pixel = 0);
+    }
+
+    /// Copies a `width` x `height` sprite of palette indices, row by row, to (`x`, `y`), leaving
+    /// the pixels under its 0s alone. It's clipped at the edges rather than wrapped. Returns
+    /// whether any pixel it covered was `collision_color`, unless that's 0.
+    pub(crate) fn blit(&mut self, x: usize, y: usize, width: usize, height: usize, sprite: &[u8], collision_color: u8) -> bool {
+        let mut collision = false;
+        for (row, colors) in sprite.chunks(width).take(height).enumerate() {
+            for (column, &color) in colors.iter().enumerate() {
+                let (pixel_x, pixel_y) = (x + column, y + row);
+                if color == 0 || pixel_x >= MEGA_WIDTH || pixel_y >= MEGA_HEIGHT {
+                    continue;
+                }
+                let pixel = &mut self.drawing[pixel_y * MEGA_WIDTH + pixel_x];
+                collision |= collision_color != 0 && *pixel == collision_color;
+                *pixel = color;
+            }
+        }
+        collision
+    }
+
+    /// Draws a classic one-bit sprite, rows as `Display::draw_sprite` takes them, in `FONT_COLOR`.
+    pub(crate) fn draw_bitmap(&mut self, x: usize, y: usize, rows: &[u16], width: usize, collision_color: u8) -> bool {
+        let sprite: Vec<u8> = rows
+            .iter()
+            .flat_map(|&row| (0..width).map(move |bit| if (row >> (15 - bit)) & 1 != 0 { FONT_COLOR } else { 0 }))
+            .collect();
+        self.blit(x, y, width, rows.len(), &sprite, collision_color)
+    }
+
+    /// Moves what's been drawn `dx` columns right and `dy` rows down, blanking what's scrolled in.
+    pub(crate) fn scroll(&mut self, dx: isize, dy: isize) {
+        let before = self.drawing.clone();
+        for y in 0..MEGA_HEIGHT {
+            for x in 0..MEGA_WIDTH {
+                let (from_x, from_y) = (x as isize - dx, y as isize - dy);
+                let inside = (0..MEGA_WIDTH as isize).contains(&from_x) && (0..MEGA_HEIGHT as isize).contains(&from_y);
+                self.drawing[y * MEGA_WIDTH + x] = if inside { before[from_y as usize * MEGA_WIDTH + from_x as usize] } else { 0 };
+            }
+        }
+    }
+}
+
+impl Default for MegaScreen {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A digitised sound started by 060N: unsigned 8-bit samples, `length` of them from `address`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct MegaSound {
+    pub address: usize,
+    /// Samples per second.
+    pub rate: u16,
+    pub length: usize,
+    /// 0600 plays the sound over and over, 0601 once.
+    pub looping: bool,
+}
+
+impl MegaSound {
+    /// The sound whose header is at `address` in `memory`.
+    pub fn at(memory: &[u8], address: usize, looping: bool) -> MegaSound {
+        let byte = |offset: usize| *memory.get(address + offset).unwrap_or(&0) as usize;
+        MegaSound {
+            address: address + SOUND_HEADER_BYTES,
+            rate: (byte(0) << 8 | byte(1)) as u16,
+            length: byte(2) << 16 | byte(3) << 8 | byte(4),
+            looping,
+        }
+    }
+
+    /// The samples, cut short where memory ends.
+    pub fn samples<'a>(&self, memory: &'a [u8]) -> &'a [u8] {
+        let start = self.address.min(memory.len());
+        &memory[start..(start + self.length).min(memory.len())]
+    }
+}
+
+/// The registers MEGA-CHIP adds.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct MegaRegisters {
+    /// Bits 16 to 23 of I, which 01NN NNNN loads and the instructions that set I otherwise clear.
+    pub i_high: u8,
+    /// The size of the sprites DXYN draws, set by 03NN and 04NN, where NN = 0 stands for 256.
+    pub sprite_width: usize,
+    pub sprite_height: usize,
+    /// DXYN sets VF when a sprite covers a pixel of this colour; set by 09NN. 0, which blank
+    /// pixels are, never collides.
+    pub collision_color: u8,
+    /// What 060N started playing, until 0700 stops it.
+    pub sound: Option<MegaSound>,
+}
+
+impl Default for MegaRegisters {
+    fn default() -> MegaRegisters {
+        MegaRegisters { i_high: 0, sprite_width: 8, sprite_height: 8, collision_color: 0, sound: None }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn blits_skip_colour_0_and_report_the_collision_colour() {
+        let mut screen = MegaScreen::new();
+        assert!(!screen.blit(10, 20, 2, 2, &[1, 2, 3, 0], 7));
+        assert!(!screen.blit(11, 20, 1, 1, &[7], 9));
+        assert!(screen.blit(0, 0, 12, 21, &vec![0; 251].into_iter().chain([5]).collect::<Vec<u8>>(), 7));
+
+        assert_eq!((screen.drawn_pixel(10, 20), screen.drawn_pixel(11, 20)), (1, 5));
+        assert_eq!((screen.drawn_pixel(10, 21), screen.drawn_pixel(11, 21)), (3, 0));
+        // nothing's shown until 00E0
+        assert_eq!(screen.pixel(10, 20), 0);
+        screen.clear();
+        assert_eq!((screen.pixel(10, 20), screen.drawn_pixel(10, 20)), (1, 0));
+    }
+
+    #[test]
+    fn blits_are_clipped_at_the_edges() {
+        let mut screen = MegaScreen::new();
+        screen.blit(MEGA_WIDTH - 1, MEGA_HEIGHT - 1, 2, 2, &[1, 2, 3, 4], 0);
+
+        assert_eq!(screen.drawn_pixel(MEGA_WIDTH - 1, MEGA_HEIGHT - 1), 1);
+        assert_eq!(screen.drawn_pixel(0, MEGA_HEIGHT - 1), 0);
+        assert_eq!(screen.drawn_pixel(MEGA_WIDTH - 1, 0), 0);
+    }
+
+    #[test]
+    fn palettes_load_from_entry_1_as_argb() {
+        let mut screen = MegaScreen::new();
+        screen.load_palette(&[0x80, 0x10, 0x20, 0x30, 0xFF, 0xAA, 0xBB, 0xCC]);
+        screen.blit(0, 0, 2, 1, &[1, 2], 0);
+        screen.clear();
+        screen.set_alpha(0x80);
+
+        assert_eq!(screen.palette()[0], [0, 0, 0, 0]);
+        assert_eq!(screen.palette()[1], [0x10, 0x20, 0x30, 0x80]);
+        assert_eq!(screen.color(1, 0), [0xAA, 0xBB, 0xCC, 0x80]);
+        assert_eq!(screen.color(0, 0), [0x10, 0x20, 0x30, 0x40]);
+    }
+
+    #[test]
+    fn sounds_read_their_header() {
+        let mut memory = vec![0; 0x20];
+        memory[0x10..0x16].copy_from_slice(&[0x1F, 0x40, 0x00, 0x00, 0x04, 0x00]);
+        memory[0x16..0x1A].copy_from_slice(&[0x80, 0xFF, 0x00, 0x80]);
+
+        let sound = MegaSound::at(&memory, 0x10, true);
+
+        assert_eq!(sound, MegaSound { address: 0x16, rate: 8000, length: 4, looping: true });
+        assert_eq!(sound.samples(&memory), &[0x80, 0xFF, 0x00, 0x80]);
+        assert_eq!(MegaSound { length: 100, ..sound }.samples(&memory).len(), 10);
+    }
+}
@@ -0,0 +1,228 @@
+use crate::heatmap::HeatMap;
+use crate::prelude::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccessKind {
+    Read,
+    Write,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WatchMode {
+    Read,
+    Write,
+    ReadWrite,
+}
+
+impl WatchMode {
+    fn matches(self, kind: AccessKind) -> bool {
+        match self {
+            WatchMode::Read => kind == AccessKind::Read,
+            WatchMode::Write => kind == AccessKind::Write,
+            WatchMode::ReadWrite => true,
+        }
+    }
+}
+
+/// An inclusive address range that reports reads and/or writes done by instructions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Watchpoint {
+    pub start: u16,
+    pub end: u16,
+    pub mode: WatchMode,
+}
+
+impl Watchpoint {
+    fn matches(&self, address: u16, kind: AccessKind) -> bool {
+        self.start <= address && address <= self.end && self.mode.matches(kind)
+    }
+}
+
+/// A watched access; for reads `old` and `new` are both the value read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryAccess {
+    pub address: u16,
+    pub kind: AccessKind,
+    pub old: u8,
+    pub new: u8,
+}
+
+/// 4 KiB of RAM, or 64 KiB for XO-CHIP; reads and writes through `read_u8`/`write_u8` wrap
+/// around at the end.
+pub const MEMORY_SIZE: usize = 0x1000;
+pub const XO_CHIP_MEMORY_SIZE: usize = 0x10000;
+
+pub struct Memory {
+    memory: Vec<u8>,
+    watchpoints: Vec<Watchpoint>,
+    // first watched access since the last `take_watch_hit`
+    watch_hit: Option<MemoryAccess>,
+    heat_map: Option<Box<HeatMap>>,
+}
+
+impl Memory {
+    pub fn new() -> Memory {
+        Memory {
+            memory: vec![0; MEMORY_SIZE],
+            watchpoints: Vec::new(),
+            watch_hit: None,
+            heat_map: None,
+        }
+    }
+
+    /// Grows memory to `size` bytes, keeping its contents; never shrinks it.
+    pub fn grow(&mut self, size: usize) {
+        if size > self.memory.len() {
+            self.memory.resize(size, 0);
+        }
+    }
+
+    pub fn read_u8(&mut self, location: u16) -> u8 {
+        let location = (location as usize % self.memory.len()) as u16;
+        let value = self.memory[location as usize];
+        self.observe(location, AccessKind::Read, value, value);
+        value
+    }
+
+    pub fn read_u16(&mut self, location: u16) -> u8 {
+        self.memory[location as usize]
+    }
+
+    pub fn write_u8(&mut self, location: u16, value: u8) {
+        let location = (location as usize % self.memory.len()) as u16;
+        let old = self.memory[location as usize];
+        self.memory[location as usize] = value;
+        self.observe(location, AccessKind::Write, old, value);
+    }
+
+    /// Copies `bytes` in at `location` without triggering watchpoints, e.g. for the font and ROM.
+    pub fn load(&mut self, location: u16, bytes: &[u8]) {
+        let start = location as usize;
+        self.memory[start..start + bytes.len()].copy_from_slice(bytes);
+    }
+
+    pub fn bytes(&self) -> &[u8] {
+        &self.memory
+    }
+
+    pub(crate) fn bytes_mut(&mut self) -> &mut [u8] {
+        &mut self.memory
+    }
+
+    pub fn write_u16(&mut self, location: u16, value: u16) {
+        let bytes = value.to_be_bytes();
+        self.memory[location as usize] = bytes[0];
+        self.memory[location as usize + 1] = bytes[1];
+    }
+
+    pub fn add_watchpoint(&mut self, watchpoint: Watchpoint) {
+        self.watchpoints.push(watchpoint);
+    }
+
+    pub fn watchpoints(&self) -> &[Watchpoint] {
+        &self.watchpoints
+    }
+
+    pub fn take_watch_hit(&mut self) -> Option<MemoryAccess> {
+        self.watch_hit.take()
+    }
+
+    /// Starts counting accesses to the first 4 KB in a `HeatMap`, including instruction fetches
+    /// reported through `executed`.
+    pub fn enable_heat_map(&mut self) {
+        self.heat_map = Some(Box::new(HeatMap::new()));
+    }
+
+    pub fn disable_heat_map(&mut self) {
+        self.heat_map = None;
+    }
+
+    pub fn heat_map(&self) -> Option<&HeatMap> {
+        self.heat_map.as_deref()
+    }
+
+    pub fn heat_map_mut(&mut self) -> Option<&mut HeatMap> {
+        self.heat_map.as_deref_mut()
+    }
+
+    /// Marks the two bytes of the instruction at `location` as executed in the heat map, if
+    /// there is one; fetches are otherwise unobserved.
+    pub fn executed(&mut self, location: u16) {
+        if let Some(heat_map) = &mut self.heat_map {
+            heat_map.executed(location);
+            heat_map.executed(location.wrapping_add(1));
+        }
+    }
+
+    fn observe(&mut self, address: u16, kind: AccessKind, old: u8, new: u8) {
+        if let Some(heat_map) = &mut self.heat_map {
+            match kind {
+                AccessKind::Read => heat_map.read(address),
+                AccessKind::Write => heat_map.written(address),
+            }
+        }
+        if self.watch_hit.is_none() && self.watchpoints.iter().any(|watchpoint| watchpoint.matches(address, kind)) {
+            self.watch_hit = Some(MemoryAccess { address, kind, old, new });
+        }
+    }
+}
+
+impl Default for Memory {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn watchpoints_report_the_first_matching_access() {
+        let mut memory = Memory::new();
+        memory.add_watchpoint(Watchpoint { start: 0x300, end: 0x30F, mode: WatchMode::Write });
+
+        memory.read_u8(0x300);
+        memory.write_u8(0x2FF, 1);
+        memory.write_u8(0x305, 7);
+        memory.write_u8(0x306, 8);
+
+        let hit = MemoryAccess { address: 0x305, kind: AccessKind::Write, old: 0, new: 7 };
+        assert_eq!(memory.take_watch_hit(), Some(hit));
+        assert_eq!(memory.take_watch_hit(), None);
+    }
+
+    #[test]
+    fn load_is_not_watched() {
+        let mut memory = Memory::new();
+        memory.add_watchpoint(Watchpoint { start: 0x000, end: 0xFFF, mode: WatchMode::ReadWrite });
+
+        memory.load(0x200, &[1, 2, 3]);
+
+        assert_eq!(&memory.bytes()[0x200..0x203], &[1, 2, 3]);
+        assert_eq!(memory.take_watch_hit(), None);
+    }
+
+    #[test]
+    fn accesses_wrap_around_at_4k() {
+        let mut memory = Memory::new();
+
+        memory.write_u8(0x1001, 7);
+
+        assert_eq!(memory.bytes().len(), MEMORY_SIZE);
+        assert_eq!(memory.bytes()[0x001], 7);
+        assert_eq!(memory.read_u8(0xF001), 7);
+    }
+
+    #[test]
+    fn growing_keeps_the_contents_and_moves_the_wrap_around() {
+        let mut memory = Memory::new();
+        memory.write_u8(0x1000, 7);
+        memory.grow(XO_CHIP_MEMORY_SIZE);
+        memory.write_u8(0x1000, 9);
+
+        assert_eq!(memory.bytes().len(), 0x10000);
+        assert_eq!(memory.read_u8(0x000), 7);
+        assert_eq!(memory.read_u8(0x1000), 9);
+    }
+}
@@ -0,0 +1,213 @@
+use std::convert::TryFrom;
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+/// A colour written as `"#RRGGBB"` in palette files.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Rgb(pub u8, pub u8, pub u8);
+
+impl Rgb {
+    /// Parses `RRGGBB`, with or without a leading `#`.
+    pub fn parse(value: &str) -> Result<Rgb, String> {
+        let digits = value.trim().trim_start_matches('#');
+        let rgb = match u32::from_str_radix(digits, 16) {
+            Ok(rgb) if digits.len() == 6 => rgb,
+            _ => return Err(format!("{} is not an RRGGBB colour", value)),
+        };
+        Ok(Rgb((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8))
+    }
+}
+
+impl fmt::Display for Rgb {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "#{:02X}{:02X}{:02X}", self.0, self.1, self.2)
+    }
+}
+
+impl TryFrom<String> for Rgb {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Rgb, String> {
+        Rgb::parse(&value)
+    }
+}
+
+impl From<Rgb> for String {
+    fn from(rgb: Rgb) -> String {
+        rgb.to_string()
+    }
+}
+
+/// The colours the display is drawn in. Palette files hold a list of these:
+///
+/// ```toml
+/// [[palette]]
+/// name = "Ocean"
+/// colors = ["#001020", "#80C0FF", "#FF8000", "#804000"]
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Palette {
+    pub name: String,
+    /// Indexed by pixel value: background, plane 1, plane 2 and both planes. Classic ROMs only
+    /// draw on plane 1, so only the first two matter to them.
+    pub colors: [Rgb; 4],
+}
+
+impl Palette {
+    pub fn new(name: &str, colors: [Rgb; 4]) -> Palette {
+        Palette { name: String::from(name), colors }
+    }
+
+    pub fn background(&self) -> Rgb {
+        self.colors[0]
+    }
+
+    /// The colour of a display pixel, whose bits say which planes are lit.
+    pub fn color(&self, pixel: u8) -> Rgb {
+        self.colors[(pixel & 0b11) as usize]
+    }
+}
+
+/// The palettes that ship with the emulator, in the order they're cycled through.
+pub fn built_in() -> Vec<Palette> {
+    vec![
+        Palette::new("Classic", [Rgb(0x00, 0x00, 0x00), Rgb(0xFF, 0xFF, 0xFF), Rgb(0xFF, 0x66, 0x00), Rgb(0x66, 0x22, 0x00)]),
+        Palette::new("Green phosphor", [Rgb(0x00, 0x14, 0x00), Rgb(0x33, 0xFF, 0x33), Rgb(0x1A, 0x99, 0x1A), Rgb(0xAA, 0xFF, 0xAA)]),
+        Palette::new("Amber", [Rgb(0x1A, 0x0D, 0x00), Rgb(0xFF, 0xB0, 0x00), Rgb(0x99, 0x5C, 0x00), Rgb(0xFF, 0xD8, 0x80)]),
+        Palette::new("Game Boy", [Rgb(0x9B, 0xBC, 0x0F), Rgb(0x0F, 0x38, 0x0F), Rgb(0x8B, 0xAC, 0x0F), Rgb(0x30, 0x62, 0x30)]),
+        Palette::new("High contrast", [Rgb(0x00, 0x00, 0x00), Rgb(0xFF, 0xFF, 0x00), Rgb(0x00, 0xFF, 0xFF), Rgb(0xFF, 0x00, 0xFF)]),
+    ]
+}
+
+#[derive(Serialize, Deserialize)]
+struct PaletteFile {
+    #[serde(default)]
+    palette: Vec<Palette>,
+}
+
+/// Reads the `[[palette]]` entries of a palette file.
+pub fn parse(text: &str) -> Result<Vec<Palette>, toml::de::Error> {
+    toml::from_str::<PaletteFile>(text).map(|file| file.palette)
+}
+
+/// Writes `palettes` in the format `parse` reads.
+pub fn to_toml(palettes: &[Palette]) -> String {
+    let file = PaletteFile { palette: palettes.to_vec() };
+    toml::to_string(&file).expect("palettes are always serializable")
+}
+
+/// The palettes to cycle through and which one is in use.
+#[derive(Clone, Debug)]
+pub struct Palettes {
+    palettes: Vec<Palette>,
+    selected: usize,
+}
+
+impl Palettes {
+    /// The built-in palettes followed by `user` ones, starting at the first. A user palette named
+    /// like a built-in one replaces it.
+    pub fn new(user: Vec<Palette>) -> Palettes {
+        let mut palettes = Palettes { palettes: built_in(), selected: 0 };
+        for palette in user {
+            palettes.add(palette);
+        }
+        palettes
+    }
+
+    /// Adds `palette` to the end of the cycle, or replaces the one with the same name.
+    pub fn add(&mut self, palette: Palette) {
+        match self.position(&palette.name) {
+            Some(index) => self.palettes[index] = palette,
+            None => self.palettes.push(palette),
+        }
+    }
+
+    pub fn current(&self) -> &Palette {
+        &self.palettes[self.selected]
+    }
+
+    /// Moves on to the next palette, wrapping around after the last.
+    pub fn cycle(&mut self) -> &Palette {
+        self.selected = (self.selected + 1) % self.palettes.len();
+        self.current()
+    }
+
+    /// Selects the palette called `name`, ignoring case; returns false if there is none.
+    pub fn select(&mut self, name: &str) -> bool {
+        match self.position(name) {
+            Some(index) => {
+                self.selected = index;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Whether there's a palette called `name`, ignoring case.
+    pub fn contains(&self, name: &str) -> bool {
+        self.position(name).is_some()
+    }
+
+    fn position(&self, name: &str) -> Option<usize> {
+        self.palettes.iter().position(|palette| palette.name.eq_ignore_ascii_case(name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_reads_palette_entries() {
+        let text = r##"
+            # a comment
+            [[palette]]
+            name = "Ocean"
+            colors = ["#001020", "80C0FF", "#ff8000", "#804000"]
+        "##;
+
+        let palettes = parse(text).unwrap();
+
+        assert_eq!(palettes, vec![Palette::new("Ocean", [Rgb(0x00, 0x10, 0x20), Rgb(0x80, 0xC0, 0xFF), Rgb(0xFF, 0x80, 0x00), Rgb(0x80, 0x40, 0x00)])]);
+        assert_eq!(parse("").unwrap(), vec![]);
+        assert!(parse("[[palette]]\nname = \"Bad\"\ncolors = [\"#12345\", \"#000000\", \"#000000\", \"#000000\"]").is_err());
+        assert!(parse("[[palette]]\nname = \"Short\"\ncolors = [\"#000000\", \"#FFFFFF\"]").is_err());
+    }
+
+    #[test]
+    fn palettes_survive_a_round_trip_through_toml() {
+        let palettes = built_in();
+
+        assert_eq!(parse(&to_toml(&palettes)).unwrap(), palettes);
+    }
+
+    #[test]
+    fn cycle_goes_through_built_in_then_user_palettes_and_wraps() {
+        let ocean = Palette::new("Ocean", [Rgb(0, 0, 0x20); 4]);
+        let amber = Palette::new("amber", [Rgb(0x20, 0x10, 0); 4]);
+        let mut palettes = Palettes::new(vec![ocean, amber.clone()]);
+        let mut names = vec![palettes.current().name.clone()];
+        for _ in 0..6 {
+            names.push(palettes.cycle().name.clone());
+        }
+
+        assert_eq!(names, ["Classic", "Green phosphor", "amber", "Game Boy", "High contrast", "Ocean", "Classic"]);
+        assert!(palettes.select("AMBER"));
+        assert_eq!(palettes.current(), &amber);
+        assert!(!palettes.select("Missing"));
+        assert_eq!(palettes.current(), &amber);
+    }
+
+    #[test]
+    fn pixel_bits_pick_the_palette_entry() {
+        let palette = Palette::new("Test", [Rgb(0, 0, 0), Rgb(1, 1, 1), Rgb(2, 2, 2), Rgb(3, 3, 3)]);
+
+        assert_eq!(palette.background(), Rgb(0, 0, 0));
+        assert_eq!(palette.color(0b00), Rgb(0, 0, 0));
+        assert_eq!(palette.color(0b01), Rgb(1, 1, 1));
+        assert_eq!(palette.color(0b10), Rgb(2, 2, 2));
+        assert_eq!(palette.color(0b11), Rgb(3, 3, 3));
+    }
+}
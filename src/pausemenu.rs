@@ -0,0 +1,184 @@
+use crate::recent::RecentRoms;
+
+/// What the pause menu offers, top to bottom.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MenuEntry {
+    Resume,
+    Reset,
+    OpenRom,
+    ToggleFullscreen,
+    Quit,
+}
+
+impl MenuEntry {
+    pub const ALL: [MenuEntry; 5] = [MenuEntry::Resume, MenuEntry::Reset, MenuEntry::OpenRom, MenuEntry::ToggleFullscreen, MenuEntry::Quit];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            MenuEntry::Resume => "Resume",
+            MenuEntry::Reset => "Reset ROM",
+            MenuEntry::OpenRom => "Open ROM",
+            MenuEntry::ToggleFullscreen => "Toggle Fullscreen",
+            MenuEntry::Quit => "Quit",
+        }
+    }
+}
+
+/// Input the menu reacts to, from the keyboard or, by entry index, the mouse.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MenuInput {
+    Up,
+    Down,
+    /// Enter: picks the selected entry.
+    Confirm,
+    /// Esc: closes the menu, as Resume does.
+    Cancel,
+    /// The pointer moved over an entry.
+    Hover(usize),
+    Click(usize),
+    /// A digit key, for the recent ROM with this index.
+    Recent(usize),
+}
+
+/// What the window has to do after an input. Every action but `None` and `ToggleFullscreen`
+/// closes the menu.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MenuAction {
+    None,
+    Resume,
+    Reset,
+    OpenRom,
+    ToggleFullscreen,
+    Quit,
+    /// Load the recent ROM with this index.
+    OpenRecent(usize),
+}
+
+/// The menu Esc brings up over the paused game. It remembers whether the game was already paused
+/// when it opened, so closing it goes back to that rather than always resuming. Below the
+/// entries it lists the recent ROMs, which the digit keys load.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PauseMenu {
+    selected: usize,
+    was_paused: bool,
+    recent: Vec<String>,
+    recent_count: usize,
+}
+
+impl PauseMenu {
+    pub fn new(was_paused: bool, recent: &RecentRoms) -> PauseMenu {
+        PauseMenu { selected: 0, was_paused, recent: recent.lines(), recent_count: recent.roms().len() }
+    }
+
+    /// Whether the game was paused before the menu opened, and so should stay paused after.
+    pub fn was_paused(&self) -> bool {
+        self.was_paused
+    }
+
+    pub fn selected(&self) -> MenuEntry {
+        MenuEntry::ALL[self.selected]
+    }
+
+    /// Moves the selection or picks an entry. The selection wraps around at either end, and mouse
+    /// input for an index past the last entry is ignored.
+    pub fn update(&mut self, input: MenuInput) -> MenuAction {
+        let count = MenuEntry::ALL.len();
+        match input {
+            MenuInput::Up => self.selected = (self.selected + count - 1) % count,
+            MenuInput::Down => self.selected = (self.selected + 1) % count,
+            MenuInput::Confirm => return action(self.selected()),
+            MenuInput::Cancel => return MenuAction::Resume,
+            MenuInput::Hover(index) if index < count => self.selected = index,
+            MenuInput::Click(index) if index < count => {
+                self.selected = index;
+                return action(self.selected());
+            }
+            MenuInput::Recent(index) if index < self.recent_count => return MenuAction::OpenRecent(index),
+            MenuInput::Hover(_) | MenuInput::Click(_) | MenuInput::Recent(_) => {}
+        }
+        MenuAction::None
+    }
+
+    /// One line per entry, the selected one marked with `>`, then the recent ROMs after a blank
+    /// line if there are any.
+    pub fn lines(&self) -> Vec<String> {
+        let mut lines: Vec<String> = MenuEntry::ALL
+            .iter()
+            .enumerate()
+            .map(|(index, entry)| format!("{} {}", if index == self.selected { '>' } else { ' ' }, entry.label()))
+            .collect();
+        if !self.recent.is_empty() {
+            lines.push(String::new());
+            lines.extend(self.recent.iter().cloned());
+        }
+        lines
+    }
+}
+
+fn action(entry: MenuEntry) -> MenuAction {
+    match entry {
+        MenuEntry::Resume => MenuAction::Resume,
+        MenuEntry::Reset => MenuAction::Reset,
+        MenuEntry::OpenRom => MenuAction::OpenRom,
+        MenuEntry::ToggleFullscreen => MenuAction::ToggleFullscreen,
+        MenuEntry::Quit => MenuAction::Quit,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn arrow_keys_move_the_selection_around_the_entries() {
+        let mut menu = PauseMenu::new(false, &RecentRoms::default());
+
+        assert_eq!(menu.selected(), MenuEntry::Resume);
+        assert_eq!(menu.update(MenuInput::Up), MenuAction::None);
+        assert_eq!(menu.selected(), MenuEntry::Quit);
+        menu.update(MenuInput::Down);
+        menu.update(MenuInput::Down);
+        assert_eq!(menu.selected(), MenuEntry::Reset);
+        assert_eq!(menu.lines()[1], "> Reset ROM");
+        assert_eq!(menu.lines()[0], "  Resume");
+    }
+
+    #[test]
+    fn enter_and_clicks_pick_entries_and_escape_resumes() {
+        let mut menu = PauseMenu::new(true, &RecentRoms::default());
+
+        assert_eq!(menu.update(MenuInput::Confirm), MenuAction::Resume);
+        menu.update(MenuInput::Down);
+        menu.update(MenuInput::Down);
+        assert_eq!(menu.update(MenuInput::Confirm), MenuAction::OpenRom);
+        assert_eq!(menu.update(MenuInput::Cancel), MenuAction::Resume);
+        assert_eq!(menu.update(MenuInput::Click(3)), MenuAction::ToggleFullscreen);
+        assert_eq!(menu.update(MenuInput::Click(4)), MenuAction::Quit);
+        assert!(menu.was_paused());
+    }
+
+    #[test]
+    fn the_pointer_selects_what_it_is_over() {
+        let mut menu = PauseMenu::new(false, &RecentRoms::default());
+
+        assert_eq!(menu.update(MenuInput::Hover(1)), MenuAction::None);
+        assert_eq!(menu.selected(), MenuEntry::Reset);
+        assert_eq!(menu.update(MenuInput::Hover(5)), MenuAction::None);
+        assert_eq!(menu.update(MenuInput::Click(9)), MenuAction::None);
+        assert_eq!(menu.selected(), MenuEntry::Reset);
+        assert_eq!(menu.update(MenuInput::Confirm), MenuAction::Reset);
+    }
+
+    #[test]
+    fn digit_keys_load_the_recent_roms_listed() {
+        let mut recent = RecentRoms::default();
+        recent.insert(std::path::Path::new("brix.ch8"));
+        recent.insert(std::path::Path::new("pong.ch8"));
+        let mut menu = PauseMenu::new(false, &recent);
+
+        assert_eq!(menu.update(MenuInput::Recent(1)), MenuAction::OpenRecent(1));
+        assert_eq!(menu.update(MenuInput::Recent(2)), MenuAction::None);
+        assert_eq!(&menu.lines()[5..], &["", "Recent, 1 to 9 to load:", "  1 pong.ch8", "  2 brix.ch8"]);
+        assert_eq!(PauseMenu::new(false, &RecentRoms::default()).lines().len(), MenuEntry::ALL.len());
+    }
+}
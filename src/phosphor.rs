@@ -0,0 +1,133 @@
+use crate::display::{Display, Resolution, MAX_HEIGHT, MAX_WIDTH};
+
+/// Below this a fading pixel is dropped; it would round to the background colour anyway.
+const MIN_INTENSITY: f32 = 1.0 / 256.0;
+
+/// Rendering-only afterglow that hides XOR flicker: a pixel that turns on is drawn at full
+/// intensity straight away, and one that turns off keeps `decay` of its intensity every frame
+/// instead of vanishing. The display itself, and with it collision detection, is untouched.
+#[derive(Clone, Debug)]
+pub struct Phosphor {
+    decay: f32,
+    intensity: [[f32; MAX_HEIGHT]; MAX_WIDTH],
+    // the pixel value each pixel was last lit with, so a fading XO-CHIP pixel keeps its colour
+    last_lit: [[u8; MAX_HEIGHT]; MAX_WIDTH],
+    resolution: Resolution,
+}
+
+impl Phosphor {
+    /// `decay` is clamped to 0..1; 0 turns the effect off and draws exactly what's on the display.
+    pub fn new(decay: f32) -> Phosphor {
+        Phosphor {
+            decay: decay.clamp(0.0, 1.0),
+            intensity: [[0.0; MAX_HEIGHT]; MAX_WIDTH],
+            last_lit: [[0; MAX_HEIGHT]; MAX_WIDTH],
+            resolution: Resolution::Lores,
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.decay > 0.0
+    }
+
+    /// Advances the afterglow by one frame of `display`.
+    pub fn update(&mut self, display: &Display) {
+        // a resolution switch clears the screen and changes what each pixel means
+        if display.resolution() != self.resolution {
+            self.resolution = display.resolution();
+            self.intensity = [[0.0; MAX_HEIGHT]; MAX_WIDTH];
+        }
+        for (x, column) in display.pixels().iter().enumerate().take(display.width()) {
+            for (y, &pixel) in column.iter().enumerate().take(display.height()) {
+                let intensity = &mut self.intensity[x][y];
+                if pixel != 0 {
+                    *intensity = 1.0;
+                    self.last_lit[x][y] = pixel;
+                } else {
+                    *intensity = faded(*intensity, self.decay);
+                }
+            }
+        }
+    }
+
+    /// How brightly to draw the pixel at `x`, `y`, from 0 (background) to 1.
+    pub fn intensity(&self, x: usize, y: usize) -> f32 {
+        self.intensity[x][y]
+    }
+
+    /// The pixel value to colour the pixel at `x`, `y` with: what's on the display, or what was
+    /// there while it fades.
+    pub fn pixel(&self, x: usize, y: usize) -> u8 {
+        self.last_lit[x][y]
+    }
+}
+
+impl Default for Phosphor {
+    fn default() -> Phosphor {
+        Phosphor::new(0.0)
+    }
+}
+
+/// One frame of decay for a pixel that's off.
+fn faded(intensity: f32, decay: f32) -> f32 {
+    let intensity = intensity * decay;
+    if intensity < MIN_INTENSITY {
+        0.0
+    } else {
+        intensity
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn display_with_pixel() -> Display {
+        let mut display = Display::new();
+        display.draw_sprite(3, 2, &[0x8000], 8);
+        display
+    }
+
+    #[test]
+    fn faded_keeps_the_decay_fraction_until_it_is_invisible() {
+        assert_eq!(faded(1.0, 0.5), 0.5);
+        assert_eq!(faded(0.5, 0.5), 0.25);
+        assert_eq!(faded(1.0, 0.0), 0.0);
+        assert_eq!(faded(MIN_INTENSITY, 0.9), 0.0);
+    }
+
+    #[test]
+    fn erased_pixels_fade_over_several_frames() {
+        let mut display = display_with_pixel();
+        let mut phosphor = Phosphor::new(0.5);
+
+        phosphor.update(&display);
+        assert_eq!(phosphor.intensity(3, 2), 1.0);
+        display.draw_sprite(3, 2, &[0x8000], 8);
+        phosphor.update(&display);
+        assert_eq!(phosphor.intensity(3, 2), 0.5);
+        phosphor.update(&display);
+        assert_eq!(phosphor.intensity(3, 2), 0.25);
+        assert_eq!(phosphor.pixel(3, 2), 1);
+        // drawn again, it jumps straight back to full
+        display.draw_sprite(3, 2, &[0x8000], 8);
+        phosphor.update(&display);
+        assert_eq!(phosphor.intensity(3, 2), 1.0);
+        // and the display itself still only has the pixel that's really on
+        assert_eq!(display.pixels()[3][2], 1);
+        assert_eq!(display.pixels()[4][2], 0);
+    }
+
+    #[test]
+    fn off_by_default_and_mirrors_the_display_exactly() {
+        let mut display = display_with_pixel();
+        let mut phosphor = Phosphor::default();
+
+        phosphor.update(&display);
+        assert!(!phosphor.is_enabled());
+        assert_eq!(phosphor.intensity(3, 2), 1.0);
+        display.clear();
+        phosphor.update(&display);
+        assert_eq!(phosphor.intensity(3, 2), 0.0);
+    }
+}
@@ -0,0 +1,196 @@
+use core::time::Duration;
+
+use crate::instruction::Instruction;
+use crate::prelude::*;
+
+/// Instruction families counted by the profiler, indexed by `family`; the last one collects undecodable opcodes.
+pub const FAMILIES: [&str; 51] = [
+    "0NNN", "00E0", "00EE", "00CN", "00FB", "00FC", "00FD", "00FE", "00FF", "1NNN", "2NNN", "3XKK", "4XKK",
+    "5XY0", "5XY2", "5XY3", "6XKK", "7XKK", "8XY0", "8XY1", "8XY2", "8XY3", "8XY4", "8XY5", "8XY6", "8XY7",
+    "8XYE", "9XY0", "ANNN", "BNNN", "CXKK", "DXYN", "EX9E", "EXA1", "F000", "FN01", "F002", "FX3A", "FX07",
+    "FX0A", "FX15", "FX18", "FX1E", "FX29", "FX30", "FX33", "FX55", "FX65", "FX75", "FX85", "????",
+];
+
+// enough for XO-CHIP's 64 KiB
+const ADDRESSES: usize = 0x10000;
+
+fn family(opcode: u16) -> usize {
+    match Instruction::decode(opcode) {
+        Some(Instruction::Sys(_)) => 0,
+        Some(Instruction::Cls) => 1,
+        Some(Instruction::Ret) => 2,
+        Some(Instruction::ScrollDown(_)) => 3,
+        Some(Instruction::ScrollRight) => 4,
+        Some(Instruction::ScrollLeft) => 5,
+        Some(Instruction::Exit) => 6,
+        Some(Instruction::Lores) => 7,
+        Some(Instruction::Hires) => 8,
+        Some(Instruction::Jump(_)) => 9,
+        Some(Instruction::Call(_)) => 10,
+        Some(Instruction::SkipEqByte(..)) => 11,
+        Some(Instruction::SkipNeByte(..)) => 12,
+        Some(Instruction::SkipEqReg(..)) => 13,
+        Some(Instruction::SaveRange(..)) => 14,
+        Some(Instruction::LoadRange(..)) => 15,
+        Some(Instruction::LoadByte(..)) => 16,
+        Some(Instruction::AddByte(..)) => 17,
+        Some(Instruction::LoadReg(..)) => 18,
+        Some(Instruction::Or(..)) => 19,
+        Some(Instruction::And(..)) => 20,
+        Some(Instruction::Xor(..)) => 21,
+        Some(Instruction::AddReg(..)) => 22,
+        Some(Instruction::Sub(..)) => 23,
+        Some(Instruction::ShiftRight(..)) => 24,
+        Some(Instruction::SubN(..)) => 25,
+        Some(Instruction::ShiftLeft(..)) => 26,
+        Some(Instruction::SkipNeReg(..)) => 27,
+        Some(Instruction::LoadI(_)) => 28,
+        Some(Instruction::JumpV0(_)) => 29,
+        Some(Instruction::Random(..)) => 30,
+        Some(Instruction::Draw(..)) => 31,
+        Some(Instruction::SkipKeyPressed(_)) => 32,
+        Some(Instruction::SkipKeyNotPressed(_)) => 33,
+        Some(Instruction::LoadLongI) => 34,
+        Some(Instruction::Plane(_)) => 35,
+        Some(Instruction::Audio) => 36,
+        Some(Instruction::Pitch(_)) => 37,
+        Some(Instruction::LoadDelay(_)) => 38,
+        Some(Instruction::WaitKey(_)) => 39,
+        Some(Instruction::SetDelay(_)) => 40,
+        Some(Instruction::SetSound(_)) => 41,
+        Some(Instruction::AddI(_)) => 42,
+        Some(Instruction::LoadFont(_)) => 43,
+        Some(Instruction::LoadBigFont(_)) => 44,
+        Some(Instruction::StoreBcd(_)) => 45,
+        Some(Instruction::StoreRegisters(_)) => 46,
+        Some(Instruction::LoadRegisters(_)) => 47,
+        Some(Instruction::StoreFlags(_)) => 48,
+        Some(Instruction::LoadFlags(_)) => 49,
+        // `decode` leaves MEGA-CHIP's to 0NNN
+        Some(_) => 0,
+        None => 50,
+    }
+}
+
+/// Execution counters per instruction family and per pc address.
+pub struct Profile {
+    families: [u64; FAMILIES.len()],
+    addresses: Vec<u64>,
+    total: u64,
+}
+
+impl Profile {
+    pub fn new() -> Profile {
+        Profile {
+            families: [0; FAMILIES.len()],
+            addresses: vec![0; ADDRESSES],
+            total: 0,
+        }
+    }
+
+    pub fn record(&mut self, pc: u16, opcode: u16) {
+        self.families[family(opcode)] += 1;
+        self.addresses[pc as usize % ADDRESSES] += 1;
+        self.total += 1;
+    }
+
+    /// Executions per family, in the order of `FAMILIES`.
+    pub fn families(&self) -> &[u64] {
+        &self.families
+    }
+
+    /// Executions per address, indexed by pc.
+    pub fn addresses(&self) -> &[u64] {
+        &self.addresses
+    }
+
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+}
+
+impl Default for Profile {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Formats the `top` busiest families and addresses, the instruction total and the speed achieved over `elapsed`.
+pub fn report(profile: &Profile, elapsed: Duration, top: usize) -> String {
+    let mut families: Vec<(usize, u64)> = profile.families().iter().copied().enumerate().filter(|&(_, count)| count > 0).collect();
+    families.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    let mut addresses: Vec<(usize, u64)> = profile.addresses().iter().copied().enumerate().filter(|&(_, count)| count > 0).collect();
+    addresses.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+
+    let percentage = |count: u64| count as f64 * 100.0 / profile.total().max(1) as f64;
+    let mut report = String::from("Top opcodes:\n");
+    for &(index, count) in families.iter().take(top) {
+        report.push_str(&format!("  {} {:>12} {:>6.2}%\n", FAMILIES[index], count, percentage(count)));
+    }
+    report.push_str("Hot addresses:\n");
+    for &(address, count) in addresses.iter().take(top) {
+        report.push_str(&format!("  {:#05X} {:>11} {:>6.2}%\n", address, count, percentage(count)));
+    }
+
+    let seconds = elapsed.as_secs_f64();
+    let per_second = if seconds > 0.0 { profile.total() as f64 / seconds } else { 0.0 };
+    report.push_str(&format!("Total: {} instructions in {:.2}s ({:.0} per second)\n", profile.total(), seconds, per_second));
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_families_and_addresses() {
+        let mut profile = Profile::new();
+        profile.record(0x200, 0x6105);
+        profile.record(0x202, 0x7101);
+        profile.record(0x202, 0x7101);
+        profile.record(0x204, 0xFFFF);
+
+        let count = |name: &str| profile.families()[FAMILIES.iter().position(|&family| family == name).unwrap()];
+        assert_eq!(profile.total(), 4);
+        assert_eq!(count("6XKK"), 1);
+        assert_eq!(count("7XKK"), 2);
+        assert_eq!(count("????"), 1);
+        assert_eq!(profile.addresses()[0x202], 2);
+    }
+
+    #[test]
+    fn family_names_line_up_with_indices() {
+        let cases = [
+            (0x0123, "0NNN"), (0x00FF, "00FF"), (0x1200, "1NNN"), (0x8AB6, "8XY6"), (0xA123, "ANNN"),
+            (0xB200, "BNNN"), (0xD125, "DXYN"), (0xE1A1, "EXA1"), (0xF065, "FX65"), (0xFFFF, "????"),
+        ];
+
+        for &(opcode, name) in cases.iter() {
+            assert_eq!(FAMILIES[family(opcode)], name, "opcode {:#06X}", opcode);
+        }
+    }
+
+    #[test]
+    fn report_is_sorted_by_count() {
+        let mut profile = Profile::new();
+        for _ in 0..3 {
+            profile.record(0x202, 0x7101);
+        }
+        profile.record(0x200, 0x00E0);
+        profile.record(0x204, 0x1202);
+        profile.record(0x204, 0x1202);
+
+        let report = report(&profile, Duration::from_secs(2), 2);
+
+        assert_eq!(
+            report,
+            "Top opcodes:\n\
+             \x20 7XKK            3  50.00%\n\
+             \x20 1NNN            2  33.33%\n\
+             Hot addresses:\n\
+             \x20 0x202           3  50.00%\n\
+             \x20 0x204           2  33.33%\n\
+             Total: 6 instructions in 2.00s (3 per second)\n"
+        );
+    }
+}
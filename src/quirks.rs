@@ -0,0 +1,153 @@
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Shape of the sprite DXY0 draws in lores; hires always draws 16x16.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LoresBigSprite {
+    /// SUPER-CHIP 1.1: 16 rows of one byte each.
+    Tall8x16,
+    /// Octo and XO-CHIP: the same 16x16 sprite as in hires.
+    #[default]
+    Wide16x16,
+}
+
+/// What FX30 does with a digit above 9, which the SUPER-CHIP big font has no sprite for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BigFontDigits {
+    /// Use Vx modulo 10, so I always points at one of the ten big digits.
+    #[default]
+    Wrap,
+    /// Stop with `Chip8Error::InvalidBigDigit`.
+    Error,
+}
+
+/// What EX9E, EXA1 and FX29 do with a Vx above 0xF, which names no key or font digit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NibbleOperands {
+    /// Use the low nibble of Vx, as most interpreters do.
+    #[default]
+    Mask,
+    /// Stop with `Chip8Error::InvalidKey` or `Chip8Error::InvalidDigit`.
+    Error,
+}
+
+/// What 0NNN does: on the COSMAC VIP it ran the machine code at NNN, which no emulator can.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MachineCodeCalls {
+    /// Carry on with the next instruction, as modern interpreters do.
+    #[default]
+    Skip,
+    /// Stop with `Chip8Error::MachineCodeCall`.
+    Error,
+}
+
+/// Instruction set the ROM is written for. SUPER-CHIP's additions are always available, since
+/// they don't clash with anything in plain CHIP-8.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(rename_all = "lowercase"))]
+pub enum Platform {
+    #[default]
+    SuperChip,
+    /// Octo's XO-CHIP: adds FN01 and the second bitplane.
+    XoChip,
+    /// MEGA-CHIP: 16 MiB of memory and, after 0011, a 256x192 screen of palette indices that
+    /// DXYN blits colour sprites onto; see `MegaScreen`.
+    MegaChip,
+}
+
+/// Behaviours that differ between CHIP-8 interpreters; the defaults follow modern interpreters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Quirks {
+    /// SUPER-CHIP 1.1 scrolls by hires pixels even in lores, i.e. half as many lores pixels:
+    /// 00CN moves N/2 rows and 00FB/00FC move 2 columns. Off scrolls whole lores pixels.
+    pub half_pixel_lores_scroll: bool,
+    /// What DXY0 draws while in lores.
+    pub lores_big_sprite: LoresBigSprite,
+    /// What FX30 does with Vx > 9.
+    pub big_font_digits: BigFontDigits,
+    /// What EX9E, EXA1 and FX29 do with Vx > 0xF.
+    pub nibble_operands: NibbleOperands,
+    /// What 0NNN does, other than 00E0, 00EE and the SUPER-CHIP 00xx instructions.
+    pub machine_code_calls: MachineCodeCalls,
+    /// The Amiga interpreter keeps I to 12 bits: FX1E wraps it past 0xFFF and sets VF to 1 when
+    /// it does, to 0 when it doesn't. Spacefight 2091 relies on that. Off leaves VF alone and lets
+    /// I go past 0xFFF, as XO-CHIP's 64 KiB needs; accesses beyond the end of memory wrap around.
+    pub i_overflow_sets_vf: bool,
+    /// The COSMAC VIP drew sprites only during the vertical blank, so DXYN ends the frame: at
+    /// most one sprite is drawn per 60th of a second. Off draws as many as the frame has
+    /// instructions for.
+    pub display_wait: bool,
+    /// The COSMAC VIP's FX0A stored a key only once it was let go of, and some ROMs wait on that
+    /// to avoid taking one press twice. Off stores the key as soon as it's pressed, as modern
+    /// interpreters do.
+    pub fx0a_waits_for_release: bool,
+    /// The COSMAC VIP's 8XY6 and 8XYE shifted Vy and stored the result in Vx. Off shifts Vx in
+    /// place and ignores Vy, as SUPER-CHIP and most modern ROMs expect.
+    pub shift_uses_vy: bool,
+    /// The two-page hi-res CHIP-8 hack: a 64x64 screen, execution starting 0xC0 bytes into the
+    /// ROM, past the 1260 jump that stood in for the patched interpreter, and 0230 clearing the
+    /// screen. `detect_hires_chip8` says whether a ROM looks like it was written for it.
+    pub hires_chip8: bool,
+    pub platform: Platform,
+}
+
+/// The names `Quirks::profile` knows, as the command line lists them.
+pub const PROFILES: [&str; 5] = ["modern", "chip8", "schip", "xochip", "megachip"];
+
+impl Quirks {
+    /// The quirks of a well-known interpreter: `modern` (the defaults), `chip8` (the COSMAC VIP),
+    /// `schip` (SUPER-CHIP 1.1), `xochip` (Octo) or `megachip` (Mega8). `vip` and `superchip` are
+    /// accepted too.
+    pub fn profile(name: &str) -> Option<Quirks> {
+        match name.to_ascii_lowercase().as_str() {
+            "modern" => Some(Quirks::default()),
+            "chip8" | "chip-8" | "vip" => Some(Quirks {
+                display_wait: true,
+                fx0a_waits_for_release: true,
+                shift_uses_vy: true,
+                ..Quirks::default()
+            }),
+            "schip" | "superchip" => Some(Quirks {
+                half_pixel_lores_scroll: true,
+                lores_big_sprite: LoresBigSprite::Tall8x16,
+                ..Quirks::default()
+            }),
+            "xochip" | "xo-chip" => Some(Quirks { platform: Platform::XoChip, ..Quirks::default() }),
+            "megachip" | "mega-chip" => Some(Quirks { platform: Platform::MegaChip, ..Quirks::default() }),
+            _ => None,
+        }
+    }
+}
+
+/// Whether `rom` starts with the 1260 jump every two-page hi-res CHIP-8 ROM begins with.
+pub fn detect_hires_chip8(rom: &[u8]) -> bool {
+    rom.starts_with(&[0x12, 0x60])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hires_chip8_roms_are_told_apart_by_their_first_jump() {
+        // Hi-Res Astro Dodge: JP 0x260, then the patched interpreter's leftovers
+        assert!(detect_hires_chip8(&[0x12, 0x60, 0x01, 0x7A, 0x42, 0x35, 0x63, 0x00]));
+        // Pong: LD VA, 2
+        assert!(!detect_hires_chip8(&[0x6A, 0x02, 0x6B, 0x0C, 0x6C, 0x3F]));
+        // a plain ROM that jumps elsewhere first
+        assert!(!detect_hires_chip8(&[0x12, 0x4E, 0x60, 0x00]));
+        assert!(!detect_hires_chip8(&[0x12]));
+    }
+
+    #[test]
+    fn every_listed_profile_is_known() {
+        for name in PROFILES {
+            assert!(Quirks::profile(name).is_some(), "{}", name);
+        }
+        assert_eq!(Quirks::profile("modern"), Some(Quirks::default()));
+        assert_eq!(Quirks::profile("VIP"), Quirks::profile("chip8"));
+        assert!(Quirks::profile("chip8").unwrap().shift_uses_vy);
+        assert!(!Quirks::profile("schip").unwrap().shift_uses_vy);
+        assert_eq!(Quirks::profile("eti-660"), None);
+    }
+}
@@ -0,0 +1,27 @@
+use rand::rngs::SmallRng;
+use rand::{RngCore, SeedableRng};
+
+/// Source of the random bytes consumed by CXKK.
+pub trait RandomSource: Send {
+    fn next_byte(&mut self) -> u8;
+}
+
+/// The generator a new `Cpu` starts with, seeded from the OS.
+#[cfg(all(feature = "std", not(target_arch = "wasm32")))]
+pub(crate) fn entropy_rng() -> SmallRng {
+    SmallRng::from_entropy()
+}
+
+/// In the browser rand has no entropy source to read and `from_entropy` panics, and without std
+/// there's no OS to ask, so start from a fixed seed; the page or the firmware passes its own to
+/// `Cpu::seed`.
+#[cfg(any(not(feature = "std"), target_arch = "wasm32"))]
+pub(crate) fn entropy_rng() -> SmallRng {
+    SmallRng::seed_from_u64(0)
+}
+
+impl RandomSource for SmallRng {
+    fn next_byte(&mut self) -> u8 {
+        (self.next_u32() & 0xFF) as u8
+    }
+}
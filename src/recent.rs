@@ -0,0 +1,132 @@
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+/// How many ROMs the list keeps: one for each of the keys 1 to 9 that load them.
+pub const RECENT_CAPACITY: usize = 9;
+
+/// The ROMs loaded most recently, newest first and each only once, as kept in `recent.toml`:
+///
+/// ```toml
+/// roms = ["/home/me/roms/pong.ch8", "builtin:ibm"]
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecentRoms {
+    #[serde(default)]
+    roms: Vec<PathBuf>,
+}
+
+impl RecentRoms {
+    pub fn parse(text: &str) -> Result<RecentRoms, toml::de::Error> {
+        let mut recent: RecentRoms = toml::from_str(text)?;
+        // the file may have been edited by hand
+        let roms = std::mem::take(&mut recent.roms);
+        for rom in roms.iter().rev() {
+            recent.insert(rom);
+        }
+        Ok(recent)
+    }
+
+    pub fn to_toml(&self) -> String {
+        toml::to_string(self).expect("paths from the file system are always serializable")
+    }
+
+    pub fn roms(&self) -> &[PathBuf] {
+        &self.roms
+    }
+
+    pub fn get(&self, index: usize) -> Option<&Path> {
+        self.roms.get(index).map(PathBuf::as_path)
+    }
+
+    /// Puts `rom` first, moving it up if it's already listed and dropping the oldest ROM if the
+    /// list is full.
+    pub fn insert(&mut self, rom: &Path) {
+        self.roms.retain(|listed| listed != rom);
+        self.roms.insert(0, rom.to_path_buf());
+        self.roms.truncate(RECENT_CAPACITY);
+    }
+
+    /// Drops the ROMs that `exists` says are gone; returns whether there were any.
+    pub fn prune(&mut self, exists: impl Fn(&Path) -> bool) -> bool {
+        let before = self.roms.len();
+        self.roms.retain(|rom| exists(rom));
+        self.roms.len() != before
+    }
+
+    /// A heading and one line per ROM with the key that loads it, or nothing if the list is empty.
+    pub fn lines(&self) -> Vec<String> {
+        if self.roms.is_empty() {
+            return Vec::new();
+        }
+        let mut lines = vec![String::from("Recent, 1 to 9 to load:")];
+        lines.extend(self.roms.iter().enumerate().map(|(index, rom)| format!("  {} {}", index + 1, rom.display())));
+        lines
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    fn recent(names: &[&str]) -> RecentRoms {
+        let mut recent = RecentRoms::default();
+        for name in names.iter().rev() {
+            recent.insert(Path::new(name));
+        }
+        recent
+    }
+
+    #[test]
+    fn loading_a_rom_again_moves_it_to_the_front() {
+        let mut recent = recent(&["pong.ch8", "brix.ch8", "tetris.ch8"]);
+
+        recent.insert(Path::new("tetris.ch8"));
+
+        assert_eq!(recent, self::recent(&["tetris.ch8", "pong.ch8", "brix.ch8"]));
+        assert_eq!(recent.get(1), Some(Path::new("pong.ch8")));
+        assert_eq!(recent.get(3), None);
+    }
+
+    #[test]
+    fn the_oldest_rom_drops_off_a_full_list() {
+        let mut recent = RecentRoms::default();
+
+        for index in 0..=RECENT_CAPACITY {
+            recent.insert(Path::new(&format!("{}.ch8", index)));
+        }
+
+        assert_eq!(recent.roms().len(), RECENT_CAPACITY);
+        assert_eq!(recent.get(0), Some(Path::new("9.ch8")));
+        assert!(!recent.roms().contains(&PathBuf::from("0.ch8")));
+    }
+
+    #[test]
+    fn roms_that_are_gone_are_pruned() {
+        let dir = std::env::temp_dir().join(format!("chip-8-emulator-recent-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("kept.ch8"), [0x00, 0xE0]).unwrap();
+        let mut recent = recent(&[dir.join("deleted.ch8").to_str().unwrap(), dir.join("kept.ch8").to_str().unwrap()]);
+
+        assert!(recent.prune(Path::is_file));
+        assert!(!recent.prune(Path::is_file));
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(recent.roms(), &[dir.join("kept.ch8")]);
+    }
+
+    #[test]
+    fn the_list_survives_a_round_trip_through_toml() {
+        let recent = recent(&["pong.ch8", "roms/brix.ch8"]);
+
+        assert_eq!(RecentRoms::parse(&recent.to_toml()).unwrap(), recent);
+        assert_eq!(RecentRoms::parse("").unwrap(), RecentRoms::default());
+        // duplicates and overflow from hand edits are tidied up
+        assert_eq!(RecentRoms::parse("roms = [\"a.ch8\", \"b.ch8\", \"a.ch8\"]\n").unwrap(), self::recent(&["a.ch8", "b.ch8"]));
+        assert!(RecentRoms::parse("roms = 3\n").is_err());
+        assert_eq!(recent.lines(), vec!["Recent, 1 to 9 to load:", "  1 pong.ch8", "  2 roms/brix.ch8"]);
+        assert!(RecentRoms::default().lines().is_empty());
+    }
+}
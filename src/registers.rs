@@ -0,0 +1,151 @@
+use core::fmt;
+use core::ops::{Index, IndexMut};
+use core::slice;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// VF, which arithmetic, shifts and DXYN set as a flag.
+pub const VF: u8 = 0xF;
+
+/// The sixteen general-purpose registers, V0 to VF. Indexing with a register number above 0xF
+/// panics; `get` is the checked alternative.
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Registers([u8; 16]);
+
+impl Registers {
+    /// Register `index`'s value, or `None` if there's no such register.
+    pub fn get(&self, index: u8) -> Option<u8> {
+        self.0.get(index as usize).copied()
+    }
+
+    /// The values from V0 to VF.
+    pub fn iter(&self) -> impl Iterator<Item = u8> + '_ {
+        self.0.iter().copied()
+    }
+
+    pub fn as_slice(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl From<[u8; 16]> for Registers {
+    fn from(values: [u8; 16]) -> Registers {
+        Registers(values)
+    }
+}
+
+impl<'a> IntoIterator for &'a Registers {
+    type Item = &'a u8;
+    type IntoIter = slice::Iter<'a, u8>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.iter()
+    }
+}
+
+impl Index<u8> for Registers {
+    type Output = u8;
+
+    fn index(&self, index: u8) -> &Self::Output {
+        match self.0.get(index as usize) {
+            Some(value) => value,
+            None => panic!("no register V{:X}; they run from V0 to VF", index),
+        }
+    }
+}
+
+impl IndexMut<u8> for Registers {
+    fn index_mut(&mut self, index: u8) -> &mut Self::Output {
+        match self.0.get_mut(index as usize) {
+            Some(value) => value,
+            None => panic!("no register V{:X}; they run from V0 to VF", index),
+        }
+    }
+}
+
+/// `V0=00 V1=2A ... VF=01`, in hex.
+impl fmt::Display for Registers {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (index, value) in self.iter().enumerate() {
+            if index > 0 {
+                f.write_str(" ")?;
+            }
+            write!(f, "V{:X}={:02X}", index, value)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for Registers {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Registers({})", self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn counting() -> Registers {
+        let mut values = [0; 16];
+        for (index, value) in values.iter_mut().enumerate() {
+            *value = index as u8 * 0x11;
+        }
+        Registers::from(values)
+    }
+
+    #[test]
+    fn formats_every_register_in_hex() {
+        let mut registers = Registers::default();
+        registers[1] = 0x2A;
+        registers[VF] = 1;
+
+        assert_eq!(
+            registers.to_string(),
+            "V0=00 V1=2A V2=00 V3=00 V4=00 V5=00 V6=00 V7=00 V8=00 V9=00 VA=00 VB=00 VC=00 VD=00 VE=00 VF=01"
+        );
+        assert_eq!(format!("{:?}", Registers::default()), format!("Registers({})", Registers::default()));
+    }
+
+    #[test]
+    fn iterates_from_v0_to_vf() {
+        let registers = counting();
+
+        let values: Vec<u8> = registers.iter().collect();
+
+        assert_eq!(values.len(), 16);
+        assert_eq!(values[0], 0x00);
+        assert_eq!(values[0xA], 0xAA);
+        assert_eq!(values[0xF], 0xFF);
+        assert_eq!(registers.as_slice(), &values[..]);
+        assert_eq!((&registers).into_iter().copied().collect::<Vec<_>>(), values);
+    }
+
+    #[test]
+    fn get_checks_the_register_number() {
+        let registers = counting();
+
+        assert_eq!(registers.get(0x3), Some(0x33));
+        assert_eq!(registers.get(VF), Some(0xFF));
+        assert_eq!(registers.get(0x10), None);
+        assert_eq!(registers.get(0xFF), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "no register V10")]
+    fn indexing_past_vf_panics() {
+        let _ = Registers::default()[0x10];
+    }
+
+    #[test]
+    fn serializes_as_the_sixteen_values() {
+        let registers = counting();
+
+        let json = serde_json::to_string(&registers).unwrap();
+
+        assert_eq!(json, "[0,17,34,51,68,85,102,119,136,153,170,187,204,221,238,255]");
+        assert_eq!(serde_json::from_str::<Registers>(&json).unwrap(), registers);
+    }
+}
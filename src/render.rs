@@ -0,0 +1,254 @@
+use crate::display::Display;
+use crate::keymap::KEYPAD_LAYOUT;
+use crate::palette::{Palette, Rgb};
+use crate::phosphor::Phosphor;
+
+/// Colours the display with `palette` as row-major RGBA bytes, one texel per display pixel: the
+/// texture the window scales up, with or without the CRT shader. While `phosphor` is enabled
+/// erased pixels are blended towards the background by their remaining intensity.
+pub fn frame_rgba(display: &Display, palette: &Palette, phosphor: &Phosphor) -> Vec<u8> {
+    let mut rgba = Vec::new();
+    frame_rgba_into(display, palette, phosphor, &mut rgba);
+    rgba
+}
+
+/// Like `frame_rgba`, writing into `out` so a buffer kept between frames is reused. The
+/// MEGA-CHIP screen brings its own palette, which is used instead, with no afterglow.
+pub fn frame_rgba_into(display: &Display, palette: &Palette, phosphor: &Phosphor, out: &mut Vec<u8>) {
+    if let Some(mega) = display.megachip() {
+        display.to_rgba_with(out, |x, y| mega.color(x, y));
+        return;
+    }
+    let background = palette.background();
+    display.to_rgba_with(out, |x, y| {
+        let color = if phosphor.is_enabled() {
+            blend(background, palette.color(phosphor.pixel(x, y)), phosphor.intensity(x, y))
+        } else {
+            palette.color(display.pixels()[x][y])
+        };
+        [color.0, color.1, color.2, 0xFF]
+    });
+}
+
+/// Where the display goes in an area of the window: each display pixel is `scale` window pixels
+/// square and the top-left corner is at (`x`, `y`), leaving bars either side when the aspect
+/// ratios differ.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Viewport {
+    pub scale: f32,
+    pub x: f32,
+    pub y: f32,
+}
+
+/// The largest viewport that shows a `display_width` x `display_height` display in a
+/// `width` x `height` area without stretching it, centred. With `integer` the scale is rounded
+/// down to a whole number so every display pixel is the same size, unless the area is too small
+/// for even one window pixel per display pixel.
+pub fn fit(width: f32, height: f32, display_width: usize, display_height: usize, integer: bool) -> Viewport {
+    let (display_width, display_height) = (display_width as f32, display_height as f32);
+    let mut scale = (width / display_width).min(height / display_height).max(0.0);
+    if integer && scale >= 1.0 {
+        scale = scale.floor();
+    }
+    Viewport {
+        scale,
+        x: (width - display_width * scale) / 2.0,
+        y: (height - display_height * scale) / 2.0,
+    }
+}
+
+impl Viewport {
+    /// The display pixel under the window position (`x`, `y`), or None on the bars around it.
+    pub fn display_position(&self, x: f32, y: f32, display_width: usize, display_height: usize) -> Option<(usize, usize)> {
+        if self.scale <= 0.0 {
+            return None;
+        }
+        let column = ((x - self.x) / self.scale).floor();
+        let row = ((y - self.y) / self.scale).floor();
+        if column < 0.0 || row < 0.0 || column >= display_width as f32 || row >= display_height as f32 {
+            return None;
+        }
+        Some((column as usize, row as usize))
+    }
+}
+
+/// The clickable on-screen keypad: a 4x4 grid of square cells, each `cell` window pixels wide,
+/// with its top-left corner at (`x`, `y`), laid out as `KEYPAD_LAYOUT`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct KeypadPanel {
+    pub x: f32,
+    pub y: f32,
+    pub cell: f32,
+}
+
+impl KeypadPanel {
+    /// The largest keypad that fits the `width` x `height` area at (`x`, `y`), centred in it.
+    pub fn fit(x: f32, y: f32, width: f32, height: f32) -> KeypadPanel {
+        let side = width.min(height).max(0.0);
+        KeypadPanel { x: x + (width - side) / 2.0, y: y + (height - side) / 2.0, cell: side / 4.0 }
+    }
+
+    /// The key under the window position (`x`, `y`), or None outside the panel.
+    pub fn key_at(&self, x: f32, y: f32) -> Option<u8> {
+        if self.cell <= 0.0 {
+            return None;
+        }
+        let column = ((x - self.x) / self.cell).floor();
+        let row = ((y - self.y) / self.cell).floor();
+        if column < 0.0 || row < 0.0 || column >= 4.0 || row >= 4.0 {
+            return None;
+        }
+        Some(KEYPAD_LAYOUT[row as usize * 4 + column as usize])
+    }
+
+    /// Every key with the top-left corner of its cell, row by row.
+    pub fn cells(&self) -> Vec<(u8, f32, f32)> {
+        KEYPAD_LAYOUT
+            .iter()
+            .enumerate()
+            .map(|(index, &key)| (key, self.x + (index % 4) as f32 * self.cell, self.y + (index / 4) as f32 * self.cell))
+            .collect()
+    }
+}
+
+/// How `--visual-buzzer` shows that the sound timer is running, for playing muted: a border
+/// around the play area, a speaker in its corner, or nothing. Either way it's drawn over the
+/// window, never into the display, so screenshots stay as the ROM drew them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum VisualBuzzer {
+    #[default]
+    Off,
+    Border,
+    Icon,
+}
+
+impl VisualBuzzer {
+    /// The indicator called `name`: `border`, `icon` or `off`.
+    pub fn parse(name: &str) -> Option<VisualBuzzer> {
+        match name.to_ascii_lowercase().as_str() {
+            "off" => Some(VisualBuzzer::Off),
+            "border" => Some(VisualBuzzer::Border),
+            "icon" => Some(VisualBuzzer::Icon),
+            _ => None,
+        }
+    }
+
+    /// Whether to draw the indicator over a frame that ended with the sound timer at
+    /// `sound_timer`: from the frame it's set until the frame it runs out, as the beep sounds.
+    pub fn shows(self, sound_timer: u8) -> bool {
+        self != VisualBuzzer::Off && sound_timer > 0
+    }
+}
+
+/// Mixes `color` into `background`; an intensity of 1 gives `color` itself.
+fn blend(background: Rgb, color: Rgb, intensity: f32) -> Rgb {
+    let mix = |from: u8, to: u8| (from as f32 + (to as f32 - from as f32) * intensity).round() as u8;
+    Rgb(mix(background.0, color.0), mix(background.1, color.1), mix(background.2, color.2))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn palette() -> Palette {
+        Palette::new("Test", [Rgb(0, 0, 0), Rgb(200, 100, 50), Rgb(1, 2, 3), Rgb(4, 5, 6)])
+    }
+
+    #[test]
+    fn frame_colours_each_pixel_without_a_gpu() {
+        let mut display = Display::new();
+        display.draw_sprite(1, 0, &[0x8000], 8);
+
+        let rgba = frame_rgba(&display, &palette(), &Phosphor::default());
+
+        assert_eq!(rgba.len(), 64 * 32 * 4);
+        assert_eq!(&rgba[..8], &[0, 0, 0, 0xFF, 200, 100, 50, 0xFF]);
+    }
+
+    #[test]
+    fn fading_pixels_are_blended_towards_the_background() {
+        let mut display = Display::new();
+        display.draw_sprite(0, 0, &[0x8000], 8);
+        let mut phosphor = Phosphor::new(0.5);
+        phosphor.update(&display);
+        display.clear();
+        phosphor.update(&display);
+
+        let rgba = frame_rgba(&display, &palette(), &phosphor);
+
+        assert_eq!(&rgba[..4], &[100, 50, 25, 0xFF]);
+    }
+
+    #[test]
+    fn fit_fills_a_matching_window() {
+        assert_eq!(fit(640.0, 320.0, 64, 32, false), Viewport { scale: 10.0, x: 0.0, y: 0.0 });
+        assert_eq!(fit(640.0, 320.0, 128, 64, false), Viewport { scale: 5.0, x: 0.0, y: 0.0 });
+    }
+
+    #[test]
+    fn fit_letterboxes_ultrawide_and_portrait_windows() {
+        // 21:9 fullscreen: bars left and right
+        assert_eq!(fit(2560.0, 1080.0, 64, 32, false), Viewport { scale: 33.75, x: 200.0, y: 0.0 });
+        // portrait: bars above and below
+        assert_eq!(fit(1080.0, 1920.0, 128, 64, false), Viewport { scale: 8.4375, x: 0.0, y: 690.0 });
+    }
+
+    #[test]
+    fn integer_fit_snaps_to_whole_pixels_and_stays_centred() {
+        assert_eq!(fit(640.0, 320.0, 64, 32, true), Viewport { scale: 10.0, x: 0.0, y: 0.0 });
+        assert_eq!(fit(700.0, 330.0, 64, 32, true), Viewport { scale: 10.0, x: 30.0, y: 5.0 });
+        assert_eq!(fit(1000.0, 1000.0, 128, 64, true), Viewport { scale: 7.0, x: 52.0, y: 276.0 });
+        assert_eq!(fit(127.0, 100.0, 64, 32, true), Viewport { scale: 1.0, x: 31.5, y: 34.0 });
+    }
+
+    #[test]
+    fn windows_smaller_than_the_display_shrink_it() {
+        assert_eq!(fit(32.0, 32.0, 64, 32, false), Viewport { scale: 0.5, x: 0.0, y: 8.0 });
+        // too small to snap, so integer scaling falls back to the fractional scale
+        assert_eq!(fit(32.0, 32.0, 64, 32, true), Viewport { scale: 0.5, x: 0.0, y: 8.0 });
+        assert_eq!(fit(0.0, 0.0, 64, 32, true), Viewport { scale: 0.0, x: 0.0, y: 0.0 });
+    }
+
+    #[test]
+    fn display_position_accounts_for_the_bars() {
+        let viewport = fit(2560.0, 1080.0, 64, 32, false);
+
+        assert_eq!(viewport.display_position(200.0, 0.0, 64, 32), Some((0, 0)));
+        assert_eq!(viewport.display_position(2359.0, 1079.0, 64, 32), Some((63, 31)));
+        assert_eq!(viewport.display_position(199.0, 500.0, 64, 32), None);
+        assert_eq!(viewport.display_position(2360.0, 500.0, 64, 32), None);
+    }
+
+    #[test]
+    fn the_visual_buzzer_follows_the_sound_timer() {
+        assert_eq!(VisualBuzzer::parse("Border"), Some(VisualBuzzer::Border));
+        assert_eq!(VisualBuzzer::parse("flash"), None);
+        assert!(VisualBuzzer::Icon.shows(1));
+        assert!(!VisualBuzzer::Icon.shows(0));
+        assert!(!VisualBuzzer::Off.shows(255));
+    }
+
+    #[test]
+    fn keypad_fits_its_area_as_a_centred_square() {
+        assert_eq!(KeypadPanel::fit(500.0, 8.0, 144.0, 304.0), KeypadPanel { x: 500.0, y: 88.0, cell: 36.0 });
+        assert_eq!(KeypadPanel::fit(0.0, 0.0, -10.0, 100.0).key_at(0.0, 0.0), None);
+    }
+
+    #[test]
+    fn keypad_clicks_land_on_the_key_under_them() {
+        let keypad = KeypadPanel::fit(500.0, 8.0, 144.0, 304.0);
+
+        // the corners of the panel are the first and last pixels inside it
+        assert_eq!(keypad.key_at(500.0, 88.0), Some(0x1));
+        assert_eq!(keypad.key_at(643.9, 231.9), Some(0xF));
+        assert_eq!(keypad.key_at(535.9, 88.0), Some(0x1));
+        assert_eq!(keypad.key_at(536.0, 88.0), Some(0x2));
+        assert_eq!(keypad.key_at(536.0, 196.0), Some(0x0));
+        // just outside each edge, including the letterbox above and below
+        assert_eq!(keypad.key_at(499.9, 100.0), None);
+        assert_eq!(keypad.key_at(644.0, 100.0), None);
+        assert_eq!(keypad.key_at(550.0, 87.9), None);
+        assert_eq!(keypad.key_at(550.0, 232.0), None);
+        assert_eq!(keypad.cells()[13], (0x0, 536.0, 196.0));
+    }
+}
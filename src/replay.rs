@@ -0,0 +1,442 @@
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::cpu::{Cpu, CYCLES_PER_FRAME};
+use crate::debugger::Debugger;
+use crate::error::Chip8Error;
+use crate::rplflags::RPL_FLAGS;
+use crate::settings::RomSettings;
+use crate::timing::Timing;
+
+/// Roughly how many instructions apart a recording checks the machine state, about ten seconds
+/// at the default speed. Checkpoints only fall on frame boundaries, so they can land a little later.
+pub const CHECKPOINT_INTERVAL: u64 = 6000;
+
+/// A keypad key going down or up just before instruction `instruction` executed, counted as
+/// `Cpu::instructions_executed` does.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InputEvent {
+    pub instruction: u64,
+    pub key: u8,
+    pub pressed: bool,
+}
+
+/// The hash of the whole machine state once `instruction` instructions had executed.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Checkpoint {
+    pub instruction: u64,
+    pub state: String,
+}
+
+/// Everything needed to run a ROM again exactly as it ran: the ROM it was recorded with, the
+/// CXKK seed, the RPL flags it started with, the quirks and speed, and the keypad input.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Recording {
+    /// The ROM's hash, as `settings::rom_key` spells it.
+    pub rom: String,
+    pub seed: u64,
+    pub rpl_flags: [u8; RPL_FLAGS],
+    /// Every quirk and the cycles per frame, so the settings database can't change the run.
+    pub settings: RomSettings,
+    /// How many instructions had executed when the recording stopped.
+    pub instructions: u64,
+    pub events: Vec<InputEvent>,
+    pub checkpoints: Vec<Checkpoint>,
+    /// The instruction count at the first debugger edit made while recording. Input alone
+    /// can't bring the replay to the same state past it, so checkpoints from there on can't match.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub edited_at: Option<u64>,
+}
+
+#[derive(Debug)]
+pub enum ReplayError {
+    Io(io::Error),
+    Invalid(String),
+    WrongRom { recorded: String, loaded: String },
+    /// The replay matched the recording at instruction `after` but not at `by`.
+    Diverged { after: u64, by: u64 },
+    /// The recording or the replay was edited by hand at instruction `at`, so the checkpoints
+    /// from there on can't be checked.
+    Edited { at: u64 },
+    Halted(Chip8Error),
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReplayError::Io(error) => write!(f, "could not access recording: {}", error),
+            ReplayError::Invalid(reason) => write!(f, "not a valid recording: {}", reason),
+            ReplayError::WrongRom { recorded, loaded } => {
+                write!(f, "the recording is of ROM {}, but ROM {} is loaded", recorded, loaded)
+            }
+            ReplayError::Diverged { after, by } => {
+                write!(f, "replay diverged from the recording between instructions {} and {}", after, by)
+            }
+            ReplayError::Edited { at } => write!(f, "the run was edited by hand at instruction {}, so it can't be checked past there", at),
+            ReplayError::Halted(error) => write!(f, "CPU halted: {}", error),
+        }
+    }
+}
+
+impl Error for ReplayError {}
+
+impl From<io::Error> for ReplayError {
+    fn from(error: io::Error) -> Self {
+        ReplayError::Io(error)
+    }
+}
+
+impl Recording {
+    pub fn parse(text: &str) -> Result<Recording, ReplayError> {
+        let recording: Recording = serde_json::from_str(text).map_err(|error| ReplayError::Invalid(error.to_string()))?;
+        if let Some(event) = recording.events.iter().find(|event| event.key > 0xF) {
+            return Err(ReplayError::Invalid(format!("key {} at instruction {} is not a keypad key", event.key, event.instruction)));
+        }
+        if recording.events.windows(2).any(|pair| pair[0].instruction > pair[1].instruction) {
+            return Err(ReplayError::Invalid(String::from("events are out of order")));
+        }
+        if recording.checkpoints.windows(2).any(|pair| pair[0].instruction >= pair[1].instruction) {
+            return Err(ReplayError::Invalid(String::from("checkpoints are out of order")));
+        }
+        Ok(recording)
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("recordings are always serializable")
+    }
+
+    pub fn write_to(&self, path: &Path) -> Result<(), ReplayError> {
+        fs::write(path, self.to_json())?;
+        Ok(())
+    }
+
+    pub fn read_from(path: &Path) -> Result<Recording, ReplayError> {
+        Recording::parse(&fs::read_to_string(path)?)
+    }
+
+    /// The instructions per frame the recording ran at; the replay has to run frames the same
+    /// length, as the timers tick between them.
+    pub fn cycles_per_frame(&self) -> usize {
+        self.settings.cycles_per_frame.unwrap_or(CYCLES_PER_FRAME)
+    }
+
+    /// The timing model the recording ran under, which the replay has to share for the same reason.
+    pub fn timing(&self) -> Timing {
+        self.settings.timing.unwrap_or_default()
+    }
+}
+
+/// The hash the checkpoints compare, `Cpu::state_hash` in hex.
+pub fn state_hash(cpu: &Cpu) -> String {
+    format!("{:016X}", cpu.state_hash())
+}
+
+/// Logs keypad input against the instruction count while a ROM runs. Start it on a freshly
+/// loaded Cpu, before the first instruction.
+pub struct Recorder {
+    recording: Recording,
+    next_checkpoint: u64,
+}
+
+impl Recorder {
+    /// `settings` are the ones the ROM was loaded with; only the quirks and speed are kept.
+    pub fn new(cpu: &Cpu, seed: u64, settings: &RomSettings) -> Recorder {
+        let settings = RomSettings {
+            cycles_per_frame: Some(settings.cycles_per_frame.unwrap_or(CYCLES_PER_FRAME)),
+            timing: Some(settings.timing.unwrap_or_default()),
+            ..RomSettings::from_quirks(cpu.quirks())
+        };
+        let recording = Recording {
+            rom: format!("{:016X}", cpu.rom_hash()),
+            seed,
+            rpl_flags: *cpu.rpl_flags(),
+            settings,
+            instructions: cpu.instructions_executed(),
+            events: Vec::new(),
+            checkpoints: Vec::new(),
+            edited_at: None,
+        };
+        Recorder { recording, next_checkpoint: cpu.instructions_executed() + CHECKPOINT_INTERVAL }
+    }
+
+    /// Notes that `key` went down or up before the next instruction. Call it only for changes:
+    /// a key that is already down being pressed again is no event.
+    pub fn key(&mut self, cpu: &Cpu, key: u8, pressed: bool) {
+        self.recording.events.push(InputEvent { instruction: cpu.instructions_executed(), key, pressed });
+    }
+
+    /// Call after every frame; takes a checkpoint when one is due.
+    pub fn frame(&mut self, cpu: &Cpu) {
+        let instruction = cpu.instructions_executed();
+        if instruction >= self.next_checkpoint {
+            self.recording.checkpoints.push(Checkpoint { instruction, state: state_hash(cpu) });
+            self.next_checkpoint = instruction + CHECKPOINT_INTERVAL;
+        }
+    }
+
+    /// The recording up to now, ending with a checkpoint of the final state.
+    pub fn finish(mut self, cpu: &Cpu) -> Recording {
+        let instruction = cpu.instructions_executed();
+        if self.recording.checkpoints.last().map(|checkpoint| checkpoint.instruction) != Some(instruction) {
+            self.recording.checkpoints.push(Checkpoint { instruction, state: state_hash(cpu) });
+        }
+        self.recording.instructions = instruction;
+        self.recording.edited_at = cpu.edited_at();
+        self.recording
+    }
+}
+
+/// Feeds a recording's input back into a Cpu, in place of the keyboard, and checks the run
+/// against its checkpoints.
+pub struct Player {
+    recording: Recording,
+    next_event: usize,
+    next_checkpoint: usize,
+    // the last instruction count the state was known to match at
+    matched: u64,
+}
+
+impl Player {
+    /// Refuses a Cpu that isn't running the recorded ROM, and otherwise gives it the recorded
+    /// seed and RPL flags. The Cpu should be freshly loaded with the recording's settings, which
+    /// the quirks have to be set from before the ROM is loaded.
+    pub fn new(recording: Recording, cpu: &mut Cpu) -> Result<Player, ReplayError> {
+        let loaded = format!("{:016X}", cpu.rom_hash());
+        if recording.rom != loaded {
+            return Err(ReplayError::WrongRom { recorded: recording.rom, loaded });
+        }
+        cpu.seed(recording.seed);
+        cpu.set_rpl_flags(recording.rpl_flags);
+        let matched = cpu.instructions_executed();
+        Ok(Player { recording, next_event: 0, next_checkpoint: 0, matched })
+    }
+
+    pub fn recording(&self) -> &Recording {
+        &self.recording
+    }
+
+    /// Presses and releases whatever the recording did up to the current instruction; call it
+    /// before running each frame.
+    ///
+    /// FX0A is why this goes event by event rather than setting the keys to their final state.
+    /// While FX0A waits it runs again every cycle, so the instruction count keeps advancing and
+    /// each event lands on the same pass through the wait as when it was recorded. Events that
+    /// share an instruction are applied in their recorded order, so a key pressed and released
+    /// between two instructions goes unseen by FX0A here just as it did then.
+    pub fn apply(&mut self, cpu: &mut Cpu) {
+        let instruction = cpu.instructions_executed();
+        while let Some(event) = self.recording.events.get(self.next_event) {
+            if event.instruction > instruction {
+                break;
+            }
+            if event.pressed {
+                cpu.keys_mut().press(event.key);
+            } else {
+                cpu.keys_mut().release(event.key);
+            }
+            self.next_event += 1;
+        }
+    }
+
+    /// Compares the state with any checkpoint reached since the last call; call it after each
+    /// frame. A checkpoint skipped over counts as a divergence, since the recording only took
+    /// them between frames, and one after an edit by hand, to the recording or the replay, as
+    /// the edit.
+    pub fn check(&mut self, cpu: &Cpu) -> Result<(), ReplayError> {
+        let instruction = cpu.instructions_executed();
+        let edited_at = match (self.recording.edited_at, cpu.edited_at()) {
+            (Some(recorded), Some(replayed)) => Some(recorded.min(replayed)),
+            (recorded, replayed) => recorded.or(replayed),
+        };
+        while let Some(checkpoint) = self.recording.checkpoints.get(self.next_checkpoint) {
+            if checkpoint.instruction > instruction {
+                break;
+            }
+            if let Some(at) = edited_at.filter(|&at| checkpoint.instruction >= at) {
+                return Err(ReplayError::Edited { at });
+            }
+            if checkpoint.instruction < instruction || checkpoint.state != state_hash(cpu) {
+                return Err(ReplayError::Diverged { after: self.matched, by: checkpoint.instruction });
+            }
+            self.matched = checkpoint.instruction;
+            self.next_checkpoint += 1;
+        }
+        Ok(())
+    }
+
+    /// Whether the replay has reached the point where the recording stopped, or the ROM has
+    /// stopped before it, which leaves nothing more to play.
+    pub fn is_finished(&self, cpu: &Cpu) -> bool {
+        cpu.instructions_executed() >= self.recording.instructions || cpu.halted().is_some()
+    }
+}
+
+/// Plays a whole recording back without a window, frame by frame as the window runs it, and
+/// checks every checkpoint on the way.
+pub fn run(cpu: &mut Cpu, player: &mut Player) -> Result<(), ReplayError> {
+    let mut debugger = Debugger::new();
+    debugger.set_cycles_per_frame(player.recording().cycles_per_frame());
+    debugger.set_timing(player.recording().timing());
+    while !player.is_finished(cpu) {
+        player.apply(cpu);
+        debugger.run_frame(cpu).map_err(ReplayError::Halted)?;
+        player.check(cpu)?;
+    }
+    player.check(cpu)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::display::Display;
+    use crate::edit::{Edit, Target};
+    use crate::memory::Memory;
+
+    // waits for a key into V0, then keeps drawing random bytes into V1 and counting up V2
+    const ROM: [u8; 8] = [0xF0, 0x0A, 0xC1, 0xFF, 0x72, 0x01, 0x12, 0x02];
+    const SEED: u64 = 42;
+
+    fn fresh_cpu(rom: &[u8]) -> Cpu {
+        let mut cpu = Cpu::new(Memory::new(), Display::new());
+        cpu.init(rom.to_vec());
+        cpu
+    }
+
+    /// Runs `frames` frames the way the window does, with `inputs` of (frame, key, pressed)
+    /// arriving between frames, and returns the recording and the Cpu it was made on.
+    fn record(frames: usize, inputs: &[(usize, u8, bool)]) -> (Recording, Cpu) {
+        let mut cpu = fresh_cpu(&ROM);
+        cpu.seed(SEED);
+        let mut recorder = Recorder::new(&cpu, SEED, &RomSettings::default());
+        let mut debugger = Debugger::new();
+        for frame in 0..frames {
+            for &(_, key, pressed) in inputs.iter().filter(|input| input.0 == frame) {
+                if pressed {
+                    cpu.keys_mut().press(key);
+                } else {
+                    cpu.keys_mut().release(key);
+                }
+                recorder.key(&cpu, key, pressed);
+            }
+            debugger.run_frame(&mut cpu).unwrap();
+            recorder.frame(&cpu);
+        }
+        (recorder.finish(&cpu), cpu)
+    }
+
+    fn replay(recording: Recording) -> Result<Cpu, ReplayError> {
+        let mut cpu = fresh_cpu(&ROM);
+        let mut player = Player::new(recording, &mut cpu)?;
+        run(&mut cpu, &mut player)?;
+        Ok(cpu)
+    }
+
+    #[test]
+    fn replay_reproduces_the_recorded_run() {
+        let (recording, recorded) = record(1500, &[(30, 0x7, true), (31, 0x7, false)]);
+
+        let replayed = replay(recording.clone()).unwrap();
+
+        assert_eq!(replayed.save_state(), recorded.save_state());
+        assert_eq!(replayed.registers()[0], 0x7);
+        assert_eq!(replayed.instructions_executed(), recording.instructions);
+        assert!(recording.checkpoints.len() > 2);
+    }
+
+    #[test]
+    fn fx0a_gets_the_key_on_the_same_pass_through_the_wait() {
+        let (recording, recorded) = record(50, &[(20, 0xB, true), (40, 0xB, false)]);
+
+        // FX0A ran once per cycle for 20 frames before the key arrived
+        assert_eq!(recording.events[0].instruction, 20 * CYCLES_PER_FRAME as u64);
+        let replayed = replay(recording).unwrap();
+
+        assert_eq!(replayed.registers()[0], 0xB);
+        assert_eq!(replayed.registers()[2], recorded.registers()[2]);
+        assert_eq!(replayed.save_state(), recorded.save_state());
+    }
+
+    #[test]
+    fn a_tap_between_two_instructions_is_latched_for_fx0a() {
+        let (recording, recorded) = record(40, &[(10, 0x3, true), (10, 0x3, false)]);
+
+        assert_eq!(recording.events[0].instruction, recording.events[1].instruction);
+        assert!(!recorded.is_waiting_for_key());
+        let replayed = replay(recording).unwrap();
+
+        assert_eq!(replayed.registers()[0], 0x3);
+        assert_eq!(replayed.save_state(), recorded.save_state());
+    }
+
+    #[test]
+    fn replay_refuses_another_rom() {
+        let (recording, _) = record(10, &[]);
+        let mut cpu = fresh_cpu(&[0x12, 0x00]);
+
+        let result = Player::new(recording, &mut cpu);
+
+        assert!(matches!(result, Err(ReplayError::WrongRom { .. })));
+    }
+
+    #[test]
+    fn divergence_names_the_checkpoints_around_it() {
+        let (mut recording, _) = record(1500, &[(700, 0x7, true), (701, 0x7, false)]);
+        // a different key changes V0 from then on
+        recording.events[0].key = 0x8;
+        recording.events[1].key = 0x8;
+
+        let error = replay(recording.clone()).err().unwrap();
+
+        // the key went in at instruction 7000, between the first two checkpoints
+        assert_eq!(recording.checkpoints[0].instruction, 6000);
+        assert_eq!(recording.checkpoints[1].instruction, 12000);
+        assert!(matches!(error, ReplayError::Diverged { after: 6000, by: 12000 }));
+        assert_eq!(error.to_string(), "replay diverged from the recording between instructions 6000 and 12000");
+    }
+
+    #[test]
+    fn an_edit_by_hand_is_reported_rather_than_a_divergence() {
+        let mut cpu = fresh_cpu(&ROM);
+        cpu.seed(SEED);
+        let mut recorder = Recorder::new(&cpu, SEED, &RomSettings::default());
+        let mut debugger = Debugger::new();
+        cpu.keys_mut().press(0x7);
+        recorder.key(&cpu, 0x7, true);
+        for _ in 0..700 {
+            debugger.run_frame(&mut cpu).unwrap();
+            recorder.frame(&cpu);
+        }
+        cpu.apply_edit(&Edit::Set(Target::V(2), 0)).unwrap();
+        for _ in 0..700 {
+            debugger.run_frame(&mut cpu).unwrap();
+            recorder.frame(&cpu);
+        }
+        let recording = recorder.finish(&cpu);
+
+        assert_eq!(recording.edited_at, Some(7000));
+        assert!(Recording::parse(&recording.to_json()).unwrap().edited_at.is_some());
+        let error = replay(recording).err().unwrap();
+        // the checkpoint before the edit still matched
+        assert!(matches!(error, ReplayError::Edited { at: 7000 }));
+        assert_eq!(error.to_string(), "the run was edited by hand at instruction 7000, so it can't be checked past there");
+    }
+
+    #[test]
+    fn recording_survives_a_round_trip_through_json() {
+        let (recording, _) = record(700, &[(5, 0x1, true), (6, 0x1, false)]);
+
+        let parsed = Recording::parse(&recording.to_json()).unwrap();
+
+        assert_eq!(parsed, recording);
+        assert_eq!(parsed.settings.cycles_per_frame, Some(CYCLES_PER_FRAME));
+        let mut unordered = recording;
+        unordered.events.reverse();
+        assert!(matches!(Recording::parse(&unordered.to_json()), Err(ReplayError::Invalid(_))));
+        assert!(matches!(Recording::parse("{"), Err(ReplayError::Invalid(_))));
+    }
+}
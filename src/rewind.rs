@@ -0,0 +1,247 @@
+use std::collections::VecDeque;
+use std::mem;
+
+use crate::cpu::Cpu;
+use crate::savestate::SaveState;
+
+/// Frames between two rewind snapshots.
+pub const FRAMES_PER_SNAPSHOT: u32 = 10;
+// the unit memory is compared and kept in between snapshots
+const PAGE_BYTES: usize = 256;
+
+// a snapshot with its memory left out
+struct Snapshot {
+    state: SaveState,
+    // where memory was different in the snapshot after this one: the address and what this one
+    // held there
+    pages: Vec<(usize, Vec<u8>)>,
+}
+
+/// A bounded history of snapshots taken every `FRAMES_PER_SNAPSHOT` frames, oldest first. Only
+/// the newest snapshot's memory is kept whole; the others keep the pages that changed after them,
+/// so a MEGA-CHIP ROM's 16 MiB isn't copied into every snapshot.
+pub struct Rewind {
+    snapshots: VecDeque<Snapshot>,
+    newest_memory: Vec<u8>,
+    capacity: usize,
+    frames_since_snapshot: u32,
+}
+
+impl Rewind {
+    pub fn new(capacity: usize) -> Rewind {
+        Rewind {
+            snapshots: VecDeque::with_capacity(capacity),
+            newest_memory: Vec::new(),
+            capacity,
+            frames_since_snapshot: 0,
+        }
+    }
+
+    /// Keeps enough snapshots to rewind `seconds` of 60 Hz emulation.
+    pub fn with_seconds(seconds: u32) -> Rewind {
+        Rewind::new((seconds * 60 / FRAMES_PER_SNAPSHOT) as usize)
+    }
+
+    pub fn len(&self) -> usize {
+        self.snapshots.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.snapshots.is_empty()
+    }
+
+    /// Call once per emulated frame. No snapshot is taken while FX0A waits for a key: those
+    /// frames are all identical and would only push useful history out of the buffer.
+    pub fn on_frame(&mut self, cpu: &Cpu) {
+        self.frames_since_snapshot += 1;
+        if self.frames_since_snapshot < FRAMES_PER_SNAPSHOT || cpu.is_waiting_for_key() {
+            return;
+        }
+
+        self.frames_since_snapshot = 0;
+        self.capture(cpu);
+    }
+
+    pub fn capture(&mut self, cpu: &Cpu) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut state = cpu.save_state();
+        let memory = mem::take(&mut state.memory);
+        if memory.len() != self.newest_memory.len() {
+            // another machine, whose memory can't be told apart page by page
+            self.snapshots.clear();
+        }
+        if let Some(newest) = self.snapshots.back_mut() {
+            newest.pages = self
+                .newest_memory
+                .chunks(PAGE_BYTES)
+                .zip(memory.chunks(PAGE_BYTES))
+                .enumerate()
+                .filter(|(_, (before, after))| before != after)
+                .map(|(page, (before, _))| (page * PAGE_BYTES, before.to_vec()))
+                .collect();
+        }
+        if self.snapshots.len() == self.capacity {
+            self.snapshots.pop_front();
+        }
+        self.newest_memory = memory;
+        self.snapshots.push_back(Snapshot { state, pages: Vec::new() });
+    }
+
+    /// Restores the most recent snapshot, dropping it from the history. The keys currently held
+    /// are kept, so live input isn't overwritten by whatever was held back then.
+    pub fn rewind(&mut self, cpu: &mut Cpu) -> bool {
+        let mut snapshot = match self.snapshots.pop_back() {
+            Some(snapshot) => snapshot.state,
+            None => return false,
+        };
+        snapshot.keys = cpu.keys().keys;
+        snapshot.memory = mem::take(&mut self.newest_memory);
+        self.frames_since_snapshot = 0;
+
+        let restored = cpu.load_state(&snapshot).is_ok();
+        // back to the memory of the snapshot that's now the newest
+        self.newest_memory = snapshot.memory;
+        if let Some(newest) = self.snapshots.back_mut() {
+            for (address, page) in newest.pages.drain(..) {
+                self.newest_memory[address..address + page.len()].copy_from_slice(&page);
+            }
+        }
+        restored
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cpu::CYCLES_PER_FRAME;
+    use crate::display::Display;
+    use crate::megachip::MEGA_CHIP_MEMORY_SIZE;
+    use crate::memory::Memory;
+    use crate::quirks::Quirks;
+
+    fn cpu() -> Cpu {
+        // scrolls a digit across the screen, keyed off the delay timer
+        let rom = vec![0x70, 0x01, 0xF0, 0x29, 0xD1, 0x05, 0x71, 0x01, 0xF0, 0x15, 0xF2, 0x07, 0x32, 0x00, 0x12, 0x0A, 0x12, 0x00];
+        let mut cpu = Cpu::new(Memory::new(), Display::new());
+        cpu.init(rom);
+        cpu
+    }
+
+    fn run_frame(cpu: &mut Cpu) {
+        for _ in 0..CYCLES_PER_FRAME {
+            cpu.cycle().unwrap();
+        }
+        cpu.tick_timers();
+    }
+
+    #[test]
+    fn snapshot_every_few_frames() {
+        let mut cpu = cpu();
+        let mut rewind = Rewind::new(8);
+
+        for _ in 0..FRAMES_PER_SNAPSHOT * 3 - 1 {
+            run_frame(&mut cpu);
+            rewind.on_frame(&cpu);
+        }
+
+        assert_eq!(rewind.len(), 2);
+    }
+
+    #[test]
+    fn capacity_drops_oldest_snapshots() {
+        let mut cpu = cpu();
+        let mut rewind = Rewind::new(3);
+        let mut states = Vec::new();
+
+        for _ in 0..5 {
+            run_frame(&mut cpu);
+            rewind.capture(&cpu);
+            states.push(cpu.save_state());
+        }
+
+        assert_eq!(rewind.len(), 3);
+        for expected in states.iter().rev().take(3) {
+            assert!(rewind.rewind(&mut cpu));
+            assert!(&cpu.save_state() == expected);
+        }
+        assert!(!rewind.rewind(&mut cpu));
+    }
+
+    #[test]
+    fn rewind_and_replay_reproduces_state() {
+        let mut cpu = cpu();
+        let mut rewind = Rewind::with_seconds(10);
+        let mut states = Vec::new();
+        for _ in 0..FRAMES_PER_SNAPSHOT * 6 {
+            run_frame(&mut cpu);
+            rewind.on_frame(&cpu);
+            states.push(cpu.save_state());
+        }
+
+        for _ in 0..3 {
+            assert!(rewind.rewind(&mut cpu));
+        }
+        // the third snapshot from the end was taken after frame 40
+        let resumed_from = FRAMES_PER_SNAPSHOT as usize * 4;
+        assert!(cpu.save_state() == states[resumed_from - 1]);
+        for (frame, expected) in states.iter().enumerate().skip(resumed_from) {
+            run_frame(&mut cpu);
+            assert!(&cpu.save_state() == expected, "diverged at frame {}", frame);
+        }
+    }
+
+    #[test]
+    fn older_snapshots_keep_only_the_pages_that_changed() {
+        // counts V0 up and stores its BCD at 0x300, in MEGA-CHIP's 16 MiB
+        let mut cpu = Cpu::new(Memory::new(), Display::new());
+        cpu.set_quirks(Quirks::profile("megachip").unwrap());
+        cpu.init(vec![0x70, 0x01, 0xA3, 0x00, 0xF0, 0x33, 0x12, 0x00]);
+        let mut rewind = Rewind::new(4);
+        let mut states = Vec::new();
+
+        for _ in 0..6 {
+            run_frame(&mut cpu);
+            rewind.capture(&cpu);
+            states.push(cpu.save_state());
+        }
+
+        assert_eq!(rewind.newest_memory.len(), MEGA_CHIP_MEMORY_SIZE);
+        for snapshot in &rewind.snapshots {
+            assert!(snapshot.state.memory.is_empty());
+            assert!(snapshot.pages.len() <= 1);
+        }
+        for expected in states.iter().rev().take(4) {
+            assert!(rewind.rewind(&mut cpu));
+            assert!(&cpu.save_state() == expected);
+        }
+        assert!(!rewind.rewind(&mut cpu));
+    }
+
+    #[test]
+    fn rewind_keeps_held_keys() {
+        let mut cpu = cpu();
+        let mut rewind = Rewind::new(1);
+        rewind.capture(&cpu);
+        cpu.keys_mut().press(4);
+
+        rewind.rewind(&mut cpu);
+
+        assert!(cpu.keys().is_pressed(4));
+    }
+
+    #[test]
+    fn no_snapshots_while_waiting_for_a_key() {
+        let mut cpu = Cpu::new(Memory::new(), Display::new());
+        cpu.init(vec![0xF0, 0x0A]);
+        let mut rewind = Rewind::new(8);
+
+        for _ in 0..FRAMES_PER_SNAPSHOT * 4 {
+            run_frame(&mut cpu);
+            rewind.on_frame(&cpu);
+        }
+
+        assert!(rewind.is_empty());
+    }
+}
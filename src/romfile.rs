@@ -0,0 +1,151 @@
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io::{self, Read};
+use std::path::Path;
+
+/// The ROM path that means standard input.
+pub const STDIN: &str = "-";
+
+/// How a ROM file's bytes are written down.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RomFormat {
+    /// The bytecode itself, or a `.c8b` container.
+    Binary,
+    /// Hex byte pairs as text, like Octo's hex dumps.
+    Hex,
+}
+
+impl RomFormat {
+    pub fn parse(name: &str) -> Option<RomFormat> {
+        match name {
+            "binary" => Some(RomFormat::Binary),
+            "hex" => Some(RomFormat::Hex),
+            _ => None,
+        }
+    }
+
+    /// Hex for `.hex` and `.txt` files, binary for anything else, stdin included.
+    pub fn detect(path: &Path) -> RomFormat {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some(extension) if extension.eq_ignore_ascii_case("hex") || extension.eq_ignore_ascii_case("txt") => RomFormat::Hex,
+            _ => RomFormat::Binary,
+        }
+    }
+}
+
+/// Why a hex dump doesn't parse; `line` and `column` count from 1, in characters.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HexError {
+    NotText,
+    NotHex { line: usize, column: usize, found: char },
+    /// A run of digits starting at `line`:`column` that doesn't make whole bytes.
+    OddDigits { line: usize, column: usize },
+}
+
+impl fmt::Display for HexError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HexError::NotText => write!(f, "a hex dump must be UTF-8 text"),
+            HexError::NotHex { line, column, found } => write!(f, "line {}, column {}: {:?} is not a hex digit", line, column, found),
+            HexError::OddDigits { line, column } => {
+                write!(f, "line {}, column {}: odd number of hex digits, which doesn't make whole bytes", line, column)
+            }
+        }
+    }
+}
+
+impl Error for HexError {}
+
+pub fn is_stdin(path: &Path) -> bool {
+    path.as_os_str() == STDIN
+}
+
+/// Reads the file at `path`, or everything on stdin for `-`.
+pub fn read(path: &Path) -> io::Result<Vec<u8>> {
+    if is_stdin(path) {
+        let mut buffer = Vec::new();
+        io::stdin().lock().read_to_end(&mut buffer)?;
+        Ok(buffer)
+    } else {
+        fs::read(path)
+    }
+}
+
+/// Turns a ROM file's bytes into the bytes to load.
+pub fn decode(bytes: Vec<u8>, format: RomFormat) -> Result<Vec<u8>, HexError> {
+    match format {
+        RomFormat::Binary => Ok(bytes),
+        RomFormat::Hex => parse_hex(std::str::from_utf8(&bytes).map_err(|_| HexError::NotText)?),
+    }
+}
+
+/// Parses hex byte pairs separated by whitespace or commas, e.g. `0x00 0xE0, A2 2A` or `00E0A22A`.
+/// `0x` prefixes are optional and `#` starts a comment.
+pub fn parse_hex(text: &str) -> Result<Vec<u8>, HexError> {
+    let is_separator = |c: char| c.is_whitespace() || c == ',';
+    let mut bytes = Vec::new();
+    for (index, line) in text.lines().enumerate() {
+        let line_number = index + 1;
+        let chars: Vec<char> = line.split('#').next().unwrap_or_default().chars().collect();
+        let mut column = 0;
+        while column < chars.len() {
+            if is_separator(chars[column]) {
+                column += 1;
+                continue;
+            }
+            let start = column;
+            let end = chars[start..].iter().position(|&c| is_separator(c)).map_or(chars.len(), |length| start + length);
+            let first = if chars[start..end].starts_with(&['0', 'x']) || chars[start..end].starts_with(&['0', 'X']) {
+                start + 2
+            } else {
+                start
+            };
+            let mut digits = Vec::with_capacity(end - first);
+            for (column, &c) in chars.iter().enumerate().take(end).skip(first) {
+                match c.to_digit(16) {
+                    Some(digit) => digits.push(digit as u8),
+                    None => return Err(HexError::NotHex { line: line_number, column: column + 1, found: c }),
+                }
+            }
+            if digits.len() % 2 == 1 {
+                return Err(HexError::OddDigits { line: line_number, column: start + 1 });
+            }
+            bytes.extend(digits.chunks(2).map(|pair| pair[0] << 4 | pair[1]));
+            column = end;
+        }
+    }
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_octo_dumps_and_plain_runs() {
+        assert_eq!(parse_hex("0x00 0xE0 0xa2 0x2A\n0x60,0x0C,").unwrap(), vec![0x00, 0xE0, 0xA2, 0x2A, 0x60, 0x0C]);
+        assert_eq!(parse_hex("00E0A22A  # CLS; LD I\n\n# done\n1228").unwrap(), vec![0x00, 0xE0, 0xA2, 0x2A, 0x12, 0x28]);
+        assert_eq!(parse_hex("12#comment right after\n").unwrap(), vec![0x12]);
+        assert_eq!(parse_hex("").unwrap(), Vec::<u8>::new());
+    }
+
+    #[test]
+    fn reports_where_a_dump_goes_wrong() {
+        assert_eq!(parse_hex("00 E0\n  A2 2G").unwrap_err(), HexError::NotHex { line: 2, column: 7, found: 'G' });
+        assert_eq!(parse_hex("00 E0\n0x123 45").unwrap_err(), HexError::OddDigits { line: 2, column: 1 });
+        assert_eq!(
+            parse_hex("E0 x1").unwrap_err().to_string(),
+            "line 1, column 4: 'x' is not a hex digit"
+        );
+    }
+
+    #[test]
+    fn detects_hex_by_extension() {
+        assert_eq!(RomFormat::detect(Path::new("games/pong.HEX")), RomFormat::Hex);
+        assert_eq!(RomFormat::detect(Path::new("dump.txt")), RomFormat::Hex);
+        assert_eq!(RomFormat::detect(Path::new("pong.ch8")), RomFormat::Binary);
+        assert_eq!(RomFormat::detect(Path::new(STDIN)), RomFormat::Binary);
+        assert_eq!(decode(vec![0xFF], RomFormat::Hex), Err(HexError::NotText));
+    }
+}
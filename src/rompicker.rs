@@ -0,0 +1,180 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// File extensions the picker lists, compared without regard to case.
+pub const ROM_EXTENSIONS: [&str; 4] = ["ch8", "c8", "c8b", "rom"];
+
+/// A file the picker offers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RomEntry {
+    pub path: PathBuf,
+    pub size: u64,
+}
+
+/// Lists the ROMs directly inside each of `dirs`, sorted by file name. Directories that don't
+/// exist are skipped, and files in `.` are listed by name alone.
+pub fn scan(dirs: &[&Path]) -> Vec<RomEntry> {
+    let mut entries = Vec::new();
+    for dir in dirs {
+        let listing = match fs::read_dir(dir) {
+            Ok(listing) => listing,
+            Err(_) => continue,
+        };
+        for item in listing.flatten() {
+            let path = item.path();
+            let is_rom = path
+                .extension()
+                .and_then(|extension| extension.to_str())
+                .is_some_and(|extension| ROM_EXTENSIONS.iter().any(|rom| rom.eq_ignore_ascii_case(extension)));
+            let metadata = match item.metadata() {
+                Ok(metadata) if metadata.is_file() && is_rom => metadata,
+                _ => continue,
+            };
+            let path = if *dir == Path::new(".") { PathBuf::from(item.file_name()) } else { path };
+            entries.push(RomEntry { path, size: metadata.len() });
+        }
+    }
+    entries.sort_by_key(|entry| (entry.path.file_name().map(|name| name.to_string_lossy().to_lowercase()), entry.path.clone()));
+    entries
+}
+
+/// What the picker shows: the ROMs, which one is selected and which `rows` of them fit on screen.
+#[derive(Debug, Clone)]
+pub struct RomPicker {
+    entries: Vec<RomEntry>,
+    selected: usize,
+    first_visible: usize,
+    rows: usize,
+    // bytes of memory a ROM may take up; bigger files are marked
+    capacity: u64,
+}
+
+impl RomPicker {
+    pub fn new(entries: Vec<RomEntry>, rows: usize, capacity: u64) -> RomPicker {
+        RomPicker { entries, selected: 0, first_visible: 0, rows: rows.max(1), capacity }
+    }
+
+    pub fn selected(&self) -> Option<&RomEntry> {
+        self.entries.get(self.selected)
+    }
+
+    pub fn up(&mut self) {
+        self.select(self.selected.saturating_sub(1));
+    }
+
+    pub fn down(&mut self) {
+        self.select(self.selected + 1);
+    }
+
+    pub fn page_up(&mut self) {
+        self.select(self.selected.saturating_sub(self.rows));
+    }
+
+    pub fn page_down(&mut self) {
+        self.select(self.selected + self.rows);
+    }
+
+    /// Moves the selection to `index`, clamped to the list, scrolling just enough to keep it visible.
+    fn select(&mut self, index: usize) {
+        self.selected = index.min(self.entries.len().saturating_sub(1));
+        if self.selected < self.first_visible {
+            self.first_visible = self.selected;
+        } else if self.selected >= self.first_visible + self.rows {
+            self.first_visible = self.selected + 1 - self.rows;
+        }
+    }
+
+    /// The visible part of the list, one line per ROM with its size; the selected ROM is marked
+    /// with `>` and ROMs that can't be loaded with `(too large)`.
+    pub fn lines(&self) -> Vec<String> {
+        if self.entries.is_empty() {
+            return vec![String::from("No .ch8, .c8, .c8b or .rom files in roms/ or the current directory")];
+        }
+        self.entries
+            .iter()
+            .enumerate()
+            .skip(self.first_visible)
+            .take(self.rows)
+            .map(|(index, entry)| {
+                let marker = if index == self.selected { '>' } else { ' ' };
+                let warning = if entry.size > self.capacity { " (too large)" } else { "" };
+                format!("{} {:<32} {:>6} B{}", marker, entry.path.display(), entry.size, warning)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(name: &str, size: u64) -> RomEntry {
+        RomEntry { path: PathBuf::from(name), size }
+    }
+
+    fn picker(count: usize, rows: usize) -> RomPicker {
+        RomPicker::new((0..count).map(|index| entry(&format!("{}.ch8", index), 100)).collect(), rows, 3584)
+    }
+
+    #[test]
+    fn scan_lists_roms_by_name() {
+        let dir = std::env::temp_dir().join(format!("chip-8-emulator-roms-{}", std::process::id()));
+        fs::create_dir_all(dir.join("nested.ch8")).unwrap();
+        fs::write(dir.join("pong.ch8"), [0; 246]).unwrap();
+        fs::write(dir.join("Brix.C8"), [0; 280]).unwrap();
+        fs::write(dir.join("ibm.rom"), [0; 132]).unwrap();
+        fs::write(dir.join("notes.txt"), b"not a rom").unwrap();
+
+        let entries = scan(&[&dir, Path::new("/no/such/directory")]);
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(entries, vec![
+            RomEntry { path: dir.join("Brix.C8"), size: 280 },
+            RomEntry { path: dir.join("ibm.rom"), size: 132 },
+            RomEntry { path: dir.join("pong.ch8"), size: 246 },
+        ]);
+    }
+
+    #[test]
+    fn selection_stays_within_the_list() {
+        let mut picker = picker(3, 10);
+
+        picker.up();
+        assert_eq!(picker.selected(), Some(&entry("0.ch8", 100)));
+        picker.down();
+        picker.down();
+        picker.down();
+        assert_eq!(picker.selected(), Some(&entry("2.ch8", 100)));
+        assert_eq!(RomPicker::new(Vec::new(), 10, 3584).selected(), None);
+    }
+
+    #[test]
+    fn long_lists_scroll_to_keep_the_selection_visible() {
+        let mut picker = picker(10, 3);
+
+        picker.down();
+        picker.down();
+        assert_eq!(picker.lines()[0], format!("  {:<32}    100 B", "0.ch8"));
+        picker.down();
+        assert!(picker.lines()[0].contains("1.ch8"));
+        assert!(picker.lines()[2].starts_with("> 3.ch8"));
+        picker.page_down();
+        assert!(picker.lines()[2].starts_with("> 6.ch8"));
+        picker.page_down();
+        picker.page_down();
+        assert!(picker.lines()[2].starts_with("> 9.ch8"));
+        picker.page_up();
+        assert!(picker.lines()[0].starts_with("> 6.ch8"));
+        assert_eq!(picker.lines().len(), 3);
+    }
+
+    #[test]
+    fn lines_mark_roms_that_do_not_fit() {
+        let picker = RomPicker::new(vec![entry("big.ch8", 4000), entry("small.ch8", 3584)], 10, 3584);
+
+        assert_eq!(picker.lines(), vec![
+            format!("> {:<32}   4000 B (too large)", "big.ch8"),
+            format!("  {:<32}   3584 B", "small.ch8"),
+        ]);
+    }
+}
@@ -0,0 +1,98 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+/// How often a `RomWatcher` looks at the ROM file.
+pub const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Notices when a ROM file is rewritten, e.g. by an assembler, so the emulator can reload it.
+/// Assemblers don't write atomically, so a file that's missing, unreadable or empty is taken to
+/// be mid-write and looked at again on the next poll.
+#[derive(Debug, Clone)]
+pub struct RomWatcher {
+    path: PathBuf,
+    // modification time and length of the version last handed out, or of the file when watching
+    // started
+    version: Option<(SystemTime, u64)>,
+    last_poll: Instant,
+}
+
+impl RomWatcher {
+    /// Starts watching `path` at `now`; whatever is there already doesn't count as a change.
+    pub fn new(path: &Path, now: Instant) -> RomWatcher {
+        RomWatcher { path: path.to_path_buf(), version: version(path), last_poll: now }
+    }
+
+    /// The ROM's new contents if it changed since the last time, looking at most once every
+    /// `POLL_INTERVAL`.
+    pub fn poll(&mut self, now: Instant) -> Option<Vec<u8>> {
+        if now.saturating_duration_since(self.last_poll) < POLL_INTERVAL {
+            return None;
+        }
+        self.last_poll = now;
+        let current = version(&self.path)?;
+        if Some(current) == self.version {
+            return None;
+        }
+        match fs::read(&self.path) {
+            Ok(bytes) if !bytes.is_empty() => {
+                self.version = Some(current);
+                Some(bytes)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// A file's modification time and length, which between them change on every rewrite even on
+/// file systems with coarse timestamps.
+fn version(path: &Path) -> Option<(SystemTime, u64)> {
+    let metadata = fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Cpu, Display, Memory};
+
+    fn temp_rom(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("chip-8-emulator-{}-{}.ch8", name, std::process::id()))
+    }
+
+    #[test]
+    fn rewritten_roms_are_reloaded_on_the_next_poll() {
+        let path = temp_rom("watch");
+        fs::write(&path, [0x00, 0xE0]).unwrap();
+        let start = Instant::now();
+        let mut watcher = RomWatcher::new(&path, start);
+
+        assert_eq!(watcher.poll(start + POLL_INTERVAL), None);
+        fs::write(&path, [0x12, 0x00, 0x00, 0xE0]).unwrap();
+        // not checked again until a full interval has passed
+        assert_eq!(watcher.poll(start + POLL_INTERVAL + Duration::from_millis(500)), None);
+        let bytes = watcher.poll(start + POLL_INTERVAL * 2).unwrap();
+        assert_eq!(watcher.poll(start + POLL_INTERVAL * 3), None);
+        fs::remove_file(&path).unwrap();
+
+        let mut cpu = Cpu::new(Memory::new(), Display::new());
+        cpu.init(bytes);
+        assert_eq!(&cpu.memory().bytes()[0x200..0x204], &[0x12, 0x00, 0x00, 0xE0]);
+    }
+
+    #[test]
+    fn missing_and_truncated_files_are_retried() {
+        let path = temp_rom("truncated");
+        fs::write(&path, [0x00, 0xE0]).unwrap();
+        let start = Instant::now();
+        let mut watcher = RomWatcher::new(&path, start);
+
+        fs::remove_file(&path).unwrap();
+        assert_eq!(watcher.poll(start + POLL_INTERVAL), None);
+        fs::write(&path, []).unwrap();
+        assert_eq!(watcher.poll(start + POLL_INTERVAL * 2), None);
+        fs::write(&path, [0x00, 0xE0, 0x12, 0x00]).unwrap();
+        assert_eq!(watcher.poll(start + POLL_INTERVAL * 3), Some(vec![0x00, 0xE0, 0x12, 0x00]));
+        fs::remove_file(&path).unwrap();
+    }
+}
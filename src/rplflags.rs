@@ -0,0 +1,59 @@
+#[cfg(feature = "std")]
+use std::fs;
+#[cfg(feature = "std")]
+use std::io;
+#[cfg(feature = "std")]
+use std::path::Path;
+
+/// Size of the flag storage; SUPER-CHIP only uses the first 8, XO-CHIP all 16.
+pub const RPL_FLAGS: usize = 16;
+
+#[cfg(feature = "std")]
+const FILE_LENGTH: usize = 8 + RPL_FLAGS;
+
+/// Reads the RPL user flags saved for the ROM with `rom_hash`. A missing or corrupt file, or
+/// one written for a different ROM, gives all zeros, which is what a fresh HP-48 starts with.
+#[cfg(feature = "std")]
+pub fn read(path: &Path, rom_hash: u64) -> [u8; RPL_FLAGS] {
+    let mut flags = [0; RPL_FLAGS];
+    if let Ok(bytes) = fs::read(path) {
+        if bytes.len() == FILE_LENGTH && bytes[..8] == rom_hash.to_le_bytes() {
+            flags.copy_from_slice(&bytes[8..]);
+        }
+    }
+    flags
+}
+
+/// Saves the flags next to the hash of the ROM they belong to.
+#[cfg(feature = "std")]
+pub fn write(path: &Path, rom_hash: u64, flags: &[u8; RPL_FLAGS]) -> io::Result<()> {
+    let mut bytes = Vec::with_capacity(FILE_LENGTH);
+    bytes.extend_from_slice(&rom_hash.to_le_bytes());
+    bytes.extend_from_slice(flags);
+    fs::write(path, bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip_through_a_file() {
+        let path = std::env::temp_dir().join(format!("chip-8-emulator-{}.flags", std::process::id()));
+        let mut flags = [0; RPL_FLAGS];
+        flags[..3].copy_from_slice(&[7, 0, 42]);
+
+        write(&path, 0x1234, &flags).unwrap();
+        let same_rom = read(&path, 0x1234);
+        let other_rom = read(&path, 0x5678);
+        fs::write(&path, b"not a flags file").unwrap();
+        let corrupt = read(&path, 0x1234);
+        fs::remove_file(&path).unwrap();
+        let missing = read(&path, 0x1234);
+
+        assert_eq!(same_rom, flags);
+        assert_eq!(other_rom, [0; RPL_FLAGS]);
+        assert_eq!(corrupt, [0; RPL_FLAGS]);
+        assert_eq!(missing, [0; RPL_FLAGS]);
+    }
+}
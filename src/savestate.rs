@@ -0,0 +1,250 @@
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::megachip::MegaRegisters;
+use crate::statejson::STATE_JSON_VERSION;
+
+/// A snapshot of the whole machine, enough to resume a ROM exactly where it was left.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct SaveState {
+    pub(crate) rom_hash: u64,
+    pub(crate) registers: [u8; 16],
+    pub(crate) i: u16,
+    pub(crate) pc: u16,
+    pub(crate) stack: [u16; 16],
+    pub(crate) sp: u8,
+    pub(crate) delay: u8,
+    pub(crate) sound: u8,
+    pub(crate) memory: Vec<u8>,
+    pub(crate) pixels: Vec<u8>,
+    pub(crate) hires: bool,
+    pub(crate) planes: u8,
+    pub(crate) audio_pattern: [u8; 16],
+    pub(crate) pitch: u8,
+    pub(crate) keys: [bool; 16],
+    pub(crate) waiting_for_input: bool,
+    pub(crate) megachip: MegaRegisters,
+    /// `MegaScreen::bytes`, while 0011 has MEGA-CHIP's screen on.
+    pub(crate) mega_screen: Option<Vec<u8>>,
+}
+
+#[derive(Debug)]
+pub enum SaveStateError {
+    Io(io::Error),
+    Corrupt(String),
+    WrongRom,
+    /// A JSON save state in a layout this version doesn't read.
+    Version(u32),
+    /// A JSON save state whose `field` is missing or holds something it can't.
+    Field { field: String, reason: String },
+}
+
+impl fmt::Display for SaveStateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SaveStateError::Io(error) if error.kind() == io::ErrorKind::NotFound => write!(f, "no save state found"),
+            SaveStateError::Io(error) => write!(f, "could not access save state: {}", error),
+            SaveStateError::Corrupt(reason) => write!(f, "save state is corrupt: {}", reason),
+            SaveStateError::WrongRom => write!(f, "save state belongs to a different ROM"),
+            SaveStateError::Version(version) => write!(
+                f,
+                "save state format version {} is not supported; this emulator reads version {}",
+                version, STATE_JSON_VERSION
+            ),
+            SaveStateError::Field { field, reason } => write!(f, "save state field `{}` is invalid: {}", field, reason),
+        }
+    }
+}
+
+impl Error for SaveStateError {}
+
+impl From<io::Error> for SaveStateError {
+    fn from(error: io::Error) -> Self {
+        SaveStateError::Io(error)
+    }
+}
+
+impl SaveState {
+    /// The hash of the ROM the state was saved from, which `Cpu::load_state` insists on.
+    pub fn rom_hash(&self) -> u64 {
+        self.rom_hash
+    }
+
+    /// The same state claiming to belong to the ROM with `rom_hash`, for loading it into a ROM
+    /// it wasn't saved from, e.g. a newer build of the same game.
+    pub fn with_rom_hash(self, rom_hash: u64) -> SaveState {
+        SaveState { rom_hash, ..self }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        bincode::serialize(self).expect("save state is always serializable")
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<SaveState, SaveStateError> {
+        bincode::deserialize(bytes).map_err(|error| SaveStateError::Corrupt(error.to_string()))
+    }
+
+    pub fn write_to(&self, path: &Path) -> Result<(), SaveStateError> {
+        fs::write(path, self.to_bytes())?;
+        Ok(())
+    }
+
+    pub fn read_from(path: &Path) -> Result<SaveState, SaveStateError> {
+        SaveState::from_bytes(&fs::read(path)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cpu::{Cpu, Flow};
+    use crate::display::{Display, Resolution};
+    use crate::memory::Memory;
+    use crate::quirks::Quirks;
+
+    fn running_cpu() -> Cpu {
+        // counts V0 up, draws the digit for it and stores its BCD at 0x300
+        let rom = vec![0x70, 0x01, 0xA3, 0x00, 0xF0, 0x33, 0xF0, 0x29, 0xD1, 0x25, 0x22, 0x0E, 0x12, 0x00, 0x00, 0xEE];
+        let mut cpu = Cpu::new(Memory::new(), Display::new());
+        cpu.init(rom);
+        cpu.seed(7);
+        for _ in 0..25 {
+            cpu.cycle().unwrap();
+        }
+        cpu.tick_timers();
+        cpu
+    }
+
+    #[test]
+    fn round_trip_through_bytes() {
+        let cpu = running_cpu();
+        let state = cpu.save_state();
+
+        let restored = SaveState::from_bytes(&state.to_bytes()).unwrap();
+
+        assert_eq!(restored, state);
+    }
+
+    #[test]
+    fn load_state_restores_identical_state() {
+        let mut cpu = running_cpu();
+        let state = cpu.save_state();
+        for _ in 0..10 {
+            cpu.cycle().unwrap();
+        }
+
+        cpu.load_state(&state).unwrap();
+
+        assert_eq!(cpu.save_state(), state);
+    }
+
+    #[test]
+    fn execution_after_restore_matches_uninterrupted_execution() {
+        let mut uninterrupted = running_cpu();
+        let mut restored = running_cpu();
+        let bytes = restored.save_state().to_bytes();
+        for _ in 0..7 {
+            restored.cycle().unwrap();
+        }
+        restored.load_state(&SaveState::from_bytes(&bytes).unwrap()).unwrap();
+
+        for _ in 0..40 {
+            uninterrupted.cycle().unwrap();
+            restored.cycle().unwrap();
+        }
+
+        assert_eq!(restored.save_state(), uninterrupted.save_state());
+    }
+
+    #[test]
+    fn a_wait_for_a_key_carries_on_after_a_restore() {
+        // LD V0, 0x3C; LD DT, V0; LD V3, K; LD V4, 1
+        let mut cpu = Cpu::new(Memory::new(), Display::new());
+        cpu.init(vec![0x60, 0x3C, 0xF0, 0x15, 0xF3, 0x0A, 0x64, 0x01]);
+        cpu.cycle().unwrap();
+        cpu.cycle().unwrap();
+        assert_eq!(cpu.cycle(), Ok(Flow::WaitingForKey));
+        cpu.tick_timers();
+        let bytes = cpu.save_state().to_bytes();
+
+        let mut restored = Cpu::new(Memory::new(), Display::new());
+        restored.init(vec![0x60, 0x3C, 0xF0, 0x15, 0xF3, 0x0A, 0x64, 0x01]);
+        restored.load_state(&SaveState::from_bytes(&bytes).unwrap()).unwrap();
+
+        assert!(restored.is_waiting_for_key());
+        assert_eq!((restored.pc(), restored.delay_timer()), (0x204, 0x3B));
+        assert_eq!(restored.cycle(), Ok(Flow::WaitingForKey));
+        restored.keys_mut().press(0xA);
+        assert_eq!(restored.cycle(), Ok(Flow::Continue));
+        assert_eq!(restored.registers()[3], 0xA);
+        assert!(!restored.is_waiting_for_key());
+        assert_eq!(restored.pc(), 0x206);
+        restored.cycle().unwrap();
+        assert_eq!((restored.registers()[4], restored.pc()), (1, 0x208));
+    }
+
+    #[test]
+    fn megachip_registers_and_screen_survive_a_round_trip() {
+        // 0011 and a 16x4 sprite of the program's own bytes from 0x201, shown by 00E0 and drawn
+        // again, then back to the classic screen with 0010
+        let program: [u16; 11] = [0x0011, 0x0310, 0x0404, 0x0907, 0x0580, 0xA201, 0xD000, 0x00E0, 0xD000, 0x0010, 0x1214];
+        let mut cpu = Cpu::new(Memory::new(), Display::new());
+        cpu.set_quirks(Quirks::profile("megachip").unwrap());
+        cpu.init(program.iter().flat_map(|opcode| opcode.to_be_bytes()).collect());
+        for _ in 0..9 {
+            cpu.cycle().unwrap();
+        }
+        let state = SaveState::from_bytes(&cpu.save_state().to_bytes()).unwrap();
+        let screen = cpu.display().megachip().unwrap().clone();
+        cpu.cycle().unwrap();
+        assert_eq!(cpu.display().resolution(), Resolution::Lores);
+
+        cpu.load_state(&state).unwrap();
+
+        assert_eq!(cpu.display().resolution(), Resolution::Mega);
+        assert_eq!(cpu.display().megachip(), Some(&screen));
+        assert_eq!(screen.alpha(), 0x80);
+        assert_ne!(screen.pixel(0, 0), 0);
+        assert_ne!(screen.drawn_pixel(0, 0), 0);
+        assert_eq!(cpu.save_state(), state);
+    }
+
+    #[test]
+    fn reject_state_of_another_rom() {
+        let state = running_cpu().save_state();
+        let mut cpu = Cpu::new(Memory::new(), Display::new());
+        cpu.init(vec![0x12, 0x00]);
+
+        assert!(matches!(cpu.load_state(&state), Err(SaveStateError::WrongRom)));
+    }
+
+    #[test]
+    fn reject_truncated_state() {
+        let bytes = running_cpu().save_state().to_bytes();
+
+        let result = SaveState::from_bytes(&bytes[..bytes.len() / 2]);
+
+        assert!(matches!(result, Err(SaveStateError::Corrupt(_))));
+    }
+
+    #[test]
+    fn reject_state_with_wrong_memory_size() {
+        let mut cpu = running_cpu();
+        let mut state = cpu.save_state();
+        state.memory.truncate(16);
+
+        assert!(matches!(cpu.load_state(&state), Err(SaveStateError::Corrupt(_))));
+    }
+
+    #[test]
+    fn missing_file() {
+        let path = std::env::temp_dir().join("chip-8-emulator-missing.state");
+
+        assert!(matches!(SaveState::read_from(&path), Err(SaveStateError::Io(_))));
+    }
+}
@@ -0,0 +1,190 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::display::Display;
+use crate::palette::Palette;
+use crate::phosphor::Phosphor;
+use crate::render::frame_rgba;
+
+/// How many image pixels square each display pixel becomes unless asked otherwise.
+pub const DEFAULT_SCALE: usize = 8;
+
+const PNG_SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n'];
+// the most a stored (uncompressed) deflate block can hold
+const STORED_BLOCK_LENGTH: usize = 0xFFFF;
+
+/// The display coloured with `palette` as a PNG, each pixel `scale` image pixels square. It's
+/// drawn from the framebuffer rather than read back from the window, so it's the same whatever
+/// the window size, shader or afterglow.
+pub fn to_png(display: &Display, palette: &Palette, scale: usize) -> Vec<u8> {
+    let rgba = frame_rgba(display, palette, &Phosphor::default());
+    encode_png(&rgba, display.width(), display.height(), scale)
+}
+
+/// Encodes row-major RGBA bytes, `width` x `height` of them, as an RGB PNG scaled up by `scale`.
+/// The image data is stored uncompressed: screenshots are small, and it keeps the encoder
+/// simple enough to live here.
+pub fn encode_png(rgba: &[u8], width: usize, height: usize, scale: usize) -> Vec<u8> {
+    let scale = scale.max(1);
+    let (image_width, image_height) = (width * scale, height * scale);
+
+    // each row starts with filter type 0, none
+    let mut raw = Vec::with_capacity((image_width * 3 + 1) * image_height);
+    for y in 0..image_height {
+        raw.push(0);
+        let row = &rgba[(y / scale) * width * 4..][..width * 4];
+        for pixel in row.chunks(4) {
+            for _ in 0..scale {
+                raw.extend_from_slice(&pixel[..3]);
+            }
+        }
+    }
+
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&(image_width as u32).to_be_bytes());
+    header.extend_from_slice(&(image_height as u32).to_be_bytes());
+    // 8 bits per channel, RGB, deflate, no filtering variants, not interlaced
+    header.extend_from_slice(&[8, 2, 0, 0, 0]);
+
+    let mut png = PNG_SIGNATURE.to_vec();
+    write_chunk(&mut png, b"IHDR", &header);
+    write_chunk(&mut png, b"IDAT", &zlib_stored(&raw));
+    write_chunk(&mut png, b"IEND", &[]);
+    png
+}
+
+/// The file name for a screenshot of `rom_name` taken at `time`, e.g.
+/// `PONG-2024-05-01T12-33-07.png`.
+pub fn file_name(rom_name: &str, time: SystemTime) -> String {
+    timestamped_name(rom_name, time, "png")
+}
+
+/// `rom_name`, then `time` and `extension`, e.g. `PONG-2024-05-01T12-33-07.png`. Times are
+/// UTC, and use `-` rather than `:` so the name is valid on Windows too.
+pub fn timestamped_name(rom_name: &str, time: SystemTime, extension: &str) -> String {
+    let seconds = time.duration_since(UNIX_EPOCH).map(|since| since.as_secs()).unwrap_or(0);
+    let (year, month, day) = civil_date(seconds / 86_400);
+    let seconds_of_day = seconds % 86_400;
+    format!(
+        "{}-{:04}-{:02}-{:02}T{:02}-{:02}-{:02}.{}",
+        rom_name,
+        year,
+        month,
+        day,
+        seconds_of_day / 3600,
+        seconds_of_day / 60 % 60,
+        seconds_of_day % 60,
+        extension
+    )
+}
+
+/// The Gregorian year, month and day `days` after 1970-01-01, after Howard Hinnant's
+/// `civil_from_days`.
+fn civil_date(days: u64) -> (u64, u64, u64) {
+    let days = days + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days % 146_097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+fn write_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = png.len();
+    png.extend_from_slice(kind);
+    png.extend_from_slice(data);
+    let crc = crc32(&png[start..]);
+    png.extend_from_slice(&crc.to_be_bytes());
+}
+
+/// `data` wrapped as a zlib stream of stored deflate blocks.
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    let mut zlib = vec![0x78, 0x01];
+    let blocks: Vec<&[u8]> = if data.is_empty() { vec![&[]] } else { data.chunks(STORED_BLOCK_LENGTH).collect() };
+    for (index, block) in blocks.iter().enumerate() {
+        let last = index == blocks.len() - 1;
+        zlib.push(last as u8);
+        let length = block.len() as u16;
+        zlib.extend_from_slice(&length.to_le_bytes());
+        zlib.extend_from_slice(&(!length).to_le_bytes());
+        zlib.extend_from_slice(block);
+    }
+    zlib.extend_from_slice(&adler32(data).to_be_bytes());
+    zlib
+}
+
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+    for &byte in bytes {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+fn adler32(bytes: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in bytes {
+        a = (a + byte as u32) % 65_521;
+        b = (b + a) % 65_521;
+    }
+    (b << 16) | a
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn checksums_match_reference_values() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+        assert_eq!(crc32(b"IEND"), 0xAE42_6082);
+        assert_eq!(adler32(b"Wikipedia"), 0x11E6_0398);
+    }
+
+    #[test]
+    fn pixels_are_scaled_into_whole_blocks() {
+        // 2x1: red then blue
+        let png = encode_png(&[0xFF, 0, 0, 0xFF, 0, 0, 0xFF, 0xFF], 2, 1, 2);
+
+        assert_eq!(&png[..8], &PNG_SIGNATURE);
+        // IHDR says 4x2
+        assert_eq!(&png[16..24], &[0, 0, 0, 4, 0, 0, 0, 2]);
+        // the single stored block holds both rows, each red, red, blue, blue after its filter byte
+        let row = [0, 0xFF, 0, 0, 0xFF, 0, 0, 0, 0, 0xFF, 0, 0, 0xFF];
+        let idat = &png[33 + 8..];
+        assert_eq!(&idat[..3], &[0x78, 0x01, 0x01]);
+        assert_eq!(&idat[7..7 + 13], &row);
+        assert_eq!(&idat[20..20 + 13], &row);
+        assert!(png.ends_with(&[0, 0, 0, 0, b'I', b'E', b'N', b'D', 0xAE, 0x42, 0x60, 0x82]));
+    }
+
+    #[test]
+    fn large_images_are_split_into_stored_blocks() {
+        let data = vec![7; STORED_BLOCK_LENGTH + 10];
+
+        let zlib = zlib_stored(&data);
+
+        assert_eq!(&zlib[2..7], &[0x00, 0xFF, 0xFF, 0x00, 0x00]);
+        let second = 7 + STORED_BLOCK_LENGTH;
+        assert_eq!(&zlib[second..second + 5], &[0x01, 10, 0, !10, 0xFF]);
+        assert_eq!(zlib.len(), 2 + 2 * 5 + data.len() + 4);
+    }
+
+    #[test]
+    fn file_names_carry_the_rom_and_utc_time() {
+        assert_eq!(file_name("PONG", UNIX_EPOCH), "PONG-1970-01-01T00-00-00.png");
+        let time = UNIX_EPOCH + Duration::from_secs(1_714_566_787);
+        assert_eq!(file_name("PONG", time), "PONG-2024-05-01T12-33-07.png");
+        // the day after a leap day
+        let time = UNIX_EPOCH + Duration::from_secs(951_868_800);
+        assert_eq!(file_name("BRIX", time), "BRIX-2000-03-01T00-00-00.png");
+    }
+}
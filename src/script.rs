@@ -0,0 +1,369 @@
+//! Rhai scripts that watch and steer a ROM as it runs, e.g. to log when a variable changes,
+//! press keys at the right moment or fail a run once something goes wrong:
+//!
+//! ```rhai
+//! on_frame(|| {
+//!     if peek(0x3F0) == 0 {
+//!         halt("out of lives");
+//!     }
+//! });
+//! ```
+//!
+//! The top level runs once, before the first instruction, and registers hooks with
+//! `on_instruction(fn)` and `on_frame(fn)`. Hooks run after every instruction and at the end of
+//! every frame, before the timers tick, so they always see the machine between two
+//! instructions. They reach it only through `peek(addr)`, `poke(addr, val)`, `reg(x)`,
+//! `set_reg(x, val)`, `pc()`, `i()`, `instructions()`, `press(key)`, `release(key)` and
+//! `halt(msg)`; a script can't touch files or anything else outside the machine.
+
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
+use std::sync::{Arc, Mutex, PoisonError};
+
+use rhai::{Dynamic, Engine, EvalAltResult, FnPtr, AST, INT};
+
+use crate::cpu::Cpu;
+use crate::error::Chip8Error;
+use crate::headless::{self, KeyScript};
+
+/// Operations one top level or hook may take before it's stopped as stuck in a loop.
+const MAX_OPERATIONS: u64 = 1_000_000;
+
+type ScriptResult<T> = Result<T, Box<EvalAltResult>>;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ScriptError {
+    /// The script doesn't parse.
+    Syntax(String),
+    /// The script went wrong while running, e.g. peeked past the end of memory.
+    Failed(String),
+    /// The script called `halt` with this message.
+    Halted(String),
+    /// The ROM crashed.
+    Cpu(Chip8Error),
+}
+
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScriptError::Syntax(reason) => write!(f, "script doesn't parse: {}", reason),
+            ScriptError::Failed(reason) => write!(f, "script failed: {}", reason),
+            ScriptError::Halted(message) => write!(f, "script halted the run: {}", message),
+            ScriptError::Cpu(error) => write!(f, "CPU halted: {}", error),
+        }
+    }
+}
+
+impl Error for ScriptError {}
+
+impl From<Chip8Error> for ScriptError {
+    fn from(error: Chip8Error) -> Self {
+        ScriptError::Cpu(error)
+    }
+}
+
+/// The machine as a script sees it: a copy taken before the script runs, with its writes
+/// applied once it's done.
+#[derive(Default)]
+struct View {
+    registers: [u8; 16],
+    pc: u16,
+    i: u16,
+    instructions: u64,
+    memory: Vec<u8>,
+    pokes: Vec<(u16, u8)>,
+    register_writes: Vec<(u8, u8)>,
+    key_changes: Vec<(u8, bool)>,
+}
+
+impl View {
+    fn load(&mut self, cpu: &Cpu) {
+        self.registers.copy_from_slice(cpu.registers().as_slice());
+        self.pc = cpu.pc();
+        self.i = cpu.i();
+        self.instructions = cpu.instructions_executed();
+        self.memory.clear();
+        self.memory.extend_from_slice(cpu.memory().bytes());
+    }
+
+    fn apply(&mut self, cpu: &mut Cpu) {
+        for (address, value) in self.pokes.drain(..) {
+            cpu.poke(address, value);
+        }
+        for (x, value) in self.register_writes.drain(..) {
+            cpu.set_register(x, value);
+        }
+        for (key, pressed) in self.key_changes.drain(..) {
+            if pressed {
+                cpu.keys_mut().press(key);
+            } else {
+                cpu.keys_mut().release(key);
+            }
+        }
+    }
+
+    fn address(&self, address: INT) -> ScriptResult<u16> {
+        match usize::try_from(address) {
+            Ok(index) if index < self.memory.len() => Ok(index as u16),
+            _ => Err(format!("address {:#X} is outside the {} bytes of memory", address, self.memory.len()).into()),
+        }
+    }
+}
+
+// what the functions registered with the engine share with the Script that runs them
+#[derive(Default)]
+struct Shared {
+    view: View,
+    // hooks registered since the Script last took them
+    instruction_hooks: Vec<FnPtr>,
+    frame_hooks: Vec<FnPtr>,
+    halt: Option<String>,
+}
+
+fn register(x: INT) -> ScriptResult<u8> {
+    match u8::try_from(x) {
+        Ok(x) if x <= 0xF => Ok(x),
+        _ => Err(format!("there is no register V{}; they run from 0 to 15", x).into()),
+    }
+}
+
+fn byte(value: INT) -> ScriptResult<u8> {
+    u8::try_from(value).map_err(|_| format!("{} doesn't fit in a byte", value).into())
+}
+
+fn key(key: INT) -> ScriptResult<u8> {
+    match u8::try_from(key) {
+        Ok(key) if key <= 0xF => Ok(key),
+        _ => Err(format!("{} is not a keypad key; they run from 0 to 15", key).into()),
+    }
+}
+
+pub struct Script {
+    engine: Engine,
+    ast: AST,
+    shared: Arc<Mutex<Shared>>,
+    instruction_hooks: Vec<FnPtr>,
+    frame_hooks: Vec<FnPtr>,
+}
+
+impl Script {
+    /// Parses `source`; nothing runs until `start`.
+    pub fn compile(source: &str) -> Result<Script, ScriptError> {
+        let shared = Arc::new(Mutex::new(Shared::default()));
+        let engine = engine(&shared);
+        let ast = engine.compile(source).map_err(|error| ScriptError::Syntax(error.to_string()))?;
+
+        Ok(Script { engine, ast, shared, instruction_hooks: Vec::new(), frame_hooks: Vec::new() })
+    }
+
+    /// Runs the top level against `cpu` as it is, registering the script's hooks.
+    pub fn start(&mut self, cpu: &mut Cpu) -> Result<(), ScriptError> {
+        self.enter(cpu);
+        let result = self.engine.run_ast(&self.ast);
+        self.leave(cpu, result)
+    }
+
+    /// Runs the `on_instruction` hooks; call it after every instruction.
+    pub fn after_instruction(&mut self, cpu: &mut Cpu) -> Result<(), ScriptError> {
+        self.run_hooks(cpu, |script| &script.instruction_hooks)
+    }
+
+    /// Runs the `on_frame` hooks; call it at the end of every frame, before the timers tick.
+    pub fn after_frame(&mut self, cpu: &mut Cpu) -> Result<(), ScriptError> {
+        self.run_hooks(cpu, |script| &script.frame_hooks)
+    }
+
+    fn run_hooks(&mut self, cpu: &mut Cpu, hooks: impl Fn(&Script) -> &Vec<FnPtr>) -> Result<(), ScriptError> {
+        if hooks(self).is_empty() {
+            return Ok(());
+        }
+        self.enter(cpu);
+        let result = hooks(self).iter().try_for_each(|hook| hook.call::<Dynamic>(&self.engine, &self.ast, ()).map(|_| ()));
+        self.leave(cpu, result)
+    }
+
+    fn enter(&self, cpu: &Cpu) {
+        with(&self.shared, |shared| shared.view.load(cpu));
+    }
+
+    // applies what the script did even when it went on to fail, since that's what it saw happen
+    fn leave(&mut self, cpu: &mut Cpu, result: ScriptResult<()>) -> Result<(), ScriptError> {
+        let (instruction_hooks, frame_hooks) = (&mut self.instruction_hooks, &mut self.frame_hooks);
+        let halt = with(&self.shared, |shared| {
+            shared.view.apply(cpu);
+            instruction_hooks.append(&mut shared.instruction_hooks);
+            frame_hooks.append(&mut shared.frame_hooks);
+            shared.halt.take()
+        });
+        match halt {
+            Some(message) => Err(ScriptError::Halted(message)),
+            None => result.map_err(|error| ScriptError::Failed(error.to_string())),
+        }
+    }
+}
+
+/// An engine with the machine's functions and nothing that reaches outside it.
+fn engine(shared: &Arc<Mutex<Shared>>) -> Engine {
+    let mut engine = Engine::new();
+    engine.set_max_operations(MAX_OPERATIONS);
+    engine.disable_symbol("eval");
+
+    let state = Arc::clone(shared);
+    engine.register_fn("pc", move || with(&state, |shared| shared.view.pc as INT));
+    let state = Arc::clone(shared);
+    engine.register_fn("i", move || with(&state, |shared| shared.view.i as INT));
+    let state = Arc::clone(shared);
+    engine.register_fn("instructions", move || with(&state, |shared| shared.view.instructions as INT));
+    let state = Arc::clone(shared);
+    engine.register_fn("reg", move |x: INT| -> ScriptResult<INT> {
+        let x = register(x)?;
+        Ok(with(&state, |shared| shared.view.registers[x as usize] as INT))
+    });
+    let state = Arc::clone(shared);
+    engine.register_fn("set_reg", move |x: INT, value: INT| -> ScriptResult<()> {
+        let (x, value) = (register(x)?, byte(value)?);
+        with(&state, |shared| {
+            shared.view.registers[x as usize] = value;
+            shared.view.register_writes.push((x, value));
+        });
+        Ok(())
+    });
+    let state = Arc::clone(shared);
+    engine.register_fn("peek", move |address: INT| -> ScriptResult<INT> {
+        with(&state, |shared| Ok(shared.view.memory[shared.view.address(address)? as usize] as INT))
+    });
+    let state = Arc::clone(shared);
+    engine.register_fn("poke", move |address: INT, value: INT| -> ScriptResult<()> {
+        let value = byte(value)?;
+        with(&state, |shared| {
+            let address = shared.view.address(address)?;
+            shared.view.memory[address as usize] = value;
+            shared.view.pokes.push((address, value));
+            Ok(())
+        })
+    });
+    let state = Arc::clone(shared);
+    engine.register_fn("press", move |pressed: INT| -> ScriptResult<()> {
+        let pressed = key(pressed)?;
+        with(&state, |shared| shared.view.key_changes.push((pressed, true)));
+        Ok(())
+    });
+    let state = Arc::clone(shared);
+    engine.register_fn("release", move |released: INT| -> ScriptResult<()> {
+        let released = key(released)?;
+        with(&state, |shared| shared.view.key_changes.push((released, false)));
+        Ok(())
+    });
+    let state = Arc::clone(shared);
+    engine.register_fn("halt", move |message: &str| -> ScriptResult<()> {
+        with(&state, |shared| shared.halt = Some(message.to_string()));
+        // stop the script where it is, rather than running the rest of the hook
+        Err(format!("halted: {}", message).into())
+    });
+    let state = Arc::clone(shared);
+    engine.register_fn("on_instruction", move |hook: FnPtr| with(&state, |shared| shared.instruction_hooks.push(hook)));
+    let state = Arc::clone(shared);
+    engine.register_fn("on_frame", move |hook: FnPtr| with(&state, |shared| shared.frame_hooks.push(hook)));
+
+    engine
+}
+
+fn with<R>(shared: &Mutex<Shared>, f: impl FnOnce(&mut Shared) -> R) -> R {
+    f(&mut shared.lock().unwrap_or_else(PoisonError::into_inner))
+}
+
+/// `headless::run` with `script` started first and its hooks run as the ROM goes.
+pub fn run(cpu: &mut Cpu, cycles: u64, keys: &KeyScript, script: &mut Script) -> Result<(), ScriptError> {
+    script.start(cpu)?;
+    headless::run_observed(cpu, cycles, keys, |cpu, frame_ended| {
+        script.after_instruction(cpu)?;
+        if frame_ended {
+            script.after_frame(cpu)?;
+        }
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::display::Display;
+    use crate::memory::Memory;
+
+    // counts V0 up forever
+    const COUNTER: [u8; 4] = [0x70, 0x01, 0x12, 0x00];
+    // waits for a key into V1, then sets V2 to 1 and stops in a jump to itself
+    const WAIT_FOR_KEY: [u8; 6] = [0xF1, 0x0A, 0x62, 0x01, 0x12, 0x04];
+
+    fn cpu(rom: &[u8]) -> Cpu {
+        let mut cpu = Cpu::new(Memory::new(), Display::new());
+        cpu.init(rom.to_vec());
+        cpu
+    }
+
+    fn run_script(rom: &[u8], source: &str, cycles: u64) -> (Cpu, Result<(), ScriptError>) {
+        let mut cpu = cpu(rom);
+        let mut script = Script::compile(source).unwrap();
+        let result = run(&mut cpu, cycles, &KeyScript::default(), &mut script);
+        (cpu, result)
+    }
+
+    #[test]
+    fn a_script_can_stop_the_run_when_a_register_reaches_a_value() {
+        let (cpu, result) = run_script(&COUNTER, r#"on_instruction(|| if reg(0) == 5 { halt("V0 reached 5") });"#, 1000);
+
+        assert_eq!(result, Err(ScriptError::Halted(String::from("V0 reached 5"))));
+        assert_eq!(cpu.registers()[0], 5);
+        assert_eq!(cpu.instructions_executed(), 9);
+    }
+
+    #[test]
+    fn a_key_pressed_by_a_script_changes_what_the_rom_does() {
+        let (cpu, result) = run_script(&WAIT_FOR_KEY, "", 100);
+        assert_eq!(result, Ok(()));
+        assert_eq!(cpu.registers()[2], 0);
+
+        let (cpu, result) = run_script(&WAIT_FOR_KEY, "let frames = 0; on_frame(|| { frames += 1; if frames == 3 { press(7) } });", 100);
+
+        assert_eq!(result, Ok(()));
+        assert_eq!((cpu.registers()[1], cpu.registers()[2]), (7, 1));
+    }
+
+    #[test]
+    fn writes_reach_the_machine() {
+        let (cpu, result) = run_script(&COUNTER, "poke(0x300, 0xAB); set_reg(3, peek(0x300) - 1); set_reg(0, 100);", 2);
+
+        assert_eq!(result, Ok(()));
+        assert_eq!(cpu.memory().bytes()[0x300], 0xAB);
+        assert_eq!(cpu.registers()[3], 0xAA);
+        assert_eq!(cpu.registers()[0], 101);
+    }
+
+    #[test]
+    fn mistakes_stop_the_run_with_a_message() {
+        let failure = |source: &str| match run_script(&COUNTER, source, 10).1 {
+            Err(ScriptError::Failed(reason)) => reason,
+            other => panic!("{:?}", other),
+        };
+
+        assert!(failure("peek(0x1000)").contains("address 0x1000 is outside the 4096 bytes of memory"));
+        assert!(failure("set_reg(16, 0)").contains("there is no register V16"));
+        assert!(failure("poke(0x300, 256)").contains("256 doesn't fit in a byte"));
+        assert!(failure("on_frame(|| press(-1))").contains("-1 is not a keypad key"));
+        assert!(failure("on_instruction(|| { loop {} })").contains("Too many operations"));
+        assert!(matches!(Script::compile("on_frame(|| {"), Err(ScriptError::Syntax(_))));
+    }
+
+    #[test]
+    fn the_example_scripts_run() {
+        for source in [
+            include_str!("../examples/scripts/watch_memory.rhai"),
+            include_str!("../examples/scripts/press_start.rhai"),
+            include_str!("../examples/scripts/pc_out_of_bounds.rhai"),
+        ] {
+            let (_, result) = run_script(&WAIT_FOR_KEY, source, 2000);
+            assert_eq!(result, Ok(()));
+        }
+    }
+}
@@ -0,0 +1,275 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::cheats::Cheats;
+use crate::hash::fnv1a;
+use crate::palette::Rgb;
+use crate::quirks::{detect_hires_chip8, BigFontDigits, LoresBigSprite, MachineCodeCalls, NibbleOperands, Platform, Quirks};
+use crate::timing::Timing;
+
+/// The key a ROM's settings are stored under: the FNV-1a hash of its bytes in hex, so renaming
+/// or moving the file doesn't lose them.
+pub fn rom_key(rom: &[u8]) -> String {
+    format!("{:016X}", fnv1a(rom))
+}
+
+/// How to run one ROM. Anything left out falls back to whatever comes underneath: the command
+/// line's settings override the database's, which override the defaults.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RomSettings {
+    /// Only for people reading the file, and for saying which settings were applied.
+    pub name: Option<String>,
+    pub platform: Option<Platform>,
+    pub half_pixel_scroll: Option<bool>,
+    pub tall_lores_sprites: Option<bool>,
+    pub strict_big_font: Option<bool>,
+    pub strict_machine_code: Option<bool>,
+    pub strict_operands: Option<bool>,
+    pub i_overflow_flag: Option<bool>,
+    pub display_wait: Option<bool>,
+    pub key_release_wait: Option<bool>,
+    pub shift_vy: Option<bool>,
+    /// Left out, ROMs that start with the two-page hi-res jump run in that mode.
+    pub hires_chip8: Option<bool>,
+    pub cycles_per_frame: Option<usize>,
+    pub timing: Option<Timing>,
+    pub colors: Option<[Rgb; 4]>,
+    /// A `[cheats]` table; the command line's replace these rather than adding to them.
+    pub cheats: Option<Cheats>,
+    /// A `[keymap]` table, as in `chip8.toml`. Last, since TOML wants tables after values.
+    pub keymap: Option<BTreeMap<String, String>>,
+}
+
+impl RomSettings {
+    /// Settings that spell out every one of `quirks`, so nothing is left to the defaults.
+    pub fn from_quirks(quirks: Quirks) -> RomSettings {
+        RomSettings {
+            platform: Some(quirks.platform),
+            half_pixel_scroll: Some(quirks.half_pixel_lores_scroll),
+            tall_lores_sprites: Some(quirks.lores_big_sprite == LoresBigSprite::Tall8x16),
+            strict_big_font: Some(quirks.big_font_digits == BigFontDigits::Error),
+            strict_machine_code: Some(quirks.machine_code_calls == MachineCodeCalls::Error),
+            strict_operands: Some(quirks.nibble_operands == NibbleOperands::Error),
+            i_overflow_flag: Some(quirks.i_overflow_sets_vf),
+            display_wait: Some(quirks.display_wait),
+            key_release_wait: Some(quirks.fx0a_waits_for_release),
+            shift_vy: Some(quirks.shift_uses_vy),
+            hires_chip8: Some(quirks.hires_chip8),
+            ..RomSettings::default()
+        }
+    }
+
+    /// These settings with any that `overrides` has replaced.
+    pub fn merged(&self, overrides: &RomSettings) -> RomSettings {
+        RomSettings {
+            name: overrides.name.clone().or_else(|| self.name.clone()),
+            platform: overrides.platform.or(self.platform),
+            half_pixel_scroll: overrides.half_pixel_scroll.or(self.half_pixel_scroll),
+            tall_lores_sprites: overrides.tall_lores_sprites.or(self.tall_lores_sprites),
+            strict_big_font: overrides.strict_big_font.or(self.strict_big_font),
+            strict_machine_code: overrides.strict_machine_code.or(self.strict_machine_code),
+            strict_operands: overrides.strict_operands.or(self.strict_operands),
+            i_overflow_flag: overrides.i_overflow_flag.or(self.i_overflow_flag),
+            display_wait: overrides.display_wait.or(self.display_wait),
+            key_release_wait: overrides.key_release_wait.or(self.key_release_wait),
+            shift_vy: overrides.shift_vy.or(self.shift_vy),
+            hires_chip8: overrides.hires_chip8.or(self.hires_chip8),
+            cycles_per_frame: overrides.cycles_per_frame.or(self.cycles_per_frame),
+            timing: overrides.timing.or(self.timing),
+            colors: overrides.colors.or(self.colors),
+            cheats: overrides.cheats.clone().or_else(|| self.cheats.clone()),
+            keymap: overrides.keymap.clone().or_else(|| self.keymap.clone()),
+        }
+    }
+
+    /// The quirks these settings ask for, with the defaults for the rest.
+    pub fn quirks(&self) -> Quirks {
+        let mut quirks = Quirks::default();
+        if let Some(platform) = self.platform {
+            quirks.platform = platform;
+        }
+        if let Some(half_pixel_scroll) = self.half_pixel_scroll {
+            quirks.half_pixel_lores_scroll = half_pixel_scroll;
+        }
+        if self.tall_lores_sprites == Some(true) {
+            quirks.lores_big_sprite = LoresBigSprite::Tall8x16;
+        }
+        if self.strict_big_font == Some(true) {
+            quirks.big_font_digits = BigFontDigits::Error;
+        }
+        if self.strict_machine_code == Some(true) {
+            quirks.machine_code_calls = MachineCodeCalls::Error;
+        }
+        if self.strict_operands == Some(true) {
+            quirks.nibble_operands = NibbleOperands::Error;
+        }
+        if let Some(i_overflow_flag) = self.i_overflow_flag {
+            quirks.i_overflow_sets_vf = i_overflow_flag;
+        }
+        if let Some(display_wait) = self.display_wait {
+            quirks.display_wait = display_wait;
+        }
+        if let Some(key_release_wait) = self.key_release_wait {
+            quirks.fx0a_waits_for_release = key_release_wait;
+        }
+        if let Some(shift_vy) = self.shift_vy {
+            quirks.shift_uses_vy = shift_vy;
+        }
+        if let Some(hires_chip8) = self.hires_chip8 {
+            quirks.hires_chip8 = hires_chip8;
+        }
+        quirks
+    }
+
+    /// `quirks` for running `rom`, which turns on two-page hi-res CHIP-8 if the settings don't
+    /// say either way and the ROM looks like it was written for it.
+    pub fn quirks_for(&self, rom: &[u8]) -> Quirks {
+        let mut quirks = self.quirks();
+        if self.hires_chip8.is_none() {
+            quirks.hires_chip8 = detect_hires_chip8(rom);
+        }
+        quirks
+    }
+}
+
+/// Settings for the ROMs that need them, keyed by `rom_key`:
+///
+/// ```toml
+/// [roms.A1B2C3D4E5F60718]
+/// name = "Octojam game"
+/// platform = "xochip"
+/// cycles_per_frame = 200
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SettingsDatabase {
+    #[serde(default)]
+    roms: BTreeMap<String, RomSettings>,
+}
+
+impl SettingsDatabase {
+    pub fn parse(text: &str) -> Result<SettingsDatabase, toml::de::Error> {
+        toml::from_str(text)
+    }
+
+    pub fn to_toml(&self) -> String {
+        toml::to_string(self).expect("settings are always serializable")
+    }
+
+    pub fn get(&self, key: &str) -> Option<&RomSettings> {
+        self.roms.get(key)
+    }
+
+    /// Stores `settings` for the ROM with `key`, replacing what was there.
+    pub fn insert(&mut self, key: &str, settings: RomSettings) {
+        self.roms.insert(String::from(key), settings);
+    }
+
+    /// What to run the ROM with `key` with: its stored settings under `overrides`.
+    pub fn resolve(&self, key: &str, overrides: &RomSettings) -> RomSettings {
+        self.get(key).cloned().unwrap_or_default().merged(overrides)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cheats::Poke;
+
+    fn pong() -> RomSettings {
+        RomSettings {
+            name: Some(String::from("PONG")),
+            platform: Some(Platform::XoChip),
+            half_pixel_scroll: Some(true),
+            cycles_per_frame: Some(15),
+            timing: Some(Timing::Vip),
+            colors: Some([Rgb(0, 0, 0), Rgb(0xFF, 0xB0, 0), Rgb(1, 2, 3), Rgb(4, 5, 6)]),
+            cheats: Some(Cheats { freeze: vec![Poke { address: 0x3A2, value: 5 }], ..Cheats::default() }),
+            keymap: Some([(String::from("Up"), String::from("1")), (String::from("Down"), String::from("4"))].iter().cloned().collect()),
+            ..RomSettings::default()
+        }
+    }
+
+    #[test]
+    fn rom_key_is_the_hash_of_the_bytes() {
+        assert_eq!(rom_key(b""), "CBF29CE484222325");
+        assert_eq!(rom_key(b"foobar"), "85944171F73967E8");
+    }
+
+    #[test]
+    fn stored_settings_override_the_defaults() {
+        let mut database = SettingsDatabase::default();
+        database.insert("85944171F73967E8", pong());
+
+        let settings = database.resolve("85944171F73967E8", &RomSettings::default());
+
+        assert_eq!(settings, pong());
+        assert_eq!(settings.quirks().platform, Platform::XoChip);
+        assert!(settings.quirks().half_pixel_lores_scroll);
+        assert_eq!(settings.quirks().lores_big_sprite, LoresBigSprite::Wide16x16);
+        assert_eq!(database.resolve("0000000000000000", &RomSettings::default()), RomSettings::default());
+        assert_eq!(RomSettings::default().quirks(), Quirks::default());
+    }
+
+    #[test]
+    fn quirks_survive_a_round_trip_through_settings() {
+        let quirks = pong()
+            .merged(&RomSettings {
+                tall_lores_sprites: Some(true),
+                i_overflow_flag: Some(true),
+                display_wait: Some(true),
+                key_release_wait: Some(true),
+                shift_vy: Some(true),
+                hires_chip8: Some(true),
+                strict_machine_code: Some(true),
+                strict_operands: Some(true),
+                ..RomSettings::default()
+            })
+            .quirks();
+
+        let settings = RomSettings::from_quirks(quirks);
+
+        assert_eq!(settings.quirks(), quirks);
+        assert_eq!(settings.strict_big_font, Some(false));
+        assert_eq!(RomSettings::from_quirks(Quirks::default()).quirks(), Quirks::default());
+    }
+
+    #[test]
+    fn command_line_settings_override_stored_ones() {
+        let mut database = SettingsDatabase::default();
+        database.insert("85944171F73967E8", pong());
+        let command_line = RomSettings { cycles_per_frame: Some(30), strict_big_font: Some(true), ..RomSettings::default() };
+
+        let settings = database.resolve("85944171F73967E8", &command_line);
+
+        assert_eq!(settings.cycles_per_frame, Some(30));
+        assert_eq!(settings.quirks().big_font_digits, BigFontDigits::Error);
+        // and what the command line leaves alone still comes from the database
+        assert_eq!(settings.platform, Some(Platform::XoChip));
+        assert_eq!(settings.colors, pong().colors);
+    }
+
+    #[test]
+    fn hires_chip8_is_detected_unless_the_settings_say_otherwise() {
+        let hires = [0x12, 0x60, 0x01, 0x7A];
+        let pong = [0x6A, 0x02, 0x6B, 0x0C];
+
+        assert!(RomSettings::default().quirks_for(&hires).hires_chip8);
+        assert!(!RomSettings::default().quirks_for(&pong).hires_chip8);
+        assert!(!RomSettings { hires_chip8: Some(false), ..RomSettings::default() }.quirks_for(&hires).hires_chip8);
+        assert!(RomSettings { hires_chip8: Some(true), ..RomSettings::default() }.quirks_for(&pong).hires_chip8);
+    }
+
+    #[test]
+    fn database_survives_a_round_trip_through_toml() {
+        let mut database = SettingsDatabase::default();
+        database.insert("85944171F73967E8", pong());
+        database.insert("CBF29CE484222325", RomSettings { tall_lores_sprites: Some(true), ..RomSettings::default() });
+
+        let text = database.to_toml();
+
+        assert_eq!(SettingsDatabase::parse(&text).unwrap(), database);
+        assert_eq!(SettingsDatabase::parse("").unwrap(), SettingsDatabase::default());
+        assert!(SettingsDatabase::parse("[roms.A]\nplatform = \"gameboy\"\n").is_err());
+    }
+}
@@ -0,0 +1,183 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use crate::debugger::SlowMotion;
+
+/// How often `SpeedMeter::update` reports new rates; the window title changes this often.
+pub const MEASUREMENT_WINDOW: Duration = Duration::from_millis(500);
+
+/// How far back the rates look.
+pub const RATE_WINDOW: Duration = Duration::from_secs(1);
+
+/// Counts drawn frames and executed instructions, turning them into per-second rates over the
+/// last `RATE_WINDOW`. The instruction rate comes from what the CPU really ran, so it drops
+/// below the configured speed when the emulator can't keep up. The instruction counter may wrap
+/// around, and the difference is taken modulo 2^64 to match.
+#[derive(Debug, Clone)]
+pub struct SpeedMeter {
+    // (when, frames so far, instructions so far), oldest first; the first is at least
+    // RATE_WINDOW old once there's that much history
+    samples: VecDeque<(Instant, u64, u64)>,
+    frames: u64,
+    last_report: Instant,
+    fps: f64,
+    ips: f64,
+}
+
+impl SpeedMeter {
+    /// Starts measuring at `now`, with the CPU having executed `instructions` so far.
+    pub fn new(now: Instant, instructions: u64) -> SpeedMeter {
+        SpeedMeter { samples: VecDeque::from(vec![(now, 0, instructions)]), frames: 0, last_report: now, fps: 0.0, ips: 0.0 }
+    }
+
+    /// Counts a drawn frame.
+    pub fn frame(&mut self) {
+        self.frames += 1;
+    }
+
+    /// Works out the rates over the last `RATE_WINDOW` up to `now`, when the CPU has executed
+    /// `instructions`; returns whether `MEASUREMENT_WINDOW` has passed since it last did, i.e.
+    /// whether there's anything new to show.
+    pub fn update(&mut self, now: Instant, instructions: u64) -> bool {
+        self.samples.push_back((now, self.frames, instructions));
+        // keep one sample from before the window, so the rates cover all of it
+        while self.samples.get(1).is_some_and(|&(time, _, _)| now.saturating_duration_since(time) >= RATE_WINDOW) {
+            self.samples.pop_front();
+        }
+        if now.saturating_duration_since(self.last_report) < MEASUREMENT_WINDOW {
+            return false;
+        }
+        let (start, frames, instructions_at_start) = self.samples[0];
+        let seconds = now.saturating_duration_since(start).as_secs_f64();
+        self.fps = self.frames.wrapping_sub(frames) as f64 / seconds;
+        self.ips = instructions.wrapping_sub(instructions_at_start) as f64 / seconds;
+        self.last_report = now;
+        true
+    }
+
+    pub fn fps(&self) -> f64 {
+        self.fps
+    }
+
+    pub fn ips(&self) -> f64 {
+        self.ips
+    }
+}
+
+/// The window title: `CHIP-8 — PONG — 60 FPS — 700 IPS`, with the slow-motion speed, e.g.
+/// `— 1/10x`, and `— paused` on the end while they apply.
+pub fn title(rom_name: &str, meter: &SpeedMeter, paused: bool, slow_motion: Option<SlowMotion>) -> String {
+    let mut title = format!("CHIP-8 — {} — {:.0} FPS — {:.0} IPS", rom_name, meter.fps(), meter.ips());
+    if let Some(speed) = slow_motion {
+        title.push_str(&format!(" — {}", speed));
+    }
+    if paused {
+        title.push_str(" — paused");
+    }
+    title
+}
+
+/// The debug overlay's lines on speed: the rates, the instruction count and the slow-motion
+/// speed, if it's on.
+pub fn overlay(meter: &SpeedMeter, instructions: u64, slow_motion: Option<SlowMotion>) -> Vec<String> {
+    let mut lines = vec![format!("{:.0} FPS {:.0} IPS", meter.fps(), meter.ips()), format!("Ran {}", instructions)];
+    if let Some(speed) = slow_motion {
+        lines.push(format!("Slow motion {}", speed));
+    }
+    lines
+}
+
+/// What `--headless` prints when it's done: how much ran, how long it took and how fast that was.
+pub fn summary(instructions: u64, elapsed: Duration) -> String {
+    let seconds = elapsed.as_secs_f64();
+    let ips = if seconds > 0.0 { instructions as f64 / seconds } else { 0.0 };
+    format!("Ran {} instructions in {:.3} s, {:.0} IPS on average", instructions, seconds, ips)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rates_cover_the_whole_window() {
+        let start = Instant::now();
+        let mut meter = SpeedMeter::new(start, 1000);
+        for _ in 0..30 {
+            meter.frame();
+        }
+
+        assert!(!meter.update(start + Duration::from_millis(499), 1300));
+        assert!(meter.update(start + MEASUREMENT_WINDOW, 1350));
+        assert_eq!(meter.fps(), 60.0);
+        assert_eq!(meter.ips(), 700.0);
+    }
+
+    #[test]
+    fn each_window_starts_from_scratch() {
+        let start = Instant::now();
+        let mut meter = SpeedMeter::new(start, 0);
+        meter.frame();
+        meter.update(start + Duration::from_secs(1), 600);
+
+        // nothing drawn or executed while paused
+        assert!(meter.update(start + Duration::from_secs(2), 600));
+        assert_eq!(meter.fps(), 0.0);
+        assert_eq!(meter.ips(), 0.0);
+    }
+
+    #[test]
+    fn the_window_rolls_rather_than_restarting() {
+        let start = Instant::now();
+        let mut meter = SpeedMeter::new(start, 0);
+        // 60 frames of 10 instructions a second for a second, then 30 frames of 20
+        for frame in 1..90u64 {
+            meter.frame();
+            let instructions = if frame <= 60 { frame * 10 } else { 600 + (frame - 60) * 20 };
+            meter.update(start + Duration::from_millis(frame * 1000 / 60), instructions);
+        }
+        meter.frame();
+
+        // the last second is the second half of the first plus the first half of the second
+        assert!(meter.update(start + Duration::from_millis(1500), 1200));
+        assert_eq!(meter.fps(), 60.0);
+        assert_eq!(meter.ips(), 900.0);
+    }
+
+    #[test]
+    fn instruction_counts_wrapping_around_still_give_the_rate() {
+        let start = Instant::now();
+        let mut meter = SpeedMeter::new(start, u64::MAX - 99);
+
+        meter.update(start + Duration::from_secs(1), 600);
+        assert_eq!(meter.ips(), 700.0);
+    }
+
+    #[test]
+    fn overlay_and_summary_report_the_counts() {
+        let start = Instant::now();
+        let mut meter = SpeedMeter::new(start, 0);
+        for _ in 0..60 {
+            meter.frame();
+        }
+        meter.update(start + Duration::from_secs(1), 700);
+
+        assert_eq!(overlay(&meter, 700, None), vec!["60 FPS 700 IPS", "Ran 700"]);
+        assert_eq!(overlay(&meter, 700, Some(SlowMotion::Half)), vec!["60 FPS 700 IPS", "Ran 700", "Slow motion 1/2x"]);
+        assert_eq!(summary(1_500_000, Duration::from_millis(1500)), "Ran 1500000 instructions in 1.500 s, 1000000 IPS on average");
+        assert_eq!(summary(0, Duration::ZERO), "Ran 0 instructions in 0.000 s, 0 IPS on average");
+    }
+
+    #[test]
+    fn title_shows_rom_rates_slow_motion_and_pause() {
+        let start = Instant::now();
+        let mut meter = SpeedMeter::new(start, 0);
+        for _ in 0..60 {
+            meter.frame();
+        }
+        meter.update(start + Duration::from_secs(1), 699);
+
+        assert_eq!(title("PONG", &meter, false, None), "CHIP-8 — PONG — 60 FPS — 699 IPS");
+        assert_eq!(title("PONG", &meter, true, None), "CHIP-8 — PONG — 60 FPS — 699 IPS — paused");
+        assert_eq!(title("PONG", &meter, true, Some(SlowMotion::Sixtieth)), "CHIP-8 — PONG — 60 FPS — 699 IPS — 1/60x — paused");
+    }
+}
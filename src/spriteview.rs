@@ -0,0 +1,250 @@
+use crate::cpu::Cpu;
+use crate::prelude::*;
+
+/// DXYN's N for SUPER-CHIP's 16x16 sprite; 1 to 15 are 8-pixel-wide sprites that many rows tall.
+pub const BIG_SPRITE: u8 = 0;
+/// The sprite rows the viewer starts on, as tall as a font digit.
+pub const DEFAULT_ROWS: u8 = 5;
+/// Bytes of raw hex per line under the preview.
+pub const HEX_BYTES_PER_LINE: usize = 8;
+
+/// How many bytes of memory a sprite DXYN draws with `n` takes.
+pub fn sprite_bytes(n: u8) -> usize {
+    if n == BIG_SPRITE {
+        32
+    } else {
+        n as usize
+    }
+}
+
+/// The sprite DXYN with `n` would draw from memory at some address, as the viewer shows it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpritePreview {
+    /// Where the bytes were read from, which may be below the address asked for near the end of memory.
+    pub address: usize,
+    pub width: usize,
+    pub height: usize,
+    /// Row by row, whether each pixel is set.
+    pub pixels: Vec<bool>,
+    pub bytes: Vec<u8>,
+}
+
+/// Reads the sprite DXYN with `n` would draw from `address`, moving back from the end of memory
+/// as far as it takes for the whole sprite to fit rather than reading past it.
+pub fn preview(memory: &[u8], address: usize, n: u8) -> SpritePreview {
+    let length = sprite_bytes(n);
+    let address = address.min(memory.len().saturating_sub(length));
+    let mut bytes = memory[address..].iter().copied().take(length).collect::<Vec<u8>>();
+    // only a memory smaller than one sprite comes up short
+    bytes.resize(length, 0);
+    let (width, height) = if n == BIG_SPRITE { (16, 16) } else { (8, length) };
+
+    let bytes_per_row = width / 8;
+    let pixels = bytes
+        .chunks(bytes_per_row)
+        .flat_map(|row| {
+            let bits = row.iter().fold(0u32, |bits, &byte| bits << 8 | byte as u32);
+            (0..width).rev().map(move |bit| bits & (1 << bit) != 0)
+        })
+        .collect();
+    SpritePreview { address, width, height, pixels, bytes }
+}
+
+impl SpritePreview {
+    /// The sprite as RGBA, a pixel per sprite pixel: set pixels white, the rest dark grey so the
+    /// sprite's edges show.
+    pub fn rgba(&self) -> Vec<u8> {
+        self.pixels.iter().flat_map(|&set| if set { [0xFF, 0xFF, 0xFF, 0xFF] } else { [0x30, 0x30, 0x30, 0xFF] }).collect()
+    }
+
+    /// The bytes as hex, `HEX_BYTES_PER_LINE` a line with the address of the first in front.
+    pub fn hex_lines(&self) -> Vec<String> {
+        self.bytes
+            .chunks(HEX_BYTES_PER_LINE)
+            .enumerate()
+            .map(|(line, chunk)| {
+                let mut text = format!("{:#05X}", self.address + line * HEX_BYTES_PER_LINE);
+                for byte in chunk {
+                    text.push_str(&format!(" {:02X}", byte));
+                }
+                text
+            })
+            .collect()
+    }
+}
+
+/// What the sprite viewer looks at: the memory at I, or at a cursor scrubbed away from it, read
+/// as a sprite of however many rows are picked.
+pub struct SpriteView {
+    cursor: Option<usize>,
+    rows: u8,
+}
+
+impl SpriteView {
+    pub fn new() -> SpriteView {
+        SpriteView { cursor: None, rows: DEFAULT_ROWS }
+    }
+
+    /// As DXYN's N: `BIG_SPRITE` for 16x16.
+    pub fn rows(&self) -> u8 {
+        self.rows
+    }
+
+    pub fn is_following_i(&self) -> bool {
+        self.cursor.is_none()
+    }
+
+    /// One row more, from 15 on to 16x16 and from there back round to 1.
+    pub fn more_rows(&mut self) {
+        self.rows = match self.rows {
+            15 => BIG_SPRITE,
+            BIG_SPRITE => 1,
+            rows => rows + 1,
+        };
+    }
+
+    pub fn fewer_rows(&mut self) {
+        self.rows = match self.rows {
+            1 => BIG_SPRITE,
+            BIG_SPRITE => 15,
+            rows => rows - 1,
+        };
+    }
+
+    /// Moves the preview `offset` bytes on from where it is, which leaves I behind until
+    /// `follow_i`. It stops at the start of memory and where the last whole sprite fits.
+    pub fn scrub(&mut self, offset: isize, cpu: &Cpu) {
+        let last = cpu.memory().bytes().len().saturating_sub(sprite_bytes(self.rows));
+        let from = self.address(cpu).min(last);
+        self.cursor = Some(from.saturating_add_signed(offset).min(last));
+    }
+
+    pub fn follow_i(&mut self) {
+        self.cursor = None;
+    }
+
+    /// Where the preview reads from, before any moving back to fit the sprite in memory.
+    pub fn address(&self, cpu: &Cpu) -> usize {
+        self.cursor.unwrap_or(cpu.i() as usize)
+    }
+
+    pub fn preview(&self, cpu: &Cpu) -> SpritePreview {
+        preview(cpu.memory().bytes(), self.address(cpu), self.rows)
+    }
+}
+
+impl Default for SpriteView {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::display::Display;
+    use crate::memory::Memory;
+
+    fn picture(preview: &SpritePreview) -> Vec<String> {
+        preview.pixels.chunks(preview.width).map(|row| row.iter().map(|&set| if set { '#' } else { '.' }).collect()).collect()
+    }
+
+    #[test]
+    fn bytes_become_rows_of_eight_pixels_high_bit_first() {
+        let memory = [0x00, 0xF0, 0x90, 0x81, 0x00];
+
+        let preview = preview(&memory, 1, 3);
+
+        assert_eq!((preview.address, preview.width, preview.height), (1, 8, 3));
+        assert_eq!(picture(&preview), vec!["####....", "#..#....", "#......#"]);
+        assert_eq!(preview.bytes, vec![0xF0, 0x90, 0x81]);
+    }
+
+    #[test]
+    fn a_big_sprite_is_sixteen_rows_of_two_bytes() {
+        let mut memory = vec![0; 64];
+        memory[0x10] = 0x80;
+        memory[0x11] = 0x01;
+        memory[0x2E] = 0xFF;
+        memory[0x2F] = 0x00;
+
+        let preview = preview(&memory, 0x10, BIG_SPRITE);
+
+        assert_eq!((preview.width, preview.height, preview.bytes.len()), (16, 16, 32));
+        let rows = picture(&preview);
+        assert_eq!(rows[0], "#..............#");
+        assert_eq!(rows[1], "................");
+        assert_eq!(rows[15], "########........");
+    }
+
+    #[test]
+    fn the_end_of_memory_clamps_instead_of_reading_past_it() {
+        let memory: Vec<u8> = (0..=255).collect();
+
+        let short = preview(&memory, 0xFE, 5);
+        let big = preview(&memory, usize::MAX, BIG_SPRITE);
+
+        assert_eq!(short.address, 0xFB);
+        assert_eq!(short.bytes, vec![0xFB, 0xFC, 0xFD, 0xFE, 0xFF]);
+        assert_eq!(big.address, 0xE0);
+        assert_eq!(big.bytes.len(), 32);
+        // a memory smaller than the sprite reads as zeroes past its end
+        assert_eq!(preview(&[0xFF, 0x81], 1, 4).bytes, vec![0xFF, 0x81, 0, 0]);
+    }
+
+    #[test]
+    fn rgba_and_hex_show_the_same_bytes() {
+        let memory: Vec<u8> = (0..32).map(|byte| byte * 8).collect();
+        let preview = preview(&memory, 0x10, 10);
+
+        let rgba = preview.rgba();
+
+        assert_eq!(rgba.len(), 8 * 10 * 4);
+        // 0x80 is only its leftmost pixel
+        assert_eq!(&rgba[..8], &[0xFF, 0xFF, 0xFF, 0xFF, 0x30, 0x30, 0x30, 0xFF]);
+        assert_eq!(preview.hex_lines(), vec!["0x010 80 88 90 98 A0 A8 B0 B8", "0x018 C0 C8"]);
+    }
+
+    #[test]
+    fn rows_go_round_through_the_big_sprite() {
+        let mut view = SpriteView::new();
+        assert_eq!(view.rows(), DEFAULT_ROWS);
+
+        for _ in 0..10 {
+            view.more_rows();
+        }
+        assert_eq!(view.rows(), 15);
+        view.more_rows();
+        assert_eq!(view.rows(), BIG_SPRITE);
+        view.more_rows();
+        assert_eq!(view.rows(), 1);
+        view.fewer_rows();
+        view.fewer_rows();
+        assert_eq!(view.rows(), 15);
+    }
+
+    #[test]
+    fn scrubbing_leaves_i_behind_until_following_it_again() {
+        // LD I, 0x300; LD I, 0x400
+        let mut cpu = Cpu::new(Memory::new(), Display::new());
+        cpu.init(vec![0xA3, 0x00, 0xA4, 0x00]);
+        cpu.cycle().unwrap();
+        let mut view = SpriteView::new();
+        assert_eq!(view.preview(&cpu).address, 0x300);
+
+        view.scrub(-8, &cpu);
+        cpu.cycle().unwrap();
+        assert!(!view.is_following_i());
+        assert_eq!(view.preview(&cpu).address, 0x2F8);
+
+        view.follow_i();
+        assert_eq!(view.preview(&cpu).address, 0x400);
+
+        // neither end of memory can be scrubbed past
+        view.scrub(-0x1000, &cpu);
+        assert_eq!(view.address(&cpu), 0);
+        view.scrub(0x1000, &cpu);
+        assert_eq!(view.address(&cpu), 0x1000 - DEFAULT_ROWS as usize);
+        assert_eq!(view.preview(&cpu).bytes.len(), DEFAULT_ROWS as usize);
+    }
+}
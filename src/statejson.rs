@@ -0,0 +1,336 @@
+use std::fs;
+use std::path::Path;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use crate::base64;
+use crate::display::{MAX_HEIGHT, MAX_WIDTH};
+use crate::megachip::MegaRegisters;
+use crate::savestate::{SaveState, SaveStateError};
+
+/// The layout `SaveState::to_json` writes; bumped whenever a field changes meaning or goes away,
+/// but not for new ones that older documents can leave out.
+pub const STATE_JSON_VERSION: u32 = 1;
+
+// a pixel's planes, by the character that stands for them in `framebuffer`
+const PIXELS: [char; 4] = ['.', '#', '+', '@'];
+
+// the layout `to_json` describes, in the order it's written
+#[derive(Serialize)]
+struct Document {
+    version: u32,
+    rom_hash: String,
+    pc: u16,
+    i: u16,
+    sp: u8,
+    registers: [u8; 16],
+    stack: [u16; 16],
+    delay_timer: u8,
+    sound_timer: u8,
+    keys: [bool; 16],
+    waiting_for_input: bool,
+    audio_pattern: [u8; 16],
+    pitch: u8,
+    hires: bool,
+    planes: u8,
+    framebuffer: Vec<String>,
+    memory: String,
+    megachip: MegaRegisters,
+    mega_screen: Option<String>,
+}
+
+const FIELDS: [&str; 19] = [
+    "version",
+    "rom_hash",
+    "pc",
+    "i",
+    "sp",
+    "registers",
+    "stack",
+    "delay_timer",
+    "sound_timer",
+    "keys",
+    "waiting_for_input",
+    "audio_pattern",
+    "pitch",
+    "hires",
+    "planes",
+    "framebuffer",
+    "memory",
+    "megachip",
+    "mega_screen",
+];
+
+fn invalid(field: &str, reason: impl ToString) -> SaveStateError {
+    SaveStateError::Field { field: field.to_string(), reason: reason.to_string() }
+}
+
+fn field<T: DeserializeOwned>(object: &Map<String, Value>, name: &str) -> Result<T, SaveStateError> {
+    let value = object.get(name).ok_or_else(|| invalid(name, "missing"))?;
+    serde_json::from_value(value.clone()).map_err(|error| invalid(name, error))
+}
+
+// for the fields documents written before MEGA-CHIP was saved don't have
+fn optional_field<T: DeserializeOwned + Default>(object: &Map<String, Value>, name: &str) -> Result<T, SaveStateError> {
+    match object.get(name) {
+        Some(_) => field(object, name),
+        None => Ok(T::default()),
+    }
+}
+
+// column by column, as `SaveState::pixels` holds them
+fn pixels(rows: &[String]) -> Result<Vec<u8>, SaveStateError> {
+    if rows.len() != MAX_HEIGHT {
+        return Err(invalid("framebuffer", format!("expected {} rows, found {}", MAX_HEIGHT, rows.len())));
+    }
+    let mut pixels = vec![0; MAX_WIDTH * MAX_HEIGHT];
+    for (y, row) in rows.iter().enumerate() {
+        let count = row.chars().count();
+        if count != MAX_WIDTH {
+            return Err(invalid("framebuffer", format!("row {} has {} pixels rather than {}", y, count, MAX_WIDTH)));
+        }
+        for (x, character) in row.chars().enumerate() {
+            let planes = PIXELS
+                .iter()
+                .position(|&known| known == character)
+                .ok_or_else(|| invalid("framebuffer", format!("row {}, column {}: {:?} is not one of . # + @", y, x, character)))?;
+            pixels[x * MAX_HEIGHT + y] = planes as u8;
+        }
+    }
+    Ok(pixels)
+}
+
+impl SaveState {
+    /// The state as JSON, for reading, editing by hand and moving between emulator versions:
+    ///
+    /// ```json
+    /// {
+    ///   "version": 1,
+    ///   "rom_hash": "A3F0C1D2E4B59687",
+    ///   "pc": 524, "i": 768, "sp": 1,
+    ///   "registers": [25, 0, ...],
+    ///   "stack": [522, 0, ...],
+    ///   "delay_timer": 0, "sound_timer": 0,
+    ///   "keys": [false, ...],
+    ///   "waiting_for_input": false,
+    ///   "audio_pattern": [0, ...], "pitch": 64,
+    ///   "hires": false, "planes": 1,
+    ///   "framebuffer": ["....##..", ...],
+    ///   "memory": "AAAA8JCQkPAg...",
+    ///   "megachip": {"i_high": 0, "sprite_width": 8, "sprite_height": 8, "collision_color": 0, "sound": null},
+    ///   "mega_screen": null
+    /// }
+    /// ```
+    ///
+    /// `rom_hash` is the FNV-1a hash of the ROM as 16 hex digits, since JSON numbers can't hold
+    /// every u64. `registers` runs V0 to VF and `stack` holds all 16 slots, `sp` of them in use.
+    /// `framebuffer` has all 64 rows of 128 pixels whatever the resolution, drawn as in core dumps:
+    /// `.` off, `#` plane 1, `+` plane 2 and `@` both. `memory` is every byte from address 0, as
+    /// standard base64. `megachip` holds MEGA-CHIP's registers and `mega_screen`, null until 0011,
+    /// its screen in base64: the drawing and shown buffers a byte per pixel, row by row, then the
+    /// palette as RGBA and the screen's alpha. Both may be left out, as they are from documents
+    /// written before save states held MEGA-CHIP.
+    pub fn to_json(&self) -> String {
+        let framebuffer = (0..MAX_HEIGHT)
+            .map(|y| (0..MAX_WIDTH).map(|x| PIXELS[self.pixels[x * MAX_HEIGHT + y] as usize & 0b11]).collect())
+            .collect();
+        let document = Document {
+            version: STATE_JSON_VERSION,
+            rom_hash: format!("{:016X}", self.rom_hash),
+            pc: self.pc,
+            i: self.i,
+            sp: self.sp,
+            registers: self.registers,
+            stack: self.stack,
+            delay_timer: self.delay,
+            sound_timer: self.sound,
+            keys: self.keys,
+            waiting_for_input: self.waiting_for_input,
+            audio_pattern: self.audio_pattern,
+            pitch: self.pitch,
+            hires: self.hires,
+            planes: self.planes,
+            framebuffer,
+            memory: base64::encode(&self.memory),
+            megachip: self.megachip,
+            mega_screen: self.mega_screen.as_deref().map(base64::encode),
+        };
+        serde_json::to_string_pretty(&document).expect("save state is always serializable")
+    }
+
+    /// Reads what `to_json` wrote, naming the field at fault when the document doesn't fit the
+    /// layout. Whether the state fits the machine is left to `Cpu::load_state`.
+    pub fn from_json(text: &str) -> Result<SaveState, SaveStateError> {
+        let document: Value = serde_json::from_str(text).map_err(|error| SaveStateError::Corrupt(error.to_string()))?;
+        let object = document.as_object().ok_or_else(|| SaveStateError::Corrupt(String::from("expected a JSON object")))?;
+        let version: u32 = field(object, "version")?;
+        if version != STATE_JSON_VERSION {
+            return Err(SaveStateError::Version(version));
+        }
+        if let Some(unknown) = object.keys().find(|key| !FIELDS.contains(&key.as_str())) {
+            return Err(invalid(unknown, "not a save state field"));
+        }
+
+        let rom_hash: String = field(object, "rom_hash")?;
+        let rom_hash = Some(&rom_hash)
+            .filter(|hash| hash.len() == 16)
+            .and_then(|hash| u64::from_str_radix(hash, 16).ok())
+            .ok_or_else(|| invalid("rom_hash", format!("expected 16 hex digits, found {:?}", rom_hash)))?;
+        let sp: u8 = field(object, "sp")?;
+        if sp > 16 {
+            return Err(invalid("sp", format!("{} is more than the 16 stack slots", sp)));
+        }
+        let planes: u8 = field(object, "planes")?;
+        if planes > 0b11 {
+            return Err(invalid("planes", format!("{} selects planes that don't exist", planes)));
+        }
+        let memory: String = field(object, "memory")?;
+        let memory = base64::decode(&memory).map_err(|reason| invalid("memory", reason))?;
+        let framebuffer: Vec<String> = field(object, "framebuffer")?;
+        let mega_screen: Option<String> = optional_field(object, "mega_screen")?;
+        let mega_screen = mega_screen.map(|screen| base64::decode(&screen).map_err(|reason| invalid("mega_screen", reason))).transpose()?;
+
+        Ok(SaveState {
+            rom_hash,
+            registers: field(object, "registers")?,
+            i: field(object, "i")?,
+            pc: field(object, "pc")?,
+            stack: field(object, "stack")?,
+            sp,
+            delay: field(object, "delay_timer")?,
+            sound: field(object, "sound_timer")?,
+            memory,
+            pixels: pixels(&framebuffer)?,
+            hires: field(object, "hires")?,
+            planes,
+            audio_pattern: field(object, "audio_pattern")?,
+            pitch: field(object, "pitch")?,
+            keys: field(object, "keys")?,
+            waiting_for_input: field(object, "waiting_for_input")?,
+            megachip: optional_field(object, "megachip")?,
+            mega_screen,
+        })
+    }
+
+    pub fn write_json_to(&self, path: &Path) -> Result<(), SaveStateError> {
+        fs::write(path, self.to_json())?;
+        Ok(())
+    }
+
+    pub fn read_json_from(path: &Path) -> Result<SaveState, SaveStateError> {
+        SaveState::from_json(&fs::read_to_string(path)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cpu::Cpu;
+    use crate::display::Display;
+    use crate::memory::Memory;
+    use crate::quirks::{Platform, Quirks};
+
+    fn xo_chip_state() -> SaveState {
+        // LD I, 0x210; F201: select plane 2; DRW V0, V0, 1; F301; DRW V0, V0, 2; JP 0x20A; sprite
+        let rom = vec![0xA2, 0x10, 0xF2, 0x01, 0xD0, 0x01, 0xF3, 0x01, 0xD0, 0x02, 0x12, 0x0A, 0x00, 0x00, 0x00, 0x00, 0xF0, 0x81];
+        let mut cpu = Cpu::new(Memory::new(), Display::new());
+        cpu.set_quirks(Quirks { platform: Platform::XoChip, ..Quirks::default() });
+        cpu.init(rom);
+        for _ in 0..6 {
+            cpu.cycle().unwrap();
+        }
+        cpu.save_state()
+    }
+
+    fn replaced(json: &str, field: &str, value: &str) -> String {
+        let mut document: Value = serde_json::from_str(json).unwrap();
+        document[field] = serde_json::from_str(value).unwrap();
+        document.to_string()
+    }
+
+    fn error(json: &str) -> String {
+        SaveState::from_json(json).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn round_trip_keeps_every_byte() {
+        let mut state = xo_chip_state();
+        state.keys[5] = true;
+        state.sound = 9;
+        state.audio_pattern[3] = 0xA5;
+
+        let restored = SaveState::from_json(&state.to_json()).unwrap();
+
+        assert_eq!(restored, state);
+        assert_eq!(restored.to_bytes(), state.to_bytes());
+    }
+
+    #[test]
+    fn the_framebuffer_is_drawn_row_by_row() {
+        let json = xo_chip_state().to_json();
+        let document: Value = serde_json::from_str(&json).unwrap();
+        let rows = document["framebuffer"].as_array().unwrap();
+
+        assert_eq!(rows.len(), MAX_HEIGHT);
+        assert!(rows[0].as_str().unwrap().starts_with("@@@@....."), "{}", rows[0]);
+        assert!(rows[1].as_str().unwrap().starts_with("#......#."), "{}", rows[1]);
+    }
+
+    #[test]
+    fn mistakes_name_the_field() {
+        let json = xo_chip_state().to_json();
+
+        assert_eq!(error(&replaced(&json, "version", "2")), "save state format version 2 is not supported; this emulator reads version 1");
+        assert_eq!(error(&replaced(&json, "pc", "\"0x200\"")), "save state field `pc` is invalid: invalid type: string \"0x200\", expected u16");
+        assert_eq!(error(&replaced(&json, "registers", "[1, 2]")), "save state field `registers` is invalid: invalid length 2, expected an array of length 16");
+        assert_eq!(error(&replaced(&json, "rom_hash", "\"12\"")), "save state field `rom_hash` is invalid: expected 16 hex digits, found \"12\"");
+        assert_eq!(error(&replaced(&json, "sp", "17")), "save state field `sp` is invalid: 17 is more than the 16 stack slots");
+        assert_eq!(error(&replaced(&json, "memory", "\"AA*A\"")), "save state field `memory` is invalid: unexpected '*' at character 2");
+        assert_eq!(error(&replaced(&json, "framebuffer", "[]")), "save state field `framebuffer` is invalid: expected 64 rows, found 0");
+        assert_eq!(error(&replaced(&json, "cheats", "true")), "save state field `cheats` is invalid: not a save state field");
+        let mut document: Value = serde_json::from_str(&json).unwrap();
+        document.as_object_mut().unwrap().remove("pitch");
+        assert_eq!(error(&document.to_string()), "save state field `pitch` is invalid: missing");
+        assert!(error("{\"version\": 1,").starts_with("save state is corrupt: EOF while parsing"));
+    }
+
+    #[test]
+    fn the_megachip_screen_round_trips_and_older_documents_go_without_it() {
+        // 0011; 0580: half the screen's alpha; 03NN and 04NN: 16x2 sprites; DRW V0, V0 of the ROM
+        let rom = vec![0x00, 0x11, 0x05, 0x80, 0x03, 0x10, 0x04, 0x02, 0xD0, 0x00, 0x12, 0x0A];
+        let mut cpu = Cpu::new(Memory::new(), Display::new());
+        cpu.set_quirks(Quirks::profile("megachip").unwrap());
+        cpu.init(rom);
+        for _ in 0..5 {
+            cpu.cycle().unwrap();
+        }
+        let state = cpu.save_state();
+
+        let restored = SaveState::from_json(&state.to_json()).unwrap();
+
+        assert!(state.mega_screen.is_some());
+        assert_eq!(restored, state);
+
+        let mut document: Value = serde_json::from_str(&xo_chip_state().to_json()).unwrap();
+        document.as_object_mut().unwrap().remove("megachip");
+        document.as_object_mut().unwrap().remove("mega_screen");
+        let older = SaveState::from_json(&document.to_string()).unwrap();
+        assert_eq!(older, xo_chip_state());
+        assert_eq!(error(&replaced(&xo_chip_state().to_json(), "mega_screen", "\"AA*A\"")), "save state field `mega_screen` is invalid: unexpected '*' at character 2");
+    }
+
+    #[test]
+    fn pixels_outside_the_planes_are_rejected() {
+        let json = xo_chip_state().to_json();
+        let mut document: Value = serde_json::from_str(&json).unwrap();
+        let row = document["framebuffer"][3].as_str().unwrap().replacen('.', "x", 1);
+        document["framebuffer"][3] = Value::String(row);
+
+        assert_eq!(
+            error(&document.to_string()),
+            "save state field `framebuffer` is invalid: row 3, column 0: 'x' is not one of . # + @"
+        );
+    }
+}
@@ -0,0 +1,247 @@
+use std::error::Error;
+use std::fmt;
+use std::io::{self, Stdout, Write};
+use std::panic;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crossterm::cursor::{Hide, MoveTo, Show};
+use crossterm::event::{self, Event, KeyCode, KeyModifiers};
+use crossterm::style::Print;
+use crossterm::terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen};
+use crossterm::{execute, queue};
+
+use crate::cpu::Cpu;
+use crate::display::{Display, Rect};
+use crate::error::Chip8Error;
+use crate::frontend::{self, Frontend};
+use crate::halfblock::{diff, Cells};
+use crate::keymap::Keymap;
+use crate::keys::Keys;
+use crate::timing::Timing;
+
+/// Terminals only report key presses, and repeats while a key is held, so a key counts as held
+/// for this many frames after the last one.
+const KEY_HOLD_FRAMES: u8 = 10;
+
+/// Why a terminal run stopped early.
+#[derive(Debug)]
+pub enum TerminalError {
+    Io(io::Error),
+    Halted(Chip8Error),
+}
+
+impl fmt::Display for TerminalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TerminalError::Io(error) => write!(f, "problem drawing to the terminal: {}", error),
+            TerminalError::Halted(error) => write!(f, "CPU halted: {}", error),
+        }
+    }
+}
+
+impl Error for TerminalError {}
+
+impl From<io::Error> for TerminalError {
+    fn from(error: io::Error) -> TerminalError {
+        TerminalError::Io(error)
+    }
+}
+
+/// Draws into the terminal it was created in, reads the keypad from its keyboard and rings its
+/// bell for sound, if asked to.
+pub struct TerminalFrontend<'a> {
+    out: Stdout,
+    keymap: &'a Keymap,
+    bell: bool,
+    size: (u16, u16),
+    // what's on screen, None after a clear
+    shown: Option<Cells>,
+    too_small_shown: bool,
+    // frames each key stays held for
+    held: [u8; 16],
+    beeping: bool,
+    quit: bool,
+    error: Option<io::Error>,
+}
+
+impl<'a> TerminalFrontend<'a> {
+    pub fn new(keymap: &'a Keymap, bell: bool) -> io::Result<TerminalFrontend<'a>> {
+        Ok(TerminalFrontend {
+            out: io::stdout(),
+            keymap,
+            bell,
+            size: terminal::size()?,
+            shown: None,
+            too_small_shown: false,
+            held: [0; 16],
+            beeping: false,
+            quit: false,
+            error: None,
+        })
+    }
+
+    /// Whether Esc or Ctrl+C has been pressed.
+    pub fn wants_to_quit(&self) -> bool {
+        self.quit
+    }
+
+    /// The first error drawing or reading keys ran into, since `Frontend` has no way to return it.
+    pub fn take_error(&mut self) -> Option<io::Error> {
+        self.error.take()
+    }
+
+    fn draw(&mut self, display: &Display) -> io::Result<()> {
+        let cells = Cells::new(display);
+        if self.shown.as_ref().is_some_and(|shown| (shown.width(), shown.height()) != (cells.width(), cells.height())) {
+            self.clear()?;
+        }
+        let (x, y) = match cells.origin(self.size.0, self.size.1) {
+            Some(origin) => origin,
+            None => return self.show_too_small(&cells),
+        };
+        for change in diff(self.shown.as_ref(), &cells) {
+            queue!(self.out, MoveTo(x + change.column as u16, y + change.row as u16), Print(change.text))?;
+        }
+        self.shown = Some(cells);
+        self.out.flush()
+    }
+
+    fn show_too_small(&mut self, cells: &Cells) -> io::Result<()> {
+        if !self.too_small_shown {
+            let message = format!(
+                "The terminal is {}x{}; make it at least {}x{} to see the display. Esc quits.",
+                self.size.0,
+                self.size.1,
+                cells.width(),
+                cells.height()
+            );
+            queue!(self.out, MoveTo(0, 0), Print(message))?;
+            self.too_small_shown = true;
+        }
+        self.out.flush()
+    }
+
+    fn clear(&mut self) -> io::Result<()> {
+        self.shown = None;
+        self.too_small_shown = false;
+        queue!(self.out, Clear(ClearType::All))
+    }
+
+    fn read_keys(&mut self) -> io::Result<()> {
+        while event::poll(Duration::from_secs(0))? {
+            match event::read()? {
+                Event::Key(key) if key.code == KeyCode::Esc => self.quit = true,
+                // raw mode swallows the signal, so Ctrl+C arrives as a key
+                Event::Key(key) if key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL) => self.quit = true,
+                Event::Key(key) => {
+                    if let KeyCode::Char(c) = key.code {
+                        if let Some(key) = self.keymap.key(&c.to_ascii_uppercase().to_string()) {
+                            self.held[key as usize] = KEY_HOLD_FRAMES;
+                        }
+                    }
+                }
+                Event::Resize(columns, rows) => {
+                    self.size = (columns, rows);
+                    self.clear()?;
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Frontend for TerminalFrontend<'_> {
+    fn present(&mut self, display: &Display) {
+        if let Err(error) = self.draw(display) {
+            self.error.get_or_insert(error);
+        }
+    }
+
+    // what's shown is only thrown away by a clear, so an unchanged display needs no diffing
+    fn present_dirty(&mut self, display: &Display, dirty: Option<Rect>) {
+        if dirty.is_some() || self.shown.is_none() {
+            self.present(display);
+        }
+    }
+
+    fn poll_keys(&mut self, keys: &mut Keys) {
+        if let Err(error) = self.read_keys() {
+            self.error.get_or_insert(error);
+        }
+        for (key, frames) in self.held.iter_mut().enumerate() {
+            if *frames > 0 {
+                keys.press(key as u8);
+                *frames -= 1;
+            } else {
+                keys.release(key as u8);
+            }
+        }
+    }
+
+    fn beep(&mut self, on: bool) {
+        if on && !self.beeping && self.bell {
+            if let Err(error) = queue!(self.out, Print('\x07')) {
+                self.error.get_or_insert(error);
+            }
+        }
+        self.beeping = on;
+    }
+}
+
+/// Puts the terminal back as it was, leaving raw mode and the alternate screen.
+fn restore() {
+    let _ = execute!(io::stdout(), Show, LeaveAlternateScreen);
+    let _ = terminal::disable_raw_mode();
+}
+
+/// Restores the terminal when dropped, however the run ends.
+struct RawMode;
+
+impl RawMode {
+    fn enter() -> io::Result<RawMode> {
+        terminal::enable_raw_mode()?;
+        let raw_mode = RawMode;
+        execute!(io::stdout(), EnterAlternateScreen, Hide)?;
+        // a panic message printed on the alternate screen would vanish with it, so restore first
+        let report = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            restore();
+            report(info);
+        }));
+        Ok(raw_mode)
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        restore();
+    }
+}
+
+/// Runs `cpu` in the terminal, `cycles_per_frame` instructions a frame under `timing` at 60
+/// frames a second, until Esc or Ctrl+C, 00FD or an error. Keys go through `keymap`; with `bell` the terminal
+/// bell rings whenever the sound timer starts.
+pub fn run(cpu: &mut Cpu, keymap: &Keymap, cycles_per_frame: usize, timing: Timing, bell: bool) -> Result<(), TerminalError> {
+    let frame = Duration::from_secs(1) / 60;
+    let _raw_mode = RawMode::enter()?;
+    let mut frontend = TerminalFrontend::new(keymap, bell)?;
+    frontend.clear()?;
+    let mut next_frame = Instant::now();
+    while !frontend.wants_to_quit() && !cpu.is_finished() {
+        frontend::run_frame(cpu, &mut frontend, cycles_per_frame, timing).map_err(TerminalError::Halted)?;
+        if let Some(error) = frontend.take_error() {
+            return Err(error.into());
+        }
+        next_frame += frame;
+        let now = Instant::now();
+        if next_frame > now {
+            thread::sleep(next_frame - now);
+        } else {
+            // too slow to keep up, so don't try to catch up later
+            next_frame = now;
+        }
+    }
+    Ok(())
+}
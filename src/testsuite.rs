@@ -0,0 +1,361 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::cpu::{Cpu, DEFAULT_LOAD_ADDRESS};
+use crate::display::Display;
+use crate::hash::fnv1a;
+use crate::headless::{self, KeyScript};
+use crate::memory::Memory;
+use crate::settings::RomSettings;
+
+/// Instructions a case runs for when its manifest entry doesn't say.
+pub const DEFAULT_CYCLES: u64 = 1000;
+
+/// A list of ROMs to run headlessly and what each should leave on the screen, as read by the
+/// `test-suite` subcommand. Paths are relative to the manifest:
+///
+/// ```toml
+/// [[rom]]
+/// path = "ibm.ch8"
+/// cycles = 100
+/// hash = "5C1D6E8A10F2B3C4"
+///
+/// [[rom]]
+/// path = "octo/skyward.ch8"
+/// cycles = 5000
+/// keys = "5@100,release5@160"
+/// pbm = "expected/skyward.pbm"
+///
+/// [rom.quirks]
+/// platform = "xochip"
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Manifest {
+    #[serde(default, rename = "rom")]
+    pub cases: Vec<TestCase>,
+}
+
+/// One ROM in a manifest. It's expected to end up with the framebuffer whose `framebuffer_hash`
+/// is `hash`, or the one drawn in the plain PBM file `pbm`; with neither, only `--update` can
+/// pass it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TestCase {
+    pub path: PathBuf,
+    #[serde(default = "default_cycles")]
+    pub cycles: u64,
+    /// Scripted key input, in `--keys` syntax.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub keys: String,
+    /// Seeds CXKK's generator; 0 unless given, so every run is the same.
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub seed: u64,
+    pub hash: Option<String>,
+    pub pbm: Option<PathBuf>,
+    /// The quirk settings `roms.toml` takes; the speed and colours are ignored.
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub quirks: RomSettings,
+}
+
+fn default_cycles() -> u64 {
+    DEFAULT_CYCLES
+}
+
+fn is_zero(value: &u64) -> bool {
+    *value == 0
+}
+
+fn is_default(settings: &RomSettings) -> bool {
+    *settings == RomSettings::default()
+}
+
+impl Manifest {
+    /// Reads a manifest, checking every entry so a typo is reported before anything runs.
+    pub fn parse(text: &str) -> Result<Manifest, ManifestError> {
+        let manifest: Manifest = toml::from_str(text).map_err(|error| ManifestError::Toml(error.to_string()))?;
+        for case in &manifest.cases {
+            let invalid = |problem: String| ManifestError::Invalid { rom: case.path.clone(), problem };
+            if case.cycles == 0 {
+                return Err(invalid(String::from("cycles must be a positive integer")));
+            }
+            KeyScript::parse(&case.keys).map_err(invalid)?;
+            if case.hash.is_some() && case.pbm.is_some() {
+                return Err(invalid(String::from("has both a hash and a pbm; expect one or the other")));
+            }
+            if let Some(hash) = &case.hash {
+                if hash.len() != 16 || u64::from_str_radix(hash, 16).is_err() {
+                    return Err(invalid(format!("hash {} isn't 16 hex digits", hash)));
+                }
+            }
+        }
+        Ok(manifest)
+    }
+
+    pub fn to_toml(&self) -> String {
+        toml::to_string(self).expect("manifests are always serializable")
+    }
+}
+
+impl TestCase {
+    /// The script `keys` holds; it was checked when the manifest was parsed.
+    pub fn script(&self) -> KeyScript {
+        KeyScript::parse(&self.keys).unwrap_or_default()
+    }
+}
+
+/// Runs `case` on `bytecode`, loaded at 0x200, the way `--headless` would. The display is
+/// returned even when the ROM crashes, with the crash as the error.
+pub fn run_case(case: &TestCase, bytecode: Vec<u8>) -> (Display, Result<(), String>) {
+    let mut cpu = Cpu::new(Memory::new(), Display::new());
+    // before loading, since XO-CHIP ROMs may need more than 4 KiB
+    cpu.set_quirks(case.quirks.quirks_for(&bytecode));
+    if let Err(error) = cpu.load_rom(DEFAULT_LOAD_ADDRESS, &bytecode) {
+        return (Display::new(), Err(error.to_string()));
+    }
+    cpu.seed(case.seed);
+    let result = headless::run(&mut cpu, case.cycles, &case.script()).map_err(|error| error.to_string());
+    (cpu.display().clone(), result)
+}
+
+/// A hash of what's on screen: the pixels in use, their planes and so the resolution, but not the
+/// pixels hires mode left off screen or which planes are selected.
+pub fn framebuffer_hash(display: &Display) -> u64 {
+    fnv1a(display.to_ascii().as_bytes())
+}
+
+/// How a hash is written in manifests.
+pub fn format_hash(hash: u64) -> String {
+    format!("{:016X}", hash)
+}
+
+/// Checks `display` against what `case` expects. `expected_pbm` is the contents of the case's PBM
+/// file, which the caller reads. The error says how it differs.
+pub fn compare(case: &TestCase, display: &Display, expected_pbm: Option<&str>) -> Result<(), String> {
+    match (&case.hash, expected_pbm) {
+        (Some(expected), _) => {
+            let actual = format_hash(framebuffer_hash(display));
+            if actual.eq_ignore_ascii_case(expected) {
+                Ok(())
+            } else {
+                Err(format!("framebuffer hash {}, expected {}", actual, expected.to_uppercase()))
+            }
+        }
+        (None, Some(expected)) => compare_pbm(expected, &display.to_pbm()),
+        (None, None) => Err(String::from("nothing to compare with; --update records the framebuffer")),
+    }
+}
+
+/// Compares two plain (P1) PBM images pixel by pixel, so whitespace and comments don't matter.
+pub fn compare_pbm(expected: &str, actual: &str) -> Result<(), String> {
+    let expected = parse_pbm(expected).ok_or("the expected PBM isn't a plain (P1) PBM image")?;
+    let actual = parse_pbm(actual).ok_or("the framebuffer didn't convert to PBM")?;
+    if (expected.0, expected.1) != (actual.0, actual.1) {
+        return Err(format!("the display is {}x{}, expected {}x{}", actual.0, actual.1, expected.0, expected.1));
+    }
+    match expected.2.iter().zip(&actual.2).filter(|(expected, actual)| expected != actual).count() {
+        0 => Ok(()),
+        1 => Err(String::from("1 pixel differs from the expected PBM")),
+        differing => Err(format!("{} pixels differ from the expected PBM", differing)),
+    }
+}
+
+// the width, height and pixels, row-major, of a plain PBM; bits needn't be separated by whitespace
+fn parse_pbm(text: &str) -> Option<(usize, usize, Vec<bool>)> {
+    let mut tokens = text.lines().map(|line| line.split('#').next().unwrap_or_default()).flat_map(str::split_whitespace);
+    if tokens.next()? != "P1" {
+        return None;
+    }
+    let width = tokens.next()?.parse::<usize>().ok()?;
+    let height = tokens.next()?.parse::<usize>().ok()?;
+    let mut pixels = Vec::with_capacity(width * height);
+    for bit in tokens.flat_map(str::chars) {
+        match bit {
+            '0' => pixels.push(false),
+            '1' => pixels.push(true),
+            _ => return None,
+        }
+    }
+    if pixels.len() == width * height {
+        Some((width, height, pixels))
+    } else {
+        None
+    }
+}
+
+/// Where a failing case's framebuffer is written for inspection: next to the manifest, named
+/// after the ROM, e.g. `ibm.actual.pbm`.
+pub fn actual_path(manifest_dir: &Path, rom: &Path, extension: &str) -> PathBuf {
+    let stem = rom.file_stem().map(|stem| stem.to_string_lossy().into_owned()).unwrap_or_default();
+    manifest_dir.join(format!("{}.actual.{}", stem, extension))
+}
+
+/// How a case went.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Verdict {
+    Pass,
+    Fail(String),
+    /// `--update` recorded its framebuffer as the new expectation.
+    Updated,
+}
+
+/// A row of the results table.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CaseReport {
+    pub rom: PathBuf,
+    pub elapsed: Duration,
+    pub verdict: Verdict,
+}
+
+/// The results as a table, one row per case and a count of each verdict below:
+///
+/// ```text
+/// PASS     ibm.ch8    1.2 ms
+/// FAIL     brix.ch8  12.0 ms  framebuffer hash 0123456789ABCDEF, expected FEDCBA9876543210
+/// 1 passed, 1 failed
+/// ```
+pub fn table(reports: &[CaseReport]) -> Vec<String> {
+    let width = reports.iter().map(|report| report.rom.display().to_string().len()).max().unwrap_or(0);
+    let mut lines: Vec<String> = reports
+        .iter()
+        .map(|report| {
+            let (status, reason) = match &report.verdict {
+                Verdict::Pass => ("PASS", ""),
+                Verdict::Fail(reason) => ("FAIL", reason.as_str()),
+                Verdict::Updated => ("UPDATED", ""),
+            };
+            let millis = report.elapsed.as_secs_f64() * 1000.0;
+            let row = format!("{:<8} {:<width$} {:>7.1} ms  {}", status, report.rom.display(), millis, reason, width = width);
+            row.trim_end().to_string()
+        })
+        .collect();
+    let count = |wanted: fn(&Verdict) -> bool| reports.iter().filter(|report| wanted(&report.verdict)).count();
+    let mut summary = format!("{} passed, {} failed", count(|verdict| *verdict == Verdict::Pass), count(|verdict| matches!(verdict, Verdict::Fail(_))));
+    let updated = count(|verdict| *verdict == Verdict::Updated);
+    if updated > 0 {
+        summary.push_str(&format!(", {} updated", updated));
+    }
+    lines.push(summary);
+    lines
+}
+
+/// Why a manifest was rejected.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ManifestError {
+    /// The file isn't valid TOML, or a value has the wrong type.
+    Toml(String),
+    Invalid { rom: PathBuf, problem: String },
+}
+
+impl fmt::Display for ManifestError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ManifestError::Toml(error) => write!(f, "{}", error),
+            ManifestError::Invalid { rom, problem } => write!(f, "{}: {}", rom.display(), problem),
+        }
+    }
+}
+
+impl std::error::Error for ManifestError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::quirks::Platform;
+
+    const MANIFEST: &str = "[[rom]]\npath = \"ibm.ch8\"\nhash = \"00000000000000ff\"\n\n[[rom]]\npath = \"games/brix.ch8\"\ncycles = 5000\nkeys = \"4@10,release4@20\"\npbm = \"brix.pbm\"\n\n[rom.quirks]\nplatform = \"xochip\"\n";
+
+    fn case(hash: Option<&str>) -> TestCase {
+        TestCase { path: PathBuf::from("test.ch8"), cycles: 10, keys: String::new(), seed: 0, hash: hash.map(String::from), pbm: None, quirks: RomSettings::default() }
+    }
+
+    #[test]
+    fn manifests_are_read_with_defaults_for_what_is_left_out() {
+        let manifest = Manifest::parse(MANIFEST).unwrap();
+
+        assert_eq!(manifest.cases.len(), 2);
+        assert_eq!(manifest.cases[0].cycles, DEFAULT_CYCLES);
+        assert_eq!(manifest.cases[0].hash.as_deref(), Some("00000000000000ff"));
+        assert_eq!(manifest.cases[0].quirks, RomSettings::default());
+        assert_eq!(manifest.cases[1].path, PathBuf::from("games/brix.ch8"));
+        assert_eq!(manifest.cases[1].script().events().len(), 2);
+        assert_eq!(manifest.cases[1].pbm, Some(PathBuf::from("brix.pbm")));
+        assert_eq!(manifest.cases[1].quirks.quirks().platform, Platform::XoChip);
+        assert_eq!(Manifest::parse(&manifest.to_toml()).unwrap(), manifest);
+        assert_eq!(Manifest::parse("").unwrap(), Manifest::default());
+    }
+
+    #[test]
+    fn mistakes_in_manifests_are_reported_with_the_rom() {
+        let error = |text: &str| Manifest::parse(text).unwrap_err().to_string();
+
+        assert!(error("[[rom]]\npath = \"a.ch8\"\ncycles = 0\n").starts_with("a.ch8: cycles"));
+        assert!(error("[[rom]]\npath = \"a.ch8\"\nkeys = \"5\"\n").contains("missing '@cycle'"));
+        assert!(error("[[rom]]\npath = \"a.ch8\"\nhash = \"xyz\"\n").contains("isn't 16 hex digits"));
+        assert!(error("[[rom]]\npath = \"a.ch8\"\nhash = \"0000000000000000\"\npbm = \"a.pbm\"\n").contains("both"));
+        assert!(matches!(Manifest::parse("[[rom]]\ncycles = 5\n"), Err(ManifestError::Toml(_))));
+    }
+
+    #[test]
+    fn hashes_compare_regardless_of_case() {
+        let mut display = Display::new();
+        display.pixels[3][4] = 1;
+        let hash = format_hash(framebuffer_hash(&display));
+
+        assert_eq!(compare(&case(Some(&hash)), &display, None), Ok(()));
+        assert_eq!(compare(&case(Some(&hash.to_lowercase())), &display, None), Ok(()));
+        let error = compare(&case(Some(&hash)), &Display::new(), None).unwrap_err();
+        assert!(error.ends_with(&format!("expected {}", hash)), "{}", error);
+        assert!(compare(&case(None), &display, None).unwrap_err().contains("--update"));
+    }
+
+    #[test]
+    fn pbm_files_compare_pixel_by_pixel() {
+        let mut display = Display::new();
+        display.pixels[0][0] = 1;
+        display.pixels[63][31] = 1;
+        let pbm = display.to_pbm();
+        // the same image, with a comment and its bits run together
+        let packed = format!("P1\n# drawn by hand\n64 32\n1{}1\n", "0".repeat(64 * 32 - 2));
+
+        assert_eq!(compare(&case(None), &display, Some(&pbm)), Ok(()));
+        assert_eq!(compare_pbm(&packed, &pbm), Ok(()));
+        assert_eq!(compare_pbm(&pbm, &Display::new().to_pbm()), Err(String::from("2 pixels differ from the expected PBM")));
+        let mut hires = Display::new();
+        hires.set_resolution(crate::display::Resolution::Hires);
+        assert_eq!(compare_pbm(&pbm, &hires.to_pbm()), Err(String::from("the display is 128x64, expected 64x32")));
+        assert!(compare_pbm("P4\n64 32\n", &pbm).is_err());
+        assert!(compare_pbm("P1\n2 2\n0 1 1\n", &pbm).is_err());
+    }
+
+    #[test]
+    fn cases_run_headlessly_and_keep_the_display_when_they_crash() {
+        // draw the font's 0 at (0, 0), then return with an empty stack
+        let rom = vec![0xF0, 0x29, 0xD0, 0x05, 0x00, 0xEE];
+
+        let (display, result) = run_case(&case(None), rom);
+
+        assert_eq!(display.pixels()[0][0], 1);
+        assert!(result.unwrap_err().contains("stack"));
+        assert!(run_case(&case(None), Vec::new()).1.is_err());
+    }
+
+    #[test]
+    fn the_table_lines_up_and_counts_the_verdicts() {
+        let report = |rom: &str, millis: u64, verdict: Verdict| CaseReport { rom: PathBuf::from(rom), elapsed: Duration::from_millis(millis), verdict };
+        let lines = table(&[
+            report("ibm.ch8", 2, Verdict::Pass),
+            report("games/brix.ch8", 120, Verdict::Fail(String::from("1 pixel differs from the expected PBM"))),
+            report("bcd.ch8", 1, Verdict::Updated),
+        ]);
+
+        assert_eq!(lines, vec![
+            "PASS     ibm.ch8            2.0 ms",
+            "FAIL     games/brix.ch8   120.0 ms  1 pixel differs from the expected PBM",
+            "UPDATED  bcd.ch8            1.0 ms",
+            "1 passed, 1 failed, 1 updated",
+        ]);
+        assert_eq!(actual_path(Path::new("suite"), Path::new("games/brix.ch8"), "pbm"), Path::new("suite").join("brix.actual.pbm"));
+    }
+}
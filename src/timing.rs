@@ -0,0 +1,129 @@
+use serde::{Deserialize, Serialize};
+
+use crate::instruction::{Instruction, VIP_INSTRUCTION_CYCLES};
+
+/// Clock cycles the COSMAC VIP's 1.76 MHz CDP1802 gets through in one 60 Hz frame.
+pub const VIP_CYCLES_PER_FRAME: u32 = 1_760_000 / 60;
+
+/// How much of the program a frame runs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Timing {
+    /// A flat `cycles_per_frame` instructions, as modern interpreters do.
+    #[default]
+    Modern,
+    /// As many instructions as fit in `VIP_CYCLES_PER_FRAME`, each costing what it took on the
+    /// COSMAC VIP, so sprite-heavy code runs slower than arithmetic.
+    Vip,
+}
+
+impl Timing {
+    pub fn parse(name: &str) -> Option<Timing> {
+        match name.to_ascii_lowercase().as_str() {
+            "modern" => Some(Timing::Modern),
+            "vip" => Some(Timing::Vip),
+            _ => None,
+        }
+    }
+}
+
+/// What's left of one frame under a `Timing`: instructions for `Modern`, clock cycles for
+/// `Vip`. The instruction that runs it out still finishes, so a frame never ends mid-way.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameBudget {
+    timing: Timing,
+    total: i64,
+    left: i64,
+}
+
+impl FrameBudget {
+    /// A fresh frame's budget; `cycles_per_frame` only counts under `Timing::Modern`.
+    pub fn new(timing: Timing, cycles_per_frame: usize) -> FrameBudget {
+        let total = match timing {
+            Timing::Modern => cycles_per_frame as i64,
+            Timing::Vip => VIP_CYCLES_PER_FRAME as i64,
+        };
+        FrameBudget { timing, total, left: total }
+    }
+
+    /// Whether another instruction starts in this frame.
+    pub fn has_time(&self) -> bool {
+        self.left > 0
+    }
+
+    /// Whether `numerator / denominator` of the frame has been spent, for spreading it over
+    /// several ticks in slow motion.
+    pub fn has_spent(&self, numerator: u32, denominator: u32) -> bool {
+        self.total - self.left >= self.total * i64::from(numerator) / i64::from(denominator.max(1))
+    }
+
+    /// Takes what the instruction `opcode`, just executed, cost out of the budget.
+    pub fn spend(&mut self, opcode: u16) {
+        self.left -= match self.timing {
+            Timing::Modern => 1,
+            // an undecodable opcode halts the CPU, so what it costs hardly matters
+            Timing::Vip => Instruction::decode(opcode).map_or(VIP_INSTRUCTION_CYCLES, Instruction::vip_cycles) as i64,
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn instructions_in_a_frame(timing: Timing, opcode: u16) -> usize {
+        let mut budget = FrameBudget::new(timing, 15);
+        let mut executed = 0;
+        while budget.has_time() {
+            budget.spend(opcode);
+            executed += 1;
+        }
+        executed
+    }
+
+    #[test]
+    fn modern_frames_run_a_flat_count_and_vip_frames_run_what_fits() {
+        assert_eq!(instructions_in_a_frame(Timing::Modern, 0x7101), 15);
+        assert_eq!(instructions_in_a_frame(Timing::Modern, 0xD01F), 15);
+
+        // 29333 cycles at 4540 apiece, the last one running over
+        assert_eq!(instructions_in_a_frame(Timing::Vip, 0x7101), 7);
+        // DRW V0, V1, 15 costs 31540, so one is all a frame has room for
+        assert_eq!(instructions_in_a_frame(Timing::Vip, 0xD01F), 1);
+        assert_eq!(instructions_in_a_frame(Timing::Vip, 0xD012), 4);
+    }
+
+    #[test]
+    fn a_frame_can_be_spent_a_share_at_a_time() {
+        let mut budget = FrameBudget::new(Timing::Modern, 10);
+        assert!(budget.has_spent(0, 2));
+        assert!(!budget.has_spent(1, 2));
+
+        for _ in 0..5 {
+            budget.spend(0x7101);
+        }
+        assert!(budget.has_spent(1, 2));
+        assert!(!budget.has_spent(6, 10));
+
+        // shares are rounded down, so a tenth of 15 instructions is one
+        let mut budget = FrameBudget::new(Timing::Modern, 15);
+        budget.spend(0x7101);
+        assert!(budget.has_spent(1, 10));
+        assert!(!budget.has_spent(2, 10));
+
+        // VIP shares are clock cycles: a sixth of a frame is 4888, more than one ADD costs
+        let mut budget = FrameBudget::new(Timing::Vip, 15);
+        budget.spend(0x7101);
+        assert!(!budget.has_spent(1, 6));
+        budget.spend(0x7101);
+        assert!(budget.has_spent(1, 6));
+    }
+
+    #[test]
+    fn timing_parses_and_serializes_in_lowercase() {
+        assert_eq!(Timing::parse("VIP"), Some(Timing::Vip));
+        assert_eq!(Timing::parse("modern"), Some(Timing::Modern));
+        assert_eq!(Timing::parse("eti660"), None);
+        assert_eq!(serde_json::to_string(&Timing::Vip).unwrap(), "\"vip\"");
+    }
+}
@@ -0,0 +1,39 @@
+; Draws the VF each arithmetic instruction leaves behind, which should read 1 0 1 1 1;
+; assembles to tests/roms/flags.ch8.
+
+        LD V0, 0xFF             ; 8XY4 carries
+        LD V1, 1
+        ADD V0, V1
+        LD V5, VF
+        LD V0, 0x10             ; 8XY5 borrows
+        LD V1, 0x20
+        SUB V0, V1
+        LD V6, VF
+        LD V0, 0x81             ; 8XY6 shifts a 1 out
+        SHR V0
+        LD V7, VF
+        LD V0, 0x81             ; 8XYE shifts a 1 out
+        SHL V0
+        LD V8, VF
+        LD V0, 5                ; 8XY7 doesn't borrow
+        LD V1, 9
+        SUBN V0, V1
+        LD V9, VF
+
+        LD VA, 0                ; x
+        LD VB, 0                ; y
+        LD F, V5
+        DRW VA, VB, 5
+        ADD VA, 5
+        LD F, V6
+        DRW VA, VB, 5
+        ADD VA, 5
+        LD F, V7
+        DRW VA, VB, 5
+        ADD VA, 5
+        LD F, V8
+        DRW VA, VB, 5
+        ADD VA, 5
+        LD F, V9
+        DRW VA, VB, 5
+done:   JP done
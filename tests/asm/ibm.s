@@ -0,0 +1,36 @@
+; The IBM logo, drawn as six 15-row sprites; assembles to tests/roms/ibm.ch8.
+
+        CLS
+        LD I, letter_i
+        LD V0, 12               ; x
+        LD V1, 8                ; y
+        DRW V0, V1, 15
+        ADD V0, 9
+        LD I, letter_b_left
+        DRW V0, V1, 15
+        LD I, letter_b_right
+        ADD V0, 8
+        DRW V0, V1, 15
+        ADD V0, 4
+        LD I, letter_m_left
+        DRW V0, V1, 15
+        ADD V0, 8
+        LD I, letter_m_middle
+        DRW V0, V1, 15
+        ADD V0, 8
+        LD I, letter_m_right
+        DRW V0, V1, 15
+halt:   JP halt
+
+letter_i:
+        .byte 0xFF, 0x00, 0xFF, 0x00, 0x3C, 0x00, 0x3C, 0x00, 0x3C, 0x00, 0x3C, 0x00, 0xFF, 0x00, 0xFF
+letter_b_left:
+        .byte 0xFF, 0x00, 0xFF, 0x00, 0x38, 0x00, 0x3F, 0x00, 0x3F, 0x00, 0x38, 0x00, 0xFF, 0x00, 0xFF
+letter_b_right:
+        .byte 0x80, 0x00, 0xE0, 0x00, 0xE0, 0x00, 0x80, 0x00, 0x80, 0x00, 0xE0, 0x00, 0xE0, 0x00, 0x80
+letter_m_left:
+        .byte 0xF8, 0x00, 0xFC, 0x00, 0x3E, 0x00, 0x3F, 0x00, 0x3B, 0x00, 0x39, 0x00, 0xF8, 0x00, 0xF8
+letter_m_middle:
+        .byte 0x03, 0x00, 0x07, 0x00, 0x0F, 0x00, 0xBF, 0x00, 0xFB, 0x00, 0xF3, 0x00, 0xE3, 0x00, 0x43
+letter_m_right:
+        .byte 0xE0, 0x00, 0xE0, 0x00, 0x80, 0x00, 0x80, 0x00, 0x80, 0x00, 0x80, 0x00, 0xE0, 0x00, 0xE0
@@ -0,0 +1,40 @@
+//! Assembles the example sources in `tests/asm` and compares them byte for byte with the ROMs
+//! in `tests/roms` they were written from, then checks that the disassembly of each ROM
+//! assembles back into it.
+
+use std::fs;
+use std::path::PathBuf;
+
+use chip_8_emulator::assembler::assemble;
+use chip_8_emulator::instruction;
+use chip_8_emulator::DEFAULT_LOAD_ADDRESS;
+
+fn fixture(directory: &str, file_name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests").join(directory).join(file_name)
+}
+
+fn assert_assembles_to_rom(name: &str) {
+    let source = fs::read_to_string(fixture("asm", &format!("{}.s", name))).expect("example source is missing");
+    let rom = fs::read(fixture("roms", &format!("{}.ch8", name))).expect("test rom is missing");
+
+    assert_eq!(assemble(&source, DEFAULT_LOAD_ADDRESS).unwrap(), rom, "{}.s", name);
+}
+
+#[test]
+fn ibm_logo() {
+    assert_assembles_to_rom("ibm");
+}
+
+#[test]
+fn flags() {
+    assert_assembles_to_rom("flags");
+}
+
+#[test]
+fn disassembly_round_trips() {
+    for name in ["bcd.ch8", "checker16.ch8", "flags.ch8", "ibm.ch8", "planes_dual.ch8"].iter() {
+        let rom = fs::read(fixture("roms", name)).expect("test rom is missing");
+
+        assert_eq!(assemble(&instruction::source(&rom), DEFAULT_LOAD_ADDRESS).unwrap(), rom, "{}", name);
+    }
+}
@@ -0,0 +1,97 @@
+//! Runs the binary itself, headlessly, for what only the command line does: reading the ROM
+//! from stdin and reporting on stderr. Needs the `frontend-ggez` feature the binary is built
+//! with.
+//!
+//! `bcd.ch8` (see `rom_framebuffers.rs`) is piped to stdin and should draw the same screen as
+//! when it's loaded from its file. A ROM that loops over a 0NNN machine-code call runs to the end
+//! with one warning, or halts with `--strict`, leaving a core dump that `dump` prints.
+
+use std::fs;
+use std::io::Write;
+use std::env;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+
+fn rom_path(file_name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests").join("roms").join(file_name)
+}
+
+/// An empty directory of its own for a test to run the emulator in, so what it writes, like
+/// core dumps, stays out of the source tree.
+fn working_directory(test: &str) -> PathBuf {
+    let directory = env::temp_dir().join(format!("chip-8-emulator-cli-{}-{}", test, std::process::id()));
+    let _ = fs::remove_dir_all(&directory);
+    fs::create_dir_all(&directory).unwrap();
+    directory
+}
+
+fn run_piped(directory: &Path, rom: &[u8], args: &[&str]) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_chip-8-emulator"))
+        .current_dir(directory)
+        .args(["--headless", "-"])
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("the emulator doesn't start");
+    child.stdin.take().unwrap().write_all(rom).unwrap();
+    child.wait_with_output().unwrap()
+}
+
+#[test]
+fn bcd_from_stdin() {
+    let rom = fs::read(rom_path("bcd.ch8")).expect("test rom is missing");
+    let directory = working_directory("bcd");
+    let output = run_piped(&directory, &rom, &["--cycles", "50", "--hash-every", "5"]);
+    let again = run_piped(&directory, &rom, &["--cycles", "50", "--hash-every", "5"]);
+    fs::remove_dir_all(directory).unwrap();
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let expected = fs::read_to_string(rom_path("bcd.txt")).expect("expected framebuffer is missing");
+    assert!(stdout.ends_with(&expected));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Ran 15 instructions in "));
+    // a hash every 5 instructions until the ROM stops, the same every run
+    let hashes: Vec<&str> = stdout.lines().take(3).collect();
+    for (line, instructions) in hashes.iter().zip(["5 ", "10 ", "15 "]) {
+        assert!(line.starts_with(instructions) && line.len() == instructions.len() + 16, "{}", line);
+    }
+    assert_eq!(output.stdout, again.stdout);
+}
+
+#[test]
+fn machine_code_calls_are_skipped_or_halt_when_strict() {
+    // SYS 0x123; JP 0x200
+    let rom = [0x01, 0x23, 0x12, 0x00];
+
+    let directory = working_directory("machine-code");
+    let permissive = run_piped(&directory, &rom, &["--cycles", "100"]);
+    let strict = run_piped(&directory, &rom, &["--cycles", "100", "--strict"]);
+    fs::remove_dir_all(directory).unwrap();
+
+    assert!(permissive.status.success());
+    let warnings = String::from_utf8_lossy(&permissive.stderr);
+    assert_eq!(warnings.matches("WARN").count(), 1, "{}", warnings);
+    assert!(warnings.contains("skipped 50 calls to machine code, the first to 0x123 from 0x200"), "{}", warnings);
+    assert_eq!(strict.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&strict.stderr).contains("call to machine code at 0x123 from 0x200"));
+}
+
+#[test]
+fn a_crash_leaves_a_core_dump_that_dump_prints() {
+    // RET with nothing to return to
+    let directory = working_directory("core-dump");
+    let crash = run_piped(&directory, &[0x00, 0xEE], &["--cycles", "10"]);
+    let dumps: Vec<PathBuf> = fs::read_dir(directory.join("dumps")).unwrap().map(|entry| entry.unwrap().path()).collect();
+    let printed = Command::new(env!("CARGO_BIN_EXE_chip-8-emulator")).arg("dump").arg(&dumps[0]).output().unwrap();
+    fs::remove_dir_all(directory).unwrap();
+
+    assert_eq!(crash.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&crash.stderr).contains("Core dump written to dumps"));
+    assert_eq!(dumps.len(), 1);
+    assert!(printed.status.success());
+    let printed = String::from_utf8_lossy(&printed.stdout);
+    assert!(printed.starts_with("stdin halted after 1 instructions: return with an empty stack at 0x200\n"), "{}", printed);
+    assert!(printed.contains("0x0200: 00 EE 00"), "{}", printed);
+}
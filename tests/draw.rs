@@ -0,0 +1,138 @@
+//! DXYN edge cases, each checked against the whole framebuffer: the collision flag, start
+//! coordinates that wrap, and sprites clipped at the right and bottom edges.
+//!
+//! Expected screens are written as rows of `#` for a lit pixel and `.` otherwise, starting at a
+//! given row of the lores display. Rows are padded with `.` to the buffer's full width, and every
+//! row above or below the ones written has to be blank.
+
+use chip_8_emulator::{Cpu, Display, Memory, VF};
+
+/// Where the tests put their sprites, well clear of the programs.
+const SPRITES: u16 = 0x300;
+
+/// Loads `program` at 0x200 and `sprite` at `SPRITES`, then executes every instruction of it.
+fn run(program: &[u16], sprite: &[u8]) -> Cpu {
+    let mut memory = Memory::new();
+    for (index, &opcode) in program.iter().enumerate() {
+        memory.write_u16(0x200 + 2 * index as u16, opcode);
+    }
+    memory.load(SPRITES, sprite);
+    let mut cpu = Cpu::new(memory, Display::new());
+    for _ in program {
+        cpu.cycle().unwrap();
+    }
+    cpu
+}
+
+/// `rows` as a buffer shaped like `Display::pixels`, indexed by column then row, with row
+/// `top` the first of them.
+fn pixels(top: usize, rows: &str) -> Vec<Vec<u8>> {
+    let display = Display::new();
+    let (columns, height) = (display.pixels().len(), display.pixels()[0].len());
+    let mut buffer = vec![vec![0; height]; columns];
+    for (y, row) in rows.lines().map(str::trim).filter(|row| !row.is_empty()).enumerate() {
+        for (x, cell) in row.chars().enumerate() {
+            buffer[x][top + y] = (cell == '#') as u8;
+        }
+    }
+    buffer
+}
+
+fn row_text(buffer: &[Vec<u8>], y: usize) -> String {
+    buffer.iter().map(|column| if column[y] != 0 { '#' } else { '.' }).collect()
+}
+
+/// Compares the whole of `cpu`'s framebuffer with `rows` starting at row `top`, and on a
+/// mismatch shows each row that differs above what was expected of it.
+fn assert_screen(cpu: &Cpu, top: usize, rows: &str) {
+    let expected = pixels(top, rows);
+    let actual: Vec<Vec<u8>> = cpu.display().pixels().iter().map(|column| column.to_vec()).collect();
+
+    if actual != expected {
+        let mut report = String::new();
+        for y in 0..expected[0].len() {
+            let (actual_row, expected_row) = (row_text(&actual, y), row_text(&expected, y));
+            if actual_row != expected_row {
+                report.push_str(&format!("row {:2}  actual   {}\n        expected {}\n", y, actual_row, expected_row));
+            }
+        }
+        panic!("the framebuffer differs\n{}", report);
+    }
+}
+
+#[test]
+fn overlapping_pixels_set_vf_once_and_erase_each_other() {
+    // LD I, sprites; DRW V0, V0, 1; ADD I, V1 (V1 = 1); DRW V0, V0, 1
+    let cpu = run(&[0xA300, 0x6101, 0xD001, 0xF11E, 0xD001], &[0xF0, 0xFF]);
+
+    assert_eq!(cpu.registers()[VF], 1);
+    assert_screen(&cpu, 0, "....####");
+}
+
+#[test]
+fn a_draw_without_collision_clears_vf() {
+    // a collision sets VF, then a sprite beside the first clears it again
+    let cpu = run(&[0xA300, 0xD001, 0xD001, 0x6108, 0xD101], &[0xFF]);
+
+    assert_eq!(cpu.registers()[VF], 0);
+    assert_screen(&cpu, 0, "........########");
+
+    // and a VF left over from arithmetic doesn't survive a clean draw either
+    let cpu = run(&[0xA300, 0x6F05, 0xD001], &[0x81]);
+
+    assert_eq!(cpu.registers()[VF], 0);
+    assert_screen(&cpu, 0, "#......#");
+}
+
+#[test]
+fn start_coordinates_past_the_edges_wrap() {
+    // V0 = 64, V1 = 32 draws at (0, 0); V2 = 65, V3 = 33 draws at (1, 1)
+    let cpu = run(&[0xA300, 0x6040, 0x6120, 0x6241, 0x6321, 0xD011, 0xD231], &[0xE0]);
+
+    assert_eq!(cpu.registers()[VF], 0);
+    assert_screen(
+        &cpu,
+        0,
+        "
+        ###
+        .###
+        ",
+    );
+}
+
+#[test]
+fn a_sprite_at_the_last_column_and_row_is_clipped_to_one_pixel() {
+    // V0 = 63, V1 = 31
+    let cpu = run(&[0xA300, 0x603F, 0x611F, 0xD012], &[0xFF, 0xFF]);
+
+    assert_eq!(cpu.registers()[VF], 0);
+    assert_screen(&cpu, 31, &format!("{}#", ".".repeat(63)));
+}
+
+#[test]
+fn a_tall_sprite_crossing_the_bottom_edge_is_clipped() {
+    // a diagonal, one pixel further right on each of its 15 rows, drawn from row 20
+    let sprite: Vec<u8> = (0..15).map(|row| 0x80 >> (row % 8)).collect();
+    let cpu = run(&[0xA300, 0x6014, 0xD10F], &sprite);
+
+    assert_eq!(cpu.registers()[VF], 0);
+    // rows 32 to 34 are dropped rather than wrapped to the top
+    assert_screen(
+        &cpu,
+        20,
+        "
+        #
+        .#
+        ..#
+        ...#
+        ....#
+        .....#
+        ......#
+        .......#
+        #
+        .#
+        ..#
+        ...#
+        ",
+    );
+}
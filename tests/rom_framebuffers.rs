@@ -0,0 +1,157 @@
+//! Runs whole ROMs headlessly and compares the final framebuffer with a stored
+//! expectation in `tests/roms/<rom>.txt` (`#` for a lit pixel, `.` otherwise).
+//!
+//! The corax89 opcode test and Timendus's flags and quirks tests aren't vendored yet, so their
+//! tests at the bottom are ignored and show up as such in every run; until then small ROMs
+//! written for this suite (`ibm.s` and `flags.s` are in `tests/asm`) cover the same DXYN, BCD and
+//! flag handling. Taking an ignore off needs the ROM and its licence in `tests/roms` and a golden
+//! `.txt` from a run checked against the reference screenshots.
+//!
+//! `bcd.ch8` stores 234 with FX33, reads the digits back with FX65 and draws
+//! them with FX29/DXYN. `flags.ch8` draws the VF produced by 8XY4, 8XY5, 8XY6,
+//! 8XYE and 8XY7, which should read `1 0 1 1 1`. `checker16.ch8` switches to
+//! hires and draws a 16x16 checkerboard with DXY0 at (120, 56), so only its
+//! top-left 8x8 quarter is left on screen.
+//!
+//! The XO-CHIP `planes_*.ch8` ROMs draw with FN01: `planes_single.ch8` draws a
+//! digit onto plane 2 only, `planes_dual.ch8` draws a sprite onto both planes at
+//! once so every pixel value shows up. Plane 2 is `+` and both planes are `@`.
+//!
+//! `planes_dual.png` is the same framebuffer as a screenshot in the classic palette, checked
+//! against an independent PNG decoder.
+//!
+//! `bcd.hex` is `bcd.ch8` as a hex dump, loaded by extension as the emulator does.
+//!
+//! `mega_blit.ch8` turns on MEGA-CHIP's screen, loads red and blue into palette entries 1 and 2,
+//! and blits a 2x2 sprite of `1 2 / 2 0` at (10, 5). Its colours are checked in the RGBA the
+//! renderer produces rather than as text.
+
+use std::fs;
+use std::path::PathBuf;
+
+use chip_8_emulator::headless::{self, KeyScript};
+use chip_8_emulator::romfile::{self, RomFormat};
+use chip_8_emulator::phosphor::Phosphor;
+use chip_8_emulator::{palette, render, screenshot, Cpu, Display, Memory, Platform, Quirks};
+
+fn rom_path(file_name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests").join("roms").join(file_name)
+}
+
+fn run_rom(rom: &str, cycles: u64, keys: &str, quirks: Quirks) -> Cpu {
+    let buffer = fs::read(rom_path(rom)).expect("test rom is missing");
+    let buffer = romfile::decode(buffer, RomFormat::detect(&rom_path(rom))).expect("test rom doesn't decode");
+    let mut cpu = Cpu::new(Memory::new(), Display::new());
+    // before `init`, so XO-CHIP and MEGA-CHIP ROMs load into memory of the size they expect
+    cpu.set_quirks(quirks);
+    cpu.init(buffer);
+    cpu.seed(0);
+
+    headless::run(&mut cpu, cycles, &KeyScript::parse(keys).unwrap()).unwrap();
+
+    cpu
+}
+
+fn assert_framebuffer(rom: &str, cycles: u64, keys: &str) {
+    assert_framebuffer_with(rom, cycles, keys, Quirks::default());
+}
+
+fn assert_framebuffer_with(rom: &str, cycles: u64, keys: &str, quirks: Quirks) {
+    let cpu = run_rom(rom, cycles, keys, quirks);
+    let actual = cpu.display().to_ascii();
+    let expected_path = rom_path(rom).with_extension("txt");
+    let expected = fs::read_to_string(&expected_path).expect("expected framebuffer is missing");
+
+    if actual != expected {
+        let mut report = String::new();
+        for (actual_line, expected_line) in actual.lines().zip(expected.lines()) {
+            let marker = if actual_line == expected_line { ' ' } else { '!' };
+            report.push_str(&format!("{} {}   {}\n", marker, actual_line, expected_line));
+        }
+        panic!(
+            "framebuffer of {} differs from {} after {} cycles\n  actual{}expected\n{}",
+            rom,
+            expected_path.display(),
+            cycles,
+            " ".repeat(61),
+            report
+        );
+    }
+}
+
+#[test]
+fn ibm_logo() {
+    assert_framebuffer("ibm.ch8", 100, "");
+}
+
+#[test]
+fn bcd() {
+    assert_framebuffer("bcd.ch8", 50, "");
+}
+
+#[test]
+fn bcd_from_a_hex_dump() {
+    assert_framebuffer("bcd.hex", 50, "");
+}
+
+#[test]
+fn flags() {
+    assert_framebuffer("flags.ch8", 100, "");
+}
+
+#[test]
+fn checkerboard_clipped_at_the_hires_corner() {
+    assert_framebuffer("checker16.ch8", 20, "");
+}
+
+#[test]
+fn xochip_single_plane() {
+    assert_framebuffer_with("planes_single.ch8", 20, "", Quirks { platform: Platform::XoChip, ..Quirks::default() });
+}
+
+#[test]
+fn xochip_dual_plane() {
+    assert_framebuffer_with("planes_dual.ch8", 20, "", Quirks { platform: Platform::XoChip, ..Quirks::default() });
+}
+
+#[test]
+fn screenshot_matches_the_known_good_png() {
+    let cpu = run_rom("planes_dual.ch8", 20, "", Quirks { platform: Platform::XoChip, ..Quirks::default() });
+    let expected = fs::read(rom_path("planes_dual.png")).expect("expected screenshot is missing");
+
+    assert!(screenshot::to_png(cpu.display(), &palette::built_in()[0], 1) == expected, "screenshot differs from planes_dual.png");
+}
+
+#[test]
+fn megachip_colour_blit() {
+    let cpu = run_rom("mega_blit.ch8", 20, "", Quirks::profile("megachip").unwrap());
+    let rgba = render::frame_rgba(cpu.display(), &palette::built_in()[0], &Phosphor::default());
+    let color = |x: usize, y: usize| &rgba[(y * cpu.display().width() + x) * 4..][..4];
+
+    assert_eq!(rgba.len(), 256 * 192 * 4);
+    assert_eq!(color(10, 5), [0xFF, 0, 0, 0xFF]);
+    assert_eq!(color(11, 5), [0, 0, 0xFF, 0xFF]);
+    assert_eq!(color(10, 6), [0, 0, 0xFF, 0xFF]);
+    assert_eq!(color(11, 6), [0, 0, 0, 0]);
+    assert_eq!(color(9, 5), [0, 0, 0, 0]);
+    assert_eq!(cpu.registers()[0xF], 0);
+}
+
+#[test]
+#[ignore = "needs corax89's test_opcode.ch8 and its licence in tests/roms"]
+fn corax89_opcode_test() {
+    assert_framebuffer("test_opcode.ch8", 1_000, "");
+}
+
+#[test]
+#[ignore = "needs Timendus's 4-flags.ch8 and its licence in tests/roms"]
+fn timendus_flags() {
+    assert_framebuffer("4-flags.ch8", 2_000, "");
+}
+
+#[test]
+#[ignore = "needs Timendus's 5-quirks.ch8 and its licence in tests/roms"]
+fn timendus_quirks_on_chip8() {
+    // 1 picks CHIP-8 from the ROM's menu
+    assert_framebuffer_with("5-quirks.ch8", 20_000, "1@1000,release1@1200", Quirks::profile("chip8").unwrap());
+}
@@ -0,0 +1,46 @@
+//! Guards the JSON save-state layout against drifting by accident. `tests/states/bcd.json` is
+//! the state `bcd.ch8` (see `rom_framebuffers.rs`) leaves after 15 instructions, exported when
+//! the layout was version 1: exporting the same state has to give the same document, and
+//! importing the document has to give back the same state.
+//!
+//! Changing or removing a field on purpose means bumping `STATE_JSON_VERSION` and regenerating
+//! the file; adding one that older documents can leave out only means regenerating it.
+
+use std::fs;
+use std::path::PathBuf;
+
+use chip_8_emulator::headless::{self, KeyScript};
+use chip_8_emulator::{Cpu, Display, Memory, SaveState, STATE_JSON_VERSION};
+
+fn path(directory: &str, file_name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests").join(directory).join(file_name)
+}
+
+fn bcd_state() -> SaveState {
+    let mut cpu = Cpu::new(Memory::new(), Display::new());
+    cpu.init(fs::read(path("roms", "bcd.ch8")).expect("test rom is missing"));
+    cpu.seed(0);
+    headless::run(&mut cpu, 15, &KeyScript::default()).unwrap();
+    cpu.save_state()
+}
+
+#[test]
+fn exports_match_the_fixture() {
+    let fixture = fs::read_to_string(path("states", "bcd.json")).expect("fixture is missing");
+
+    assert_eq!(STATE_JSON_VERSION, 1, "the fixture is of version 1; regenerate it along with the version");
+    assert_eq!(bcd_state().to_json(), fixture.trim_end());
+}
+
+#[test]
+fn the_fixture_imports_as_the_state_it_was_exported_from() {
+    let state = SaveState::read_json_from(&path("states", "bcd.json")).unwrap();
+
+    assert_eq!(state, bcd_state());
+    assert_eq!(state.to_bytes(), bcd_state().to_bytes());
+
+    let mut cpu = Cpu::new(Memory::new(), Display::new());
+    cpu.init(fs::read(path("roms", "bcd.ch8")).unwrap());
+    cpu.load_state(&state).unwrap();
+    assert_eq!(cpu.display().to_ascii(), fs::read_to_string(path("roms", "bcd.txt")).unwrap());
+}
@@ -0,0 +1,81 @@
+// Build the package first with `wasm-pack build --target web`, then serve this directory.
+import init, { Emulator, keypad_key } from "./pkg/chip_8_emulator_web.js";
+
+const FRAME_MS = 1000 / 60;
+
+await init();
+
+const canvas = document.getElementById("screen");
+const context = canvas.getContext("2d");
+const status = document.getElementById("status");
+const emulator = new Emulator(Math.random() * 2 ** 32);
+let running = false;
+let audio = null;
+let beeper = null;
+
+document.getElementById("rom").addEventListener("change", async (event) => {
+  const file = event.target.files[0];
+  try {
+    emulator.load_rom(new Uint8Array(await file.arrayBuffer()));
+    status.textContent = file.name;
+    running = true;
+  } catch (error) {
+    status.textContent = error;
+  }
+});
+
+for (const [type, pressed] of [["keydown", true], ["keyup", false]]) {
+  document.addEventListener(type, (event) => {
+    const key = keypad_key(event.key);
+    if (key !== undefined) {
+      emulator.key_event(key, pressed);
+      event.preventDefault();
+    }
+  });
+}
+
+function beep(on) {
+  if (on && !beeper) {
+    audio = audio || new AudioContext();
+    beeper = audio.createOscillator();
+    beeper.type = "square";
+    beeper.connect(audio.destination);
+    beeper.start();
+  } else if (!on && beeper) {
+    beeper.stop();
+    beeper = null;
+  }
+}
+
+function draw() {
+  if (canvas.width !== emulator.width() || canvas.height !== emulator.height()) {
+    canvas.width = emulator.width();
+    canvas.height = emulator.height();
+  }
+  const pixels = new Uint8ClampedArray(emulator.framebuffer_rgba());
+  context.putImageData(new ImageData(pixels, canvas.width, canvas.height), 0, 0);
+}
+
+// requestAnimationFrame follows the monitor's refresh rate, so run as many 60 Hz frames as
+// the time since the last one calls for.
+let last = performance.now();
+function frame(now) {
+  let frames = Math.min(Math.floor((now - last) / FRAME_MS), 4);
+  last += frames * FRAME_MS;
+  if (now - last > FRAME_MS) {
+    last = now;
+  }
+  while (running && frames-- > 0) {
+    try {
+      emulator.step_frame();
+    } catch (error) {
+      status.textContent = "CPU halted: " + error;
+      running = false;
+    }
+    emulator.tick_timers();
+  }
+  beep(running && emulator.sound_on());
+  draw();
+  requestAnimationFrame(frame);
+}
+requestAnimationFrame(frame);
@@ -0,0 +1,101 @@
+use wasm_bindgen::prelude::*;
+
+use chip_8_emulator::keymap::Keymap;
+use chip_8_emulator::palette::{self, Palette};
+use chip_8_emulator::phosphor::Phosphor;
+use chip_8_emulator::render;
+use chip_8_emulator::{Cpu, Display, Flow, Memory, CYCLES_PER_FRAME, DEFAULT_LOAD_ADDRESS};
+
+/// The interpreter as a page sees it. The page owns the timing: every animation frame it calls
+/// `step_frame` and `tick_timers`, then draws `framebuffer_rgba` into a `width()` x `height()`
+/// canvas.
+#[wasm_bindgen]
+pub struct Emulator {
+    cpu: Cpu,
+    seed: u32,
+    palette: Palette,
+    // disabled; frame_rgba wants one
+    phosphor: Phosphor,
+}
+
+#[wasm_bindgen]
+impl Emulator {
+    /// An emulator with nothing loaded, whose CXKK generator starts from `seed`; pass something
+    /// like `Math.random() * 2 ** 32` for a different game every time.
+    #[wasm_bindgen(constructor)]
+    pub fn new(seed: u32) -> Emulator {
+        Emulator { cpu: new_cpu(seed), seed, palette: palette::built_in().remove(0), phosphor: Phosphor::new(0.0) }
+    }
+
+    /// Resets the machine and loads `rom` at 0x200; throws the reason it was turned away.
+    pub fn load_rom(&mut self, rom: &[u8]) -> Result<(), String> {
+        self.cpu.load_rom(DEFAULT_LOAD_ADDRESS, rom).map_err(|error| error.to_string())?;
+        // the same seed plays the same game however many ROMs came before
+        self.cpu.seed(u64::from(self.seed));
+        Ok(())
+    }
+
+    /// Runs one 60th of a second's worth of instructions, fewer if the program halts or the
+    /// display-wait quirk ends the frame; throws the error it halted on.
+    pub fn step_frame(&mut self) -> Result<(), String> {
+        for _ in 0..CYCLES_PER_FRAME {
+            let flow = self.cpu.cycle().map_err(|error| error.to_string())?;
+            if flow == Flow::FrameBoundary || self.cpu.halted().is_some() {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// Counts the delay and sound timers down by one; call it 60 times a second.
+    pub fn tick_timers(&mut self) {
+        self.cpu.tick_timers();
+    }
+
+    /// Presses or releases keypad key `key`, 0 to F; others are ignored.
+    pub fn key_event(&mut self, key: u8, pressed: bool) {
+        if pressed {
+            self.cpu.keys_mut().press(key);
+        } else {
+            self.cpu.keys_mut().release(key);
+        }
+    }
+
+    /// The display as row-major RGBA bytes, `width()` x `height()` pixels, ready for `ImageData`.
+    pub fn framebuffer_rgba(&self) -> Vec<u8> {
+        render::frame_rgba(self.cpu.display(), &self.palette, &self.phosphor)
+    }
+
+    /// The display's width in pixels: 64, or 128 once a SUPER-CHIP program switches to hires.
+    pub fn width(&self) -> usize {
+        self.cpu.display().width()
+    }
+
+    /// The display's height in pixels: 32, or 64 in hires.
+    pub fn height(&self) -> usize {
+        self.cpu.display().height()
+    }
+
+    /// Whether the buzzer should be sounding.
+    pub fn sound_on(&self) -> bool {
+        self.cpu.is_buzzing()
+    }
+
+    /// Whether the program has stopped, by 00FD, a jump to itself or an error.
+    pub fn is_halted(&self) -> bool {
+        self.cpu.halted().is_some()
+    }
+}
+
+/// The keypad key the desktop build binds to keyboard key `name`, such as `"Q"` for 4, so the
+/// page plays the same way.
+#[wasm_bindgen]
+pub fn keypad_key(name: &str) -> Option<u8> {
+    Keymap::default().key(name)
+}
+
+fn new_cpu(seed: u32) -> Cpu {
+    let mut cpu = Cpu::new(Memory::new(), Display::new());
+    cpu.seed(u64::from(seed));
+    cpu
+}
@@ -0,0 +1,65 @@
+//! The API the page drives, run in wasm with `wasm-pack test --node`.
+#![cfg(target_arch = "wasm32")]
+
+use wasm_bindgen_test::wasm_bindgen_test;
+
+use chip_8_emulator_web::{keypad_key, Emulator};
+
+// LD V0, K; LD ST, V0; LD F, V0; DRW V1, V1, 5; JP 0x208
+const DRAW_PRESSED_KEY: [u8; 10] = [0xF0, 0x0A, 0xF0, 0x18, 0xF0, 0x29, 0xD1, 0x15, 0x12, 0x08];
+
+fn lit(emulator: &Emulator, x: usize, y: usize) -> bool {
+    let rgba = emulator.framebuffer_rgba();
+    rgba[(y * emulator.width() + x) * 4] != 0
+}
+
+#[wasm_bindgen_test]
+fn a_loaded_rom_draws_the_key_pressed() {
+    let mut emulator = Emulator::new(1);
+    emulator.load_rom(&DRAW_PRESSED_KEY).unwrap();
+
+    emulator.step_frame().unwrap();
+    emulator.key_event(keypad_key("w").unwrap(), true);
+    emulator.step_frame().unwrap();
+
+    assert_eq!((emulator.width(), emulator.height()), (64, 32));
+    assert_eq!(emulator.framebuffer_rgba().len(), 64 * 32 * 4);
+    // the top row of the 5 sprite is lit across, its second row only on the left
+    assert!(lit(&emulator, 0, 0) && lit(&emulator, 3, 0));
+    assert!(lit(&emulator, 0, 1) && !lit(&emulator, 3, 1));
+    assert!(emulator.is_halted());
+}
+
+#[wasm_bindgen_test]
+fn the_sound_timer_runs_down_as_the_page_ticks_it() {
+    let mut emulator = Emulator::new(1);
+    emulator.load_rom(&DRAW_PRESSED_KEY).unwrap();
+    emulator.key_event(0x2, true);
+    emulator.step_frame().unwrap();
+
+    assert!(emulator.sound_on());
+    emulator.tick_timers();
+    assert!(emulator.sound_on());
+    emulator.tick_timers();
+    assert!(!emulator.sound_on());
+}
+
+#[wasm_bindgen_test]
+fn bad_roms_are_turned_away_and_keep_the_old_one() {
+    let mut emulator = Emulator::new(1);
+    emulator.load_rom(&DRAW_PRESSED_KEY).unwrap();
+
+    assert!(emulator.load_rom(&[]).unwrap_err().contains("empty"));
+    assert!(emulator.load_rom(&[0; 0x1000]).is_err());
+    emulator.key_event(0x1, true);
+    emulator.step_frame().unwrap();
+    assert!(lit(&emulator, 2, 0));
+}
+
+#[wasm_bindgen_test]
+fn keyboard_keys_follow_the_desktop_layout() {
+    assert_eq!(keypad_key("1"), Some(0x1));
+    assert_eq!(keypad_key("Q"), Some(0x4));
+    assert_eq!(keypad_key("v"), Some(0xF));
+    assert_eq!(keypad_key("P"), None);
+}